@@ -0,0 +1,118 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{ImageBuffer, Rgba};
+use rust_filter::blur::{self, ImageData};
+use rust_filter::cli::FilterOptions;
+use rust_filter::integral::IntegralImage;
+use rust_filter::kuwahara;
+use rust_filter::timing::WorkerClock;
+use std::sync::{Arc, Mutex};
+
+const SIZES: [u32; 3] = [256, 512, 1024];
+const RADII: [usize; 3] = [3, 10, 30];
+const THREADS: usize = 4;
+
+// Deterministic gradient with some high-frequency detail so no branch is trivially predictable
+fn test_image(size: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(size, size, |x, y| {
+        let noise = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663)) as u8;
+        Rgba([(x * 255 / size) as u8, (y * 255 / size) as u8, noise, 255])
+    })
+}
+
+fn bench_kernel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gaussian_kernel");
+    for radius in RADII {
+        group.bench_with_input(BenchmarkId::from_parameter(radius), &radius, |b, &radius| {
+            b.iter(|| blur::generate_gaussian_kernel(black_box(radius)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_transpose(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transpose");
+    for size in SIZES {
+        let src = ImageData::from_image_buffer(&test_image(size));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &src, |b, src| {
+            b.iter(|| src.transpose())
+        });
+    }
+    group.finish();
+}
+
+fn bench_horizontal_blur(c: &mut Criterion) {
+    let mut group = c.benchmark_group("horizontal_blur");
+    for size in SIZES {
+        let src = ImageData::from_image_buffer(&test_image(size));
+        for radius in RADII {
+            let kernel = blur::generate_gaussian_kernel(radius);
+            let dst = Arc::new(Mutex::new(ImageData {
+                data: vec![0; src.data.len()],
+                width: src.width,
+                height: src.height,
+                channels: src.channels,
+            }));
+            group.bench_with_input(BenchmarkId::new(format!("{}px", size), radius), &radius, |b, &radius| {
+                b.iter(|| {
+                    let mut clock = WorkerClock::start("bench", 0, 0..src.height);
+                    blur::horizontal_gaussian_blur(&src, Arc::clone(&dst), &kernel, radius, FilterOptions::default(), 0..src.height, &mut clock)
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_sat_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sat_build");
+    for size in SIZES {
+        let values = kuwahara::convert_to_space(&test_image(size), FilterOptions::default(), THREADS);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &values, |b, values| {
+            b.iter(|| {
+                let mut integral = IntegralImage::new(size as usize, size as usize);
+                integral.build(values, None);
+                integral
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_full_blur(c: &mut Criterion) {
+    let mut group = c.benchmark_group("blur");
+    group.sample_size(10);
+    for size in SIZES {
+        let img = test_image(size);
+        for radius in RADII {
+            group.bench_with_input(BenchmarkId::new(format!("{}px", size), radius), &radius, |b, &radius| {
+                b.iter(|| blur::apply_gaussian_blur(&img, radius as i32, THREADS, FilterOptions::default()))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_full_kuwahara(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kuwahara");
+    group.sample_size(10);
+    for size in SIZES {
+        let img = test_image(size);
+        for radius in RADII {
+            group.bench_with_input(BenchmarkId::new(format!("{}px", size), radius), &radius, |b, &radius| {
+                b.iter(|| kuwahara::apply_kuwahara_filter(&img, radius as i32, THREADS, FilterOptions::default()))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_kernel,
+    bench_transpose,
+    bench_horizontal_blur,
+    bench_sat_build,
+    bench_full_blur,
+    bench_full_kuwahara
+);
+criterion_main!(benches);
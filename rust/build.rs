@@ -0,0 +1,18 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    // The header is checked in so C users do not need a Rust toolchain to read it
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        cbindgen::generate(&crate_dir)
+            .expect("Failed to generate the C header")
+            .write_to_file(format!("{}/include/concurrency.h", crate_dir));
+    }
+
+    // Lets the addon resolve Node's symbols when it is loaded, on platforms that need it
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
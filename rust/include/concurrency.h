@@ -0,0 +1,76 @@
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#ifndef CONCURRENCY_H
+#define CONCURRENCY_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of every `concurrency_*` call.
+ */
+typedef enum ConcurrencyStatus {
+  CONCURRENCY_STATUS_OK = 0,
+  /**
+   * `data` was null.
+   */
+  CONCURRENCY_STATUS_NULL_POINTER = 1,
+  /**
+   * A size or `threads` was zero, `radius` was negative, or `stride` was shorter than a row.
+   */
+  CONCURRENCY_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * The filter panicked; the image is left unchanged.
+   */
+  CONCURRENCY_STATUS_PANIC = 3,
+  /**
+   * The image needs more memory than this platform can address.
+   */
+  CONCURRENCY_STATUS_TOO_LARGE = 4,
+} ConcurrencyStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Gaussian blur of an RGBA8 image in place, using `threads` worker threads.
+ *
+ * # Safety
+ *
+ * `data` must point to `height` rows of `width` RGBA pixels, each row starting `stride` bytes
+ * after the previous one, and stay valid and unaliased for the duration of the call.
+ */
+enum ConcurrencyStatus concurrency_blur(uint8_t *data,
+                                        uint32_t width,
+                                        uint32_t height,
+                                        uint32_t stride,
+                                        int32_t radius,
+                                        uint32_t threads);
+
+/**
+ * Kuwahara filter of an RGBA8 image in place, using `threads` worker threads.
+ *
+ * # Safety
+ *
+ * Same requirements on `data` as `concurrency_blur`.
+ */
+enum ConcurrencyStatus concurrency_kuwahara(uint8_t *data,
+                                            uint32_t width,
+                                            uint32_t height,
+                                            uint32_t stride,
+                                            int32_t radius,
+                                            uint32_t threads);
+
+/**
+ * Static, NUL-terminated description of a status code returned by this library.
+ */
+const char *concurrency_status_message(int32_t status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CONCURRENCY_H */
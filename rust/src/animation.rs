@@ -0,0 +1,334 @@
+use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposal {
+    None,
+    Background,
+    Previous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    Source,
+    Over,
+}
+
+// A whole frame as a viewer shows it, the size of the canvas: GIF and APNG frames often cover only
+// the region that changed, and a filter run on that patch alone would see none of its surroundings
+pub struct AnimationFrame {
+    pub image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    // Frame delay as a fraction of a second (numerator, denominator)
+    pub delay: (u16, u16),
+}
+
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    // Number of times the animation plays, 0 means forever
+    pub plays: u32,
+    pub frames: Vec<AnimationFrame>,
+}
+
+// Counting semaphore bounding how many frames are filtered at the same time
+struct Semaphore {
+    permits: Mutex<usize>,
+    available: Condvar,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Semaphore {
+            permits: Mutex::new(permits),
+            available: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.available.wait(permits).unwrap();
+        }
+        *permits -= 1;
+    }
+
+    fn release(&self) {
+        *self.permits.lock().unwrap() += 1;
+        self.available.notify_one();
+    }
+}
+
+// The canvas the frames of a file are drawn onto in turn
+struct Canvas {
+    image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+}
+
+impl Canvas {
+    // Fully transparent, as both formats start
+    fn new(width: u32, height: u32) -> Self {
+        Canvas { image: ImageBuffer::new(width, height) }
+    }
+
+    // Draws a frame of the file at its offset and returns the canvas as shown, then disposes of the
+    // frame's region as the next frame expects. Parts outside the canvas are dropped.
+    fn compose(&mut self, patch: &ImageBuffer<Rgba<u8>, Vec<u8>>, left: u32, top: u32, dispose: Disposal, blend: Blend) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let previous = (dispose == Disposal::Previous).then(|| self.image.clone());
+        let (width, height) = self.image.dimensions();
+        let right = left.saturating_add(patch.width()).min(width);
+        let bottom = top.saturating_add(patch.height()).min(height);
+        for y in top.min(bottom)..bottom {
+            for x in left.min(right)..right {
+                let src = *patch.get_pixel(x - left, y - top);
+                let dst = self.image.get_pixel_mut(x, y);
+                *dst = match blend {
+                    Blend::Source => src,
+                    Blend::Over => over(src, *dst),
+                };
+            }
+        }
+        let shown = self.image.clone();
+        match (dispose, previous) {
+            (Disposal::Previous, Some(previous)) => self.image = previous,
+            (Disposal::Background, _) => {
+                for y in top.min(bottom)..bottom {
+                    for x in left.min(right)..right {
+                        self.image.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    }
+                }
+            }
+            _ => {}
+        }
+        shown
+    }
+}
+
+// `src` composited over `dst` by its alpha, neither premultiplied
+fn over(src: Rgba<u8>, dst: Rgba<u8>) -> Rgba<u8> {
+    match (src[3], dst[3]) {
+        (255, _) | (_, 0) => src,
+        (0, _) => dst,
+        (src_alpha, dst_alpha) => {
+            let src_alpha = src_alpha as f32 / 255.0;
+            let dst_alpha = dst_alpha as f32 / 255.0 * (1.0 - src_alpha);
+            let alpha = src_alpha + dst_alpha;
+            let channel = |ch: usize| ((src[ch] as f32 * src_alpha + dst[ch] as f32 * dst_alpha) / alpha).round() as u8;
+            Rgba([channel(0), channel(1), channel(2), (alpha * 255.0).round() as u8])
+        }
+    }
+}
+
+fn extension(path: &str) -> String {
+    path.rsplit('.').next().unwrap_or("").to_ascii_lowercase()
+}
+
+fn is_apng(path: &str) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map(|reader| reader.info().animation_control.is_some())
+        .unwrap_or(false)
+}
+
+pub fn is_animation(path: &str) -> bool {
+    match extension(path).as_str() {
+        "gif" => true,
+        "png" | "apng" => is_apng(path),
+        _ => false,
+    }
+}
+
+pub fn decode_animation(path: &str) -> Result<Animation, Box<dyn Error>> {
+    match extension(path).as_str() {
+        "gif" => decode_gif(path),
+        _ => decode_apng(path),
+    }
+}
+
+pub fn encode_animation(path: &str, animation: &Animation) -> Result<(), Box<dyn Error>> {
+    match extension(path).as_str() {
+        "gif" => encode_gif(path, animation),
+        "png" | "apng" => encode_apng(path, animation),
+        other => Err(format!("Animated output must be .gif or .png, got '.{}'", other).into()),
+    }
+}
+
+fn decode_gif(path: &str) -> Result<Animation, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(BufReader::new(file))?;
+
+    let width = decoder.width() as u32;
+    let height = decoder.height() as u32;
+    let mut canvas = Canvas::new(width, height);
+    let mut frames = Vec::new();
+
+    while let Some(frame) = decoder.read_next_frame()? {
+        let patch = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(
+            frame.width as u32,
+            frame.height as u32,
+            frame.buffer.to_vec(),
+        ).ok_or("Frame buffer does not match frame dimensions")?;
+
+        let dispose = match frame.dispose {
+            gif::DisposalMethod::Background => Disposal::Background,
+            gif::DisposalMethod::Previous => Disposal::Previous,
+            _ => Disposal::None,
+        };
+
+        // Transparent GIF pixels let the canvas show through
+        frames.push(AnimationFrame {
+            image: canvas.compose(&patch, frame.left as u32, frame.top as u32, dispose, Blend::Over),
+            delay: (frame.delay, 100),
+        });
+    }
+
+    let plays = match decoder.repeat() {
+        gif::Repeat::Infinite => 0,
+        gif::Repeat::Finite(count) => count as u32,
+    };
+
+    Ok(Animation {
+        width,
+        height,
+        plays,
+        frames,
+    })
+}
+
+fn encode_gif(path: &str, animation: &Animation) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path)?;
+    let mut encoder = gif::Encoder::new(
+        BufWriter::new(file),
+        animation.width as u16,
+        animation.height as u16,
+        &[],
+    )?;
+    let repeat = match animation.plays {
+        0 => gif::Repeat::Infinite,
+        count => gif::Repeat::Finite(count.min(u16::MAX as u32) as u16),
+    };
+    encoder.set_repeat(repeat)?;
+
+    for src in &animation.frames {
+        let (width, height) = src.image.dimensions();
+        let mut pixels = src.image.as_raw().clone();
+        let mut frame = gif::Frame::from_rgba_speed(width as u16, height as u16, &mut pixels, 10);
+        let (num, den) = src.delay;
+        frame.delay = (num as u32 * 100 / den.max(1) as u32) as u16;
+        // Every frame is whole, and clearing it lets the next one's transparent pixels stay transparent
+        frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&frame)?;
+    }
+
+    Ok(())
+}
+
+pub fn to_rgba(buffer: &[u8], color_type: png::ColorType) -> Vec<u8> {
+    match color_type {
+        png::ColorType::Rgba => buffer.to_vec(),
+        png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => unreachable!("palette images are expanded while decoding"),
+    }
+}
+
+fn decode_apng(path: &str) -> Result<Animation, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    let info = reader.info();
+    let width = info.width;
+    let height = info.height;
+    let control = info.animation_control.ok_or("PNG file is not animated")?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let mut canvas = Canvas::new(width, height);
+    let mut frames = Vec::new();
+
+    while frames.len() < control.num_frames as usize {
+        let output = reader.next_frame(&mut buffer)?;
+
+        // A default image without fcTL is not part of the animation
+        let Some(fctl) = reader.info().frame_control else {
+            continue;
+        };
+
+        let data = to_rgba(&buffer[..output.buffer_size()], output.color_type);
+        let patch = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(output.width, output.height, data)
+            .ok_or("Frame buffer does not match frame dimensions")?;
+
+        let dispose = match fctl.dispose_op {
+            png::DisposeOp::None => Disposal::None,
+            png::DisposeOp::Background => Disposal::Background,
+            png::DisposeOp::Previous => Disposal::Previous,
+        };
+        let blend = match fctl.blend_op {
+            png::BlendOp::Source => Blend::Source,
+            png::BlendOp::Over => Blend::Over,
+        };
+
+        frames.push(AnimationFrame {
+            image: canvas.compose(&patch, fctl.x_offset, fctl.y_offset, dispose, blend),
+            delay: (fctl.delay_num, fctl.delay_den),
+        });
+    }
+
+    Ok(Animation {
+        width,
+        height,
+        plays: control.num_plays,
+        frames,
+    })
+}
+
+fn encode_apng(path: &str, animation: &Animation) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), animation.width, animation.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(animation.frames.len() as u32, animation.plays)?;
+    let mut writer = encoder.write_header()?;
+
+    for frame in &animation.frames {
+        // Whole frames replace the canvas, transparent pixels included
+        let (width, height) = frame.image.dimensions();
+        writer.set_frame_dimension(width, height)?;
+        writer.set_frame_delay(frame.delay.0, frame.delay.1)?;
+        writer.set_dispose_op(png::DisposeOp::None)?;
+        writer.set_blend_op(png::BlendOp::Source)?;
+        writer.write_image_data(frame.image.as_raw())?;
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn filter_frames<F>(animation: &mut Animation, max_frames_in_flight: usize, filter: F)
+where
+    F: Fn(&ImageBuffer<Rgba<u8>, Vec<u8>>) -> ImageBuffer<Rgba<u8>, Vec<u8>> + Sync,
+{
+    let semaphore = Semaphore::new(max_frames_in_flight.max(1));
+
+    thread::scope(|scope| {
+        for frame in animation.frames.iter_mut() {
+            semaphore.acquire();
+            let semaphore = &semaphore;
+            let filter = &filter;
+
+            scope.spawn(move || {
+                frame.image = filter(&frame.image);
+                semaphore.release();
+            });
+        }
+    });
+}
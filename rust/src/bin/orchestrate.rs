@@ -0,0 +1,240 @@
+// Runs every built implementation in the repository on the same input and worker counts, reads the
+// times each one prints, and renders them as one table with the same layout `report` uses.
+// Run it from the repository root, or point --root at it.
+
+use rust_filter::bench::Measurement;
+use rust_filter::report::{self, ReportFormat, Row};
+use rust_filter::synthetic;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+// Display name as report::implementation_name spells it, then how to run it from the root
+const IMPLEMENTATIONS: [(&str, &[&str]); 7] = [
+    ("C", &["c/filter_c"]),
+    ("Go", &["go/filter_go"]),
+    ("Rust threads", &["rust/target/release/rust_filter"]),
+    ("Rust async", &["rust_async/target/release/rust_filter_async"]),
+    ("Odin", &["odin/filter_odin"]),
+    ("Zig", &["zig/zig-out/bin/filter_zig"]),
+    ("Python", &["python3", "python/main.py"]),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Metric {
+    // The filter alone, as every implementation times it around its workers
+    Filter,
+    // Load, filter and save
+    Total,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "filter" => Ok(Metric::Filter),
+            "total" => Ok(Metric::Total),
+            other => Err(format!("Unknown metric: {}. Use 'filter' or 'total'", other)),
+        }
+    }
+}
+
+struct Options {
+    workers: Vec<usize>,
+    runs: usize,
+    warmup: usize,
+    only: Option<Vec<String>>,
+    metric: Metric,
+    format: ReportFormat,
+    root: PathBuf,
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} <operation> <input_image> <radius> [options]", program);
+    eprintln!("  input_image may be synthetic:noise:WxH (or gradient, checkerboard, perlin), generated once and shared");
+    eprintln!("  --workers 1,4,16        worker counts to run each implementation with (default 1,4,16)");
+    eprintln!("  --runs N                timed runs per worker count (default 5)");
+    eprintln!("  --warmup N              untimed runs before them (default 1)");
+    eprintln!("  --only c,go,...         implementations to run, by name (default: every one that is built)");
+    eprintln!("  --metric M              'filter' (default) or 'total' time");
+    eprintln!("  --report-format F       'markdown' (default) or 'html'");
+    eprintln!("  --root DIR              repository root (default: the current directory)");
+    std::process::exit(1);
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| {
+        eprintln!("Missing value for {}", flag);
+        std::process::exit(1);
+    });
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value for {}: {}", flag, value);
+        std::process::exit(1);
+    })
+}
+
+fn parse_args(args: Vec<String>) -> (Vec<String>, Options) {
+    let mut options = Options {
+        workers: vec![1, 4, 16],
+        runs: 5,
+        warmup: 1,
+        only: None,
+        metric: Metric::Filter,
+        format: ReportFormat::Markdown,
+        root: PathBuf::from("."),
+    };
+    let mut positional = Vec::new();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--workers" => {
+                let list: String = parse_value(&arg, iter.next());
+                options.workers = list.split(',').map(|w| parse_value(&arg, Some(w.trim().to_string()))).collect();
+            }
+            "--runs" => options.runs = parse_value(&arg, iter.next()),
+            "--warmup" => options.warmup = parse_value(&arg, iter.next()),
+            "--only" => {
+                let list: String = parse_value(&arg, iter.next());
+                options.only = Some(list.split(',').map(|name| name.trim().to_lowercase()).collect());
+            }
+            "--metric" => options.metric = parse_value(&arg, iter.next()),
+            "--report-format" => options.format = parse_value(&arg, iter.next()),
+            "--root" => options.root = parse_value(&arg, iter.next()),
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option: {}", arg);
+                std::process::exit(1);
+            }
+            _ => positional.push(arg),
+        }
+    }
+    (positional, options)
+}
+
+// Matches `--only` names against the display name or the directory, so "rust_async" and "rust async" both work
+fn selected(only: &Option<Vec<String>>, name: &str, command: &[&str]) -> bool {
+    let Some(only) = only else {
+        return true;
+    };
+    let program = command.last().expect("Commands are not empty");
+    let directory = program.split('/').next().unwrap_or(program);
+    only.iter().any(|wanted| *wanted == name.to_lowercase() || wanted == directory)
+}
+
+// The times each implementation prints, in milliseconds. Spellings differ: "Filter time: 12ms",
+// "Kuwahara filter time: 12ms" (Odin) and "Blur processing took 12.34ms" (Python)
+fn parse_time(output: &str, metric: Metric) -> Option<f64> {
+    output.lines().find_map(|line| {
+        let lower = line.to_lowercase();
+        let wanted = match metric {
+            Metric::Filter => lower.contains("filter time") || lower.contains("processing took"),
+            Metric::Total => lower.contains("total time"),
+        };
+        if !wanted {
+            return None;
+        }
+        let number = lower.trim_end().strip_suffix("ms")?;
+        let start = number.rfind(|c: char| !(c.is_ascii_digit() || c == '.')).map_or(0, |i| i + 1);
+        number[start..].parse().ok()
+    })
+}
+
+fn run_once(root: &Path, command: &[&str], args: &[String], metric: Metric) -> Result<f64, String> {
+    // Paths in the table are relative to the root, interpreters like python3 come from PATH
+    let resolve = |part: &&str| if part.contains('/') { root.join(part).into_os_string() } else { part.into() };
+    let output = Command::new(resolve(&command[0]))
+        .args(command[1..].iter().map(resolve))
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("failed to start: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    parse_time(&stdout, metric).ok_or_else(|| "printed no timing line".to_string())
+}
+
+fn main() {
+    let (args, options) = parse_args(env::args().collect());
+    if args.len() < 4 {
+        usage(&args[0]);
+    }
+    let operation = &args[1];
+    let radius = &args[3];
+    if !matches!(operation.as_str(), "blur" | "kuwahara") || radius.parse::<i32>().is_err() || options.runs == 0 {
+        usage(&args[0]);
+    }
+
+    // Every implementation gets the same file, and a synthetic one is written once so none regenerates it
+    let scratch = env::temp_dir().join(format!("orchestrate-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch).expect("Failed to create scratch directory");
+    let input = if synthetic::is_synthetic(&args[2]) {
+        let path = scratch.join("input.png");
+        let img = synthetic::load(&args[2], 4).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        img.save(&path).expect("Failed to write synthetic input");
+        path
+    } else {
+        std::path::absolute(&args[2]).expect("Invalid input path")
+    };
+
+    let mut rows = Vec::new();
+    for (name, command) in IMPLEMENTATIONS {
+        if !selected(&options.only, name, command) {
+            continue;
+        }
+        let program = command.last().expect("Commands are not empty");
+        if !options.root.join(program).exists() {
+            eprintln!("Skipping {}: {} is not built", name, program);
+            continue;
+        }
+
+        let output = scratch.join(format!("{}.png", name.replace(' ', "_")));
+        for &workers in &options.workers {
+            let run_args = [
+                operation.clone(),
+                input.display().to_string(),
+                output.display().to_string(),
+                radius.clone(),
+                workers.to_string(),
+            ];
+            let mut samples = Vec::new();
+            for run in 0..options.warmup + options.runs {
+                match run_once(&options.root, command, &run_args, options.metric) {
+                    Ok(ms) if run >= options.warmup => samples.push(ms),
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("{} with {} workers {}", name, workers, e);
+                        break;
+                    }
+                }
+            }
+            if samples.is_empty() {
+                continue;
+            }
+            let measurement = Measurement::new(workers, samples);
+            eprintln!("{} with {} workers:\n{}", name, workers, measurement.format());
+            rows.push(Row {
+                implementation: name.to_string(),
+                operation: Some(operation.clone()),
+                workers: Some(workers),
+                median_ms: measurement.median(),
+            });
+        }
+    }
+    let _ = std::fs::remove_dir_all(&scratch);
+
+    if rows.is_empty() {
+        eprintln!("No implementation produced a result");
+        std::process::exit(1);
+    }
+    match options.format {
+        ReportFormat::Markdown => println!("{}", report::render_markdown(&rows)),
+        ReportFormat::Html => println!("{}", report::render_html(&rows)),
+    }
+}
@@ -0,0 +1,170 @@
+use crate::bands;
+use crate::image_pyramid::{expand_at, gaussian_pyramid};
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::str::FromStr;
+use std::thread;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// The coarsest level is at most this many pixels on its shorter side
+const TOP_SIZE: u32 = 16;
+
+// Levels of the pyramids, from the full size down to the first with a short side of TOP_SIZE or less
+pub fn levels(width: u32, height: u32) -> usize {
+    let mut side = width.min(height);
+    let mut levels = 1;
+    while side > TOP_SIZE {
+        side = side.div_ceil(2);
+        levels += 1;
+    }
+    levels
+}
+
+// One level of the blended Laplacian pyramid, RGBA as f32: the band-pass detail of `a` and `b`
+// mixed by the mask blurred to that level's scale, or at the top the residual images themselves
+fn blend_level(a: &[Frame], b: &[Frame], mask: &[Frame], level: usize) -> Vec<f32> {
+    let (width, height) = a[level].dimensions();
+    let coarser = a.get(level + 1).map(|next| next.dimensions());
+    let mut out = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let weight = mask[level].get_pixel(x, y)[0] as f32 / 255.0;
+            for ch in 0..4 {
+                let detail = |pyramid: &[Frame]| {
+                    let value = pyramid[level].get_pixel(x, y)[ch] as f32;
+                    match coarser {
+                        Some((w, h)) => value - expand_at(|sx, sy| pyramid[level + 1].get_pixel(sx, sy)[ch] as f32, w, h, x, y),
+                        None => value,
+                    }
+                };
+                out.push(weight * detail(a) + (1.0 - weight) * detail(b));
+            }
+        }
+    }
+    out
+}
+
+// Multi-band blending: each frequency band is mixed across a transition as wide as its scale, so
+// the seam between `a`, where `mask` is white, and `b`, where it is black, neither shows a hard
+// edge nor ghosts fine detail. The three Gaussian pyramids are built concurrently, each level is
+// blended on its own thread, and the collapse runs band by band.
+pub fn blend(a: &Frame, b: &Frame, mask: &Frame, num_threads: usize) -> Result<Frame, String> {
+    let (width, height) = a.dimensions();
+    for (name, img) in [("Second image", b), ("Mask", mask)] {
+        if img.dimensions() != (width, height) {
+            return Err(format!("{} is {}x{}, expected {}x{} like the first image", name, img.width(), img.height(), width, height));
+        }
+    }
+    let levels = levels(width, height);
+    let per_pyramid = (num_threads / 3).max(1);
+    let (a, b, mask) = thread::scope(|scope| {
+        let a = scope.spawn(|| gaussian_pyramid(a, levels, per_pyramid));
+        let b = scope.spawn(|| gaussian_pyramid(b, levels, per_pyramid));
+        let mask = scope.spawn(|| gaussian_pyramid(mask, levels, per_pyramid));
+        [a, b, mask].map(|handle| handle.join().expect("Pyramid thread panicked")).into()
+    });
+    let blended: Vec<Vec<f32>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..levels).map(|level| scope.spawn({
+            let (a, b, mask) = (&a, &b, &mask);
+            move || blend_level(a, b, mask, level)
+        })).collect();
+        handles.into_iter().map(|handle| handle.join().expect("Blending thread panicked")).collect()
+    });
+
+    // Collapse from the top: expand the running image and add the next finer detail
+    let mut collapsed = blended[levels - 1].clone();
+    for level in (0..levels - 1).rev() {
+        let (coarse_width, coarse_height) = a[level + 1].dimensions();
+        let (level_width, _) = a[level].dimensions();
+        let mut finer = blended[level].clone();
+        let row_len = level_width as usize * 4;
+        let coarse = &collapsed;
+        workers::scope_each(bands::split_mut(&mut finer, row_len, num_threads), |(rows, band)| {
+            for (i, value) in band.iter_mut().enumerate() {
+                let (x, y, ch) = ((i / 4) % level_width as usize, rows.start + i / row_len, i % 4);
+                let sample = |sx: u32, sy: u32| coarse[(sy as usize * coarse_width as usize + sx as usize) * 4 + ch];
+                *value += expand_at(sample, coarse_width, coarse_height, x as u32, y as u32);
+            }
+        });
+        collapsed = finer;
+    }
+
+    let data = collapsed.iter().map(|value| value.round().clamp(0.0, 255.0) as u8).collect();
+    Ok(ImageBuffer::from_raw(width, height, data).expect("Blended buffer matches dimensions"))
+}
+
+// How `composite` mixes the top image's color with the base's, on values in 0..=1
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Mode::Normal),
+            "multiply" => Ok(Mode::Multiply),
+            "screen" => Ok(Mode::Screen),
+            "overlay" => Ok(Mode::Overlay),
+            other => Err(format!("Unknown blend mode: {}. Use 'normal', 'multiply', 'screen' or 'overlay'", other)),
+        }
+    }
+}
+
+impl Mode {
+    pub fn mix(self, base: f64, top: f64) -> f64 {
+        match self {
+            Mode::Normal => top,
+            Mode::Multiply => base * top,
+            Mode::Screen => 1.0 - (1.0 - base) * (1.0 - top),
+            // Multiply in the shadows of the base and screen in its highlights
+            Mode::Overlay if base < 0.5 => 2.0 * base * top,
+            Mode::Overlay => 1.0 - 2.0 * (1.0 - base) * (1.0 - top),
+        }
+    }
+}
+
+// `top` over `base`, the top's alpha scaled by `opacity`. Where the base is opaque the top's color
+// is replaced by the mode's mix of the two, where it is transparent the top shows as it is, and
+// the result is composited source-over.
+pub fn composite_pixel(base: [u8; 4], top: [u8; 4], mode: Mode, opacity: f64) -> [u8; 4] {
+    let base_alpha = base[3] as f64 / 255.0;
+    let top_alpha = top[3] as f64 / 255.0 * opacity;
+    let alpha = top_alpha + base_alpha * (1.0 - top_alpha);
+    let mut out = [0, 0, 0, (alpha * 255.0).round() as u8];
+    if alpha == 0.0 {
+        return out;
+    }
+    for ch in 0..3 {
+        let (b, t) = (base[ch] as f64 / 255.0, top[ch] as f64 / 255.0);
+        let mixed = (1.0 - base_alpha) * t + base_alpha * mode.mix(b, t);
+        let color = (top_alpha * mixed + base_alpha * (1.0 - top_alpha) * b) / alpha;
+        out[ch] = (color * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    out
+}
+
+// Composites `top` over `base` of the same size, one band of rows per thread
+pub fn composite(base: &Frame, top: &Frame, mode: Mode, opacity: f64, num_threads: usize) -> Result<Frame, String> {
+    let (width, height) = base.dimensions();
+    if top.dimensions() != (width, height) {
+        return Err(format!("Second image is {}x{}, expected {}x{} like the first image", top.width(), top.height(), width, height));
+    }
+    let mut result = base.clone();
+    let row_len = width as usize * 4;
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        let top = top.as_raw()[rows.start * row_len..rows.end * row_len].chunks_exact(4);
+        for (pixel, over) in band.chunks_exact_mut(4).zip(top) {
+            let mixed = composite_pixel([pixel[0], pixel[1], pixel[2], pixel[3]], [over[0], over[1], over[2], over[3]], mode, opacity);
+            pixel.copy_from_slice(&mixed);
+        }
+    });
+    Ok(result)
+}
@@ -1,17 +1,29 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::fft::{self, Complex};
+use crate::progress;
+use crate::srgb;
+use crate::timing::WorkerClock;
+use crate::workers;
 use image::{ImageBuffer, Rgba};
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
-use std::thread;
+
+// Radius from which the passes convolve through the FFT. The direct pass costs 2r+1 taps a pixel
+// and the FFT a few transforms of the padded row whatever the radius, which wins from about here.
+pub const FFT_RADIUS: usize = 32;
 
 #[derive(Debug)]
-struct ImageData {
-    data: Vec<u8>,
-    width: usize,
-    height: usize,
-    channels: usize,
+pub struct ImageData {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
 }
 
 impl ImageData {
-    fn from_image_buffer(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Self {
+    pub fn from_image_buffer(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Self {
         let (width, height) = img.dimensions();
         ImageData {
             data: img.as_raw().clone(),
@@ -21,7 +33,7 @@ impl ImageData {
         }
     }
 
-    fn to_image_buffer(&self) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    pub fn to_image_buffer(&self) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
         ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(
             self.width as u32,
             self.height as u32,
@@ -29,7 +41,7 @@ impl ImageData {
         ).expect("Failed to create image buffer")
     }
 
-    fn transpose(&self) -> ImageData {
+    pub fn transpose(&self) -> ImageData {
         let mut dst = ImageData {
             data: vec![0; self.data.len()],
             width: self.height,
@@ -51,29 +63,73 @@ impl ImageData {
     }
 }
 
-fn generate_gaussian_kernel(radius: usize) -> Vec<f64> {
+// The kernel of `radius` with sigma a third of it, so it reaches three sigmas
+pub fn generate_gaussian_kernel(radius: usize) -> Vec<f64> {
+    gaussian_kernel(radius, radius as f64 / 3.0)
+}
+
+// Weights of a Gaussian of `sigma` over 2 * radius + 1 taps, normalized to sum to 1
+pub fn gaussian_kernel(radius: usize, sigma: f64) -> Vec<f64> {
+    // sigma would be 0 and every weight NaN; a single tap copies the pixel
+    if radius == 0 {
+        return vec![1.0];
+    }
     let size = 2 * radius + 1;
     let mut kernel = vec![0.0; size];
-    let sigma = radius as f64 / 3.0;
+    // The same for an axis left unblurred next to a blurred one: all the weight on the center tap
+    if sigma == 0.0 {
+        kernel[radius] = 1.0;
+        return kernel;
+    }
     let mut sum = 0.0;
 
-    for i in 0..size {
+    for (i, weight) in kernel.iter_mut().enumerate() {
         let x = i as f64 - radius as f64;
-        kernel[i] = (-x * x / (2.0 * sigma * sigma)).exp();
-        sum += kernel[i];
+        *weight = (-x * x / (2.0 * sigma * sigma)).exp();
+        sum += *weight;
     }
 
-    for i in 0..size {
-        kernel[i] /= sum;
+    for weight in kernel.iter_mut() {
+        *weight /= sum;
     }
 
     kernel
 }
 
-fn horizontal_gaussian_blur(src: &ImageData, dst: Arc<Mutex<ImageData>>, kernel: &[f64], radius: usize, start_y: usize, end_y: usize) {
-    let mut local_rows = Vec::new();
+// Radius of the blur: the given one, or when it is 0 and a sigma is given, three of the larger
+// sigma rounded up, where the Gaussian has fallen below 1.2% of its peak
+pub fn blur_radius(radius: usize, filter: FilterOptions) -> usize {
+    if radius > 0 {
+        return radius;
+    }
+    let sigma = filter.sigma_x.unwrap_or(0.0).max(filter.sigma_y.unwrap_or(0.0));
+    (3.0 * sigma).ceil() as usize
+}
 
-    for y in start_y..end_y {
+// A radius wider than the image is valid, but its kernel would be any size: it is capped at the
+// image's longest side, as Kuwahara's radius is
+pub fn capped_radius(radius: usize, width: u32, height: u32) -> usize {
+    radius.min(width.max(height) as usize)
+}
+
+// Sigmas of the horizontal and the vertical pass: `--sigma-x` and `--sigma-y` when given, otherwise
+// a third of `radius`, the one given rather than the one derived from the other axis's sigma. With
+// a radius of 0 an axis without a sigma is not blurred at all.
+pub fn sigmas(radius: usize, filter: FilterOptions) -> (f64, f64) {
+    let default = radius as f64 / 3.0;
+    (filter.sigma_x.unwrap_or(default), filter.sigma_y.unwrap_or(default))
+}
+
+pub fn horizontal_gaussian_blur(src: &ImageData, dst: Arc<Mutex<ImageData>>, kernel: &[f64], radius: usize, filter: FilterOptions, rows: Range<usize>, clock: &mut WorkerClock) {
+    let mut local_rows = Vec::new();
+    // In linear mode color channels are decoded on read and re-encoded on write; alpha is already linear
+    let (linear, edge) = (filter.linear, filter.edge);
+    let lut = srgb::lut();
+    let decode = |value: u8| if linear { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64| if linear { lut.encode(value) } else { value.round() as u8 };
+    let outside = edge.outside();
+
+    for y in rows {
         let mut row_data = vec![0u8; src.width * src.channels];
 
         for x in 0..src.width {
@@ -83,25 +139,32 @@ fn horizontal_gaussian_blur(src: &ImageData, dst: Arc<Mutex<ImageData>>, kernel:
             let mut a_sum = 0.0;
 
             for k in -(radius as i32)..=(radius as i32) {
-                let sx = (x as i32 + k).clamp(0, src.width as i32 - 1) as usize;
-                let idx = (y * src.width + sx) * src.channels;
+                let sample = match edge.index(x as i32 + k, src.width) {
+                    Some(sx) => {
+                        let idx = (y * src.width + sx) * src.channels;
+                        &src.data[idx..idx + 4]
+                    }
+                    None => &outside[..],
+                };
                 let weight = kernel[(k + radius as i32) as usize];
 
-                r_sum += src.data[idx] as f64 * weight;
-                g_sum += src.data[idx + 1] as f64 * weight;
-                b_sum += src.data[idx + 2] as f64 * weight;
-                a_sum += src.data[idx + 3] as f64 * weight;
+                r_sum += decode(sample[0]) * weight;
+                g_sum += decode(sample[1]) * weight;
+                b_sum += decode(sample[2]) * weight;
+                a_sum += sample[3] as f64 * weight;
             }
 
             let dst_idx = x * src.channels;
-            row_data[dst_idx] = r_sum.round() as u8;
-            row_data[dst_idx + 1] = g_sum.round() as u8;
-            row_data[dst_idx + 2] = b_sum.round() as u8;
+            row_data[dst_idx] = encode(r_sum);
+            row_data[dst_idx + 1] = encode(g_sum);
+            row_data[dst_idx + 2] = encode(b_sum);
             row_data[dst_idx + 3] = a_sum.round() as u8;
         }
 
         local_rows.push((y, row_data));
+        progress::advance(1);
     }
+    clock.computed();
 
     let mut dst = dst.lock().unwrap();
     for (y, row_data) in local_rows {
@@ -110,12 +173,89 @@ fn horizontal_gaussian_blur(src: &ImageData, dst: Arc<Mutex<ImageData>>, kernel:
     }
 }
 
-pub fn apply_gaussian_blur(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, num_threads: usize) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+// Transform of the kernel laid out circularly over `size` samples, centered on the first
+pub fn kernel_spectrum(kernel: &[f64], size: usize) -> Vec<Complex> {
+    let radius = kernel.len() / 2;
+    let mut spectrum = vec![Complex::default(); size];
+    for (i, &weight) in kernel.iter().enumerate() {
+        spectrum[(i + size - radius) % size] = Complex::new(weight, 0.0);
+    }
+    fft::fft(&mut spectrum, false);
+    spectrum
+}
+
+// The same pass as `horizontal_gaussian_blur` through the FFT. Each row is padded with `radius`
+// samples on both sides, read by the edge mode as the direct pass does, and multiplied by the
+// kernel's spectrum. The channels go two to a transform as its real and imaginary parts; the kernel
+// is real, so they come back apart.
+pub fn horizontal_fft_blur(src: &ImageData, dst: Arc<Mutex<ImageData>>, kernel: &[f64], radius: usize, filter: FilterOptions, rows: Range<usize>, clock: &mut WorkerClock) {
+    let mut local_rows = Vec::new();
+    let (linear, edge) = (filter.linear, filter.edge);
+    let lut = srgb::lut();
+    let decode = |value: u8, channel: usize| if linear && channel < 3 { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64| if linear { lut.encode(value) } else { value.round() as u8 };
+    let padded = src.width + 2 * radius;
+    let size = padded.next_power_of_two();
+    let spectrum = kernel_spectrum(kernel, size);
+    let mut pairs = [vec![Complex::default(); size], vec![Complex::default(); size]];
+    let outside = edge.outside();
+
+    for y in rows {
+        for (pair, first) in pairs.iter_mut().zip([0, 2]) {
+            pair.fill(Complex::default());
+            for (j, value) in pair[..padded].iter_mut().enumerate() {
+                let sample = match edge.index(j as i32 - radius as i32, src.width) {
+                    Some(sx) => {
+                        let idx = (y * src.width + sx) * src.channels;
+                        &src.data[idx..idx + 4]
+                    }
+                    None => &outside[..],
+                };
+                *value = Complex::new(decode(sample[first], first), decode(sample[first + 1], first + 1));
+            }
+            fft::fft(pair, false);
+            for (value, &weight) in pair.iter_mut().zip(&spectrum) {
+                *value = *value * weight;
+            }
+            fft::fft(pair, true);
+        }
+
+        let mut row_data = vec![0u8; src.width * src.channels];
+        for (x, pixel) in row_data.chunks_exact_mut(src.channels).enumerate() {
+            let (rg, ba) = (pairs[0][x + radius], pairs[1][x + radius]);
+            pixel.copy_from_slice(&[encode(rg.re), encode(rg.im), encode(ba.re), ba.im.round() as u8]);
+        }
+        local_rows.push((y, row_data));
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst = dst.lock().unwrap();
+    for (y, row_data) in local_rows {
+        let row_start = y * src.width * src.channels;
+        dst.data[row_start..row_start + src.width * src.channels].copy_from_slice(&row_data);
+    }
+}
+
+// Separable Gaussian blur: a horizontal pass in bands of rows, then the same pass over the
+// transposed result, each with its own sigma. Radii of `FFT_RADIUS` and up convolve through the FFT.
+pub fn apply_gaussian_blur(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    // Samples past the edges map back into the image, so any radius is valid; past the longest side
+    // it is capped, which keeps the kernel no larger than the image
+    let given = capped_radius(usize::try_from(radius).expect("radius must not be negative"), img.width(), img.height());
+    let radius = capped_radius(blur_radius(given, filter), img.width(), img.height());
+    if radius == 0 {
+        return img.clone();
+    }
     let src = ImageData::from_image_buffer(img);
-    let radius = radius as usize;
+    // Both passes, the second over the transposed image
+    progress::expect(src.height + src.width);
 
-    let kernel = generate_gaussian_kernel(radius);
-    let kernel_arc = Arc::new(kernel);
+    let (sigma_x, sigma_y) = sigmas(given, filter);
+    let (kernel_x, kernel_y) = tracing::info_span!("kernel", radius, sigma_x, sigma_y)
+        .in_scope(|| (gaussian_kernel(radius, sigma_x), gaussian_kernel(radius, sigma_y)));
+    let kernel_arc = Arc::new(kernel_x);
+    let blur_rows = if radius >= FFT_RADIUS { horizontal_fft_blur } else { horizontal_gaussian_blur };
 
     let dst_horizontal = Arc::new(Mutex::new(ImageData {
         data: vec![0; src.data.len()],
@@ -124,24 +264,24 @@ pub fn apply_gaussian_blur(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, nu
         channels: src.channels,
     }));
 
-    let rows_per_thread = src.height / num_threads;
+    let bands = bands::split(src.height, num_threads);
     let src_arc = Arc::new(src);
 
-    let handles: Vec<_> = (0..num_threads)
-        .map(|thread_id| {
+    let pass = tracing::info_span!("blur_pass", direction = "horizontal").entered();
+    let handles: Vec<_> = bands
+        .into_iter()
+        .enumerate()
+        .map(|(thread_id, rows)| {
+            let parent = tracing::Span::current();
             let src = Arc::clone(&src_arc);
             let dst = Arc::clone(&dst_horizontal);
             let kernel = Arc::clone(&kernel_arc);
 
-            thread::spawn(move || {
-                let start_y = thread_id * rows_per_thread;
-                let end_y = if thread_id == num_threads - 1 {
-                    src.height
-                } else {
-                    (thread_id + 1) * rows_per_thread
-                };
-
-                horizontal_gaussian_blur(&src, dst, &kernel, radius, start_y, end_y);
+            workers::spawn(move || {
+                let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?rows).entered();
+                let mut clock = WorkerClock::start("blur-h", thread_id, rows.clone());
+                blur_rows(&src, dst, &kernel, radius, filter, rows, &mut clock);
+                clock.finish();
             })
         })
         .collect();
@@ -149,12 +289,13 @@ pub fn apply_gaussian_blur(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, nu
     for handle in handles {
         handle.join().unwrap();
     }
+    pass.exit();
 
     let horizontal_result = Arc::try_unwrap(dst_horizontal)
         .unwrap()
         .into_inner()
         .unwrap();
-    let transposed = horizontal_result.transpose();
+    let transposed = tracing::info_span!("transpose").in_scope(|| horizontal_result.transpose());
 
     let dst_vertical = Arc::new(Mutex::new(ImageData {
         data: vec![0; transposed.data.len()],
@@ -163,24 +304,25 @@ pub fn apply_gaussian_blur(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, nu
         channels: transposed.channels,
     }));
 
-    let rows_per_thread = transposed.height / num_threads;
+    let bands = bands::split(transposed.height, num_threads);
     let transposed_arc = Arc::new(transposed);
-
-    let handles: Vec<_> = (0..num_threads)
-        .map(|thread_id| {
+    let kernel_arc = Arc::new(kernel_y);
+
+    let pass = tracing::info_span!("blur_pass", direction = "vertical").entered();
+    let handles: Vec<_> = bands
+        .into_iter()
+        .enumerate()
+        .map(|(thread_id, rows)| {
+            let parent = tracing::Span::current();
             let src = Arc::clone(&transposed_arc);
             let dst = Arc::clone(&dst_vertical);
             let kernel = Arc::clone(&kernel_arc);
 
-            thread::spawn(move || {
-                let start_y = thread_id * rows_per_thread;
-                let end_y = if thread_id == num_threads - 1 {
-                    src.height
-                } else {
-                    (thread_id + 1) * rows_per_thread
-                };
-
-                horizontal_gaussian_blur(&src, dst, &kernel, radius, start_y, end_y);
+            workers::spawn(move || {
+                let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?rows).entered();
+                let mut clock = WorkerClock::start("blur-v", thread_id, rows.clone());
+                blur_rows(&src, dst, &kernel, radius, filter, rows, &mut clock);
+                clock.finish();
             })
         })
         .collect();
@@ -188,12 +330,15 @@ pub fn apply_gaussian_blur(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, nu
     for handle in handles {
         handle.join().unwrap();
     }
+    pass.exit();
 
     let vertical_result = Arc::try_unwrap(dst_vertical)
         .unwrap()
         .into_inner()
         .unwrap();
-    let final_result = vertical_result.transpose();
+    let final_result = tracing::info_span!("transpose").in_scope(|| vertical_result.transpose());
 
-    final_result.to_image_buffer()
+    let mut result = final_result.to_image_buffer();
+    tracing::info_span!("channels").in_scope(|| channels::restore(img, &mut result, filter.channels, num_threads));
+    result
 }
\ No newline at end of file
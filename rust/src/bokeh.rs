@@ -0,0 +1,144 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::srgb;
+use crate::timing::WorkerClock;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+// Luma from which `--highlights` starts boosting a pixel, rising to the full boost at white
+pub const HIGHLIGHT_START: f64 = 192.0;
+
+// The disc of the kernel as a run of columns for each row: (dy, half width), every offset with
+// dx² + dy² <= r² inside
+pub fn disc(radius: i32) -> Vec<(i32, i32)> {
+    (-radius..=radius)
+        .map(|dy| {
+            let mut half = 0;
+            while (half + 1) * (half + 1) + dy * dy <= radius * radius {
+                half += 1;
+            }
+            (dy, half)
+        })
+        .collect()
+}
+
+// A pixel as the disc sums it: its channels times its weight, then the weight. Color is linear
+// with `--linear`. Bright pixels weigh up to 1 + `highlights` times as much, so they spread into
+// the bright discs an out-of-focus lens makes of lights.
+pub fn sample(pixel: [u8; 4], filter: FilterOptions) -> [f64; 5] {
+    let lut = srgb::lut();
+    let luma = 0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64;
+    let t = ((luma - HIGHLIGHT_START) / (255.0 - HIGHLIGHT_START)).clamp(0.0, 1.0);
+    let weight = 1.0 + filter.highlights * t * t;
+    let decode = |value: u8| if filter.linear { lut.decode(value) } else { value as f64 };
+    [decode(pixel[0]) * weight, decode(pixel[1]) * weight, decode(pixel[2]) * weight, pixel[3] as f64 * weight, weight]
+}
+
+// The weighted mean of summed samples
+pub fn resolve(sum: [f64; 5], filter: FilterOptions) -> Rgba<u8> {
+    let lut = srgb::lut();
+    let encode = |value: f64| if filter.linear { lut.encode(value / sum[4]) } else { (value / sum[4]).round() as u8 };
+    Rgba([encode(sum[0]), encode(sum[1]), encode(sum[2]), (sum[3] / sum[4]).round() as u8])
+}
+
+fn process_bokeh_rows(
+    samples: Arc<Vec<[f64; 5]>>,
+    dst: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
+    (width, height): (i32, i32),
+    disc: &[(i32, i32)],
+    filter: FilterOptions,
+    rows: Range<u32>,
+    clock: &mut WorkerClock,
+) {
+    let mut local_pixels = Vec::new();
+
+    for y in rows {
+        for x in 0..width {
+            let mut sum = [0.0; 5];
+            for &(dy, half) in disc {
+                let sy = (y as i32 + dy).clamp(0, height - 1);
+                for dx in -half..=half {
+                    let sx = (x + dx).clamp(0, width - 1);
+                    let sample = &samples[(sy * width + sx) as usize];
+                    for (total, value) in sum.iter_mut().zip(sample) {
+                        *total += value;
+                    }
+                }
+            }
+            local_pixels.push((x as u32, y, resolve(sum, filter)));
+        }
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst_locked = dst.lock().unwrap();
+    for (x, y, pixel) in local_pixels {
+        dst_locked.put_pixel(x, y, pixel);
+    }
+}
+
+// Lens blur: every pixel becomes the mean of the hard-edged disc of radius r around it, the shape
+// an out-of-focus aperture draws. The disc does not split into a horizontal and a vertical pass
+// like the Gaussian, so a pixel costs about πr² samples instead of 2(2r+1), and large radii get
+// expensive fast. Samples are weighted once up front; then one band of rows per thread, edge
+// pixels repeating past the border.
+pub fn apply_bokeh(
+    src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    radius: i32,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let radius = u32::try_from(radius).expect("radius must not be negative") as i32;
+    let (width, height) = src.dimensions();
+    if radius == 0 {
+        return src.clone();
+    }
+    progress::expect(height as usize);
+
+    let mut samples = vec![[0.0; 5]; width as usize * height as usize];
+    tracing::info_span!("weights").in_scope(|| {
+        workers::scope_each(bands::split_mut(&mut samples, width as usize, num_threads), |(rows, band)| {
+            let pixels = src.as_raw()[rows.start * width as usize * 4..rows.end * width as usize * 4].chunks_exact(4);
+            for (sample, pixel) in band.iter_mut().zip(pixels) {
+                *sample = self::sample([pixel[0], pixel[1], pixel[2], pixel[3]], filter);
+            }
+        });
+    });
+    let samples = Arc::new(samples);
+    let disc = Arc::new(disc(radius));
+    let dst = Arc::new(Mutex::new(ImageBuffer::new(width, height)));
+    let mut handles = Vec::new();
+
+    let pass = tracing::info_span!("bokeh_pass").entered();
+    for (thread_id, rows) in bands::split(height as usize, num_threads).into_iter().enumerate() {
+        let parent = tracing::Span::current();
+        let samples = Arc::clone(&samples);
+        let disc = Arc::clone(&disc);
+        let dst = Arc::clone(&dst);
+
+        let handle = workers::spawn(move || {
+            let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?rows).entered();
+            let mut clock = WorkerClock::start("bokeh", thread_id, rows.clone());
+            process_bokeh_rows(samples, dst, (width as i32, height as i32), &disc, filter, rows.start as u32..rows.end as u32, &mut clock);
+            clock.finish();
+        });
+
+        handles.push(handle);
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    pass.exit();
+
+    let mut result = Arc::try_unwrap(dst)
+        .unwrap()
+        .into_inner()
+        .unwrap();
+    tracing::info_span!("channels").in_scope(|| channels::restore(src, &mut result, filter.channels, num_threads));
+    result
+}
@@ -0,0 +1,64 @@
+use crate::bands;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::str::FromStr;
+
+// Channels a filter may change; the others keep the input's values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    Rgb,
+    Rgba,
+    // Alpha only, for feathering masks
+    A,
+    // Luma only, leaving chroma sharp
+    Luma,
+}
+
+impl FromStr for Channels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rgb" => Ok(Channels::Rgb),
+            "rgba" => Ok(Channels::Rgba),
+            "a" => Ok(Channels::A),
+            "luma" => Ok(Channels::Luma),
+            other => Err(format!("Unknown channels: {}", other)),
+        }
+    }
+}
+
+// BT.601 luma of gamma-encoded values
+pub fn luma(pixel: &[u8]) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+// The output pixel from the input `src` and the filtered `filtered`. Luma, Cb and Cr are linear in
+// R, G and B, so shifting all three by the change in luma keeps the input's chroma.
+pub fn select(src: [u8; 4], filtered: [u8; 4], channels: Channels) -> [u8; 4] {
+    match channels {
+        Channels::Rgba => filtered,
+        Channels::Rgb => [filtered[0], filtered[1], filtered[2], src[3]],
+        Channels::A => [src[0], src[1], src[2], filtered[3]],
+        Channels::Luma => {
+            let shift = luma(&filtered) - luma(&src);
+            let [r, g, b] = [0, 1, 2].map(|ch| (src[ch] as f32 + shift).round().clamp(0.0, 255.0) as u8);
+            [r, g, b, src[3]]
+        }
+    }
+}
+
+// Puts the unselected channels of `src` back into `filtered`, one band of rows per thread
+pub fn restore(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, filtered: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, channels: Channels, num_threads: usize) {
+    if channels == Channels::Rgba {
+        return;
+    }
+    let row_len = src.width() as usize * 4;
+    workers::scope_each(bands::split_mut(filtered, row_len, num_threads), |(rows, band)| {
+        let src_band = &src.as_raw()[rows.start * row_len..rows.end * row_len];
+        for (dst, src) in band.chunks_exact_mut(4).zip(src_band.chunks_exact(4)) {
+            let merged = select([src[0], src[1], src[2], src[3]], [dst[0], dst[1], dst[2], dst[3]], channels);
+            dst.copy_from_slice(&merged);
+        }
+    });
+}
@@ -0,0 +1,49 @@
+#[cfg(not(target_arch = "wasm32"))]
+use arboard::{Clipboard, ImageData};
+use image::{ImageBuffer, Rgba};
+#[cfg(not(target_arch = "wasm32"))]
+use std::borrow::Cow;
+use std::error::Error;
+
+// Placeholder positional path standing in for the clipboard
+pub const PATH: &str = "clipboard:";
+
+#[cfg(target_arch = "wasm32")]
+pub fn read() -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    Err("The clipboard is not available in WebAssembly builds".into())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(_img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    Err("The clipboard is not available in WebAssembly builds".into())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read() -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    let image = Clipboard::new()?.get_image()?;
+    let buffer = ImageBuffer::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+        .ok_or("Clipboard image does not match its dimensions")?;
+    Ok(buffer)
+}
+
+// On Linux the clipboard is served by the process that set it, so this blocks until
+// another program (usually the clipboard manager) takes the contents over
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    let image = ImageData {
+        width: img.width() as usize,
+        height: img.height() as usize,
+        bytes: Cow::Borrowed(img.as_raw()),
+    };
+    let mut clipboard = Clipboard::new()?;
+
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        clipboard.set().wait().image(image)?;
+    }
+    #[cfg(not(target_os = "linux"))]
+    clipboard.set_image(image)?;
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use crate::bands;
+use crate::blur;
+use crate::channels::{self, Channels};
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::fs;
+use std::str::FromStr;
+
+// A kernel of odd width and height centered on the pixel, its weights row by row. `bias` is added
+// to the weighted sum, and `absolute` keeps the magnitude of the sum for kernels that answer an
+// edge with either sign.
+#[derive(Clone, Copy, Debug)]
+pub struct Kernel {
+    pub width: usize,
+    pub height: usize,
+    pub weights: &'static [f32],
+    pub bias: f32,
+    pub absolute: bool,
+}
+
+// Relief lit from the top left: flat areas turn mid gray, and edges light or dark by which way
+// they face
+pub const EMBOSS: Kernel = Kernel {
+    width: 3,
+    height: 3,
+    weights: &[-1.0, -1.0, 0.0, -1.0, 0.0, 1.0, 0.0, 1.0, 1.0],
+    bias: 128.0,
+    absolute: false,
+};
+
+// 8-neighbor Laplacian: zero on flat areas and linear ramps, large where the intensity bends
+pub const LAPLACIAN: Kernel = Kernel {
+    width: 3,
+    height: 3,
+    weights: &[-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0],
+    bias: 0.0,
+    absolute: true,
+};
+
+// A kernel file: one row of weights per line, separated by spaces or commas, with `#` comments.
+// Optional `divisor N` divides every weight, `bias N` sets the offset and `absolute` the magnitude.
+// Kernels are read once per run, so the weights are leaked to stay `Copy` like the built-in ones.
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rows: Vec<Vec<f32>> = Vec::new();
+        let mut divisor = 1.0;
+        let mut bias = 0.0;
+        let mut absolute = false;
+
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == ',').filter(|word| !word.is_empty()).collect();
+            match words.as_slice() {
+                [] => {}
+                ["divisor", n] => {
+                    divisor = n.parse().map_err(|_| format!("Invalid divisor: {}", n))?;
+                    if divisor == 0.0 {
+                        return Err("divisor must not be 0".to_string());
+                    }
+                }
+                ["bias", n] => bias = n.parse().map_err(|_| format!("Invalid bias: {}", n))?,
+                ["absolute"] => absolute = true,
+                [first, ..] if first.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    return Err(format!("Unknown kernel keyword: {}", first));
+                }
+                values => rows.push(values.iter().map(|v| v.parse().map_err(|_| format!("Invalid weight: {}", v))).collect::<Result<_, _>>()?),
+            }
+        }
+
+        let height = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+        if height == 0 {
+            return Err("Kernel has no weights".to_string());
+        }
+        if let Some(row) = rows.iter().find(|row| row.len() != width) {
+            return Err(format!("Kernel rows must be the same length, got {} and {}", width, row.len()));
+        }
+        if width.is_multiple_of(2) || height.is_multiple_of(2) {
+            return Err(format!("Kernel must have an odd width and height to center on a pixel, got {}x{}", width, height));
+        }
+        let weights: Vec<f32> = rows.into_iter().flatten().map(|weight| weight / divisor).collect();
+        Ok(Kernel { width, height, weights: Box::leak(weights.into_boxed_slice()), bias, absolute })
+    }
+}
+
+impl Kernel {
+    pub fn load(path: &str) -> Result<Kernel, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        text.parse().map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Rows on each side of a pixel the kernel reads
+    pub fn reach(&self) -> usize {
+        self.height / 2
+    }
+
+    // The kernel at one pixel of one channel, `value` giving the channel at an offset from it.
+    // Taps are summed row by row, then rounded and clamped to 8 bits.
+    pub fn apply(&self, value: impl Fn(i32, i32) -> f32) -> u8 {
+        let (reach_x, reach_y) = ((self.width / 2) as i32, (self.height / 2) as i32);
+        let mut sum = 0.0;
+        for (i, weight) in self.weights.iter().enumerate() {
+            let (dx, dy) = ((i % self.width) as i32 - reach_x, (i / self.width) as i32 - reach_y);
+            sum += weight * value(dx, dy);
+        }
+        sum += self.bias;
+        if self.absolute {
+            sum = sum.abs();
+        }
+        sum.round().clamp(0.0, 255.0) as u8
+    }
+}
+
+// Runs `kernel` over every color channel, alpha kept, reading past the border by `--edge`. A radius
+// above 0 smooths the image with the Gaussian blur first, as for Sobel.
+pub fn apply_convolution(
+    src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    kernel: Kernel,
+    radius: i32,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let radius = u32::try_from(radius).expect("radius must not be negative") as i32;
+    let blurred;
+    let smoothed = if radius == 0 {
+        src
+    } else {
+        blurred = blur::apply_gaussian_blur(src, radius, num_threads, FilterOptions { channels: Channels::Rgba, ..filter });
+        &blurred
+    };
+
+    let (width, height) = src.dimensions();
+    let mut result = src.clone();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        for (y, row) in rows.clone().zip(band.chunks_exact_mut(row_len)) {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                for (channel, value) in pixel[..3].iter_mut().enumerate() {
+                    *value = kernel.apply(|dx, dy| {
+                        filter.edge.pixel(x as i32 + dx, y as i32 + dy, width, height, |sx, sy| smoothed.get_pixel(sx, sy).0)[channel] as f32
+                    });
+                }
+            }
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(src, &mut result, filter.channels, num_threads);
+    result
+}
@@ -0,0 +1,45 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{ImageBuffer, ImageFormat, Rgba};
+use std::error::Error;
+use std::io::{self, Cursor, Read};
+
+pub fn is_data_uri(input: &str) -> bool {
+    input.starts_with("data:")
+}
+
+// Decodes `data:<mime>;base64,<payload>`
+pub fn decode(uri: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (header, payload) = uri
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        .ok_or("Malformed data URI")?;
+    if !header.ends_with(";base64") {
+        return Err("Only base64 data URIs are supported".into());
+    }
+    Ok(STANDARD.decode(payload.trim())?)
+}
+
+// Reads the encoded input from stdin, either as a data URI or as plain image bytes.
+// Large images do not fit in a single command line argument, so this is the usual route.
+pub fn read_stdin() -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = Vec::new();
+    io::stdin().read_to_end(&mut data)?;
+    if data.starts_with(b"data:") {
+        decode(std::str::from_utf8(&data)?)
+    } else {
+        Ok(data)
+    }
+}
+
+// Encodes in the format implied by the output path extension, PNG when there is none
+pub fn encode_image(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, path: &str) -> Result<(Vec<u8>, ImageFormat), Box<dyn Error>> {
+    let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Png);
+    let mut encoded = Vec::new();
+    img.write_to(&mut Cursor::new(&mut encoded), format)?;
+    Ok((encoded, format))
+}
+
+pub fn encode(data: &[u8], format: ImageFormat) -> String {
+    format!("data:{};base64,{}", format.to_mime_type(), STANDARD.encode(data))
+}
@@ -0,0 +1,247 @@
+use image::{ImageBuffer, Luma};
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+
+// Part 10 files start with a 128-byte preamble followed by this magic
+const PREAMBLE_LEN: usize = 128;
+const MAGIC: &[u8; 4] = b"DICM";
+
+pub fn is_dicom(path: &str) -> bool {
+    if path.to_ascii_lowercase().ends_with(".dcm") {
+        return true;
+    }
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let mut header = [0u8; PREAMBLE_LEN + 4];
+    file.read_exact(&mut header).is_ok() && &header[PREAMBLE_LEN..] == MAGIC
+}
+
+#[cfg(not(feature = "dicom"))]
+pub fn load(_path: &str) -> Result<ImageBuffer<Luma<u16>, Vec<u16>>, Box<dyn Error>> {
+    Err("DICOM input requires building with `--features dicom`".into())
+}
+
+#[cfg(feature = "dicom")]
+mod reader {
+    use std::error::Error;
+
+    pub const TRANSFER_SYNTAX: u32 = 0x0002_0010;
+    pub const SAMPLES_PER_PIXEL: u32 = 0x0028_0002;
+    pub const PHOTOMETRIC: u32 = 0x0028_0004;
+    pub const ROWS: u32 = 0x0028_0010;
+    pub const COLUMNS: u32 = 0x0028_0011;
+    pub const BITS_ALLOCATED: u32 = 0x0028_0100;
+    pub const BITS_STORED: u32 = 0x0028_0101;
+    pub const PIXEL_REPRESENTATION: u32 = 0x0028_0103;
+    pub const WINDOW_CENTER: u32 = 0x0028_1050;
+    pub const WINDOW_WIDTH: u32 = 0x0028_1051;
+    pub const RESCALE_INTERCEPT: u32 = 0x0028_1052;
+    pub const RESCALE_SLOPE: u32 = 0x0028_1053;
+    pub const PIXEL_DATA: u32 = 0x7FE0_0010;
+
+    const ITEM: u32 = 0xFFFE_E000;
+    const ITEM_DELIMITER: u32 = 0xFFFE_E00D;
+    const SEQUENCE_DELIMITER: u32 = 0xFFFE_E0DD;
+    pub const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+    // Little endian element reader; `explicit_vr` switches between the two uncompressed syntaxes
+    pub struct Reader<'a> {
+        pub data: &'a [u8],
+        pub pos: usize,
+        pub explicit_vr: bool,
+    }
+
+    impl<'a> Reader<'a> {
+        pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+            let bytes = self.data.get(self.pos..self.pos + len).ok_or("Truncated DICOM file")?;
+            self.pos += len;
+            Ok(bytes)
+        }
+
+        fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+            Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?))
+        }
+
+        fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+            Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+        }
+
+        pub fn peek_group(&self) -> Option<u16> {
+            Some(u16::from_le_bytes(self.data.get(self.pos..self.pos + 2)?.try_into().ok()?))
+        }
+
+        // Reads an element header, returning the tag and value length
+        pub fn element(&mut self) -> Result<(u32, u32), Box<dyn Error>> {
+            let group = self.u16()?;
+            let tag = (group as u32) << 16 | self.u16()? as u32;
+
+            // Items and delimiters never carry a VR
+            if group == 0xFFFE || !self.explicit_vr {
+                return Ok((tag, self.u32()?));
+            }
+
+            let len = match self.bytes(2)? {
+                b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV" => {
+                    self.bytes(2)?;
+                    self.u32()?
+                }
+                _ => self.u16()? as u32,
+            };
+            Ok((tag, len))
+        }
+
+        // Skips a sequence of undefined length, up to and including its delimiter
+        pub fn skip_sequence(&mut self) -> Result<(), Box<dyn Error>> {
+            loop {
+                match self.element()? {
+                    (SEQUENCE_DELIMITER, _) => return Ok(()),
+                    (ITEM, UNDEFINED_LENGTH) => self.skip_item()?,
+                    (ITEM, len) => {
+                        self.bytes(len as usize)?;
+                    }
+                    (tag, _) => return Err(format!("Unexpected tag {:08X} in DICOM sequence", tag).into()),
+                }
+            }
+        }
+
+        fn skip_item(&mut self) -> Result<(), Box<dyn Error>> {
+            loop {
+                match self.element()? {
+                    (ITEM_DELIMITER, _) => return Ok(()),
+                    (_, UNDEFINED_LENGTH) => self.skip_sequence()?,
+                    (_, len) => {
+                        self.bytes(len as usize)?;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn text(value: &[u8]) -> String {
+        String::from_utf8_lossy(value).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string()
+    }
+
+    // Decimal strings may hold several values separated by backslashes; the first one is used
+    pub fn decimal(value: &[u8]) -> Result<f64, Box<dyn Error>> {
+        let text = text(value);
+        let first = text.split('\\').next().unwrap_or("").trim();
+        first.parse().map_err(|_| format!("Invalid DICOM decimal string: {}", text).into())
+    }
+
+    pub fn unsigned(value: &[u8]) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_le_bytes(value.get(..2).ok_or("Truncated DICOM value")?.try_into()?))
+    }
+}
+
+// Reads the first frame of an uncompressed single-channel DICOM image. Stored values go through
+// the rescale slope/intercept and are then mapped to the full 16-bit range, using the VOI window
+// when the file has one and the value range otherwise.
+#[cfg(feature = "dicom")]
+pub fn load(path: &str) -> Result<ImageBuffer<Luma<u16>, Vec<u16>>, Box<dyn Error>> {
+    use reader::*;
+
+    let data = std::fs::read(path)?;
+    if data.get(PREAMBLE_LEN..PREAMBLE_LEN + 4) != Some(MAGIC) {
+        return Err("Not a DICOM Part 10 file".into());
+    }
+
+    // The file meta group is always explicit VR little endian
+    let mut reader = Reader { data: &data, pos: PREAMBLE_LEN + 4, explicit_vr: true };
+    let mut in_meta = true;
+    let mut explicit_vr = false;
+    let (mut rows, mut columns, mut samples, mut bits_allocated, mut bits_stored) = (0, 0, 1, 16, 0);
+    let (mut signed, mut inverted) = (false, false);
+    let (mut slope, mut intercept) = (1.0, 0.0);
+    let (mut window_center, mut window_width) = (None, None);
+    let mut pixels = None;
+
+    while let Some(group) = reader.peek_group() {
+        // The dataset after the meta group uses the declared transfer syntax
+        if in_meta && group != 0x0002 {
+            in_meta = false;
+            reader.explicit_vr = explicit_vr;
+        }
+
+        let (tag, len) = reader.element()?;
+        if len == UNDEFINED_LENGTH {
+            if tag == PIXEL_DATA {
+                return Err("Compressed (encapsulated) DICOM pixel data is not supported".into());
+            }
+            reader.skip_sequence()?;
+            continue;
+        }
+
+        let value = reader.bytes(len as usize)?;
+        match tag {
+            TRANSFER_SYNTAX => {
+                explicit_vr = match text(value).as_str() {
+                    "1.2.840.10008.1.2" => false,
+                    "1.2.840.10008.1.2.1" => true,
+                    other => return Err(format!("Unsupported DICOM transfer syntax {}", other).into()),
+                };
+            }
+            SAMPLES_PER_PIXEL => samples = unsigned(value)?,
+            PHOTOMETRIC => inverted = text(value) == "MONOCHROME1",
+            ROWS => rows = unsigned(value)? as u32,
+            COLUMNS => columns = unsigned(value)? as u32,
+            BITS_ALLOCATED => bits_allocated = unsigned(value)?,
+            BITS_STORED => bits_stored = unsigned(value)?,
+            PIXEL_REPRESENTATION => signed = unsigned(value)? == 1,
+            WINDOW_CENTER => window_center = Some(decimal(value)?),
+            WINDOW_WIDTH => window_width = Some(decimal(value)?),
+            RESCALE_INTERCEPT => intercept = decimal(value)?,
+            RESCALE_SLOPE => slope = decimal(value)?,
+            PIXEL_DATA => {
+                pixels = Some(value);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let pixels = pixels.ok_or("DICOM file has no pixel data")?;
+    if samples != 1 {
+        return Err("Only single-channel (grayscale) DICOM images are supported".into());
+    }
+    if bits_allocated != 8 && bits_allocated != 16 {
+        return Err(format!("Unsupported DICOM bits allocated: {}", bits_allocated).into());
+    }
+
+    let count = rows as usize * columns as usize;
+    let bytes_per_sample = bits_allocated as usize / 8;
+    let pixels = pixels.get(..count * bytes_per_sample).ok_or("Truncated DICOM pixel data")?;
+
+    // Only the low `bits_stored` bits hold the sample; signed values are sign extended from there
+    let bits_stored = if bits_stored == 0 { bits_allocated } else { bits_stored.min(bits_allocated) } as u32;
+    let mask = ((1u32 << bits_stored) - 1) as i32;
+    let values: Vec<f64> = pixels
+        .chunks_exact(bytes_per_sample)
+        .map(|sample| {
+            let raw = if bytes_per_sample == 2 { u16::from_le_bytes([sample[0], sample[1]]) as i32 } else { sample[0] as i32 };
+            let mut stored = raw & mask;
+            if signed && stored >> (bits_stored - 1) & 1 == 1 {
+                stored -= 1 << bits_stored;
+            }
+            stored as f64 * slope + intercept
+        })
+        .collect();
+
+    let (low, high) = match (window_center, window_width) {
+        (Some(center), Some(width)) if width > 0.0 => (center - width / 2.0, center + width / 2.0),
+        _ => values.iter().fold((f64::MAX, f64::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v))),
+    };
+    let range = (high - low).max(f64::EPSILON);
+
+    let luma: Vec<u16> = values
+        .iter()
+        .map(|&v| {
+            let level = ((v - low) / range).clamp(0.0, 1.0);
+            let level = if inverted { 1.0 - level } else { level };
+            (level * u16::MAX as f64).round() as u16
+        })
+        .collect();
+
+    Ok(ImageBuffer::from_raw(columns, rows, luma).ok_or("DICOM buffer does not match its dimensions")?)
+}
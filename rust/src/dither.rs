@@ -0,0 +1,117 @@
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+// Columns of diffused error a row hands to the next in one message. Smaller chunks let the next
+// row start sooner but cost more messages.
+pub const CHUNK: usize = 32;
+
+// Nearest of `levels` values spread evenly over 0..=255
+pub fn quantize(value: i32, levels: u32) -> u8 {
+    let steps = levels as i32 - 1;
+    let level = (value * steps + 127) / 255;
+    ((level * 255 + steps / 2) / steps) as u8
+}
+
+// The Floyd–Steinberg split of a pixel's error into the parts for its right, below-left, below and
+// below-right neighbors, 7/16, 3/16, 5/16 and 1/16 of it. The parts are integers and the right one
+// takes what rounding leaves over, so no error is lost and any order of adding them up gives the
+// same sums.
+pub fn diffuse(error: i32) -> [i32; 4] {
+    let below_left = error * 3 / 16;
+    let below = error * 5 / 16;
+    let below_right = error / 16;
+    [error - below_left - below - below_right, below_left, below, below_right]
+}
+
+// Dithers one row given the error diffused into it from the row above, which arrives a chunk at a
+// time on `incoming`, and sends the error it diffuses into the row below the same way. The first
+// `x` columns of the row below are final once column `x` is done, so the next row can start while
+// this one is still running.
+fn dither_row(
+    row: &mut [u8],
+    levels: u32,
+    incoming: Option<&Receiver<Vec<[i32; 3]>>>,
+    outgoing: Option<&Sender<Vec<[i32; 3]>>>,
+) {
+    let width = row.len() / 4;
+    let mut above: Vec<[i32; 3]> = Vec::with_capacity(width);
+    let mut below = vec![[0; 3]; width];
+    let mut sent = 0;
+    let mut right = [0; 3];
+
+    for x in 0..width {
+        while above.len() <= x {
+            match incoming {
+                Some(incoming) => above.extend(incoming.recv().expect("Row above stopped early")),
+                None => above.resize(width, [0; 3]),
+            }
+        }
+        for ch in 0..3 {
+            let value = (row[x * 4 + ch] as i32 + above[x][ch] + right[ch]).clamp(0, 255);
+            let quantized = quantize(value, levels);
+            row[x * 4 + ch] = quantized;
+            let [to_right, below_left, to_below, below_right] = diffuse(value - quantized as i32);
+            right[ch] = to_right;
+            if x > 0 {
+                below[x - 1][ch] += below_left;
+            }
+            below[x][ch] += to_below;
+            if x + 1 < width {
+                below[x + 1][ch] += below_right;
+            }
+        }
+        if let Some(outgoing) = outgoing {
+            if x - sent >= CHUNK {
+                outgoing.send(below[sent..x].to_vec()).expect("Row below stopped early");
+                sent = x;
+            }
+        }
+    }
+    if let Some(outgoing) = outgoing {
+        outgoing.send(below[sent..].to_vec()).expect("Row below stopped early");
+    }
+}
+
+// Floyd–Steinberg dithering of every color channel to `levels` values, alpha kept. Error diffusion
+// runs left to right and top to bottom, so no pixel can be finished before those above and to its
+// left. Rows are dealt out to the threads in turn and run as a staircase: each thread passes the
+// error of its row to the thread of the next a chunk of columns at a time, and that thread starts
+// on its row as soon as the first chunk arrives. The output is the same as dithering serially.
+pub fn apply_dither(
+    src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    levels: i32,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let levels = u32::try_from(levels).ok().filter(|levels| (2..=256).contains(levels)).expect("levels must be between 2 and 256");
+    let (width, height) = src.dimensions();
+    progress::expect(height as usize);
+    // Scoped workers run one after another on WebAssembly, so the first would wait forever on the last
+    let num_threads = if cfg!(target_arch = "wasm32") { 1 } else { num_threads.clamp(1, height.max(1) as usize) };
+
+    let mut result = src.clone();
+    // A ring of channels, each thread sending to the one after it
+    let (mut senders, receivers): (Vec<_>, Vec<_>) = (0..num_threads).map(|_| mpsc::channel()).unzip();
+    senders.rotate_left(1);
+    let mut rows: Vec<Vec<(usize, &mut [u8])>> = (0..num_threads).map(|_| Vec::new()).collect();
+    for (y, row) in result.chunks_exact_mut(width as usize * 4).enumerate() {
+        rows[y % num_threads].push((y, row));
+    }
+
+    let parent = tracing::Span::current();
+    workers::scope_each(rows.into_iter().zip(receivers).zip(senders).enumerate(), |(thread_id, ((rows, incoming), outgoing))| {
+        let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = rows.len()).entered();
+        for (y, row) in rows {
+            let incoming = (y > 0).then_some(&incoming);
+            let outgoing = (y + 1 < height as usize).then_some(&outgoing);
+            dither_row(row, levels, incoming, outgoing);
+            progress::advance(1);
+        }
+    });
+    channels::restore(src, &mut result, filter.channels, num_threads);
+    result
+}
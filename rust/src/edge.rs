@@ -0,0 +1,92 @@
+use std::fmt;
+use std::str::FromStr;
+
+// What the blur and the convolutions read past the border of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    // The nearest edge pixel, repeated
+    Clamp,
+    // The image reflected about its edge pixels, which are not repeated
+    Mirror,
+    // The opposite side of the image, so a tiling texture stays seamless
+    Wrap,
+    // One color everywhere outside
+    Constant([u8; 4]),
+}
+
+impl FromStr for Edge {
+    type Err = String;
+
+    // `clamp`, `mirror`, `wrap`, or `constant:r,g,b[,a]` with alpha opaque when left out
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None => match s {
+                "clamp" => Ok(Edge::Clamp),
+                "mirror" => Ok(Edge::Mirror),
+                "wrap" => Ok(Edge::Wrap),
+                other => Err(format!("Unknown edge mode: {}", other)),
+            },
+            Some(("constant", color)) => {
+                let values = color.split(',').map(|value| value.trim().parse::<u8>()).collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| format!("Invalid edge color: {}", color))?;
+                match values[..] {
+                    [r, g, b] => Ok(Edge::Constant([r, g, b, 255])),
+                    [r, g, b, a] => Ok(Edge::Constant([r, g, b, a])),
+                    _ => Err(format!("Edge color takes 3 or 4 values, got {}", values.len())),
+                }
+            }
+            Some(_) => Err(format!("Unknown edge mode: {}", s)),
+        }
+    }
+}
+
+// The form `--edge` takes, so the bench can pass the mode on
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edge::Clamp => write!(f, "clamp"),
+            Edge::Mirror => write!(f, "mirror"),
+            Edge::Wrap => write!(f, "wrap"),
+            Edge::Constant([r, g, b, a]) => write!(f, "constant:{},{},{},{}", r, g, b, a),
+        }
+    }
+}
+
+impl Edge {
+    // Sample that position `i` along an axis of `len` samples reads, or None where it reads the
+    // constant color. Positions inside the axis read themselves.
+    pub fn index(self, i: i32, len: usize) -> Option<usize> {
+        let len = len as i32;
+        if (0..len).contains(&i) {
+            return Some(i as usize);
+        }
+        match self {
+            Edge::Clamp => Some(i.clamp(0, len - 1) as usize),
+            // A single sample reflects onto itself
+            Edge::Mirror if len == 1 => Some(0),
+            Edge::Mirror => {
+                let period = 2 * (len - 1);
+                let i = i.rem_euclid(period);
+                Some(if i < len { i } else { period - i } as usize)
+            }
+            Edge::Wrap => Some(i.rem_euclid(len) as usize),
+            Edge::Constant(_) => None,
+        }
+    }
+
+    // Color read where `index` gives None; only the constant mode ever does
+    pub fn outside(self) -> [u8; 4] {
+        match self {
+            Edge::Constant(color) => color,
+            _ => [0; 4],
+        }
+    }
+
+    // The pixel that (x, y) reads in an image of `width` by `height`, `pixel` fetching the ones inside
+    pub fn pixel(self, x: i32, y: i32, width: u32, height: u32, pixel: impl Fn(u32, u32) -> [u8; 4]) -> [u8; 4] {
+        match (self.index(x, width as usize), self.index(y, height as usize)) {
+            (Some(x), Some(y)) => pixel(x as u32, y as u32),
+            _ => self.outside(),
+        }
+    }
+}
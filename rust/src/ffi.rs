@@ -0,0 +1,103 @@
+// C entry points over the filters, built into a shared library with
+// `cargo rustc --release --lib --features ffi --crate-type cdylib`.
+// The header in include/concurrency.h is regenerated by build.rs with cbindgen.
+
+use crate::blur;
+use crate::cli::FilterOptions;
+use crate::kuwahara;
+use crate::size;
+use image::{ImageBuffer, Rgba};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Result of every `concurrency_*` call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcurrencyStatus {
+    Ok = 0,
+    /// `data` was null.
+    NullPointer = 1,
+    /// A size or `threads` was zero, `radius` was negative, or `stride` was shorter than a row.
+    InvalidArgument = 2,
+    /// The filter panicked; the image is left unchanged.
+    Panic = 3,
+    /// The image needs more memory than this platform can address.
+    TooLarge = 4,
+}
+
+type Filter = fn(&ImageBuffer<Rgba<u8>, Vec<u8>>, i32, usize, FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+fn filter_for(operation: &str) -> Filter {
+    match operation {
+        "kuwahara" => kuwahara::apply_kuwahara_filter,
+        _ => blur::apply_gaussian_blur,
+    }
+}
+
+unsafe fn filter_in_place(data: *mut u8, width: u32, height: u32, stride: u32, radius: i32, threads: u32, operation: &str) -> ConcurrencyStatus {
+    if data.is_null() {
+        return ConcurrencyStatus::NullPointer;
+    }
+    if width == 0 || height == 0 || threads == 0 || radius < 0 || (stride as u64) < width as u64 * 4 {
+        return ConcurrencyStatus::InvalidArgument;
+    }
+    // The caller's buffer, `stride` bytes per row, must be addressable as well as the filter's own
+    let len = stride as u128 * (height as u128 - 1) + width as u128 * 4;
+    if len > size::MAX_BYTES || size::check(operation, width, height, FilterOptions::default()).is_err() {
+        return ConcurrencyStatus::TooLarge;
+    }
+
+    // The caller's rows may be padded, so they are packed into an image and copied back
+    let (row, stride) = (width as usize * 4, stride as usize);
+    let pixels = std::slice::from_raw_parts_mut(data, len as usize);
+    let mut packed = Vec::with_capacity(row * height as usize);
+    for y in 0..height as usize {
+        packed.extend_from_slice(&pixels[y * stride..y * stride + row]);
+    }
+    let img = ImageBuffer::from_raw(width, height, packed).expect("Packed rows match dimensions");
+
+    // Unwinding into C is undefined behavior
+    let result = panic::catch_unwind(AssertUnwindSafe(|| filter_for(operation)(&img, radius, threads as usize, FilterOptions::default())));
+    let Ok(result) = result else {
+        return ConcurrencyStatus::Panic;
+    };
+
+    for (y, src) in result.as_raw().chunks_exact(row).enumerate() {
+        pixels[y * stride..y * stride + row].copy_from_slice(src);
+    }
+    ConcurrencyStatus::Ok
+}
+
+/// Gaussian blur of an RGBA8 image in place, using `threads` worker threads.
+///
+/// # Safety
+///
+/// `data` must point to `height` rows of `width` RGBA pixels, each row starting `stride` bytes
+/// after the previous one, and stay valid and unaliased for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn concurrency_blur(data: *mut u8, width: u32, height: u32, stride: u32, radius: i32, threads: u32) -> ConcurrencyStatus {
+    filter_in_place(data, width, height, stride, radius, threads, "blur")
+}
+
+/// Kuwahara filter of an RGBA8 image in place, using `threads` worker threads.
+///
+/// # Safety
+///
+/// Same requirements on `data` as `concurrency_blur`.
+#[no_mangle]
+pub unsafe extern "C" fn concurrency_kuwahara(data: *mut u8, width: u32, height: u32, stride: u32, radius: i32, threads: u32) -> ConcurrencyStatus {
+    filter_in_place(data, width, height, stride, radius, threads, "kuwahara")
+}
+
+/// Static, NUL-terminated description of a status code returned by this library.
+#[no_mangle]
+pub extern "C" fn concurrency_status_message(status: i32) -> *const std::ffi::c_char {
+    let message: &'static [u8] = match status {
+        0 => b"ok\0",
+        1 => b"data is null\0",
+        2 => b"invalid size, stride, radius or thread count\0",
+        3 => b"filter panicked\0",
+        4 => b"image too large for this platform\0",
+        _ => b"unknown status\0",
+    };
+    message.as_ptr().cast()
+}
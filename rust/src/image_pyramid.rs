@@ -0,0 +1,184 @@
+use crate::bands;
+use crate::blur;
+use crate::cli::FilterOptions;
+use crate::pyramid;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Blur before each halving; sigma 1 keeps the 2x2 box from aliasing
+const REDUCE_RADIUS: i32 = 3;
+// Laplacian levels are signed; zero detail is stored as mid gray
+const DETAIL_OFFSET: f32 = 128.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // Every level a blurred, halved copy of the one before
+    Gaussian,
+    // Every level the detail its Gaussian level has over the next, the last the coarsest Gaussian level
+    Laplacian,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gaussian" => Ok(Kind::Gaussian),
+            "laplacian" => Ok(Kind::Laplacian),
+            other => Err(format!("Unknown pyramid kind: {}. Use 'gaussian' or 'laplacian'", other)),
+        }
+    }
+}
+
+// How long each level took to make and to write
+pub struct LevelStats {
+    pub width: u32,
+    pub height: u32,
+    pub compute: Duration,
+    pub write: Duration,
+}
+
+// Levels until the image is down to a single pixel, the full size included
+pub fn max_levels(width: u32, height: u32) -> usize {
+    let mut side = width.max(height);
+    let mut levels = 1;
+    while side > 1 {
+        side = side.div_ceil(2);
+        levels += 1;
+    }
+    levels
+}
+
+// The next Gaussian level: blurred, then halved with odd sizes rounding up
+pub fn reduce(img: &Frame, num_threads: usize) -> Frame {
+    let blurred = blur::apply_gaussian_blur(img, REDUCE_RADIUS, num_threads, FilterOptions::default());
+    pyramid::halve(&blurred, num_threads)
+}
+
+// Bilinear sample of a level at the center of pixel (x, y) of the level twice its size
+pub fn expand_at(sample: impl Fn(u32, u32) -> f32, width: u32, height: u32, x: u32, y: u32) -> f32 {
+    let position = |p: u32, size: u32| ((p as f32 + 0.5) / 2.0 - 0.5).clamp(0.0, (size - 1) as f32);
+    let (sx, sy) = (position(x, width), position(y, height));
+    let (x0, y0) = (sx.floor() as u32, sy.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+    let top = sample(x0, y0) * (1.0 - fx) + sample(x1, y0) * fx;
+    let bottom = sample(x0, y1) * (1.0 - fx) + sample(x1, y1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+// Detail of a Gaussian level over the next coarser one expanded back to its size, offset to mid
+// gray and clamped to 8 bits; alpha is the level's own
+pub fn laplacian(level: &Frame, coarser: &Frame, num_threads: usize) -> Frame {
+    let width = level.width();
+    let (coarse_width, coarse_height) = coarser.dimensions();
+    let mut result = level.clone();
+    let row_len = width as usize * 4;
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
+            let (x, y) = ((i % width as usize) as u32, (rows.start + i / width as usize) as u32);
+            for (ch, value) in pixel.iter_mut().take(3).enumerate() {
+                let expanded = expand_at(|sx, sy| coarser.get_pixel(sx, sy)[ch] as f32, coarse_width, coarse_height, x, y);
+                *value = (*value as f32 - expanded + DETAIL_OFFSET).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    });
+    result
+}
+
+// The Gaussian levels, the full size first
+pub fn gaussian_pyramid(img: &Frame, levels: usize, num_threads: usize) -> Vec<Frame> {
+    let mut pyramid = vec![img.clone()];
+    for _ in 1..levels {
+        pyramid.push(reduce(pyramid.last().expect("The pyramid starts with the image"), num_threads));
+    }
+    pyramid
+}
+
+// A level of the pyramid as it is written, given its Gaussian level and the next coarser one
+pub fn level(kind: Kind, gaussian: &Frame, coarser: Option<&Frame>, num_threads: usize) -> Frame {
+    match (kind, coarser) {
+        (Kind::Laplacian, Some(coarser)) => laplacian(gaussian, coarser, num_threads),
+        _ => gaussian.clone(),
+    }
+}
+
+// `<output>/level_<n>.png`, level 0 the full size
+pub fn level_path(output: &Path, level: usize) -> PathBuf {
+    output.join(format!("level_{}.png", level))
+}
+
+// Writes `levels` levels of the pyramid to `output`. Each level is blurred and halved in bands
+// across the threads, and needs the level before it, so the levels themselves come one after
+// another. With `pipelined` a writer thread takes each Gaussian level over a channel holding one,
+// computes its Laplacian once the next arrives and encodes it while the next level is being
+// reduced; without, every level is made first and then written.
+pub fn write(img: &Frame, kind: Kind, levels: usize, output: &str, pipelined: bool, num_threads: usize) -> Result<Vec<LevelStats>, Box<dyn Error>> {
+    let output = Path::new(output);
+    fs::create_dir_all(output)?;
+    let write_level = |index: usize, gaussian: &Frame, coarser: Option<&Frame>, compute: Duration| -> Result<LevelStats, Box<dyn Error>> {
+        let _span = tracing::info_span!("write_level", level = index).entered();
+        let start = Instant::now();
+        let frame = level(kind, gaussian, coarser, num_threads);
+        let compute = compute + start.elapsed();
+        let start = Instant::now();
+        frame.save(level_path(output, index))?;
+        Ok(LevelStats { width: frame.width(), height: frame.height(), compute, write: start.elapsed() })
+    };
+
+    if !pipelined {
+        let mut reduced = Vec::with_capacity(levels);
+        let mut pyramid = vec![img.clone()];
+        reduced.push(Duration::ZERO);
+        for index in 1..levels {
+            let start = Instant::now();
+            let next = tracing::info_span!("reduce", level = index).in_scope(|| reduce(&pyramid[index - 1], num_threads));
+            reduced.push(start.elapsed());
+            pyramid.push(next);
+        }
+        return (0..levels).map(|index| write_level(index, &pyramid[index], pyramid.get(index + 1), reduced[index])).collect();
+    }
+
+    let (sender, receiver) = mpsc::sync_channel::<(Arc<Frame>, Duration)>(1);
+    thread::scope(|scope| {
+        let writer = scope.spawn(move || -> Result<Vec<LevelStats>, String> {
+            let mut stats = Vec::with_capacity(levels);
+            // Each level waits for the next, which its Laplacian is taken against
+            let mut previous: Option<(Arc<Frame>, Duration)> = None;
+            for (next, reduced) in receiver {
+                if let Some((gaussian, compute)) = previous.take() {
+                    stats.push(write_level(stats.len(), &gaussian, Some(&next), compute).map_err(|e| e.to_string())?);
+                }
+                previous = Some((next, reduced));
+            }
+            if let Some((gaussian, compute)) = previous {
+                stats.push(write_level(stats.len(), &gaussian, None, compute).map_err(|e| e.to_string())?);
+            }
+            Ok(stats)
+        });
+
+        let mut current = Arc::new(img.clone());
+        // A failed writer drops the receiver, which stops the reducing too
+        if sender.send((Arc::clone(&current), Duration::ZERO)).is_ok() {
+            for index in 1..levels {
+                let start = Instant::now();
+                current = Arc::new(tracing::info_span!("reduce", level = index).in_scope(|| reduce(&current, num_threads)));
+                if sender.send((Arc::clone(&current), start.elapsed())).is_err() {
+                    break;
+                }
+            }
+        }
+        drop(sender);
+        writer.join().expect("Writer thread panicked").map_err(Into::into)
+    })
+}
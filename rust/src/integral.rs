@@ -0,0 +1,127 @@
+use crate::size;
+
+// Summed-area tables of `C` channels and their squares, so the mean and variance of any rectangle
+// take four lookups. Kuwahara compares quadrants with them and pixelate averages blocks. The first
+// three channels are the color; a fourth, when there is one, is alpha.
+// Sums are kept in f64: squares of 8-bit values summed over an 8K image pass 2^40, far beyond the
+// 2^24 that f32 holds exactly, and the rounding flipped the choice between close quadrants
+pub struct IntegralImage<const C: usize = 3> {
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+    // Summed alpha when the sums are alpha-weighted, empty otherwise
+    weight: Vec<f64>,
+    width: usize,
+    height: usize,
+}
+
+impl IntegralImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_size(width, height)
+    }
+}
+
+impl IntegralImage<4> {
+    // Tables of the color and alpha together, so alpha is smoothed by the same regions as the color
+    pub fn with_alpha(width: usize, height: usize) -> Self {
+        Self::with_size(width, height)
+    }
+}
+
+impl<const C: usize> IntegralImage<C> {
+    fn with_size(width: usize, height: usize) -> Self {
+        let size = size::integral_len(width, height, C).expect("Image too large for a summed-area table");
+        IntegralImage {
+            sum: vec![0.0; size],
+            sum_sq: vec![0.0; size],
+            weight: Vec::new(),
+            width,
+            height,
+        }
+    }
+
+    // `values` holds the `C` converted channels of every pixel, row by row. With `alpha`, one byte
+    // per pixel, every color value counts as much as its alpha, so transparent pixels drop out.
+    // Alpha itself is never weighted by alpha, or its mean would lean towards the opaque pixels.
+    pub fn build(&mut self, values: &[f32], alpha: Option<&[u8]>) {
+        let w = self.width;
+        let h = self.height;
+        let iw = self.width + 1;
+        if alpha.is_some() {
+            self.weight = vec![0.0; iw * (h + 1)];
+        }
+
+        for y in 1..=h {
+            for x in 1..=w {
+                let channels = &values[((y - 1) * w + x - 1) * C..][..C];
+                let weight = alpha.map_or(1.0, |alpha| alpha[(y - 1) * w + x - 1] as f64);
+                if alpha.is_some() {
+                    let idx = y * iw + x;
+                    self.weight[idx] = weight + self.weight[idx - iw] + self.weight[idx - 1] - self.weight[idx - iw - 1];
+                }
+
+                for (ch, &val) in channels.iter().enumerate() {
+                    let val = val as f64;
+                    let weight = if ch < 3 { weight } else { 1.0 };
+                    let idx = (y * iw + x) * C + ch;
+                    let idx_up = ((y - 1) * iw + x) * C + ch;
+                    let idx_left = (y * iw + (x - 1)) * C + ch;
+                    let idx_diag = ((y - 1) * iw + (x - 1)) * C + ch;
+
+                    self.sum[idx] = weight * val + self.sum[idx_up] + self.sum[idx_left] - self.sum[idx_diag];
+                    self.sum_sq[idx] = weight * val * val
+                        + self.sum_sq[idx_up]
+                        + self.sum_sq[idx_left]
+                        - self.sum_sq[idx_diag];
+                }
+            }
+        }
+    }
+
+    // Mean and variance of the inclusive rectangle from (x1, y1) to (x2, y2), clipped to the image.
+    // None when every pixel of the region is transparent and the sums are alpha-weighted
+    pub fn get_region_stats(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> Option<([f64; C], [f64; C])> {
+        let iw = self.width + 1;
+
+        let x1 = x1.max(0) as usize;
+        let y1 = y1.max(0) as usize;
+        let x2 = x2.min(self.width as i32 - 1) as usize;
+        let y2 = y2.min(self.height as i32 - 1) as usize;
+
+        let x1 = x1 + 1;
+        let y1 = y1 + 1;
+        let x2 = x2 + 1;
+        let y2 = y2 + 1;
+
+        let pixels = ((x2 - x1 + 1) * (y2 - y1 + 1)) as f64;
+        let area = if self.weight.is_empty() {
+            pixels
+        } else {
+            self.weight[y2 * iw + x2] - self.weight[y2 * iw + x1 - 1] - self.weight[(y1 - 1) * iw + x2] + self.weight[(y1 - 1) * iw + x1 - 1]
+        };
+        if area <= 0.0 {
+            return None;
+        }
+        let mut mean = [0.0; C];
+        let mut variance = [0.0; C];
+
+        for ch in 0..C {
+            let idx_br = (y2 * iw + x2) * C + ch;
+            let idx_bl = (y2 * iw + x1 - 1) * C + ch;
+            let idx_tr = ((y1 - 1) * iw + x2) * C + ch;
+            let idx_tl = ((y1 - 1) * iw + x1 - 1) * C + ch;
+            let area = if ch < 3 { area } else { pixels };
+
+            let sum = self.sum[idx_br] - self.sum[idx_bl] - self.sum[idx_tr] + self.sum[idx_tl];
+            let sum_sq = self.sum_sq[idx_br] - self.sum_sq[idx_bl] - self.sum_sq[idx_tr]
+                + self.sum_sq[idx_tl];
+
+            mean[ch] = sum / area;
+            variance[ch] = (sum_sq / area) - (mean[ch] * mean[ch]);
+            if variance[ch] < 0.0 {
+                variance[ch] = 0.0;
+            }
+        }
+
+        Some((mean, variance))
+    }
+}
@@ -0,0 +1,76 @@
+// Filters and codecs shared by the command line binary and the benchmarks
+pub mod animation;
+pub mod autolevel;
+pub mod bands;
+pub mod bench;
+pub mod bilateral;
+pub mod blend;
+pub mod blur;
+pub mod bokeh;
+pub mod channels;
+pub mod cli;
+pub mod clipboard;
+pub mod colorspace;
+pub mod convolve;
+pub mod data_uri;
+pub mod dicom;
+pub mod dither;
+pub mod edge;
+pub mod energy;
+pub mod fft;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod histeq;
+pub mod image_pyramid;
+pub mod integral;
+pub mod kuwahara;
+pub mod lut;
+pub mod magick;
+pub mod mandelbrot;
+pub mod mask;
+pub mod median;
+pub mod memory;
+pub mod metadata;
+pub mod monte_carlo;
+pub mod morphology;
+pub mod motion_blur;
+pub mod nlmeans;
+pub mod noise;
+pub mod oil;
+pub mod overlay;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod perf;
+pub mod pixelate;
+pub mod png_encoder;
+pub mod pnm;
+pub mod point;
+pub mod progress;
+pub mod pyramid;
+pub mod qoi_codec;
+pub mod quantize;
+pub mod raw;
+pub mod reference;
+pub mod report;
+pub mod resize;
+pub mod roi;
+pub mod sharpen;
+pub mod size;
+pub mod sobel;
+pub mod srgb;
+pub mod stack;
+pub mod stream;
+pub mod synthetic;
+pub mod thumbs;
+pub mod timing;
+pub mod tonemap;
+pub mod transform;
+pub mod varblur;
+pub mod verify;
+// Pipes frames through ffmpeg, which browsers cannot spawn
+#[cfg(not(target_arch = "wasm32"))]
+pub mod video;
+pub mod vignette;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod workers;
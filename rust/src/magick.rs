@@ -0,0 +1,160 @@
+// ImageMagick-style command lines such as `in.png -blur 0x5 -resize 50% out.png`, so scripts written
+// for `convert` or `magick` can switch over. Only operators with a native counterpart are accepted,
+// anything else is an error rather than silently ignored.
+
+pub enum Step {
+    // `sigma` overrides --sigma-x and --sigma-y for this step only
+    Filter { operation: &'static str, radius: i32, sigma: Option<f64> },
+    Resize(Geometry),
+}
+
+pub struct Command {
+    pub input: String,
+    pub output: String,
+    // Applied in command line order, as ImageMagick does
+    pub steps: Vec<Step>,
+    // From `-limit thread N`
+    pub threads: Option<usize>,
+}
+
+// True when the arguments use single-dash operators, which the native command line never does
+pub fn is_magick_style(args: &[String]) -> bool {
+    args.iter().any(|arg| {
+        let mut chars = arg.chars();
+        chars.next() == Some('-') && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+    })
+}
+
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut files = Vec::new();
+    let mut steps = Vec::new();
+    let mut threads = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !is_magick_style(std::slice::from_ref(arg)) {
+            files.push(arg.clone());
+            continue;
+        }
+        let mut value = || iter.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "-blur" | "-gaussian-blur" => {
+                let (radius, sigma) = blur_geometry(value()?)?;
+                steps.push(Step::Filter { operation: "blur", radius, sigma: Some(sigma) });
+            }
+            "-kuwahara" => {
+                let (radius, _) = parse_pair(value()?)?;
+                steps.push(Step::Filter { operation: "kuwahara", radius: radius.round() as i32, sigma: None });
+            }
+            "-resize" => steps.push(Step::Resize(Geometry::parse(value()?)?)),
+            "-limit" => {
+                let resource = value()?;
+                let amount = value()?;
+                if resource != "thread" {
+                    return Err(format!("Unsupported ImageMagick option: -limit {}", resource));
+                }
+                threads = Some(amount.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("Invalid thread limit: {}", amount))?);
+            }
+            other => return Err(format!("Unsupported ImageMagick option: {}", other)),
+        }
+    }
+
+    let [input, output] = <[String; 2]>::try_from(files).map_err(|files| {
+        format!("Expected an input and an output image around the operators, got {} file names", files.len())
+    })?;
+    Ok(Command { input, output, steps, threads })
+}
+
+// `radius[xsigma]` with both parts optional, e.g. "5", "0x2.5" or "x2"
+fn parse_pair(value: &str) -> Result<(f64, Option<f64>), String> {
+    let invalid = || format!("Invalid geometry: {}", value);
+    let (first, second) = match value.split_once('x') {
+        Some((first, second)) => (first, Some(second)),
+        None => (value, None),
+    };
+    let first = if first.is_empty() { 0.0 } else { first.parse().map_err(|_| invalid())? };
+    let second = second.map(|second| second.parse().map_err(|_| invalid())).transpose()?;
+    if first < 0.0 || second.is_some_and(|second: f64| second < 0.0) {
+        return Err(invalid());
+    }
+    Ok((first, second))
+}
+
+// The sigma goes to the native blur as it is, defaulting to 1 as it does in ImageMagick. The radius
+// bounds the kernel there as here, and 0 leaves it to the sigma.
+fn blur_geometry(value: &str) -> Result<(i32, f64), String> {
+    let (radius, sigma) = parse_pair(value)?;
+    Ok((radius.round() as i32, sigma.unwrap_or(1.0)))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Geometry {
+    width: Option<f64>,
+    height: Option<f64>,
+    percent: bool,
+    // `!`: exactly this size, ignoring the aspect ratio
+    exact: bool,
+    // `>`: only ever shrink, `<`: only ever enlarge
+    only_shrink: bool,
+    only_enlarge: bool,
+}
+
+impl Geometry {
+    // "50%", "180x70%", "640x480", "640x", "x480", with an optional trailing !, > or <
+    pub fn parse(value: &str) -> Result<Geometry, String> {
+        let invalid = || format!("Invalid geometry: {}", value);
+        let mut size = value;
+        let mut flags = String::new();
+        while let Some(last) = size.chars().last().filter(|c| "%!<>".contains(*c)) {
+            flags.push(last);
+            size = &size[..size.len() - 1];
+        }
+        let dimension = |part: &str| -> Result<Option<f64>, String> {
+            match part {
+                "" => Ok(None),
+                part => part.parse().ok().filter(|&n: &f64| n > 0.0).map(Some).ok_or_else(invalid),
+            }
+        };
+        // "180%x70%" marks both sides, as "180x70%" does
+        let (width, height) = match size.split_once('x') {
+            Some((width, height)) => (dimension(width.strip_suffix('%').unwrap_or(width))?, dimension(height)?),
+            None => (dimension(size)?, None),
+        };
+        let percent = flags.contains('%') || size.contains('%');
+        if width.is_none() && height.is_none() {
+            return Err(invalid());
+        }
+        Ok(Geometry {
+            width,
+            // "50%" scales both sides alike
+            height: if percent && !size.contains('x') { width } else { height },
+            percent,
+            exact: flags.contains('!'),
+            only_shrink: flags.contains('>'),
+            only_enlarge: flags.contains('<'),
+        })
+    }
+
+    // Size an image of `width` x `height` ends up at
+    pub fn target(&self, width: u32, height: u32) -> (u32, u32) {
+        let (w, h) = (width as f64, height as f64);
+        let (scale_x, scale_y) = if self.percent {
+            let x = self.width.unwrap_or(100.0) / 100.0;
+            (x, self.height.map_or(x, |y| y / 100.0))
+        } else {
+            let x = self.width.map(|target| target / w);
+            let y = self.height.map(|target| target / h);
+            match (x, y) {
+                (Some(x), Some(y)) if self.exact => (x, y),
+                // Fits inside the box, keeping the aspect ratio
+                (Some(x), Some(y)) => (x.min(y), x.min(y)),
+                (Some(x), None) => (x, if self.exact { 1.0 } else { x }),
+                (None, Some(y)) => (if self.exact { 1.0 } else { y }, y),
+                (None, None) => (1.0, 1.0),
+            }
+        };
+        if (self.only_shrink && scale_x >= 1.0 && scale_y >= 1.0) || (self.only_enlarge && scale_x <= 1.0 && scale_y <= 1.0) {
+            return (width, height);
+        }
+        (((w * scale_x).round() as u32).max(1), ((h * scale_y).round() as u32).max(1))
+    }
+}
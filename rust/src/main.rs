@@ -1,21 +1,1011 @@
-mod blur;
-mod kuwahara;
-mod monte_carlo;
-
+use rust_filter::{animation, autolevel, bench, bilateral, blend, blur, bokeh, cli, clipboard, convolve, data_uri, dicom, dither, edge, energy, histeq, image_pyramid, kuwahara, lut, magick, mandelbrot, mask, median, memory, metadata, monte_carlo, morphology, motion_blur, nlmeans, noise, oil, overlay, perf, pixelate, png_encoder, pnm, point, progress, pyramid, qoi_codec, quantize, raw, report, resize, roi, sharpen, size, sobel, stack, stream, synthetic, thumbs, timing, tonemap, transform, varblur, verify, video, vignette};
+use image::{ImageBuffer, Rgba};
 use std::env;
+use std::sync::Arc;
 use std::time::Instant;
 
+// Progress lines go to stderr when stdout carries the encoded output
+macro_rules! status {
+    ($options:expr, $($arg:tt)*) => {
+        if $options.output_format == cli::OutputFormat::DataUri {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 fn print_usage(program: &str) {
-    eprintln!("Usage: {} <operation> <input_image> <output_image> <radius> [threads]", program);
-    eprintln!("  operation: 'blur', 'kuwahara', or 'monte_carlo'");
+    eprintln!("Usage: {} <operation> <input_image> <output_image> <radius> [threads] [options]", program);
+    eprintln!("  operation: {}", operation_list(&["monte_carlo"]));
     eprintln!("  For monte_carlo: radius represents number of samples");
+    eprintln!("  For blur: radius may be left out, or 0 to give a thread count, when --sigma sets the Gaussian");
+    eprintln!("  For sobel, emboss and edges: radius blurs the image before the kernel runs, 0 for none");
+    eprintln!("  For sharpen: radius is the blur the detail is measured against, see --amount and --sharpen-threshold");
+    eprintln!("  For motion-blur: radius is the length of the streak in pixels, see --angle");
+    eprintln!("  For convolve: the --kernel file sets the weights, and radius is optional, blurring the image first");
+    eprintln!("  For nlmeans: radius is the search window, see --patch and --strength");
+    eprintln!("  For histeq: radius is ignored and may be left out, or 0 to give a thread count");
+    eprintln!("  For autolevel: radius is ignored and may be left out, or 0 to give a thread count; see --clip");
+    eprintln!("  For oil: radius is the brush, see --levels");
+    eprintln!("  For pixelate: radius is the block size in pixels");
+    eprintln!("  For dither: radius is the number of levels per channel, 2 to 256");
+    eprintln!("  For quantize: radius is the number of colors in the palette, 2 to 256");
+    eprintln!("  For bokeh: radius is the radius of the disc, see --highlights");
+    eprintln!("  For vignette: radius is the darkening in the corners in percent, 0 to 100, see --falloff");
+    eprintln!("  For posterize: radius is the number of levels per channel, 2 to 256");
+    eprintln!("  For gamma: radius is the gamma, e.g. 2.2 to brighten the midtones or 0.5 to darken them");
+    eprintln!("  For transform: radius is ignored and may be left out, or 0 to give a thread count; see --angle, --scale and --translate");
+    eprintln!("  For resize: radius is the output size, e.g. 50%, 640x480 or 640x, see --resample");
+    eprintln!("  For lut: radius is the path of a 3D .cube color table to grade the image with");
+    eprintln!("  For add-noise: radius is 'gaussian:<sigma>' or 'saltpepper:<amount>', drawn from --seed");
     eprintln!("  threads: optional, defaults to 4");
+    eprintln!("  Animated GIF and APNG inputs are filtered frame by frame and saved as animations");
+    eprintln!("  DICOM (.dcm) inputs are read when built with --features dicom");
+    eprintln!("  input_image may also be a base64 data: URI, or '-' to read the image or URI from stdin");
+    eprintln!("       {} verify <operation> <input_image> <backend_a> <backend_b> <radius> [threads] [--tolerance N]", program);
+    eprintln!("  backend: 'self', a reference output image, or another filter binary to run");
+    eprintln!("       {} bench <operation> <input_image> <radius> [threads] [--runs N] [--warmup N] [--sweep 1,2,4] [--json] [--against BIN] [--save-baseline NAME] [--compare-baseline NAME]", program);
+    eprintln!("       {} stack <mean|median> <output_image> <frame>... [threads]", program);
+    eprintln!("       {} focus-stack <output_image> <frame>... [threads]", program);
+    eprintln!("  frame: aligned exposures of the same size, combined per pixel");
+    eprintln!("       {} blend <image_a> <image_b> <mask> <output_image> [threads]", program);
+    eprintln!("  mask: white keeps image_a, black image_b, blended over a Laplacian pyramid");
+    eprintln!("       {} blend <image_a> <image_b> <output_image> [threads] [--mode normal|multiply|screen|overlay] [--opacity O]", program);
+    eprintln!("  without a mask image_b is composited over image_a by its alpha and the blend mode");
+    eprintln!("       {} overlay <input_image|input_dir> <stamp> <output_image|output_dir> [threads] [--at X,Y | --tile] [--opacity O]", program);
+    eprintln!("  stamps a watermark by its alpha; a directory is stamped file by file into output_dir under the same names");
+    eprintln!("       {} tonemap <input_image> <output_image> [threads] [--operator reinhard|aces] [--exposure EV]", program);
+    eprintln!("  maps a linear HDR image, such as Radiance .hdr or OpenEXR, to 8-bit sRGB by its average luminance");
+    eprintln!("       {} varblur <input_image> <mask> <output_image> <radius> [threads] [--blur-levels N]", program);
+    eprintln!("  blurs by the mask's brightness, sharp where it is black and by the full radius where it is white");
+    eprintln!("       {} thumbs <input_dir> <output_dir> [threads] [--sizes 256,512,1024] [--sharpen]", program);
+    eprintln!("  every image in input_dir is decoded once and written at each size as <name>_<size>.<ext>");
+    eprintln!("       {} mandelbrot <output_image> <width>x<height> [threads] [--center X,Y] [--zoom Z] [--max-iter N]", program);
+    eprintln!("  renders the Mandelbrot set, one band of rows per worker; --worker-timing shows how unevenly they finish");
+    eprintln!("       {} noise <output_image> <width>x<height>[:frequency[:octaves]] [threads]", program);
+    eprintln!("  writes tileable Perlin noise, the image --synthetic perlin:... generates (default frequency {}, {} octaves)", synthetic::DEFAULT_FREQUENCY, synthetic::DEFAULT_OCTAVES);
+    eprintln!("       {} pyramid <gaussian|laplacian> <input_image> <output_dir> <levels> [threads] [--pipeline]", program);
+    eprintln!("  writes each level as level_<n>.png, level 0 the full size; --pipeline writes levels while reducing the next");
+    eprintln!("       {} report <log>... [--report-format markdown|html]", program);
+    eprintln!("  log: a 'bench --json' result or a hyperfine --export-json/--export-csv file");
+    eprintln!("       {} <input_image> [-blur RxS] [-kuwahara R] [-resize GEOMETRY] [-limit thread N] <output_image>", program);
+    eprintln!("  ImageMagick-style operators, applied in order");
+    eprintln!("  Set RUST_LOG=info to print tracing spans for each phase, or RUST_LOG=debug to include every worker");
+    cli::print_options();
+}
+
+fn report_allocations(options: &cli::Options, phase: &str, allocations: Option<memory::PhaseAllocations>) {
+    if let Some(allocations) = allocations {
+        status!(options, "{} allocations: {:.1} MiB, peak live {:.1} MiB", phase, memory::to_mib(allocations.allocated), memory::to_mib(allocations.peak));
+    }
+}
+
+fn report_peak_memory(options: &cli::Options) {
+    if let Some(peak) = memory::peak_rss() {
+        status!(options, "Peak memory: {:.1} MiB", memory::to_mib(peak));
+    }
+}
+
+// Counters that fail to open (no permission, not Linux) only cost the report, not the run
+fn start_counters(options: &cli::Options) -> Option<perf::Counters> {
+    if !options.perf_counters {
+        return None;
+    }
+    match perf::Counters::new().and_then(|mut counters| counters.enable().map(|_| counters)) {
+        Ok(counters) => Some(counters),
+        Err(e) => {
+            eprintln!("Warning: {}", e);
+            None
+        }
+    }
+}
+
+// Unavailable counters are reported once and the run continues without them
+fn start_energy(options: &cli::Options) -> Option<energy::EnergyMeter> {
+    if !options.energy {
+        return None;
+    }
+    match energy::EnergyMeter::start() {
+        Ok(meter) => Some(meter),
+        Err(e) => {
+            eprintln!("Warning: {}", e);
+            None
+        }
+    }
+}
+
+fn report_counters(options: &cli::Options, counters: Option<perf::Counters>) {
+    let Some(mut counters) = counters else {
+        return;
+    };
+    match counters.disable().and_then(|_| counters.report()) {
+        Ok(report) => status!(options, "{}", report),
+        Err(e) => eprintln!("Warning: failed to read performance counters: {}", e),
+    }
+}
+
+// A radius of 0 leaves the image as it is; a negative one is an error rather than a huge kernel
+fn parse_radius(arg: &str) -> i32 {
+    match arg.parse::<i32>() {
+        Ok(radius) if radius >= 0 => radius,
+        Ok(_) => {
+            eprintln!("Radius must not be negative: {}", arg);
+            std::process::exit(1);
+        }
+        Err(_) => {
+            eprintln!("Invalid radius: {}", arg);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Every operation the filter, bench and verify paths run through `apply_filter`, with the name the
+// filter path reports it under
+const OPERATIONS: &[(&str, &str)] = &[
+    ("blur", "Gaussian blur"),
+    ("kuwahara", "Kuwahara filter"),
+    ("median", "Median filter"),
+    ("bilateral", "Bilateral filter"),
+    ("sobel", "Sobel edge detection"),
+    ("sharpen", "Unsharp mask"),
+    ("motion-blur", "Motion blur"),
+    ("emboss", "Emboss"),
+    ("edges", "Laplacian edges"),
+    ("convolve", "Convolution"),
+    ("nlmeans", "Non-local means"),
+    ("dilate", "Dilation"),
+    ("erode", "Erosion"),
+    ("histeq", "Histogram equalization"),
+    ("autolevel", "Auto-levels"),
+    ("oil", "Oil painting"),
+    ("pixelate", "Pixelate"),
+    ("dither", "Floyd-Steinberg dithering"),
+    ("quantize", "K-means quantization"),
+    ("bokeh", "Bokeh blur"),
+    ("vignette", "Vignette"),
+    ("posterize", "Posterize"),
+    ("gamma", "Gamma"),
+    ("transform", "Affine transform"),
+    ("resize", "Resize"),
+    ("lut", "3D LUT"),
+    ("add-noise", "Additive noise"),
+];
+
+// Names quoted for the usage and the errors, e.g. 'blur', 'kuwahara', or 'median'
+fn operation_list(extra: &[&str]) -> String {
+    let names: Vec<String> = OPERATIONS.iter().map(|(name, _)| name).chain(extra).map(|name| format!("'{}'", name)).collect();
+    match names.split_last() {
+        Some((last, rest)) => format!("{}, or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+// Exits listing the operations, and `extra` besides, when `operation` is none of them
+fn operation_description(operation: &str, extra: &[&str]) -> &'static str {
+    match OPERATIONS.iter().find(|(name, _)| *name == operation) {
+        Some((_, description)) => description,
+        None => {
+            eprintln!("Unknown operation: {}. Use {}", operation, operation_list(extra));
+            std::process::exit(1);
+        }
+    }
+}
+
+// A blur given a sigma derives its radius from it
+fn sigma_blur(operation: &str, filter: cli::FilterOptions) -> bool {
+    operation == "blur" && (filter.sigma_x.is_some() || filter.sigma_y.is_some())
+}
+
+// The argument in the radius' place. Gamma, resize, lut and add-noise take their value there
+// instead, stored in `options`, with the table read once before any image.
+fn parse_operand(operation: &str, arg: Option<&str>, options: &mut cli::Options) -> i32 {
+    if operation == "lut" {
+        match lut::Lut::load(arg.unwrap_or("")) {
+            Ok(table) => options.lut = Some(Arc::new(table)),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        0
+    } else if operation == "add-noise" {
+        match arg.unwrap_or("").parse::<noise::Noise>() {
+            Ok(noise) => options.filter.noise = Some(noise),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        0
+    } else if operation == "gamma" {
+        match arg.unwrap_or("").parse::<f64>() {
+            Ok(gamma) if gamma.is_finite() && gamma > 0.0 => options.filter.gamma = Some(gamma),
+            _ => {
+                eprintln!("gamma needs a positive value: {}", arg.unwrap_or(""));
+                std::process::exit(1);
+            }
+        }
+        0
+    } else if operation == "convolve" {
+        if options.filter.kernel.is_none() {
+            eprintln!("convolve needs a kernel file, pass it with --kernel");
+            std::process::exit(1);
+        }
+        arg.map_or(0, parse_radius)
+    } else if sigma_blur(operation, options.filter) {
+        arg.map_or(0, parse_radius)
+    } else if matches!(operation, "histeq" | "autolevel" | "transform") {
+        0
+    } else if operation == "resize" {
+        match magick::Geometry::parse(arg.unwrap_or("")) {
+            Ok(size) => options.filter.size = Some(size),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        0
+    } else {
+        parse_radius(arg.unwrap_or(""))
+    }
+}
+
+// Refused before filtering, so a huge image ends with a message rather than a wrapped size or a failed allocation
+fn check_size(operation: &str, width: u32, height: u32, filter: cli::FilterOptions) {
+    if let Err(message) = size::check(operation, width, height, filter) {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
+
+// Reads an image for the subcommands, which skip the codecs of the main path
+fn load_image(path: &str, num_threads: usize) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    if synthetic::is_synthetic(path) {
+        synthetic::load(path, num_threads).expect("Failed to generate image")
+    } else {
+        image::open(path).expect("Failed to load image").to_rgba8()
+    }
+}
+
+// With `--roi` only the rectangle and the context its pixels read are filtered
+fn apply_filter(operation: &str, img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, num_threads: usize, filter: cli::FilterOptions, lut: Option<&lut::Lut>) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    match filter.roi {
+        Some(region) => roi::apply(img, region, roi::margin(operation, radius, filter), |window| {
+            apply_operation(operation, window, radius, num_threads, cli::FilterOptions { roi: None, ..filter }, lut)
+        }),
+        None => apply_operation(operation, img, radius, num_threads, filter, lut),
+    }
+}
+
+fn apply_operation(operation: &str, img: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, num_threads: usize, filter: cli::FilterOptions, lut: Option<&lut::Lut>) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    match operation {
+        "blur" => blur::apply_gaussian_blur(img, radius, num_threads, filter),
+        "kuwahara" => kuwahara::apply_kuwahara_filter(img, radius, num_threads, filter),
+        "median" => median::apply_median_filter(img, radius, num_threads, filter),
+        "bilateral" => bilateral::apply_bilateral_filter(img, radius, bilateral::sigma_space(radius, filter), filter.sigma_color, num_threads, filter),
+        "sobel" => sobel::apply_sobel(img, radius, num_threads, filter),
+        "sharpen" => sharpen::apply_unsharp_mask(img, radius, filter.amount, filter.sharpen_threshold, num_threads, filter),
+        "motion-blur" => motion_blur::apply_motion_blur(img, radius as u32, filter.angle, num_threads, filter),
+        "emboss" => convolve::apply_convolution(img, convolve::EMBOSS, radius, num_threads, filter),
+        "edges" => convolve::apply_convolution(img, convolve::LAPLACIAN, radius, num_threads, filter),
+        "convolve" => convolve::apply_convolution(img, filter.kernel.expect("convolve needs a kernel"), radius, num_threads, filter),
+        "nlmeans" => nlmeans::apply_nlmeans(img, radius, num_threads, filter),
+        "dilate" => morphology::apply_morphology(img, morphology::Morphology::Dilate, radius, num_threads, filter),
+        "erode" => morphology::apply_morphology(img, morphology::Morphology::Erode, radius, num_threads, filter),
+        "histeq" => histeq::apply_histogram_equalization(img, num_threads, filter),
+        "autolevel" => autolevel::apply_autolevel(img, num_threads, filter),
+        "oil" => oil::apply_oil_painting(img, radius, num_threads, filter),
+        "pixelate" => pixelate::apply_pixelate(img, radius, num_threads, filter),
+        "dither" => dither::apply_dither(img, radius, num_threads, filter),
+        "quantize" => quantize::apply_quantize(img, radius, num_threads, filter),
+        "bokeh" => bokeh::apply_bokeh(img, radius, num_threads, filter),
+        "vignette" => vignette::apply_vignette(img, radius as f64 / 100.0, num_threads, filter),
+        "posterize" => point::apply_posterize(img, radius as u32, num_threads, filter),
+        "gamma" => point::apply_gamma(img, filter.gamma.expect("gamma needs a value"), num_threads, filter),
+        "transform" => transform::apply_transform(img, num_threads, filter),
+        "resize" => {
+            let (width, height) = filter.size.expect("resize needs a size").target(img.width(), img.height());
+            resize::apply_resize(img, width, height, num_threads, filter)
+        }
+        "lut" => lut::apply_lut(img, lut.expect("lut needs a table"), num_threads, filter),
+        "add-noise" => noise::add_noise(img, filter.noise.expect("add-noise needs a noise"), num_threads, filter),
+        _ => unreachable!("operation is validated before filtering"),
+    }
+}
+
+fn run_animation(operation: &str, input_path: &str, output_path: &str, radius: i32, num_threads: usize, options: &cli::Options) {
+    let start = Instant::now();
+    let mut animation = animation::decode_animation(input_path).expect("Failed to load animation");
+    let load_time = start.elapsed();
+
+    println!("Animation loaded: {}x{} pixels, {} frames", animation.width, animation.height, animation.frames.len());
+    println!("Load time: {}ms", load_time.as_millis());
+
+    let start = Instant::now();
+    animation::filter_frames(&mut animation, options.frames_in_flight, |frame| {
+        apply_filter(operation, frame, radius, num_threads, options.filter, options.lut.as_deref())
+    });
+    let filter_time = start.elapsed();
+    println!("Filter time: {}ms", filter_time.as_millis());
+
+    let start = Instant::now();
+    animation::encode_animation(output_path, &animation).expect("Failed to save animation");
+    let save_time = start.elapsed();
+
+    println!("Save time: {}ms", save_time.as_millis());
+    println!("Total time: {}ms", (load_time + filter_time + save_time).as_millis());
+    report_peak_memory(options);
+}
+
+fn run_video(operation: &str, input_path: &str, output_path: &str, radius: i32, num_threads: usize, options: &cli::Options) {
+    let info = video::probe(input_path).expect("Failed to probe video");
+    println!("Video loaded: {}x{} pixels at {} fps", info.width, info.height, info.frame_rate);
+
+    let start = Instant::now();
+    let frame_count = video::process_video(input_path, output_path, &info, options.frames_in_flight, |frame| {
+        apply_filter(operation, frame, radius, num_threads, options.filter, options.lut.as_deref())
+    }).expect("Failed to process video");
+    let total_time = start.elapsed();
+
+    println!("Frames: {}", frame_count);
+    println!("Throughput: {:.2} fps", frame_count as f64 / total_time.as_secs_f64());
+    println!("Total time: {}ms", total_time.as_millis());
+    report_peak_memory(options);
+}
+
+fn run_stream(operation: &str, input_path: &str, output_path: &str, radius: i32, num_threads: usize, options: &cli::Options) {
+    let start = Instant::now();
+    let stats = stream::process_stream(input_path, output_path, options.band_rows, stream::overlap(operation, radius, options.filter), |band| {
+        apply_filter(operation, band, radius, num_threads, options.filter, options.lut.as_deref())
+    }).expect("Failed to stream image");
+    let total_time = start.elapsed();
+
+    println!("Image streamed: {}x{} pixels in {} bands", stats.width, stats.height, stats.bands);
+    println!("Peak window: {} rows", stats.peak_window_rows);
+    println!("Total time: {}ms", total_time.as_millis());
+    report_peak_memory(options);
+}
+
+// Times repeated filter runs on one decoded image and reports their spread
+fn run_bench(args: &[String], options: &mut cli::Options) {
+    let operation = &args[2];
+    let input_path = &args[3];
+    operation_description(operation, &[]);
+    let radius = parse_operand(operation, Some(&args[4]), options);
+    let num_threads: usize = args.get(5)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+    if options.runs == 0 {
+        eprintln!("--runs must be at least 1");
+        std::process::exit(1);
+    }
+
+    let img = load_image(input_path, num_threads);
+    let (width, height) = img.dimensions();
+    check_size(operation, width, height, options.filter);
+    let worker_counts = if options.sweep.is_empty() { vec![num_threads] } else { options.sweep.clone() };
+    // With --json stdout carries only the JSON result
+    let report = |text: String| if options.json { eprintln!("{}", text) } else { println!("{}", text) };
+    let mut measurements = Vec::new();
+
+    for &workers in &worker_counts {
+        for _ in 0..options.warmup {
+            apply_filter(operation, &img, radius, workers, options.filter, options.lut.as_deref());
+        }
+
+        let meter = start_energy(options);
+        let mut samples = Vec::with_capacity(options.runs);
+        for _ in 0..options.runs {
+            let start = Instant::now();
+            apply_filter(operation, &img, radius, workers, options.filter, options.lut.as_deref());
+            samples.push(start.elapsed().as_secs_f64() * 1000.0);
+        }
+
+        let mut measurement = bench::Measurement::new(workers, samples);
+        measurement.joules = meter.and_then(|meter| meter.joules().ok()).map(|joules| joules / options.runs as f64);
+        report(format!("Bench: {} with radius {} using {} threads, {} runs", operation, radius, workers, options.runs));
+        report(measurement.format());
+        measurements.push(measurement);
+    }
+
+    if measurements.len() > 1 {
+        report(bench::scaling_report(&measurements));
+    }
+
+    let result = bench::BenchReport {
+        backend: bench::BACKEND.to_string(),
+        operation: operation.clone(),
+        radius,
+        width,
+        height,
+        measurements,
+    };
+
+    if let Some(program) = &options.against {
+        let other = match bench::run_backend(program, &bench::against_args(args, &options.option_args)) {
+            Ok(other) => other,
+            Err(e) => {
+                eprintln!("Failed to bench {}: {}", program, e);
+                std::process::exit(1);
+            }
+        };
+        let (threads, tokio) = if result.backend == "threads" { (&result, &other) } else { (&other, &result) };
+        report(bench::overhead_report(threads, tokio));
+    }
+
+    // Compared before saving so one run can check against and then replace the same baseline
+    let mut regressed = false;
+    if let Some(name) = &options.compare_baseline {
+        let baseline = bench::load_baseline(name).expect("Failed to load baseline");
+        let (text, any_regressed) = bench::baseline_report(name, &baseline, &result, options.threshold);
+        report(text);
+        regressed = any_regressed;
+    }
+    if let Some(name) = &options.save_baseline {
+        let path = bench::save_baseline(&result, name).expect("Failed to save baseline");
+        report(format!("Baseline '{}' saved to {}", name, path.display()));
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string(&result).expect("Failed to serialize bench results"));
+    }
+    if regressed {
+        std::process::exit(1);
+    }
+}
+
+// Renders the medians of earlier bench logs as tables for the results section
+fn run_report(paths: &[String], options: &cli::Options) {
+    let mut rows = Vec::new();
+    for path in paths {
+        rows.extend(report::load(path).expect("Failed to read benchmark log"));
+    }
+    match options.report_format {
+        report::ReportFormat::Markdown => println!("{}", report::render_markdown(&rows)),
+        report::ReportFormat::Html => println!("{}", report::render_html(&rows)),
+    }
+}
+
+// Combines aligned exposures into one image, the frames decoded concurrently
+fn run_stack(args: &[String]) {
+    let mode: stack::StackMode = match args[2].parse() {
+        Ok(mode) => mode,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    let (paths, num_threads) = frames_and_workers(&args[4..]);
+    let start = Instant::now();
+    let result = stack::stack(paths.len(), |index| load_frame(&paths[index]), mode, num_threads);
+    save_stack(result, &format!("Stacked {} frames by {:?}", paths.len(), mode), &args[3], start);
+}
+
+// Merges frames focused at different depths into one sharp throughout
+fn run_focus_stack(args: &[String]) {
+    let (paths, num_threads) = frames_and_workers(&args[3..]);
+    let start = Instant::now();
+    let result = stack::focus_stack(paths.len(), |index| load_frame(&paths[index]), num_threads);
+    save_stack(result, &format!("Focus-stacked {} frames", paths.len()), &args[2], start);
+}
+
+// Writes thumbnails of every image in a directory, decoding each source once for all sizes
+fn run_thumbs(args: &[String], options: &cli::Options) {
+    let num_threads: usize = args.get(4)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+    if options.thumb_sizes.is_empty() || options.thumb_sizes.contains(&0) {
+        eprintln!("--sizes must list sizes of at least 1 pixel");
+        std::process::exit(1);
+    }
+
+    let start = Instant::now();
+    let stats = match thumbs::generate(&args[2], &args[3], &options.thumb_sizes, options.sharpen, num_threads) {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let elapsed = start.elapsed();
+    println!("Thumbnails: {} from {} images", stats.thumbnails, stats.sources);
+    println!("Throughput: {:.1} images/s", stats.sources as f64 / elapsed.as_secs_f64());
+    println!("Total time: {}ms", elapsed.as_millis());
+}
+
+// Writes Perlin noise, the `perlin` pattern of `--synthetic`, so it can be looked at or tiled
+fn run_noise(args: &[String]) {
+    let spec: synthetic::SyntheticSpec = match format!("perlin:{}", args[3]).parse() {
+        Ok(spec) => spec,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    let num_threads: usize = args.get(4)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+    check_size("noise", spec.width, spec.height, cli::FilterOptions::default());
+
+    let start = Instant::now();
+    let result = synthetic::generate(&spec, num_threads);
+    println!("Generated {} in {}ms", spec, start.elapsed().as_millis());
+    result.save(&args[2]).expect("Failed to save image");
+}
+
+// Renders the Mandelbrot set, whose rows cost anything from a few iterations a pixel to the full
+// count, to show how unevenly a static split into bands balances
+fn run_mandelbrot(args: &[String], options: &cli::Options) {
+    let (width, height) = match mandelbrot::parse_size(&args[3]) {
+        Ok(size) => size,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    let num_threads: usize = args.get(4)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+    check_size("mandelbrot", width, height, cli::FilterOptions::default());
+    if options.worker_timing.is_some() {
+        timing::enable();
+    }
+
+    let view = options.view;
+    println!("Rendering {}x{} around ({}, {}) at zoom {}, {} iterations, using {} threads", width, height, view.center.0, view.center.1, view.zoom, view.max_iterations, num_threads);
+    let start = Instant::now();
+    let result = tracing::info_span!("render", workers = num_threads).in_scope(|| mandelbrot::render(width, height, view, num_threads));
+    println!("Render time: {}ms", start.elapsed().as_millis());
+    if let Some(format) = options.worker_timing {
+        let records = timing::take();
+        match format {
+            timing::TimingFormat::Table => println!("{}", timing::format_table(&records)),
+            timing::TimingFormat::Json => println!("{}", timing::format_json(&records)),
+        }
+    }
+    result.save(&args[2]).expect("Failed to save image");
+}
+
+// Joins two images along a mask without a visible seam
+fn run_blend(args: &[String], options: &cli::Options) {
+    let (paths, num_threads) = frames_and_workers(&args[2..]);
+    // Without a mask the second image is composited over the first
+    let (a_path, b_path, mask_path, output_path) = match paths {
+        [a, b, output] => (a, b, None, output),
+        [a, b, mask, output] => (a, b, Some(mask), output),
+        _ => {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+    };
+
+    let start = Instant::now();
+    let a = load_image(a_path, num_threads);
+    let b = load_image(b_path, num_threads);
+    // Only the mask's brightness counts
+    let mask = mask_path.map(|path| image::DynamicImage::ImageLuma8(image::open(path).expect("Failed to load mask").to_luma8()).to_rgba8());
+    println!("Images loaded: {}x{} pixels", a.width(), a.height());
+    println!("Load time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    let result = match &mask {
+        Some(mask) => blend::blend(&a, &b, mask, num_threads),
+        None => blend::composite(&a, &b, options.blend_mode, options.opacity, num_threads),
+    };
+    let result = match result {
+        Ok(result) => result,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    if mask.is_some() {
+        println!("Blended over {} levels", blend::levels(a.width(), a.height()));
+    } else {
+        println!("Composited in {:?} mode at opacity {}", options.blend_mode, options.opacity);
+    }
+    println!("Blend time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    result.save(output_path).expect("Failed to save image");
+    println!("Save time: {}ms", start.elapsed().as_millis());
+}
+
+// Stamps a watermark over one image, or over every image in a directory
+fn run_overlay(args: &[String], options: &cli::Options) {
+    let num_threads: usize = args.get(5)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+    let stamp = image::open(&args[3]).expect("Failed to load stamp").to_rgba8();
+
+    let start = Instant::now();
+    if std::path::Path::new(&args[2]).is_dir() {
+        let stats = match overlay::stamp_dir(&args[2], &args[4], &stamp, options.placement, options.opacity, num_threads) {
+            Ok(stats) => stats,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        let elapsed = start.elapsed();
+        println!("Stamped {} images", stats.images);
+        println!("Throughput: {:.1} images/s", stats.images as f64 / elapsed.as_secs_f64());
+        println!("Total time: {}ms", elapsed.as_millis());
+        return;
+    }
+
+    let img = load_image(&args[2], num_threads);
+    println!("Image loaded: {}x{} pixels", img.width(), img.height());
+    println!("Load time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    let result = overlay::apply_overlay(&img, &stamp, options.placement, options.opacity, num_threads);
+    let rows = overlay::affected_rows(img.dimensions(), stamp.dimensions(), options.placement);
+    println!("Stamped {} rows", rows.len());
+    println!("Stamp time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    result.save(&args[4]).expect("Failed to save image");
+    println!("Save time: {}ms", start.elapsed().as_millis());
+}
+
+// Tone maps a linear HDR image to 8-bit sRGB
+fn run_tonemap(args: &[String], options: &cli::Options) {
+    let num_threads: usize = args.get(4)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+
+    let start = Instant::now();
+    let img = tonemap::open(&args[2]).expect("Failed to load image");
+    println!("Image loaded: {}x{} pixels, {:?}", img.width(), img.height(), img.color());
+    println!("Load time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    let result = tonemap::apply_tonemap(&tonemap::linear(&img), options.tone_operator, options.exposure, num_threads);
+    println!("Tone mapped with {:?} at {:+} EV", options.tone_operator, options.exposure);
+    println!("Tone map time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    result.save(&args[3]).expect("Failed to save image");
+    println!("Save time: {}ms", start.elapsed().as_millis());
+}
+
+// Blurs an image by as much as a mask asks for at each pixel
+fn run_varblur(args: &[String], options: &cli::Options) {
+    let radius = parse_radius(&args[5]);
+    let num_threads: usize = args.get(6)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+
+    let start = Instant::now();
+    let img = load_image(&args[2], num_threads);
+    // Only the mask's brightness counts
+    let mask = image::DynamicImage::ImageLuma8(image::open(&args[3]).expect("Failed to load mask").to_luma8()).to_rgba8();
+    println!("Image loaded: {}x{} pixels", img.width(), img.height());
+    println!("Load time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    let result = match varblur::apply_variable_blur(&img, &mask, radius, options.blur_levels, num_threads, options.filter) {
+        Ok(result) => result,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    println!("Blurred up to radius {} over {} levels", radius, options.blur_levels);
+    println!("Blur time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    result.save(&args[4]).expect("Failed to save image");
+    println!("Save time: {}ms", start.elapsed().as_millis());
+}
+
+// Writes the Gaussian or Laplacian pyramid of an image, a file per level
+fn run_pyramid(args: &[String], options: &cli::Options) {
+    let kind: image_pyramid::Kind = match args[2].parse() {
+        Ok(kind) => kind,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    let num_threads: usize = args.get(6)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+
+    let start = Instant::now();
+    let img = load_image(&args[3], num_threads);
+    println!("Image loaded: {}x{} pixels", img.width(), img.height());
+    println!("Load time: {}ms", start.elapsed().as_millis());
+    let max_levels = image_pyramid::max_levels(img.width(), img.height());
+    let levels = match args[5].parse::<usize>() {
+        Ok(levels) if (1..=max_levels).contains(&levels) => levels,
+        _ => {
+            eprintln!("levels must be between 1 and {} for a {}x{} image", max_levels, img.width(), img.height());
+            std::process::exit(1);
+        }
+    };
+
+    println!("Writing {} {:?} levels to {} using {} threads{}", levels, kind, args[4], num_threads, if options.pipeline { ", pipelined" } else { "" });
+    let start = Instant::now();
+    let stats = match image_pyramid::write(&img, kind, levels, &args[4], options.pipeline, num_threads) {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    for (level, stats) in stats.iter().enumerate() {
+        println!("Level {}: {}x{}, computed in {}ms, written in {}ms", level, stats.width, stats.height, stats.compute.as_millis(), stats.write.as_millis());
+    }
+    println!("Total time: {}ms", start.elapsed().as_millis());
+}
+
+// Frame paths, then the thread count if the last argument is a number, as for the other operations
+fn frames_and_workers(args: &[String]) -> (&[String], usize) {
+    match args.split_last() {
+        Some((last, frames)) if !frames.is_empty() && last.parse::<usize>().is_ok() => (frames, last.parse().unwrap_or(4)),
+        _ => (args, 4),
+    }
+}
+
+fn load_frame(path: &str) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, String> {
+    image::open(path).map(|img| img.to_rgba8()).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn save_stack(result: Result<ImageBuffer<Rgba<u8>, Vec<u8>>, String>, description: &str, output_path: &str, start: Instant) {
+    let result = match result {
+        Ok(result) => result,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    println!("{}: {}x{} pixels", description, result.width(), result.height());
+    println!("Stack time: {}ms", start.elapsed().as_millis());
+
+    let start = Instant::now();
+    result.save(output_path).expect("Failed to save image");
+    println!("Save time: {}ms", start.elapsed().as_millis());
+}
+
+// Renders the same operation through two backends and fails if their outputs diverge
+fn run_verify(args: &[String], options: &mut cli::Options) {
+    let operation = &args[2];
+    let input_path = &args[3];
+    let backends = [verify::Backend::parse(&args[4]), verify::Backend::parse(&args[5])];
+    operation_description(operation, &[]);
+    let radius = parse_operand(operation, Some(&args[6]), options);
+    let num_threads: usize = args.get(7)
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+
+    let mut outputs = Vec::new();
+    for (index, backend) in backends.iter().enumerate() {
+        println!("Backend {}: {}", index + 1, backend.describe());
+        let output = match backend {
+            verify::Backend::InProcess => {
+                let img = load_image(input_path, num_threads);
+                apply_filter(operation, &img, radius, num_threads, options.filter, options.lut.as_deref())
+            }
+            verify::Backend::Reference(path) => image::open(path).expect("Failed to load reference image").to_rgba8(),
+            verify::Backend::Command(program) => verify::run_command(program, operation, input_path, &args[6], num_threads, index)
+                .expect("Failed to run backend"),
+        };
+        outputs.push(output);
+    }
+
+    let comparison = match verify::compare(&outputs[0], &outputs[1], options.tolerance) {
+        Ok(comparison) => comparison,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    report_comparison(options, &comparison);
+    if comparison.mismatches > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn report_comparison(options: &cli::Options, comparison: &verify::Comparison) {
+    let [r, g, b, a] = comparison.max_diff;
+    status!(options, "Max difference: R {} G {} B {} A {}", r, g, b, a);
+    status!(options, "Mismatched pixels: {} (tolerance {})", comparison.mismatches, options.tolerance);
+    for (x, y) in &comparison.first_mismatches {
+        status!(options, "  mismatch at ({}, {})", x, y);
+    }
+    if comparison.mismatches > comparison.first_mismatches.len() {
+        status!(options, "  ... and {} more", comparison.mismatches - comparison.first_mismatches.len());
+    }
+}
+
+fn run_magick(args: &[String], options: &cli::Options) {
+    let command = match magick::parse(args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    let num_threads = command.threads.unwrap_or(4);
+
+    let start = Instant::now();
+    let mut img = load_image(&command.input, num_threads);
+    println!("Image loaded: {}x{} pixels", img.width(), img.height());
+    println!("Load time: {}ms", start.elapsed().as_millis());
+
+    for step in &command.steps {
+        let start = Instant::now();
+        img = match step {
+            magick::Step::Filter { operation, radius, sigma } => {
+                let filter = cli::FilterOptions { sigma_x: sigma.or(options.filter.sigma_x), sigma_y: sigma.or(options.filter.sigma_y), ..options.filter };
+                let result = apply_filter(operation, &img, *radius, num_threads, filter, options.lut.as_deref());
+                println!("{} radius {}: {}ms", operation, radius, start.elapsed().as_millis());
+                result
+            }
+            magick::Step::Resize(geometry) => {
+                let (width, height) = geometry.target(img.width(), img.height());
+                let result = resize::apply_resize(&img, width, height, num_threads, options.filter);
+                println!("resize to {}x{}: {}ms", width, height, start.elapsed().as_millis());
+                result
+            }
+        };
+    }
+
+    let start = Instant::now();
+    img.save(&command.output).expect("Failed to save image");
+    println!("Save time: {}ms", start.elapsed().as_millis());
+}
+
+// Phase and worker spans are printed to stderr as they close when RUST_LOG enables them,
+// e.g. RUST_LOG=info for phases or RUST_LOG=debug to include every worker
+fn init_tracing() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .init();
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    init_tracing();
+    let (args, mut options) = match cli::parse(env::args().collect()) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    if args.get(1).map(String::as_str) == Some("bench") {
+        if args.len() < 5 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_bench(&args, &mut options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("stack") {
+        if args.len() < 5 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_stack(&args);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("blend") {
+        if args.len() < 5 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_blend(&args, &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("overlay") {
+        if args.len() < 5 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_overlay(&args, &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("focus-stack") {
+        if args.len() < 4 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_focus_stack(&args);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("thumbs") {
+        if args.len() < 4 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_thumbs(&args, &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("noise") {
+        if args.len() < 4 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_noise(&args);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("mandelbrot") {
+        if args.len() < 4 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_mandelbrot(&args, &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("tonemap") {
+        if args.len() < 4 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_tonemap(&args, &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("varblur") {
+        if args.len() < 6 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_varblur(&args, &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("pyramid") {
+        if args.len() < 6 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_pyramid(&args, &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("report") {
+        if args.len() < 3 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_report(&args[2..], &options);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("verify") {
+        if args.len() < 7 {
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        run_verify(&args, &mut options);
+        return;
+    }
+
+    if magick::is_magick_style(&args[1..]) {
+        run_magick(&args[1..], &options);
+        return;
+    }
 
-    if args.len() < 5 {
+    // The kernel file is enough for convolve, its blur radius is optional, and histeq, autolevel and transform have no radius.
+    // A blur given a sigma derives its radius from it.
+    let min_args = if args.get(1).is_some_and(|operation| sigma_blur(operation, options.filter)) || matches!(args.get(1).map(String::as_str), Some("convolve" | "histeq" | "autolevel" | "transform")) { 4 } else { 5 };
+    if args.len() < min_args {
         print_usage(&args[0]);
         std::process::exit(1);
     }
@@ -23,7 +1013,7 @@ fn main() {
     let operation = &args[1];
     let input_path = &args[2];
     let output_path = &args[3];
-    let radius: i32 = args[4].parse().expect("Invalid radius");
+    let radius = parse_operand(operation, args.get(4).map(String::as_str), &mut options);
     let num_threads: usize = args.get(5)
         .and_then(|s| s.parse().ok())
         .unwrap_or(4);
@@ -38,36 +1028,254 @@ fn main() {
         return;
     }
 
+    let description = operation_description(operation, &["monte_carlo"]);
+
+    let data_uri_output = options.output_format == cli::OutputFormat::DataUri;
+    let single_image_only = data_uri_output || options.from_clipboard || options.to_clipboard || options.pyramid.is_some() || options.polygon.is_some() || options.verify;
+    if single_image_only && (options.video || options.stream || animation::is_animation(input_path)) {
+        eprintln!("--output-format datauri, --pyramid, --polygon, --verify and the clipboard options only support single images");
+        std::process::exit(1);
+    }
+    // A band's or a window's sides would wrap to each other rather than to the far side of the image
+    if options.filter.edge == edge::Edge::Wrap && (options.stream || options.filter.roi.is_some()) {
+        eprintln!("--edge wrap reads the far side of the image and supports neither --stream nor --roi");
+        std::process::exit(1);
+    }
+    // The rectangle is placed on the whole image, not on each band
+    if options.filter.roi.is_some() && options.stream {
+        eprintln!("--roi filters a rectangle of the whole image and does not support --stream");
+        std::process::exit(1);
+    }
+    // The window would be equalized, quantized or laid out on its own rather than as part of the image
+    if options.filter.roi.is_some() && matches!(operation.as_str(), "add-noise" | "histeq" | "autolevel" | "pixelate" | "dither" | "quantize" | "vignette" | "transform") {
+        eprintln!("{} works on the whole image and does not support --roi", operation);
+        std::process::exit(1);
+    }
+    // Bands would each draw the noise of the image's first rows
+    if operation == "add-noise" && options.stream {
+        eprintln!("add-noise draws its noise by pixel position and does not support --stream");
+        std::process::exit(1);
+    }
+    // Bands would each be equalized by their own histogram
+    if operation == "histeq" && options.stream {
+        eprintln!("histeq equalizes the histogram of the whole image and does not support --stream");
+        std::process::exit(1);
+    }
+    // Bands would each be stretched to their own range
+    if operation == "autolevel" && options.stream {
+        eprintln!("autolevel stretches the range of the whole image and does not support --stream");
+        std::process::exit(1);
+    }
+    // Blocks would restart at the top of every band
+    if operation == "pixelate" && options.stream {
+        eprintln!("pixelate lays its blocks over the whole image and does not support --stream");
+        std::process::exit(1);
+    }
+    // Bands would each start without the error diffused from the rows above
+    if operation == "dither" && options.stream {
+        eprintln!("dither carries its error down the whole image and does not support --stream");
+        std::process::exit(1);
+    }
+    if operation == "dither" && !(2..=256).contains(&radius) {
+        eprintln!("dither needs between 2 and 256 levels per channel");
+        std::process::exit(1);
+    }
+    // Bands would each find a palette of their own
+    if operation == "quantize" && options.stream {
+        eprintln!("quantize finds one palette for the whole image and does not support --stream");
+        std::process::exit(1);
+    }
+    if operation == "quantize" && !(2..=256).contains(&radius) {
+        eprintln!("quantize needs between 2 and 256 colors");
+        std::process::exit(1);
+    }
+    // Bands would each be darkened around their own center
+    if operation == "vignette" && options.stream {
+        eprintln!("vignette darkens by the distance from the image's center and does not support --stream");
+        std::process::exit(1);
+    }
+    if operation == "vignette" && radius > 100 {
+        eprintln!("vignette takes a strength between 0 and 100 percent");
+        std::process::exit(1);
+    }
+    // Output rows gather from anywhere in the source, not from the rows around them
+    if operation == "transform" && options.stream {
+        eprintln!("transform samples the whole image and does not support --stream");
+        std::process::exit(1);
+    }
+    if operation == "posterize" && !(2..=256).contains(&radius) {
+        eprintln!("posterize needs between 2 and 256 levels per channel");
+        std::process::exit(1);
+    }
+    // Bands, frames, the polygon's mask and the --roi rectangle all assume the output is the input's size
+    if operation == "resize" && (options.video || options.stream || options.polygon.is_some() || options.filter.roi.is_some() || animation::is_animation(input_path)) {
+        eprintln!("resize changes the image size and supports neither --video, --stream, --polygon, --roi nor animations");
+        std::process::exit(1);
+    }
+
+    if options.video {
+        println!("Applying {} with radius {} using {} threads per frame", description, radius, num_threads);
+        run_video(operation, input_path, output_path, radius, num_threads, &options);
+        return;
+    }
+
+    if options.stream {
+        println!("Applying {} with radius {} using {} threads per band", description, radius, num_threads);
+        run_stream(operation, input_path, output_path, radius, num_threads, &options);
+        return;
+    }
+
+    if animation::is_animation(input_path) {
+        println!("Applying {} with radius {} using {} threads per frame", description, radius, num_threads);
+        run_animation(operation, input_path, output_path, radius, num_threads, &options);
+        return;
+    }
+
     let start = Instant::now();
-    let img = image::open(input_path).expect("Failed to load image").to_rgba8();
+    let phase = memory::Phase::start();
+    let decode = tracing::info_span!("decode", path = %input_path).entered();
+    let (mut metadata, img) = match &options.raw_format {
+        Some(spec) => (metadata::Metadata::default(), raw::load(input_path, spec).expect("Failed to load raw frame")),
+        None if options.from_clipboard => (metadata::Metadata::default(), clipboard::read().expect("Failed to read clipboard image")),
+        None if input_path == "-" || data_uri::is_data_uri(input_path) => {
+            let data = if input_path == "-" { data_uri::read_stdin() } else { data_uri::decode(input_path) }
+                .expect("Failed to read encoded input");
+            (metadata::read_from_memory(&data), image::load_from_memory(&data).expect("Failed to load image").to_rgba8())
+        }
+        // Filters work on 8-bit RGBA, so the 16-bit scan is reduced once it is loaded
+        None if dicom::is_dicom(input_path) => {
+            let scan = dicom::load(input_path).expect("Failed to load DICOM image");
+            (metadata::Metadata::default(), image::DynamicImage::ImageLuma16(scan).to_rgba8())
+        }
+        None if qoi_codec::is_qoi(input_path) => (metadata::Metadata::default(), qoi_codec::load(input_path).expect("Failed to load image")),
+        None if pnm::is_pnm(input_path) => (metadata::Metadata::default(), pnm::load(input_path, num_threads).expect("Failed to load image")),
+        None if synthetic::is_synthetic(input_path) => (metadata::Metadata::default(), synthetic::load(input_path, num_threads).expect("Failed to generate image")),
+        None => (metadata::read(input_path), image::open(input_path).expect("Failed to load image").to_rgba8()),
+    };
+    let mut img = metadata::apply_orientation(img, metadata.orientation);
+    if options.to_srgb {
+        metadata::convert_to_srgb(&mut img, &mut metadata).expect("Failed to convert to sRGB");
+    }
+    decode.exit();
     let load_time = start.elapsed();
 
     let (width, height) = img.dimensions();
-    println!("Image loaded: {}x{} pixels", width, height);
-    println!("Load time: {}ms", load_time.as_millis());
+    status!(options, "Image loaded: {}x{} pixels", width, height);
+    check_size(operation, width, height, options.filter);
+    if let Some(size) = options.filter.size {
+        let (width, height) = size.target(width, height);
+        check_size(operation, width, height, options.filter);
+    }
+    status!(options, "Load time: {}ms", load_time.as_millis());
+    report_allocations(&options, "Load", phase.finish());
 
-    let start = Instant::now();
-    let result = match operation.as_str() {
-        "blur" => {
-            println!("Applying Gaussian blur with radius {} using {} threads", radius, num_threads);
-            blur::apply_gaussian_blur(&img, radius, num_threads)
-        },
-        "kuwahara" => {
-            println!("Applying Kuwahara filter with radius {} using {} threads", radius, num_threads);
-            kuwahara::apply_kuwahara_filter(&img, radius, num_threads)
-        },
-        _ => {
-            eprintln!("Unknown operation: {}. Use 'blur', 'kuwahara', or 'monte_carlo'", operation);
-            std::process::exit(1);
+    if options.worker_timing.is_some() {
+        timing::enable();
+    }
+
+    // Warmup runs reuse the decoded image so only the filter itself is repeated
+    if options.warmup > 0 {
+        let start = Instant::now();
+        for _ in 0..options.warmup {
+            apply_filter(operation, &img, radius, num_threads, options.filter, options.lut.as_deref());
         }
-    };
+        status!(options, "Warmup: {} runs in {}ms", options.warmup, start.elapsed().as_millis());
+    }
+
+    let start = Instant::now();
+    let phase = memory::Phase::start();
+    let meter = start_energy(&options);
+    let counters = start_counters(&options);
+    let eta = options.eta.then(|| {
+        progress::reset();
+        progress::Reporter::start(progress::INTERVAL, |progress| eprintln!("{}", progress.format()))
+    });
+    status!(options, "Applying {} with radius {} using {} threads", description, radius, num_threads);
+    let mut result = tracing::info_span!("filter", operation = %operation, radius, workers = num_threads)
+        .in_scope(|| apply_filter(operation, &img, radius, num_threads, options.filter, options.lut.as_deref()));
+    let mask = options.polygon.as_ref().map(|polygon| {
+        tracing::info_span!("mask").in_scope(|| {
+            let mask = polygon.rasterize(width, height, num_threads);
+            mask::blend(&img, &mut result, &mask, num_threads);
+            mask
+        })
+    });
     let filter_time = start.elapsed();
-    println!("Filter time: {}ms", filter_time.as_millis());
+    if let Some(eta) = eta {
+        eta.stop();
+    }
+    status!(options, "Filter time: {}ms", filter_time.as_millis());
+    report_counters(&options, counters);
+    match meter.map(|meter| meter.joules()) {
+        Some(Ok(joules)) => status!(options, "{}", energy::format(joules, width, height)),
+        Some(Err(e)) => eprintln!("Warning: {}", e),
+        None => {}
+    }
+    report_allocations(&options, "Filter", phase.finish());
+    if let Some(format) = options.worker_timing {
+        // Warmup runs recorded timings too, only the last run is reported
+        let records: Vec<_> = timing::take().into_iter().filter(|record| record.start >= start).collect();
+        match format {
+            timing::TimingFormat::Table => status!(options, "{}", timing::format_table(&records)),
+            timing::TimingFormat::Json => status!(options, "{}", timing::format_json(&records)),
+        }
+    }
 
     let start = Instant::now();
-    result.save(output_path).expect("Failed to save image");
+    let phase = memory::Phase::start();
+    let encode = tracing::info_span!("encode", path = %output_path).entered();
+    if let Some(layout) = options.pyramid {
+        let tile_size = options.tile_size.unwrap_or(layout.default_tile_size());
+        let stats = pyramid::write_pyramid(&result, output_path, layout, tile_size, options.tile_overlap, &options.tile_format, num_threads)
+            .expect("Failed to write tile pyramid");
+        status!(options, "Pyramid: {} levels, {} tiles", stats.levels, stats.tiles);
+    } else if options.to_clipboard {
+        clipboard::write(&result).expect("Failed to copy image to clipboard");
+    } else if data_uri_output {
+        let (encoded, format) = data_uri::encode_image(&result, output_path).expect("Failed to encode image");
+        let encoded = metadata::embed_in_memory(encoded, &metadata);
+        println!("{}", data_uri::encode(&encoded, format));
+    } else if qoi_codec::is_qoi(output_path) {
+        qoi_codec::save(output_path, &result).expect("Failed to save image");
+    } else if pnm::is_pnm(output_path) {
+        pnm::save(output_path, &result, num_threads).expect("Failed to save image");
+    } else {
+        // Strip compression only pays off when strips are compressed in parallel
+        if png_encoder::is_png(output_path) && num_threads > 1 {
+            png_encoder::save(output_path, &result, num_threads).expect("Failed to save image");
+        } else {
+            result.save(output_path).expect("Failed to save image");
+        }
+        metadata::embed(output_path, &metadata).expect("Failed to write metadata");
+    }
+    encode.exit();
     let save_time = start.elapsed();
 
-    println!("Save time: {}ms", save_time.as_millis());
-    println!("Total time: {}ms", (load_time + filter_time + save_time).as_millis());
+    status!(options, "Save time: {}ms", save_time.as_millis());
+    report_allocations(&options, "Save", phase.finish());
+    status!(options, "Total time: {}ms", (load_time + filter_time + save_time).as_millis());
+    report_peak_memory(&options);
+
+    // After the timings, which the serial recomputation would otherwise dwarf
+    if options.verify {
+        // Resizing samples the output, which is not the input's size
+        let (out_width, out_height) = (result.width(), result.height());
+        let mut points = verify::sample_points(out_width, out_height);
+        // Outside the polygon or the --roi rectangle the output is the input, which the reference does not model
+        if let Some(mask) = &mask {
+            points.retain(|&(x, y)| mask[y as usize * width as usize + x as usize] == 255);
+        }
+        if let Some(region) = options.filter.roi {
+            points.retain(|&(x, y)| region.contains(x, y));
+        }
+        let comparison = match &options.lut {
+            Some(lut) => verify::check_lut(&img, &result, lut, options.filter, &points, options.tolerance),
+            None => verify::check_reference(operation, &img, &result, radius, options.filter, &points, options.tolerance),
+        };
+        status!(options, "Verify: {} of {} pixels recomputed with the serial reference", points.len(), out_width as usize * out_height as usize);
+        report_comparison(&options, &comparison);
+        if comparison.mismatches > 0 {
+            std::process::exit(1);
+        }
+    }
 }
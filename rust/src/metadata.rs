@@ -0,0 +1,244 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::tiff::TiffDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{imageops, ImageBuffer, ImageDecoder, ImageFormat, Rgba};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Seek, Write};
+
+const ORIENTATION_TAG: u16 = 0x0112;
+const ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+// Largest ICC payload that fits one APP2 segment next to its marker and sequence bytes
+const ICC_CHUNK_SIZE: usize = u16::MAX as usize - 2 - ICC_MARKER.len() - 2;
+
+#[derive(Default)]
+pub struct Metadata {
+    // Raw EXIF (TIFF structure) as stored in the input file
+    pub exif: Option<Vec<u8>>,
+    // EXIF orientation, 1 means the pixels are already upright
+    pub orientation: u32,
+    // Embedded ICC color profile
+    pub icc: Option<Vec<u8>>,
+}
+
+fn read_icc<R: BufRead + Seek>(reader: &mut R) -> Option<Vec<u8>> {
+    let format = image::io::Reader::new(&mut *reader).with_guessed_format().ok()?.format()?;
+    reader.rewind().ok()?;
+
+    match format {
+        ImageFormat::Png => PngDecoder::new(reader).ok()?.icc_profile(),
+        ImageFormat::Jpeg => JpegDecoder::new(reader).ok()?.icc_profile(),
+        ImageFormat::WebP => WebPDecoder::new(reader).ok()?.icc_profile(),
+        ImageFormat::Tiff => TiffDecoder::new(reader).ok()?.icc_profile(),
+        _ => None,
+    }
+}
+
+fn read_from<R: BufRead + Seek>(mut reader: R) -> Metadata {
+    let mut metadata = Metadata {
+        orientation: 1,
+        icc: read_icc(&mut reader),
+        ..Metadata::default()
+    };
+
+    if reader.rewind().is_err() {
+        return metadata;
+    }
+
+    if let Ok(exif) = exif::Reader::new().read_from_container(&mut reader) {
+        metadata.orientation = exif
+            .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+            .unwrap_or(1);
+        metadata.exif = Some(exif.buf().to_vec());
+    }
+
+    metadata
+}
+
+pub fn read(path: &str) -> Metadata {
+    match File::open(path) {
+        Ok(file) => read_from(BufReader::new(file)),
+        Err(_) => Metadata {
+            orientation: 1,
+            ..Metadata::default()
+        },
+    }
+}
+
+pub fn read_from_memory(data: &[u8]) -> Metadata {
+    read_from(Cursor::new(data))
+}
+
+// Converts the pixels from the embedded profile to sRGB; the output is then left untagged
+pub fn convert_to_srgb(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, metadata: &mut Metadata) -> Result<(), String> {
+    let Some(icc) = &metadata.icc else {
+        return Ok(());
+    };
+
+    let input = qcms::Profile::new_from_slice(icc, false).ok_or("Unsupported ICC profile")?;
+    let mut output = qcms::Profile::new_sRGB();
+    output.precache_output_transform();
+    let transform = qcms::Transform::new(&input, &output, qcms::DataType::RGBA8, qcms::Intent::Perceptual)
+        .ok_or("Cannot build ICC transform to sRGB")?;
+
+    transform.apply(img);
+    metadata.icc = None;
+    Ok(())
+}
+
+// Rotates/flips the decoded pixels so they display upright without the orientation tag
+pub fn apply_orientation(img: ImageBuffer<Rgba<u8>, Vec<u8>>, orientation: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    match orientation {
+        2 => imageops::flip_horizontal(&img),
+        3 => imageops::rotate180(&img),
+        4 => imageops::flip_vertical(&img),
+        5 => imageops::flip_horizontal(&imageops::rotate90(&img)),
+        6 => imageops::rotate90(&img),
+        7 => imageops::flip_horizontal(&imageops::rotate270(&img)),
+        8 => imageops::rotate270(&img),
+        _ => img,
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let bytes = [*data.get(offset)?, *data.get(offset + 1)?];
+    Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+}
+
+fn read_u32(data: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let bytes: [u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+}
+
+// Rewrites the orientation entry of IFD0 to 1 once the rotation is baked into the pixels
+fn reset_orientation(tiff: &mut [u8]) -> Option<()> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let ifd = read_u32(tiff, 4, little_endian)? as usize;
+    let entries = read_u16(tiff, ifd, little_endian)? as usize;
+
+    for i in 0..entries {
+        let entry = ifd + 2 + i * 12;
+        if read_u16(tiff, entry, little_endian)? == ORIENTATION_TAG {
+            let value = if little_endian { 1u16.to_le_bytes() } else { 1u16.to_be_bytes() };
+            tiff.get_mut(entry + 8..entry + 10)?.copy_from_slice(&value);
+            return Some(());
+        }
+    }
+
+    None
+}
+
+pub fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    chunk.extend_from_slice(&hasher.finalize().to_be_bytes());
+    chunk
+}
+
+fn jpeg_segment(marker: u8, payload: &[&[u8]]) -> Vec<u8> {
+    let len: usize = payload.iter().map(|part| part.len()).sum();
+    let mut segment = vec![0xFF, marker];
+    segment.extend_from_slice(&((len + 2) as u16).to_be_bytes());
+    for part in payload {
+        segment.extend_from_slice(part);
+    }
+    segment
+}
+
+fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn insert_metadata(encoded: &[u8], exif: Option<&[u8]>, icc: Option<&[u8]>) -> Option<Vec<u8>> {
+    let mut blocks = Vec::new();
+    let insert_at;
+
+    if encoded.starts_with(&[0xFF, 0xD8]) {
+        // JPEG: APP1/APP2 segments after SOI and the JFIF APP0 segment, if any
+        insert_at = if encoded.get(2..4)? == [0xFF, 0xE0] {
+            4 + read_u16(encoded, 4, false)? as usize
+        } else {
+            2
+        };
+
+        if let Some(tiff) = exif {
+            if tiff.len() + 8 > u16::MAX as usize {
+                return None;
+            }
+            blocks.push(jpeg_segment(0xE1, &[b"Exif\0\0", tiff]));
+        }
+        if let Some(icc) = icc {
+            let count = icc.len().div_ceil(ICC_CHUNK_SIZE);
+            for (i, chunk) in icc.chunks(ICC_CHUNK_SIZE).enumerate() {
+                blocks.push(jpeg_segment(0xE2, &[ICC_MARKER, &[i as u8 + 1, count as u8], chunk]));
+            }
+        }
+    } else if encoded.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // PNG: ancillary chunks right after IHDR (signature + 25-byte IHDR chunk)
+        insert_at = 8 + 25;
+
+        if let Some(tiff) = exif {
+            blocks.push(png_chunk(b"eXIf", tiff));
+        }
+        if let Some(icc) = icc {
+            let mut data = b"ICC Profile\0\0".to_vec();
+            data.extend_from_slice(&compress(icc).ok()?);
+            blocks.push(png_chunk(b"iCCP", &data));
+        }
+    } else {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(encoded.len() + blocks.iter().map(Vec::len).sum::<usize>());
+    out.extend_from_slice(encoded.get(..insert_at)?);
+    for block in blocks {
+        out.extend_from_slice(&block);
+    }
+    out.extend_from_slice(&encoded[insert_at..]);
+    Some(out)
+}
+
+// Copies the input EXIF block and ICC profile into an encoded JPEG or PNG image
+pub fn embed_in_memory(encoded: Vec<u8>, metadata: &Metadata) -> Vec<u8> {
+    if metadata.exif.is_none() && metadata.icc.is_none() {
+        return encoded;
+    }
+
+    let mut tiff = metadata.exif.clone();
+    if let Some(tiff) = tiff.as_mut().filter(|_| metadata.orientation != 1) {
+        reset_orientation(tiff);
+    }
+
+    match insert_metadata(&encoded, tiff.as_deref(), metadata.icc.as_deref()) {
+        Some(with_metadata) => with_metadata,
+        None => {
+            eprintln!("Warning: EXIF and ICC metadata are only preserved for JPEG and PNG outputs");
+            encoded
+        }
+    }
+}
+
+// Same as `embed_in_memory` for an already saved output file
+pub fn embed(path: &str, metadata: &Metadata) -> io::Result<()> {
+    if metadata.exif.is_none() && metadata.icc.is_none() {
+        return Ok(());
+    }
+
+    let encoded = fs::read(path)?;
+    fs::write(path, embed_in_memory(encoded, metadata))
+}
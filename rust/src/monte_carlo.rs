@@ -1,47 +1,66 @@
+use crate::bands;
+use std::ops::Range;
 use std::thread;
 
+const LCG_MUL: u32 = 1664525;
+const LCG_ADD: u32 = 1013904223;
+pub const SEED: u32 = 12345;
+
 // Linear Congruential Generator - same formula across all languages
 fn lcg_random(seed: &mut u32) -> f64 {
-    *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+    *seed = seed.wrapping_mul(LCG_MUL).wrapping_add(LCG_ADD);
     (*seed & 0x7FFFFFFF) as f64 / 0x7FFFFFFF as f64
 }
 
-pub fn monte_carlo_operation(total_samples: usize, num_workers: usize) {
-    let samples_per_worker = total_samples / num_workers;
-    let remainder = total_samples % num_workers;
-    
+// The state `steps` draws after `seed` in O(log steps), composing the step `a * x + c` with itself
+// by repeated squaring as in fast exponentiation
+fn lcg_skip(seed: u32, mut steps: u64) -> u32 {
+    let (mut mul, mut add) = (1u32, 0u32);
+    let (mut step_mul, mut step_add) = (LCG_MUL, LCG_ADD);
+    while steps > 0 {
+        if steps & 1 == 1 {
+            mul = mul.wrapping_mul(step_mul);
+            add = add.wrapping_mul(step_mul).wrapping_add(step_add);
+        }
+        step_add = step_mul.wrapping_add(1).wrapping_mul(step_add);
+        step_mul = step_mul.wrapping_mul(step_mul);
+        steps >>= 1;
+    }
+    seed.wrapping_mul(mul).wrapping_add(add)
+}
+
+// Points of `samples` inside the quarter circle. Sample i takes draws 2i and 2i + 1 of the one
+// stream started at `seed`, so the split into workers never changes which points are drawn.
+pub fn count_inside(seed: u32, samples: Range<usize>) -> usize {
+    let mut state = lcg_skip(seed, samples.start as u64 * 2);
+    let mut inside = 0;
+    for _ in samples {
+        let x = lcg_random(&mut state);
+        let y = lcg_random(&mut state);
+        if x * x + y * y <= 1.0 {
+            inside += 1;
+        }
+    }
+    inside
+}
+
+// Points inside the circle out of `total_samples`, the same for any number of workers
+pub fn sample_inside(total_samples: usize, num_workers: usize) -> usize {
     let mut handles = vec![];
     
-    for worker_id in 0..num_workers {
-        let samples = if worker_id == num_workers - 1 {
-            samples_per_worker + remainder
-        } else {
-            samples_per_worker
-        };
-        
-        let handle = thread::spawn(move || {
-            let mut seed = (12345 + worker_id * 67890) as u32; // Consistent seed pattern
-            let mut inside = 0;
-            
-            for _ in 0..samples {
-                let x = lcg_random(&mut seed);
-                let y = lcg_random(&mut seed);
-                if x * x + y * y <= 1.0 {
-                    inside += 1;
-                }
-            }
-            
-            inside
-        });
-        
-        handles.push(handle);
+    for range in bands::split(total_samples, num_workers) {
+        handles.push(thread::spawn(move || count_inside(SEED, range)));
     }
     
     let mut total_inside = 0;
     for handle in handles {
         total_inside += handle.join().unwrap();
     }
-    
+    total_inside
+}
+
+pub fn monte_carlo_operation(total_samples: usize, num_workers: usize) {
+    let total_inside = sample_inside(total_samples, num_workers);
     let pi_estimate = 4.0 * total_inside as f64 / total_samples as f64;
     
     println!("Monte Carlo Pi Estimation");
@@ -0,0 +1,123 @@
+use crate::bands;
+use crate::blur::ImageData;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::timing::WorkerClock;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+// Dilation keeps the brightest value under the structuring element, erosion the darkest
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Morphology {
+    Dilate,
+    Erode,
+}
+
+impl Morphology {
+    pub fn extremum(self, values: impl Iterator<Item = u8>) -> u8 {
+        match self {
+            Morphology::Dilate => values.max().unwrap_or(0),
+            Morphology::Erode => values.min().unwrap_or(255),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Morphology::Dilate => "dilate",
+            Morphology::Erode => "erode",
+        }
+    }
+}
+
+// Extremum of every channel over the 2r+1 pixels around each pixel of a row, edges repeated
+pub fn horizontal_extremum(src: &ImageData, dst: Arc<Mutex<ImageData>>, op: Morphology, radius: usize, rows: Range<usize>, clock: &mut WorkerClock) {
+    let mut local_rows = Vec::new();
+    let row_len = src.width * src.channels;
+
+    for y in rows {
+        let row = &src.data[y * row_len..(y + 1) * row_len];
+        let mut row_data = vec![0u8; row_len];
+        for x in 0..src.width {
+            let window = x.saturating_sub(radius)..(x + radius + 1).min(src.width);
+            for ch in 0..src.channels {
+                row_data[x * src.channels + ch] = op.extremum(window.clone().map(|sx| row[sx * src.channels + ch]));
+            }
+        }
+        local_rows.push((y, row_data));
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst = dst.lock().unwrap();
+    for (y, row_data) in local_rows {
+        dst.data[y * row_len..(y + 1) * row_len].copy_from_slice(&row_data);
+    }
+}
+
+// One pass of `horizontal_extremum` over every row, split into bands
+fn extremum_pass(src: ImageData, op: Morphology, radius: usize, num_threads: usize, direction: &'static str, clock_name: &'static str) -> ImageData {
+    let dst = Arc::new(Mutex::new(ImageData {
+        data: vec![0; src.data.len()],
+        width: src.width,
+        height: src.height,
+        channels: src.channels,
+    }));
+    let bands = bands::split(src.height, num_threads);
+    let src_arc = Arc::new(src);
+
+    let pass = tracing::info_span!("morphology_pass", op = op.name(), direction).entered();
+    let handles: Vec<_> = bands
+        .into_iter()
+        .enumerate()
+        .map(|(thread_id, rows)| {
+            let parent = tracing::Span::current();
+            let src = Arc::clone(&src_arc);
+            let dst = Arc::clone(&dst);
+
+            workers::spawn(move || {
+                let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?rows).entered();
+                let mut clock = WorkerClock::start(clock_name, thread_id, rows.clone());
+                horizontal_extremum(&src, dst, op, radius, rows, &mut clock);
+                clock.finish();
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    pass.exit();
+
+    Arc::try_unwrap(dst).unwrap().into_inner().unwrap()
+}
+
+// Dilation or erosion of every channel by a (2r+1)x(2r+1) square. The square is the extremum of a
+// row of 2r+1 pixels over a column of 2r+1, so like the Gaussian blur it runs as a horizontal pass,
+// a transpose and the same pass again.
+pub fn apply_morphology(
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    op: Morphology,
+    radius: i32,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let radius = usize::try_from(radius).expect("radius must not be negative");
+    if radius == 0 {
+        return img.clone();
+    }
+    let src = ImageData::from_image_buffer(img);
+    // Both passes, the second over the transposed image
+    progress::expect(src.height + src.width);
+
+    let horizontal = extremum_pass(src, op, radius, num_threads, "horizontal", "morph-h");
+    let transposed = tracing::info_span!("transpose").in_scope(|| horizontal.transpose());
+    let vertical = extremum_pass(transposed, op, radius, num_threads, "vertical", "morph-v");
+    let final_result = tracing::info_span!("transpose").in_scope(|| vertical.transpose());
+
+    let mut result = final_result.to_image_buffer();
+    tracing::info_span!("channels").in_scope(|| channels::restore(img, &mut result, filter.channels, num_threads));
+    result
+}
@@ -0,0 +1,61 @@
+// Node.js addon, built with `cargo rustc --release --lib --features node --crate-type cdylib`
+// and loaded after copying the library to `rust_filter.node` (see the README).
+// Each call filters on its own worker threads and settles a promise when done,
+// so the libuv thread pool and the JavaScript thread stay free.
+
+use crate::blur;
+use crate::cli::FilterOptions;
+use crate::kuwahara;
+use crate::size;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use napi::bindgen_prelude::Buffer;
+use napi::{Env, Error, JsObject, Result, Status};
+use napi_derive::napi;
+use std::panic::{self, AssertUnwindSafe};
+
+type Filter = fn(&ImageBuffer<Rgba<u8>, Vec<u8>>, i32, usize, FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+fn filter_for(operation: &str) -> Filter {
+    match operation {
+        "kuwahara" => kuwahara::apply_kuwahara_filter,
+        _ => blur::apply_gaussian_blur,
+    }
+}
+
+fn filter_buffer(env: Env, pixels: Buffer, width: u32, height: u32, radius: i32, threads: Option<u32>, operation: &str) -> Result<JsObject> {
+    let threads = match threads {
+        Some(threads) => threads as usize,
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    if width == 0 || height == 0 || threads == 0 || radius < 0 {
+        return Err(Error::new(Status::InvalidArg, "width, height and threads must be positive and radius not negative"));
+    }
+    size::check(operation, width, height, FilterOptions::default()).map_err(|message| Error::new(Status::InvalidArg, message))?;
+    // Copied so the JavaScript side may reuse its Buffer while the filter runs
+    let img = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, pixels.to_vec())
+        .ok_or_else(|| Error::new(Status::InvalidArg, "Buffer must hold width * height RGBA pixels"))?;
+
+    let (deferred, promise) = env.create_deferred()?;
+    let filter = filter_for(operation);
+    workers::spawn(move || {
+        // Unwinding into Node would abort the process
+        match panic::catch_unwind(AssertUnwindSafe(|| filter(&img, radius, threads, FilterOptions::default()))) {
+            Ok(result) => deferred.resolve(move |_| Ok(Buffer::from(result.into_raw()))),
+            Err(_) => deferred.reject(Error::new(Status::GenericFailure, "filter panicked")),
+        }
+    });
+    Ok(promise)
+}
+
+/// Gaussian blur of `width * height` RGBA pixels into a new Buffer.
+#[napi(ts_return_type = "Promise<Buffer>")]
+pub fn blur(env: Env, pixels: Buffer, width: u32, height: u32, radius: i32, threads: Option<u32>) -> Result<JsObject> {
+    filter_buffer(env, pixels, width, height, radius, threads, "blur")
+}
+
+/// Kuwahara filter of `width * height` RGBA pixels into a new Buffer.
+#[napi(ts_return_type = "Promise<Buffer>")]
+pub fn kuwahara(env: Env, pixels: Buffer, width: u32, height: u32, radius: i32, threads: Option<u32>) -> Result<JsObject> {
+    filter_buffer(env, pixels, width, height, radius, threads, "kuwahara")
+}
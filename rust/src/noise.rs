@@ -0,0 +1,90 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::str::FromStr;
+
+// Noise the `add-noise` operation puts on an image, to make controlled inputs for denoisers:
+// `gaussian:15` adds normal noise with a standard deviation of 15 levels to every color channel,
+// `saltpepper:0.02` turns 2% of the pixels black or white.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Noise {
+    Gaussian(f64),
+    SaltPepper(f64),
+}
+
+impl FromStr for Noise {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, amount) = s.split_once(':').ok_or("Expected gaussian:<sigma> or saltpepper:<amount>")?;
+        let value: f64 = amount.parse().map_err(|_| format!("Invalid noise amount: {}", amount))?;
+        match kind {
+            "gaussian" if value.is_finite() && value >= 0.0 => Ok(Noise::Gaussian(value)),
+            "gaussian" => Err(format!("Gaussian sigma must not be negative: {}", amount)),
+            "saltpepper" if (0.0..=1.0).contains(&value) => Ok(Noise::SaltPepper(value)),
+            "saltpepper" => Err(format!("Salt and pepper amount must be between 0 and 1: {}", amount)),
+            other => Err(format!("Unknown noise: {}", other)),
+        }
+    }
+}
+
+// SplitMix64 finalizer
+fn mix(mut h: u64) -> u64 {
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+// Random bits for one channel of one pixel, so every band draws the same noise whichever thread
+// takes it and however the rows are split
+fn draw(seed: u64, x: u32, y: u32, channel: u64) -> u64 {
+    mix(seed.wrapping_add(mix(((y as u64) << 32) | x as u64)).wrapping_add(channel))
+}
+
+impl Noise {
+    pub fn pixel(&self, seed: u64, x: u32, y: u32, src: [u8; 4]) -> [u8; 4] {
+        let mut out = src;
+        match *self {
+            Noise::Gaussian(sigma) => {
+                for (channel, value) in out[..3].iter_mut().enumerate() {
+                    // Box-Muller from the two halves of the bits; u1 is in (0, 1] so its log is finite
+                    let bits = draw(seed, x, y, channel as u64);
+                    let u1 = ((bits >> 32) + 1) as f64 / (1u64 << 32) as f64;
+                    let u2 = (bits as u32) as f64 / (1u64 << 32) as f64;
+                    let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                    *value = (*value as f64 + sigma * z).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+            Noise::SaltPepper(amount) => {
+                let bits = draw(seed, x, y, 0);
+                if ((bits >> 11) as f64 / (1u64 << 53) as f64) < amount {
+                    let value = if bits & 1 == 0 { 0 } else { 255 };
+                    out[..3].fill(value);
+                }
+            }
+        }
+        out
+    }
+}
+
+// Adds the noise to every pixel, one band of rows per thread; alpha is kept
+pub fn add_noise(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, noise: Noise, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let mut result = img.clone();
+    let width = img.width() as usize;
+    progress::expect(img.height() as usize);
+    workers::scope_each(bands::split_mut(&mut result, width * 4, num_threads), |(rows, band)| {
+        for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (i % width, rows.start + i / width);
+            let noisy = noise.pixel(filter.seed, x as u32, y as u32, [pixel[0], pixel[1], pixel[2], pixel[3]]);
+            pixel.copy_from_slice(&noisy);
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(img, &mut result, filter.channels, num_threads);
+    result
+}
@@ -0,0 +1,151 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::timing::WorkerClock;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+// Intensity buckets when `--levels` is not given
+pub const DEFAULT_LEVELS: u32 = 20;
+
+// Bucket of a pixel's intensity, the mean of its color channels, out of `levels`
+pub fn bucket(pixel: &[u8], levels: u32) -> usize {
+    let intensity = (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3;
+    (intensity * levels / 256) as usize
+}
+
+// Mean color of the most common bucket, the darker one on a tie, with the given alpha
+pub fn dominant(counts: &[u32], sums: &[[u64; 3]], alpha: u8) -> Rgba<u8> {
+    let mut best = 0;
+    for (index, &count) in counts.iter().enumerate() {
+        if count > counts[best] {
+            best = index;
+        }
+    }
+    let count = counts[best].max(1) as u64;
+    let [r, g, b] = sums[best].map(|sum| ((sum + count / 2) / count) as u8);
+    Rgba([r, g, b, alpha])
+}
+
+// Pixel counts and color sums of every intensity bucket over the window. Like the median's
+// histogram it slides along a row a column at a time.
+struct BucketHistogram {
+    levels: u32,
+    counts: Vec<u32>,
+    sums: Vec<[u64; 3]>,
+}
+
+impl BucketHistogram {
+    fn new(levels: u32) -> Self {
+        BucketHistogram { levels, counts: vec![0; levels as usize], sums: vec![[0; 3]; levels as usize] }
+    }
+
+    // The column at `x` over the rows `y - radius..=y + radius`, repeating edge pixels past the border
+    fn update_column(&mut self, src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: i32, y: i32, radius: i32, add: bool) {
+        let (width, height) = src.dimensions();
+        let x = x.clamp(0, width as i32 - 1) as u32;
+        for sy in y - radius..=y + radius {
+            let pixel = src.get_pixel(x, sy.clamp(0, height as i32 - 1) as u32);
+            let bucket = bucket(&pixel.0, self.levels);
+            for (sum, &value) in self.sums[bucket].iter_mut().zip(pixel.0.iter()) {
+                if add {
+                    *sum += value as u64;
+                } else {
+                    *sum -= value as u64;
+                }
+            }
+            if add {
+                self.counts[bucket] += 1;
+            } else {
+                self.counts[bucket] -= 1;
+            }
+        }
+    }
+}
+
+fn process_oil_rows(
+    src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>,
+    dst: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
+    radius: i32,
+    levels: u32,
+    rows: Range<u32>,
+    clock: &mut WorkerClock,
+) {
+    let width = src.dimensions().0 as i32;
+    let mut local_pixels = Vec::new();
+
+    for y in rows {
+        let y = y as i32;
+        let mut histogram = BucketHistogram::new(levels);
+        for x in -radius..=radius {
+            histogram.update_column(&src, x, y, radius, true);
+        }
+        for x in 0..width {
+            let alpha = src.get_pixel(x as u32, y as u32)[3];
+            local_pixels.push((x as u32, y as u32, dominant(&histogram.counts, &histogram.sums, alpha)));
+            histogram.update_column(&src, x - radius, y, radius, false);
+            histogram.update_column(&src, x + radius + 1, y, radius, true);
+        }
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst_locked = dst.lock().unwrap();
+    for (x, y, pixel) in local_pixels {
+        dst_locked.put_pixel(x, y, pixel);
+    }
+}
+
+// Oil painting: every pixel takes the mean color of the most common intensity bucket in the
+// (2r+1)x(2r+1) square around it, so regions flatten into strokes of one color while the edges
+// between them stay put. Fewer `--levels` give broader strokes. Alpha is kept; one band of rows
+// per thread.
+pub fn apply_oil_painting(
+    src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    radius: i32,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let radius = u32::try_from(radius).expect("radius must not be negative") as i32;
+    let (width, height) = src.dimensions();
+    if radius == 0 {
+        return src.clone();
+    }
+    progress::expect(height as usize);
+
+    let levels = filter.levels;
+    let src_arc = Arc::new(src.clone());
+    let dst = Arc::new(Mutex::new(ImageBuffer::new(width, height)));
+    let mut handles = Vec::new();
+
+    let pass = tracing::info_span!("oil_pass").entered();
+    for (thread_id, rows) in bands::split(height as usize, num_threads).into_iter().enumerate() {
+        let parent = tracing::Span::current();
+        let src = Arc::clone(&src_arc);
+        let dst = Arc::clone(&dst);
+
+        let handle = workers::spawn(move || {
+            let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?rows).entered();
+            let mut clock = WorkerClock::start("oil", thread_id, rows.clone());
+            process_oil_rows(src, dst, radius, levels, rows.start as u32..rows.end as u32, &mut clock);
+            clock.finish();
+        });
+
+        handles.push(handle);
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    pass.exit();
+
+    let mut result = Arc::try_unwrap(dst)
+        .unwrap()
+        .into_inner()
+        .unwrap();
+    tracing::info_span!("channels").in_scope(|| channels::restore(src, &mut result, filter.channels, num_threads));
+    result
+}
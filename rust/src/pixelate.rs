@@ -0,0 +1,76 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::colorspace::{self, ColorSpace};
+use crate::integral::IntegralImage;
+use crate::kuwahara;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::ops::Range;
+
+// Rows of blocks split into one run per thread, each with the pixel rows it covers and their
+// slice of `data`. The last block row may be cut short by the bottom of the image.
+fn block_bands(data: &mut [u8], row_len: usize, height: usize, block: usize, parts: usize) -> Vec<(Range<usize>, &mut [u8])> {
+    let mut rest = data;
+    bands::split(height.div_ceil(block), parts)
+        .into_iter()
+        .map(|blocks| {
+            let rows = blocks.start * block..(blocks.end * block).min(height);
+            let (band, tail) = std::mem::take(&mut rest).split_at_mut(rows.len() * row_len);
+            rest = tail;
+            (rows, band)
+        })
+        .collect()
+}
+
+// Mosaic: the image cut into squares of `block` pixels from the top left, each filled with its
+// mean color. The means come from a summed-area table, so a block costs four lookups whatever
+// its size. Every thread owns a run of block rows and fills them. With `--alpha-weighted`
+// transparent pixels do not tint their block; alpha itself is kept.
+pub fn apply_pixelate(
+    src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    block: i32,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let block = u32::try_from(block).expect("block size must not be negative") as usize;
+    if block <= 1 {
+        return src.clone();
+    }
+    let (width, height) = (src.width() as usize, src.height() as usize);
+    progress::expect(height);
+
+    // Averaged in RGB, linear with `--linear`; hue does not average
+    let rgb = FilterOptions { colorspace: ColorSpace::Rgb, ..filter };
+    let values = tracing::info_span!("convert").in_scope(|| kuwahara::convert_to_space(src, rgb, num_threads));
+    let alpha: Option<Vec<u8>> = filter.alpha_weighted.then(|| src.pixels().map(|pixel| pixel[3]).collect());
+    let mut integral = IntegralImage::new(width, height);
+    tracing::info_span!("sat_build").in_scope(|| integral.build(&values, alpha.as_deref()));
+
+    let mut result = src.clone();
+    let row_len = width * 4;
+    let parent = tracing::Span::current();
+    workers::scope_each(block_bands(&mut result, row_len, height, block, num_threads).into_iter().enumerate(), |(thread_id, (rows, band))| {
+        let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?rows).entered();
+        for top in rows.clone().step_by(block) {
+            let bottom = (top + block).min(rows.end);
+            for left in (0..width).step_by(block) {
+                let right = (left + block).min(width);
+                let Some((mean, _)) = integral.get_region_stats(left as i32, top as i32, right as i32 - 1, bottom as i32 - 1) else {
+                    continue;
+                };
+                let color = colorspace::from_space(mean.map(|mean| mean as f32), ColorSpace::Rgb, filter.linear);
+                for y in top..bottom {
+                    let row = &mut band[(y - rows.start) * row_len..][..row_len];
+                    for pixel in row[left * 4..right * 4].chunks_exact_mut(4) {
+                        pixel[..3].copy_from_slice(&color);
+                    }
+                }
+            }
+            progress::advance(bottom - top);
+        }
+    });
+    channels::restore(src, &mut result, filter.channels, num_threads);
+    result
+}
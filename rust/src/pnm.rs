@@ -0,0 +1,187 @@
+use crate::bands;
+use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::thread;
+
+pub fn is_pnm(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    [".ppm", ".pgm", ".pnm", ".pam"].iter().any(|ext| lower.ends_with(ext))
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    channels: usize,
+    maxval: u32,
+    // 2 for maxvals above 255, stored big-endian
+    sample_bytes: usize,
+    src_row: usize,
+    data_offset: usize,
+}
+
+// Reads whitespace separated header tokens, skipping `#` comments
+fn next_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    loop {
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if data.get(*pos) != Some(&b'#') {
+            break;
+        }
+        while *pos < data.len() && data[*pos] != b'\n' {
+            *pos += 1;
+        }
+    }
+
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    std::str::from_utf8(&data[start..*pos]).ok().filter(|token| !token.is_empty())
+}
+
+fn parse_number(data: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let token = next_token(data, pos).ok_or("Truncated PNM header")?;
+    token.parse().map_err(|_| format!("Invalid PNM header value: {}", token))
+}
+
+fn parse_header(data: &[u8]) -> Result<Header, String> {
+    let mut pos = 2;
+    let (width, height, channels, maxval) = match data.get(..2) {
+        Some(b"P5") | Some(b"P6") => {
+            let channels = if data[1] == b'5' { 1 } else { 3 };
+            let width = parse_number(data, &mut pos)?;
+            let height = parse_number(data, &mut pos)?;
+            let maxval = parse_number(data, &mut pos)?;
+            (width, height, channels, maxval)
+        }
+        Some(b"P7") => {
+            let (mut width, mut height, mut channels, mut maxval) = (0, 0, 0, 255);
+            loop {
+                match next_token(data, &mut pos).ok_or("Truncated PAM header")? {
+                    "WIDTH" => width = parse_number(data, &mut pos)?,
+                    "HEIGHT" => height = parse_number(data, &mut pos)?,
+                    "DEPTH" => channels = parse_number(data, &mut pos)?,
+                    "MAXVAL" => maxval = parse_number(data, &mut pos)?,
+                    "TUPLTYPE" => {
+                        next_token(data, &mut pos);
+                    }
+                    "ENDHDR" => break,
+                    other => return Err(format!("Unknown PAM header field: {}", other)),
+                }
+            }
+            (width, height, channels, maxval)
+        }
+        _ => return Err("Only binary PGM (P5), PPM (P6) and PAM (P7) files are supported".into()),
+    };
+
+    if !(1..=4).contains(&channels) || maxval == 0 || maxval > u16::MAX as usize {
+        return Err("Unsupported PNM channel count or maxval".into());
+    }
+
+    // The sizes come from the file, so they are checked rather than left to wrap
+    let sample_bytes = if maxval > 255 { 2 } else { 1 };
+    let too_large = || format!("PNM image too large: {}x{}", width, height);
+    let src_row = width.checked_mul(channels * sample_bytes).ok_or_else(too_large)?;
+    let src_len = src_row.checked_mul(height).ok_or_else(too_large)?;
+    if u32::try_from(width).is_err() || u32::try_from(height).is_err() || width.checked_mul(4).and_then(|row| row.checked_mul(height)).is_none() {
+        return Err(too_large());
+    }
+
+    // Exactly one whitespace byte separates the header from the samples
+    let data_offset = pos + 1;
+    if data.len().saturating_sub(data_offset) < src_len {
+        return Err("Truncated PNM pixel data".into());
+    }
+    Ok(Header {
+        width,
+        height,
+        channels,
+        maxval: maxval as u32,
+        sample_bytes,
+        src_row,
+        data_offset,
+    })
+}
+
+fn expand_pixel(src: &[u8], dst: &mut [u8], channels: usize) {
+    match channels {
+        1 => dst.copy_from_slice(&[src[0], src[0], src[0], 255]),
+        2 => dst.copy_from_slice(&[src[0], src[0], src[0], src[1]]),
+        3 => dst.copy_from_slice(&[src[0], src[1], src[2], 255]),
+        _ => dst.copy_from_slice(&src[..4]),
+    }
+}
+
+pub fn load(path: &str, num_threads: usize) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    let data = fs::read(path)?;
+    let header = parse_header(&data)?;
+
+    let (sample_bytes, src_row) = (header.sample_bytes, header.src_row);
+    let payload = &data[header.data_offset..header.data_offset + src_row * header.height];
+
+    // Rescale samples to 8 bits when maxval is not 255
+    let scale = 255.0 / header.maxval as f32;
+    let sample = |bytes: &[u8]| -> u8 {
+        let value = if sample_bytes == 2 { u16::from_be_bytes([bytes[0], bytes[1]]) as u32 } else { bytes[0] as u32 };
+        if header.maxval == 255 { value as u8 } else { (value as f32 * scale).round().min(255.0) as u8 }
+    };
+
+    let dst_row = header.width * 4;
+    let mut rgba = vec![0u8; dst_row * header.height];
+
+    thread::scope(|scope| {
+        for (rows, dst_band) in bands::split_mut(&mut rgba, dst_row, num_threads) {
+            let src_band = &payload[rows.start * src_row..rows.end * src_row];
+            scope.spawn(move || {
+                let mut pixel = [0u8; 4];
+                for (src, dst) in src_band.chunks_exact(header.channels * sample_bytes).zip(dst_band.chunks_exact_mut(4)) {
+                    for (ch, value) in pixel.iter_mut().enumerate().take(header.channels) {
+                        *value = sample(&src[ch * sample_bytes..]);
+                    }
+                    expand_pixel(&pixel, dst, header.channels);
+                }
+            });
+        }
+    });
+
+    Ok(ImageBuffer::from_raw(header.width as u32, header.height as u32, rgba).expect("PNM buffer matches dimensions"))
+}
+
+pub fn save(path: &str, img: &ImageBuffer<Rgba<u8>, Vec<u8>>, num_threads: usize) -> io::Result<()> {
+    let (width, height) = img.dimensions();
+    let lower = path.to_ascii_lowercase();
+    let (header, channels) = if lower.ends_with(".pgm") {
+        (format!("P5\n{} {}\n255\n", width, height), 1)
+    } else if lower.ends_with(".pam") {
+        (format!("P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n", width, height), 4)
+    } else {
+        (format!("P6\n{} {}\n255\n", width, height), 3)
+    };
+
+    let src_row = width as usize * 4;
+    let dst_row = width as usize * channels;
+    let mut samples = vec![0u8; dst_row * height as usize];
+
+    thread::scope(|scope| {
+        for (rows, dst_band) in bands::split_mut(&mut samples, dst_row, num_threads) {
+            let src_band = &img.as_raw()[rows.start * src_row..rows.end * src_row];
+            scope.spawn(move || {
+                for (src, dst) in src_band.chunks_exact(4).zip(dst_band.chunks_exact_mut(channels)) {
+                    match channels {
+                        // Rec. 601 luma for grayscale output
+                        1 => dst[0] = ((src[0] as u32 * 299 + src[1] as u32 * 587 + src[2] as u32 * 114 + 500) / 1000) as u8,
+                        _ => dst.copy_from_slice(&src[..channels]),
+                    }
+                }
+            });
+        }
+    });
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(header.as_bytes())?;
+    writer.write_all(&samples)?;
+    writer.flush()
+}
@@ -0,0 +1,53 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::dither;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+
+// Nearest of `levels` values spread evenly over 0..=255, the same steps `dither` rounds to
+pub fn posterize_value(value: u8, levels: u32) -> u8 {
+    dither::quantize(value as i32, levels)
+}
+
+// The value raised to 1 / gamma over 0..=1, so a gamma above 1 brightens the midtones and one
+// below darkens them; black and white stay put
+pub fn gamma_value(value: u8, gamma: f64) -> u8 {
+    (255.0 * (value as f64 / 255.0).powf(1.0 / gamma)).round() as u8
+}
+
+// Every 8-bit value through `map` once, so the per-pixel work is three lookups
+fn table(map: impl Fn(u8) -> u8) -> [u8; 256] {
+    std::array::from_fn(|value| map(value as u8))
+}
+
+// Runs `map` over every pixel, one band of rows per thread. The shared body of the point
+// operations, whose output pixel depends on nothing but the input pixel at the same place: with
+// no neighborhood to read and little arithmetic per pixel they are bound by memory bandwidth.
+pub fn map_pixels(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, num_threads: usize, filter: FilterOptions, map: impl Fn([u8; 4]) -> [u8; 4] + Sync) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let mut result = img.clone();
+    let row_len = img.width() as usize * 4;
+    progress::expect(img.height() as usize);
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        for pixel in band.chunks_exact_mut(4) {
+            let mapped = map([pixel[0], pixel[1], pixel[2], pixel[3]]);
+            pixel.copy_from_slice(&mapped);
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(img, &mut result, filter.channels, num_threads);
+    result
+}
+
+// Rounds every color channel to one of `levels` values; alpha is kept
+pub fn apply_posterize(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, levels: u32, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let table = table(|value| posterize_value(value, levels));
+    map_pixels(img, num_threads, filter, |[r, g, b, a]| [table[r as usize], table[g as usize], table[b as usize], a])
+}
+
+// Applies the gamma curve to every color channel; alpha is kept
+pub fn apply_gamma(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, gamma: f64, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let table = table(|value| gamma_value(value, gamma));
+    map_pixels(img, num_threads, filter, |[r, g, b, a]| [table[r as usize], table[g as usize], table[b as usize], a])
+}
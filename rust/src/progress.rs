@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Rows every filter pass has finished and announced, across all workers
+static DONE: AtomicUsize = AtomicUsize::new(0);
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+// How often `--eta` prints an estimate
+pub const INTERVAL: Duration = Duration::from_secs(2);
+// Weight of the newest throughput sample in the moving estimate
+const SMOOTHING: f64 = 0.3;
+
+// Filters announce the rows they are about to process, over all of their passes
+pub fn expect(rows: usize) {
+    TOTAL.fetch_add(rows, Ordering::Relaxed);
+}
+
+pub fn advance(rows: usize) {
+    DONE.fetch_add(rows, Ordering::Relaxed);
+}
+
+pub fn reset() {
+    DONE.store(0, Ordering::Relaxed);
+    TOTAL.store(0, Ordering::Relaxed);
+}
+
+pub struct Progress {
+    pub fraction: f64,
+    // Unknown until some throughput has been measured
+    pub remaining: Option<Duration>,
+}
+
+impl Progress {
+    pub fn format(&self) -> String {
+        let done = format!("{:.0}% done", self.fraction * 100.0);
+        match self.remaining {
+            Some(remaining) if remaining.as_secs() >= 60 => format!("{}, ~{}m {}s remaining", done, remaining.as_secs() / 60, remaining.as_secs() % 60),
+            Some(remaining) => format!("{}, ~{}s remaining", done, remaining.as_secs()),
+            None => done,
+        }
+    }
+}
+
+// Samples the row counter on its own thread and hands an estimate to the callback every interval
+pub struct Reporter {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Reporter {
+    pub fn start<F>(interval: Duration, mut callback: F) -> Reporter
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut last = (Instant::now(), DONE.load(Ordering::Relaxed));
+            let mut rate: Option<f64> = None;
+
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let now = (Instant::now(), DONE.load(Ordering::Relaxed));
+                let (done, total) = (now.1, TOTAL.load(Ordering::Relaxed));
+                if total == 0 {
+                    continue;
+                }
+
+                // Exponentially weighted rows per second, so a slow start or a stalled pass fades out
+                let sample = (now.1 - last.1) as f64 / (now.0 - last.0).as_secs_f64();
+                rate = Some(rate.map_or(sample, |rate| SMOOTHING * sample + (1.0 - SMOOTHING) * rate));
+                last = now;
+
+                let remaining = rate.filter(|&rate| rate > 0.0).map(|rate| Duration::from_secs_f64(total.saturating_sub(done) as f64 / rate));
+                callback(Progress { fraction: (done as f64 / total as f64).min(1.0), remaining });
+            }
+        });
+        Reporter { stop, handle }
+    }
+
+    pub fn stop(self) {
+        drop(self.stop);
+        self.handle.join().unwrap();
+    }
+}
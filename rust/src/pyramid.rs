@@ -0,0 +1,185 @@
+use crate::bands;
+use image::{imageops, ImageBuffer, ImageFormat, Rgba};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // Deep Zoom (OpenSeadragon): `<name>.dzi` plus `<name>_files/<level>/<col>_<row>.<ext>`
+    DeepZoom,
+    // Slippy map (Leaflet): `<dir>/<zoom>/<x>/<y>.<ext>` with fixed-size tiles
+    Xyz,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dzi" => Ok(Layout::DeepZoom),
+            "xyz" => Ok(Layout::Xyz),
+            other => Err(format!("Unknown pyramid layout: {}", other)),
+        }
+    }
+}
+
+impl Layout {
+    pub fn default_tile_size(self) -> u32 {
+        match self {
+            Layout::DeepZoom => 254,
+            Layout::Xyz => 256,
+        }
+    }
+}
+
+pub struct PyramidStats {
+    pub levels: usize,
+    pub tiles: usize,
+}
+
+struct Tile {
+    level: usize,
+    col: u32,
+    row: u32,
+}
+
+// 2x2 box filter, rounding odd sizes up; rows are split into bands across threads
+pub fn halve(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, num_threads: usize) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (width, height) = img.dimensions();
+    let (half_width, half_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut dst = vec![0u8; half_width as usize * half_height as usize * 4];
+    let row_len = half_width as usize * 4;
+
+    thread::scope(|scope| {
+        for (rows, chunk) in bands::split_mut(&mut dst, row_len, num_threads) {
+            scope.spawn(move || {
+                for (i, pixel) in chunk.chunks_exact_mut(4).enumerate() {
+                    let x = (i % half_width as usize) as u32 * 2;
+                    let y = (rows.start + i / half_width as usize) as u32 * 2;
+                    let (x1, y1) = ((x + 1).min(width - 1), (y + 1).min(height - 1));
+                    for (ch, value) in pixel.iter_mut().enumerate() {
+                        let sum = img.get_pixel(x, y)[ch] as u32
+                            + img.get_pixel(x1, y)[ch] as u32
+                            + img.get_pixel(x, y1)[ch] as u32
+                            + img.get_pixel(x1, y1)[ch] as u32;
+                        *value = ((sum + 2) / 4) as u8;
+                    }
+                }
+            });
+        }
+    });
+
+    ImageBuffer::from_raw(half_width, half_height, dst).expect("Halved buffer matches dimensions")
+}
+
+// Writes `img` as a tile pyramid; levels are reduced one after the other, then the tiles of
+// all levels are cropped and encoded by `num_threads` workers pulling from a shared queue
+pub fn write_pyramid(
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    output: &str,
+    layout: Layout,
+    tile_size: u32,
+    overlap: u32,
+    extension: &str,
+    num_threads: usize,
+) -> Result<PyramidStats, Box<dyn Error>> {
+    ImageFormat::from_extension(extension).ok_or_else(|| format!("Unsupported tile format: {}", extension))?;
+    let tile_size = tile_size.max(1);
+    let overlap = if layout == Layout::DeepZoom { overlap } else { 0 };
+
+    // Deep Zoom halves down to a single pixel, slippy maps until the image fits one tile
+    let mut reduced = Vec::new();
+    loop {
+        let current = reduced.last().unwrap_or(img);
+        let (width, height) = current.dimensions();
+        let done = match layout {
+            Layout::DeepZoom => width <= 1 && height <= 1,
+            Layout::Xyz => width <= tile_size && height <= tile_size,
+        };
+        if done {
+            break;
+        }
+        let next = halve(current, num_threads);
+        reduced.push(next);
+    }
+    let levels: Vec<&ImageBuffer<Rgba<u8>, Vec<u8>>> = std::iter::once(img).chain(reduced.iter()).rev().collect();
+
+    let root = match layout {
+        Layout::DeepZoom => {
+            let base = output.strip_suffix(".dzi").unwrap_or(output);
+            let (width, height) = img.dimensions();
+            let descriptor = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" Format=\"{}\" Overlap=\"{}\" TileSize=\"{}\">\n  \
+                 <Size Width=\"{}\" Height=\"{}\"/>\n</Image>\n",
+                extension, overlap, tile_size, width, height
+            );
+            fs::write(format!("{}.dzi", base), descriptor)?;
+            PathBuf::from(format!("{}_files", base))
+        }
+        Layout::Xyz => PathBuf::from(output),
+    };
+
+    let mut tiles = Vec::new();
+    for (level, image) in levels.iter().enumerate() {
+        let (cols, rows) = (image.width().div_ceil(tile_size), image.height().div_ceil(tile_size));
+        for col in 0..cols {
+            let dir = match layout {
+                Layout::DeepZoom => root.join(level.to_string()),
+                Layout::Xyz => root.join(level.to_string()).join(col.to_string()),
+            };
+            fs::create_dir_all(dir)?;
+            tiles.extend((0..rows).map(|row| Tile { level, col, row }));
+        }
+    }
+
+    let next = AtomicUsize::new(0);
+    let write_tile = |tile: &Tile| -> Result<(), image::ImageError> {
+        let image = levels[tile.level];
+        let x = (tile.col * tile_size).saturating_sub(overlap);
+        let y = (tile.row * tile_size).saturating_sub(overlap);
+        let right = ((tile.col + 1) * tile_size + overlap).min(image.width());
+        let bottom = ((tile.row + 1) * tile_size + overlap).min(image.height());
+        let mut pixels = imageops::crop_imm(image, x, y, right - x, bottom - y).to_image();
+
+        // Slippy map viewers expect every tile at full size, so edge tiles are padded
+        if layout == Layout::Xyz && (pixels.width() < tile_size || pixels.height() < tile_size) {
+            let mut padded = ImageBuffer::new(tile_size, tile_size);
+            imageops::replace(&mut padded, &pixels, 0, 0);
+            pixels = padded;
+        }
+
+        let path = match layout {
+            Layout::DeepZoom => root.join(tile.level.to_string()).join(format!("{}_{}.{}", tile.col, tile.row, extension)),
+            Layout::Xyz => root.join(tile.level.to_string()).join(tile.col.to_string()).join(format!("{}.{}", tile.row, extension)),
+        };
+        pixels.save(Path::new(&path))
+    };
+
+    thread::scope(|scope| -> Result<(), Box<dyn Error>> {
+        let workers: Vec<_> = (0..num_threads.max(1))
+            .map(|_| {
+                scope.spawn(|| -> Result<(), image::ImageError> {
+                    while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        write_tile(tile)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().unwrap()?;
+        }
+        Ok(())
+    })?;
+
+    Ok(PyramidStats {
+        levels: levels.len(),
+        tiles: tiles.len(),
+    })
+}
@@ -0,0 +1,22 @@
+use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs;
+
+pub fn is_qoi(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".qoi")
+}
+
+// Decodes straight into RGBA, whatever the channel count stored in the file
+pub fn load(path: &str) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    let data = fs::read(path)?;
+    let mut decoder = qoi::Decoder::new(&data)?.with_channels(qoi::Channels::Rgba);
+    let (width, height) = (decoder.header().width, decoder.header().height);
+    let pixels = decoder.decode_to_vec()?;
+    Ok(ImageBuffer::from_raw(width, height, pixels).ok_or("QOI buffer does not match its dimensions")?)
+}
+
+pub fn save(path: &str, img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    let encoded = qoi::Encoder::new(img.as_raw(), img.width(), img.height())?.encode_to_vec()?;
+    fs::write(path, encoded)?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+
+// Rounds of assignment and update before k-means gives up on converging
+pub const MAX_ITERATIONS: usize = 32;
+
+// Color sums and pixel counts of every cluster, or of every luma value while seeding. Integers,
+// so partial sums merge to the same totals however the image was split.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sums {
+    pub colors: Vec<[u64; 3]>,
+    pub counts: Vec<u64>,
+}
+
+impl Sums {
+    pub fn new(len: usize) -> Self {
+        Sums { colors: vec![[0; 3]; len], counts: vec![0; len] }
+    }
+
+    pub fn add(&mut self, index: usize, pixel: &[u8]) {
+        for (sum, &value) in self.colors[index].iter_mut().zip(pixel) {
+            *sum += value as u64;
+        }
+        self.counts[index] += 1;
+    }
+
+    pub fn merge(partials: &[Sums]) -> Sums {
+        let mut total = Sums::new(partials[0].counts.len());
+        for partial in partials {
+            for (sums, colors) in total.colors.iter_mut().zip(&partial.colors) {
+                for (sum, color) in sums.iter_mut().zip(colors) {
+                    *sum += color;
+                }
+            }
+            for (count, partial) in total.counts.iter_mut().zip(&partial.counts) {
+                *count += partial;
+            }
+        }
+        total
+    }
+
+    // Mean color of every entry; empty entries keep the given fallback
+    pub fn means(&self, fallback: &[[f64; 3]]) -> Vec<[f64; 3]> {
+        self.colors
+            .iter()
+            .zip(&self.counts)
+            .zip(fallback)
+            .map(|((colors, &count), &fallback)| if count == 0 { fallback } else { colors.map(|sum| sum as f64 / count as f64) })
+            .collect()
+    }
+}
+
+// Rec. 601 luma of a pixel, 0 to 255
+pub fn luma(pixel: &[u8]) -> usize {
+    ((pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114 + 500) / 1000) as usize
+}
+
+// Starting centroids spread over the image's brightness: centroid i is the mean color of the
+// pixels whose luma holds quantile (i + 1/2) / k, found from per-luma sums
+pub fn seed(by_luma: &Sums, k: usize) -> Vec<[f64; 3]> {
+    let total: u64 = by_luma.counts.iter().sum();
+    let means = by_luma.means(&[[0.0; 3]; 256]);
+    (0..k)
+        .map(|i| {
+            let quantile = (2 * i as u64 + 1) * total / (2 * k as u64);
+            let mut seen = 0;
+            let value = by_luma.counts.iter().position(|&count| {
+                seen += count;
+                seen > quantile
+            });
+            means[value.unwrap_or(255)]
+        })
+        .collect()
+}
+
+// Index of the centroid closest to the pixel, the lowest one on a tie
+pub fn nearest(centroids: &[[f64; 3]], pixel: &[u8]) -> usize {
+    let distance = |centroid: &[f64; 3]| (0..3).map(|ch| (pixel[ch] as f64 - centroid[ch]).powi(2)).sum::<f64>();
+    let mut best = 0;
+    let mut best_distance = f64::INFINITY;
+    for (index, centroid) in centroids.iter().enumerate() {
+        let distance = distance(centroid);
+        if distance < best_distance {
+            best = index;
+            best_distance = distance;
+        }
+    }
+    best
+}
+
+// The palette's 8-bit colors
+pub fn palette(centroids: &[[f64; 3]]) -> Vec<[u8; 3]> {
+    centroids.iter().map(|centroid| centroid.map(|value| value.round() as u8)).collect()
+}
+
+// Sums of one pass over every band, each thread summing its own and the partial sums merged
+fn reduce(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, len: usize, num_threads: usize, index: impl Fn(&[u8]) -> usize + Sync) -> Sums {
+    let row_len = src.width() as usize * 4;
+    let bands = bands::split(src.height() as usize, num_threads);
+    let mut partials = vec![Sums::new(len); bands.len()];
+    workers::scope_each(bands.into_iter().zip(partials.iter_mut()), |(rows, partial)| {
+        for pixel in src.as_raw()[rows.start * row_len..rows.end * row_len].chunks_exact(4) {
+            partial.add(index(pixel), pixel);
+        }
+        progress::advance(rows.len());
+    });
+    Sums::merge(&partials)
+}
+
+// Reduces the colors to a palette of `k` with k-means, alpha kept. Every iteration is a parallel
+// assignment step, each thread summing the pixels of its band by nearest centroid, then a serial
+// update from the merged sums; the threads meet at the end of every iteration. Iterations stop
+// once no centroid moves, or after `MAX_ITERATIONS`.
+pub fn apply_quantize(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, k: i32, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let k = usize::try_from(k).ok().filter(|k| (2..=256).contains(k)).expect("k must be between 2 and 256");
+    let height = src.height() as usize;
+    // Seeding, every iteration and the final mapping
+    progress::expect((MAX_ITERATIONS + 2) * height);
+
+    let by_luma = tracing::info_span!("seed").in_scope(|| reduce(src, 256, num_threads, luma));
+    let mut centroids = seed(&by_luma, k);
+    for iteration in 0..MAX_ITERATIONS {
+        let sums = tracing::info_span!("kmeans_iteration", iteration).in_scope(|| reduce(src, k, num_threads, |pixel| nearest(&centroids, pixel)));
+        let updated = sums.means(&centroids);
+        if updated == centroids {
+            progress::advance((MAX_ITERATIONS - iteration - 1) * height);
+            break;
+        }
+        centroids = updated;
+    }
+
+    let palette = palette(&centroids);
+    let mut result = src.clone();
+    workers::scope_each(bands::split_mut(&mut result, src.width() as usize * 4, num_threads), |(rows, band)| {
+        for pixel in band.chunks_exact_mut(4) {
+            let color = palette[nearest(&centroids, pixel)];
+            pixel[..3].copy_from_slice(&color);
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(src, &mut result, filter.channels, num_threads);
+    result
+}
@@ -0,0 +1,545 @@
+use crate::autolevel;
+use crate::bilateral;
+use crate::blur;
+use crate::bokeh;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::colorspace::{self, ColorSpace};
+use crate::convolve::{self, Kernel};
+use crate::dither;
+use crate::histeq;
+use crate::kuwahara::{self, KuwaharaMode};
+use crate::lut::Lut;
+use crate::morphology::Morphology;
+use crate::motion_blur;
+use crate::oil;
+use crate::point;
+use crate::quantize;
+use crate::resize;
+use crate::sharpen;
+use crate::sobel;
+use crate::srgb;
+use crate::transform;
+use crate::vignette;
+use image::{ImageBuffer, Rgba};
+
+// Straightforward single-threaded filters that compute one output pixel at a time, with no bands,
+// transposes or summed-area tables. `--verify` checks the parallel filters against them.
+
+pub fn filter_pixel(operation: &str, src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    // The output is not the input's size, so there is no input pixel to keep channels from
+    if operation == "resize" {
+        return resize_pixel(src, x, y, filter);
+    }
+    let filtered = match operation {
+        "blur" => blur_pixel(src, x, y, radius, filter),
+        "median" => median_pixel(src, x, y, radius),
+        "bilateral" => bilateral_pixel(src, x, y, radius, filter),
+        "sobel" => sobel_pixel(src, x, y, radius, filter),
+        "sharpen" => sharpen_pixel(src, x, y, radius, filter),
+        "motion-blur" => motion_blur_pixel(src, x, y, radius, filter),
+        "emboss" => convolve_pixel(src, x, y, convolve::EMBOSS, radius, filter),
+        "edges" => convolve_pixel(src, x, y, convolve::LAPLACIAN, radius, filter),
+        "histeq" => histeq_pixel(src, x, y, &equalization_tables(src)),
+        "autolevel" => histeq_pixel(src, x, y, &autolevel_tables(src, filter.clip)),
+        "dither" => *dither(src, radius as u32).get_pixel(x, y),
+        "oil" => oil_pixel(src, x, y, radius, filter),
+        "bokeh" => bokeh_pixel(src, x, y, radius, filter),
+        "pixelate" => pixelate_pixel(src, x, y, radius, filter),
+        "quantize" => quantize_pixel(src, x, y, &kmeans(src, radius as usize)),
+        "dilate" => morphology_pixel(src, x, y, radius, Morphology::Dilate),
+        "erode" => morphology_pixel(src, x, y, radius, Morphology::Erode),
+        "nlmeans" => nlmeans_pixel(src, x, y, radius, filter),
+        "convolve" => convolve_pixel(src, x, y, filter.kernel.expect("convolve needs a kernel"), radius, filter),
+        "posterize" => {
+            let [r, g, b, a] = src.get_pixel(x, y).0;
+            let level = |value: u8| point::posterize_value(value, radius as u32);
+            Rgba([level(r), level(g), level(b), a])
+        }
+        "gamma" => {
+            let [r, g, b, a] = src.get_pixel(x, y).0;
+            let curve = |value: u8| point::gamma_value(value, filter.gamma.expect("gamma needs a value"));
+            Rgba([curve(r), curve(g), curve(b), a])
+        }
+        "transform" => {
+            let (sx, sy) = transform::Affine::new(src.width(), src.height(), filter).source(x, y);
+            transform::sample(src, sx, sy, filter.linear)
+        }
+        "vignette" => {
+            let factor = vignette::factor(x, y, src.width(), src.height(), radius as f64 / 100.0, filter.falloff);
+            Rgba(vignette::darken(src.get_pixel(x, y).0, factor, filter.linear))
+        }
+        "add-noise" => Rgba(filter.noise.expect("add-noise needs a noise").pixel(filter.seed, x, y, src.get_pixel(x, y).0)),
+        _ => kuwahara_pixel(src, x, y, radius, filter),
+    };
+    Rgba(channels::select(src.get_pixel(x, y).0, filtered.0, filter.channels))
+}
+
+// Both passes at one pixel: the horizontal pass over the rows the vertical kernel covers, rounded
+// to 8 bits in between as the filter stores it, then the vertical pass over those
+pub fn blur_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let given = blur::capped_radius(radius as usize, width, height);
+    let radius = blur::capped_radius(blur::blur_radius(given, filter), width, height);
+    if radius == 0 {
+        return *src.get_pixel(x, y);
+    }
+    let (sigma_x, sigma_y) = blur::sigmas(given, filter);
+    let (kernel_x, kernel_y) = (blur::gaussian_kernel(radius, sigma_x), blur::gaussian_kernel(radius, sigma_y));
+    let radius = radius as i32;
+    let lut = srgb::lut();
+    let decode = |value: u8, channel: usize| if filter.linear && channel < 3 { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64, channel: usize| if filter.linear && channel < 3 { lut.encode(value) } else { value.round() as u8 };
+
+    let convolve = |kernel: &[f64], sample: &dyn Fn(i32) -> Rgba<u8>| {
+        let mut sums = [0.0; 4];
+        for (k, weight) in (-radius..=radius).zip(kernel) {
+            for (channel, sum) in sums.iter_mut().enumerate() {
+                *sum += decode(sample(k)[channel], channel) * weight;
+            }
+        }
+        Rgba([0, 1, 2, 3].map(|channel| encode(sums[channel], channel)))
+    };
+    // Each pass reads past the border by the edge mode, the vertical one from the horizontal's result
+    let outside = Rgba(filter.edge.outside());
+    let horizontal = |row: u32| convolve(&kernel_x, &|k| filter.edge.index(x as i32 + k, width as usize).map_or(outside, |sx| *src.get_pixel(sx as u32, row)));
+    convolve(&kernel_y, &|k| filter.edge.index(y as i32 + k, height as usize).map_or(outside, |sy| horizontal(sy as u32)))
+}
+
+// Every channel of the window sorted on its own, edges repeated as the filter repeats them
+pub fn median_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let mut window = Vec::new();
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+            let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+            window.push(src.get_pixel(sx, sy).0);
+        }
+    }
+    Rgba([0, 1, 2, 3].map(|channel| {
+        let mut values: Vec<u8> = window.iter().map(|pixel| pixel[channel]).collect();
+        values.sort_unstable();
+        values[values.len() / 2]
+    }))
+}
+
+// Every weight computed at its tap, the spatial Gaussian times the color Gaussian of each of R, G and B
+pub fn bilateral_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    if radius == 0 {
+        return *src.get_pixel(x, y);
+    }
+    let (width, height) = src.dimensions();
+    let sigma_space = bilateral::sigma_space(radius, filter);
+    let sigma_color = filter.sigma_color;
+    let lut = srgb::lut();
+    let decode = |value: u8, channel: usize| if filter.linear && channel < 3 { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64, channel: usize| if filter.linear && channel < 3 { lut.encode(value) } else { value.round() as u8 };
+    let gaussian = |squared: f64, sigma: f64| (-squared / (2.0 * sigma * sigma)).exp();
+
+    let center = src.get_pixel(x, y).0;
+    let mut sums = [0.0; 4];
+    let mut total = 0.0;
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+            let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+            let pixel = src.get_pixel(sx, sy).0;
+            let mut weight = gaussian((dx * dx + dy * dy) as f64, sigma_space);
+            for channel in 0..3 {
+                let difference = pixel[channel].abs_diff(center[channel]) as f64;
+                weight *= gaussian(difference * difference, sigma_color);
+            }
+            for (channel, sum) in sums.iter_mut().enumerate() {
+                *sum += decode(pixel[channel], channel) * weight;
+            }
+            total += weight;
+        }
+    }
+    Rgba([0, 1, 2, 3].map(|channel| encode(sums[channel] / total, channel)))
+}
+
+// Every tap of the kernels blurred on its own with `blur_pixel`, then its luma taken
+pub fn sobel_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let edge = sobel::magnitude(|dx, dy| {
+        let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+        let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+        channels::luma(&blur_pixel(src, sx, sy, radius, filter).0)
+    });
+    Rgba([edge, edge, edge, src.get_pixel(x, y)[3]])
+}
+
+// Every tap of the kernel blurred on its own with `blur_pixel`
+pub fn convolve_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, kernel: Kernel, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let tap = |dx: i32, dy: i32| Rgba(filter.edge.pixel(x as i32 + dx, y as i32 + dy, width, height, |sx, sy| blur_pixel(src, sx, sy, radius, filter).0));
+    Rgba([0, 1, 2, 3].map(|ch| if ch < 3 { kernel.apply(|dx, dy| tap(dx, dy)[ch] as f32) } else { src.get_pixel(x, y)[ch] }))
+}
+
+// Tables of the histograms counted over the whole image in one pass
+pub fn equalization_tables(src: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> [[u8; 256]; 3] {
+    let mut histograms = [[0; 256]; 3];
+    for pixel in src.pixels() {
+        for ch in 0..3 {
+            histograms[ch][pixel[ch] as usize] += 1;
+        }
+    }
+    histograms.map(|histogram| histeq::equalization_table(&histogram))
+}
+
+pub fn histeq_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, tables: &[[u8; 256]; 3]) -> Rgba<u8> {
+    let pixel = src.get_pixel(x, y);
+    Rgba([tables[0][pixel[0] as usize], tables[1][pixel[1] as usize], tables[2][pixel[2] as usize], pixel[3]])
+}
+
+// Tables stretching the range of each channel, read off its values sorted rather than a histogram
+pub fn autolevel_tables(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, clip: f64) -> [[u8; 256]; 3] {
+    [0, 1, 2].map(|ch| {
+        let mut values: Vec<u8> = src.pixels().map(|pixel| pixel[ch]).collect();
+        if values.is_empty() {
+            return autolevel::stretch_table(0, 255);
+        }
+        values.sort_unstable();
+        let skipped = ((values.len() as f64 * clip / 100.0) as usize).min(values.len() - 1);
+        let (low, high) = (values[skipped], values[values.len() - 1 - skipped]);
+        autolevel::stretch_table(low, high.max(low))
+    })
+}
+
+// Every pixel of the square sorted into its bucket afresh
+pub fn oil_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let mut counts = vec![0; filter.levels as usize];
+    let mut sums = vec![[0; 3]; filter.levels as usize];
+    for sy in y as i32 - radius..=y as i32 + radius {
+        for sx in x as i32 - radius..=x as i32 + radius {
+            let pixel = src.get_pixel(sx.clamp(0, width as i32 - 1) as u32, sy.clamp(0, height as i32 - 1) as u32);
+            let bucket = oil::bucket(&pixel.0, filter.levels);
+            counts[bucket] += 1;
+            for ch in 0..3 {
+                sums[bucket][ch] += pixel[ch] as u64;
+            }
+        }
+    }
+    oil::dominant(&counts, &sums, src.get_pixel(x, y)[3])
+}
+
+// Every offset of the square tested against the circle, weighing each sample afresh
+pub fn bokeh_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let mut sum = [0.0; 5];
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx * dx + dy * dy > radius * radius {
+                continue;
+            }
+            let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+            let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+            for (total, value) in sum.iter_mut().zip(bokeh::sample(src.get_pixel(sx, sy).0, filter)) {
+                *total += value;
+            }
+        }
+    }
+    bokeh::resolve(sum, filter)
+}
+
+// Output pixel (x, y) of `resize` straight from its taps along both axes
+pub fn resize_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, filter: FilterOptions) -> Rgba<u8> {
+    let (width, height) = filter.size.expect("resize needs a size").target(src.width(), src.height());
+    let columns = resize::tap(src.width() as usize, width as usize, x as usize, filter.resample);
+    let rows = resize::tap(src.height() as usize, height as usize, y as usize, filter.resample);
+    let row = |sy: usize| resize::convolve(|sx| resize::load(src.get_pixel(sx as u32, sy as u32).0, filter), &columns);
+    resize::store(resize::convolve(row, &rows), filter)
+}
+
+// Error diffusion over the whole image in one pass, row after row, into a full-size error buffer
+pub fn dither(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, levels: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (width, height) = (src.width() as usize, src.height() as usize);
+    let mut errors = vec![[0i32; 3]; width * height];
+    let mut result = src.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = result.get_pixel_mut(x as u32, y as u32);
+            for ch in 0..3 {
+                let value = (pixel[ch] as i32 + errors[y * width + x][ch]).clamp(0, 255);
+                pixel[ch] = dither::quantize(value, levels);
+                let [right, below_left, below, below_right] = dither::diffuse(value - pixel[ch] as i32);
+                let mut spread = |x: usize, y: usize, part: i32| {
+                    if x < width && y < height {
+                        errors[y * width + x][ch] += part;
+                    }
+                };
+                spread(x + 1, y, right);
+                spread(x.wrapping_sub(1), y + 1, below_left);
+                spread(x, y + 1, below);
+                spread(x + 1, y + 1, below_right);
+            }
+        }
+    }
+    result
+}
+
+// The k-means centroids of the whole image, every iteration one serial pass over all of it
+pub fn kmeans(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, k: usize) -> Vec<[f64; 3]> {
+    let mut by_luma = quantize::Sums::new(256);
+    for pixel in src.pixels() {
+        by_luma.add(quantize::luma(&pixel.0), &pixel.0);
+    }
+    let mut centroids = quantize::seed(&by_luma, k);
+    for _ in 0..quantize::MAX_ITERATIONS {
+        let mut sums = quantize::Sums::new(k);
+        for pixel in src.pixels() {
+            sums.add(quantize::nearest(&centroids, &pixel.0), &pixel.0);
+        }
+        let updated = sums.means(&centroids);
+        if updated == centroids {
+            break;
+        }
+        centroids = updated;
+    }
+    centroids
+}
+
+// The palette color nearest the pixel
+pub fn quantize_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, centroids: &[[f64; 3]]) -> Rgba<u8> {
+    let pixel = *src.get_pixel(x, y);
+    let [r, g, b] = quantize::palette(centroids)[quantize::nearest(centroids, &pixel.0)];
+    Rgba([r, g, b, pixel[3]])
+}
+
+// Mean of the block holding the pixel, summed directly instead of from a summed-area table
+pub fn pixelate_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, block: i32, filter: FilterOptions) -> Rgba<u8> {
+    let pixel = *src.get_pixel(x, y);
+    if block <= 1 {
+        return pixel;
+    }
+    let (width, height) = src.dimensions();
+    let block = block as u32;
+    let (left, top) = (x / block * block, y / block * block);
+    let mut sums = [0.0f64; 3];
+    let mut total = 0.0;
+    for sy in top..(top + block).min(height) {
+        for sx in left..(left + block).min(width) {
+            let sample = src.get_pixel(sx, sy);
+            let values = colorspace::to_space([sample[0], sample[1], sample[2]], ColorSpace::Rgb, filter.linear);
+            let weight = if filter.alpha_weighted { sample[3] as f64 } else { 1.0 };
+            for (sum, value) in sums.iter_mut().zip(values) {
+                *sum += weight * value as f64;
+            }
+            total += weight;
+        }
+    }
+    if total <= 0.0 {
+        return pixel;
+    }
+    let [r, g, b] = colorspace::from_space(sums.map(|sum| (sum / total) as f32), ColorSpace::Rgb, filter.linear);
+    Rgba([r, g, b, pixel[3]])
+}
+
+// Extremum of every channel over the whole square at once
+pub fn morphology_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, op: Morphology) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let (x, y) = (x as i32, y as i32);
+    let window = || (y - radius..=y + radius).flat_map(move |sy| (x - radius..=x + radius).map(move |sx| (sx, sy)));
+    let pixel = |(sx, sy): (i32, i32)| src.get_pixel(sx.clamp(0, width as i32 - 1) as u32, sy.clamp(0, height as i32 - 1) as u32);
+    Rgba([0, 1, 2, 3].map(|ch| op.extremum(window().map(|point| pixel(point)[ch]))))
+}
+
+// Every pixel of the search window weighted by the mean squared difference of its patch
+pub fn nlmeans_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    if radius == 0 {
+        return *src.get_pixel(x, y);
+    }
+    let (width, height) = src.dimensions();
+    let pixel = |x: i32, y: i32| src.get_pixel(x.clamp(0, width as i32 - 1) as u32, y.clamp(0, height as i32 - 1) as u32);
+    let (x, y, patch) = (x as i32, y as i32, filter.patch_radius as i32);
+    let strength = filter.strength as f32;
+    let mut sums = [0.0f32; 4];
+    let mut total = 0.0f32;
+    for sy in y - radius..=y + radius {
+        for sx in x - radius..=x + radius {
+            let mut distance = 0.0;
+            for dy in -patch..=patch {
+                for dx in -patch..=patch {
+                    for ch in 0..3 {
+                        let diff = pixel(x + dx, y + dy)[ch] as f32 - pixel(sx + dx, sy + dy)[ch] as f32;
+                        distance += diff * diff;
+                    }
+                }
+            }
+            distance /= ((2 * patch + 1) * (2 * patch + 1) * 3) as f32;
+            let weight = (-distance / (strength * strength)).exp();
+            for (ch, sum) in sums.iter_mut().enumerate() {
+                *sum += weight * pixel(sx, sy)[ch] as f32;
+            }
+            total += weight;
+        }
+    }
+    Rgba(sums.map(|sum| (sum / total).round().clamp(0.0, 255.0) as u8))
+}
+
+// The pixel against its own `blur_pixel`
+pub fn sharpen_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    let pixel = src.get_pixel(x, y).0;
+    let soft = blur_pixel(src, x, y, radius, filter).0;
+    Rgba([0, 1, 2, 3].map(|ch| if ch < 3 { sharpen::unsharp(pixel[ch], soft[ch], filter.amount, filter.sharpen_threshold) } else { pixel[ch] }))
+}
+
+// Every tap interpolated from its four surrounding pixels, row by row
+pub fn motion_blur_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    if radius <= 1 {
+        return *src.get_pixel(x, y);
+    }
+    let (width, height) = src.dimensions();
+    let lut = srgb::lut();
+    let decode = |value: u8, channel: usize| if filter.linear && channel < 3 { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64, channel: usize| if filter.linear && channel < 3 { lut.encode(value) } else { value.round() as u8 };
+    let offsets = motion_blur::offsets(radius as u32, filter.angle);
+
+    let mut sums = [0.0; 4];
+    for &(dx, dy) in &offsets {
+        let (sx, sy) = (x as f64 + dx, y as f64 + dy);
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let pixel = |x: f64, y: f64| src.get_pixel((x as i64).clamp(0, width as i64 - 1) as u32, (y as i64).clamp(0, height as i64 - 1) as u32);
+        for (channel, sum) in sums.iter_mut().enumerate() {
+            let top = decode(pixel(x0, y0)[channel], channel) * (1.0 - fx) + decode(pixel(x0 + 1.0, y0)[channel], channel) * fx;
+            let bottom = decode(pixel(x0, y0 + 1.0)[channel], channel) * (1.0 - fx) + decode(pixel(x0 + 1.0, y0 + 1.0)[channel], channel) * fx;
+            *sum += top * (1.0 - fy) + bottom * fy;
+        }
+    }
+    Rgba([0, 1, 2, 3].map(|channel| encode(sums[channel] / offsets.len() as f64, channel)))
+}
+
+// Mean and variance of every quadrant summed directly in f64, exact for 8-bit channels. Alpha-weighted
+// sums count each pixel by its alpha and skip quadrants that are fully transparent. With
+// `--filter-alpha` the classic mode takes alpha from the chosen quadrants too.
+pub fn kuwahara_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let radius = radius.min(width.max(height) as i32);
+    let src_pixel = src.get_pixel(x, y);
+    if radius == 0 {
+        return *src_pixel;
+    }
+    let radius = if filter.kuwahara_mode == KuwaharaMode::Adaptive {
+        let edge = sobel::magnitude(|dx, dy| {
+            let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+            let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+            channels::luma(&src.get_pixel(sx, sy).0)
+        });
+        kuwahara::adaptive_radius(edge, filter.min_radius.min(radius as u32) as i32, radius)
+    } else {
+        radius
+    };
+    let best_mean = if filter.kuwahara_mode == KuwaharaMode::Anisotropic {
+        anisotropic_mean(src, x, y, radius, filter).map(|[r, g, b]| [r, g, b, src_pixel[3] as f64])
+    } else if let (KuwaharaMode::Classic, Some(sectors)) = (filter.kuwahara_mode, filter.sectors) {
+        kuwahara::SectorKernel::new(radius, sectors)
+            .mean(|dx, dy| sample(src, x as i32 + dx, y as i32 + dy, filter))
+            .map(|[r, g, b]| [r, g, b, src_pixel[3] as f64])
+    } else if filter.scales > 1 {
+        kuwahara::blend_scales(kuwahara::scale_radii(radius, filter.scales).filter_map(|radius| best_quadrant(src, x, y, radius, filter)))
+    } else {
+        best_quadrant(src, x, y, radius, filter).map(|(mean, _)| mean)
+    };
+    let Some(best_mean) = best_mean else {
+        return *src_pixel;
+    };
+
+    let [r, g, b] = colorspace::from_space([0, 1, 2].map(|ch| best_mean[ch] as f32), filter.colorspace, filter.linear);
+    let a = if filter.filter_alpha && kuwahara::uses_quadrants(filter) {
+        best_mean[3].round().clamp(0.0, 255.0) as u8
+    } else {
+        src_pixel[3]
+    };
+    Rgba([r, g, b, a])
+}
+
+// The smoothed tensor at the pixel taken as the two passes would: the horizontal pass over every
+// row the vertical kernel covers, then the vertical pass over those
+fn anisotropic_mean(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Option<[f64; 3]> {
+    let (width, height) = src.dimensions();
+    let (x, y) = (x as i32, y as i32);
+    let kernel = kuwahara::tensor_kernel();
+    let rgb = |x: i32, y: i32| {
+        let pixel = src.get_pixel(x.clamp(0, width as i32 - 1) as u32, y.clamp(0, height as i32 - 1) as u32);
+        [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]
+    };
+    let tensor = |x: i32, y: i32| kuwahara::tensor_at(|dx, dy| rgb(x + dx, y + dy));
+    let horizontal = |row: i32| kuwahara::smooth_tensor(&kernel, |k| tensor((x + k).clamp(0, width as i32 - 1), row));
+    let smoothed = kuwahara::smooth_tensor(&kernel, |k| horizontal((y + k).clamp(0, height as i32 - 1)));
+
+    kuwahara::sector_mean(radius, kuwahara::orientation(smoothed), |dx, dy| sample(src, x + dx, y + dy, filter))
+}
+
+// The converted color and weight of the pixel at (x, y) for the sector modes, None past the image
+fn sample(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: i32, y: i32, filter: FilterOptions) -> Option<([f32; 3], f64)> {
+    let (width, height) = src.dimensions();
+    if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+        return None;
+    }
+    let pixel = src.get_pixel(x as u32, y as u32);
+    let values = colorspace::to_space([pixel[0], pixel[1], pixel[2]], filter.colorspace, filter.linear);
+    Some((values, if filter.alpha_weighted { pixel[3] as f64 } else { 1.0 }))
+}
+
+// Mean and total variance of the quadrant with the least variance at one radius. The fourth mean is
+// the quadrant's alpha, never weighted by itself; its variance only counts with `--filter-alpha`.
+fn best_quadrant(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, radius: i32, filter: FilterOptions) -> Option<([f64; 4], f64)> {
+    let (width, height) = src.dimensions();
+    let (x, y) = (x as i32, y as i32);
+    let quadrants = [
+        [x - radius, y - radius, x, y],
+        [x, y - radius, x + radius, y],
+        [x - radius, y, x, y + radius],
+        [x, y, x + radius, y + radius],
+    ];
+
+    let mut min_variance = f64::MAX;
+    let mut best_mean = None;
+    for [x1, y1, x2, y2] in quadrants {
+        let (x1, y1) = (x1.max(0) as u32, y1.max(0) as u32);
+        let (x2, y2) = (x2.min(width as i32 - 1) as u32, y2.min(height as i32 - 1) as u32);
+        let mut sum = [0.0f64; 4];
+        let mut sum_sq = [0.0f64; 4];
+        let mut area = 0.0;
+        let mut pixels = 0.0;
+        for qy in y1..=y2 {
+            for qx in x1..=x2 {
+                let pixel = src.get_pixel(qx, qy);
+                let values = colorspace::to_space([pixel[0], pixel[1], pixel[2]], filter.colorspace, filter.linear);
+                let weight = if filter.alpha_weighted { pixel[3] as f64 } else { 1.0 };
+                for (ch, &value) in values.iter().enumerate() {
+                    sum[ch] += weight * value as f64;
+                    sum_sq[ch] += weight * value as f64 * value as f64;
+                }
+                sum[3] += pixel[3] as f64;
+                sum_sq[3] += pixel[3] as f64 * pixel[3] as f64;
+                area += weight;
+                pixels += 1.0;
+            }
+        }
+        if area == 0.0 {
+            continue;
+        }
+
+        let areas = [area, area, area, pixels];
+        let mean = [0, 1, 2, 3].map(|ch| sum[ch] / areas[ch]);
+        let channels = if filter.filter_alpha { 4 } else { 3 };
+        let variance: f64 = (0..channels).map(|ch| (sum_sq[ch] / areas[ch] - mean[ch] * mean[ch]).max(0.0)).sum();
+        if variance < min_variance {
+            min_variance = variance;
+            best_mean = Some(mean);
+        }
+    }
+    best_mean.map(|mean| (mean, min_variance))
+}
+
+// The table's interpolation at one pixel; a grade has no neighbourhood to get wrong. The table is
+// not part of FilterOptions, so `filter_pixel` has no `lut` arm and the channels are kept here.
+pub fn lut_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, lut: &Lut, filter: FilterOptions) -> Rgba<u8> {
+    let pixel = src.get_pixel(x, y);
+    let [r, g, b] = lut.map([pixel[0], pixel[1], pixel[2]]);
+    Rgba(channels::select(pixel.0, [r, g, b, pixel[3]], filter.channels))
+}
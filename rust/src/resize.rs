@@ -0,0 +1,169 @@
+use crate::bands;
+use crate::cli::FilterOptions;
+use crate::colorspace::{self, ColorSpace};
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+// Kernels the `resize` operation samples the source with, from blockiest to sharpest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resample {
+    Nearest,
+    Bilinear,
+    // Catmull-Rom, the cubic that passes through the samples
+    Bicubic,
+    Lanczos3,
+}
+
+impl FromStr for Resample {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Resample::Nearest),
+            "bilinear" => Ok(Resample::Bilinear),
+            "bicubic" => Ok(Resample::Bicubic),
+            "lanczos3" => Ok(Resample::Lanczos3),
+            other => Err(format!("Unknown resample filter: {}. Use 'nearest', 'bilinear', 'bicubic' or 'lanczos3'", other)),
+        }
+    }
+}
+
+impl Resample {
+    // Distance in source pixels past which the kernel is zero, at a scale of 1
+    fn support(self) -> f64 {
+        match self {
+            Resample::Nearest => 0.5,
+            Resample::Bilinear => 1.0,
+            Resample::Bicubic => 2.0,
+            Resample::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, t: f64) -> f64 {
+        let t = t.abs();
+        let sinc = |x: f64| if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+        match self {
+            Resample::Nearest => 1.0,
+            Resample::Bilinear => (1.0 - t).max(0.0),
+            Resample::Bicubic if t < 1.0 => 1.5 * t * t * t - 2.5 * t * t + 1.0,
+            Resample::Bicubic if t < 2.0 => -0.5 * t * t * t + 2.5 * t * t - 4.0 * t + 2.0,
+            Resample::Bicubic => 0.0,
+            Resample::Lanczos3 if t < 3.0 => sinc(t) * sinc(t / 3.0),
+            Resample::Lanczos3 => 0.0,
+        }
+    }
+}
+
+// The source pixels one output pixel along an axis is made of: a run from `start`, with weights
+// that add up to 1
+pub struct Taps {
+    pub start: usize,
+    pub weights: Vec<f32>,
+}
+
+// Taps of output pixel `x` when `src_len` pixels are resampled to `dst_len`. Shrinking widens the
+// kernel by the scale so every source pixel still counts.
+pub fn tap(src_len: usize, dst_len: usize, x: usize, resample: Resample) -> Taps {
+    let scale = src_len as f64 / dst_len as f64;
+    let center = (x as f64 + 0.5) * scale;
+    if resample == Resample::Nearest {
+        return Taps { start: (center as usize).min(src_len - 1), weights: vec![1.0] };
+    }
+    let spread = scale.max(1.0);
+    let support = resample.support() * spread;
+    let start = (center - support).floor().max(0.0) as usize;
+    let end = ((center + support).ceil() as usize).min(src_len);
+    let weights: Vec<f64> = (start..end).map(|i| resample.weight((i as f64 + 0.5 - center) / spread)).collect();
+    let total: f64 = weights.iter().sum();
+    Taps { start, weights: weights.into_iter().map(|weight| (weight / total) as f32).collect() }
+}
+
+pub fn taps(src_len: usize, dst_len: usize, resample: Resample) -> Vec<Taps> {
+    (0..dst_len).map(|x| tap(src_len, dst_len, x, resample)).collect()
+}
+
+// A pixel as the kernels sum it: RGB in linear light with `--linear`, and premultiplied by alpha
+// with `--alpha-weighted` so transparent pixels do not darken their neighbors' edges
+pub fn load(pixel: [u8; 4], filter: FilterOptions) -> [f32; 4] {
+    let [r, g, b] = colorspace::to_space([pixel[0], pixel[1], pixel[2]], ColorSpace::Rgb, filter.linear);
+    let coverage = if filter.alpha_weighted { pixel[3] as f32 / 255.0 } else { 1.0 };
+    [r * coverage, g * coverage, b * coverage, pixel[3] as f32]
+}
+
+// Back from `load`'s form, clamping the overshoot of the sharper kernels
+pub fn store(value: [f32; 4], filter: FilterOptions) -> Rgba<u8> {
+    let alpha = value[3].clamp(0.0, 255.0);
+    let coverage = if filter.alpha_weighted { alpha / 255.0 } else { 1.0 };
+    let rgb = if coverage > 0.0 { [value[0] / coverage, value[1] / coverage, value[2] / coverage] } else { [0.0; 3] };
+    // Rounded rather than truncated as the plain RGB means are, or a flat area would lose a level
+    // wherever the weights add up to just under 1
+    let [r, g, b] = if filter.linear { colorspace::from_space(rgb, ColorSpace::Rgb, true) } else { rgb.map(|c| c.round().clamp(0.0, 255.0) as u8) };
+    Rgba([r, g, b, alpha.round() as u8])
+}
+
+// Sum of `values`, 4 per pixel, weighted by the taps
+pub fn convolve(values: impl Fn(usize) -> [f32; 4], taps: &Taps) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    for (i, &weight) in taps.weights.iter().enumerate() {
+        let value = values(taps.start + i);
+        for ch in 0..4 {
+            sum[ch] += value[ch] * weight;
+        }
+    }
+    sum
+}
+
+// Resamples the image to `width` x `height` with `filter.resample`. Like the blur it is separable:
+// the first pass resizes every source row, the second every column of that, both in bands of rows
+// with one band per thread. Every channel is resampled, so `--channels` does not apply.
+pub fn apply_resize(
+    src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    width: u32,
+    height: u32,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    if (width, height) == src.dimensions() {
+        return src.clone();
+    }
+    let (src_width, src_height) = (src.width() as usize, src.height() as usize);
+    let (width, height) = (width as usize, height as usize);
+    // Both passes, the second over output rows
+    progress::expect(src_height + height);
+    let columns = taps(src_width, width, filter.resample);
+    let rows = taps(src_height, height, filter.resample);
+
+    // Source rows resized to the output width, kept unrounded for the second pass
+    let mut horizontal = vec![0.0f32; width * src_height * 4];
+    let parent = tracing::Span::current();
+    tracing::info_span!("resize_pass", direction = "horizontal").in_scope(|| {
+        workers::scope_each(bands::split_mut(&mut horizontal, width * 4, num_threads).into_iter().enumerate(), |(thread_id, (band_rows, band))| {
+            let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?band_rows).entered();
+            for (y, row) in band_rows.clone().zip(band.chunks_exact_mut(width * 4)) {
+                let source: Vec<[f32; 4]> = (0..src_width).map(|x| load(src.get_pixel(x as u32, y as u32).0, filter)).collect();
+                for (pixel, taps) in row.chunks_exact_mut(4).zip(&columns) {
+                    pixel.copy_from_slice(&convolve(|x| source[x], taps));
+                }
+            }
+            progress::advance(band_rows.len());
+        });
+    });
+
+    let mut result = ImageBuffer::new(width as u32, height as u32);
+    tracing::info_span!("resize_pass", direction = "vertical").in_scope(|| {
+        workers::scope_each(bands::split_mut(&mut result, width * 4, num_threads).into_iter().enumerate(), |(thread_id, (band_rows, band))| {
+            let _span = tracing::debug_span!(parent: &parent, "worker", id = thread_id, rows = ?band_rows).entered();
+            for (y, row) in band_rows.clone().zip(band.chunks_exact_mut(width * 4)) {
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let value = convolve(|sy| horizontal[(sy * width + x) * 4..][..4].try_into().unwrap(), &rows[y]);
+                    pixel.copy_from_slice(&store(value, filter).0);
+                }
+            }
+            progress::advance(band_rows.len());
+        });
+    });
+    result
+}
@@ -0,0 +1,45 @@
+use crate::bands;
+use crate::blur;
+use crate::channels::{self, Channels};
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+
+// One channel of the unsharp mask: the value pushed `amount` times its difference from the blurred
+// value further away from it. Differences of at most `threshold` levels are left alone, so noise
+// and smooth gradients are not sharpened along with the edges.
+pub fn unsharp(value: u8, soft: u8, amount: f32, threshold: u8) -> u8 {
+    if value.abs_diff(soft) <= threshold {
+        return value;
+    }
+    let sharpened = value as f32 + amount * (value as f32 - soft as f32);
+    sharpened.round().clamp(0.0, 255.0) as u8
+}
+
+// Unsharp mask: the Gaussian blur at `radius`, then a parallel pass over bands of rows that adds
+// back the detail the blur took out. Alpha is kept.
+pub fn apply_unsharp_mask(
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    radius: i32,
+    amount: f32,
+    threshold: u8,
+    num_threads: usize,
+    filter: FilterOptions,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let blurred = blur::apply_gaussian_blur(img, radius, num_threads, FilterOptions { channels: Channels::Rgba, ..filter });
+    let mut result = img.clone();
+    let row_len = img.width() as usize * 4;
+    progress::expect(img.height() as usize);
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        let soft = &blurred.as_raw()[rows.start * row_len..rows.end * row_len];
+        for (pixel, soft) in band.chunks_exact_mut(4).zip(soft.chunks_exact(4)) {
+            for ch in 0..3 {
+                pixel[ch] = unsharp(pixel[ch], soft[ch], amount, threshold);
+            }
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(img, &mut result, filter.channels, num_threads);
+    result
+}
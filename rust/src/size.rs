@@ -0,0 +1,50 @@
+// Buffer sizes of the filters, computed so that they cannot wrap. On 32-bit targets, wasm among
+// them, usize stops at 4 GiB and a product like `(width + 1) * (height + 1) * 3` for the
+// summed-area table can wrap to a small number in release builds.
+
+use crate::cli::FilterOptions;
+
+// The largest allocation a Vec may make on this target
+pub const MAX_BYTES: u128 = isize::MAX as u128;
+
+pub fn too_large(width: u32, height: u32) -> String {
+    format!("Image too large: {}x{} does not fit in memory on this platform", width, height)
+}
+
+// Bytes of a summed-area table of `channels` channels and their squares, in f64 with a row and a
+// column of zeros, plus the summed alpha when the sums are alpha-weighted
+fn integral_bytes(width: u128, height: u128, channels: u128, weighted: bool) -> u128 {
+    (width + 1) * (height + 1) * (2 * channels + weighted as u128) * 8
+}
+
+// Bytes the filter's largest buffers take; u128 holds them for any pair of u32 sides
+fn bytes_needed(operation: &str, width: u32, height: u32, filter: FilterOptions) -> u128 {
+    let (width, height) = (width as u128, height as u128);
+    match operation {
+        // Alpha is a fourth channel of the table when it is filtered with the colors
+        "kuwahara" => integral_bytes(width, height, if filter.filter_alpha { 4 } else { 3 }, filter.alpha_weighted),
+        "pixelate" => integral_bytes(width, height, 3, filter.alpha_weighted),
+        // Four channels of f32 between the passes
+        "resize" => width * height * 4 * 4,
+        // RGBA bytes, copied between the passes
+        _ => width * height * 4,
+    }
+}
+
+// Checks `operation` on a `width`x`height` image against a byte limit, which tests lower to
+// stand in for a 32-bit target
+pub fn check_with_limit(operation: &str, width: u32, height: u32, filter: FilterOptions, max_bytes: u128) -> Result<(), String> {
+    if bytes_needed(operation, width, height, filter) > max_bytes {
+        return Err(too_large(width, height));
+    }
+    Ok(())
+}
+
+pub fn check(operation: &str, width: u32, height: u32, filter: FilterOptions) -> Result<(), String> {
+    check_with_limit(operation, width, height, filter, MAX_BYTES)
+}
+
+// Elements of one summed-area table of `channels` channels, None when they do not fit in usize
+pub fn integral_len(width: usize, height: usize, channels: usize) -> Option<usize> {
+    width.checked_add(1)?.checked_mul(height.checked_add(1)?)?.checked_mul(channels)
+}
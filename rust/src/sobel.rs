@@ -0,0 +1,48 @@
+use crate::bands;
+use crate::blur;
+use crate::channels::{self, Channels};
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+
+// Gradient magnitude from the 3x3 Sobel kernels, `luma` giving the luma at an offset from the
+// pixel. Clamped to 8 bits, so only edges steeper than a quarter of the range saturate.
+pub fn magnitude(luma: impl Fn(i32, i32) -> f32) -> u8 {
+    let gx = luma(1, -1) + 2.0 * luma(1, 0) + luma(1, 1) - luma(-1, -1) - 2.0 * luma(-1, 0) - luma(-1, 1);
+    let gy = luma(-1, 1) + 2.0 * luma(0, 1) + luma(1, 1) - luma(-1, -1) - 2.0 * luma(0, -1) - luma(1, -1);
+    (gx * gx + gy * gy).sqrt().round().min(255.0) as u8
+}
+
+// Edge map: the gradient magnitude of the luma in every color channel, alpha kept. A radius above
+// 0 smooths the image with the Gaussian blur first, so noise does not show up as edges.
+pub fn apply_sobel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, radius: i32, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let radius = u32::try_from(radius).expect("radius must not be negative") as i32;
+    let blurred;
+    let smoothed = if radius == 0 {
+        src
+    } else {
+        blurred = blur::apply_gaussian_blur(src, radius, num_threads, FilterOptions { channels: Channels::Rgba, ..filter });
+        &blurred
+    };
+
+    let (width, height) = src.dimensions();
+    let mut result = src.clone();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        for (y, row) in rows.clone().zip(band.chunks_exact_mut(row_len)) {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let edge = magnitude(|dx, dy| {
+                    let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                    let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                    channels::luma(&smoothed.get_pixel(sx, sy).0)
+                });
+                pixel[..3].copy_from_slice(&[edge, edge, edge]);
+            }
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(src, &mut result, filter.channels, num_threads);
+    result
+}
@@ -0,0 +1,215 @@
+use crate::bands;
+use crate::blur;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+use std::str::FromStr;
+use std::thread;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// How the `stack` operation combines aligned exposures of the same scene, one value per channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackMode {
+    // Averages the noise down by the square root of the frame count
+    Mean,
+    // Also drops outliers such as satellite trails and hot pixels, but needs every frame in memory
+    Median,
+}
+
+impl FromStr for StackMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(StackMode::Mean),
+            "median" => Ok(StackMode::Median),
+            other => Err(format!("Unknown stack mode: {}. Use 'mean' or 'median'", other)),
+        }
+    }
+}
+
+fn check_dimensions(index: usize, frame: &Frame, expected: (u32, u32)) -> Result<(), String> {
+    if frame.dimensions() != expected {
+        let (width, height) = frame.dimensions();
+        return Err(format!("Frame {} is {}x{}, expected {}x{} like the first", index + 1, width, height, expected.0, expected.1));
+    }
+    Ok(())
+}
+
+// Stacks `count` frames, `load` decoding the frame at an index. Frames are split between the
+// threads, which decode theirs concurrently; a mean never holds more than one frame per thread.
+pub fn stack<F>(count: usize, load: F, mode: StackMode, num_threads: usize) -> Result<Frame, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Sync,
+{
+    if count == 0 {
+        return Err("stack needs at least one frame".to_string());
+    }
+    // The first frame sets the size every other one must match
+    let first = load(0)?;
+    let dimensions = first.dimensions();
+    match mode {
+        StackMode::Mean => mean(count, first, &load, num_threads),
+        StackMode::Median => {
+            let mut frames = vec![first];
+            frames.extend(decode_all(1..count, &load, dimensions, num_threads)?);
+            Ok(median(&frames, num_threads))
+        }
+    }
+}
+
+// Each thread sums its frames into its own buffer, then the partial sums are reduced band by band
+fn mean<F>(count: usize, first: Frame, load: &F, num_threads: usize) -> Result<Frame, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Sync,
+{
+    let dimensions = first.dimensions();
+    let mut partials: Vec<Vec<u32>> = vec![first.as_raw().iter().map(|&value| value as u32).collect()];
+    let groups: Vec<_> = bands::split(count - 1, num_threads).into_iter().map(|group| group.start + 1..group.end + 1).collect();
+    let sums: Vec<Result<Vec<u32>, String>> = thread::scope(|scope| {
+        let handles: Vec<_> = groups.into_iter().map(|group| scope.spawn(move || {
+            let mut sum = vec![0u32; frame_len(dimensions)];
+            for index in group {
+                let frame = load(index)?;
+                check_dimensions(index, &frame, dimensions)?;
+                for (sum, &value) in sum.iter_mut().zip(frame.as_raw()) {
+                    *sum += value as u32;
+                }
+            }
+            Ok(sum)
+        })).collect();
+        handles.into_iter().map(|handle| handle.join().expect("Stacking thread panicked")).collect()
+    });
+    for sum in sums {
+        partials.push(sum?);
+    }
+
+    let mut data = vec![0u8; frame_len(dimensions)];
+    let row_len = dimensions.0 as usize * 4;
+    let count = count as u32;
+    workers::scope_each(bands::split_mut(&mut data, row_len, num_threads), |(rows, band)| {
+        let offset = rows.start * row_len;
+        for (i, value) in band.iter_mut().enumerate() {
+            let total: u32 = partials.iter().map(|sum| sum[offset + i]).sum();
+            *value = ((total + count / 2) / count) as u8;
+        }
+    });
+    Ok(ImageBuffer::from_raw(dimensions.0, dimensions.1, data).expect("Stacked buffer matches dimensions"))
+}
+
+fn frame_len((width, height): (u32, u32)) -> usize {
+    width as usize * height as usize * 4
+}
+
+// Decodes the frames of `indices` concurrently, each thread taking a run of them, kept in order
+fn decode_all<F>(indices: std::ops::Range<usize>, load: &F, dimensions: (u32, u32), num_threads: usize) -> Result<Vec<Frame>, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Sync,
+{
+    let groups: Vec<_> = bands::split(indices.len(), num_threads).into_iter().map(|group| group.start + indices.start..group.end + indices.start).collect();
+    let decoded: Vec<Result<Vec<_>, String>> = thread::scope(|scope| {
+        let handles: Vec<_> = groups.into_iter().map(|group| scope.spawn(move || {
+            group.map(|index| {
+                let frame = load(index)?;
+                check_dimensions(index, &frame, dimensions)?;
+                Ok(frame)
+            }).collect()
+        })).collect();
+        handles.into_iter().map(|handle| handle.join().expect("Decoding thread panicked")).collect()
+    });
+    let mut frames = Vec::new();
+    for group in decoded {
+        frames.extend(group?);
+    }
+    Ok(frames)
+}
+
+// The middle value of every channel, an even count taking the rounded mean of the two middle ones
+pub fn median_of(values: &mut [u8]) -> u8 {
+    let len = values.len();
+    let (below, &mut upper, _) = values.select_nth_unstable(len / 2);
+    if len % 2 == 1 {
+        return upper;
+    }
+    let lower = *below.iter().max().expect("An even count has a lower half");
+    (lower as u16 + upper as u16).div_ceil(2) as u8
+}
+
+fn median(frames: &[Frame], num_threads: usize) -> Frame {
+    let (width, height) = frames[0].dimensions();
+    let mut data = vec![0u8; frame_len((width, height))];
+    let row_len = width as usize * 4;
+    workers::scope_each(bands::split_mut(&mut data, row_len, num_threads), |(rows, band)| {
+        let offset = rows.start * row_len;
+        let mut values = vec![0u8; frames.len()];
+        for (i, value) in band.iter_mut().enumerate() {
+            for (slot, frame) in values.iter_mut().zip(frames) {
+                *slot = frame.as_raw()[offset + i];
+            }
+            *value = median_of(&mut values);
+        }
+    });
+    ImageBuffer::from_raw(width, height, data).expect("Stacked buffer matches dimensions")
+}
+
+// Radius the sharpness maps are blurred over, so the sharpest frame is chosen by region rather
+// than by single noisy pixels
+pub const FOCUS_RADIUS: i32 = 4;
+
+// Magnitude of the luma's Laplacian, clamped to 8 bits and written to every color channel so the
+// Gaussian blur can smooth it like any image
+pub fn laplacian_energy(frame: &Frame) -> Frame {
+    let (width, height) = frame.dimensions();
+    let luma = |x: i32, y: i32| channels::luma(&frame.get_pixel(x.clamp(0, width as i32 - 1) as u32, y.clamp(0, height as i32 - 1) as u32).0);
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i32, y as i32);
+        let laplacian = 4.0 * luma(x, y) - luma(x - 1, y) - luma(x + 1, y) - luma(x, y - 1) - luma(x, y + 1);
+        let energy = laplacian.abs().round().min(255.0) as u8;
+        Rgba([energy, energy, energy, 255])
+    })
+}
+
+// Merges frames focused at different depths into one that is sharp throughout. Frames are decoded
+// and their sharpness maps built concurrently, then every pixel is taken from the frame whose map
+// is highest there.
+pub fn focus_stack<F>(count: usize, load: F, num_threads: usize) -> Result<Frame, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Sync,
+{
+    if count == 0 {
+        return Err("focus-stack needs at least one frame".to_string());
+    }
+    let first = load(0)?;
+    let dimensions = first.dimensions();
+    let mut frames = vec![first];
+    frames.extend(decode_all(1..count, &load, dimensions, num_threads)?);
+
+    // Threads left over when there are fewer frames than threads go to each frame's blur
+    let groups = bands::split(count, num_threads);
+    let threads_per_frame = (num_threads / groups.len()).max(1);
+    let sharpness: Vec<Frame> = thread::scope(|scope| {
+        let handles: Vec<_> = groups.into_iter().map(|group| {
+            let frames = &frames;
+            scope.spawn(move || {
+                group.map(|index| blur::apply_gaussian_blur(&laplacian_energy(&frames[index]), FOCUS_RADIUS, threads_per_frame, FilterOptions::default()))
+                    .collect::<Vec<_>>()
+            })
+        }).collect();
+        handles.into_iter().flat_map(|handle| handle.join().expect("Sharpness thread panicked")).collect()
+    });
+
+    let mut data = vec![0u8; frame_len(dimensions)];
+    let row_len = dimensions.0 as usize * 4;
+    workers::scope_each(bands::split_mut(&mut data, row_len, num_threads), |(rows, band)| {
+        let offset = rows.start * row_len;
+        for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
+            let index = offset + i * 4;
+            // The first of equally sharp frames wins
+            let best = (0..frames.len()).rev().max_by_key(|&frame| sharpness[frame].as_raw()[index]).unwrap_or(0);
+            pixel.copy_from_slice(&frames[best].as_raw()[index..index + 4]);
+        }
+    });
+    Ok(ImageBuffer::from_raw(dimensions.0, dimensions.1, data).expect("Stacked buffer matches dimensions"))
+}
@@ -0,0 +1,116 @@
+use crate::animation::to_rgba;
+use crate::blur;
+use crate::cli::FilterOptions;
+use crate::convolve;
+use crate::kuwahara::{self, KuwaharaMode};
+use crate::motion_blur;
+use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+pub struct StreamStats {
+    pub width: u32,
+    pub height: u32,
+    pub bands: usize,
+    pub peak_window_rows: usize,
+}
+
+// Rows of context each band needs: the radius, plus the rows on each side a kernel or a non-local
+// means patch reaches, or the rows a motion blur's line spans. Anisotropic Kuwahara's ellipses
+// stretch to twice the radius, and their orientation comes from a structure tensor smoothed over
+// `TENSOR_RADIUS` rows around Sobel gradients of one more.
+pub fn overlap(operation: &str, radius: i32, filter: FilterOptions) -> usize {
+    match operation {
+        "blur" => blur::blur_radius(radius as usize, filter),
+        "kuwahara" if filter.kuwahara_mode == KuwaharaMode::Anisotropic => 2 * radius as usize + kuwahara::TENSOR_RADIUS + 1,
+        "sobel" => radius as usize + 1,
+        "emboss" => radius as usize + convolve::EMBOSS.reach(),
+        "edges" => radius as usize + convolve::LAPLACIAN.reach(),
+        "convolve" => radius as usize + filter.kernel.map_or(0, |kernel| kernel.reach()),
+        "nlmeans" => radius as usize + filter.patch_radius as usize,
+        "motion-blur" => motion_blur::overlap(radius as u32),
+        // The radius is a level count, and each pixel maps on its own
+        "posterize" => 0,
+        _ => radius as usize,
+    }
+}
+
+// Filters a PNG in horizontal bands without ever holding the whole image in memory.
+// Each band is filtered together with `overlap` rows of context above and below, which
+// is exact for neighborhood filters whose reach does not exceed `overlap` rows.
+pub fn process_stream<F>(
+    input: &str,
+    output: &str,
+    band_rows: usize,
+    overlap: usize,
+    filter: F,
+) -> Result<StreamStats, Box<dyn Error>>
+where
+    F: Fn(&ImageBuffer<Rgba<u8>, Vec<u8>>) -> ImageBuffer<Rgba<u8>, Vec<u8>>,
+{
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(input)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    if reader.info().interlaced {
+        return Err("Streaming requires a non-interlaced PNG input".into());
+    }
+
+    let width = reader.info().width;
+    let height = reader.info().height;
+    let (color_type, _) = reader.output_color_type();
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(output)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?.into_stream_writer()?;
+
+    let row_len = width as usize * 4;
+    let height = height as usize;
+    let band_rows = band_rows.max(1);
+
+    // Decoded rows [window_start, rows_read) kept as RGBA
+    let mut window = Vec::new();
+    let mut window_start = 0;
+    let mut rows_read = 0;
+    let mut stats = StreamStats {
+        width,
+        height: height as u32,
+        bands: 0,
+        peak_window_rows: 0,
+    };
+
+    let mut band_start = 0;
+    while band_start < height {
+        let band_end = (band_start + band_rows).min(height);
+
+        while rows_read < (band_end + overlap).min(height) {
+            let row = reader.next_row()?.ok_or("Unexpected end of image data")?;
+            window.extend_from_slice(&to_rgba(row.data(), color_type));
+            rows_read += 1;
+        }
+
+        let keep_from = band_start.saturating_sub(overlap);
+        if keep_from > window_start {
+            window.drain(..(keep_from - window_start) * row_len);
+            window_start = keep_from;
+        }
+
+        let window_rows = rows_read - window_start;
+        stats.peak_window_rows = stats.peak_window_rows.max(window_rows);
+
+        let band = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, window_rows as u32, window.clone())
+            .expect("Window buffer does not match band dimensions");
+        let filtered = filter(&band);
+
+        let offset = (band_start - window_start) * row_len;
+        writer.write_all(&filtered.as_raw()[offset..offset + (band_end - band_start) * row_len])?;
+
+        stats.bands += 1;
+        band_start = band_end;
+    }
+
+    writer.finish()?;
+    Ok(stats)
+}
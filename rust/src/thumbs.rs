@@ -0,0 +1,137 @@
+use crate::cli::FilterOptions;
+use crate::png_encoder;
+use crate::sharpen;
+use image::{imageops, ImageBuffer, ImageFormat, Rgba};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Strength of `--sharpen`, an unsharp mask that restores the crispness downscaling takes away
+const SHARPEN_AMOUNT: f32 = 0.5;
+
+pub struct ThumbStats {
+    pub sources: usize,
+    pub thumbnails: usize,
+}
+
+// Images in `dir` the decoder knows by extension, sorted so runs write in the same order
+pub fn list_sources(dir: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut sources: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+        .collect();
+    sources.sort();
+    Ok(sources)
+}
+
+// Largest size that fits a `size` square with the same aspect ratio; thumbnails never upscale
+pub fn fit(width: u32, height: u32, size: u32) -> (u32, u32) {
+    if width <= size && height <= size {
+        return (width, height);
+    }
+    let scale = size as f64 / width.max(height) as f64;
+    (((width as f64 * scale).round() as u32).max(1), ((height as f64 * scale).round() as u32).max(1))
+}
+
+// Adds back the difference from a radius 1 blur; alpha is kept
+pub fn sharpen(img: &Frame) -> Frame {
+    sharpen::apply_unsharp_mask(img, 1, SHARPEN_AMOUNT, 0, 1, FilterOptions::default())
+}
+
+pub fn thumbnail(img: &Frame, size: u32, sharpened: bool) -> Frame {
+    let (width, height) = fit(img.width(), img.height(), size);
+    let resized = if (width, height) == img.dimensions() { img.clone() } else { imageops::resize(img, width, height, imageops::FilterType::Lanczos3) };
+    if sharpened { sharpen(&resized) } else { resized }
+}
+
+// `<output>/<stem>_<size>.<extension of the source>`
+pub fn thumbnail_path(output: &Path, source: &Path, size: u32) -> PathBuf {
+    let stem = source.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let extension = source.extension().map(|extension| extension.to_string_lossy()).unwrap_or_default();
+    output.join(format!("{}_{}.{}", stem, size, extension))
+}
+
+// Decoders pull sources from a shared queue, decode each once and resize it to every size on its
+// own thread. Thumbnails go through a channel holding at most `num_threads` of them to as many
+// encoders, so slow encoding holds the decoders back instead of piling images up in memory.
+pub fn generate(input: &str, output: &str, sizes: &[u32], sharpened: bool, num_threads: usize) -> Result<ThumbStats, Box<dyn Error>> {
+    let sources = list_sources(input)?;
+    let output = Path::new(output);
+    fs::create_dir_all(output)?;
+    let num_threads = num_threads.max(1);
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::sync_channel::<(PathBuf, Frame)>(num_threads);
+    let receiver = Mutex::new(receiver);
+    let written = AtomicUsize::new(0);
+    // The first failure; later sources are skipped and queued thumbnails dropped
+    let failure: Mutex<Option<String>> = Mutex::new(None);
+    let fail = |message: String| {
+        failure.lock().expect("Failure lock poisoned").get_or_insert(message);
+    };
+    let failed = || failure.lock().expect("Failure lock poisoned").is_some();
+
+    thread::scope(|scope| {
+        for _ in 0..num_threads {
+            let sender = sender.clone();
+            scope.spawn(|| {
+                let sender = sender;
+                while let Some(source) = sources.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if failed() {
+                        return;
+                    }
+                    let img = match image::open(source) {
+                        Ok(img) => img.to_rgba8(),
+                        Err(e) => return fail(format!("{}: {}", source.display(), e)),
+                    };
+                    let thumbnails: Vec<Frame> = thread::scope(|scope| {
+                        let handles: Vec<_> = sizes.iter().map(|&size| {
+                            let img = &img;
+                            scope.spawn(move || thumbnail(img, size, sharpened))
+                        }).collect();
+                        handles.into_iter().map(|handle| handle.join().expect("Resize thread panicked")).collect()
+                    });
+                    for (&size, thumbnail) in sizes.iter().zip(thumbnails) {
+                        sender.send((thumbnail_path(output, source, size), thumbnail)).expect("Encoders run until the channel closes");
+                    }
+                }
+            });
+        }
+        // Encoders see the channel close once every decoder has dropped its sender
+        drop(sender);
+
+        for _ in 0..num_threads {
+            scope.spawn(|| loop {
+                let Ok((path, thumbnail)) = receiver.lock().expect("Receiver lock poisoned").recv() else {
+                    return;
+                };
+                if failed() {
+                    continue;
+                }
+                let path_str = path.to_string_lossy();
+                let saved = if png_encoder::is_png(&path_str) {
+                    png_encoder::save(&path_str, &thumbnail, 1).map_err(|e| e.to_string())
+                } else {
+                    thumbnail.save(&path).map_err(|e| e.to_string())
+                };
+                match saved {
+                    Ok(()) => {
+                        written.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => fail(format!("{}: {}", path.display(), e)),
+                }
+            });
+        }
+    });
+
+    if let Some(message) = failure.into_inner().expect("Failure lock poisoned") {
+        return Err(message.into());
+    }
+    Ok(ThumbStats { sources: sources.len(), thumbnails: written.load(Ordering::Relaxed) })
+}
@@ -0,0 +1,155 @@
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for TimingFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(TimingFormat::Table),
+            "json" => Ok(TimingFormat::Json),
+            other => Err(format!("Unknown timing format: {}", other)),
+        }
+    }
+}
+
+pub struct WorkerTiming {
+    // Filter pass the worker belonged to, e.g. "blur-h"
+    pub pass: &'static str,
+    pub worker: usize,
+    pub rows: Range<usize>,
+    pub start: Instant,
+    pub end: Instant,
+    // Time spent filtering its rows, without waiting for the output lock or copying into it
+    pub busy: Duration,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDS: Mutex<Vec<WorkerTiming>> = Mutex::new(Vec::new());
+
+// Workers only record their timings once this is called
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub struct WorkerClock {
+    pass: &'static str,
+    worker: usize,
+    rows: Range<usize>,
+    // Only read while recording, so workers never need a clock otherwise
+    start: Option<Instant>,
+    busy: Option<Duration>,
+}
+
+impl WorkerClock {
+    pub fn start(pass: &'static str, worker: usize, rows: Range<usize>) -> WorkerClock {
+        WorkerClock {
+            pass,
+            worker,
+            rows,
+            start: ENABLED.load(Ordering::Relaxed).then(Instant::now),
+            busy: None,
+        }
+    }
+
+    // Marks the end of the worker's own computation, before it publishes the results
+    pub fn computed(&mut self) {
+        self.busy = self.start.map(|start| start.elapsed());
+    }
+
+    pub fn finish(self) {
+        let Some(start) = self.start else {
+            return;
+        };
+        let end = Instant::now();
+        let busy = self.busy.unwrap_or(end - start);
+        RECORDS.lock().unwrap().push(WorkerTiming {
+            pass: self.pass,
+            worker: self.worker,
+            rows: self.rows,
+            start,
+            end,
+            busy,
+        });
+    }
+}
+
+// Drains the timings recorded so far, grouped by pass in the order the passes ran
+pub fn take() -> Vec<WorkerTiming> {
+    let mut records = std::mem::take(&mut *RECORDS.lock().unwrap());
+    records.sort_by_key(|record| record.start);
+
+    let mut passes = Vec::new();
+    for record in &records {
+        if !passes.contains(&record.pass) {
+            passes.push(record.pass);
+        }
+    }
+    records.sort_by_key(|record| (passes.iter().position(|&pass| pass == record.pass), record.worker));
+    records
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Slowest worker's busy time relative to the mean, per pass
+fn imbalance(records: &[WorkerTiming], pass: &str) -> f64 {
+    let busy: Vec<f64> = records.iter().filter(|r| r.pass == pass).map(|r| millis(r.busy)).collect();
+    let mean = busy.iter().sum::<f64>() / busy.len() as f64;
+    let max = busy.iter().cloned().fold(0.0, f64::max);
+    if mean > 0.0 { max / mean } else { 1.0 }
+}
+
+pub fn format_table(records: &[WorkerTiming]) -> String {
+    let Some(epoch) = records.iter().map(|r| r.start).min() else {
+        return "No worker timings recorded".to_string();
+    };
+
+    let mut out = format!("{:<10} {:>6} {:>13} {:>10} {:>10} {:>10}\n", "pass", "worker", "rows", "start ms", "end ms", "busy ms");
+    for (i, record) in records.iter().enumerate() {
+        out += &format!(
+            "{:<10} {:>6} {:>13} {:>10.2} {:>10.2} {:>10.2}\n",
+            record.pass,
+            record.worker,
+            format!("{}-{}", record.rows.start, record.rows.end),
+            millis(record.start - epoch),
+            millis(record.end - epoch),
+            millis(record.busy),
+        );
+        if records.get(i + 1).map(|next| next.pass) != Some(record.pass) {
+            out += &format!("{:<10} imbalance {:.2}x (slowest busy / mean busy)\n", record.pass, imbalance(records, record.pass));
+        }
+    }
+    out.trim_end().to_string()
+}
+
+pub fn format_json(records: &[WorkerTiming]) -> String {
+    let epoch = records.iter().map(|r| r.start).min();
+    let workers: Vec<String> = records
+        .iter()
+        .map(|record| {
+            let epoch = epoch.unwrap_or(record.start);
+            format!(
+                "{{\"pass\":\"{}\",\"worker\":{},\"rows\":[{},{}],\"start_ms\":{:.3},\"end_ms\":{:.3},\"busy_ms\":{:.3}}}",
+                record.pass,
+                record.worker,
+                record.rows.start,
+                record.rows.end,
+                millis(record.start - epoch),
+                millis(record.end - epoch),
+                millis(record.busy),
+            )
+        })
+        .collect();
+    format!("{{\"workers\":[{}]}}", workers.join(","))
+}
@@ -0,0 +1,168 @@
+use crate::bands;
+use crate::progress;
+use crate::srgb;
+use crate::workers;
+use image::codecs::hdr::HdrDecoder;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+
+// Brightness the log-average luminance is mapped to, the middle gray of Reinhard's paper
+pub const KEY: f64 = 0.18;
+// Keeps the logarithm of black pixels finite
+const DELTA: f64 = 1e-4;
+
+// Curve taking scene luminance to display values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Operator {
+    // Extended Reinhard, the brightest pixel of the image becoming white
+    #[default]
+    Reinhard,
+    // Narkowicz' fit of the ACES filmic curve, per channel, with a toe and a soft shoulder
+    Aces,
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reinhard" => Ok(Operator::Reinhard),
+            "aces" => Ok(Operator::Aces),
+            _ => Err(format!("Unknown tone mapping operator '{}', expected 'reinhard' or 'aces'", s)),
+        }
+    }
+}
+
+// Luminance of the image, summed over a run of pixels so that parts can be merged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub log_sum: f64,
+    pub max: f64,
+    pub count: usize,
+}
+
+impl Stats {
+    // Statistics of a run of linear RGBA pixels
+    pub fn of(pixels: &[f32]) -> Self {
+        let mut stats = Stats { log_sum: 0.0, max: 0.0, count: 0 };
+        for pixel in pixels.chunks_exact(4) {
+            let l = luminance(pixel);
+            stats.log_sum += (DELTA + l).ln();
+            stats.max = stats.max.max(l);
+            stats.count += 1;
+        }
+        stats
+    }
+
+    // Statistics of the whole image from those of its parts
+    pub fn merge(partials: &[Stats]) -> Self {
+        partials.iter().fold(Stats { log_sum: 0.0, max: 0.0, count: 0 }, |total, part| Stats {
+            log_sum: total.log_sum + part.log_sum,
+            max: total.max.max(part.max),
+            count: total.count + part.count,
+        })
+    }
+
+    // Geometric mean of the luminance, a measure of the scene's brightness that one hot pixel does not skew
+    pub fn log_average(&self) -> f64 {
+        if self.count == 0 {
+            return 1.0;
+        }
+        (self.log_sum / self.count as f64).exp()
+    }
+}
+
+// Rec. 709 luminance of linear RGB; negative and NaN values, which some HDR files hold, count as black
+pub fn luminance(pixel: &[f32]) -> f64 {
+    let [r, g, b] = [0, 1, 2].map(|ch| (pixel[ch] as f64).max(0.0));
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+// Opens an image keeping its full range. Radiance files are decoded to floats here, since the
+// generic loader would clip them to 8 bits; OpenEXR and everything else go through it.
+pub fn open(path: &str) -> image::ImageResult<DynamicImage> {
+    if !Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("hdr")) {
+        return image::open(path);
+    }
+    let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+    let meta = decoder.metadata();
+    let data = decoder.read_image_hdr()?.into_iter().flat_map(|pixel| pixel.0).collect();
+    Ok(DynamicImage::ImageRgb32F(ImageBuffer::from_raw(meta.width, meta.height, data).expect("Radiance pixels match dimensions")))
+}
+
+// Linear light of any input: float formats such as Radiance HDR and OpenEXR are linear already,
+// while 8- and 16-bit images are sRGB encoded and are decoded first
+pub fn linear(img: &DynamicImage) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+    let mut pixels = img.to_rgba32f();
+    if !matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)) {
+        for pixel in pixels.pixels_mut() {
+            for value in &mut pixel.0[..3] {
+                *value = if *value <= 0.04045 { *value / 12.92 } else { ((*value + 0.055) / 1.055).powf(2.4) };
+            }
+        }
+    }
+    pixels
+}
+
+// Multiplier bringing the log-average luminance to the key, `exposure` stops brighter or darker
+pub fn exposure_scale(stats: &Stats, exposure: f64) -> f64 {
+    KEY * exposure.exp2() / stats.log_average()
+}
+
+// Display value of one linear pixel, sRGB encoded. `scale` is the exposure and `white` the scaled
+// luminance Reinhard maps to 1; ACES clips on its own.
+pub fn map_pixel(pixel: &[f32], operator: Operator, scale: f64, white: f64) -> [u8; 4] {
+    let lut = srgb::lut();
+    let rgb: [f64; 3] = match operator {
+        Operator::Reinhard => {
+            let l = luminance(pixel) * scale;
+            if l <= 0.0 {
+                [0.0; 3]
+            } else {
+                let mapped = if white > 0.0 { l * (1.0 + l / (white * white)) / (1.0 + l) } else { l / (1.0 + l) };
+                // Scaling the color by the change of luminance keeps its hue and saturation
+                [0, 1, 2].map(|ch| (pixel[ch] as f64).max(0.0) * scale * mapped / l)
+            }
+        }
+        Operator::Aces => [0, 1, 2].map(|ch| {
+            let x = (pixel[ch] as f64).max(0.0) * scale;
+            x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)
+        }),
+    };
+    let alpha = (pixel[3] as f64).clamp(0.0, 1.0) * 255.0;
+    [lut.encode(rgb[0].clamp(0.0, 1.0) * 255.0), lut.encode(rgb[1].clamp(0.0, 1.0) * 255.0), lut.encode(rgb[2].clamp(0.0, 1.0) * 255.0), alpha.round() as u8]
+}
+
+// Tone mapping of linear, possibly HDR, pixels to 8-bit sRGB. The curve depends on the whole
+// image's luminance, so each thread first reduces its band to a few sums; once all have finished
+// they are merged and a second pass over the same bands maps every pixel with the exposure and
+// white point they give.
+pub fn apply_tonemap(src: &ImageBuffer<Rgba<f32>, Vec<f32>>, operator: Operator, exposure: f64, num_threads: usize) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    // Both passes
+    progress::expect(2 * height as usize);
+
+    let bands = bands::split(height as usize, num_threads);
+    let mut partials = vec![Stats { log_sum: 0.0, max: 0.0, count: 0 }; bands.len()];
+    workers::scope_each(bands.into_iter().zip(partials.iter_mut()), |(rows, partial)| {
+        *partial = Stats::of(&src.as_raw()[rows.start * row_len..rows.end * row_len]);
+        progress::advance(rows.len());
+    });
+    let stats = tracing::info_span!("merge", parts = partials.len()).in_scope(|| Stats::merge(&partials));
+    let scale = exposure_scale(&stats, exposure);
+    let white = stats.max * scale;
+
+    let mut result = ImageBuffer::new(width, height);
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        let pixels = &src.as_raw()[rows.start * row_len..rows.end * row_len];
+        for (out, pixel) in band.chunks_exact_mut(4).zip(pixels.chunks_exact(4)) {
+            out.copy_from_slice(&map_pixel(pixel, operator, scale, white));
+        }
+        progress::advance(rows.len());
+    });
+    result
+}
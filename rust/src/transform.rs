@@ -0,0 +1,84 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::srgb;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+
+// Rotation by `angle` degrees counterclockwise, scaling by `scale` and then a shift by
+// `translate` pixels, all about the image's center. The output keeps the input's size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    center: (f64, f64),
+    sin: f64,
+    cos: f64,
+    scale: f64,
+    translate: (f64, f64),
+}
+
+impl Affine {
+    pub fn new(width: u32, height: u32, filter: FilterOptions) -> Self {
+        let (sin, cos) = filter.angle.to_radians().sin_cos();
+        Affine { center: (width as f64 / 2.0, height as f64 / 2.0), sin, cos, scale: filter.scale, translate: filter.translate }
+    }
+
+    // Point of the source that lands on the center of output pixel (x, y), in the coordinates
+    // bilinear sampling uses, where pixel centers are whole numbers. Rows grow downward, so
+    // undoing a counterclockwise turn on screen is the matrix [cos -sin; sin cos].
+    pub fn source(&self, x: u32, y: u32) -> (f64, f64) {
+        let dx = (x as f64 + 0.5 - self.center.0 - self.translate.0) / self.scale;
+        let dy = (y as f64 + 0.5 - self.center.1 - self.translate.1) / self.scale;
+        (self.center.0 + self.cos * dx - self.sin * dy - 0.5, self.center.1 + self.sin * dx + self.cos * dy - 0.5)
+    }
+}
+
+// Bilinear sample at a point between pixels. Taps past the border are transparent, and colors
+// are weighted by alpha so that the edges of the image fade out instead of darkening.
+pub fn sample(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: f64, y: f64, linear: bool) -> Rgba<u8> {
+    let lut = srgb::lut();
+    let (width, height) = src.dimensions();
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut colors = [0.0; 3];
+    let mut alpha = 0.0;
+    for (sx, sy, weight) in [(x0, y0, (1.0 - fx) * (1.0 - fy)), (x0 + 1.0, y0, fx * (1.0 - fy)), (x0, y0 + 1.0, (1.0 - fx) * fy), (x0 + 1.0, y0 + 1.0, fx * fy)] {
+        if sx < 0.0 || sy < 0.0 || sx >= width as f64 || sy >= height as f64 || weight == 0.0 {
+            continue;
+        }
+        let pixel = src.get_pixel(sx as u32, sy as u32);
+        let covered = pixel[3] as f64 * weight;
+        for (color, &value) in colors.iter_mut().zip(&pixel.0[..3]) {
+            *color += if linear { lut.decode(value) } else { value as f64 } * covered;
+        }
+        alpha += covered;
+    }
+    if alpha == 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let encode = |value: f64| if linear { lut.encode(value / alpha) } else { (value / alpha).round() as u8 };
+    Rgba([encode(colors[0]), encode(colors[1]), encode(colors[2]), alpha.round() as u8])
+}
+
+// Rotation, scaling and translation by inverse mapping: each output pixel gathers from the point
+// of the source that lands on it, so every output pixel is written exactly once and the bands of
+// output rows share nothing, though each reads wherever its rows came from. What no source pixel
+// covers is left transparent.
+pub fn apply_transform(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (width, height) = src.dimensions();
+    let affine = Affine::new(width, height, filter);
+    let mut result = ImageBuffer::new(width, height);
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    workers::scope_each(bands::split_mut(&mut result, row_len, num_threads), |(rows, band)| {
+        for (y, row) in rows.clone().zip(band.chunks_exact_mut(row_len)) {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let (sx, sy) = affine.source(x as u32, y as u32);
+                pixel.copy_from_slice(&sample(src, sx, sy, filter.linear).0);
+            }
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(src, &mut result, filter.channels, num_threads);
+    result
+}
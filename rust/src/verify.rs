@@ -0,0 +1,163 @@
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::lut::Lut;
+use crate::reference;
+use image::{ImageBuffer, ImageFormat, Rgba};
+use std::error::Error;
+use std::fs;
+use std::process::{Command, Stdio};
+
+// Mismatching pixel coordinates listed in the report
+pub const MAX_REPORTED: usize = 10;
+// Pixels `--verify` recomputes with the serial reference; smaller images are checked whole
+pub const SAMPLE_PIXELS: usize = 4096;
+
+pub enum Backend {
+    // The filters built into this binary
+    InProcess,
+    // An output rendered earlier, e.g. by the C or Go implementation
+    Reference(String),
+    // Another filter binary taking `<operation> <input> <output> <radius> <workers>`
+    Command(String),
+}
+
+impl Backend {
+    // 'self' selects this binary, image paths are references and anything else is run as a program
+    pub fn parse(spec: &str) -> Backend {
+        if spec == "self" {
+            Backend::InProcess
+        } else if ImageFormat::from_path(spec).is_ok() {
+            Backend::Reference(spec.to_string())
+        } else {
+            Backend::Command(spec.to_string())
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Backend::InProcess => "threads (this binary)".to_string(),
+            Backend::Reference(path) => format!("reference {}", path),
+            Backend::Command(program) => format!("command {}", program),
+        }
+    }
+}
+
+pub struct Comparison {
+    pub max_diff: [u8; 4],
+    pub mismatches: usize,
+    pub first_mismatches: Vec<(u32, u32)>,
+}
+
+impl Comparison {
+    fn new() -> Self {
+        Comparison {
+            max_diff: [0; 4],
+            mismatches: 0,
+            first_mismatches: Vec::new(),
+        }
+    }
+
+    // Counts the pixel as a mismatch when any channel differs by more than `tolerance`
+    fn add(&mut self, x: u32, y: u32, a: &Rgba<u8>, b: &Rgba<u8>, tolerance: u8) {
+        let mut mismatch = false;
+        for c in 0..4 {
+            let diff = a[c].abs_diff(b[c]);
+            self.max_diff[c] = self.max_diff[c].max(diff);
+            mismatch |= diff > tolerance;
+        }
+        if mismatch {
+            self.mismatches += 1;
+            if self.first_mismatches.len() < MAX_REPORTED {
+                self.first_mismatches.push((x, y));
+            }
+        }
+    }
+}
+
+// Runs another implementation on the input and reads back what it wrote
+pub fn run_command(program: &str, operation: &str, input_path: &str, operand: &str, num_workers: usize, index: usize) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    let output_path = std::env::temp_dir().join(format!("verify_{}_{}.png", std::process::id(), index));
+    let status = Command::new(program)
+        .arg(operation)
+        .arg(input_path)
+        .arg(&output_path)
+        .arg(operand)
+        .arg(num_workers.to_string())
+        .stdout(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", program, status).into());
+    }
+
+    let output = image::open(&output_path).map(|img| img.to_rgba8());
+    let _ = fs::remove_file(&output_path);
+    Ok(output?)
+}
+
+// Counts pixels whose channels differ by more than `tolerance`
+pub fn compare(a: &ImageBuffer<Rgba<u8>, Vec<u8>>, b: &ImageBuffer<Rgba<u8>, Vec<u8>>, tolerance: u8) -> Result<Comparison, String> {
+    if a.dimensions() != b.dimensions() {
+        return Err(format!("Dimensions differ: {}x{} vs {}x{}", a.width(), a.height(), b.width(), b.height()));
+    }
+
+    let mut comparison = Comparison::new();
+    for ((x, y, pa), pb) in a.enumerate_pixels().zip(b.pixels()) {
+        comparison.add(x, y, pa, pb, tolerance);
+    }
+
+    Ok(comparison)
+}
+
+// Every pixel of a small image, otherwise `SAMPLE_PIXELS` distinct ones picked at random
+pub fn sample_points(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let pixels = width as usize * height as usize;
+    if pixels <= SAMPLE_PIXELS {
+        return (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).collect();
+    }
+    rand::seq::index::sample(&mut rand::thread_rng(), pixels, SAMPLE_PIXELS)
+        .into_iter()
+        .map(|index| ((index % width as usize) as u32, (index / width as usize) as u32))
+        .collect()
+}
+
+// Recomputes the given pixels of a filtered image one at a time with the serial reference filter
+pub fn check_reference(
+    operation: &str,
+    src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    output: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    radius: i32,
+    filter: FilterOptions,
+    points: &[(u32, u32)],
+    tolerance: u8,
+) -> Comparison {
+    // Equalization and auto-levels depend on the histogram of the whole image, dithering on every
+    // pixel before and quantization on the palette of all of them, so their tables, the dithered
+    // image and the palette are made once here rather than again for every pixel
+    let tables = match operation {
+        "histeq" => Some(reference::equalization_tables(src)),
+        "autolevel" => Some(reference::autolevel_tables(src, filter.clip)),
+        _ => None,
+    };
+    let dithered = (operation == "dither").then(|| reference::dither(src, radius as u32));
+    let centroids = (operation == "quantize").then(|| reference::kmeans(src, radius as usize));
+    let mut comparison = Comparison::new();
+    for &(x, y) in points {
+        let expected = match (&tables, &dithered, &centroids) {
+            (Some(tables), _, _) => Rgba(channels::select(src.get_pixel(x, y).0, reference::histeq_pixel(src, x, y, tables).0, filter.channels)),
+            (_, Some(dithered), _) => Rgba(channels::select(src.get_pixel(x, y).0, dithered.get_pixel(x, y).0, filter.channels)),
+            (_, _, Some(centroids)) => Rgba(channels::select(src.get_pixel(x, y).0, reference::quantize_pixel(src, x, y, centroids).0, filter.channels)),
+            _ => reference::filter_pixel(operation, src, x, y, radius, filter),
+        };
+        comparison.add(x, y, output.get_pixel(x, y), &expected, tolerance);
+    }
+    comparison
+}
+
+// `check_reference` for the `lut` operation, whose table FilterOptions does not carry
+pub fn check_lut(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, output: &ImageBuffer<Rgba<u8>, Vec<u8>>, lut: &Lut, filter: FilterOptions, points: &[(u32, u32)], tolerance: u8) -> Comparison {
+    let mut comparison = Comparison::new();
+    for &(x, y) in points {
+        comparison.add(x, y, output.get_pixel(x, y), &reference::lut_pixel(src, x, y, lut, filter), tolerance);
+    }
+    comparison
+}
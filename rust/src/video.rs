@@ -0,0 +1,126 @@
+use image::{ImageBuffer, Rgba};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    // Frame rate as reported by ffprobe, e.g. "30000/1001"
+    pub frame_rate: String,
+}
+
+pub fn probe(path: &str) -> Result<VideoInfo, Box<dyn Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,r_frame_rate"])
+        .args(["-of", "csv=p=0", path])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    let text = String::from_utf8(output.stdout)?;
+    let fields: Vec<&str> = text.trim().split(',').collect();
+    if fields.len() < 3 {
+        return Err(format!("Unexpected ffprobe output: {}", text.trim()).into());
+    }
+
+    Ok(VideoInfo {
+        width: fields[0].parse()?,
+        height: fields[1].parse()?,
+        frame_rate: fields[2].to_string(),
+    })
+}
+
+// Fills `buf` with the next frame, returning false on a clean end of stream
+fn read_frame(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool, Box<dyn Error>> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err("Truncated frame in decoder output".into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+// Decodes `input` with ffmpeg, filters up to `frames_in_flight` frames concurrently and
+// re-encodes the result (keeping the source audio) to `output`. Returns the frame count.
+pub fn process_video<F>(
+    input: &str,
+    output: &str,
+    info: &VideoInfo,
+    frames_in_flight: usize,
+    filter: F,
+) -> Result<usize, Box<dyn Error>>
+where
+    F: Fn(&ImageBuffer<Rgba<u8>, Vec<u8>>) -> ImageBuffer<Rgba<u8>, Vec<u8>> + Sync,
+{
+    let size = format!("{}x{}", info.width, info.height);
+    let frame_len = info.width as usize * info.height as usize * 4;
+
+    let mut decoder = Command::new("ffmpeg")
+        .args(["-v", "error", "-i", input])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let mut encoder = Command::new("ffmpeg")
+        .args(["-v", "error", "-y"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-s", &size, "-r", &info.frame_rate, "-i", "-"])
+        .args(["-i", input, "-map", "0:v:0", "-map", "1:a?", "-c:a", "copy"])
+        .args(["-pix_fmt", "yuv420p", "-shortest", output])
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    let mut frames_out = decoder.stdout.take().ok_or("ffmpeg decoder has no stdout")?;
+    let mut frames_in = encoder.stdin.take().ok_or("ffmpeg encoder has no stdin")?;
+    let filter = &filter;
+
+    let frame_count = thread::scope(|scope| -> Result<usize, Box<dyn Error>> {
+        let mut pending: VecDeque<thread::ScopedJoinHandle<ImageBuffer<Rgba<u8>, Vec<u8>>>> = VecDeque::new();
+        let mut frame_count = 0;
+
+        loop {
+            let mut buf = vec![0u8; frame_len];
+            if !read_frame(&mut frames_out, &mut buf)? {
+                break;
+            }
+
+            // Keep the pipeline bounded: write the oldest frame before admitting a new one
+            if pending.len() == frames_in_flight.max(1) {
+                let handle = pending.pop_front().unwrap();
+                frames_in.write_all(handle.join().unwrap().as_raw())?;
+            }
+
+            let frame = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(info.width, info.height, buf)
+                .expect("Frame buffer does not match video dimensions");
+            pending.push_back(scope.spawn(move || filter(&frame)));
+            frame_count += 1;
+        }
+
+        for handle in pending {
+            frames_in.write_all(handle.join().unwrap().as_raw())?;
+        }
+
+        Ok(frame_count)
+    })?;
+
+    drop(frames_in);
+
+    if !decoder.wait()?.success() {
+        return Err("ffmpeg decoder exited with an error".into());
+    }
+    if !encoder.wait()?.success() {
+        return Err("ffmpeg encoder exited with an error".into());
+    }
+
+    Ok(frame_count)
+}
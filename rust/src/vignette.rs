@@ -0,0 +1,48 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::srgb;
+use crate::workers;
+use image::{ImageBuffer, Rgba};
+
+// Falloff when `--falloff` is not given: the darkening grows with the square of the distance
+pub const DEFAULT_FALLOFF: f64 = 2.0;
+
+// Brightness left at (x, y): 1 at the center, falling to 1 - strength in the corners. The
+// distance is measured from pixel centers and scaled by half the diagonal, so the gradient is
+// round whatever the aspect ratio and reaches the corners exactly.
+pub fn factor(x: u32, y: u32, width: u32, height: u32, strength: f64, falloff: f64) -> f64 {
+    let dx = x as f64 + 0.5 - width as f64 / 2.0;
+    let dy = y as f64 + 0.5 - height as f64 / 2.0;
+    let half_diagonal = (width as f64).hypot(height as f64) / 2.0;
+    let distance = (dx.hypot(dy) / half_diagonal).min(1.0);
+    1.0 - strength * distance.powf(falloff)
+}
+
+// Color channels scaled by the factor, in linear light with `--linear`; alpha is kept
+pub fn darken(pixel: [u8; 4], factor: f64, linear: bool) -> [u8; 4] {
+    let lut = srgb::lut();
+    let scale = |value: u8| if linear { lut.encode(lut.decode(value) * factor) } else { (value as f64 * factor).round() as u8 };
+    [scale(pixel[0]), scale(pixel[1]), scale(pixel[2]), pixel[3]]
+}
+
+// Vignette: the image darkens with the distance from its center, `strength` being the fraction
+// taken off in the corners and `falloff` the power of the distance, higher keeping more of the
+// middle bright. Each pixel depends only on its own position, with no neighborhood to read, so
+// one band of rows per thread is all there is to it.
+pub fn apply_vignette(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, strength: f64, num_threads: usize, filter: FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let mut result = img.clone();
+    let (width, height) = img.dimensions();
+    progress::expect(height as usize);
+    workers::scope_each(bands::split_mut(&mut result, width as usize * 4, num_threads), |(rows, band)| {
+        for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
+            let (x, y) = ((i % width as usize) as u32, (rows.start + i / width as usize) as u32);
+            let factor = factor(x, y, width, height, strength, filter.falloff);
+            pixel.copy_from_slice(&darken([pixel[0], pixel[1], pixel[2], pixel[3]], factor, filter.linear));
+        }
+        progress::advance(rows.len());
+    });
+    channels::restore(img, &mut result, filter.channels, num_threads);
+    result
+}
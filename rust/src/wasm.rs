@@ -0,0 +1,45 @@
+// JavaScript bindings, built with
+// `cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+// followed by wasm-bindgen (see the README). Each call filters on the calling thread;
+// web/ spreads an image over Web Workers by giving each a band with `radius` rows of overlap.
+
+use crate::blur;
+use crate::cli::FilterOptions;
+use crate::kuwahara;
+use crate::size;
+use image::{ImageBuffer, Rgba};
+use js_sys::Uint8ClampedArray;
+use wasm_bindgen::prelude::*;
+use web_sys::ImageData;
+
+type Filter = fn(&ImageBuffer<Rgba<u8>, Vec<u8>>, i32, usize, FilterOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+fn filter_for(operation: &str) -> Filter {
+    match operation {
+        "kuwahara" => kuwahara::apply_kuwahara_filter,
+        _ => blur::apply_gaussian_blur,
+    }
+}
+
+fn filter_image_data(image: &ImageData, radius: i32, operation: &str) -> Result<ImageData, JsValue> {
+    if radius < 0 {
+        return Err(JsValue::from_str("radius must not be negative"));
+    }
+    let (width, height) = (image.width(), image.height());
+    // wasm32 addresses 4 GiB at most, less than a large canvas needs for the summed-area table
+    size::check(operation, width, height, FilterOptions::default()).map_err(|message| JsValue::from_str(&message))?;
+    let img = ImageBuffer::from_raw(width, height, image.data().0).ok_or_else(|| JsValue::from_str("ImageData does not match its dimensions"))?;
+    let result = filter_for(operation)(&img, radius, 1, FilterOptions::default());
+    // Copied out of wasm memory, which would otherwise back the ImageData and be freed on return
+    ImageData::new_with_js_u8_clamped_array_and_sh(&Uint8ClampedArray::from(result.as_raw().as_slice()), width, height)
+}
+
+#[wasm_bindgen]
+pub fn blur(image: &ImageData, radius: i32) -> Result<ImageData, JsValue> {
+    filter_image_data(image, radius, "blur")
+}
+
+#[wasm_bindgen]
+pub fn kuwahara(image: &ImageData, radius: i32) -> Result<ImageData, JsValue> {
+    filter_image_data(image, radius, "kuwahara")
+}
@@ -0,0 +1,51 @@
+// Filter workers run on their own threads, except on WebAssembly, which has no threads:
+// there each worker runs to completion on the calling thread, and browsers get their
+// parallelism from Web Workers that each filter a band of the image instead (see web/).
+use std::thread;
+
+pub enum Worker<T> {
+    #[cfg(not(target_arch = "wasm32"))]
+    Thread(thread::JoinHandle<T>),
+    #[cfg(target_arch = "wasm32")]
+    Done(T),
+}
+
+impl<T> Worker<T> {
+    pub fn join(self) -> thread::Result<T> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Worker::Thread(handle) => handle.join(),
+            #[cfg(target_arch = "wasm32")]
+            Worker::Done(value) => Ok(value),
+        }
+    }
+}
+
+pub fn spawn<F, T>(work: F) -> Worker<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    return Worker::Thread(thread::spawn(work));
+    #[cfg(target_arch = "wasm32")]
+    return Worker::Done(work());
+}
+
+// Runs `work` once per item, each on its own scoped thread, and returns when all are done
+pub fn scope_each<I, F>(items: I, work: F)
+where
+    I: IntoIterator,
+    I::Item: Send,
+    F: Fn(I::Item) + Sync,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    thread::scope(|scope| {
+        for item in items {
+            let work = &work;
+            scope.spawn(move || work(item));
+        }
+    });
+    #[cfg(target_arch = "wasm32")]
+    items.into_iter().for_each(work);
+}
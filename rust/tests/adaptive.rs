@@ -0,0 +1,73 @@
+// Adaptive Kuwahara: a Sobel pass maps every pixel to a radius between `--min-radius` and the
+// given one, and the filter pass reads that map, so edges keep their detail and flat areas smooth.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::colorspace::ColorSpace;
+use rust_filter::kuwahara::{self, KuwaharaMode};
+use std::path::PathBuf;
+
+mod common;
+
+fn adaptive(min_radius: u32) -> FilterOptions {
+    FilterOptions { kuwahara_mode: KuwaharaMode::Adaptive, min_radius, ..FilterOptions::default() }
+}
+
+// Black and white squares two pixels wide: an edge at every pixel
+fn checker() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(20, 20, |x, y| if (x / 2 + y / 2) % 2 == 0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) })
+}
+
+#[test]
+fn radius_falls_with_edge_strength() {
+    assert_eq!(kuwahara::adaptive_radius(0, 1, 8), 8);
+    assert_eq!(kuwahara::adaptive_radius(255, 1, 8), 1);
+    assert_eq!(kuwahara::adaptive_radius(128, 2, 10), 6);
+    // A minimum above the radius leaves the radius everywhere
+    assert_eq!(kuwahara::adaptive_radius(255, 12, 5), 5);
+}
+
+#[test]
+fn map_keeps_the_full_radius_away_from_edges() {
+    // Black on the left, white from column 8
+    let img = ImageBuffer::from_fn(16, 6, |x, _| if x < 8 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) });
+    let radii = kuwahara::radius_map(&img, 1, 6, 3);
+    for (i, &radius) in radii.iter().enumerate() {
+        let expected = if (7..=8).contains(&(i % 16)) { 1 } else { 6 };
+        assert_eq!(radius, expected, "column {}", i % 16);
+    }
+}
+
+#[test]
+fn edges_keep_their_contrast() {
+    let img = checker();
+    let contrast = |result: &ImageBuffer<Rgba<u8>, Vec<u8>>| {
+        let values: Vec<u8> = (6..14).flat_map(|y| (6..14).map(move |x| (x, y))).map(|(x, y)| result.get_pixel(x, y)[0]).collect();
+        values.iter().max().unwrap() - values.iter().min().unwrap()
+    };
+    let classic = kuwahara::apply_kuwahara_filter(&img, 6, 3, FilterOptions::default());
+    let result = kuwahara::apply_kuwahara_filter(&img, 6, 3, adaptive(1));
+    assert!(contrast(&result) > contrast(&classic), "expected {} above {}", contrast(&result), contrast(&classic));
+}
+
+#[test]
+fn result_is_independent_of_worker_count() {
+    let img = checker();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| kuwahara::apply_kuwahara_filter(&img, 5, num_threads, adaptive(2)));
+}
+
+#[test]
+fn reference_matches_with_adaptive_radii() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let img = image::open(path).expect("Missing fixture").to_rgba8();
+    for filter in [
+        adaptive(1),
+        FilterOptions { colorspace: ColorSpace::Lab, ..adaptive(0) },
+        FilterOptions { scales: 2, filter_alpha: true, ..adaptive(2) },
+    ] {
+        for radius in [1, 5] {
+            let result = kuwahara::apply_kuwahara_filter(&img, radius, 3, filter);
+            common::assert_matches_reference("kuwahara", &img, &result, radius, filter, &format!("radius {} {:?}", radius, filter));
+        }
+    }
+}
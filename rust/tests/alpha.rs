@@ -0,0 +1,53 @@
+// `--alpha-weighted` Kuwahara: transparent pixels carry no weight, so their RGB, usually black,
+// does not bleed into the colors of the opaque pixels around them.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::colorspace::ColorSpace;
+use rust_filter::kuwahara;
+use std::path::PathBuf;
+
+mod common;
+
+fn weighted() -> FilterOptions {
+    FilterOptions { alpha_weighted: true, ..FilterOptions::default() }
+}
+
+// One opaque pixel in the middle of transparent black
+fn lone_pixel() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(9, 9, |x, y| if (x, y) == (4, 4) { Rgba([200, 150, 100, 255]) } else { Rgba([0, 0, 0, 0]) })
+}
+
+#[test]
+fn transparent_neighbours_do_not_darken_opaque_pixels() {
+    let img = lone_pixel();
+    let unweighted = kuwahara::apply_kuwahara_filter(&img, 2, 2, FilterOptions::default());
+    assert!(unweighted.get_pixel(4, 4)[0] < 200, "expected the unweighted filter to darken the pixel");
+
+    let weighted = kuwahara::apply_kuwahara_filter(&img, 2, 2, weighted());
+    assert_eq!(*weighted.get_pixel(4, 4), Rgba([200, 150, 100, 255]));
+}
+
+#[test]
+fn fully_transparent_images_are_unchanged() {
+    let img = ImageBuffer::from_fn(12, 7, |x, y| Rgba([(x * 20) as u8, (y * 30) as u8, 90, 0]));
+    assert!(kuwahara::apply_kuwahara_filter(&img, 3, 3, weighted()) == img);
+}
+
+#[test]
+fn reference_matches_with_alpha_weights() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let mut img = image::open(path).expect("Missing fixture").to_rgba8();
+    // Transparent stripes between ramps of partial alpha. The ramps never reach 0, as a quadrant
+    // with a single pixel of weight has no variance and ties with any other such quadrant.
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        pixel[3] = if (x / 8 + y / 8) % 3 == 0 { 0 } else { (1 + (x * 7 + y * 13) % 255) as u8 };
+    }
+    for colorspace in [ColorSpace::Rgb, ColorSpace::Lab] {
+        let filter = FilterOptions { colorspace, ..weighted() };
+        for radius in [1, 4] {
+            let result = kuwahara::apply_kuwahara_filter(&img, radius, 3, filter);
+            common::assert_matches_reference("kuwahara", &img, &result, radius, filter, &format!("radius {} {:?}", radius, colorspace));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// GIF and APNG frames that cover only the region that changed are composed onto the whole canvas
+// before filtering, with each format's disposal and blending applied.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::animation;
+use std::fs::File;
+use std::io::BufWriter;
+
+type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+const RED: Rgba<u8> = Rgba([200, 0, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 200, 255]);
+const GREEN: Rgba<u8> = Rgba([0, 200, 0, 255]);
+const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("animation_{}_{}", std::process::id(), name)).to_string_lossy().into_owned()
+}
+
+// An 8x6 APNG: a red background, a blue 3x2 patch at (4, 3) drawn over it with its first pixel
+// transparent and then restored away, and a green 1x1 patch at (0, 0)
+fn write_apng(path: &str) {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path).unwrap()), 8, 6);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_animated(3, 0).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+
+    writer.write_image_data(Image::from_pixel(8, 6, RED).as_raw()).unwrap();
+
+    let mut patch = Image::from_pixel(3, 2, BLUE);
+    patch.put_pixel(0, 0, CLEAR);
+    writer.set_frame_dimension(3, 2).unwrap();
+    writer.set_frame_position(4, 3).unwrap();
+    writer.set_blend_op(png::BlendOp::Over).unwrap();
+    writer.set_dispose_op(png::DisposeOp::Previous).unwrap();
+    writer.write_image_data(patch.as_raw()).unwrap();
+
+    writer.set_frame_position(0, 0).unwrap();
+    writer.set_frame_dimension(1, 1).unwrap();
+    writer.set_blend_op(png::BlendOp::Source).unwrap();
+    writer.set_dispose_op(png::DisposeOp::None).unwrap();
+    writer.write_image_data(Image::from_pixel(1, 1, GREEN).as_raw()).unwrap();
+    writer.finish().unwrap();
+}
+
+#[test]
+fn apng_delta_frames_are_composed_onto_the_canvas() {
+    let path = temp_path("delta.png");
+    write_apng(&path);
+    let decoded = animation::decode_animation(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(decoded.frames.len(), 3);
+    assert!(decoded.frames.iter().all(|frame| frame.image.dimensions() == (8, 6)));
+    let second = &decoded.frames[1].image;
+    // The patch's transparent pixel shows the red under it
+    assert_eq!(second.get_pixel(4, 3), &RED);
+    assert_eq!(second.get_pixel(5, 3), &BLUE);
+    assert_eq!(second.get_pixel(6, 4), &BLUE);
+    assert_eq!(second.get_pixel(3, 3), &RED);
+    // The blue patch was disposed of before the green one was drawn
+    let third = &decoded.frames[2].image;
+    assert_eq!(third.get_pixel(0, 0), &GREEN);
+    assert_eq!(third.get_pixel(5, 3), &RED);
+}
+
+#[test]
+fn gif_background_disposal_clears_the_region() {
+    let path = temp_path("delta.gif");
+    {
+        let mut encoder = gif::Encoder::new(BufWriter::new(File::create(&path).unwrap()), 6, 4, &[]).unwrap();
+        let mut background = Image::from_pixel(6, 4, RED).into_raw();
+        let mut frame = gif::Frame::from_rgba(6, 4, &mut background);
+        frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&frame).unwrap();
+        let mut patch = Image::from_pixel(2, 2, BLUE).into_raw();
+        let mut frame = gif::Frame::from_rgba(2, 2, &mut patch);
+        frame.left = 3;
+        frame.top = 1;
+        encoder.write_frame(&frame).unwrap();
+    }
+    let decoded = animation::decode_animation(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let second = &decoded.frames[1].image;
+    assert_eq!(second.dimensions(), (6, 4));
+    assert_eq!(second.get_pixel(3, 1), &BLUE);
+    assert_eq!(second.get_pixel(0, 0), &CLEAR);
+}
+
+#[test]
+fn whole_frames_round_trip() {
+    let (input, output) = (temp_path("round_trip_in.png"), temp_path("round_trip_out.png"));
+    write_apng(&input);
+    let mut decoded = animation::decode_animation(&input).unwrap();
+    // Every pixel of every frame reaches the filter, patches' surroundings included
+    animation::filter_frames(&mut decoded, 2, |frame| ImageBuffer::from_fn(frame.width(), frame.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *frame.get_pixel(x, y);
+        Rgba([255 - r, 255 - g, 255 - b, a])
+    }));
+    animation::encode_animation(&output, &decoded).unwrap();
+    let reread = animation::decode_animation(&output).unwrap();
+    let _ = (std::fs::remove_file(&input), std::fs::remove_file(&output));
+
+    assert_eq!(reread.frames.len(), 3);
+    for (written, read) in decoded.frames.iter().zip(&reread.frames) {
+        assert!(written.image == read.image);
+    }
+    assert_eq!(reread.frames[1].image.get_pixel(4, 3), &Rgba([55, 255, 255, 255]));
+}
@@ -0,0 +1,72 @@
+// The `autolevel` operation: the range read off the merged histograms must match the serial
+// reference whatever the thread count, clipping must ignore stray specks, and a flat channel must
+// be left alone.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::autolevel;
+use rust_filter::channels::Channels;
+use rust_filter::cli::FilterOptions;
+
+mod common;
+
+// Red spans 60 to 123 but for one black and one white speck, green is flat at 90
+fn dull() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(64, 40, |x, y| match (x, y) {
+        (3, 3) => Rgba([0, 90, 0, 200]),
+        (9, 9) => Rgba([255, 90, 255, 200]),
+        _ => Rgba([60 + x as u8, 90, 100 + (y % 20) as u8, 200]),
+    })
+}
+
+#[test]
+fn autolevel_matches_the_reference() {
+    let img = common::fixture();
+    for (channels, clip) in [(Channels::Rgba, 0.0), (Channels::Rgba, 2.5), (Channels::Luma, 0.5)] {
+        let filter = FilterOptions { channels, clip, ..FilterOptions::default() };
+        let result = autolevel::apply_autolevel(&img, 3, filter);
+        common::assert_matches_reference("autolevel", &img, &result, 0, filter, &format!("{:?} clip {}", channels, clip));
+    }
+}
+
+#[test]
+fn autolevel_is_independent_of_worker_count() {
+    let img = common::fixture();
+    let filter = FilterOptions { clip: 1.0, ..FilterOptions::default() };
+    common::assert_independent_of_worker_count(&[2, 5, 8, 1000], |num_threads| autolevel::apply_autolevel(&img, num_threads, filter));
+}
+
+#[test]
+fn levels_skip_the_clipped_tails() {
+    let mut histogram = [0; 256];
+    histogram[0] = 1;
+    histogram[40] = 49;
+    histogram[200] = 49;
+    histogram[255] = 1;
+    assert_eq!(autolevel::levels(&histogram, 0.0), (0, 255));
+    assert_eq!(autolevel::levels(&histogram, 1.0), (40, 200));
+    assert_eq!(autolevel::levels(&histogram, 49.9), (40, 200));
+    assert_eq!(autolevel::levels(&[0; 256], 5.0), (0, 255));
+}
+
+#[test]
+fn table_stretches_the_range_and_clamps_beyond_it() {
+    let table = autolevel::stretch_table(40, 200);
+    assert_eq!((table[0], table[40], table[120], table[200], table[255]), (0, 0, 128, 255, 255));
+    assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(autolevel::stretch_table(77, 77)[77], 77);
+}
+
+#[test]
+fn clipping_ignores_stray_specks() {
+    let img = dull();
+    let specked = autolevel::apply_autolevel(&img, 4, FilterOptions::default());
+    // The specks hold red open at 0 to 255, so nothing moves
+    assert_eq!(specked.get_pixel(20, 20)[0], img.get_pixel(20, 20)[0]);
+
+    let clipped = autolevel::apply_autolevel(&img, 4, FilterOptions { clip: 0.1, ..FilterOptions::default() });
+    let reds: Vec<u8> = clipped.pixels().map(|pixel| pixel[0]).collect();
+    assert_eq!((reds.iter().min(), reds.iter().max()), (Some(&0), Some(&255)));
+    assert_eq!((clipped.get_pixel(0, 20)[0], clipped.get_pixel(63, 20)[0]), (0, 255));
+    // Green holds a single value and alpha is never touched
+    assert!(clipped.pixels().all(|pixel| pixel[1] == 90 && pixel[3] == 200));
+}
@@ -0,0 +1,45 @@
+// Row splits for awkward heights and worker counts: every row covered once and in order, no
+// empty bands, and band lengths within one of each other, so no worker carries the remainder.
+
+use rust_filter::bands;
+
+#[test]
+fn bands_are_balanced_and_cover_every_row() {
+    for len in [0, 1, 2, 3, 5, 7, 9, 16, 17, 63, 64, 65, 100, 1000, 1081] {
+        for parts in [0, 1, 2, 3, 4, 7, 8, 16, 33, 64, 1000, 5000] {
+            let split = bands::split(len, parts);
+            assert_eq!(split.len(), parts.max(1).min(len), "{} rows in {} parts", len, parts);
+
+            let mut next = 0;
+            for range in &split {
+                assert_eq!(range.start, next, "{} rows in {} parts are not contiguous", len, parts);
+                assert!(!range.is_empty(), "{} rows in {} parts give an empty band", len, parts);
+                next = range.end;
+            }
+            assert_eq!(next, len, "{} rows in {} parts do not cover every row", len, parts);
+
+            let lengths = split.iter().map(|range| range.len());
+            let (shortest, longest) = (lengths.clone().min().unwrap_or(0), lengths.max().unwrap_or(0));
+            assert!(longest - shortest <= 1, "{} rows in {} parts range from {} to {}", len, parts, shortest, longest);
+        }
+    }
+}
+
+#[test]
+fn remainder_goes_to_the_first_bands() {
+    let lengths: Vec<usize> = bands::split(10, 4).iter().map(|range| range.len()).collect();
+    assert_eq!(lengths, [3, 3, 2, 2]);
+}
+
+#[test]
+fn mutable_bands_match_their_rows() {
+    let row_len = 3;
+    let mut data: Vec<usize> = (0..11 * row_len).collect();
+    let split = bands::split_mut(&mut data, row_len, 4);
+    assert_eq!(split.len(), 4);
+    for (rows, band) in split {
+        assert_eq!(band.len(), rows.len() * row_len);
+        assert_eq!(band[0], rows.start * row_len);
+    }
+    assert!(bands::split_mut(&mut Vec::<u8>::new(), 0, 4).is_empty());
+}
@@ -0,0 +1,62 @@
+// The `bilateral` operation: it must match the serial reference whatever the thread count, smooth
+// noise on flat regions and keep a hard edge that a blur of the same radius softens.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::noise::{self, Noise};
+use rust_filter::{bilateral, blur};
+
+mod common;
+
+// Dark left half, light right half
+fn step() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(64, 32, |x, _| if x < 32 { Rgba([40, 40, 40, 255]) } else { Rgba([220, 220, 220, 255]) })
+}
+
+#[test]
+fn bilateral_matches_the_reference() {
+    let img = common::fixture();
+    for linear in [false, true] {
+        let filter = FilterOptions { linear, sigma_color: 30.0, ..FilterOptions::default() };
+        for radius in [1, 4] {
+            let result = bilateral::apply_bilateral_filter(&img, radius, bilateral::sigma_space(radius, filter), 30.0, 3, filter);
+            common::assert_matches_reference("bilateral", &img, &result, radius, filter, &format!("radius {} linear {}", radius, linear));
+        }
+    }
+}
+
+#[test]
+fn bilateral_is_independent_of_worker_count() {
+    let img = common::fixture();
+    let filter = FilterOptions::default();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| bilateral::apply_bilateral_filter(&img, 3, 2.0, 25.0, num_threads, filter));
+}
+
+#[test]
+fn bilateral_keeps_edges_the_blur_softens() {
+    let img = step();
+    let filter = FilterOptions::default();
+    let smoothed = bilateral::apply_bilateral_filter(&img, 4, 2.0, 25.0, 4, filter);
+    assert!(smoothed == img, "a clean step should pass through unchanged");
+    let blurred = blur::apply_gaussian_blur(&img, 4, 4, filter);
+    assert!(blurred.get_pixel(31, 16)[0] > 40 && blurred.get_pixel(32, 16)[0] < 220);
+}
+
+#[test]
+fn bilateral_smooths_noise_within_a_region() {
+    let clean = step();
+    let filter = FilterOptions { seed: 5, ..FilterOptions::default() };
+    let noisy = noise::add_noise(&clean, Noise::Gaussian(8.0), 4, filter);
+    let smoothed = bilateral::apply_bilateral_filter(&noisy, 3, 2.0, 40.0, 4, FilterOptions::default());
+    let error = |img: &ImageBuffer<Rgba<u8>, Vec<u8>>| {
+        img.enumerate_pixels().map(|(x, y, pixel)| (pixel[0] as f64 - clean.get_pixel(x, y)[0] as f64).powi(2)).sum::<f64>()
+    };
+    assert!(error(&smoothed) < error(&noisy) / 4.0, "{} against {}", error(&smoothed), error(&noisy));
+    assert!(smoothed.get_pixel(31, 16)[0] < 80 && smoothed.get_pixel(32, 16)[0] > 180, "edge was smeared");
+}
+
+#[test]
+fn radius_zero_keeps_the_image() {
+    let img = common::fixture();
+    assert!(bilateral::apply_bilateral_filter(&img, 0, 1.0, 25.0, 4, FilterOptions::default()) == img);
+}
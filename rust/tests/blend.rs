@@ -0,0 +1,107 @@
+// Multi-band blending: a mask of one color returns that image, a hard mask edge becomes a seam as
+// wide as the coarsest level, and the result does not depend on the thread count. Compositing: each
+// mode mixes as its formula says, opacity and alpha decide how much of the top shows, and the
+// banded result is the per-pixel one.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::blend;
+
+mod common;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+fn detail(seed: u32) -> Frame {
+    ImageBuffer::from_fn(80, 50, |x, y| Rgba([((x * 37 + y * 91 + seed) % 256) as u8, ((x * 11 + seed) % 256) as u8, ((y * 53) % 256) as u8, 255]))
+}
+
+fn gray(value: u8) -> Frame {
+    ImageBuffer::from_pixel(80, 50, Rgba([value, value, value, 255]))
+}
+
+// White on the left half, black on the right
+fn step_mask() -> Frame {
+    ImageBuffer::from_fn(80, 50, |x, _| if x < 40 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) })
+}
+
+#[test]
+fn levels_stop_at_the_top_size() {
+    assert_eq!(blend::levels(96, 64), 3);
+    assert_eq!(blend::levels(80, 50), 3);
+    assert_eq!(blend::levels(16, 1000), 1);
+    assert_eq!(blend::levels(4096, 4096), 9);
+}
+
+#[test]
+fn uniform_masks_return_one_image() {
+    let (a, b) = (detail(0), detail(100));
+    assert!(blend::blend(&a, &b, &gray(255), 3).expect("Blending failed") == a);
+    assert!(blend::blend(&a, &b, &gray(0), 3).expect("Blending failed") == b);
+}
+
+#[test]
+fn hard_mask_edges_blend_smoothly() {
+    let result = blend::blend(&gray(0), &gray(255), &step_mask(), 4).expect("Blending failed");
+    let row: Vec<u8> = (0..80).map(|x| result.get_pixel(x, 25)[0]).collect();
+    assert_eq!((row[0], row[79]), (0, 255));
+    assert!(row.windows(2).all(|pair| pair[0] <= pair[1]), "expected a monotonic seam: {:?}", row);
+    let transition = row.iter().filter(|&&value| value > 10 && value < 245).count();
+    assert!(transition > 4, "expected a seam wider than a few pixels: {:?}", row);
+}
+
+#[test]
+fn result_is_independent_of_thread_count() {
+    let (a, b) = (detail(0), detail(100));
+    common::assert_independent_of_worker_count(&[2, 3, 8], |num_threads| blend::blend(&a, &b, &step_mask(), num_threads).expect("Blending failed"));
+}
+
+#[test]
+fn inputs_must_match_in_size() {
+    let small = ImageBuffer::new(10, 10);
+    assert_eq!(blend::blend(&detail(0), &small, &step_mask(), 2).err().as_deref(), Some("Second image is 10x10, expected 80x50 like the first image"));
+    assert_eq!(blend::blend(&detail(0), &detail(1), &small, 2).err().as_deref(), Some("Mask is 10x10, expected 80x50 like the first image"));
+}
+
+#[test]
+fn modes_mix_as_their_formulas() {
+    let base = [200, 100, 40, 255];
+    let composite = |top: [u8; 4], mode| blend::composite_pixel(base, top, mode, 1.0);
+    assert_eq!(composite([10, 20, 30, 255], blend::Mode::Normal), [10, 20, 30, 255]);
+    // White is multiply's identity and black screen's
+    assert_eq!(composite([255, 255, 255, 255], blend::Mode::Multiply), base);
+    assert_eq!(composite([0, 0, 0, 255], blend::Mode::Screen), base);
+    assert_eq!(composite([128, 128, 128, 255], blend::Mode::Multiply), [100, 50, 20, 255]);
+    // Overlay screens the bright base channel and multiplies the dark ones
+    assert_eq!(composite([128, 128, 128, 255], blend::Mode::Overlay), [200, 100, 40, 255]);
+    assert_eq!(composite([255, 255, 255, 255], blend::Mode::Overlay), [255, 200, 80, 255]);
+    assert_eq!("screen".parse(), Ok(blend::Mode::Screen));
+    assert!("dodge".parse::<blend::Mode>().is_err());
+}
+
+#[test]
+fn opacity_and_alpha_decide_how_much_shows() {
+    let base = [200, 100, 40, 255];
+    assert_eq!(blend::composite_pixel(base, [0, 0, 0, 255], blend::Mode::Normal, 0.0), base);
+    assert_eq!(blend::composite_pixel(base, [0, 0, 0, 255], blend::Mode::Normal, 0.5), [100, 50, 20, 255]);
+    assert_eq!(blend::composite_pixel(base, [0, 0, 0, 0], blend::Mode::Multiply, 1.0), base);
+    // Over nothing the top shows as it is, whatever the mode
+    assert_eq!(blend::composite_pixel([0, 0, 0, 0], [10, 20, 30, 128], blend::Mode::Multiply, 1.0), [10, 20, 30, 128]);
+    assert_eq!(blend::composite_pixel([0, 0, 0, 0], [0, 0, 0, 0], blend::Mode::Normal, 1.0), [0, 0, 0, 0]);
+}
+
+#[test]
+fn composite_matches_the_per_pixel_one() {
+    let base = detail(0);
+    let top = ImageBuffer::from_fn(80, 50, |x, y| Rgba([((x * 7) % 256) as u8, ((y * 5) % 256) as u8, 90, ((x + y) * 3 % 256) as u8]));
+    for mode in [blend::Mode::Normal, blend::Mode::Multiply, blend::Mode::Screen, blend::Mode::Overlay] {
+        let expected = ImageBuffer::from_fn(80, 50, |x, y| Rgba(blend::composite_pixel(base.get_pixel(x, y).0, top.get_pixel(x, y).0, mode, 0.7)));
+        for num_threads in [1, 3, 8] {
+            assert!(blend::composite(&base, &top, mode, 0.7, num_threads).expect("Compositing failed") == expected, "{:?} {} threads", mode, num_threads);
+        }
+    }
+}
+
+#[test]
+fn composited_images_must_match_in_size() {
+    let small = ImageBuffer::new(10, 10);
+    assert_eq!(blend::composite(&detail(0), &small, blend::Mode::Normal, 1.0, 2).err().as_deref(), Some("Second image is 10x10, expected 80x50 like the first image"));
+}
@@ -0,0 +1,54 @@
+// The `bokeh` operation: the banded disc blur must match the serial one exactly whatever the
+// thread count, leave a flat image alone and, with --highlights, spread bright pixels further.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::bokeh;
+
+mod common;
+
+// A dark image with one white dot in the middle
+fn dot() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(31, 31, |x, y| if (x, y) == (15, 15) { Rgba([255, 255, 255, 255]) } else { Rgba([20, 30, 40, 255]) })
+}
+
+#[test]
+fn bokeh_matches_the_reference() {
+    let img = common::fixture();
+    let linear = FilterOptions { linear: true, ..FilterOptions::default() };
+    let boosted = FilterOptions { highlights: 4.0, ..FilterOptions::default() };
+    for (radius, num_threads, filter) in [(1, 1, FilterOptions::default()), (4, 4, linear), (6, 3, boosted), (9, 7, FilterOptions::default())] {
+        let result = bokeh::apply_bokeh(&img, radius, num_threads, filter);
+        common::assert_matches_reference("bokeh", &img, &result, radius, filter, &format!("radius {} threads {}", radius, num_threads));
+    }
+}
+
+#[test]
+fn bokeh_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 3, 8, 100], |num_threads| bokeh::apply_bokeh(&img, 5, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn disc_is_round() {
+    assert_eq!(bokeh::disc(0), vec![(0, 0)]);
+    assert_eq!(bokeh::disc(2), vec![(-2, 0), (-1, 1), (0, 2), (1, 1), (2, 0)]);
+}
+
+#[test]
+fn flat_image_is_unchanged() {
+    let img = ImageBuffer::from_pixel(20, 12, Rgba([200, 100, 50, 180]));
+    let filter = FilterOptions { highlights: 3.0, ..FilterOptions::default() };
+    assert!(bokeh::apply_bokeh(&img, 4, 3, filter) == img);
+}
+
+#[test]
+fn highlights_brighten_the_disc_around_a_light() {
+    let plain = bokeh::apply_bokeh(&dot(), 5, 4, FilterOptions::default());
+    let boosted = bokeh::apply_bokeh(&dot(), 5, 4, FilterOptions { highlights: 8.0, ..FilterOptions::default() });
+    // Inside the disc the dot is averaged in, brighter with the boost; outside it never reaches
+    assert!(plain.get_pixel(18, 15)[0] > 20);
+    assert!(boosted.get_pixel(18, 15)[0] > plain.get_pixel(18, 15)[0]);
+    assert_eq!(boosted.get_pixel(15, 21)[0], 20);
+    assert_eq!(boosted.get_pixel(19, 19)[0], 20);
+}
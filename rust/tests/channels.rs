@@ -0,0 +1,69 @@
+// `--channels` keeps the input's values in every channel it does not select, so a mask can be
+// feathered without touching the colors, or the luma blurred while chroma stays sharp.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::channels::{self, Channels};
+use rust_filter::cli::FilterOptions;
+use rust_filter::{blur, kuwahara};
+
+mod common;
+
+type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// The fixture with a ramp of alpha, so the alpha channel has something to blur
+fn translucent() -> Image {
+    let mut img = common::fixture();
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        pixel[3] = ((x * 5 + y * 3) % 256) as u8;
+    }
+    img
+}
+
+fn with_channels(channels: Channels) -> FilterOptions {
+    FilterOptions { channels, ..FilterOptions::default() }
+}
+
+#[test]
+fn unselected_channels_pass_through() {
+    let img = translucent();
+    let full = blur::apply_gaussian_blur(&img, 4, 3, FilterOptions::default());
+    let alpha = blur::apply_gaussian_blur(&img, 4, 3, with_channels(Channels::A));
+    let rgb = blur::apply_gaussian_blur(&img, 4, 3, with_channels(Channels::Rgb));
+    for ((src, full), (alpha, rgb)) in img.pixels().zip(full.pixels()).zip(alpha.pixels().zip(rgb.pixels())) {
+        assert_eq!(alpha.0, [src[0], src[1], src[2], full[3]]);
+        assert_eq!(rgb.0, [full[0], full[1], full[2], src[3]]);
+    }
+}
+
+#[test]
+fn luma_keeps_chroma() {
+    let src = [200, 40, 90, 128];
+    let merged = channels::select(src, [100, 100, 100, 7], Channels::Luma);
+    // Every color channel moves by the change in luma, and alpha is left alone
+    let shift = merged[0] as i32 - src[0] as i32;
+    assert_eq!(merged[1] as i32 - src[1] as i32, shift);
+    assert_eq!(merged[2] as i32 - src[2] as i32, shift);
+    assert_eq!(merged[3], 128);
+}
+
+#[test]
+fn reference_matches_every_channel_selection() {
+    let img = translucent();
+    for channels in [Channels::Rgb, Channels::A, Channels::Luma] {
+        let filter = with_channels(channels);
+        for operation in ["blur", "kuwahara"] {
+            let result = match operation {
+                "blur" => blur::apply_gaussian_blur(&img, 3, 3, filter),
+                _ => kuwahara::apply_kuwahara_filter(&img, 3, 3, filter),
+            };
+            common::assert_matches_reference(operation, &img, &result, 3, filter, &format!("{} {:?}", operation, channels));
+        }
+    }
+}
+
+#[test]
+fn channel_names_parse() {
+    assert_eq!("luma".parse(), Ok(Channels::Luma));
+    assert_eq!("a".parse(), Ok(Channels::A));
+    assert!("rgbx".parse::<Channels>().is_err());
+}
@@ -0,0 +1,32 @@
+// Shared by the operation tests: the golden input image, and the two checks every banded
+// operation gets, against the serial reference and across worker counts.
+
+#![allow(dead_code)]
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::verify;
+use std::path::PathBuf;
+
+pub type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+pub fn fixture() -> Image {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    image::open(path).expect("Missing fixture").to_rgba8()
+}
+
+// Every pixel of `result` must be what the serial reference of `operation` gives on `src`;
+// `case` names the parameters in the failure message
+pub fn assert_matches_reference(operation: &str, src: &Image, result: &Image, radius: i32, filter: FilterOptions, case: &str) {
+    let points: Vec<_> = result.enumerate_pixels().map(|(x, y, _)| (x, y)).collect();
+    let comparison = verify::check_reference(operation, src, result, radius, filter, &points, 0);
+    assert_eq!(comparison.mismatches, 0, "{} first at {:?}", case, comparison.first_mismatches);
+}
+
+// `apply` must give on every one of `counts` threads what it gives on one
+pub fn assert_independent_of_worker_count<T: PartialEq>(counts: &[usize], apply: impl Fn(usize) -> T) {
+    let one = apply(1);
+    for &num_threads in counts {
+        assert!(apply(num_threads) == one, "{} threads", num_threads);
+    }
+}
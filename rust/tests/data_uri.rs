@@ -0,0 +1,41 @@
+// Data URIs: base64 payloads decode whatever the MIME type, other encodings are refused, and
+// encoding picks the format from the output extension.
+
+use image::{ImageBuffer, ImageFormat, Rgba, RgbaImage};
+use rust_filter::data_uri;
+
+fn image() -> RgbaImage {
+    ImageBuffer::from_fn(6, 4, |x, y| Rgba([x as u8 * 40, y as u8 * 60, 128, 255 - x as u8]))
+}
+
+#[test]
+fn decodes_base64_payloads() {
+    assert!(data_uri::is_data_uri("data:image/png;base64,AAAA"));
+    assert!(!data_uri::is_data_uri("image.png"));
+    assert_eq!(data_uri::decode("data:image/png;base64,aGVsbG8=").unwrap(), b"hello");
+    // Trailing newlines from `base64` or `echo` are tolerated
+    assert_eq!(data_uri::decode("data:application/octet-stream;base64,aGVsbG8=\n").unwrap(), b"hello");
+}
+
+#[test]
+fn rejects_other_forms() {
+    let error = |uri: &str| data_uri::decode(uri).unwrap_err().to_string();
+    assert!(error("data:image/png;base64").contains("Malformed"));
+    assert!(error("image/png;base64,aGVsbG8=").contains("Malformed"));
+    assert!(error("data:text/plain,hello").contains("Only base64"));
+    assert!(data_uri::decode("data:image/png;base64,not*base64").is_err());
+}
+
+#[test]
+fn encodes_in_the_output_format() {
+    let img = image();
+    for (path, format) in [("out.png", ImageFormat::Png), ("out.bmp", ImageFormat::Bmp), ("-", ImageFormat::Png)] {
+        let (bytes, chosen) = data_uri::encode_image(&img, path).unwrap();
+        assert_eq!(chosen, format, "{}", path);
+
+        let uri = data_uri::encode(&bytes, chosen);
+        assert!(uri.starts_with(&format!("data:{};base64,", format.to_mime_type())), "{}", uri);
+        let decoded = image::load_from_memory_with_format(&data_uri::decode(&uri).unwrap(), format).unwrap();
+        assert_eq!(decoded.to_rgba8(), img, "{}", path);
+    }
+}
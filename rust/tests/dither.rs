@@ -0,0 +1,46 @@
+// The `dither` operation: the staircase of rows passing their error down to each other must give
+// exactly the serial result whatever the thread count, use only the quantized levels and keep the
+// mean brightness of the input.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::dither;
+
+mod common;
+
+#[test]
+fn dither_matches_the_reference() {
+    let img = common::fixture();
+    for (levels, num_threads) in [(2, 1), (2, 4), (5, 3), (16, 7)] {
+        let result = dither::apply_dither(&img, levels, num_threads, FilterOptions::default());
+        common::assert_matches_reference("dither", &img, &result, levels, FilterOptions::default(), &format!("levels {} threads {}", levels, num_threads));
+    }
+}
+
+#[test]
+fn dither_is_independent_of_worker_count() {
+    // Wider than a chunk so rows overlap, and more threads than rows
+    let img = ImageBuffer::from_fn(150, 9, |x, y| Rgba([x as u8, (y * 25) as u8, 128, 255]));
+    common::assert_independent_of_worker_count(&[2, 3, 8, 32], |num_threads| dither::apply_dither(&img, 2, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn only_the_quantized_levels_are_used() {
+    let result = dither::apply_dither(&common::fixture(), 3, 4, FilterOptions::default());
+    for pixel in result.pixels() {
+        assert!(pixel.0[..3].iter().all(|value| [0, 128, 255].contains(value)), "{:?}", pixel);
+    }
+    let unchanged = dither::apply_dither(&common::fixture(), 256, 4, FilterOptions::default());
+    assert!(unchanged == common::fixture());
+}
+
+#[test]
+fn flat_gray_keeps_its_brightness() {
+    let img = ImageBuffer::from_pixel(64, 64, Rgba([64, 128, 192, 200]));
+    let result = dither::apply_dither(&img, 2, 4, FilterOptions::default());
+    for (ch, expected) in [64.0, 128.0, 192.0].into_iter().enumerate() {
+        let mean = result.pixels().map(|pixel| pixel[ch] as f64).sum::<f64>() / (64.0 * 64.0);
+        assert!((mean - expected).abs() < 2.0, "channel {} mean {}", ch, mean);
+    }
+    assert!(result.pixels().all(|pixel| pixel[3] == 200));
+}
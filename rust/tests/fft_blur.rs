@@ -0,0 +1,66 @@
+// Large blur radii convolve through the FFT. The transform must give back its input, and the FFT
+// pass must agree with the direct one it replaces to within rounding of the last bit.
+
+use rust_filter::blur::{self, ImageData};
+use rust_filter::cli::FilterOptions;
+use rust_filter::fft::{self, Complex};
+use rust_filter::timing::WorkerClock;
+use rust_filter::verify;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+mod common;
+
+type Pass = fn(&ImageData, Arc<Mutex<ImageData>>, &[f64], usize, FilterOptions, Range<usize>, &mut WorkerClock);
+
+// One pass over every row of the image with the given pass function
+fn pass(src: &ImageData, radius: usize, filter: FilterOptions, blur_rows: Pass) -> Vec<u8> {
+    let dst = Arc::new(Mutex::new(ImageData { data: vec![0; src.data.len()], width: src.width, height: src.height, channels: src.channels }));
+    let mut clock = WorkerClock::start("test", 0, 0..src.height);
+    blur_rows(src, Arc::clone(&dst), &blur::generate_gaussian_kernel(radius), radius, filter, 0..src.height, &mut clock);
+    let data = dst.lock().unwrap().data.clone();
+    data
+}
+
+#[test]
+fn inverse_transform_gives_back_the_input() {
+    let input: Vec<Complex> = (0..64).map(|i| Complex::new((i as f64 * 0.7).sin() * 100.0, (i % 5) as f64)).collect();
+    let mut data = input.clone();
+    fft::fft(&mut data, false);
+    // A constant's whole energy is in the first bin
+    let mut constant = vec![Complex::new(2.0, 0.0); 16];
+    fft::fft(&mut constant, false);
+    assert!((constant[0].re - 32.0).abs() < 1e-9 && constant[1..].iter().all(|value| value.re.abs() < 1e-9 && value.im.abs() < 1e-9));
+    fft::fft(&mut data, true);
+    for (a, b) in data.iter().zip(&input) {
+        assert!((a.re - b.re).abs() < 1e-9 && (a.im - b.im).abs() < 1e-9, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn fft_pass_matches_the_direct_pass() {
+    let src = ImageData::from_image_buffer(&common::fixture());
+    for linear in [false, true] {
+        let filter = FilterOptions { linear, ..FilterOptions::default() };
+        for radius in [1, 7, 40, 90] {
+            let direct = pass(&src, radius, filter, blur::horizontal_gaussian_blur);
+            let fft = pass(&src, radius, filter, blur::horizontal_fft_blur);
+            let worst = direct.iter().zip(&fft).map(|(&a, &b)| a.abs_diff(b)).max().unwrap_or(0);
+            assert!(worst <= 1, "radius {} linear {} differs by {}", radius, linear, worst);
+        }
+    }
+}
+
+#[test]
+fn large_radii_match_the_reference() {
+    let img = common::fixture();
+    // The reference costs a whole kernel of rows a pixel, so every seventh pixel is enough
+    let points: Vec<_> = img.enumerate_pixels().map(|(x, y, _)| (x, y)).step_by(7).collect();
+    let radius = blur::FFT_RADIUS as i32 + 8;
+    let result = blur::apply_gaussian_blur(&img, radius, 3, FilterOptions::default());
+    let comparison = verify::check_reference("blur", &img, &result, radius, FilterOptions::default(), &points, 1);
+    assert_eq!(comparison.mismatches, 0, "first at {:?}", comparison.first_mismatches);
+    for num_threads in [1, 4, 7] {
+        assert!(blur::apply_gaussian_blur(&img, radius, num_threads, FilterOptions::default()) == result, "{} threads", num_threads);
+    }
+}
@@ -0,0 +1,71 @@
+// `--filter-alpha` Kuwahara: alpha is averaged over the same quadrant as the color, so a soft edge
+// keeps its color and coverage together instead of pairing a new color with the old alpha.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::colorspace::ColorSpace;
+use rust_filter::kuwahara::KuwaharaMode;
+use rust_filter::kuwahara;
+use std::path::PathBuf;
+
+mod common;
+
+fn filtered() -> FilterOptions {
+    FilterOptions { filter_alpha: true, ..FilterOptions::default() }
+}
+
+// Opaque red on the left, transparent black on the right, and one column of half coverage between
+fn soft_edge() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(11, 5, |x, _| match x {
+        0..=4 => Rgba([255, 0, 0, 255]),
+        5 => Rgba([255, 0, 0, 128]),
+        _ => Rgba([0, 0, 0, 0]),
+    })
+}
+
+#[test]
+fn alpha_comes_from_the_chosen_quadrant() {
+    let img = soft_edge();
+    let kept = kuwahara::apply_kuwahara_filter(&img, 2, 2, FilterOptions::default());
+    assert_eq!(*kept.get_pixel(5, 2), Rgba([255, 0, 0, 128]));
+
+    // The red quadrant to the left wins, and with it two opaque columns and the half-covered one
+    let result = kuwahara::apply_kuwahara_filter(&img, 2, 2, filtered());
+    assert_eq!(*result.get_pixel(5, 2), Rgba([255, 0, 0, 213]));
+    assert_eq!(*result.get_pixel(1, 2), Rgba([255, 0, 0, 255]));
+    assert_eq!(*result.get_pixel(9, 2), Rgba([0, 0, 0, 0]));
+}
+
+#[test]
+fn opaque_images_are_filtered_as_before() {
+    let img = ImageBuffer::from_fn(17, 13, |x, y| Rgba([(x * 15) as u8, (y * 19) as u8, ((x * y) % 256) as u8, 255]));
+    let plain = kuwahara::apply_kuwahara_filter(&img, 3, 3, FilterOptions::default());
+    assert!(kuwahara::apply_kuwahara_filter(&img, 3, 3, filtered()) == plain);
+}
+
+#[test]
+fn anisotropic_mode_keeps_the_input_alpha() {
+    let img = soft_edge();
+    let filter = FilterOptions { kuwahara_mode: KuwaharaMode::Anisotropic, ..filtered() };
+    let result = kuwahara::apply_kuwahara_filter(&img, 2, 2, filter);
+    for (out, pixel) in result.pixels().zip(img.pixels()) {
+        assert_eq!(out[3], pixel[3]);
+    }
+}
+
+#[test]
+fn reference_matches_with_filtered_alpha() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let mut img = image::open(path).expect("Missing fixture").to_rgba8();
+    // Ramps of partial alpha that never reach 0, as in the alpha-weighted test
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        pixel[3] = (1 + (x * 7 + y * 13) % 255) as u8;
+    }
+    for (colorspace, alpha_weighted, scales) in [(ColorSpace::Rgb, false, 1), (ColorSpace::Lab, true, 1), (ColorSpace::Rgb, false, 3)] {
+        let filter = FilterOptions { colorspace, alpha_weighted, scales, ..filtered() };
+        for radius in [1, 4] {
+            let result = kuwahara::apply_kuwahara_filter(&img, radius, 3, filter);
+            common::assert_matches_reference("kuwahara", &img, &result, radius, filter, &format!("radius {} {:?}", radius, filter));
+        }
+    }
+}
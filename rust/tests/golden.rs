@@ -0,0 +1,78 @@
+// Filters the fixture in testdata/golden at a few radii and worker counts and compares every result
+// with the committed expected image, pixel for pixel. Both Rust crates check against the same files;
+// the C, Go, Zig, Odin and Python implementations are not checked against them.
+// After a change that is meant to alter the output, regenerate them from the single-threaded
+// results here and review the image diff:
+//   cd rust && UPDATE_GOLDEN=1 cargo test --test golden
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::blur;
+use rust_filter::cli::FilterOptions;
+use rust_filter::kuwahara;
+use std::path::PathBuf;
+
+mod common;
+
+type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Radii up to more than a band's height at the largest worker count, so band edges are covered
+const CASES: [(&str, i32); 5] = [("blur", 1), ("blur", 4), ("blur", 12), ("kuwahara", 2), ("kuwahara", 6)];
+const WORKERS: [usize; 3] = [1, 3, 8];
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden")
+}
+
+// With UPDATE_GOLDEN set the reference result is written first, and the other backends still have to match it
+fn check(operation: &str, radius: i32, backend: &str, actual: &Image, reference: bool) {
+    let path = golden_dir().join(format!("{}_r{}.png", operation, radius));
+    if reference && std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&path).expect("Failed to write expected image");
+        return;
+    }
+    let expected = image::open(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)).to_rgba8();
+    assert_eq!(actual.dimensions(), expected.dimensions(), "{} radius {} on {}", operation, radius, backend);
+    if let Some((x, y, pixel)) = actual.enumerate_pixels().find(|(x, y, pixel)| expected.get_pixel(*x, *y) != *pixel) {
+        panic!(
+            "{} radius {} on {}: pixel ({}, {}) is {:?}, expected {:?}",
+            operation,
+            radius,
+            backend,
+            x,
+            y,
+            pixel.0,
+            expected.get_pixel(x, y).0
+        );
+    }
+}
+
+#[test]
+fn threads_match_golden_images() {
+    let img = common::fixture();
+    for (operation, radius) in CASES {
+        for workers in WORKERS {
+            let result = match operation {
+                "blur" => blur::apply_gaussian_blur(&img, radius, workers, FilterOptions::default()),
+                _ => kuwahara::apply_kuwahara_filter(&img, radius, workers, FilterOptions::default()),
+            };
+            check(operation, radius, &format!("{} threads", workers), &result, workers == 1);
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn c_api_matches_golden_images() {
+    use rust_filter::ffi;
+
+    let img = common::fixture();
+    let (width, height) = img.dimensions();
+    for (operation, radius) in CASES {
+        let mut pixels = img.clone().into_raw();
+        let filter = if operation == "blur" { ffi::concurrency_blur } else { ffi::concurrency_kuwahara };
+        // Safety: the buffer holds width * height tightly packed RGBA8 pixels
+        let status = unsafe { filter(pixels.as_mut_ptr(), width, height, width * 4, radius, 3) };
+        assert_eq!(status, ffi::ConcurrencyStatus::Ok);
+        check(operation, radius, "the C API", &Image::from_raw(width, height, pixels).unwrap(), false);
+    }
+}
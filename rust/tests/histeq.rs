@@ -0,0 +1,67 @@
+// The `histeq` operation: the merged partial histograms must equal one count over the whole image,
+// the output must match the serial reference whatever the thread count, and a low-contrast image
+// must come out spread over the full range.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::channels::Channels;
+use rust_filter::cli::FilterOptions;
+use rust_filter::histeq;
+
+mod common;
+
+// A gradient squeezed into levels 100 to 131
+fn dull() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(64, 40, |x, y| {
+        let value = 100 + (x / 2) as u8;
+        Rgba([value, value, 100 + (y % 32) as u8, 200])
+    })
+}
+
+#[test]
+fn partial_histograms_merge_into_the_whole() {
+    let img = common::fixture();
+    let row_len = img.width() as usize * 4;
+    let partials: Vec<_> = img.as_raw().chunks(row_len * 7).map(histeq::count).collect();
+    assert_eq!(histeq::merge(&partials), histeq::count(img.as_raw()));
+    assert_eq!(histeq::merge(&[]), [[0; 256]; 3]);
+}
+
+#[test]
+fn histeq_matches_the_reference() {
+    let img = common::fixture();
+    for channels in [Channels::Rgba, Channels::Luma] {
+        let filter = FilterOptions { channels, ..FilterOptions::default() };
+        let result = histeq::apply_histogram_equalization(&img, 3, filter);
+        common::assert_matches_reference("histeq", &img, &result, 0, filter, &format!("{:?}", channels));
+    }
+}
+
+#[test]
+fn histeq_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8, 1000], |num_threads| histeq::apply_histogram_equalization(&img, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn table_stretches_to_the_full_range() {
+    let mut histogram = [0; 256];
+    histogram[10] = 5;
+    histogram[20] = 5;
+    histogram[30] = 10;
+    let table = histeq::equalization_table(&histogram);
+    assert_eq!((table[10], table[20], table[30]), (0, 85, 255));
+    assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    // A flat channel has no distribution to stretch
+    let mut flat = [0; 256];
+    flat[77] = 40;
+    assert_eq!(histeq::equalization_table(&flat)[77], 77);
+}
+
+#[test]
+fn low_contrast_image_spreads_out() {
+    let equalized = histeq::apply_histogram_equalization(&dull(), 4, FilterOptions::default());
+    let reds: Vec<u8> = equalized.pixels().map(|pixel| pixel[0]).collect();
+    assert_eq!((reds.iter().min(), reds.iter().max()), (Some(&0), Some(&255)));
+    assert!(equalized.pixels().all(|pixel| pixel[3] == 200));
+}
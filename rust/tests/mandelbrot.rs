@@ -0,0 +1,48 @@
+// The `mandelbrot` subcommand: the render must not depend on the thread count, map pixels onto
+// the view as documented and count escapes correctly.
+
+use rust_filter::mandelbrot::{self, View};
+
+mod common;
+
+#[test]
+fn render_is_independent_of_worker_count() {
+    let view = View { max_iterations: 100, ..View::default() };
+    common::assert_independent_of_worker_count(&[2, 3, 8, 200], |num_threads| mandelbrot::render(120, 80, view, num_threads));
+}
+
+#[test]
+fn the_default_view_is_symmetric_about_the_real_axis() {
+    let img = mandelbrot::render(90, 60, View::default(), 4);
+    for (x, y, pixel) in img.enumerate_pixels() {
+        assert_eq!(pixel, img.get_pixel(x, 59 - y), "at {} {}", x, y);
+    }
+    // The center of the default view lies inside the set
+    assert_eq!(img.get_pixel(45, 30).0, [0, 0, 0, 255]);
+}
+
+#[test]
+fn pixels_map_onto_the_view() {
+    let view = View { center: (1.0, -2.0), zoom: 3.0, max_iterations: 10 };
+    // One unit of the plane across the 300 pixels of the shorter side
+    let (re, im) = view.point(0, 0, 400, 300);
+    assert!((re - (1.0 - 199.5 / 300.0)).abs() < 1e-12, "{}", re);
+    assert!((im - (-2.0 + 149.5 / 300.0)).abs() < 1e-12, "{}", im);
+}
+
+#[test]
+fn points_escape_as_expected() {
+    assert_eq!(mandelbrot::escape_time((0.0, 0.0), 50), 50);
+    assert_eq!(mandelbrot::escape_time((-1.0, 0.0), 50), 50);
+    assert_eq!(mandelbrot::escape_time((2.0, 2.0), 50), 1);
+    assert_eq!(mandelbrot::escape_time((1.0, 0.0), 50), 3);
+    assert_eq!(mandelbrot::color(50, 50).0, [0, 0, 0, 255]);
+}
+
+#[test]
+fn sizes_must_be_two_positive_numbers() {
+    assert_eq!(mandelbrot::parse_size("640x480"), Ok((640, 480)));
+    for size in ["640", "0x480", "640x", "axb", "-1x5"] {
+        assert!(mandelbrot::parse_size(size).is_err(), "{}", size);
+    }
+}
@@ -0,0 +1,65 @@
+// `--polygon` masks: which pixel centers a polygon covers, written as points or as an SVG path,
+// and the blend that leaves everything outside untouched.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::mask::{self, Polygon};
+
+fn inside(polygon: &str, width: u32, height: u32) -> Vec<(u32, u32)> {
+    let polygon: Polygon = polygon.parse().expect("Invalid polygon");
+    let mask = polygon.rasterize(width, height, 3);
+    (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).filter(|&(x, y)| mask[(y * width + x) as usize] == 255).collect()
+}
+
+#[test]
+fn rectangle_covers_the_pixels_whose_centers_it_contains() {
+    let expected: Vec<_> = (1..4).flat_map(|y| (2..6).map(move |x| (x, y))).collect();
+    assert_eq!(inside("2,1 6,1 6,4 2,4", 8, 6), expected);
+    assert_eq!(inside("M2 1 H6 V4 H2 Z", 8, 6), expected);
+    assert_eq!(inside("m2,1 h4 v3 h-4 z", 8, 6), expected);
+    assert_eq!(inside("M2 1 L6 1 6 4 2 4Z", 8, 6), expected);
+}
+
+#[test]
+fn reversed_inner_contour_cuts_a_hole() {
+    let hole = inside("M0 0 H8 V8 H0 Z M2 2 V6 H6 V2 Z", 8, 8);
+    assert_eq!(hole.len(), 64 - 16);
+    assert!(!hole.contains(&(3, 3)));
+    // Drawn the same way round, the inner square adds to the winding and stays filled
+    assert_eq!(inside("M0 0 H8 V8 H0 Z M2 2 H6 V6 H2 Z", 8, 8).len(), 64);
+}
+
+#[test]
+fn outlines_past_the_image_are_clipped() {
+    assert_eq!(inside("-5,-5 20,-5 20,20 -5,20", 4, 3).len(), 12);
+    assert!(inside("10,10 20,10 20,20", 4, 3).is_empty());
+}
+
+#[test]
+fn mask_is_independent_of_thread_count() {
+    let polygon: Polygon = "3.2,1.7 91.5,12 60,77.3 8,55".parse().unwrap();
+    let expected = polygon.rasterize(97, 83, 1);
+    for threads in [0, 2, 7, 16, 200] {
+        assert!(polygon.rasterize(97, 83, threads) == expected, "{} threads", threads);
+    }
+}
+
+#[test]
+fn blend_keeps_the_input_outside() {
+    let src = ImageBuffer::from_pixel(6, 4, Rgba([10, 20, 30, 255]));
+    let mut filtered = ImageBuffer::from_pixel(6, 4, Rgba([200, 100, 50, 128]));
+    let polygon: Polygon = "0,0 3,0 3,4 0,4".parse().unwrap();
+    mask::blend(&src, &mut filtered, &polygon.rasterize(6, 4, 2), 2);
+    for (x, _, pixel) in filtered.enumerate_pixels() {
+        let expected = if x < 3 { [200, 100, 50, 128] } else { [10, 20, 30, 255] };
+        assert_eq!(pixel.0, expected);
+    }
+}
+
+#[test]
+fn invalid_outlines_are_rejected() {
+    assert!("1,1 5,5".parse::<Polygon>().is_err());
+    assert!("1,1 5;5 3,3".parse::<Polygon>().is_err());
+    let curve = "M0 0 C 1 2 3 4 5 6 Z".parse::<Polygon>().unwrap_err();
+    assert!(curve.contains("Unsupported path command: C"), "{}", curve);
+    assert!("L1 1 2 2 3 0".parse::<Polygon>().is_err());
+}
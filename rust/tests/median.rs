@@ -0,0 +1,42 @@
+// The `median` operation: the sliding histogram must pick the same value as sorting the window,
+// whatever the thread count, and clear isolated salt and pepper pixels.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::median;
+use rust_filter::noise::{self, Noise};
+
+mod common;
+
+#[test]
+fn median_matches_the_sorted_reference() {
+    let img = common::fixture();
+    for radius in [1, 3, 7] {
+        let result = median::apply_median_filter(&img, radius, 3, FilterOptions::default());
+        common::assert_matches_reference("median", &img, &result, radius, FilterOptions::default(), &format!("radius {}", radius));
+    }
+}
+
+#[test]
+fn median_removes_salt_and_pepper() {
+    let gray = ImageBuffer::from_pixel(96, 64, Rgba([128, 128, 128, 255]));
+    let filter = FilterOptions { seed: 7, ..FilterOptions::default() };
+    let noisy = noise::add_noise(&gray, Noise::SaltPepper(0.05), 4, filter);
+    // Only a clump of five like hits in one window survives radius 1
+    let denoised = median::apply_median_filter(&noisy, 1, 4, FilterOptions::default());
+    let hits = |img: &ImageBuffer<Rgba<u8>, Vec<u8>>| img.pixels().filter(|pixel| pixel.0 != [128, 128, 128, 255]).count();
+    assert!(hits(&noisy) > 200, "{} noisy pixels", hits(&noisy));
+    assert!(hits(&denoised) < 5, "{} pixels left", hits(&denoised));
+}
+
+#[test]
+fn median_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| median::apply_median_filter(&img, 2, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn radius_zero_keeps_the_image() {
+    let img = common::fixture();
+    assert!(median::apply_median_filter(&img, 0, 4, FilterOptions::default()) == img);
+}
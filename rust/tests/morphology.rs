@@ -0,0 +1,53 @@
+// The `dilate` and `erode` operations: both passes must match the serial reference whatever the
+// thread count, grow or shrink a shape by exactly the radius, and undo each other on a square.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::morphology::{self, Morphology};
+
+mod common;
+
+// A white 8x8 square on black, from (12, 10) to (19, 17)
+fn square() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(32, 28, |x, y| if (12..20).contains(&x) && (10..18).contains(&y) { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) })
+}
+
+fn white_bounds(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> (u32, u32, u32, u32) {
+    let white: Vec<_> = img.enumerate_pixels().filter(|(_, _, p)| p[0] == 255).map(|(x, y, _)| (x, y)).collect();
+    let xs = white.iter().map(|p| p.0);
+    let ys = white.iter().map(|p| p.1);
+    (xs.clone().min().unwrap(), ys.clone().min().unwrap(), xs.max().unwrap(), ys.max().unwrap())
+}
+
+#[test]
+fn morphology_matches_the_reference() {
+    let img = common::fixture();
+    for (operation, op) in [("dilate", Morphology::Dilate), ("erode", Morphology::Erode)] {
+        for radius in [1, 3, 100] {
+            let result = morphology::apply_morphology(&img, op, radius, 3, FilterOptions::default());
+            common::assert_matches_reference(operation, &img, &result, radius, FilterOptions::default(), &format!("{} radius {}", operation, radius));
+        }
+    }
+}
+
+#[test]
+fn morphology_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| morphology::apply_morphology(&img, Morphology::Erode, 2, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn radius_grows_and_shrinks_the_square() {
+    let dilated = morphology::apply_morphology(&square(), Morphology::Dilate, 2, 4, FilterOptions::default());
+    assert_eq!(white_bounds(&dilated), (10, 8, 21, 19));
+    let eroded = morphology::apply_morphology(&square(), Morphology::Erode, 2, 4, FilterOptions::default());
+    assert_eq!(white_bounds(&eroded), (14, 12, 17, 15));
+}
+
+#[test]
+fn closing_and_opening_keep_a_square() {
+    let dilated = morphology::apply_morphology(&square(), Morphology::Dilate, 3, 4, FilterOptions::default());
+    assert!(morphology::apply_morphology(&dilated, Morphology::Erode, 3, 4, FilterOptions::default()) == square());
+    let eroded = morphology::apply_morphology(&square(), Morphology::Erode, 3, 4, FilterOptions::default());
+    assert!(morphology::apply_morphology(&eroded, Morphology::Dilate, 3, 4, FilterOptions::default()) == square());
+}
@@ -0,0 +1,52 @@
+// The `motion-blur` operation: the directional blur must match the serial reference whatever the
+// thread count, smear only along its angle and leave the image alone for lengths below two.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::motion_blur;
+
+mod common;
+
+// A single white column on black
+fn vertical_line() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(32, 16, |x, _| if x == 16 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) })
+}
+
+#[test]
+fn motion_blur_matches_the_reference() {
+    let img = common::fixture();
+    for (linear, angle, length) in [(false, 0.0, 7), (false, 90.0, 6), (true, 30.0, 9), (false, -135.0, 5)] {
+        let filter = FilterOptions { linear, angle, ..FilterOptions::default() };
+        let result = motion_blur::apply_motion_blur(&img, length, angle, 3, filter);
+        common::assert_matches_reference("motion-blur", &img, &result, length as i32, filter, &format!("linear {} angle {} length {}", linear, angle, length));
+    }
+}
+
+#[test]
+fn motion_blur_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| motion_blur::apply_motion_blur(&img, 8, 45.0, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn horizontal_blur_spreads_a_line_sideways_only() {
+    let blurred = motion_blur::apply_motion_blur(&vertical_line(), 5, 0.0, 4, FilterOptions::default());
+    for y in 0..16 {
+        assert_eq!(blurred.get_pixel(16, y)[0], 51);
+        assert_eq!(blurred.get_pixel(14, y)[0], 51);
+        assert_eq!(blurred.get_pixel(18, y)[0], 51);
+        assert_eq!(blurred.get_pixel(13, y)[0], 0);
+        assert_eq!(blurred.get_pixel(19, y)[0], 0);
+    }
+    // Along the line itself nothing changes
+    let along = motion_blur::apply_motion_blur(&vertical_line(), 5, 90.0, 4, FilterOptions::default());
+    assert!(along == vertical_line());
+}
+
+#[test]
+fn short_lengths_are_the_identity() {
+    let img = common::fixture();
+    for length in [0, 1] {
+        assert!(motion_blur::apply_motion_blur(&img, length, 20.0, 3, FilterOptions::default()) == img, "length {}", length);
+    }
+}
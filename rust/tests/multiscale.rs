@@ -0,0 +1,61 @@
+// `--scales` Kuwahara: the radius and its halvings, blended by how flat each one's best quadrant is,
+// so fine detail survives where the full radius alone would smooth it away.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::colorspace::ColorSpace;
+use rust_filter::kuwahara;
+use std::path::PathBuf;
+
+mod common;
+
+fn scales(scales: u32) -> FilterOptions {
+    FilterOptions { scales, ..FilterOptions::default() }
+}
+
+// A white line one pixel wide down the middle of a black image
+fn thin_line() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(21, 21, |x, _| if x == 10 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) })
+}
+
+#[test]
+fn radii_halve_until_zero() {
+    assert_eq!(kuwahara::scale_radii(8, 3).collect::<Vec<_>>(), [8, 4, 2]);
+    assert_eq!(kuwahara::scale_radii(2, 5).collect::<Vec<_>>(), [2, 1]);
+    assert_eq!(kuwahara::scale_radii(5, 0).collect::<Vec<_>>(), [5]);
+}
+
+#[test]
+fn one_scale_is_the_plain_filter() {
+    let img = thin_line();
+    assert!(kuwahara::apply_kuwahara_filter(&img, 6, 3, scales(1)) == kuwahara::apply_kuwahara_filter(&img, 6, 3, FilterOptions::default()));
+}
+
+#[test]
+fn finer_scales_keep_thin_detail() {
+    let img = thin_line();
+    let single = kuwahara::apply_kuwahara_filter(&img, 6, 3, scales(1));
+    let blended = kuwahara::apply_kuwahara_filter(&img, 6, 3, scales(3));
+    assert!(blended.get_pixel(10, 10)[0] > single.get_pixel(10, 10)[0], "expected the line to keep more of its contrast");
+    // Every scale agrees on flat areas
+    assert_eq!(*blended.get_pixel(2, 10), Rgba([0, 0, 0, 255]));
+}
+
+#[test]
+fn result_is_independent_of_worker_count() {
+    let img = thin_line();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| kuwahara::apply_kuwahara_filter(&img, 6, num_threads, scales(3)));
+}
+
+#[test]
+fn reference_matches_across_scales() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let img = image::open(path).expect("Missing fixture").to_rgba8();
+    for colorspace in [ColorSpace::Rgb, ColorSpace::Lab] {
+        let filter = FilterOptions { colorspace, ..scales(3) };
+        for radius in [2, 6] {
+            let result = kuwahara::apply_kuwahara_filter(&img, radius, 3, filter);
+            common::assert_matches_reference("kuwahara", &img, &result, radius, filter, &format!("radius {} {:?}", radius, colorspace));
+        }
+    }
+}
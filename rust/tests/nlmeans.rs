@@ -0,0 +1,73 @@
+// The `nlmeans` operation: non-local means must match the serial reference whatever the thread
+// count, cover every pixel with exactly one tile, and smooth noise away without blurring an edge.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::nlmeans::{self, TILE_SIZE};
+use rust_filter::noise::Noise;
+
+mod common;
+
+type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Dark on the left, light on the right, with Gaussian noise over both, three tiles by two
+fn noisy_step() -> (Image, Image) {
+    let clean = ImageBuffer::from_fn(150, 80, |x, _| if x < 75 { Rgba([60, 60, 60, 255]) } else { Rgba([190, 190, 190, 255]) });
+    let noise = Noise::Gaussian(12.0);
+    let noisy = ImageBuffer::from_fn(150, 80, |x, y| Rgba(noise.pixel(7, x, y, clean.get_pixel(x, y).0)));
+    (clean, noisy)
+}
+
+// Mean absolute difference of the red channel
+fn error(a: &Image, b: &Image) -> f64 {
+    a.pixels().zip(b.pixels()).map(|(p, q)| p[0].abs_diff(q[0]) as f64).sum::<f64>() / a.pixels().len() as f64
+}
+
+#[test]
+fn nlmeans_matches_the_reference() {
+    let img = common::fixture();
+    for (patch_radius, strength) in [(1, 10.0), (2, 25.0), (0, 4.0)] {
+        let filter = FilterOptions { patch_radius, strength, ..FilterOptions::default() };
+        let result = nlmeans::apply_nlmeans(&img, 3, 3, filter);
+        common::assert_matches_reference("nlmeans", &img, &result, 3, filter, &format!("patch {} strength {}", patch_radius, strength));
+    }
+}
+
+#[test]
+fn nlmeans_is_independent_of_worker_count() {
+    let (_, img) = noisy_step();
+    common::assert_independent_of_worker_count(&[2, 4, 9], |num_threads| nlmeans::apply_nlmeans(&img, 2, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn tiles_cover_every_pixel_once() {
+    for (width, height) in [(150, 80), (64, 64), (1, 200), (TILE_SIZE + 1, 3)] {
+        let mut covered = vec![0; (width * height) as usize];
+        for tile in nlmeans::tiles(width, height) {
+            assert!(tile.x.len() as u32 <= TILE_SIZE && tile.y.len() as u32 <= TILE_SIZE);
+            for y in tile.y.clone() {
+                for x in tile.x.clone() {
+                    covered[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&count| count == 1), "{}x{}", width, height);
+    }
+}
+
+#[test]
+fn nlmeans_removes_noise_and_keeps_the_edge() {
+    let (clean, noisy) = noisy_step();
+    let filter = FilterOptions { strength: 15.0, ..FilterOptions::default() };
+    let denoised = nlmeans::apply_nlmeans(&noisy, 4, 4, filter);
+    assert!(error(&denoised, &clean) < error(&noisy, &clean) / 2.0, "{} against {}", error(&denoised, &clean), error(&noisy, &clean));
+    for y in 10..70 {
+        assert!(denoised.get_pixel(73, y)[0] < 100 && denoised.get_pixel(76, y)[0] > 150, "row {}", y);
+    }
+}
+
+#[test]
+fn zero_radius_is_the_identity() {
+    let img = common::fixture();
+    assert!(nlmeans::apply_nlmeans(&img, 0, 3, FilterOptions::default()) == img);
+}
@@ -0,0 +1,53 @@
+// The `add-noise` operation: seeded noise drawn per pixel, so the same seed gives the same image
+// whatever the thread count, with the strength the spec asks for.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::noise::{self, Noise};
+
+mod common;
+
+fn gray() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_pixel(128, 96, Rgba([128, 128, 128, 200]))
+}
+
+fn seeded(seed: u64) -> FilterOptions {
+    FilterOptions { seed, ..FilterOptions::default() }
+}
+
+#[test]
+fn parses_noise_specs() {
+    assert_eq!("gaussian:15".parse(), Ok(Noise::Gaussian(15.0)));
+    assert_eq!("saltpepper:0.02".parse(), Ok(Noise::SaltPepper(0.02)));
+    assert!("gaussian:-1".parse::<Noise>().is_err());
+    assert!("saltpepper:1.5".parse::<Noise>().is_err());
+    assert!("speckle:3".parse::<Noise>().is_err());
+    assert!("gaussian".parse::<Noise>().is_err());
+}
+
+#[test]
+fn gaussian_noise_has_the_requested_spread() {
+    let noisy = noise::add_noise(&gray(), Noise::Gaussian(15.0), 4, seeded(1));
+    let values: Vec<f64> = noisy.pixels().flat_map(|pixel| pixel.0[..3].to_vec()).map(|value| value as f64).collect();
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let sigma = (values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt();
+    assert!((mean - 128.0).abs() < 0.5, "mean {}", mean);
+    assert!((sigma - 15.0).abs() < 0.5, "sigma {}", sigma);
+    assert!(noisy.pixels().all(|pixel| pixel[3] == 200), "expected alpha to be kept");
+}
+
+#[test]
+fn salt_and_pepper_hits_the_requested_fraction() {
+    let noisy = noise::add_noise(&gray(), Noise::SaltPepper(0.05), 4, seeded(1));
+    let hit = noisy.pixels().filter(|pixel| pixel[0] != 128).count();
+    let fraction = hit as f64 / (128.0 * 96.0);
+    assert!((fraction - 0.05).abs() < 0.01, "fraction {}", fraction);
+    assert!(noisy.pixels().all(|pixel| matches!(pixel.0, [0, 0, 0, 200] | [255, 255, 255, 200] | [128, 128, 128, 200])));
+}
+
+#[test]
+fn noise_is_independent_of_worker_count_and_follows_the_seed() {
+    let img = gray();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| noise::add_noise(&img, Noise::Gaussian(20.0), num_threads, seeded(3)));
+    assert!(noise::add_noise(&img, Noise::Gaussian(20.0), 1, seeded(4)) != noise::add_noise(&img, Noise::Gaussian(20.0), 1, seeded(3)));
+}
@@ -0,0 +1,51 @@
+// The `oil` operation: the sliding bucket histogram must match the serial reference whatever the
+// thread count, keep the majority color of a neighborhood and break ties toward the darker bucket.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::oil;
+
+mod common;
+
+// Red on the left, blue on the right, with a lone white speck in the red half
+fn halves() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(24, 16, |x, y| match (x, y) {
+        (5, 8) => Rgba([255, 255, 255, 255]),
+        (x, _) if x < 12 => Rgba([200, 30, 30, 180]),
+        _ => Rgba([20, 40, 220, 180]),
+    })
+}
+
+#[test]
+fn oil_matches_the_reference() {
+    let img = common::fixture();
+    for (levels, radius) in [(20, 2), (4, 3), (256, 1)] {
+        let filter = FilterOptions { levels, ..FilterOptions::default() };
+        let result = oil::apply_oil_painting(&img, radius, 3, filter);
+        common::assert_matches_reference("oil", &img, &result, radius, filter, &format!("levels {} radius {}", levels, radius));
+    }
+}
+
+#[test]
+fn oil_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| oil::apply_oil_painting(&img, 3, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn strokes_keep_the_majority_color() {
+    let painted = oil::apply_oil_painting(&halves(), 2, 4, FilterOptions::default());
+    // The speck takes the red around it but keeps its own alpha, which oil never paints
+    assert_eq!(painted.get_pixel(5, 8), &Rgba([200, 30, 30, 255]));
+    assert_eq!(painted.get_pixel(10, 3), &Rgba([200, 30, 30, 180]));
+    assert_eq!(painted.get_pixel(13, 3), &Rgba([20, 40, 220, 180]));
+}
+
+#[test]
+fn ties_go_to_the_darker_bucket() {
+    let counts = [0, 3, 0, 3];
+    let sums = [[0; 3], [30, 60, 90], [0; 3], [600, 600, 600]];
+    assert_eq!(oil::dominant(&counts, &sums, 7), Rgba([10, 20, 30, 7]));
+    assert_eq!(oil::bucket(&[255, 255, 255], 20), 19);
+    assert_eq!(oil::bucket(&[0, 0, 12], 20), 0);
+}
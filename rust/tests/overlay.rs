@@ -0,0 +1,85 @@
+// The `overlay` operation: the stamp lands where its offsets say, negative ones from the bottom
+// right, only the rows it covers change, the banded result is the per-pixel composite whatever
+// the thread count, and a directory is stamped file by file.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::blend::{self, Mode};
+use rust_filter::overlay::{self, Placement};
+use std::fs;
+use std::path::PathBuf;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// A fresh directory under the target's scratch space
+fn scratch(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("overlay").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create scratch directory");
+    dir
+}
+
+fn photo() -> Frame {
+    ImageBuffer::from_fn(90, 60, |x, y| Rgba([((x * 37 + y * 11) % 256) as u8, ((y * 53) % 256) as u8, (x % 256) as u8, 255]))
+}
+
+// Half transparent in its left column, opaque elsewhere
+fn stamp() -> Frame {
+    ImageBuffer::from_fn(7, 5, |x, y| Rgba([250, (y * 60) as u8, 10, if x == 0 { 128 } else { 255 }]))
+}
+
+#[test]
+fn negative_offsets_count_from_the_bottom_right() {
+    assert_eq!(overlay::origin((90, 60), (7, 5), 3, 4), (3, 4));
+    assert_eq!(overlay::origin((90, 60), (7, 5), -1, -1), (83, 55));
+    assert_eq!(overlay::origin((90, 60), (7, 5), -11, 2), (73, 2));
+    assert_eq!(overlay::affected_rows((90, 60), (7, 5), Placement::At(0, -1)), 55..60);
+    assert_eq!(overlay::affected_rows((90, 60), (7, 5), Placement::At(0, 58)), 58..60);
+    assert_eq!(overlay::affected_rows((90, 60), (7, 5), Placement::At(0, 100)), 60..60);
+    assert_eq!(overlay::affected_rows((90, 60), (7, 5), Placement::Tile), 0..60);
+}
+
+#[test]
+fn overlay_matches_the_per_pixel_composite() {
+    let (img, stamp) = (photo(), stamp());
+    for placement in [Placement::At(10, 20), Placement::At(-1, -1), Placement::At(-3, -2), Placement::Tile] {
+        let expected = ImageBuffer::from_fn(90, 60, |x, y| {
+            let base = img.get_pixel(x, y).0;
+            Rgba(overlay::stamp_at(&stamp, (90, 60), placement, x, y).map_or(base, |over| blend::composite_pixel(base, over, Mode::Normal, 0.8)))
+        });
+        for num_threads in [1, 2, 7, 100] {
+            assert!(overlay::apply_overlay(&img, &stamp, placement, 0.8, num_threads) == expected, "{:?} {} threads", placement, num_threads);
+        }
+    }
+}
+
+#[test]
+fn only_the_stamp_changes_the_image() {
+    let (img, stamp) = (photo(), stamp());
+    let result = overlay::apply_overlay(&img, &stamp, Placement::At(10, 20), 1.0, 4);
+    for (x, y, pixel) in result.enumerate_pixels() {
+        let inside = (10..17).contains(&x) && (20..25).contains(&y);
+        if !inside {
+            assert_eq!(pixel, img.get_pixel(x, y), "({}, {})", x, y);
+        } else if x > 10 {
+            assert_eq!(pixel, stamp.get_pixel(x - 10, y - 20), "({}, {})", x, y);
+        }
+    }
+    assert!(overlay::apply_overlay(&img, &stamp, Placement::Tile, 0.0, 4) == img);
+}
+
+#[test]
+fn a_directory_is_stamped_file_by_file() {
+    let input = scratch("sources");
+    let output = scratch("stamped");
+    for name in ["a.png", "b.png", "c.png"] {
+        photo().save(input.join(name)).expect("Failed to write source");
+    }
+    fs::write(input.join("notes.txt"), "not an image").expect("Failed to write note");
+    let stats = overlay::stamp_dir(input.to_str().unwrap(), output.to_str().unwrap(), &stamp(), Placement::At(-1, -1), 1.0, 2).expect("Stamping failed");
+    assert_eq!(stats.images, 3);
+    let expected = overlay::apply_overlay(&photo(), &stamp(), Placement::At(-1, -1), 1.0, 1);
+    for name in ["a.png", "b.png", "c.png"] {
+        assert!(image::open(output.join(name)).expect("Missing output").to_rgba8() == expected, "{}", name);
+    }
+    assert!(!output.join("notes.txt").exists());
+}
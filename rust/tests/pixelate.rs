@@ -0,0 +1,43 @@
+// The `pixelate` operation: block means from the shared summed-area table must match the serial
+// reference whatever the thread count, fill every block with one color and cut the last blocks
+// short at the image edge.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::pixelate;
+
+mod common;
+
+#[test]
+fn pixelate_matches_the_reference() {
+    let img = common::fixture();
+    for (block, linear, alpha_weighted) in [(4, false, false), (7, true, false), (5, false, true)] {
+        let filter = FilterOptions { linear, alpha_weighted, ..FilterOptions::default() };
+        let result = pixelate::apply_pixelate(&img, block, 3, filter);
+        common::assert_matches_reference("pixelate", &img, &result, block, filter, &format!("block {}", block));
+    }
+}
+
+#[test]
+fn pixelate_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8, 64], |num_threads| pixelate::apply_pixelate(&img, 6, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn every_block_is_one_color_and_the_last_ones_are_cut_short() {
+    let img = ImageBuffer::from_fn(10, 7, |x, y| Rgba([(x * 20) as u8, (y * 30) as u8, 90, 255]));
+    let result = pixelate::apply_pixelate(&img, 4, 2, FilterOptions::default());
+    for (x, y, pixel) in result.enumerate_pixels() {
+        assert_eq!(pixel, result.get_pixel(x / 4 * 4, y / 4 * 4), "at {} {}", x, y);
+    }
+    // The bottom right block is the 2x3 corner alone
+    assert_eq!(result.get_pixel(9, 6), &Rgba([170, 150, 90, 255]));
+}
+
+#[test]
+fn blocks_of_one_pixel_change_nothing() {
+    let img = common::fixture();
+    assert!(pixelate::apply_pixelate(&img, 1, 4, FilterOptions::default()) == img);
+    assert!(pixelate::apply_pixelate(&img, 0, 4, FilterOptions::default()) == img);
+}
@@ -0,0 +1,93 @@
+// The strip-parallel PNG encoder: the output decodes to the same pixels with the png crate at any
+// strip count, and the zlib trailer is the Adler-32 of the whole filtered stream.
+
+use flate2::read::DeflateDecoder;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rust_filter::png_encoder;
+use std::io::Read;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("png_encoder_{}_{}.png", std::process::id(), name)).to_string_lossy().into_owned()
+}
+
+// Gradients with some noise, so every filter type gets picked somewhere
+fn image(width: u32, height: u32) -> RgbaImage {
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let hash = (x.wrapping_mul(2654435761) ^ y.wrapping_mul(40503)) >> 13;
+        Rgba([(x * 7) as u8, (y * 3) as u8, hash as u8, (x + y) as u8 | 0x80])
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// The data of every IDAT chunk, in order
+fn idat_chunks(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos + 8 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        if &bytes[pos + 4..pos + 8] == b"IDAT" {
+            chunks.push(&bytes[pos + 8..pos + 8 + len]);
+        }
+        pos += 12 + len;
+    }
+    chunks
+}
+
+fn encode(name: &str, img: &RgbaImage, threads: usize) -> Vec<u8> {
+    let path = temp_path(name);
+    png_encoder::save(&path, img, threads).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> RgbaImage {
+    let mut reader = png::Decoder::new(bytes).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.color_type, info.bit_depth), (png::ColorType::Rgba, png::BitDepth::Eight));
+    ImageBuffer::from_raw(info.width, info.height, pixels).unwrap()
+}
+
+#[test]
+fn decodes_to_the_same_pixels() {
+    for (width, height, threads) in [(1, 1, 4), (5, 63, 4), (33, 261, 4), (17, 1000, 8), (64, 200, 1)] {
+        let img = image(width, height);
+        let bytes = encode(&format!("{}x{}", width, height), &img, threads);
+        assert_eq!(decode(&bytes), img, "{}x{} on {} threads", width, height, threads);
+    }
+}
+
+#[test]
+fn strips_share_one_zlib_stream_and_checksum() {
+    let img = image(40, 300);
+    let bytes = encode("strips", &img, 4);
+    let chunks = idat_chunks(&bytes);
+    // 300 rows make four strips of at least 64 rows, one IDAT each
+    assert_eq!(chunks.len(), 4);
+
+    let stream = chunks.concat();
+    assert_eq!(&stream[..2], &[0x78, 0x01]);
+    let (deflated, trailer) = stream[2..].split_at(stream.len() - 6);
+    let mut filtered = Vec::new();
+    DeflateDecoder::new(deflated).read_to_end(&mut filtered).unwrap();
+
+    // Each row is its filter byte followed by the filtered pixels
+    assert_eq!(filtered.len(), 300 * (1 + 40 * 4));
+    assert_eq!(u32::from_be_bytes(trailer.try_into().unwrap()), adler32(&filtered));
+}
+
+#[test]
+fn output_does_not_depend_on_thread_count_when_short() {
+    // Under 128 rows there is room for only one strip, whatever the thread count
+    let img = image(20, 100);
+    assert_eq!(encode("one", &img, 1), encode("many", &img, 8));
+}
@@ -0,0 +1,57 @@
+// The point operations `posterize` and `gamma`: each pixel must match the serial formula whatever
+// the thread count, posterize must leave only its levels and gamma must keep black and white.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::point;
+use std::collections::HashSet;
+
+mod common;
+
+fn ramp() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(256, 3, |x, y| Rgba([x as u8, (255 - x) as u8, (x * y) as u8, 200]))
+}
+
+#[test]
+fn posterize_and_gamma_match_the_reference() {
+    let img = common::fixture();
+    for (levels, num_threads) in [(2, 1), (5, 4), (256, 7)] {
+        let result = point::apply_posterize(&img, levels, num_threads, FilterOptions::default());
+        common::assert_matches_reference("posterize", &img, &result, levels as i32, FilterOptions::default(), &format!("{} levels", levels));
+    }
+    for (gamma, num_threads) in [(0.45, 1), (1.0, 3), (2.2, 8)] {
+        let filter = FilterOptions { gamma: Some(gamma), ..FilterOptions::default() };
+        let result = point::apply_gamma(&img, gamma, num_threads, filter);
+        common::assert_matches_reference("gamma", &img, &result, 0, filter, &format!("gamma {}", gamma));
+    }
+}
+
+#[test]
+fn point_operations_are_independent_of_worker_count() {
+    let img = common::fixture();
+    let posterized = point::apply_posterize(&img, 4, 1, FilterOptions::default());
+    let corrected = point::apply_gamma(&img, 1.8, 1, FilterOptions::default());
+    for num_threads in [2, 3, 8, 100] {
+        assert!(point::apply_posterize(&img, 4, num_threads, FilterOptions::default()) == posterized, "{} threads", num_threads);
+        assert!(point::apply_gamma(&img, 1.8, num_threads, FilterOptions::default()) == corrected, "{} threads", num_threads);
+    }
+}
+
+#[test]
+fn posterize_leaves_only_its_levels() {
+    let result = point::apply_posterize(&ramp(), 4, 3, FilterOptions::default());
+    let values: HashSet<u8> = result.pixels().flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    assert_eq!(values, HashSet::from([0, 85, 170, 255]));
+    assert!(result.pixels().all(|pixel| pixel[3] == 200));
+}
+
+#[test]
+fn gamma_bends_the_midtones_only() {
+    let img = ramp();
+    assert!(point::apply_gamma(&img, 1.0, 4, FilterOptions::default()) == img);
+    let brighter = point::apply_gamma(&img, 2.2, 4, FilterOptions::default());
+    assert_eq!(brighter.get_pixel(0, 0)[0], 0);
+    assert_eq!(brighter.get_pixel(255, 0)[0], 255);
+    assert!(brighter.get_pixel(128, 0)[0] > 128);
+    assert!(point::apply_gamma(&img, 0.5, 4, FilterOptions::default()).get_pixel(128, 0)[0] < 128);
+}
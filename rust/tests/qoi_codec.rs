@@ -0,0 +1,62 @@
+// QOI files read and written through the qoi crate: lossless RGBA round trips, RGB files widened
+// to opaque RGBA, and damaged input reported rather than decoded.
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rust_filter::qoi_codec;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("qoi_codec_{}_{}.qoi", std::process::id(), name)).to_string_lossy().into_owned()
+}
+
+// Runs, repeats and gradients, so the encoder uses each of its chunk kinds
+fn image() -> RgbaImage {
+    ImageBuffer::from_fn(37, 23, |x, y| match (x / 8 + y / 8) % 3 {
+        0 => Rgba([200, 30, 30, 255]),
+        1 => Rgba([x as u8 * 3, y as u8 * 5, 90, 255]),
+        _ => Rgba([(x * y) as u8, 17, (x ^ y) as u8, (x * 11) as u8]),
+    })
+}
+
+#[test]
+fn recognizes_the_extension() {
+    assert!(qoi_codec::is_qoi("out.qoi"));
+    assert!(qoi_codec::is_qoi("OUT.QOI"));
+    assert!(!qoi_codec::is_qoi("out.qoi.png"));
+}
+
+#[test]
+fn round_trips_losslessly() {
+    let img = image();
+    let path = temp_path("round_trip");
+    qoi_codec::save(&path, &img).unwrap();
+    let loaded = qoi_codec::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(loaded, img);
+}
+
+#[test]
+fn rgb_files_load_opaque() {
+    let rgb: Vec<u8> = (0..4 * 3).flat_map(|i| [i as u8 * 20, 100, 255 - i as u8]).collect();
+    let path = temp_path("rgb");
+    std::fs::write(&path, qoi::encode_to_vec(&rgb, 4, 3).unwrap()).unwrap();
+    let loaded = qoi_codec::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(loaded.dimensions(), (4, 3));
+    for (pixel, rgb) in loaded.pixels().zip(rgb.chunks_exact(3)) {
+        assert_eq!(pixel.0, [rgb[0], rgb[1], rgb[2], 255]);
+    }
+}
+
+#[test]
+fn damaged_files_are_errors() {
+    let path = temp_path("damaged");
+    qoi_codec::save(&path, &image()).unwrap();
+    let data = std::fs::read(&path).unwrap();
+
+    std::fs::write(&path, &data[..data.len() / 2]).unwrap();
+    assert!(qoi_codec::load(&path).is_err());
+    std::fs::write(&path, b"not a qoi file at all").unwrap();
+    assert!(qoi_codec::load(&path).is_err());
+    let _ = std::fs::remove_file(&path);
+}
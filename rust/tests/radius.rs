@@ -0,0 +1,66 @@
+// Degenerate radii: 0 is the identity, a radius wider than the image clamps at the border, and a
+// negative one is refused by the binary before anything is loaded.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::colorspace::ColorSpace;
+use rust_filter::{blur, kuwahara};
+use std::process::Command;
+
+mod common;
+
+type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+fn pattern(width: u32, height: u32) -> Image {
+    Image::from_fn(width, height, |x, y| Rgba([(x * 37 + y * 11) as u8, (y * 53) as u8, ((x ^ y) * 29) as u8, (200 + x) as u8]))
+}
+
+#[test]
+fn zero_radius_is_identity() {
+    let img = pattern(13, 7);
+    let lab = FilterOptions { colorspace: ColorSpace::Lab, linear: true, ..FilterOptions::default() };
+    for filter in [FilterOptions::default(), lab] {
+        assert!(blur::apply_gaussian_blur(&img, 0, 4, filter) == img);
+        assert!(kuwahara::apply_kuwahara_filter(&img, 0, 4, filter) == img);
+    }
+    assert_eq!(blur::generate_gaussian_kernel(0), vec![1.0]);
+}
+
+#[test]
+fn oversized_radius_clamps_at_the_border() {
+    let img = pattern(9, 5);
+    let longest = kuwahara::apply_kuwahara_filter(&img, 9, 2, FilterOptions::default());
+    assert!(kuwahara::apply_kuwahara_filter(&img, 500, 2, FilterOptions::default()) == longest);
+
+    let single = pattern(1, 1);
+    assert!(blur::apply_gaussian_blur(&single, 50, 2, FilterOptions::default()) == single);
+    let flat = Image::from_pixel(6, 4, Rgba([90, 140, 30, 255]));
+    assert!(blur::apply_gaussian_blur(&flat, 50, 2, FilterOptions::default()) == flat);
+}
+
+#[test]
+fn huge_blur_radius_is_capped_at_the_longest_side() {
+    let img = common::fixture();
+    // 96x64: a radius of 2 billion would otherwise allocate a kernel of 32 GB
+    let capped = blur::apply_gaussian_blur(&img, 96, 2, FilterOptions::default());
+    assert!(blur::apply_gaussian_blur(&img, 2_000_000_000, 2, FilterOptions::default()) == capped);
+    assert_eq!(blur::capped_radius(2_000_000_000, 96, 64), 96);
+}
+
+#[test]
+#[should_panic(expected = "radius must not be negative")]
+fn negative_radius_panics_in_the_library() {
+    blur::apply_gaussian_blur(&pattern(4, 4), -1, 1, FilterOptions::default());
+}
+
+#[test]
+fn negative_radius_is_an_error_on_the_command_line() {
+    for operation in ["blur", "kuwahara"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_rust_filter"))
+            .args([operation, "missing.png", "out.png", "-3"])
+            .output()
+            .expect("Failed to run rust_filter");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Radius must not be negative"));
+    }
+}
@@ -0,0 +1,66 @@
+// Raw frames: the spec parser, frame sizes with odd dimensions, and I420 and NV12 carrying the
+// same chroma decoding to the same pixels.
+
+use rust_filter::raw::{self, RawFormat, RawSpec};
+
+fn spec(s: &str) -> RawSpec {
+    s.parse().unwrap()
+}
+
+// A 3x3 frame whose chroma is 2x2, each sample distinct so a wrong index shows
+const LUMA: [u8; 9] = [16, 60, 100, 120, 140, 160, 180, 200, 235];
+const U: [u8; 4] = [90, 128, 160, 200];
+const V: [u8; 4] = [200, 128, 100, 60];
+
+#[test]
+fn parses_format_and_size() {
+    assert_eq!(spec("rgba8:1920x1080"), RawSpec { format: RawFormat::Rgba8, width: 1920, height: 1080 });
+    assert_eq!(spec("i420:3x3").format, RawFormat::Yuv420p);
+    assert_eq!(spec("nv12:3x3").format, RawFormat::Nv12);
+    assert!("rgba8".parse::<RawSpec>().is_err());
+    assert!("bgr8:2x2".parse::<RawSpec>().is_err());
+    assert!("gray8:2xtwo".parse::<RawSpec>().is_err());
+}
+
+#[test]
+fn odd_dimensions_round_the_chroma_up() {
+    assert_eq!(spec("yuv420p:3x3").frame_len(), 9 + 2 * 4);
+    assert_eq!(spec("nv12:5x1").frame_len(), 5 + 2 * 3);
+    assert_eq!(spec("rgb8:3x3").frame_len(), 27);
+}
+
+#[test]
+fn i420_and_nv12_decode_alike() {
+    let i420 = [&LUMA[..], &U, &V].concat();
+    let nv12: Vec<u8> = LUMA.iter().copied().chain(U.iter().zip(V).flat_map(|(&u, v)| [u, v])).collect();
+
+    let planar = raw::decode(&spec("yuv420p:3x3"), i420).unwrap();
+    let interleaved = raw::decode(&spec("nv12:3x3"), nv12).unwrap();
+    assert_eq!(planar, interleaved);
+
+    // Black and white luma with neutral chroma stay gray
+    let gray = raw::decode(&spec("i420:1x1"), vec![16, 128, 128]).unwrap();
+    assert_eq!(gray.get_pixel(0, 0).0, [0, 0, 0, 255]);
+    let gray = raw::decode(&spec("i420:1x1"), vec![235, 128, 128]).unwrap();
+    assert_eq!(gray.get_pixel(0, 0).0, [255, 255, 255, 255]);
+
+    // The last column and row read the last chroma sample: strong U makes it blue, weak V less red
+    let corner = planar.get_pixel(2, 2).0;
+    assert!(corner[2] == 255 && corner[0] < corner[2], "{:?}", corner);
+    // The top-left 2x2 block shares one strongly red sample
+    let top = planar.get_pixel(1, 1).0;
+    assert!(top[0] > top[2], "{:?}", top);
+    assert_eq!(planar.get_pixel(0, 2).0, raw::decode(&spec("i420:1x1"), vec![180, U[2], V[2]]).unwrap().get_pixel(0, 0).0);
+}
+
+#[test]
+fn short_input_is_rejected_and_trailing_frames_ignored() {
+    let err = raw::decode(&spec("i420:3x3"), vec![0; 16]).unwrap_err();
+    assert!(err.contains("16 bytes, 17 needed"), "{}", err);
+
+    let two_frames = [vec![7, 8], vec![9, 9]].concat();
+    let img = raw::decode(&spec("gray8:2x1"), two_frames).unwrap();
+    assert_eq!(img.as_raw(), &[7, 7, 7, 255, 8, 8, 8, 255]);
+    let img = raw::decode(&spec("rgb8:1x1"), vec![1, 2, 3]).unwrap();
+    assert_eq!(img.as_raw(), &[1, 2, 3, 255]);
+}
@@ -0,0 +1,46 @@
+// The serial reference behind `--verify` must agree with the parallel filters, or `--verify` would
+// report mismatches that are not there.
+
+use rust_filter::cli::FilterOptions;
+use rust_filter::{blur, kuwahara, synthetic, verify};
+
+mod common;
+
+#[test]
+fn reference_blur_matches_every_pixel() {
+    let img = common::fixture();
+    for linear in [false, true] {
+        let filter = FilterOptions { linear, ..FilterOptions::default() };
+        for radius in [1, 4, 12] {
+            let result = blur::apply_gaussian_blur(&img, radius, 3, filter);
+            common::assert_matches_reference("blur", &img, &result, radius, filter, &format!("radius {} linear {}", radius, linear));
+        }
+    }
+}
+
+#[test]
+fn samples_are_distinct_and_inside_the_image() {
+    let small = verify::sample_points(40, 30);
+    assert_eq!(small.len(), 1200);
+
+    let mut large = verify::sample_points(1000, 700);
+    assert_eq!(large.len(), verify::SAMPLE_PIXELS);
+    assert!(large.iter().all(|&(x, y)| x < 1000 && y < 700));
+    large.sort();
+    large.dedup();
+    assert_eq!(large.len(), verify::SAMPLE_PIXELS);
+}
+
+// A smooth gradient keeps the quadrants' variances close, and the sums of a large image grow past
+// what f32 holds exactly, which used to flip the choice between quadrants towards the bottom right
+#[test]
+fn reference_kuwahara_matches_on_a_large_gradient() {
+    let (width, height) = (2048, 2048);
+    let img = synthetic::load(&format!("synthetic:gradient:{}x{}", width, height), 4).expect("Failed to generate image");
+    let result = kuwahara::apply_kuwahara_filter(&img, 5, 4, FilterOptions::default());
+    let last_rows: Vec<_> = (height - 2..height).flat_map(|y| (0..width).map(move |x| (x, y))).collect();
+    for points in [last_rows, verify::sample_points(width, height)] {
+        let comparison = verify::check_reference("kuwahara", &img, &result, 5, FilterOptions::default(), &points, 0);
+        assert_eq!(comparison.mismatches, 0, "first at {:?}", comparison.first_mismatches);
+    }
+}
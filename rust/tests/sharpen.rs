@@ -0,0 +1,44 @@
+// The `sharpen` operation: the unsharp mask must match the serial reference whatever the thread
+// count, steepen edges and leave differences within the threshold alone.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::sharpen;
+
+mod common;
+
+// Mid gray on the left, lighter gray on the right
+fn step() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(32, 16, |x, _| if x < 16 { Rgba([100, 100, 100, 255]) } else { Rgba([150, 150, 150, 255]) })
+}
+
+#[test]
+fn sharpen_matches_the_reference() {
+    let img = common::fixture();
+    for (linear, amount, threshold) in [(false, 1.0, 0), (true, 1.5, 0), (false, 0.8, 6)] {
+        let filter = FilterOptions { linear, amount, sharpen_threshold: threshold, ..FilterOptions::default() };
+        let result = sharpen::apply_unsharp_mask(&img, 3, amount, threshold, 3, filter);
+        common::assert_matches_reference("sharpen", &img, &result, 3, filter, &format!("linear {} amount {} threshold {}", linear, amount, threshold));
+    }
+}
+
+#[test]
+fn sharpen_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| sharpen::apply_unsharp_mask(&img, 2, 1.0, 0, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn sharpen_steepens_an_edge() {
+    let sharpened = sharpen::apply_unsharp_mask(&step(), 2, 1.0, 0, 4, FilterOptions::default());
+    assert!(sharpened.get_pixel(15, 8)[0] < 100 && sharpened.get_pixel(16, 8)[0] > 150);
+    assert_eq!(sharpened.get_pixel(0, 8), &Rgba([100, 100, 100, 255]));
+    assert_eq!(sharpened.get_pixel(31, 8), &Rgba([150, 150, 150, 255]));
+}
+
+#[test]
+fn threshold_leaves_small_differences_alone() {
+    let img = step();
+    assert!(sharpen::apply_unsharp_mask(&img, 2, 1.0, 50, 4, FilterOptions::default()) == img);
+    assert!(sharpen::apply_unsharp_mask(&img, 0, 1.0, 0, 4, FilterOptions::default()) == img);
+}
@@ -0,0 +1,74 @@
+// `--sigma`, `--sigma-x` and `--sigma-y`: the blur's Gaussian set by its sigma, as other tools
+// specify it, with the radius reaching three sigmas when it is left at 0.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::blur;
+use rust_filter::cli::FilterOptions;
+use std::path::PathBuf;
+
+mod common;
+
+fn sigma(sigma_x: f64, sigma_y: f64) -> FilterOptions {
+    FilterOptions { sigma_x: Some(sigma_x), sigma_y: Some(sigma_y), ..FilterOptions::default() }
+}
+
+// A white row across a black image
+fn line() -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(24, 21, |_, y| if y == 10 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) })
+}
+
+#[test]
+fn default_sigma_is_a_third_of_the_radius() {
+    assert_eq!(blur::gaussian_kernel(6, 2.0), blur::generate_gaussian_kernel(6));
+    assert_eq!(blur::sigmas(9, FilterOptions::default()), (3.0, 3.0));
+    assert_eq!(blur::sigmas(9, FilterOptions { sigma_y: Some(1.5), ..FilterOptions::default() }), (3.0, 1.5));
+}
+
+#[test]
+fn radius_reaches_three_sigmas_when_left_out() {
+    assert_eq!(blur::blur_radius(0, sigma(2.0, 2.0)), 6);
+    assert_eq!(blur::blur_radius(0, sigma(1.5, 2.2)), 7);
+    // A given radius wins, and without a sigma 0 still means no blur
+    assert_eq!(blur::blur_radius(5, sigma(10.0, 10.0)), 5);
+    assert_eq!(blur::blur_radius(0, FilterOptions::default()), 0);
+}
+
+#[test]
+fn sigma_alone_matches_the_equivalent_radius() {
+    let img = line();
+    let by_radius = blur::apply_gaussian_blur(&img, 6, 3, FilterOptions::default());
+    assert!(blur::apply_gaussian_blur(&img, 0, 3, sigma(2.0, 2.0)) == by_radius);
+}
+
+#[test]
+fn sigma_x_alone_leaves_the_vertical_profile() {
+    let filter = FilterOptions { sigma_x: Some(2.0), ..FilterOptions::default() };
+    // Without a radius the vertical pass has no sigma of its own to fall back on
+    assert_eq!(blur::sigmas(0, filter), (2.0, 0.0));
+    assert_eq!(blur::gaussian_kernel(2, 0.0), [0.0, 0.0, 1.0, 0.0, 0.0]);
+    // Every row is flat, so only a vertical blur could change the line
+    assert!(blur::apply_gaussian_blur(&line(), 0, 3, filter) == line());
+}
+
+#[test]
+fn each_pass_takes_its_own_sigma() {
+    let img = line();
+    // Wide along the line, next to nothing across it
+    let result = blur::apply_gaussian_blur(&img, 6, 3, sigma(3.0, 0.1));
+    for (x, y, pixel) in result.enumerate_pixels() {
+        let expected = if y == 10 { 255 } else { 0 };
+        assert_eq!(pixel[0], expected, "({}, {})", x, y);
+    }
+    let across = blur::apply_gaussian_blur(&img, 6, 3, sigma(0.1, 3.0));
+    assert!(across.get_pixel(5, 12)[0] > 0 && across.get_pixel(5, 10)[0] < 255);
+}
+
+#[test]
+fn reference_matches_with_sigmas() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let img = image::open(path).expect("Missing fixture").to_rgba8();
+    for (radius, filter) in [(0, sigma(1.2, 2.5)), (4, sigma(3.0, 0.8)), (5, FilterOptions { sigma_x: Some(1.0), ..FilterOptions::default() })] {
+        let result = blur::apply_gaussian_blur(&img, radius, 3, filter);
+        common::assert_matches_reference("blur", &img, &result, radius, filter, &format!("radius {} {:?}", radius, filter));
+    }
+}
@@ -0,0 +1,36 @@
+// The `sobel` operation: the edge map must match the serial reference whatever the thread count,
+// stay black on flat regions and light up along an edge.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::sobel;
+
+mod common;
+
+#[test]
+fn sobel_matches_the_reference() {
+    let img = common::fixture();
+    for linear in [false, true] {
+        let filter = FilterOptions { linear, ..FilterOptions::default() };
+        for radius in [0, 2] {
+            let result = sobel::apply_sobel(&img, radius, 3, filter);
+            common::assert_matches_reference("sobel", &img, &result, radius, filter, &format!("radius {} linear {}", radius, linear));
+        }
+    }
+}
+
+#[test]
+fn sobel_is_independent_of_worker_count() {
+    let img = common::fixture();
+    common::assert_independent_of_worker_count(&[2, 5, 8], |num_threads| sobel::apply_sobel(&img, 1, num_threads, FilterOptions::default()));
+}
+
+#[test]
+fn sobel_marks_only_the_edge() {
+    let step = ImageBuffer::from_fn(32, 16, |x, _| if x < 16 { Rgba([20, 20, 20, 180]) } else { Rgba([200, 200, 200, 180]) });
+    let edges = sobel::apply_sobel(&step, 0, 4, FilterOptions::default());
+    for (x, _, pixel) in edges.enumerate_pixels() {
+        let expected = if x == 15 || x == 16 { 255 } else { 0 };
+        assert_eq!(pixel.0, [expected, expected, expected, 180], "column {}", x);
+    }
+}
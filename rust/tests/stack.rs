@@ -0,0 +1,124 @@
+// The `stack` and `focus-stack` operations: aligned frames combined per channel, or per pixel
+// from the sharpest frame, decoded and reduced in parallel with the same result for any thread count.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::blur;
+use rust_filter::cli::FilterOptions;
+use rust_filter::stack::{self, StackMode};
+
+mod common;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Frames of one gradient, each offset by its own small amount of "noise"
+fn exposures(count: u32) -> Vec<Frame> {
+    (0..count)
+        .map(|i| ImageBuffer::from_fn(17, 11, |x, y| Rgba([(x * 10 + i * 7 % 5) as u8, (y * 20) as u8, (100 + i * 3) as u8, 255])))
+        .collect()
+}
+
+fn stack_frames(frames: &[Frame], mode: StackMode, num_threads: usize) -> Result<Frame, String> {
+    stack::stack(frames.len(), |index| Ok(frames[index].clone()), mode, num_threads)
+}
+
+#[test]
+fn mean_rounds_the_average_of_every_channel() {
+    let frames = exposures(6);
+    let result = stack_frames(&frames, StackMode::Mean, 3).expect("Stacking failed");
+    for (x, y, pixel) in result.enumerate_pixels() {
+        for channel in 0..4 {
+            let total: u32 = frames.iter().map(|frame| frame.get_pixel(x, y)[channel] as u32).sum();
+            assert_eq!(pixel[channel] as u32, (total + 3) / 6, "channel {} at ({}, {})", channel, x, y);
+        }
+    }
+}
+
+#[test]
+fn median_drops_outliers() {
+    let clean = exposures(5);
+    let mut frames = clean.clone();
+    // A hot pixel and a satellite trail, each in one frame only
+    frames[1].put_pixel(4, 4, Rgba([255, 255, 255, 255]));
+    for x in 0..17 {
+        frames[3].put_pixel(x, 7, Rgba([250, 250, 250, 255]));
+    }
+    let result = stack_frames(&frames, StackMode::Median, 4).expect("Stacking failed");
+    for (x, y) in [(4, 4), (9, 7)] {
+        for channel in 0..3 {
+            let values: Vec<u8> = clean.iter().map(|frame| frame.get_pixel(x, y)[channel]).collect();
+            let value = result.get_pixel(x, y)[channel];
+            assert!((*values.iter().min().unwrap()..=*values.iter().max().unwrap()).contains(&value), "channel {} at ({}, {}) is {}", channel, x, y, value);
+        }
+    }
+}
+
+#[test]
+fn even_medians_average_the_middle_values() {
+    let mut values = [10, 200, 3, 11];
+    assert_eq!(stack::median_of(&mut values), 11);
+    let mut values = [7, 1, 9];
+    assert_eq!(stack::median_of(&mut values), 7);
+}
+
+#[test]
+fn result_is_independent_of_thread_count() {
+    let frames = exposures(7);
+    for mode in [StackMode::Mean, StackMode::Median] {
+        let one = stack_frames(&frames, mode, 1).expect("Stacking failed");
+        for num_threads in [2, 3, 16] {
+            assert!(stack_frames(&frames, mode, num_threads).expect("Stacking failed") == one, "{:?} with {} threads", mode, num_threads);
+        }
+    }
+}
+
+#[test]
+fn frames_must_match_the_first() {
+    let mut frames = exposures(3);
+    frames[2] = ImageBuffer::new(5, 5);
+    for mode in [StackMode::Mean, StackMode::Median] {
+        assert_eq!(stack_frames(&frames, mode, 2).err().as_deref(), Some("Frame 3 is 5x5, expected 17x11 like the first"));
+    }
+    assert!(stack_frames(&[], StackMode::Mean, 2).is_err());
+    let failing = stack::stack(3, |index| if index == 1 { Err("missing.png: not found".to_string()) } else { Ok(exposures(1).remove(0)) }, StackMode::Mean, 2);
+    assert_eq!(failing.err().as_deref(), Some("missing.png: not found"));
+}
+
+// Fine detail everywhere, and a copy of it blurred
+fn detail_and_blurred() -> (Frame, Frame) {
+    let sharp = ImageBuffer::from_fn(64, 40, |x, y| Rgba([((x * 37 + y * 91) % 256) as u8, ((x * 11) % 256) as u8, ((y * 53) % 256) as u8, 255]));
+    let blurred = blur::apply_gaussian_blur(&sharp, 3, 2, FilterOptions::default());
+    (sharp, blurred)
+}
+
+// Two frames each in focus on one half, split at column 32
+fn half_focused() -> (Frame, Vec<Frame>) {
+    let (sharp, blurred) = detail_and_blurred();
+    let left = ImageBuffer::from_fn(64, 40, |x, y| if x < 32 { *sharp.get_pixel(x, y) } else { *blurred.get_pixel(x, y) });
+    let right = ImageBuffer::from_fn(64, 40, |x, y| if x < 32 { *blurred.get_pixel(x, y) } else { *sharp.get_pixel(x, y) });
+    (sharp, vec![left, right])
+}
+
+#[test]
+fn flat_images_have_no_laplacian_energy() {
+    let flat = ImageBuffer::from_pixel(9, 7, Rgba([90, 120, 30, 255]));
+    assert!(stack::laplacian_energy(&flat).pixels().all(|pixel| pixel.0 == [0, 0, 0, 255]));
+}
+
+#[test]
+fn focus_stack_takes_the_sharp_half_of_each_frame() {
+    let (sharp, frames) = half_focused();
+    let result = stack::focus_stack(frames.len(), |index| Ok(frames[index].clone()), 4).expect("Focus stacking failed");
+    // Near the seam the blurred sharpness maps of both frames overlap
+    let margin = stack::FOCUS_RADIUS as u32 + 2;
+    for (x, y, pixel) in result.enumerate_pixels() {
+        if x.abs_diff(32) > margin {
+            assert_eq!(pixel, sharp.get_pixel(x, y), "at ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn focus_stack_is_independent_of_thread_count() {
+    let (_, frames) = half_focused();
+    common::assert_independent_of_worker_count(&[2, 3, 16], |num_threads| stack::focus_stack(frames.len(), |index| Ok(frames[index].clone()), num_threads).expect("Focus stacking failed"));
+}
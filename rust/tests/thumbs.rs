@@ -0,0 +1,61 @@
+// The `thumbs` pipeline: every image in a directory decoded once and written at each size, fitted
+// inside the size's square without upscaling.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::thumbs;
+use std::fs;
+use std::path::PathBuf;
+
+// A fresh directory under the target's scratch space
+fn scratch(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("thumbs").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create scratch directory");
+    dir
+}
+
+fn gradient(width: u32, height: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(width, height, |x, y| Rgba([(x % 256) as u8, (y % 256) as u8, 90, 255]))
+}
+
+#[test]
+fn thumbnails_fit_their_square_without_upscaling() {
+    assert_eq!(thumbs::fit(400, 200, 100), (100, 50));
+    assert_eq!(thumbs::fit(200, 400, 100), (50, 100));
+    assert_eq!(thumbs::fit(1000, 3, 100), (100, 1));
+    assert_eq!(thumbs::fit(80, 60, 100), (80, 60));
+}
+
+#[test]
+fn sharpening_leaves_flat_areas_alone() {
+    let flat = ImageBuffer::from_pixel(12, 9, Rgba([40, 150, 220, 128]));
+    assert!(thumbs::sharpen(&flat) == flat);
+}
+
+#[test]
+fn every_source_is_written_at_every_size() {
+    let input = scratch("sources");
+    gradient(300, 150).save(input.join("wide.png")).expect("Failed to write source");
+    gradient(40, 90).save(input.join("tall.png")).expect("Failed to write source");
+    fs::write(input.join("notes.txt"), "not an image").expect("Failed to write note");
+    let output = scratch("written");
+
+    let stats = thumbs::generate(input.to_str().unwrap(), output.to_str().unwrap(), &[64, 128], true, 3).expect("Thumbnails failed");
+    assert_eq!((stats.sources, stats.thumbnails), (2, 4));
+    let size = |name: &str| image::image_dimensions(output.join(name)).expect("Missing thumbnail");
+    assert_eq!(size("wide_64.png"), (64, 32));
+    assert_eq!(size("wide_128.png"), (128, 64));
+    assert_eq!(size("tall_64.png"), (28, 64));
+    assert_eq!(size("tall_128.png"), (40, 90));
+}
+
+#[test]
+fn unreadable_sources_fail_the_run() {
+    let input = scratch("broken");
+    gradient(30, 30).save(input.join("good.png")).expect("Failed to write source");
+    fs::write(input.join("broken.png"), "not a png").expect("Failed to write broken source");
+    let output = scratch("broken_output");
+
+    let error = thumbs::generate(input.to_str().unwrap(), output.to_str().unwrap(), &[16], false, 2).err().expect("Expected a failure");
+    assert!(error.to_string().contains("broken.png"), "{}", error);
+}
@@ -0,0 +1,102 @@
+// The `tonemap` operation: the parallel luminance reduction must agree with one pass over the
+// whole image, every pixel must match the serial curve whatever the thread count, and the
+// brightest pixel of a Reinhard mapping must come out white.
+
+use image::codecs::hdr::HdrEncoder;
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+use rust_filter::tonemap::{self, Operator, Stats};
+
+// A scene spanning a wide range, from deep shadow to highlights fifty times middle gray
+fn hdr(width: u32, height: u32) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let l = 0.002 * (1.0 + x as f32).powf(2.0) * (1.0 + y as f32 / 4.0);
+        Rgba([l, l * 0.6, l * 0.3, 1.0])
+    })
+}
+
+// Maps every pixel one at a time with statistics of the whole image
+fn serial(src: &ImageBuffer<Rgba<f32>, Vec<f32>>, operator: Operator, exposure: f64) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let stats = Stats::of(src.as_raw());
+    let scale = tonemap::exposure_scale(&stats, exposure);
+    let white = stats.max * scale;
+    ImageBuffer::from_fn(src.width(), src.height(), |x, y| Rgba(tonemap::map_pixel(&src.get_pixel(x, y).0, operator, scale, white)))
+}
+
+#[test]
+fn merged_statistics_match_the_whole_image() {
+    let img = hdr(40, 30);
+    let whole = Stats::of(img.as_raw());
+    let parts: Vec<_> = img.as_raw().chunks(40 * 4 * 7).map(Stats::of).collect();
+    let merged = Stats::merge(&parts);
+    assert_eq!((merged.max, merged.count), (whole.max, whole.count));
+    assert!((merged.log_sum - whole.log_sum).abs() < 1e-9, "{} vs {}", merged.log_sum, whole.log_sum);
+}
+
+#[test]
+fn tonemap_matches_the_serial_mapping_at_any_thread_count() {
+    let img = hdr(50, 37);
+    for (operator, exposure) in [(Operator::Reinhard, 0.0), (Operator::Aces, 0.0), (Operator::Reinhard, -1.5), (Operator::Aces, 2.0)] {
+        let expected = serial(&img, operator, exposure);
+        for num_threads in [1, 3, 8, 100] {
+            assert!(tonemap::apply_tonemap(&img, operator, exposure, num_threads) == expected, "{:?} {} EV, {} threads", operator, exposure, num_threads);
+        }
+    }
+}
+
+#[test]
+fn reinhard_maps_the_brightest_pixel_to_white_and_keeps_black() {
+    let mut img = hdr(20, 10);
+    img.put_pixel(0, 0, Rgba([0.0, 0.0, 0.0, 1.0]));
+    img.put_pixel(5, 5, Rgba([500.0, 500.0, 500.0, 1.0]));
+    let result = tonemap::apply_tonemap(&img, Operator::Reinhard, 0.0, 4);
+    assert_eq!(result.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+    assert_eq!(result.get_pixel(5, 5), &Rgba([255, 255, 255, 255]));
+    // Colors keep their order: red stays the strongest channel
+    assert!(result.pixels().filter(|p| p[0] > 0 && p[0] < 255).all(|p| p[0] >= p[1] && p[1] >= p[2]));
+}
+
+#[test]
+fn exposure_brightens_and_aces_rolls_off_highlights() {
+    let img = hdr(30, 20);
+    let base = tonemap::apply_tonemap(&img, Operator::Aces, 0.0, 4);
+    let brighter = tonemap::apply_tonemap(&img, Operator::Aces, 1.0, 4);
+    assert!(base.pixels().zip(brighter.pixels()).all(|(a, b)| b[1] >= a[1]));
+    assert!(base.pixels().zip(brighter.pixels()).any(|(a, b)| b[1] > a[1]));
+    // Far above the key every channel saturates rather than wrapping
+    let hot = ImageBuffer::from_pixel(4, 4, Rgba([1e4f32, 1e4, 1e4, 1.0]));
+    let result = tonemap::apply_tonemap(&hot, Operator::Aces, 10.0, 2);
+    assert!(result.pixels().all(|p| p.0 == [255, 255, 255, 255]));
+}
+
+#[test]
+fn eight_bit_inputs_are_decoded_to_linear_light() {
+    let srgb = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(2, 2, Rgba([188, 255, 0, 128])));
+    let pixel = tonemap::linear(&srgb).get_pixel(0, 0).0;
+    assert!((pixel[0] - 0.5).abs() < 0.005 && pixel[1] == 1.0 && pixel[2] == 0.0, "{:?}", pixel);
+    assert!((pixel[3] - 128.0 / 255.0).abs() < 1e-6);
+    let float = DynamicImage::ImageRgba32F(ImageBuffer::from_pixel(2, 2, Rgba([0.5, 7.0, 0.0, 1.0])));
+    assert_eq!(tonemap::linear(&float).get_pixel(1, 1).0, [0.5, 7.0, 0.0, 1.0]);
+}
+
+#[test]
+fn radiance_files_keep_their_range() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("tonemap");
+    std::fs::create_dir_all(&dir).expect("Failed to create scratch directory");
+    let path = dir.join("scene.hdr");
+    let pixels = [Rgb([0.05f32, 0.5, 1.0]), Rgb([12.0, 40.0, 250.0])];
+    HdrEncoder::new(std::fs::File::create(&path).expect("Failed to create file")).encode(&pixels, 2, 1).expect("Failed to write Radiance file");
+    let img = tonemap::open(path.to_str().unwrap()).expect("Failed to read Radiance file");
+    let DynamicImage::ImageRgb32F(buffer) = img else { panic!("Expected float pixels, got {:?}", img.color()) };
+    // RGBE shares one exponent between the channels, keeping 8 bits of the brightest
+    for (read, written) in buffer.pixels().zip(pixels) {
+        let step = written.0.iter().fold(0.0f32, |max, &w| max.max(w)) / 128.0;
+        assert!(read.0.iter().zip(written.0).all(|(r, w)| (r - w).abs() <= step), "{:?} vs {:?}", read, written);
+    }
+}
+
+#[test]
+fn operators_parse_by_name() {
+    assert_eq!("reinhard".parse::<Operator>(), Ok(Operator::Reinhard));
+    assert_eq!("aces".parse::<Operator>(), Ok(Operator::Aces));
+    assert!("filmic".parse::<Operator>().is_err());
+}
@@ -0,0 +1,72 @@
+// The `transform` operation: every pixel must match the serial inverse map whatever the thread
+// count, the identity changes nothing, a quarter turn moves pixels where a rotation would and
+// what no source pixel covers comes out transparent.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::cli::FilterOptions;
+use rust_filter::transform;
+
+mod common;
+
+#[test]
+fn transform_matches_the_reference() {
+    let img = common::fixture();
+    let turned = FilterOptions { angle: 30.0, ..FilterOptions::default() };
+    let zoomed = FilterOptions { angle: -75.0, scale: 1.7, translate: (6.5, -3.0), linear: true, ..FilterOptions::default() };
+    let shrunk = FilterOptions { scale: 0.4, translate: (-20.0, 11.0), ..FilterOptions::default() };
+    for (num_threads, filter) in [(1, turned), (4, zoomed), (7, shrunk)] {
+        let result = transform::apply_transform(&img, num_threads, filter);
+        common::assert_matches_reference("transform", &img, &result, 0, filter, &format!("threads {}", num_threads));
+    }
+}
+
+#[test]
+fn transform_is_independent_of_worker_count() {
+    let img = common::fixture();
+    let filter = FilterOptions { angle: 40.0, scale: 1.3, ..FilterOptions::default() };
+    common::assert_independent_of_worker_count(&[2, 3, 8, 100], |num_threads| transform::apply_transform(&img, num_threads, filter));
+}
+
+#[test]
+fn identity_changes_nothing() {
+    let img = common::fixture();
+    assert!(transform::apply_transform(&img, 4, FilterOptions::default()) == img);
+}
+
+#[test]
+fn quarter_turn_rotates_pixels_counterclockwise() {
+    let img = ImageBuffer::from_fn(6, 6, |x, y| Rgba([(x * 40) as u8, (y * 40) as u8, 0, 255]));
+    let filter = FilterOptions { angle: 90.0, ..FilterOptions::default() };
+    let result = transform::apply_transform(&img, 3, filter);
+    // Turning counterclockwise on screen carries the right edge to the top
+    for y in 0..6 {
+        for x in 0..6 {
+            assert_eq!(result.get_pixel(x, y), img.get_pixel(5 - y, x), "({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn uncovered_areas_are_transparent() {
+    let img = ImageBuffer::from_pixel(20, 10, Rgba([200, 100, 40, 255]));
+    let shifted = FilterOptions { translate: (8.0, 0.0), ..FilterOptions::default() };
+    let result = transform::apply_transform(&img, 2, shifted);
+    assert_eq!(result.get_pixel(3, 5), &Rgba([0, 0, 0, 0]));
+    assert_eq!(result.get_pixel(12, 5), &Rgba([200, 100, 40, 255]));
+    // Colors are weighted by alpha, so the faded border keeps the color rather than darkening
+    let shrunk = FilterOptions { scale: 0.5, ..FilterOptions::default() };
+    let result = transform::apply_transform(&img, 2, shrunk);
+    assert_eq!(result.get_pixel(0, 0)[3], 0);
+    assert_eq!(result.get_pixel(10, 5), &Rgba([200, 100, 40, 255]));
+    assert!(result.pixels().filter(|p| p[3] > 0).all(|p| p.0[..3] == [200, 100, 40]));
+}
+
+#[test]
+fn scaling_magnifies_about_the_center() {
+    let img = ImageBuffer::from_fn(8, 8, |x, y| Rgba([(x * 30) as u8, (y * 30) as u8, 60, 255]));
+    let filter = FilterOptions { scale: 2.0, ..FilterOptions::default() };
+    let result = transform::apply_transform(&img, 3, filter);
+    // Distances from the center halve: output pixels 4 and 0 sample the source at 3.75 and 1.75
+    assert_eq!(result.get_pixel(4, 4), &Rgba([113, 113, 60, 255]));
+    assert_eq!(result.get_pixel(0, 4), &Rgba([53, 113, 60, 255]));
+}
@@ -0,0 +1,73 @@
+// The `varblur` operation: a black mask must keep the image, a white one must match the plain blur
+// at the full radius, values in between must mix the nearest levels, and the result must not
+// depend on the thread count.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter::blur;
+use rust_filter::cli::FilterOptions;
+use rust_filter::varblur;
+
+mod common;
+
+fn flat_mask(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, value: u8) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_pixel(img.width(), img.height(), Rgba([value, value, value, 255]))
+}
+
+// Black on the left, rising to white on the right
+fn ramp(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let width = img.width();
+    ImageBuffer::from_fn(width, img.height(), |x, _| {
+        let value = (x * 255 / (width - 1)) as u8;
+        Rgba([value, value, value, 255])
+    })
+}
+
+#[test]
+fn black_keeps_the_image_and_white_blurs_it_fully() {
+    let img = common::fixture();
+    let filter = FilterOptions::default();
+    let sharp = varblur::apply_variable_blur(&img, &flat_mask(&img, 0), 6, 3, 4, filter).unwrap();
+    assert!(sharp == img);
+    let blurred = varblur::apply_variable_blur(&img, &flat_mask(&img, 255), 6, 3, 4, filter).unwrap();
+    assert!(blurred == blur::apply_gaussian_blur(&img, 6, 2, filter));
+}
+
+#[test]
+fn varblur_is_independent_of_worker_count() {
+    let img = common::fixture();
+    let mask = ramp(&img);
+    common::assert_independent_of_worker_count(&[2, 3, 8, 64], |num_threads| varblur::apply_variable_blur(&img, &mask, 5, 4, num_threads, FilterOptions::default()).unwrap());
+}
+
+#[test]
+fn mask_values_between_levels_mix_the_nearest_two() {
+    let levels = [0, 100, 200].map(|value| ImageBuffer::from_pixel(1, 1, Rgba([value, value, value, 255])));
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 0), [0, 0, 0, 255]);
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 64), [50, 50, 50, 255]);
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 191), [150, 150, 150, 255]);
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 255), [200, 200, 200, 255]);
+}
+
+#[test]
+fn levels_are_spread_evenly_up_to_the_radius() {
+    assert_eq!(varblur::level_radii(8, 4), vec![0, 2, 4, 6, 8]);
+    assert_eq!(varblur::level_radii(3, 1), vec![0, 3]);
+}
+
+#[test]
+fn ramp_leaves_the_dark_side_sharp() {
+    let img = common::fixture();
+    let result = varblur::apply_variable_blur(&img, &ramp(&img), 8, 4, 4, FilterOptions::default()).unwrap();
+    let blurred = blur::apply_gaussian_blur(&img, 8, 4, FilterOptions::default());
+    let height = img.height();
+    assert!((0..height).all(|y| result.get_pixel(0, y) == img.get_pixel(0, y)));
+    let last = img.width() - 1;
+    assert!((0..height).all(|y| result.get_pixel(last, y) == blurred.get_pixel(last, y)));
+}
+
+#[test]
+fn mask_of_another_size_is_rejected() {
+    let img = common::fixture();
+    let mask = ImageBuffer::from_pixel(img.width() + 1, img.height(), Rgba([255, 255, 255, 255]));
+    assert!(varblur::apply_variable_blur(&img, &mask, 4, 2, 2, FilterOptions::default()).is_err());
+}
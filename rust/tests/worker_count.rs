@@ -0,0 +1,70 @@
+// The worker count only decides who filters which rows, never the pixels. For random small images,
+// including ones with fewer rows than workers, every count must give exactly what a naive
+// single-threaded blur does.
+
+use image::{ImageBuffer, Rgba};
+use proptest::prelude::*;
+use rust_filter::blur;
+use rust_filter::cli::FilterOptions;
+use rust_filter::kuwahara;
+
+type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+const WORKERS: [usize; 5] = [1, 2, 3, 7, 16];
+
+// Same arithmetic as the filter: a horizontal then a vertical pass, edges clamped, weights summed
+// in kernel order in f64, and each pass rounded to u8
+fn naive_blur(img: &Image, radius: i32) -> Image {
+    let (width, height) = img.dimensions();
+    // A radius past the longest side is capped there, as the filter caps it
+    let radius = radius.min(width.max(height) as i32);
+    let kernel = blur::generate_gaussian_kernel(radius as usize);
+    let pass = |src: &Image, dx: i32, dy: i32| {
+        Image::from_fn(width, height, |x, y| {
+            let mut sums = [0.0; 4];
+            for k in -radius..=radius {
+                let sx = (x as i32 + k * dx).clamp(0, width as i32 - 1) as u32;
+                let sy = (y as i32 + k * dy).clamp(0, height as i32 - 1) as u32;
+                let weight = kernel[(k + radius) as usize];
+                for (sum, &value) in sums.iter_mut().zip(&src.get_pixel(sx, sy).0) {
+                    *sum += value as f64 * weight;
+                }
+            }
+            Rgba(sums.map(|sum| sum.round() as u8))
+        })
+    };
+    pass(&pass(img, 1, 0), 0, 1)
+}
+
+fn image() -> impl Strategy<Value = Image> {
+    (1u32..40, 1u32..40).prop_flat_map(|(width, height)| {
+        proptest::collection::vec(any::<u8>(), (width * height * 4) as usize)
+            .prop_map(move |data| Image::from_raw(width, height, data).unwrap())
+    })
+}
+
+proptest! {
+    #[test]
+    fn blur_is_independent_of_worker_count(img in image(), radius in 1i32..10) {
+        let expected = naive_blur(&img, radius);
+        for workers in WORKERS {
+            let result = blur::apply_gaussian_blur(&img, radius, workers, FilterOptions::default());
+            prop_assert!(result == expected, "radius {} with {} workers differs from the naive blur", radius, workers);
+        }
+    }
+}
+
+// Single rows and columns, where most worker counts exceed the rows of one of the two passes
+#[test]
+fn thin_images_filter_with_more_workers_than_rows() {
+    for (width, height) in [(1, 1), (1, 9), (9, 1), (2, 17), (17, 2)] {
+        let img = Image::from_fn(width, height, |x, y| Rgba([(x * 29) as u8, (y * 31) as u8, ((x + y) * 7) as u8, 255]));
+        let kuwahara_reference = kuwahara::apply_kuwahara_filter(&img, 2, 1, FilterOptions::default());
+        for workers in [0, 1, 2, 3, 7, 16] {
+            let blurred = blur::apply_gaussian_blur(&img, 3, workers, FilterOptions::default());
+            assert!(blurred == naive_blur(&img, 3), "blur of {}x{} with {} workers", width, height, workers);
+            let filtered = kuwahara::apply_kuwahara_filter(&img, 2, workers, FilterOptions::default());
+            assert!(filtered == kuwahara_reference, "Kuwahara of {}x{} with {} workers", width, height, workers);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+import { joinBands, splitBands } from './shard.js';
+
+const form = document.querySelector('form');
+const input = document.querySelector('#input');
+const output = document.querySelector('#output');
+const status = document.querySelector('#status');
+form.workers.value = navigator.hardwareConcurrency || 4;
+
+let pool = [];
+
+// Workers are kept between runs so the module is only compiled once per worker
+function workers(count) {
+  while (pool.length < count) {
+    pool.push(new Worker(new URL('./worker.js', import.meta.url), { type: 'module' }));
+  }
+  return pool.slice(0, count);
+}
+
+function run(worker, message) {
+  return new Promise((resolve, reject) => {
+    worker.onmessage = ({ data }) => (data.error ? reject(new Error(data.error)) : resolve(data.image));
+    worker.postMessage(message, [message.image.data.buffer]);
+  });
+}
+
+form.file.addEventListener('change', async () => {
+  const bitmap = await createImageBitmap(form.file.files[0]);
+  input.width = output.width = bitmap.width;
+  input.height = output.height = bitmap.height;
+  input.getContext('2d').drawImage(bitmap, 0, 0);
+});
+
+form.addEventListener('submit', async (event) => {
+  event.preventDefault();
+  const operation = form.operation.value;
+  const radius = Number(form.radius.value);
+  const image = input.getContext('2d').getImageData(0, 0, input.width, input.height);
+  const bands = splitBands(image, Number(form.workers.value), radius);
+
+  status.textContent = `Filtering with ${bands.length} workers...`;
+  const start = performance.now();
+  try {
+    const pool = workers(bands.length);
+    const results = await Promise.all(bands.map((band, index) => run(pool[index], { index, operation, radius, image: band.image })));
+    results.forEach((result, index) => (bands[index].image = result));
+    output.getContext('2d').putImageData(joinBands(bands, image.width, image.height), 0, 0);
+    status.textContent = `${operation} radius ${radius}: ${(performance.now() - start).toFixed(0)} ms with ${bands.length} workers`;
+  } catch (error) {
+    status.textContent = error.message;
+  }
+});
@@ -0,0 +1,25 @@
+// Splits an image into horizontal bands, one per Web Worker. Each band carries `halo` extra
+// rows from its neighbours so pixels near a cut see the same surroundings as in the whole image.
+export function splitBands(image, count, halo) {
+  const { width, height } = image;
+  const rowsPerBand = Math.max(1, Math.ceil(height / count));
+  const bands = [];
+  for (let start = 0; start < height; start += rowsPerBand) {
+    const end = Math.min(start + rowsPerBand, height);
+    const top = Math.max(0, start - halo);
+    const bottom = Math.min(height, end + halo);
+    const data = image.data.slice(top * width * 4, bottom * width * 4);
+    bands.push({ start, end, top, image: new ImageData(data, width, bottom - top) });
+  }
+  return bands;
+}
+
+// Copies the rows each band owns, without its halo, back into one image
+export function joinBands(bands, width, height) {
+  const out = new ImageData(width, height);
+  for (const { start, end, top, image } of bands) {
+    const from = (start - top) * width * 4;
+    out.data.set(image.data.subarray(from, from + (end - start) * width * 4), start * width * 4);
+  }
+  return out;
+}
@@ -0,0 +1,13 @@
+import init, { blur, kuwahara } from './pkg/rust_filter.js';
+
+const ready = init();
+
+self.onmessage = async ({ data: { index, operation, radius, image } }) => {
+  await ready;
+  try {
+    const result = (operation === 'kuwahara' ? kuwahara : blur)(image, radius);
+    self.postMessage({ index, image: result }, [result.data.buffer]);
+  } catch (error) {
+    self.postMessage({ index, error: String(error) });
+  }
+};
@@ -0,0 +1,124 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::blur::{self, ImageData};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::integral::IntegralImage;
+use rust_filter_async::kuwahara;
+use rust_filter_async::timing::WorkerClock;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use tokio::sync::Mutex;
+
+const SIZES: [u32; 3] = [256, 512, 1024];
+const RADII: [usize; 3] = [3, 10, 30];
+const TASKS: usize = 4;
+
+// Deterministic gradient with some high-frequency detail so no branch is trivially predictable
+fn test_image(size: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(size, size, |x, y| {
+        let noise = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663)) as u8;
+        Rgba([(x * 255 / size) as u8, (y * 255 / size) as u8, noise, 255])
+    })
+}
+
+fn bench_kernel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gaussian_kernel");
+    for radius in RADII {
+        group.bench_with_input(BenchmarkId::from_parameter(radius), &radius, |b, &radius| {
+            b.iter(|| blur::generate_gaussian_kernel(black_box(radius)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_transpose(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transpose");
+    for size in SIZES {
+        let src = ImageData::from_dynamic_image(&DynamicImage::ImageRgba8(test_image(size)));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &src, |b, src| {
+            b.iter(|| src.transpose())
+        });
+    }
+    group.finish();
+}
+
+fn bench_horizontal_blur(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("horizontal_blur");
+    for size in SIZES {
+        let src = Arc::new(ImageData::from_dynamic_image(&DynamicImage::ImageRgba8(test_image(size))));
+        for radius in RADII {
+            let kernel = Arc::new(blur::generate_gaussian_kernel(radius));
+            let dst = Arc::new(Mutex::new(ImageData {
+                data: vec![0; src.data.len()],
+                width: src.width,
+                height: src.height,
+                channels: src.channels,
+            }));
+            group.bench_with_input(BenchmarkId::new(format!("{}px", size), radius), &radius, |b, &radius| {
+                b.to_async(&runtime).iter(|| async {
+                    let mut clock = WorkerClock::start("bench", 0, 0..src.height);
+                    blur::horizontal_gaussian_blur(Arc::clone(&src), Arc::clone(&dst), Arc::clone(&kernel), radius, FilterOptions::default(), 0..src.height, &mut clock).await
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_sat_build(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("sat_build");
+    for size in SIZES {
+        let values = runtime.block_on(kuwahara::convert_to_space(Arc::new(test_image(size)), FilterOptions::default(), TASKS));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &values, |b, values| {
+            b.iter(|| {
+                let mut integral = IntegralImage::new(size as usize, size as usize);
+                integral.build(values, None);
+                integral
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_full_blur(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("blur");
+    group.sample_size(10);
+    for size in SIZES {
+        let img = DynamicImage::ImageRgba8(test_image(size));
+        for radius in RADII {
+            group.bench_with_input(BenchmarkId::new(format!("{}px", size), radius), &radius, |b, &radius| {
+                b.to_async(&runtime).iter(|| blur::apply_gaussian_blur_async(&img, radius as u32, TASKS, FilterOptions::default()))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_full_kuwahara(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("kuwahara");
+    group.sample_size(10);
+    for size in SIZES {
+        let img = DynamicImage::ImageRgba8(test_image(size));
+        for radius in RADII {
+            group.bench_with_input(BenchmarkId::new(format!("{}px", size), radius), &radius, |b, &radius| {
+                b.to_async(&runtime).iter(|| kuwahara::apply_kuwahara_filter_async(&img, radius as i32, TASKS, FilterOptions::default()))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_kernel,
+    bench_transpose,
+    bench_horizontal_blur,
+    bench_sat_build,
+    bench_full_blur,
+    bench_full_kuwahara
+);
+criterion_main!(benches);
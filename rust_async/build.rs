@@ -0,0 +1,11 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/filter.proto");
+        // A bundled protoc, so building the service needs nothing installed beyond Rust
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("No bundled protoc for this platform"));
+        tonic_build::compile_protos("proto/filter.proto").expect("Failed to compile proto/filter.proto");
+    }
+}
@@ -0,0 +1,101 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::histeq;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::task;
+
+// Darkest and brightest value of a channel, ignoring `clip` percent of the pixels at each end so a
+// few stray specks do not hold the range open. An empty histogram spans the full range.
+pub fn levels(histogram: &[u64; 256], clip: f64) -> (u8, u8) {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return (0, 255);
+    }
+    // Pixels that may fall outside the range at each end, never all of them
+    let skipped = ((total as f64 * clip / 100.0) as u64).min(total - 1);
+    // Steps from one end until the running count passes the skipped pixels
+    let past = |reversed: bool| {
+        let mut cumulative = 0;
+        (0..256).position(|i| {
+            cumulative += histogram[if reversed { 255 - i } else { i }];
+            cumulative > skipped
+        }).unwrap_or(0) as u8
+    };
+    let low = past(false);
+    let high = 255 - past(true);
+    (low, high.max(low))
+}
+
+// Stretches `low` to 0 and `high` to 255, clamping what lies beyond them. A channel holding a
+// single value is left alone.
+pub fn stretch_table(low: u8, high: u8) -> [u8; 256] {
+    if high <= low {
+        return std::array::from_fn(|value| value as u8);
+    }
+    let (low, span) = (low as u32, (high - low) as u32);
+    std::array::from_fn(|value| {
+        let offset = (value as u32).clamp(low, low + span) - low;
+        ((offset * 255 + span / 2) / span) as u8
+    })
+}
+
+// Tables stretching each color channel of the image whose histograms these are
+pub fn tables(histograms: &histeq::Histograms, clip: f64) -> [[u8; 256]; 3] {
+    histograms.map(|histogram| {
+        let (low, high) = levels(&histogram, clip);
+        stretch_table(low, high)
+    })
+}
+
+// Auto-levels every color channel to the full range, alpha kept. Each task counts the histograms
+// of its band; once all have finished the partial counts are merged, the range of each channel is
+// read off the image's histogram, and a second round of tasks over the same bands stretches every
+// pixel.
+pub async fn apply_autolevel_async(img: &DynamicImage, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let src = Arc::new(img.to_rgba8());
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    // Both passes
+    progress::expect(2 * height as usize);
+
+    let bands = bands::split(height as usize, num_tasks);
+    let mut tasks = Vec::new();
+    for rows in bands.iter().cloned() {
+        let src = Arc::clone(&src);
+        tasks.push(task::spawn(async move {
+            let partial = histeq::count(&src.as_raw()[rows.start * row_len..rows.end * row_len]);
+            progress::advance(rows.len());
+            partial
+        }));
+    }
+    let mut partials = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        partials.push(task.await.unwrap());
+    }
+    let tables = tracing::info_span!("merge", parts = partials.len()).in_scope(|| tables(&histeq::merge(&partials), filter.clip));
+
+    let mut tasks = Vec::new();
+    for rows in bands {
+        let src = Arc::clone(&src);
+        tasks.push(task::spawn(async move {
+            let mut band = src.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            for pixel in band.chunks_exact_mut(4) {
+                for (value, table) in pixel.iter_mut().zip(&tables) {
+                    *value = table[*value as usize];
+                }
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Stretched buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
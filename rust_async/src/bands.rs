@@ -0,0 +1,33 @@
+use std::ops::Range;
+
+// Splits `len` rows (or samples) into at most `parts` contiguous ranges whose lengths differ by at
+// most one: the first `len % parts` ranges take one extra. No range is empty, so with more parts
+// than rows every row gets a range of its own and the remaining workers are not started.
+pub fn split(len: usize, parts: usize) -> Vec<Range<usize>> {
+    let parts = parts.clamp(1, len.max(1));
+    let (base, extra) = (len / parts, len % parts);
+    let mut start = 0;
+    (0..parts)
+        .map(|part| {
+            let end = start + base + usize::from(part < extra);
+            let range = start..end;
+            start = end;
+            range
+        })
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+// `split` over a buffer of rows `row_len` items long, each band's rows paired with its slice
+pub fn split_mut<T>(data: &mut [T], row_len: usize, parts: usize) -> Vec<(Range<usize>, &mut [T])> {
+    let rows = data.len().checked_div(row_len).unwrap_or(0);
+    let mut rest = data;
+    split(rows, parts)
+        .into_iter()
+        .map(|range| {
+            let (band, tail) = std::mem::take(&mut rest).split_at_mut(range.len() * row_len);
+            rest = tail;
+            (range, band)
+        })
+        .collect()
+}
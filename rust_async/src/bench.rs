@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+// Runs kept when a run's modified z-score is within this bound (Iglewicz and Hoaglin)
+const OUTLIER_Z: f64 = 3.5;
+// Scales the MAD so it estimates the standard deviation of normally distributed samples
+const MAD_TO_SIGMA: f64 = 0.6745;
+
+pub const DEFAULT_RUNS: usize = 10;
+// Slowdown of the median, in percent, above which `--compare-baseline` fails
+pub const DEFAULT_THRESHOLD: f64 = 5.0;
+// Saved baselines live next to Criterion's, out of version control
+const BASELINE_DIR: &str = "target/bench-baselines";
+// Name this implementation reports itself under in JSON results
+pub const BACKEND: &str = "tokio";
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn stddev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+// Filter times of repeated runs with the ones disturbed by throttling or background load set aside
+#[derive(Serialize, Deserialize)]
+pub struct Measurement {
+    pub workers: usize,
+    // Every run in milliseconds, in the order they ran
+    pub samples: Vec<f64>,
+    pub kept: Vec<f64>,
+    // Mean package energy per run, when RAPL counters could be read
+    #[serde(default)]
+    pub joules: Option<f64>,
+}
+
+impl Measurement {
+    // Drops runs whose distance from the median is too large compared to the median absolute deviation
+    pub fn new(workers: usize, samples: Vec<f64>) -> Measurement {
+        let center = median(&samples);
+        let mad = median(&samples.iter().map(|s| (s - center).abs()).collect::<Vec<_>>());
+        let kept = if mad > 0.0 {
+            samples.iter().copied().filter(|s| (MAD_TO_SIGMA * (s - center) / mad).abs() <= OUTLIER_Z).collect()
+        } else {
+            samples.clone()
+        };
+
+        Measurement { workers, samples, kept, joules: None }
+    }
+
+    pub fn rejected(&self) -> usize {
+        self.samples.len() - self.kept.len()
+    }
+
+    pub fn mean(&self) -> f64 {
+        mean(&self.kept)
+    }
+
+    pub fn median(&self) -> f64 {
+        median(&self.kept)
+    }
+
+    pub fn stddev(&self) -> f64 {
+        stddev(&self.kept)
+    }
+
+    // Coefficient of variation of the kept runs, in percent
+    pub fn cv(&self) -> f64 {
+        100.0 * self.stddev() / self.mean()
+    }
+
+    pub fn stability(&self) -> &'static str {
+        match self.cv() {
+            cv if cv < 2.0 => "stable",
+            cv if cv < 5.0 => "moderate noise",
+            _ => "noisy, rerun on an idle machine",
+        }
+    }
+
+    pub fn format(&self) -> String {
+        let runs: Vec<String> = self.samples.iter().map(|s| format!("{:.1}", s)).collect();
+        format!(
+            "  runs (ms): {}\n  kept {} of {} runs ({} outliers beyond {} MAD)\n  mean {:.2}ms, median {:.2}ms, stddev {:.2}ms (CV {:.1}%): {}",
+            runs.join(" "),
+            self.kept.len(),
+            self.samples.len(),
+            self.rejected(),
+            OUTLIER_Z,
+            self.mean(),
+            self.median(),
+            self.stddev(),
+            self.cv(),
+            self.stability(),
+        ) + &self.joules.map(|j| format!("\n  energy {:.3} J per run", j)).unwrap_or_default()
+    }
+}
+
+// Least-squares fit of T(p) = a + b/p; the serial fraction is the share of T(1) that does not shrink
+fn fit_amdahl(points: &[(f64, f64)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let (sx, sy) = points.iter().fold((0.0, 0.0), |(sx, sy), (p, t)| (sx + 1.0 / p, sy + t));
+    let (sxx, sxy) = points.iter().fold((0.0, 0.0), |(sxx, sxy), (p, t)| (sxx + 1.0 / (p * p), sxy + t / p));
+    let det = n * sxx - sx * sx;
+    if det.abs() < f64::EPSILON {
+        return None;
+    }
+    let b = (n * sxy - sx * sy) / det;
+    let a = (sy - b * sx) / n;
+    Some((a / (a + b)).clamp(0.0, 1.0))
+}
+
+fn solve3(m: [[f64; 3]; 3], v: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(m);
+    if d.abs() < 1e-12 {
+        return None;
+    }
+    // Cramer's rule
+    let mut solution = [0.0; 3];
+    for (col, value) in solution.iter_mut().enumerate() {
+        let mut replaced = m;
+        for row in 0..3 {
+            replaced[row][col] = v[row];
+        }
+        *value = det(replaced) / d;
+    }
+    Some(solution)
+}
+
+// Universal Scalability Law: p * T(p) = T1 * (1 + sigma * (p - 1) + kappa * p * (p - 1)), linear in T1, T1*sigma, T1*kappa
+fn fit_usl(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.len() < 3 {
+        return None;
+    }
+    let mut normal = [[0.0; 3]; 3];
+    let mut rhs = [0.0; 3];
+    for &(p, t) in points {
+        let x = [1.0, p - 1.0, p * (p - 1.0)];
+        for row in 0..3 {
+            for col in 0..3 {
+                normal[row][col] += x[row] * x[col];
+            }
+            rhs[row] += x[row] * p * t;
+        }
+    }
+    let [t1, t1_sigma, t1_kappa] = solve3(normal, rhs)?;
+    if t1 <= 0.0 {
+        return None;
+    }
+    Some(((t1_sigma / t1).max(0.0), (t1_kappa / t1).max(0.0)))
+}
+
+// Speedup and efficiency of a worker-count sweep, relative to the smallest count measured
+pub fn scaling_report(measurements: &[Measurement]) -> String {
+    let Some(base) = measurements.iter().min_by_key(|m| m.workers) else {
+        return String::new();
+    };
+
+    let mut out = format!("Scaling relative to {} workers:\n", base.workers);
+    out += &format!("  {:>7} {:>11} {:>8} {:>11}\n", "workers", "median ms", "speedup", "efficiency");
+    for m in measurements {
+        let speedup = base.median() / m.median();
+        let efficiency = speedup * base.workers as f64 / m.workers as f64;
+        out += &format!("  {:>7} {:>11.2} {:>7.2}x {:>10.0}%\n", m.workers, m.median(), speedup, efficiency * 100.0);
+    }
+
+    let points: Vec<(f64, f64)> = measurements.iter().map(|m| (m.workers as f64, m.median())).collect();
+    match fit_amdahl(&points) {
+        Some(serial) if serial > 0.0 => out += &format!("  Amdahl fit: serial fraction {:.1}%, speedup limit {:.1}x\n", serial * 100.0, 1.0 / serial),
+        Some(_) => out += "  Amdahl fit: no measurable serial fraction\n",
+        None => out += "  Amdahl fit: needs at least two different worker counts\n",
+    }
+    match fit_usl(&points) {
+        Some((sigma, kappa)) => {
+            out += &format!("  USL fit: contention {:.4}, coherency {:.5}", sigma, kappa);
+            if kappa > 0.0 && sigma < 1.0 {
+                out += &format!(", throughput peaks near {:.0} workers", ((1.0 - sigma) / kappa).sqrt());
+            }
+        }
+        None => out += "  USL fit: needs at least three different worker counts",
+    }
+    out
+}
+
+// One bench invocation, printed with `--json` so other tools and the other backend can read it
+#[derive(Serialize, Deserialize)]
+pub struct BenchReport {
+    pub backend: String,
+    pub operation: String,
+    pub radius: i32,
+    pub width: u32,
+    pub height: u32,
+    pub measurements: Vec<Measurement>,
+}
+
+// Options only the local run acts on, each followed by its value: the other backend must not bench
+// a third one or touch the baselines, and a synthetic input is already among the positional arguments
+const LOCAL_OPTIONS: [&str; 4] = ["--against", "--save-baseline", "--compare-baseline", "--synthetic"];
+
+// Arguments for `bench` on another backend: the local run's positional arguments after `bench` and
+// its options, both verbatim, less `LOCAL_OPTIONS`
+pub fn against_args(positional: &[String], option_args: &[String]) -> Vec<String> {
+    let mut args = positional[2..].to_vec();
+    let mut options = option_args.iter();
+    while let Some(arg) = options.next() {
+        if LOCAL_OPTIONS.contains(&arg.as_str()) {
+            options.next();
+        } else {
+            args.push(arg.clone());
+        }
+    }
+    args
+}
+
+// Runs `bench --json` on another implementation's binary; its progress lines stay on stderr
+pub async fn run_backend(program: &str, args: &[String]) -> Result<BenchReport, Box<dyn Error>> {
+    let output = Command::new(program)
+        .arg("bench")
+        .args(args)
+        .arg("--json")
+        .stderr(Stdio::inherit())
+        .output().await?;
+    if !output.status.success() {
+        return Err(format!("{} exited with {}", program, output.status).into());
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+// Median time of the tokio backend relative to std::thread for every worker count both measured
+pub fn overhead_report(threads: &BenchReport, tokio: &BenchReport) -> String {
+    let mut out = format!(
+        "Async overhead for {} radius {} on {}x{} ({:.1} megapixels):\n",
+        threads.operation,
+        threads.radius,
+        threads.width,
+        threads.height,
+        (threads.width * threads.height) as f64 / 1e6,
+    );
+    out += &format!("  {:>7} {:>11} {:>11} {:>9}\n", "workers", "threads ms", "tokio ms", "overhead");
+    for t in &threads.measurements {
+        let Some(a) = tokio.measurements.iter().find(|a| a.workers == t.workers) else {
+            continue;
+        };
+        let overhead = 100.0 * (a.median() / t.median() - 1.0);
+        out += &format!("  {:>7} {:>11.2} {:>11.2} {:>+8.1}%", t.workers, t.median(), a.median(), overhead);
+        if let (Some(tj), Some(aj)) = (t.joules, a.joules) {
+            out += &format!("  energy {:.3} J vs {:.3} J ({:+.1}%)", tj, aj, 100.0 * (aj / tj - 1.0));
+        }
+        out += "\n";
+    }
+    out.trim_end().to_string()
+}
+
+pub fn baseline_path(name: &str) -> PathBuf {
+    Path::new(BASELINE_DIR).join(format!("{}.json", name))
+}
+
+pub fn save_baseline(report: &BenchReport, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = baseline_path(name);
+    fs::create_dir_all(BASELINE_DIR)?;
+    fs::write(&path, serde_json::to_string_pretty(report)?)?;
+    Ok(path)
+}
+
+pub fn load_baseline(name: &str) -> Result<BenchReport, Box<dyn Error>> {
+    let path = baseline_path(name);
+    let data = fs::read(&path).map_err(|e| format!("Cannot read baseline {}: {}", path.display(), e))?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+// Median change against a saved run for every worker count; also returns whether any slowed down past `threshold` percent
+pub fn baseline_report(name: &str, baseline: &BenchReport, current: &BenchReport, threshold: f64) -> (String, bool) {
+    let mut out = format!("Compared to baseline '{}' (regression threshold {:.1}%):\n", name, threshold);
+    if (&baseline.backend, &baseline.operation, baseline.radius, baseline.width, baseline.height)
+        != (&current.backend, &current.operation, current.radius, current.width, current.height)
+    {
+        out += &format!(
+            "  warning: baseline measured {} {} radius {} on {}x{}\n",
+            baseline.backend, baseline.operation, baseline.radius, baseline.width, baseline.height,
+        );
+    }
+
+    out += &format!("  {:>7} {:>11} {:>11} {:>8}\n", "workers", "baseline ms", "current ms", "change");
+    let mut regressions = 0;
+    for m in &current.measurements {
+        let Some(b) = baseline.measurements.iter().find(|b| b.workers == m.workers) else {
+            out += &format!("  {:>7} {:>11} {:>11.2} {:>8}\n", m.workers, "-", m.median(), "new");
+            continue;
+        };
+        let change = 100.0 * (m.median() / b.median() - 1.0);
+        let verdict = if change > threshold {
+            regressions += 1;
+            "  regressed"
+        } else if change < -threshold {
+            "  improved"
+        } else {
+            ""
+        };
+        out += &format!("  {:>7} {:>11.2} {:>11.2} {:>+7.1}%{}\n", m.workers, b.median(), m.median(), change, verdict);
+    }
+
+    if regressions > 0 {
+        out += &format!("  {} worker count(s) slower than the baseline by more than {:.1}%", regressions, threshold);
+    } else {
+        out += "  no regressions";
+    }
+    (out, regressions > 0)
+}
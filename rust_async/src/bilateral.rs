@@ -0,0 +1,150 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::srgb;
+use crate::task_latency;
+use crate::timing::WorkerClock;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::Instrument;
+
+// Spatial sigma for `radius`: `--sigma-space` when given, otherwise a third of the radius so the
+// window covers three sigmas as the blur's kernel does
+pub fn sigma_space(radius: i32, filter: FilterOptions) -> f64 {
+    filter.sigma_space.unwrap_or(radius as f64 / 3.0)
+}
+
+// Gaussian of the distance to the center over the window, row by row
+pub fn spatial_weights(radius: i32, sigma_space: f64) -> Vec<f64> {
+    let mut weights = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            weights.push((-((dx * dx + dy * dy) as f64) / (2.0 * sigma_space * sigma_space)).exp());
+        }
+    }
+    weights
+}
+
+// Gaussian of one channel's difference to the center. The color weight is the product over R, G
+// and B, the Gaussian of their Euclidean distance, so a table of 256 entries replaces an `exp` per tap.
+pub fn range_weights(sigma_color: f64) -> [f64; 256] {
+    std::array::from_fn(|d| (-((d * d) as f64) / (2.0 * sigma_color * sigma_color)).exp())
+}
+
+// Both tables, built once per run and shared by the workers
+struct Weights {
+    spatial: Vec<f64>,
+    range: [f64; 256],
+}
+
+async fn process_bilateral_rows(
+    src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>,
+    dst: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
+    radius: i32,
+    weights: &Weights,
+    rows: Range<u32>,
+    filter: FilterOptions,
+    clock: &mut WorkerClock,
+) {
+    let (width, height) = src.dimensions();
+    let lut = srgb::lut();
+    let decode = |value: u8, channel: usize| if filter.linear && channel < 3 { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64, channel: usize| if filter.linear && channel < 3 { lut.encode(value) } else { value.round() as u8 };
+    let mut local_pixels = Vec::new();
+
+    for y in rows {
+        if progress::cancelled() {
+            break;
+        }
+        for x in 0..width {
+            let center = src.get_pixel(x, y).0;
+            let mut sums = [0.0; 4];
+            let mut total = 0.0;
+            let mut taps = weights.spatial.iter();
+            for dy in -radius..=radius {
+                let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                for dx in -radius..=radius {
+                    let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                    let pixel = src.get_pixel(sx, sy).0;
+                    let weight = taps.next().unwrap()
+                        * weights.range[pixel[0].abs_diff(center[0]) as usize]
+                        * weights.range[pixel[1].abs_diff(center[1]) as usize]
+                        * weights.range[pixel[2].abs_diff(center[2]) as usize];
+                    for (channel, sum) in sums.iter_mut().enumerate() {
+                        *sum += decode(pixel[channel], channel) * weight;
+                    }
+                    total += weight;
+                }
+            }
+            // The center always weighs 1, so the total is never zero
+            local_pixels.push((x, y, Rgba([0, 1, 2, 3].map(|channel| encode(sums[channel] / total, channel)))));
+        }
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst_locked = dst.lock().await;
+    for (x, y, pixel) in local_pixels {
+        dst_locked.put_pixel(x, y, pixel);
+    }
+}
+
+// Edge-preserving smoothing: every neighbor within `radius` is weighted by its distance to the
+// pixel and by how far its color is from the pixel's, so pixels across an edge barely count
+pub async fn apply_bilateral_filter_async(
+    img: &DynamicImage,
+    radius: i32,
+    sigma_space: f64,
+    sigma_color: f64,
+    num_tasks: usize,
+    filter: FilterOptions,
+) -> DynamicImage {
+    let radius = u32::try_from(radius).expect("radius must not be negative") as i32;
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if radius == 0 {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+    progress::expect(height as usize);
+
+    let src = Arc::new(rgba);
+    let dst = Arc::new(Mutex::new(ImageBuffer::new(width, height)));
+    let weights = Arc::new(Weights { spatial: spatial_weights(radius, sigma_space), range: range_weights(sigma_color) });
+
+    let pass = tracing::info_span!("bilateral_pass");
+    let mut tasks = Vec::new();
+
+    for (task_id, rows) in bands::split(height as usize, num_tasks).into_iter().enumerate() {
+        let src = Arc::clone(&src);
+        let dst = Arc::clone(&dst);
+        let weights = Arc::clone(&weights);
+
+        let task = task_latency::spawn(async move {
+            let mut clock = WorkerClock::start("bilateral", task_id, rows.clone());
+            process_bilateral_rows(src, dst, radius, &weights, rows.start as u32..rows.end as u32, filter, &mut clock).await;
+            clock.finish();
+        }
+        .instrument(tracing::debug_span!(parent: &pass, "task", id = task_id)));
+
+        tasks.push(task);
+    }
+
+    async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+    .instrument(pass)
+    .await;
+
+    let result = Arc::try_unwrap(dst)
+        .unwrap()
+        .into_inner();
+
+    channels::restore(img, DynamicImage::ImageRgba8(result), filter.channels, num_tasks)
+        .instrument(tracing::info_span!("channels"))
+        .await
+}
@@ -0,0 +1,195 @@
+use crate::bands;
+use crate::image_pyramid::{expand_at, gaussian_pyramid};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// The coarsest level is at most this many pixels on its shorter side
+const TOP_SIZE: u32 = 16;
+
+// Levels of the pyramids, from the full size down to the first with a short side of TOP_SIZE or less
+pub fn levels(width: u32, height: u32) -> usize {
+    let mut side = width.min(height);
+    let mut levels = 1;
+    while side > TOP_SIZE {
+        side = side.div_ceil(2);
+        levels += 1;
+    }
+    levels
+}
+
+// One level of the blended Laplacian pyramid, RGBA as f32: the band-pass detail of `a` and `b`
+// mixed by the mask blurred to that level's scale, or at the top the residual images themselves
+fn blend_level(a: &[Frame], b: &[Frame], mask: &[Frame], level: usize) -> Vec<f32> {
+    let (width, height) = a[level].dimensions();
+    let coarser = a.get(level + 1).map(|next| next.dimensions());
+    let mut out = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let weight = mask[level].get_pixel(x, y)[0] as f32 / 255.0;
+            for ch in 0..4 {
+                let detail = |pyramid: &[Frame]| {
+                    let value = pyramid[level].get_pixel(x, y)[ch] as f32;
+                    match coarser {
+                        Some((w, h)) => value - expand_at(|sx, sy| pyramid[level + 1].get_pixel(sx, sy)[ch] as f32, w, h, x, y),
+                        None => value,
+                    }
+                };
+                out.push(weight * detail(a) + (1.0 - weight) * detail(b));
+            }
+        }
+    }
+    out
+}
+
+// Multi-band blending: each frequency band is mixed across a transition as wide as its scale, so
+// the seam between `a`, where `mask` is white, and `b`, where it is black, neither shows a hard
+// edge nor ghosts fine detail. The three Gaussian pyramids are built concurrently, each level is
+// blended in its own task, and the collapse runs band by band.
+pub async fn blend_async(a: &DynamicImage, b: &DynamicImage, mask: &DynamicImage, num_tasks: usize) -> Result<DynamicImage, String> {
+    let (a, b, mask) = (a.to_rgba8(), b.to_rgba8(), mask.to_rgba8());
+    let (width, height) = a.dimensions();
+    for (name, img) in [("Second image", &b), ("Mask", &mask)] {
+        if img.dimensions() != (width, height) {
+            return Err(format!("{} is {}x{}, expected {}x{} like the first image", name, img.width(), img.height(), width, height));
+        }
+    }
+    let levels = levels(width, height);
+    let per_pyramid = (num_tasks / 3).max(1);
+    let pyramids: Vec<_> = [a, b, mask].map(|img| task::spawn(gaussian_pyramid(img, levels, per_pyramid))).into();
+    let mut built = Vec::new();
+    for pyramid in pyramids {
+        built.push(pyramid.await.expect("Pyramid task panicked"));
+    }
+    let [a, b, mask]: [Vec<Frame>; 3] = built.try_into().expect("Three pyramids were built");
+    let (a, b, mask) = (Arc::new(a), Arc::new(b), Arc::new(mask));
+
+    let mut tasks = Vec::new();
+    for level in 0..levels {
+        let (a, b, mask) = (Arc::clone(&a), Arc::clone(&b), Arc::clone(&mask));
+        tasks.push(task::spawn(async move { blend_level(&a, &b, &mask, level) }));
+    }
+    let mut blended = Vec::new();
+    for task in tasks {
+        blended.push(task.await.expect("Blending task panicked"));
+    }
+
+    // Collapse from the top: expand the running image and add the next finer detail
+    let mut collapsed = Arc::new(blended.pop().expect("There is at least one level"));
+    for (level, finer) in blended.into_iter().enumerate().rev() {
+        let (coarse_width, coarse_height) = a[level + 1].dimensions();
+        let (level_width, level_height) = a[level].dimensions();
+        let row_len = level_width as usize * 4;
+        let finer = Arc::new(finer);
+        let mut tasks = Vec::new();
+        for rows in bands::split(level_height as usize, num_tasks) {
+            let (coarse, finer) = (Arc::clone(&collapsed), Arc::clone(&finer));
+            tasks.push(task::spawn(async move {
+                (rows.start * row_len..rows.end * row_len)
+                    .map(|i| {
+                        let (x, y, ch) = ((i / 4) % level_width as usize, i / row_len, i % 4);
+                        let sample = |sx: u32, sy: u32| coarse[(sy as usize * coarse_width as usize + sx as usize) * 4 + ch];
+                        finer[i] + expand_at(sample, coarse_width, coarse_height, x as u32, y as u32)
+                    })
+                    .collect::<Vec<f32>>()
+            }));
+        }
+        let mut next = Vec::with_capacity(row_len * level_height as usize);
+        for task in tasks {
+            next.extend_from_slice(&task.await.unwrap());
+        }
+        collapsed = Arc::new(next);
+    }
+
+    let data = collapsed.iter().map(|value| value.round().clamp(0.0, 255.0) as u8).collect();
+    Ok(DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, data).expect("Blended buffer matches dimensions")))
+}
+
+// How `composite` mixes the top image's color with the base's, on values in 0..=1
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Mode::Normal),
+            "multiply" => Ok(Mode::Multiply),
+            "screen" => Ok(Mode::Screen),
+            "overlay" => Ok(Mode::Overlay),
+            other => Err(format!("Unknown blend mode: {}. Use 'normal', 'multiply', 'screen' or 'overlay'", other)),
+        }
+    }
+}
+
+impl Mode {
+    pub fn mix(self, base: f64, top: f64) -> f64 {
+        match self {
+            Mode::Normal => top,
+            Mode::Multiply => base * top,
+            Mode::Screen => 1.0 - (1.0 - base) * (1.0 - top),
+            // Multiply in the shadows of the base and screen in its highlights
+            Mode::Overlay if base < 0.5 => 2.0 * base * top,
+            Mode::Overlay => 1.0 - 2.0 * (1.0 - base) * (1.0 - top),
+        }
+    }
+}
+
+// `top` over `base`, the top's alpha scaled by `opacity`. Where the base is opaque the top's color
+// is replaced by the mode's mix of the two, where it is transparent the top shows as it is, and
+// the result is composited source-over.
+pub fn composite_pixel(base: [u8; 4], top: [u8; 4], mode: Mode, opacity: f64) -> [u8; 4] {
+    let base_alpha = base[3] as f64 / 255.0;
+    let top_alpha = top[3] as f64 / 255.0 * opacity;
+    let alpha = top_alpha + base_alpha * (1.0 - top_alpha);
+    let mut out = [0, 0, 0, (alpha * 255.0).round() as u8];
+    if alpha == 0.0 {
+        return out;
+    }
+    for ch in 0..3 {
+        let (b, t) = (base[ch] as f64 / 255.0, top[ch] as f64 / 255.0);
+        let mixed = (1.0 - base_alpha) * t + base_alpha * mode.mix(b, t);
+        let color = (top_alpha * mixed + base_alpha * (1.0 - top_alpha) * b) / alpha;
+        out[ch] = (color * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    out
+}
+
+// Composites `top` over `base` of the same size, one band of rows per task
+pub async fn composite_async(base: &DynamicImage, top: &DynamicImage, mode: Mode, opacity: f64, num_tasks: usize) -> Result<DynamicImage, String> {
+    let (base, top) = (Arc::new(base.to_rgba8()), Arc::new(top.to_rgba8()));
+    let (width, height) = base.dimensions();
+    if top.dimensions() != (width, height) {
+        return Err(format!("Second image is {}x{}, expected {}x{} like the first image", top.width(), top.height(), width, height));
+    }
+    let row_len = width as usize * 4;
+    let mut tasks = Vec::new();
+    for rows in bands::split(height as usize, num_tasks) {
+        let (base, top) = (Arc::clone(&base), Arc::clone(&top));
+        tasks.push(task::spawn(async move {
+            let range = rows.start * row_len..rows.end * row_len;
+            let mut band = base.as_raw()[range.clone()].to_vec();
+            for (pixel, over) in band.chunks_exact_mut(4).zip(top.as_raw()[range].chunks_exact(4)) {
+                let mixed = composite_pixel([pixel[0], pixel[1], pixel[2], pixel[3]], [over[0], over[1], over[2], over[3]], mode, opacity);
+                pixel.copy_from_slice(&mixed);
+            }
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    Ok(DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, data).expect("Composited buffer matches dimensions")))
+}
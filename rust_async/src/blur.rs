@@ -1,7 +1,20 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::fft::{self, Complex};
+use crate::progress;
+use crate::srgb;
+use crate::task_latency;
+use crate::timing::WorkerClock;
 use image::{DynamicImage, ImageBuffer, Rgba};
+use std::ops::Range;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tokio::task;
+use tracing::Instrument;
+
+// Radius from which the passes convolve through the FFT. The direct pass costs 2r+1 taps a pixel
+// and the FFT a few transforms of the padded row whatever the radius, which wins from about here.
+pub const FFT_RADIUS: usize = 32;
 
 #[derive(Debug, Clone)]
 pub struct ImageData {
@@ -35,7 +48,7 @@ impl ImageData {
         DynamicImage::ImageRgba8(img_buffer)
     }
 
-    fn transpose(&self) -> ImageData {
+    pub fn transpose(&self) -> ImageData {
         let mut dst = ImageData {
             data: vec![0; self.data.len()],
             width: self.height,
@@ -57,36 +70,84 @@ impl ImageData {
     }
 }
 
-fn generate_gaussian_kernel(radius: usize) -> Vec<f64> {
+// The kernel of `radius` with sigma a third of it, so it reaches three sigmas
+pub fn generate_gaussian_kernel(radius: usize) -> Vec<f64> {
+    gaussian_kernel(radius, radius as f64 / 3.0)
+}
+
+// Weights of a Gaussian of `sigma` over 2 * radius + 1 taps, normalized to sum to 1
+pub fn gaussian_kernel(radius: usize, sigma: f64) -> Vec<f64> {
+    // sigma would be 0 and every weight NaN; a single tap copies the pixel
+    if radius == 0 {
+        return vec![1.0];
+    }
     let size = 2 * radius + 1;
     let mut kernel = vec![0.0; size];
-    let sigma = radius as f64 / 3.0;
+    // The same for an axis left unblurred next to a blurred one: all the weight on the center tap
+    if sigma == 0.0 {
+        kernel[radius] = 1.0;
+        return kernel;
+    }
     let mut sum = 0.0;
 
-    for i in 0..size {
+    for (i, weight) in kernel.iter_mut().enumerate() {
         let x = i as f64 - radius as f64;
-        kernel[i] = (-x * x / (2.0 * sigma * sigma)).exp();
-        sum += kernel[i];
+        *weight = (-x * x / (2.0 * sigma * sigma)).exp();
+        sum += *weight;
     }
 
-    for i in 0..size {
-        kernel[i] /= sum;
+    for weight in kernel.iter_mut() {
+        *weight /= sum;
     }
 
     kernel
 }
 
-async fn horizontal_gaussian_blur(
+// Radius of the blur: the given one, or when it is 0 and a sigma is given, three of the larger
+// sigma rounded up, where the Gaussian has fallen below 1.2% of its peak
+pub fn blur_radius(radius: usize, filter: FilterOptions) -> usize {
+    if radius > 0 {
+        return radius;
+    }
+    let sigma = filter.sigma_x.unwrap_or(0.0).max(filter.sigma_y.unwrap_or(0.0));
+    (3.0 * sigma).ceil() as usize
+}
+
+// A radius wider than the image is valid, but its kernel would be any size: it is capped at the
+// image's longest side, as Kuwahara's radius is
+pub fn capped_radius(radius: usize, width: u32, height: u32) -> usize {
+    radius.min(width.max(height) as usize)
+}
+
+// Sigmas of the horizontal and the vertical pass: `--sigma-x` and `--sigma-y` when given, otherwise
+// a third of `radius`, the one given rather than the one derived from the other axis's sigma. With
+// a radius of 0 an axis without a sigma is not blurred at all.
+pub fn sigmas(radius: usize, filter: FilterOptions) -> (f64, f64) {
+    let default = radius as f64 / 3.0;
+    (filter.sigma_x.unwrap_or(default), filter.sigma_y.unwrap_or(default))
+}
+
+pub async fn horizontal_gaussian_blur(
     src: Arc<ImageData>,
     dst: Arc<Mutex<ImageData>>,
     kernel: Arc<Vec<f64>>,
     radius: usize,
-    start_y: usize,
-    end_y: usize,
+    filter: FilterOptions,
+    rows: Range<usize>,
+    clock: &mut WorkerClock,
 ) {
     let mut local_rows = Vec::new();
-
-    for y in start_y..end_y {
+    // In linear mode color channels are decoded on read and re-encoded on write; alpha is already linear
+    let (linear, edge) = (filter.linear, filter.edge);
+    let lut = srgb::lut();
+    let decode = |value: u8| if linear { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64| if linear { lut.encode(value) } else { value.round() as u8 };
+    let outside = edge.outside();
+
+    for y in rows {
+        if progress::cancelled() {
+            break;
+        }
         let mut row_data = vec![0u8; src.width * src.channels];
 
         for x in 0..src.width {
@@ -96,25 +157,108 @@ async fn horizontal_gaussian_blur(
             let mut a_sum = 0.0;
 
             for k in -(radius as i32)..=(radius as i32) {
-                let sx = (x as i32 + k).clamp(0, src.width as i32 - 1) as usize;
-                let idx = (y * src.width + sx) * src.channels;
+                let sample = match edge.index(x as i32 + k, src.width) {
+                    Some(sx) => {
+                        let idx = (y * src.width + sx) * src.channels;
+                        &src.data[idx..idx + 4]
+                    }
+                    None => &outside[..],
+                };
                 let weight = kernel[(k + radius as i32) as usize];
 
-                r_sum += src.data[idx] as f64 * weight;
-                g_sum += src.data[idx + 1] as f64 * weight;
-                b_sum += src.data[idx + 2] as f64 * weight;
-                a_sum += src.data[idx + 3] as f64 * weight;
+                r_sum += decode(sample[0]) * weight;
+                g_sum += decode(sample[1]) * weight;
+                b_sum += decode(sample[2]) * weight;
+                a_sum += sample[3] as f64 * weight;
             }
 
             let row_idx = x * src.channels;
-            row_data[row_idx] = r_sum.round() as u8;
-            row_data[row_idx + 1] = g_sum.round() as u8;
-            row_data[row_idx + 2] = b_sum.round() as u8;
+            row_data[row_idx] = encode(r_sum);
+            row_data[row_idx + 1] = encode(g_sum);
+            row_data[row_idx + 2] = encode(b_sum);
             row_data[row_idx + 3] = a_sum.round() as u8;
         }
 
         local_rows.push((y, row_data));
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst_locked = dst.lock().await;
+    for (y, row_data) in local_rows {
+        let row_start = y * src.width * src.channels;
+        let row_end = row_start + src.width * src.channels;
+        dst_locked.data[row_start..row_end].copy_from_slice(&row_data);
+    }
+}
+
+// Transform of the kernel laid out circularly over `size` samples, centered on the first
+pub fn kernel_spectrum(kernel: &[f64], size: usize) -> Vec<Complex> {
+    let radius = kernel.len() / 2;
+    let mut spectrum = vec![Complex::default(); size];
+    for (i, &weight) in kernel.iter().enumerate() {
+        spectrum[(i + size - radius) % size] = Complex::new(weight, 0.0);
+    }
+    fft::fft(&mut spectrum, false);
+    spectrum
+}
+
+// The same pass as `horizontal_gaussian_blur` through the FFT. Each row is padded with `radius`
+// samples on both sides, read by the edge mode as the direct pass does, and multiplied by the
+// kernel's spectrum. The channels go two to a transform as its real and imaginary parts; the kernel
+// is real, so they come back apart.
+pub async fn horizontal_fft_blur(
+    src: Arc<ImageData>,
+    dst: Arc<Mutex<ImageData>>,
+    kernel: Arc<Vec<f64>>,
+    radius: usize,
+    filter: FilterOptions,
+    rows: Range<usize>,
+    clock: &mut WorkerClock,
+) {
+    let mut local_rows = Vec::new();
+    let (linear, edge) = (filter.linear, filter.edge);
+    let lut = srgb::lut();
+    let decode = |value: u8, channel: usize| if linear && channel < 3 { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64| if linear { lut.encode(value) } else { value.round() as u8 };
+    let padded = src.width + 2 * radius;
+    let size = padded.next_power_of_two();
+    let spectrum = kernel_spectrum(&kernel, size);
+    let mut pairs = [vec![Complex::default(); size], vec![Complex::default(); size]];
+    let outside = edge.outside();
+
+    for y in rows {
+        if progress::cancelled() {
+            break;
+        }
+        for (pair, first) in pairs.iter_mut().zip([0, 2]) {
+            pair.fill(Complex::default());
+            for (j, value) in pair[..padded].iter_mut().enumerate() {
+                let sample = match edge.index(j as i32 - radius as i32, src.width) {
+                    Some(sx) => {
+                        let idx = (y * src.width + sx) * src.channels;
+                        &src.data[idx..idx + 4]
+                    }
+                    None => &outside[..],
+                };
+                *value = Complex::new(decode(sample[first], first), decode(sample[first + 1], first + 1));
+            }
+            fft::fft(pair, false);
+            for (value, &weight) in pair.iter_mut().zip(&spectrum) {
+                *value = *value * weight;
+            }
+            fft::fft(pair, true);
+        }
+
+        let mut row_data = vec![0u8; src.width * src.channels];
+        for (x, pixel) in row_data.chunks_exact_mut(src.channels).enumerate() {
+            let (rg, ba) = (pairs[0][x + radius], pairs[1][x + radius]);
+            pixel.copy_from_slice(&[encode(rg.re), encode(rg.im), encode(ba.re), ba.im.round() as u8]);
+        }
+        local_rows.push((y, row_data));
+        progress::advance(1);
     }
+    clock.computed();
 
     let mut dst_locked = dst.lock().await;
     for (y, row_data) in local_rows {
@@ -124,10 +268,23 @@ async fn horizontal_gaussian_blur(
     }
 }
 
-pub async fn apply_gaussian_blur_async(img: &DynamicImage, radius: u32, num_tasks: usize) -> DynamicImage {
+// Separable Gaussian blur: a horizontal pass in bands of rows, then the same pass over the
+// transposed result, each with its own sigma. Radii of `FFT_RADIUS` and up convolve through the FFT.
+pub async fn apply_gaussian_blur_async(img: &DynamicImage, radius: u32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    // Samples past the edges map back into the image, so any radius is valid; past the longest side
+    // it is capped, which keeps the kernel no larger than the image
+    let given = capped_radius(radius as usize, img.width(), img.height());
+    let radius = capped_radius(blur_radius(given, filter), img.width(), img.height());
+    if radius == 0 {
+        return DynamicImage::ImageRgba8(img.to_rgba8());
+    }
     let src = ImageData::from_dynamic_image(img);
-    let radius = radius as usize;
-    let kernel = Arc::new(generate_gaussian_kernel(radius));
+    // Both passes, the second over the transposed image
+    progress::expect(src.height + src.width);
+    let (sigma_x, sigma_y) = sigmas(given, filter);
+    let (kernel_x, kernel_y) = tracing::info_span!("kernel", radius, sigma_x, sigma_y)
+        .in_scope(|| (gaussian_kernel(radius, sigma_x), gaussian_kernel(radius, sigma_y)));
+    let kernel = Arc::new(kernel_x);
 
     // Phase 1: Horizontal blur
     let dst_horizontal = Arc::new(Mutex::new(ImageData {
@@ -137,38 +294,43 @@ pub async fn apply_gaussian_blur_async(img: &DynamicImage, radius: u32, num_task
         channels: src.channels,
     }));
 
-    let rows_per_task = src.height / num_tasks;
+    let bands = bands::split(src.height, num_tasks);
     let src_arc = Arc::new(src);
 
+    let pass = tracing::info_span!("blur_pass", direction = "horizontal");
     let mut tasks = Vec::new();
 
-    for task_id in 0..num_tasks {
+    for (task_id, rows) in bands.into_iter().enumerate() {
         let src = Arc::clone(&src_arc);
         let dst = Arc::clone(&dst_horizontal);
         let kernel = Arc::clone(&kernel);
 
-        let task = task::spawn(async move {
-            let start_y = task_id * rows_per_task;
-            let end_y = if task_id == num_tasks - 1 {
-                src.height
+        let task = task_latency::spawn(async move {
+            let mut clock = WorkerClock::start("blur-h", task_id, rows.clone());
+            if radius >= FFT_RADIUS {
+                horizontal_fft_blur(src, dst, kernel, radius, filter, rows, &mut clock).await;
             } else {
-                (task_id + 1) * rows_per_task
-            };
-
-            horizontal_gaussian_blur(src, dst, kernel, radius, start_y, end_y).await;
-        });
+                horizontal_gaussian_blur(src, dst, kernel, radius, filter, rows, &mut clock).await;
+            }
+            clock.finish();
+        }
+        .instrument(tracing::debug_span!(parent: &pass, "task", id = task_id)));
 
         tasks.push(task);
     }
 
-    for task in tasks {
-        task.await.unwrap();
+    async {
+        for task in tasks {
+            task.await.unwrap();
+        }
     }
+    .instrument(pass)
+    .await;
 
     let horizontal_result = Arc::try_unwrap(dst_horizontal)
         .unwrap()
         .into_inner();
-    let transposed = horizontal_result.transpose();
+    let transposed = tracing::info_span!("transpose").in_scope(|| horizontal_result.transpose());
 
     // Phase 2: Vertical blur (horizontal on transposed)
     let dst_vertical = Arc::new(Mutex::new(ImageData {
@@ -178,38 +340,46 @@ pub async fn apply_gaussian_blur_async(img: &DynamicImage, radius: u32, num_task
         channels: transposed.channels,
     }));
 
-    let rows_per_task = transposed.height / num_tasks;
+    let bands = bands::split(transposed.height, num_tasks);
     let transposed_arc = Arc::new(transposed);
+    let kernel = Arc::new(kernel_y);
 
+    let pass = tracing::info_span!("blur_pass", direction = "vertical");
     let mut tasks = Vec::new();
 
-    for task_id in 0..num_tasks {
+    for (task_id, rows) in bands.into_iter().enumerate() {
         let src = Arc::clone(&transposed_arc);
         let dst = Arc::clone(&dst_vertical);
         let kernel = Arc::clone(&kernel);
 
-        let task = task::spawn(async move {
-            let start_y = task_id * rows_per_task;
-            let end_y = if task_id == num_tasks - 1 {
-                src.height
+        let task = task_latency::spawn(async move {
+            let mut clock = WorkerClock::start("blur-v", task_id, rows.clone());
+            if radius >= FFT_RADIUS {
+                horizontal_fft_blur(src, dst, kernel, radius, filter, rows, &mut clock).await;
             } else {
-                (task_id + 1) * rows_per_task
-            };
-
-            horizontal_gaussian_blur(src, dst, kernel, radius, start_y, end_y).await;
-        });
+                horizontal_gaussian_blur(src, dst, kernel, radius, filter, rows, &mut clock).await;
+            }
+            clock.finish();
+        }
+        .instrument(tracing::debug_span!(parent: &pass, "task", id = task_id)));
 
         tasks.push(task);
     }
 
-    for task in tasks {
-        task.await.unwrap();
+    async {
+        for task in tasks {
+            task.await.unwrap();
+        }
     }
+    .instrument(pass)
+    .await;
 
     let vertical_result = Arc::try_unwrap(dst_vertical)
         .unwrap()
         .into_inner();
-    let final_result = vertical_result.transpose();
+    let final_result = tracing::info_span!("transpose").in_scope(|| vertical_result.transpose());
 
-    final_result.to_dynamic_image()
+    channels::restore(img, final_result.to_dynamic_image(), filter.channels, num_tasks)
+        .instrument(tracing::info_span!("channels"))
+        .await
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+use crate::bands;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task;
+
+// Channels a filter may change; the others keep the input's values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    Rgb,
+    Rgba,
+    // Alpha only, for feathering masks
+    A,
+    // Luma only, leaving chroma sharp
+    Luma,
+}
+
+impl FromStr for Channels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rgb" => Ok(Channels::Rgb),
+            "rgba" => Ok(Channels::Rgba),
+            "a" => Ok(Channels::A),
+            "luma" => Ok(Channels::Luma),
+            other => Err(format!("Unknown channels: {}", other)),
+        }
+    }
+}
+
+// BT.601 luma of gamma-encoded values
+pub fn luma(pixel: &[u8]) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+// The output pixel from the input `src` and the filtered `filtered`. Luma, Cb and Cr are linear in
+// R, G and B, so shifting all three by the change in luma keeps the input's chroma.
+pub fn select(src: [u8; 4], filtered: [u8; 4], channels: Channels) -> [u8; 4] {
+    match channels {
+        Channels::Rgba => filtered,
+        Channels::Rgb => [filtered[0], filtered[1], filtered[2], src[3]],
+        Channels::A => [src[0], src[1], src[2], filtered[3]],
+        Channels::Luma => {
+            let shift = luma(&filtered) - luma(&src);
+            let [r, g, b] = [0, 1, 2].map(|ch| (src[ch] as f32 + shift).round().clamp(0.0, 255.0) as u8);
+            [r, g, b, src[3]]
+        }
+    }
+}
+
+// Puts the unselected channels of `src` back into `filtered`, one band of rows per task
+pub async fn restore(src: &DynamicImage, filtered: DynamicImage, channels: Channels, num_tasks: usize) -> DynamicImage {
+    if channels == Channels::Rgba {
+        return filtered;
+    }
+    let src = Arc::new(src.to_rgba8());
+    let filtered = Arc::new(filtered.into_rgba8());
+    let (width, height) = filtered.dimensions();
+    let row_len = width as usize * 4;
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height as usize, num_tasks) {
+        let (src, filtered) = (Arc::clone(&src), Arc::clone(&filtered));
+        tasks.push(task::spawn(async move {
+            let bytes = rows.start * row_len..rows.end * row_len;
+            src.as_raw()[bytes.clone()]
+                .chunks_exact(4)
+                .zip(filtered.as_raw()[bytes].chunks_exact(4))
+                .flat_map(|(src, dst)| select([src[0], src[1], src[2], src[3]], [dst[0], dst[1], dst[2], dst[3]], channels))
+                .collect::<Vec<u8>>()
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Merged buffer matches dimensions");
+    DynamicImage::ImageRgba8(buffer)
+}
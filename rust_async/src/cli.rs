@@ -0,0 +1,647 @@
+use crate::bench;
+use crate::blend;
+use crate::channels::Channels;
+use crate::clipboard;
+use crate::mask::Polygon;
+use crate::colorspace::ColorSpace;
+use crate::convolve::Kernel;
+use crate::edge::Edge;
+use crate::kuwahara::KuwaharaMode;
+use crate::lut::Lut;
+use crate::magick::Geometry;
+use crate::mandelbrot::View;
+use crate::noise::Noise;
+use crate::oil;
+use crate::overlay::Placement;
+use crate::pyramid::Layout;
+use crate::raw::RawSpec;
+use crate::report::ReportFormat;
+use crate::resize::Resample;
+use crate::roi::Roi;
+use crate::serve;
+use crate::synthetic::SyntheticSpec;
+use crate::timing::TimingFormat;
+use crate::tonemap;
+use crate::varblur;
+use crate::vignette;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// Frames filtered concurrently for animations and video, each spawning its own tasks
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 4;
+// Rows filtered per band in streaming mode
+pub const DEFAULT_BAND_ROWS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    // Write the output path as given
+    File,
+    // Print the output on stdout as a data URI, encoded as the output path extension implies
+    DataUri,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(OutputFormat::File),
+            "datauri" => Ok(OutputFormat::DataUri),
+            other => Err(format!("Unknown output format: {}", other)),
+        }
+    }
+}
+
+// Settings shared by the filters, passed down to every worker
+#[derive(Debug, Clone, Copy)]
+pub struct FilterOptions {
+    pub linear: bool,
+    // Color space Kuwahara compares regions in
+    pub colorspace: ColorSpace,
+    // Weight Kuwahara's region statistics by alpha, so transparent pixels do not darken the means
+    pub alpha_weighted: bool,
+    // Smooth alpha by classic Kuwahara's quadrants along with the colors instead of keeping the input's
+    pub filter_alpha: bool,
+    // Quadrants or the sectors of an ellipse along the local orientation
+    pub kuwahara_mode: KuwaharaMode,
+    // Kuwahara radii blended by local variance: the radius, then halved for each further scale
+    pub scales: u32,
+    // Overlapping Gaussian sectors of a disc in place of classic Kuwahara's four square quadrants
+    pub sectors: Option<u32>,
+    // Radius the adaptive mode falls to on the strongest edges
+    pub min_radius: u32,
+    // Channels the filter changes, the rest pass through from the input
+    pub channels: Channels,
+    // Weights of the `convolve` operation, read from the `--kernel` file
+    pub kernel: Option<Kernel>,
+    // Noise of the `add-noise` operation
+    pub noise: Option<Noise>,
+    // Seed of the noise, the same image for the same seed
+    pub seed: u64,
+    // Spatial spread of the bilateral filter in pixels, a third of the radius when not given
+    pub sigma_space: Option<f64>,
+    // Sigmas of the Gaussian blur's horizontal and vertical passes, a third of the radius when not given
+    pub sigma_x: Option<f64>,
+    pub sigma_y: Option<f64>,
+    // What the blur and the convolutions read past the image's border
+    pub edge: Edge,
+    // Color difference in 8-bit levels at which the bilateral filter's weights fall off
+    pub sigma_color: f64,
+    // Strength of the `sharpen` operation, the detail the blur removed is added back this many times
+    pub amount: f32,
+    // Differences from the blurred image the `sharpen` operation leaves alone, in 8-bit levels
+    pub sharpen_threshold: u8,
+    // Direction of the motion blur in degrees, counterclockwise from the x axis
+    pub angle: f64,
+    // Radius of the patches non-local means compares, 1 for 3x3
+    pub patch_radius: u32,
+    // Patch difference in 8-bit levels at which non-local means' weights fall off
+    pub strength: f64,
+    // Intensity buckets the `oil` operation sorts the neighborhood into
+    pub levels: u32,
+    // Output size of the `resize` operation, taking the radius' place on the command line
+    pub size: Option<Geometry>,
+    // Kernel the `resize` operation samples with
+    pub resample: Resample,
+    // Extra weight of bright pixels in the `bokeh` operation's discs
+    pub highlights: f64,
+    // Power of the distance from the center the `vignette` operation darkens by
+    pub falloff: f64,
+    // Percent of the pixels at each end of a channel `autolevel` lets clip
+    pub clip: f64,
+    // Gamma of the `gamma` operation, taking the radius' place on the command line
+    pub gamma: Option<f64>,
+    // Magnification of the `transform` operation, about the image's center
+    pub scale: f64,
+    // Shift of the `transform` operation in pixels, right and down, after rotating and scaling
+    pub translate: (f64, f64),
+    // Rectangle filtered on its own, the rest of the image copied through
+    pub roi: Option<Roi>,
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        FilterOptions {
+            linear: false,
+            colorspace: ColorSpace::Rgb,
+            alpha_weighted: false,
+            filter_alpha: false,
+            kuwahara_mode: KuwaharaMode::Classic,
+            scales: 1,
+            sectors: None,
+            min_radius: 1,
+            channels: Channels::Rgba,
+            kernel: None,
+            noise: None,
+            seed: 0,
+            sigma_space: None,
+            sigma_x: None,
+            sigma_y: None,
+            edge: Edge::Clamp,
+            sigma_color: 25.0,
+            amount: 1.0,
+            sharpen_threshold: 0,
+            angle: 0.0,
+            patch_radius: 1,
+            strength: 10.0,
+            levels: oil::DEFAULT_LEVELS,
+            size: None,
+            resample: Resample::Lanczos3,
+            highlights: 0.0,
+            falloff: vignette::DEFAULT_FALLOFF,
+            clip: 0.0,
+            gamma: None,
+            scale: 1.0,
+            translate: (0.0, 0.0),
+            roi: None,
+        }
+    }
+}
+
+pub struct Options {
+    pub video: bool,
+    pub frames_in_flight: usize,
+    pub to_srgb: bool,
+    pub stream: bool,
+    pub band_rows: usize,
+    pub raw_format: Option<RawSpec>,
+    pub output_format: OutputFormat,
+    pub from_clipboard: bool,
+    pub to_clipboard: bool,
+    // Generated input standing in for <input_image>
+    pub synthetic: Option<SyntheticSpec>,
+    pub pyramid: Option<Layout>,
+    // Defaults to the layout's usual tile size
+    pub tile_size: Option<u32>,
+    // Pixels shared with neighbouring tiles, Deep Zoom only
+    pub tile_overlap: u32,
+    pub tile_format: String,
+    // Bounding squares `thumbs` writes a thumbnail for, in pixels
+    pub thumb_sizes: Vec<u32>,
+    // Unsharp mask over each thumbnail
+    pub sharpen: bool,
+    // `pyramid` writes each level while the next is being reduced
+    pub pipeline: bool,
+    // How `blend` composites its second image over the first, and how much of it shows
+    pub blend_mode: blend::Mode,
+    pub opacity: f64,
+    // Where `overlay` puts its stamp
+    pub placement: Placement,
+    // Curve `tonemap` compresses luminance with
+    pub tone_operator: tonemap::Operator,
+    // Stops added to tonemap's automatic exposure
+    pub exposure: f64,
+    // Blurred copies `varblur` mixes between by its mask
+    pub blur_levels: usize,
+    // Region and iteration limit of the `mandelbrot` subcommand
+    pub view: View,
+    pub filter: FilterOptions,
+    // Table of the `lut` operation, loaded once and shared by every filter run; FilterOptions is
+    // Copy and cannot own it
+    pub lut: Option<Arc<Lut>>,
+    // Outline to filter inside, the rest of the image passes through
+    pub polygon: Option<Polygon>,
+    // Largest per-channel difference `verify` and `--verify` accept
+    pub tolerance: u8,
+    // Recompute a sample of the output with the serial reference filter and report mismatches
+    pub verify: bool,
+    // Unmeasured filter runs before the timed one
+    pub warmup: usize,
+    // Timed runs per measurement in `bench`
+    pub runs: usize,
+    // Worker counts `bench` measures one after another instead of the positional count
+    pub sweep: Vec<usize>,
+    // Print bench results as JSON on stdout, with progress on stderr
+    pub json: bool,
+    // Other implementation's binary to run the same bench through for comparison
+    pub against: Option<String>,
+    // Every option as given, flags and their values in order, for `--against` to pass on verbatim
+    pub option_args: Vec<String>,
+    // Name to store this bench run under for later comparisons
+    pub save_baseline: Option<String>,
+    // Saved bench run to report the change against
+    pub compare_baseline: Option<String>,
+    // Slowdown in percent that makes `--compare-baseline` fail
+    pub threshold: f64,
+    pub report_format: ReportFormat,
+    // Print each worker's start, end and busy time after filtering
+    pub worker_timing: Option<TimingFormat>,
+    // Report instructions, cycles and cache misses of the filter phase
+    pub perf_counters: bool,
+    // Report RAPL package energy of the filter phase
+    pub energy: bool,
+    // Print the share of rows done and the estimated time left while filtering
+    pub eta: bool,
+    // Print tokio worker busy time and scheduling counters for the filter phase
+    pub runtime_metrics: bool,
+    // Print spawn-to-start and spawn-to-completion percentiles of the filter tasks
+    pub task_latency: bool,
+    // Defaults to the server's usual port
+    pub port: Option<u16>,
+    // Requests `serve` filters at once, defaults to the number of CPUs
+    pub max_concurrent: Option<usize>,
+    // Requests `serve` holds while all filter slots are busy
+    pub queue_depth: usize,
+    // gRPC server `grpc-client` sends images to
+    pub server: Option<String>,
+    // Stay resident and take JSON-RPC requests on stdin instead of filtering one image
+    pub rpc: bool,
+    // Redis URL `worker` takes jobs from
+    pub queue: Option<String>,
+    // Tries per job before `worker` dead-letters it, defaults to the queue's usual count
+    pub max_attempts: Option<u32>,
+    // `tile-worker` addresses `coordinate` spreads tiles over
+    pub workers: Vec<String>,
+    // Seconds `coordinate` gives a default-sized tile before sending it elsewhere
+    pub tile_timeout: Option<u64>,
+    // Unix socket `daemon` listens on, defaults to one in the runtime directory
+    pub socket: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            video: false,
+            frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+            to_srgb: false,
+            stream: false,
+            band_rows: DEFAULT_BAND_ROWS,
+            raw_format: None,
+            output_format: OutputFormat::File,
+            from_clipboard: false,
+            to_clipboard: false,
+            synthetic: None,
+            pyramid: None,
+            tile_size: None,
+            tile_overlap: 1,
+            tile_format: "png".to_string(),
+            thumb_sizes: vec![256],
+            sharpen: false,
+            pipeline: false,
+            blend_mode: blend::Mode::Normal,
+            opacity: 1.0,
+            placement: Placement::default(),
+            tone_operator: tonemap::Operator::default(),
+            exposure: 0.0,
+            blur_levels: varblur::DEFAULT_LEVELS,
+            view: View::default(),
+            filter: FilterOptions::default(),
+            lut: None,
+            polygon: None,
+            tolerance: 0,
+            verify: false,
+            warmup: 0,
+            runs: bench::DEFAULT_RUNS,
+            sweep: Vec::new(),
+            json: false,
+            against: None,
+            option_args: Vec::new(),
+            save_baseline: None,
+            compare_baseline: None,
+            threshold: bench::DEFAULT_THRESHOLD,
+            report_format: ReportFormat::Markdown,
+            worker_timing: None,
+            perf_counters: false,
+            energy: false,
+            eta: false,
+            runtime_metrics: false,
+            task_latency: false,
+            port: None,
+            max_concurrent: None,
+            queue_depth: serve::DEFAULT_QUEUE_DEPTH,
+            server: None,
+            rpc: false,
+            queue: None,
+            max_attempts: None,
+            workers: Vec::new(),
+            tile_timeout: None,
+            socket: None,
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", flag))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+// Sigmas divide the squared distances, so zero or a negative value has no meaning
+fn parse_sigma(flag: &str, value: Option<&String>) -> Result<f64, String> {
+    let sigma: f64 = parse_value(flag, value)?;
+    if sigma > 0.0 {
+        Ok(sigma)
+    } else {
+        Err(format!("{} must be positive", flag))
+    }
+}
+
+// A weight multiplier; zero turns the boost off, a negative one would let pixels weigh nothing
+fn parse_non_negative(flag: &str, value: Option<&String>) -> Result<f64, String> {
+    let value: f64 = parse_value(flag, value)?;
+    if value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("{} must not be negative", flag))
+    }
+}
+
+// A share of something, from none to all of it
+fn parse_fraction(flag: &str, value: Option<&String>) -> Result<f64, String> {
+    let fraction: f64 = parse_value(flag, value)?;
+    if (0.0..=1.0).contains(&fraction) {
+        Ok(fraction)
+    } else {
+        Err(format!("{} must be between 0 and 1", flag))
+    }
+}
+
+// Percent of a channel's pixels left outside its range at each end; half of them from both ends is all
+fn parse_clip(flag: &str, value: Option<&String>) -> Result<f64, String> {
+    let clip: f64 = parse_value(flag, value)?;
+    if (0.0..50.0).contains(&clip) {
+        Ok(clip)
+    } else {
+        Err(format!("{} must be at least 0 and below 50 percent", flag))
+    }
+}
+
+// Buckets of 8-bit intensities, so at least one and at most one per level
+fn parse_levels(flag: &str, value: Option<&String>) -> Result<u32, String> {
+    let levels: u32 = parse_value(flag, value)?;
+    if (1..=256).contains(&levels) {
+        Ok(levels)
+    } else {
+        Err(format!("{} must be between 1 and 256", flag))
+    }
+}
+
+// Parses a comma separated list such as `1,2,4,8`
+fn parse_list<T: FromStr>(flag: &str, value: Option<&String>) -> Result<Vec<T>, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", flag))?;
+    value
+        .split(',')
+        .map(|item| item.trim().parse().map_err(|_| format!("Invalid value for {}: {}", flag, value)))
+        .collect()
+}
+
+// Splits `--flag [value]` options from the positional arguments
+pub fn parse(args: Vec<String>) -> Result<(Vec<String>, Options), String> {
+    let mut positional = Vec::new();
+    let mut options = Options::default();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let start = args.len() - iter.as_slice().len() - 1;
+        match arg.as_str() {
+            "--video" => options.video = true,
+            "--frames-in-flight" => options.frames_in_flight = parse_value(arg, iter.next())?,
+            "--to-srgb" => options.to_srgb = true,
+            "--stream" => options.stream = true,
+            "--band-rows" => options.band_rows = parse_value(arg, iter.next())?,
+            "--raw-format" => options.raw_format = Some(parse_value(arg, iter.next())?),
+            "--output-format" => options.output_format = parse_value(arg, iter.next())?,
+            "--from-clipboard" => options.from_clipboard = true,
+            "--to-clipboard" => options.to_clipboard = true,
+            "--synthetic" => options.synthetic = Some(parse_value(arg, iter.next())?),
+            "--linear" => options.filter.linear = true,
+            "--alpha-weighted" => options.filter.alpha_weighted = true,
+            "--filter-alpha" => options.filter.filter_alpha = true,
+            // Kuwahara's modes and blend's have distinct names, so one flag serves both
+            "--mode" => {
+                let value = iter.next();
+                match value.map(|value| value.parse()) {
+                    Some(Ok(mode)) => options.blend_mode = mode,
+                    _ => options.filter.kuwahara_mode = parse_value(arg, value)?,
+                }
+            }
+            "--scales" => options.filter.scales = parse_value(arg, iter.next())?,
+            "--sectors" => {
+                let sectors = parse_value(arg, iter.next())?;
+                if !(3..=32).contains(&sectors) {
+                    return Err("--sectors must be between 3 and 32".to_string());
+                }
+                options.filter.sectors = Some(sectors);
+            }
+            "--min-radius" => options.filter.min_radius = parse_value(arg, iter.next())?,
+            "--polygon" => options.polygon = Some(iter.next().ok_or("Missing value for --polygon")?.parse()?),
+            "--roi" => options.filter.roi = Some(iter.next().ok_or("Missing value for --roi")?.parse()?),
+            "--seed" => options.filter.seed = parse_value(arg, iter.next())?,
+            "--sigma-space" => options.filter.sigma_space = Some(parse_sigma(arg, iter.next())?),
+            "--sigma" => {
+                let sigma = parse_sigma(arg, iter.next())?;
+                options.filter.sigma_x = Some(sigma);
+                options.filter.sigma_y = Some(sigma);
+            }
+            "--sigma-x" => options.filter.sigma_x = Some(parse_sigma(arg, iter.next())?),
+            "--sigma-y" => options.filter.sigma_y = Some(parse_sigma(arg, iter.next())?),
+            "--edge" => options.filter.edge = parse_value(arg, iter.next())?,
+            "--sigma-color" => options.filter.sigma_color = parse_sigma(arg, iter.next())?,
+            "--amount" => options.filter.amount = parse_value(arg, iter.next())?,
+            "--sharpen-threshold" => options.filter.sharpen_threshold = parse_value(arg, iter.next())?,
+            "--angle" => options.filter.angle = parse_value(arg, iter.next())?,
+            "--patch" => options.filter.patch_radius = parse_value(arg, iter.next())?,
+            "--strength" => options.filter.strength = parse_sigma(arg, iter.next())?,
+            "--levels" => options.filter.levels = parse_levels(arg, iter.next())?,
+            "--resample" => options.filter.resample = parse_value(arg, iter.next())?,
+            "--highlights" => options.filter.highlights = parse_non_negative(arg, iter.next())?,
+            "--falloff" => options.filter.falloff = parse_sigma(arg, iter.next())?,
+            "--clip" => options.filter.clip = parse_clip(arg, iter.next())?,
+            "--kernel" => options.filter.kernel = Some(Kernel::load(iter.next().ok_or("Missing value for --kernel")?)?),
+            "--channels" => options.filter.channels = parse_value(arg, iter.next())?,
+            "--colorspace" => options.filter.colorspace = parse_value(arg, iter.next())?,
+            "--pyramid" => options.pyramid = Some(parse_value(arg, iter.next())?),
+            "--tile-size" => options.tile_size = Some(parse_value(arg, iter.next())?),
+            "--tile-overlap" => options.tile_overlap = parse_value(arg, iter.next())?,
+            "--sizes" => options.thumb_sizes = parse_list(arg, iter.next())?,
+            "--sharpen" => options.sharpen = true,
+            "--pipeline" => options.pipeline = true,
+            "--opacity" => options.opacity = parse_fraction(arg, iter.next())?,
+            "--scale" => options.filter.scale = parse_sigma(arg, iter.next())?,
+            "--translate" => {
+                let shift: Vec<f64> = parse_list(arg, iter.next())?;
+                let [x, y] = shift[..] else {
+                    return Err("--translate takes the shift right and down in pixels, e.g. 20,-10".to_string());
+                };
+                options.filter.translate = (x, y);
+            }
+            "--tile" => options.placement = Placement::Tile,
+            "--operator" => options.tone_operator = parse_value(arg, iter.next())?,
+            "--exposure" => options.exposure = parse_value(arg, iter.next())?,
+            "--blur-levels" => {
+                options.blur_levels = parse_value(arg, iter.next())?;
+                if options.blur_levels == 0 {
+                    return Err("--blur-levels must be at least 1".to_string());
+                }
+            }
+            "--at" => {
+                let at: Vec<i64> = parse_list(arg, iter.next())?;
+                let [x, y] = at[..] else {
+                    return Err("--at takes the stamp's x and y offsets, e.g. 10,10 or -10,-10 from the bottom right".to_string());
+                };
+                options.placement = Placement::At(x, y);
+            }
+            "--center" => {
+                let center: Vec<f64> = parse_list(arg, iter.next())?;
+                let [x, y] = center[..] else {
+                    return Err("--center takes the real and imaginary parts, e.g. -0.75,0.1".to_string());
+                };
+                options.view.center = (x, y);
+            }
+            "--zoom" => options.view.zoom = parse_sigma(arg, iter.next())?,
+            "--max-iter" => {
+                options.view.max_iterations = parse_value(arg, iter.next())?;
+                if options.view.max_iterations == 0 {
+                    return Err("--max-iter must be at least 1".to_string());
+                }
+            }
+            "--tile-format" => options.tile_format = parse_value(arg, iter.next())?,
+            "--tolerance" => options.tolerance = parse_value(arg, iter.next())?,
+            "--verify" => options.verify = true,
+            "--warmup" => options.warmup = parse_value(arg, iter.next())?,
+            "--runs" => options.runs = parse_value(arg, iter.next())?,
+            "--sweep" => options.sweep = parse_list(arg, iter.next())?,
+            "--json" => options.json = true,
+            "--against" => options.against = Some(parse_value(arg, iter.next())?),
+            "--save-baseline" => options.save_baseline = Some(parse_value(arg, iter.next())?),
+            "--compare-baseline" => options.compare_baseline = Some(parse_value(arg, iter.next())?),
+            "--threshold" => options.threshold = parse_value(arg, iter.next())?,
+            "--report-format" => options.report_format = parse_value(arg, iter.next())?,
+            "--worker-timing" => options.worker_timing = Some(parse_value(arg, iter.next())?),
+            "--runtime-metrics" => options.runtime_metrics = true,
+            "--task-latency" => options.task_latency = true,
+            "--perf-counters" => options.perf_counters = true,
+            "--energy" => options.energy = true,
+            "--eta" => options.eta = true,
+            "--port" => options.port = Some(parse_value(arg, iter.next())?),
+            "--max-concurrent" => options.max_concurrent = Some(parse_value(arg, iter.next())?),
+            "--queue-depth" => options.queue_depth = parse_value(arg, iter.next())?,
+            "--server" => options.server = Some(parse_value(arg, iter.next())?),
+            "--rpc" => options.rpc = true,
+            "--queue" => options.queue = Some(parse_value(arg, iter.next())?),
+            "--max-attempts" => options.max_attempts = Some(parse_value(arg, iter.next())?),
+            "--workers" => options.workers = parse_list(arg, iter.next())?,
+            "--tile-timeout" => options.tile_timeout = Some(parse_value(arg, iter.next())?),
+            "--socket" => options.socket = Some(parse_value(arg, iter.next())?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ => positional.push(arg.clone()),
+        }
+        if arg.starts_with("--") {
+            let end = args.len() - iter.as_slice().len();
+            options.option_args.extend_from_slice(&args[start..end]);
+        }
+    }
+
+    // Sectors stand in for classic Kuwahara's quadrants; the adaptive radius and the anisotropic
+    // ellipse have no sectored form
+    if options.filter.sectors.is_some() && options.filter.kuwahara_mode != KuwaharaMode::Classic {
+        return Err("--sectors only applies to --mode classic".to_string());
+    }
+    // The clipboard takes the place of the input and/or output path
+    if options.from_clipboard && positional.len() >= 2 {
+        positional.insert(2, clipboard::PATH.to_string());
+    }
+    // Subcommands take the operation before the input
+    if let Some(spec) = options.synthetic {
+        let index = if matches!(positional.get(1).map(String::as_str), Some("bench" | "verify")) { 3 } else { 2 };
+        if positional.len() >= index {
+            positional.insert(index, spec.to_path());
+        }
+    }
+    if options.to_clipboard && positional.len() >= 3 {
+        positional.insert(3, clipboard::PATH.to_string());
+    }
+
+    Ok((positional, options))
+}
+
+pub fn print_options() {
+    eprintln!("Options:");
+    eprintln!("  --video                 treat input as a video, decoded and encoded through ffmpeg");
+    eprintln!("  --frames-in-flight N    frames filtered concurrently for animations and video (default {})", DEFAULT_FRAMES_IN_FLIGHT);
+    eprintln!("  --to-srgb               convert from the embedded ICC profile to sRGB before filtering");
+    eprintln!("  --stream                filter a PNG band by band instead of loading it whole");
+    eprintln!("  --band-rows N           rows per band in streaming mode (default {})", DEFAULT_BAND_ROWS);
+    eprintln!("  --raw-format F:WxH      read input as a raw frame, F is rgba8, rgb8, gray8, yuv420p or nv12");
+    eprintln!("  --output-format F       'file' (default) or 'datauri' to print the output on stdout as a data URI");
+    eprintln!("  --from-clipboard        read the input image from the clipboard instead of <input_image>");
+    eprintln!("  --to-clipboard          copy the output image to the clipboard instead of <output_image>");
+    eprintln!("  --synthetic P:WxH       generate a noise, gradient, checkerboard or perlin[:F[:O]] input instead of reading <input_image>");
+    eprintln!("  --linear                filter in linear light instead of on gamma-encoded sRGB values");
+    eprintln!("  --colorspace C          color space Kuwahara compares regions in: rgb (default), lab or hsv");
+    eprintln!("  --alpha-weighted        weight Kuwahara's region means and variances by alpha, leaving out transparent pixels");
+    eprintln!("  --filter-alpha          smooth alpha with the colors in classic Kuwahara instead of keeping the input's");
+    eprintln!("  --mode M                Kuwahara variant: classic (default) quadrants, adaptive quadrants shrinking on edges");
+    eprintln!("                          or anisotropic sectors along edges;");
+    eprintln!("                          for blend: normal (default), multiply, screen or overlay");
+    eprintln!("  --scales N              blend classic Kuwahara at N radii, halving from the given one, by local variance (default 1)");
+    eprintln!("  --sectors N             classic Kuwahara over N overlapping sectors of a disc instead of the square quadrants");
+    eprintln!("  --min-radius N          radius adaptive Kuwahara shrinks to on the strongest edges (default 1)");
+    eprintln!("  --channels C            channels to filter: rgba (default), rgb, a or luma; the others pass through");
+    eprintln!("  --seed N                seed of the add-noise operation (default 0)");
+    eprintln!("  --sigma S               sigma of the Gaussian blur in pixels (default radius / 3); a radius of 0 reaches 3 sigmas");
+    eprintln!("  --sigma-x S             sigma of the blur's horizontal pass, overriding --sigma");
+    eprintln!("  --sigma-y S             sigma of the blur's vertical pass, overriding --sigma");
+    eprintln!("  --edge E                what the blur and convolutions read past the border: clamp (default),");
+    eprintln!("                          mirror, wrap or constant:r,g,b[,a]");
+    eprintln!("  --sigma-space S         spatial sigma of the bilateral filter in pixels (default radius / 3)");
+    eprintln!("  --sigma-color S         color sigma of the bilateral filter in 8-bit levels (default 25)");
+    eprintln!("  --amount A              strength of the sharpen operation (default 1)");
+    eprintln!("  --sharpen-threshold N   differences from the blur the sharpen operation leaves alone, in levels (default 0)");
+    eprintln!("  --angle DEG             direction of the motion blur, or transform's rotation, counterclockwise (default 0)");
+    eprintln!("  --scale S               magnification of transform about the image's center (default 1)");
+    eprintln!("  --translate X,Y         shift of transform in pixels, right and down, after rotating and scaling (default 0,0)");
+    eprintln!("  --patch N               radius of the patches nlmeans compares (default 1, 3x3 patches)");
+    eprintln!("  --strength H            patch difference in levels at which nlmeans stops averaging (default 10)");
+    eprintln!("  --levels N              intensity buckets of the oil operation, fewer for broader strokes (default {})", oil::DEFAULT_LEVELS);
+    eprintln!("  --resample F            kernel of the resize operation: nearest, bilinear, bicubic or lanczos3 (default)");
+    eprintln!("  --highlights H          extra weight of bright pixels in bokeh, spreading lights into bright discs (default 0)");
+    eprintln!("  --falloff F             power of the distance from the center vignette darkens by, higher keeps more of the middle (default {})", vignette::DEFAULT_FALLOFF);
+    eprintln!("  --clip P                percent of the pixels autolevel lets clip at each end of every channel (default 0)");
+    eprintln!("  --kernel FILE           matrix of weights for the convolve operation, one row per line");
+    eprintln!("  --polygon P             filter only inside P, points like '10,10 200,10 120,150' or an SVG path of M, L, H, V and Z");
+    eprintln!("  --roi X,Y,W,H           filter only the W by H rectangle at X,Y, splitting its rows among the workers");
+    eprintln!("  --pyramid L             write a 'dzi' (Deep Zoom) or 'xyz' (slippy map) tile pyramid at <output_image>");
+    eprintln!("  --tile-size N           pyramid tile size (default 254 for dzi, 256 for xyz), or coordinate's (default 1024)");
+    eprintln!("  --tile-overlap N        Deep Zoom tile overlap in pixels (default 1)");
+    eprintln!("  --tile-format F         pyramid tile format: png, jpg or webp (default png)");
+    eprintln!("  --sizes 256,512,1024    thumbnail sizes, each the longest side in pixels (default 256)");
+    eprintln!("  --sharpen               sharpen thumbnails with an unsharp mask after resizing");
+    eprintln!("  --opacity O             share of the second image blend composites over the first, or of overlay's stamp, 0 to 1 (default 1)");
+    eprintln!("  --at X,Y                offset of overlay's stamp from the top left, negative from the bottom right (default 0,0)");
+    eprintln!("  --tile                  repeat overlay's stamp over the whole image");
+    eprintln!("  --operator O            tonemap curve: reinhard or aces (default reinhard)");
+    eprintln!("  --exposure EV           stops tonemap brightens by, negative to darken (default 0)");
+    eprintln!("  --blur-levels N         blurred copies varblur mixes between by its mask (default {})", varblur::DEFAULT_LEVELS);
+    eprintln!("  --pipeline              let pyramid write each level while it reduces the next");
+    eprintln!("  --center X,Y            point of the complex plane mandelbrot centers on (default -0.5,0)");
+    eprintln!("  --zoom Z                magnification of mandelbrot, 1 shows the whole set (default 1)");
+    eprintln!("  --max-iter N            iterations before mandelbrot counts a point as inside the set (default 256)");
+    eprintln!("  --tolerance N           largest per-channel difference verify and --verify accept (default 0)");
+    eprintln!("  --verify                recheck the output against a serial reference, every pixel of small images or a random sample");
+    eprintln!("  --warmup N              run the filter N times before the timed run (default 0)");
+    eprintln!("  --runs N                timed runs per measurement in bench (default {})", bench::DEFAULT_RUNS);
+    eprintln!("  --sweep 1,2,4,8         bench each worker count and report speedup, efficiency and scaling fits");
+    eprintln!("  --json                  print bench results as JSON on stdout");
+    eprintln!("  --against BIN           also bench the other implementation's binary and report the async overhead");
+    eprintln!("  --save-baseline NAME    store the bench results as a named baseline under target/bench-baselines");
+    eprintln!("  --compare-baseline NAME report the change against a saved baseline and fail on regressions");
+    eprintln!("  --threshold PCT         slowdown that counts as a regression (default {}%)", bench::DEFAULT_THRESHOLD);
+    eprintln!("  --report-format F       'markdown' (default) or 'html' with a chart per operation, for report");
+    eprintln!("  --worker-timing F       print per-worker start, end and busy times as a 'table' or 'json'");
+    eprintln!("  --runtime-metrics       print tokio worker busy time, parks and poll counts for the filter phase");
+    eprintln!("  --task-latency          print queueing delay and completion time percentiles of the filter tasks");
+    eprintln!("  --perf-counters         report instructions, cycles, IPC and cache misses of the filter (Linux, --features perf-counters)");
+    eprintln!("  --energy                report CPU package energy of the filter from RAPL counters (Linux, usually root)");
+    eprintln!("  --eta                   print progress and the estimated time remaining every few seconds while filtering");
+    eprintln!("  --port N                port serve (default {}), grpc-serve (default 50051) or tile-worker (default 7878) listens on", serve::DEFAULT_PORT);
+    eprintln!("  --max-concurrent N      requests serve and grpc-serve, jobs worker or tiles tile-worker filter at once (default: number of CPUs)");
+    eprintln!("  --queue-depth N         requests queued before the server turns new ones away (default {})", serve::DEFAULT_QUEUE_DEPTH);
+    eprintln!("  --server URL            gRPC server grpc-client uses (default http://127.0.0.1:50051)");
+    eprintln!("  --rpc                   stay resident and serve JSON-RPC requests on stdin, one per line");
+    eprintln!("  --queue URL             redis:// URL of the job stream worker consumes");
+    eprintln!("  --max-attempts N        tries worker gives a failing job before dead-lettering it (default 3)");
+    eprintln!("  --workers A,B           host:port of each tile-worker coordinate sends tiles to");
+    eprintln!("  --tile-timeout S        seconds coordinate gives a 1024 pixel tile, more for larger ones (default 120)");
+    eprintln!("  --socket PATH           Unix socket daemon listens on (default $XDG_RUNTIME_DIR/rust_filter_async.sock)");
+}
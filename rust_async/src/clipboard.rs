@@ -0,0 +1,36 @@
+use arboard::{Clipboard, ImageData};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::borrow::Cow;
+use std::error::Error;
+
+// Placeholder positional path standing in for the clipboard
+pub const PATH: &str = "clipboard:";
+
+pub fn read() -> Result<DynamicImage, Box<dyn Error>> {
+    let image = Clipboard::new()?.get_image()?;
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+        .ok_or("Clipboard image does not match its dimensions")?;
+    Ok(DynamicImage::ImageRgba8(buffer))
+}
+
+// On Linux the clipboard is served by the process that set it, so this blocks until
+// another program (usually the clipboard manager) takes the contents over
+pub fn write(img: &DynamicImage) -> Result<(), Box<dyn Error>> {
+    let rgba = img.to_rgba8();
+    let image = ImageData {
+        width: rgba.width() as usize,
+        height: rgba.height() as usize,
+        bytes: Cow::Borrowed(rgba.as_raw()),
+    };
+    let mut clipboard = Clipboard::new()?;
+
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        clipboard.set().wait().image(image)?;
+    }
+    #[cfg(not(target_os = "linux"))]
+    clipboard.set_image(image)?;
+
+    Ok(())
+}
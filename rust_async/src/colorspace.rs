@@ -0,0 +1,115 @@
+use crate::srgb;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Rgb,
+    Lab,
+    Hsv,
+}
+
+impl FromStr for ColorSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rgb" => Ok(ColorSpace::Rgb),
+            "lab" => Ok(ColorSpace::Lab),
+            "hsv" => Ok(ColorSpace::Hsv),
+            other => Err(format!("Unknown color space: {}", other)),
+        }
+    }
+}
+
+// D65 reference white
+const WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+
+fn lab_f(t: f32) -> f32 {
+    if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    if t > 0.206893 { t * t * t } else { (t - 16.0 / 116.0) / 7.787 }
+}
+
+fn rgb_to_lab(rgb: [u8; 3]) -> [f32; 3] {
+    let lut = srgb::lut();
+    let [r, g, b] = rgb.map(|c| lut.decode(c) as f32 / 255.0);
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / WHITE[0];
+    let y = (0.2126 * r + 0.7152 * g + 0.0722 * b) / WHITE[1];
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / WHITE[2];
+    let (fx, fy, fz) = (lab_f(x), lab_f(y), lab_f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn lab_to_rgb(lab: [f32; 3]) -> [u8; 3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let x = lab_f_inv(fy + lab[1] / 500.0) * WHITE[0];
+    let y = lab_f_inv(fy) * WHITE[1];
+    let z = lab_f_inv(fy - lab[2] / 200.0) * WHITE[2];
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    let lut = srgb::lut();
+    [r, g, b].map(|c| lut.encode(c as f64 * 255.0))
+}
+
+// Hue is stored as a (cos, sin) pair scaled by saturation so averages do not wrap around red
+fn rgb_to_hsv(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let saturation = if max > 0.0 { delta / max } else { 0.0 };
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    } * std::f32::consts::FRAC_PI_3;
+    [max, saturation * hue.cos() * 255.0, saturation * hue.sin() * 255.0]
+}
+
+fn hsv_to_rgb(hsv: [f32; 3]) -> [f32; 3] {
+    let value = hsv[0];
+    let saturation = (hsv[1].hypot(hsv[2]) / 255.0).min(1.0);
+    let hue = hsv[2].atan2(hsv[1]).rem_euclid(std::f32::consts::TAU) / std::f32::consts::FRAC_PI_3;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [r + m, g + m, b + m]
+}
+
+// Converts the color channels of a pixel into `space`. Lab always goes through linear light;
+// `linear` selects whether RGB and HSV work on decoded or gamma-encoded values
+pub fn to_space(rgb: [u8; 3], space: ColorSpace, linear: bool) -> [f32; 3] {
+    let lut = srgb::lut();
+    let channels = rgb.map(|c| if linear { lut.decode(c) as f32 } else { c as f32 });
+    match space {
+        ColorSpace::Rgb => channels,
+        ColorSpace::Lab => rgb_to_lab(rgb),
+        ColorSpace::Hsv => rgb_to_hsv(channels),
+    }
+}
+
+pub fn from_space(values: [f32; 3], space: ColorSpace, linear: bool) -> [u8; 3] {
+    let lut = srgb::lut();
+    let encode = |c: f32| if linear { lut.encode(c as f64) } else { c.round().clamp(0.0, 255.0) as u8 };
+    match space {
+        // Plain RGB means have always been truncated
+        ColorSpace::Rgb if !linear => values.map(|c| c.clamp(0.0, 255.0) as u8),
+        ColorSpace::Rgb => values.map(encode),
+        ColorSpace::Lab => lab_to_rgb(values),
+        ColorSpace::Hsv => hsv_to_rgb(values).map(encode),
+    }
+}
@@ -0,0 +1,161 @@
+use crate::bands;
+use crate::blur::apply_gaussian_blur_async;
+use crate::channels::{self, Channels};
+use crate::cli::FilterOptions;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task;
+
+// A kernel of odd width and height centered on the pixel, its weights row by row. `bias` is added
+// to the weighted sum, and `absolute` keeps the magnitude of the sum for kernels that answer an
+// edge with either sign.
+#[derive(Clone, Copy, Debug)]
+pub struct Kernel {
+    pub width: usize,
+    pub height: usize,
+    pub weights: &'static [f32],
+    pub bias: f32,
+    pub absolute: bool,
+}
+
+// Relief lit from the top left: flat areas turn mid gray, and edges light or dark by which way
+// they face
+pub const EMBOSS: Kernel = Kernel {
+    width: 3,
+    height: 3,
+    weights: &[-1.0, -1.0, 0.0, -1.0, 0.0, 1.0, 0.0, 1.0, 1.0],
+    bias: 128.0,
+    absolute: false,
+};
+
+// 8-neighbor Laplacian: zero on flat areas and linear ramps, large where the intensity bends
+pub const LAPLACIAN: Kernel = Kernel {
+    width: 3,
+    height: 3,
+    weights: &[-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0],
+    bias: 0.0,
+    absolute: true,
+};
+
+// A kernel file: one row of weights per line, separated by spaces or commas, with `#` comments.
+// Optional `divisor N` divides every weight, `bias N` sets the offset and `absolute` the magnitude.
+// Kernels are read once per run, so the weights are leaked to stay `Copy` like the built-in ones.
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rows: Vec<Vec<f32>> = Vec::new();
+        let mut divisor = 1.0;
+        let mut bias = 0.0;
+        let mut absolute = false;
+
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == ',').filter(|word| !word.is_empty()).collect();
+            match words.as_slice() {
+                [] => {}
+                ["divisor", n] => {
+                    divisor = n.parse().map_err(|_| format!("Invalid divisor: {}", n))?;
+                    if divisor == 0.0 {
+                        return Err("divisor must not be 0".to_string());
+                    }
+                }
+                ["bias", n] => bias = n.parse().map_err(|_| format!("Invalid bias: {}", n))?,
+                ["absolute"] => absolute = true,
+                [first, ..] if first.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    return Err(format!("Unknown kernel keyword: {}", first));
+                }
+                values => rows.push(values.iter().map(|v| v.parse().map_err(|_| format!("Invalid weight: {}", v))).collect::<Result<_, _>>()?),
+            }
+        }
+
+        let height = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+        if height == 0 {
+            return Err("Kernel has no weights".to_string());
+        }
+        if let Some(row) = rows.iter().find(|row| row.len() != width) {
+            return Err(format!("Kernel rows must be the same length, got {} and {}", width, row.len()));
+        }
+        if width.is_multiple_of(2) || height.is_multiple_of(2) {
+            return Err(format!("Kernel must have an odd width and height to center on a pixel, got {}x{}", width, height));
+        }
+        let weights: Vec<f32> = rows.into_iter().flatten().map(|weight| weight / divisor).collect();
+        Ok(Kernel { width, height, weights: Box::leak(weights.into_boxed_slice()), bias, absolute })
+    }
+}
+
+impl Kernel {
+    pub fn load(path: &str) -> Result<Kernel, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        text.parse().map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Rows on each side of a pixel the kernel reads
+    pub fn reach(&self) -> usize {
+        self.height / 2
+    }
+
+    // The kernel at one pixel of one channel, `value` giving the channel at an offset from it.
+    // Taps are summed row by row, then rounded and clamped to 8 bits.
+    pub fn apply(&self, value: impl Fn(i32, i32) -> f32) -> u8 {
+        let (reach_x, reach_y) = ((self.width / 2) as i32, (self.height / 2) as i32);
+        let mut sum = 0.0;
+        for (i, weight) in self.weights.iter().enumerate() {
+            let (dx, dy) = ((i % self.width) as i32 - reach_x, (i / self.width) as i32 - reach_y);
+            sum += weight * value(dx, dy);
+        }
+        sum += self.bias;
+        if self.absolute {
+            sum = sum.abs();
+        }
+        sum.round().clamp(0.0, 255.0) as u8
+    }
+}
+
+// Runs `kernel` over every color channel, alpha kept, reading past the border by `--edge`. A radius
+// above 0 smooths the image with the Gaussian blur first, as for Sobel.
+pub async fn apply_convolution_async(img: &DynamicImage, kernel: Kernel, radius: i32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let radius = u32::try_from(radius).expect("radius must not be negative");
+    let src = Arc::new(img.to_rgba8());
+    let smoothed = if radius == 0 {
+        Arc::clone(&src)
+    } else {
+        let blurred = apply_gaussian_blur_async(img, radius, num_tasks, FilterOptions { channels: Channels::Rgba, ..filter }).await;
+        Arc::new(blurred.to_rgba8())
+    };
+
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height as usize, num_tasks) {
+        let src = Arc::clone(&src);
+        let smoothed = Arc::clone(&smoothed);
+        tasks.push(task::spawn(async move {
+            let mut band = src.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            for (y, row) in rows.clone().zip(band.chunks_exact_mut(row_len)) {
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    for (channel, value) in pixel[..3].iter_mut().enumerate() {
+                        *value = kernel.apply(|dx, dy| {
+                            filter.edge.pixel(x as i32 + dx, y as i32 + dy, width, height, |sx, sy| smoothed.get_pixel(sx, sy).0)[channel] as f32
+                        });
+                    }
+                }
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Convolved buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
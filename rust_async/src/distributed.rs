@@ -0,0 +1,312 @@
+// Filters one image across several machines. The coordinator cuts it into tiles, each with `radius`
+// pixels of context around it so the seams match a single-machine run, and sends them to
+// `tile-worker` processes over TCP. A tile whose worker fails or times out goes to another one.
+//
+// Every message is a frame: a big-endian u32 length, then that many bytes.
+// Request: operation (0 blur, 1 kuwahara), then radius as a big-endian i32 of at most
+// `serve::MAX_RADIUS`, tasks, width and height as big-endian u32s, then the tile as RGBA8 rows.
+// Reply: 0 and the filtered RGBA8 tile, or 1 and an error message.
+
+use crate::serve;
+use image::{imageops, DynamicImage, RgbaImage};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+pub const DEFAULT_PORT: u16 = 7878;
+pub const DEFAULT_TILE_SIZE: u32 = 1024;
+// Two tiles in flight per worker, so one uploads while the other is filtered
+const CONNECTIONS_PER_WORKER: usize = 2;
+// A tile failing on this many workers fails the job, it is most likely the tile and not the workers
+const TILE_ATTEMPTS: u32 = 3;
+// Time a worker gets for a default-sized tile, `--tile-timeout` on the command line
+pub const DEFAULT_TILE_TIMEOUT: Duration = Duration::from_secs(120);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Workers refuse larger frames instead of allocating whatever length a peer claims
+const MAX_FRAME_BYTES: usize = 512 * 1024 * 1024;
+const HEADER_BYTES: usize = 17;
+
+const OK: u8 = 0;
+const FAILED: u8 = 1;
+
+async fn write_frame(stream: &mut TcpStream, parts: &[&[u8]]) -> io::Result<()> {
+    let len: usize = parts.iter().map(|part| part.len()).sum();
+    stream.write_all(&(len as u32).to_be_bytes()).await?;
+    for part in parts {
+        stream.write_all(part).await?;
+    }
+    stream.flush().await
+}
+
+// None when the peer closed the connection between frames
+async fn read_frame(stream: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match stream.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes is too large", len)));
+    }
+    let mut frame = vec![0; len];
+    stream.read_exact(&mut frame).await?;
+    Ok(Some(frame))
+}
+
+struct TileRequest {
+    operation: &'static str,
+    radius: i32,
+    tasks: usize,
+    tile: RgbaImage,
+}
+
+fn parse_request(frame: Vec<u8>) -> Result<TileRequest, String> {
+    if frame.len() < HEADER_BYTES {
+        return Err("request is too short".to_string());
+    }
+    let number = |at: usize| u32::from_be_bytes(frame[at..at + 4].try_into().unwrap());
+    let operation = match frame[0] {
+        0 => "blur",
+        1 => "kuwahara",
+        other => return Err(format!("unknown operation {}", other)),
+    };
+    // Signed, so a radius that would wrap negative is refused rather than filtered
+    let radius = i32::from_be_bytes(frame[1..5].try_into().unwrap());
+    if !(0..=serve::MAX_RADIUS).contains(&radius) {
+        return Err(format!("radius {} is not between 0 and {}", radius, serve::MAX_RADIUS));
+    }
+    let (tasks, width, height) = (number(5), number(9), number(13));
+    let tile = RgbaImage::from_raw(width, height, frame[HEADER_BYTES..].to_vec())
+        .ok_or_else(|| format!("{}x{} tile does not match {} bytes of pixels", width, height, frame.len() - HEADER_BYTES))?;
+    Ok(TileRequest { operation, radius, tasks: (tasks as usize).max(1), tile })
+}
+
+async fn serve_connection(mut stream: TcpStream, compute: Arc<Semaphore>) -> io::Result<()> {
+    while let Some(frame) = read_frame(&mut stream).await? {
+        let request = match parse_request(frame) {
+            Ok(request) => request,
+            Err(message) => {
+                write_frame(&mut stream, &[&[FAILED], message.as_bytes()]).await?;
+                continue;
+            }
+        };
+        let _slot = compute.acquire().await.expect("Semaphore is never closed");
+        let (width, height) = request.tile.dimensions();
+        let result = serve::apply(request.operation, &DynamicImage::ImageRgba8(request.tile), request.radius, request.tasks).await;
+        println!("{} radius {} on a {}x{} tile", request.operation, request.radius, width, height);
+        write_frame(&mut stream, &[&[OK], result.to_rgba8().as_raw()]).await?;
+    }
+    Ok(())
+}
+
+// Filters tiles for coordinators until interrupted with Ctrl-C
+pub async fn serve(port: u16, max_concurrent: usize) -> io::Result<()> {
+    if max_concurrent == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "--max-concurrent must be positive"));
+    }
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    let compute = Arc::new(Semaphore::new(max_concurrent));
+    println!("Filtering tiles on port {} with {} at once", port, max_concurrent);
+
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut shutdown => return Ok(()),
+        };
+        let compute = Arc::clone(&compute);
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, compute).await {
+                eprintln!("Warning: connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+struct Tile {
+    // Where the tile's own pixels go in the output
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    // The region sent, the tile plus its context
+    region: (u32, u32, u32, u32),
+    attempts: u32,
+}
+
+fn split(width: u32, height: u32, tile_size: u32, halo: u32) -> VecDeque<Tile> {
+    let mut tiles = VecDeque::new();
+    for y in (0..height).step_by(tile_size as usize) {
+        for x in (0..width).step_by(tile_size as usize) {
+            let (tile_width, tile_height) = (tile_size.min(width - x), tile_size.min(height - y));
+            let (left, top) = (x.saturating_sub(halo), y.saturating_sub(halo));
+            let (right, bottom) = ((x + tile_width + halo).min(width), (y + tile_height + halo).min(height));
+            tiles.push_back(Tile { x, y, width: tile_width, height: tile_height, region: (left, top, right - left, bottom - top), attempts: 0 });
+        }
+    }
+    tiles
+}
+
+struct Connection {
+    worker: usize,
+    stream: TcpStream,
+}
+
+enum Failure {
+    // The worker or the network failed, another worker may do better
+    Lost(io::Error),
+    // The worker refused the tile, another would too
+    Refused(String),
+}
+
+enum Event {
+    Connected(usize, io::Result<TcpStream>),
+    Filtered(Connection, Tile, Result<Vec<u8>, Failure>),
+}
+
+async fn connect(worker: usize, address: String) -> Event {
+    let stream = match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&address)).await {
+        Ok(stream) => stream,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "connection timed out")),
+    };
+    Event::Connected(worker, stream)
+}
+
+async fn exchange(stream: &mut TcpStream, header: &[u8], pixels: &[u8]) -> Result<Vec<u8>, Failure> {
+    write_frame(stream, &[header, pixels]).await.map_err(Failure::Lost)?;
+    let reply = read_frame(stream)
+        .await
+        .map_err(Failure::Lost)?
+        .ok_or_else(|| Failure::Lost(io::Error::new(io::ErrorKind::UnexpectedEof, "worker closed the connection")))?;
+    match reply.split_first() {
+        Some((&OK, pixels)) if pixels.len() == pixel_bytes(header) => Ok(pixels.to_vec()),
+        Some((&FAILED, message)) => Err(Failure::Refused(String::from_utf8_lossy(message).into_owned())),
+        _ => Err(Failure::Lost(io::Error::new(io::ErrorKind::InvalidData, "malformed reply"))),
+    }
+}
+
+// Bytes of RGBA8 pixels the request header announces
+fn pixel_bytes(header: &[u8]) -> usize {
+    let number = |at: usize| u32::from_be_bytes(header[at..at + 4].try_into().unwrap()) as usize;
+    number(9) * number(13) * 4
+}
+
+// Time a tile of `width` x `height` pixels, its halo included, gets: `timeout` for a tile of the
+// default size or less, and more in proportion to its pixels, so a large radius or tile size does
+// not time out on a healthy worker
+pub fn tile_timeout(timeout: Duration, width: u32, height: u32) -> Duration {
+    let pixels = width as f64 * height as f64 / (DEFAULT_TILE_SIZE as f64 * DEFAULT_TILE_SIZE as f64);
+    timeout.mul_f64(pixels.max(1.0))
+}
+
+async fn filter_tile(mut connection: Connection, tile: Tile, header: Vec<u8>, pixels: Vec<u8>, timeout: Duration) -> Event {
+    let result = match tokio::time::timeout(timeout, exchange(&mut connection.stream, &header, &pixels)).await {
+        Ok(result) => result,
+        Err(_) => Err(Failure::Lost(io::Error::new(io::ErrorKind::TimedOut, "tile timed out"))),
+    };
+    Event::Filtered(connection, tile, result)
+}
+
+pub struct DistributedStats {
+    pub tiles: usize,
+    // Tiles sent again after their worker failed
+    pub reassigned: usize,
+    // Tiles each worker filtered, in the order the workers were given
+    pub per_worker: Vec<usize>,
+}
+
+pub async fn filter(
+    img: &DynamicImage,
+    operation: &str,
+    radius: i32,
+    tasks: usize,
+    workers: &[String],
+    tile_size: u32,
+    tile_timeout: Duration,
+) -> Result<(DynamicImage, DistributedStats), Box<dyn Error>> {
+    serve::check_request(operation, radius, tasks.max(1))?;
+    if tile_size == 0 || tile_timeout.is_zero() {
+        return Err("the tile size and the tile timeout must be positive".into());
+    }
+    if workers.is_empty() {
+        return Err("no workers given, use --workers host:port,...".into());
+    }
+
+    let source = img.to_rgba8();
+    let (width, height) = source.dimensions();
+    let mut output = RgbaImage::new(width, height);
+    let mut pending = split(width, height, tile_size, radius as u32);
+    let mut stats = DistributedStats { tiles: pending.len(), reassigned: 0, per_worker: vec![0; workers.len()] };
+    let operation_code = if operation == "blur" { 0 } else { 1 };
+
+    let mut running = JoinSet::new();
+    for (worker, address) in workers.iter().enumerate() {
+        for _ in 0..CONNECTIONS_PER_WORKER {
+            running.spawn(connect(worker, address.clone()));
+        }
+    }
+
+    let mut idle = Vec::new();
+    let mut done = 0;
+    while done < stats.tiles {
+        while !pending.is_empty() {
+            let Some(connection) = idle.pop() else {
+                break;
+            };
+            let tile = pending.pop_front().expect("Checked above");
+            let (left, top, region_width, region_height) = tile.region;
+            let mut header = vec![operation_code];
+            for number in [radius as u32, tasks as u32, region_width, region_height] {
+                header.extend_from_slice(&number.to_be_bytes());
+            }
+            let pixels = imageops::crop_imm(&source, left, top, region_width, region_height).to_image().into_raw();
+            let timeout = self::tile_timeout(tile_timeout, region_width, region_height);
+            running.spawn(filter_tile(connection, tile, header, pixels, timeout));
+        }
+
+        // Nothing running means every connection failed with tiles still to go
+        let Some(event) = running.join_next().await else {
+            return Err(format!("all workers failed with {} of {} tiles left", stats.tiles - done, stats.tiles).into());
+        };
+        match event.expect("Tile task panicked") {
+            Event::Connected(worker, Ok(stream)) => idle.push(Connection { worker, stream }),
+            Event::Connected(worker, Err(e)) => eprintln!("Warning: could not connect to {}: {}", workers[worker], e),
+            Event::Filtered(connection, tile, Ok(pixels)) => {
+                let (left, top, region_width, region_height) = tile.region;
+                let region = RgbaImage::from_raw(region_width, region_height, pixels).expect("Reply length was checked");
+                let own = imageops::crop_imm(&region, tile.x - left, tile.y - top, tile.width, tile.height);
+                imageops::replace(&mut output, &*own, tile.x as i64, tile.y as i64);
+                stats.per_worker[connection.worker] += 1;
+                done += 1;
+                idle.push(connection);
+            }
+            Event::Filtered(connection, _, Err(Failure::Refused(message))) => {
+                return Err(format!("{} refused a tile: {}", workers[connection.worker], message).into());
+            }
+            Event::Filtered(connection, mut tile, Err(Failure::Lost(e))) => {
+                // The connection is dropped, the worker keeps any others it has
+                eprintln!("Warning: tile at {},{} failed on {}: {}", tile.x, tile.y, workers[connection.worker], e);
+                tile.attempts += 1;
+                if tile.attempts >= TILE_ATTEMPTS {
+                    return Err(format!("tile at {},{} failed {} times", tile.x, tile.y, tile.attempts).into());
+                }
+                stats.reassigned += 1;
+                pending.push_back(tile);
+            }
+        }
+    }
+
+    let output = DynamicImage::ImageRgba8(output);
+    // Keep opaque inputs opaque, as the local filters do
+    let output = if img.color().has_alpha() { output } else { DynamicImage::ImageRgb8(output.to_rgb8()) };
+    Ok((output, stats))
+}
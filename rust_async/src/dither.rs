@@ -0,0 +1,126 @@
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task;
+use tracing::Instrument;
+
+// Columns of diffused error a row hands to the next in one message. Smaller chunks let the next
+// row start sooner but cost more messages.
+pub const CHUNK: usize = 32;
+
+// Nearest of `levels` values spread evenly over 0..=255
+pub fn quantize(value: i32, levels: u32) -> u8 {
+    let steps = levels as i32 - 1;
+    let level = (value * steps + 127) / 255;
+    ((level * 255 + steps / 2) / steps) as u8
+}
+
+// The Floyd–Steinberg split of a pixel's error into the parts for its right, below-left, below and
+// below-right neighbors, 7/16, 3/16, 5/16 and 1/16 of it. The parts are integers and the right one
+// takes what rounding leaves over, so no error is lost and any order of adding them up gives the
+// same sums.
+pub fn diffuse(error: i32) -> [i32; 4] {
+    let below_left = error * 3 / 16;
+    let below = error * 5 / 16;
+    let below_right = error / 16;
+    [error - below_left - below - below_right, below_left, below, below_right]
+}
+
+// Dithers one row given the error diffused into it from the row above, which arrives a chunk at a
+// time on `incoming`, and sends the error it diffuses into the row below the same way. The first
+// `x` columns of the row below are final once column `x` is done, so the next row can start while
+// this one is still running.
+async fn dither_row(
+    row: &mut [u8],
+    levels: u32,
+    mut incoming: Option<&mut UnboundedReceiver<Vec<[i32; 3]>>>,
+    outgoing: Option<&UnboundedSender<Vec<[i32; 3]>>>,
+) {
+    let width = row.len() / 4;
+    let mut above: Vec<[i32; 3]> = Vec::with_capacity(width);
+    let mut below = vec![[0; 3]; width];
+    let mut sent = 0;
+    let mut right = [0; 3];
+
+    for x in 0..width {
+        while above.len() <= x {
+            match incoming.as_mut() {
+                Some(incoming) => above.extend(incoming.recv().await.expect("Row above stopped early")),
+                None => above.resize(width, [0; 3]),
+            }
+        }
+        for ch in 0..3 {
+            let value = (row[x * 4 + ch] as i32 + above[x][ch] + right[ch]).clamp(0, 255);
+            let quantized = quantize(value, levels);
+            row[x * 4 + ch] = quantized;
+            let [to_right, below_left, to_below, below_right] = diffuse(value - quantized as i32);
+            right[ch] = to_right;
+            if x > 0 {
+                below[x - 1][ch] += below_left;
+            }
+            below[x][ch] += to_below;
+            if x + 1 < width {
+                below[x + 1][ch] += below_right;
+            }
+        }
+        if let Some(outgoing) = outgoing {
+            if x - sent >= CHUNK {
+                outgoing.send(below[sent..x].to_vec()).expect("Row below stopped early");
+                sent = x;
+            }
+        }
+    }
+    if let Some(outgoing) = outgoing {
+        outgoing.send(below[sent..].to_vec()).expect("Row below stopped early");
+    }
+}
+
+// Floyd–Steinberg dithering of every color channel to `levels` values, alpha kept. Error diffusion
+// runs left to right and top to bottom, so no pixel can be finished before those above and to its
+// left. Rows are dealt out to the tasks in turn and run as a staircase: each task passes the error
+// of its row to the task of the next a chunk of columns at a time, and that task starts on its row
+// as soon as the first chunk arrives. The output is the same as dithering serially.
+pub async fn apply_dither_async(img: &DynamicImage, levels: i32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let levels = u32::try_from(levels).ok().filter(|levels| (2..=256).contains(levels)).expect("levels must be between 2 and 256");
+    let src = Arc::new(img.to_rgba8());
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    let num_tasks = num_tasks.clamp(1, height.max(1) as usize);
+
+    // A ring of channels, each task sending to the one after it
+    let (mut senders, receivers): (Vec<_>, Vec<_>) = (0..num_tasks).map(|_| mpsc::unbounded_channel()).unzip();
+    senders.rotate_left(1);
+    let mut tasks = Vec::new();
+    for (task_id, (mut incoming, outgoing)) in receivers.into_iter().zip(senders).enumerate() {
+        let src = Arc::clone(&src);
+        tasks.push(task::spawn(async move {
+            let mut rows = Vec::new();
+            for y in (task_id..height as usize).step_by(num_tasks) {
+                let mut row = src.as_raw()[y * row_len..(y + 1) * row_len].to_vec();
+                let incoming = (y > 0).then_some(&mut incoming);
+                let outgoing = (y + 1 < height as usize).then_some(&outgoing);
+                dither_row(&mut row, levels, incoming, outgoing).await;
+                progress::advance(1);
+                rows.push(row);
+            }
+            rows
+        }
+        .instrument(tracing::debug_span!("task", id = task_id))));
+    }
+
+    let mut rows = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        rows.push(task.await.unwrap().into_iter());
+    }
+    // Rows were dealt out in turn, so they are collected back the same way
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for y in 0..height as usize {
+        data.extend_from_slice(&rows[y % num_tasks].next().expect("Every row was dithered"));
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Dithered buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::PathBuf;
+
+// RAPL domains exposed by the powercap driver, one `intel-rapl:N` directory per CPU package
+// (AMD packages appear under the same name)
+const POWERCAP: &str = "/sys/class/powercap";
+
+struct Domain {
+    energy_path: PathBuf,
+    // The counter wraps around to zero after this many microjoules
+    max_range: u64,
+    start: u64,
+}
+
+fn read_counter(path: &PathBuf) -> Result<u64, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {} (RAPL counters are usually root-only)", path.display(), e))?;
+    text.trim().parse().map_err(|_| format!("Invalid energy counter in {}", path.display()))
+}
+
+// Package energy consumed since `start`, summed over all packages
+pub struct EnergyMeter {
+    domains: Vec<Domain>,
+}
+
+impl EnergyMeter {
+    pub fn start() -> Result<EnergyMeter, String> {
+        let entries = fs::read_dir(POWERCAP).map_err(|_| "RAPL energy counters are not available on this machine".to_string())?;
+        let mut package_dirs: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                // Subdomains such as intel-rapl:0:0 (cores) are already part of their package
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                name.strip_prefix("intel-rapl:").is_some_and(|index| !index.contains(':'))
+            })
+            .collect();
+        package_dirs.sort();
+        if package_dirs.is_empty() {
+            return Err("RAPL energy counters are not available on this machine".to_string());
+        }
+
+        let domains = package_dirs
+            .into_iter()
+            .map(|dir| {
+                let energy_path = dir.join("energy_uj");
+                let max_range = read_counter(&dir.join("max_energy_range_uj"))?;
+                let start = read_counter(&energy_path)?;
+                Ok(Domain { energy_path, max_range, start })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(EnergyMeter { domains })
+    }
+
+    // Assumes each counter wrapped at most once, i.e. the phase was shorter than a wrap period (minutes at full load)
+    pub fn joules(&self) -> Result<f64, String> {
+        let mut microjoules = 0;
+        for domain in &self.domains {
+            let now = read_counter(&domain.energy_path)?;
+            microjoules += if now >= domain.start { now - domain.start } else { domain.max_range - domain.start + now };
+        }
+        Ok(microjoules as f64 / 1e6)
+    }
+}
+
+pub fn format(joules: f64, width: u32, height: u32) -> String {
+    let megapixels = (width as f64 * height as f64) / 1e6;
+    format!("Energy: {:.2} J, {:.3} J/megapixel", joules, joules / megapixels)
+}
@@ -0,0 +1,142 @@
+use crate::serve::{self, Limits};
+use axum::http::StatusCode;
+use image::GenericImageView;
+use proto::filter_chunk::Part;
+use proto::filter_service_client::FilterServiceClient;
+use proto::filter_service_server::{FilterService, FilterServiceServer};
+use proto::{FilterChunk, FilterParams, FilterReply, FilterRequest, Operation};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task;
+use tonic::{Request, Response, Status, Streaming};
+
+pub mod proto {
+    tonic::include_proto!("concurrency.filter");
+}
+
+pub const DEFAULT_PORT: u16 = 50051;
+pub const DEFAULT_SERVER: &str = "http://127.0.0.1:50051";
+// Replies carry the whole filtered image, so both ends accept messages far above tonic's 4 MiB default
+const MAX_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
+// Inputs above this size are uploaded in chunks of this size
+pub const CHUNK_BYTES: usize = 1024 * 1024;
+
+struct Service {
+    limits: Arc<Limits>,
+}
+
+fn too_busy() -> Status {
+    Status::resource_exhausted("Too many requests queued, retry later")
+}
+
+impl Service {
+    async fn filter_image(&self, params: FilterParams, image: Vec<u8>) -> Result<Response<FilterReply>, Status> {
+        let operation = match Operation::try_from(params.operation) {
+            Ok(Operation::Blur) => "blur",
+            Ok(Operation::Kuwahara) => "kuwahara",
+            _ => return Err(Status::invalid_argument("operation must be BLUR or KUWAHARA")),
+        };
+        let tasks = if params.tasks == 0 { 4 } else { params.tasks as usize };
+        serve::check_request(operation, params.radius, tasks).map_err(Status::invalid_argument)?;
+        let format = Some(params.format.as_str()).filter(|format| !format.is_empty());
+        let format = serve::output_format(format, &image).map_err(Status::invalid_argument)?;
+
+        let _computing = self.limits.compute_slot().await;
+        let img = task::spawn_blocking(move || serve::decode(operation, &image))
+            .await
+            .expect("Decoder panicked")
+            .map_err(|(code, message)| match code {
+                StatusCode::PAYLOAD_TOO_LARGE => Status::resource_exhausted(message),
+                _ => Status::invalid_argument(message),
+            })?;
+
+        let start = Instant::now();
+        let result = serve::apply(operation, &img, params.radius, tasks).await;
+        let filter_time = start.elapsed();
+        let (width, height) = result.dimensions();
+        println!("{} radius {} on {}x{} with {} tasks: {}ms", operation, params.radius, width, height, tasks, filter_time.as_millis());
+
+        let encoded = task::spawn_blocking(move || {
+            let mut encoded = Vec::new();
+            serve::encode(&result, format, &mut encoded).map(|_| encoded)
+        })
+        .await
+        .expect("Encoder panicked")
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(FilterReply {
+            image: encoded,
+            content_type: format.to_mime_type().to_string(),
+            width,
+            height,
+            filter_time_us: filter_time.as_micros() as u64,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl FilterService for Service {
+    async fn filter(&self, request: Request<FilterRequest>) -> Result<Response<FilterReply>, Status> {
+        let _admitted = self.limits.admit().ok_or_else(too_busy)?;
+        let request = request.into_inner();
+        let params = request.params.ok_or_else(|| Status::invalid_argument("params are missing"))?;
+        self.filter_image(params, request.image).await
+    }
+
+    async fn filter_stream(&self, request: Request<Streaming<FilterChunk>>) -> Result<Response<FilterReply>, Status> {
+        // Admitted before the upload is read, so a full queue does not buffer images it will not filter
+        let _admitted = self.limits.admit().ok_or_else(too_busy)?;
+        let mut chunks = request.into_inner();
+        let Some(FilterChunk { part: Some(Part::Params(params)) }) = chunks.message().await? else {
+            return Err(Status::invalid_argument("the first chunk must carry the params"));
+        };
+
+        let mut image = Vec::new();
+        while let Some(chunk) = chunks.message().await? {
+            match chunk.part {
+                Some(Part::Data(data)) if image.len() + data.len() <= MAX_MESSAGE_BYTES => image.extend_from_slice(&data),
+                Some(Part::Data(_)) => return Err(Status::resource_exhausted("image is too large")),
+                _ => return Err(Status::invalid_argument("only the first chunk may carry params")),
+            }
+        }
+        self.filter_image(params, image).await
+    }
+}
+
+// Serves FilterService until interrupted with Ctrl-C
+pub async fn serve(port: u16, max_concurrent: usize, queue_depth: usize) -> Result<(), Box<dyn Error>> {
+    let service = Service { limits: Arc::new(Limits::new(max_concurrent, queue_depth)?) };
+    let server = FilterServiceServer::new(service)
+        .max_decoding_message_size(MAX_MESSAGE_BYTES)
+        .max_encoding_message_size(MAX_MESSAGE_BYTES);
+
+    let address = ([0, 0, 0, 0], port).into();
+    println!("Serving FilterService on {} with {} filters at once and {} queued", address, max_concurrent, queue_depth);
+    tonic::transport::Server::builder()
+        .add_service(server)
+        .serve_with_shutdown(address, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+// Sends small images in one request and streams larger ones in chunks
+pub async fn filter(server: String, params: FilterParams, image: Vec<u8>) -> Result<FilterReply, Box<dyn Error>> {
+    let mut client = FilterServiceClient::connect(server)
+        .await?
+        .max_decoding_message_size(MAX_MESSAGE_BYTES)
+        .max_encoding_message_size(MAX_MESSAGE_BYTES);
+
+    let reply = if image.len() <= CHUNK_BYTES {
+        client.filter(FilterRequest { params: Some(params), image }).await?
+    } else {
+        let chunks: Vec<_> = std::iter::once(Part::Params(params))
+            .chain(image.chunks(CHUNK_BYTES).map(|data| Part::Data(data.to_vec())))
+            .map(|part| FilterChunk { part: Some(part) })
+            .collect();
+        client.filter_stream(tokio_stream::iter(chunks)).await?
+    };
+    Ok(reply.into_inner())
+}
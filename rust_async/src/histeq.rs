@@ -0,0 +1,99 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::task;
+
+// Counts of every value of the red, green and blue channels
+pub type Histograms = [[u64; 256]; 3];
+
+// Histograms of a run of RGBA pixels
+pub fn count(pixels: &[u8]) -> Histograms {
+    let mut histograms = [[0; 256]; 3];
+    for pixel in pixels.chunks_exact(4) {
+        for (histogram, &value) in histograms.iter_mut().zip(pixel) {
+            histogram[value as usize] += 1;
+        }
+    }
+    histograms
+}
+
+// Sums partial histograms into those of the whole image
+pub fn merge(partials: &[Histograms]) -> Histograms {
+    let mut total = [[0; 256]; 3];
+    for partial in partials {
+        for (sums, counts) in total.iter_mut().zip(partial) {
+            for (sum, count) in sums.iter_mut().zip(counts) {
+                *sum += count;
+            }
+        }
+    }
+    total
+}
+
+// Maps each value to its place in the cumulative distribution, stretched so the darkest value
+// present becomes 0 and the brightest 255. A channel holding a single value is left alone.
+pub fn equalization_table(histogram: &[u64; 256]) -> [u8; 256] {
+    let total: u64 = histogram.iter().sum();
+    let darkest_count = histogram.iter().copied().find(|&count| count > 0).unwrap_or(0);
+    if total == darkest_count {
+        return std::array::from_fn(|value| value as u8);
+    }
+    let mut cumulative = 0;
+    std::array::from_fn(|value| {
+        cumulative += histogram[value];
+        let rank = cumulative.saturating_sub(darkest_count);
+        ((rank * 255 + (total - darkest_count) / 2) / (total - darkest_count)) as u8
+    })
+}
+
+// Histogram equalization of every color channel, alpha kept. Each task counts the histograms of
+// its band; once all have finished the partial counts are merged into the image's, and a second
+// round of tasks over the same bands remaps every pixel through the tables they give.
+pub async fn apply_histogram_equalization_async(img: &DynamicImage, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let src = Arc::new(img.to_rgba8());
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    // Both passes
+    progress::expect(2 * height as usize);
+
+    let bands = bands::split(height as usize, num_tasks);
+    let mut tasks = Vec::new();
+    for rows in bands.iter().cloned() {
+        let src = Arc::clone(&src);
+        tasks.push(task::spawn(async move {
+            let partial = count(&src.as_raw()[rows.start * row_len..rows.end * row_len]);
+            progress::advance(rows.len());
+            partial
+        }));
+    }
+    let mut partials = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        partials.push(task.await.unwrap());
+    }
+    let tables = tracing::info_span!("merge", parts = partials.len()).in_scope(|| merge(&partials).map(|histogram| equalization_table(&histogram)));
+
+    let mut tasks = Vec::new();
+    for rows in bands {
+        let src = Arc::clone(&src);
+        tasks.push(task::spawn(async move {
+            let mut band = src.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            for pixel in band.chunks_exact_mut(4) {
+                for (value, table) in pixel.iter_mut().zip(&tables) {
+                    *value = table[*value as usize];
+                }
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Equalized buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
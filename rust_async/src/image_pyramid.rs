@@ -0,0 +1,197 @@
+use crate::bands;
+use crate::blur::apply_gaussian_blur_async;
+use crate::cli::FilterOptions;
+use crate::pyramid;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task;
+use tracing::Instrument;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Blur before each halving; sigma 1 keeps the 2x2 box from aliasing
+const REDUCE_RADIUS: u32 = 3;
+// Laplacian levels are signed; zero detail is stored as mid gray
+const DETAIL_OFFSET: f32 = 128.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // Every level a blurred, halved copy of the one before
+    Gaussian,
+    // Every level the detail its Gaussian level has over the next, the last the coarsest Gaussian level
+    Laplacian,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gaussian" => Ok(Kind::Gaussian),
+            "laplacian" => Ok(Kind::Laplacian),
+            other => Err(format!("Unknown pyramid kind: {}. Use 'gaussian' or 'laplacian'", other)),
+        }
+    }
+}
+
+// How long each level took to make and to write
+pub struct LevelStats {
+    pub width: u32,
+    pub height: u32,
+    pub compute: Duration,
+    pub write: Duration,
+}
+
+// Levels until the image is down to a single pixel, the full size included
+pub fn max_levels(width: u32, height: u32) -> usize {
+    let mut side = width.max(height);
+    let mut levels = 1;
+    while side > 1 {
+        side = side.div_ceil(2);
+        levels += 1;
+    }
+    levels
+}
+
+// The next Gaussian level: blurred, then halved with odd sizes rounding up
+pub async fn reduce(img: &Frame, num_tasks: usize) -> Frame {
+    let blurred = apply_gaussian_blur_async(&DynamicImage::ImageRgba8(img.clone()), REDUCE_RADIUS, num_tasks, FilterOptions::default()).await;
+    pyramid::halve(Arc::new(blurred.to_rgba8()), num_tasks).await
+}
+
+// Bilinear sample of a level at the center of pixel (x, y) of the level twice its size
+pub fn expand_at(sample: impl Fn(u32, u32) -> f32, width: u32, height: u32, x: u32, y: u32) -> f32 {
+    let position = |p: u32, size: u32| ((p as f32 + 0.5) / 2.0 - 0.5).clamp(0.0, (size - 1) as f32);
+    let (sx, sy) = (position(x, width), position(y, height));
+    let (x0, y0) = (sx.floor() as u32, sy.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+    let top = sample(x0, y0) * (1.0 - fx) + sample(x1, y0) * fx;
+    let bottom = sample(x0, y1) * (1.0 - fx) + sample(x1, y1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+// Detail of a Gaussian level over the next coarser one expanded back to its size, offset to mid
+// gray and clamped to 8 bits; alpha is the level's own
+pub async fn laplacian(level: Arc<Frame>, coarser: Arc<Frame>, num_tasks: usize) -> Frame {
+    let (width, height) = level.dimensions();
+    let row_len = width as usize * 4;
+    let mut tasks = Vec::new();
+    for rows in bands::split(height as usize, num_tasks) {
+        let (level, coarser) = (Arc::clone(&level), Arc::clone(&coarser));
+        tasks.push(task::spawn(async move {
+            let (coarse_width, coarse_height) = coarser.dimensions();
+            let mut band = level.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
+                let (x, y) = ((i % width as usize) as u32, (rows.start + i / width as usize) as u32);
+                for (ch, value) in pixel.iter_mut().take(3).enumerate() {
+                    let expanded = expand_at(|sx, sy| coarser.get_pixel(sx, sy)[ch] as f32, coarse_width, coarse_height, x, y);
+                    *value = (*value as f32 - expanded + DETAIL_OFFSET).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+            band
+        }));
+    }
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    ImageBuffer::from_raw(width, height, data).expect("Laplacian buffer matches dimensions")
+}
+
+// The Gaussian levels, the full size first
+pub async fn gaussian_pyramid(img: Frame, levels: usize, num_tasks: usize) -> Vec<Frame> {
+    let mut pyramid = vec![img];
+    for _ in 1..levels {
+        let next = reduce(pyramid.last().expect("The pyramid starts with the image"), num_tasks).await;
+        pyramid.push(next);
+    }
+    pyramid
+}
+
+// A level of the pyramid as it is written, given its Gaussian level and the next coarser one
+pub async fn level(kind: Kind, gaussian: &Arc<Frame>, coarser: Option<&Arc<Frame>>, num_tasks: usize) -> Frame {
+    match (kind, coarser) {
+        (Kind::Laplacian, Some(coarser)) => laplacian(Arc::clone(gaussian), Arc::clone(coarser), num_tasks).await,
+        _ => gaussian.as_ref().clone(),
+    }
+}
+
+// `<output>/level_<n>.png`, level 0 the full size
+pub fn level_path(output: &Path, level: usize) -> PathBuf {
+    output.join(format!("level_{}.png", level))
+}
+
+async fn write_level(kind: Kind, output: &Path, index: usize, gaussian: &Arc<Frame>, coarser: Option<&Arc<Frame>>, reduced: Duration, num_tasks: usize) -> Result<LevelStats, String> {
+    let start = Instant::now();
+    let frame = level(kind, gaussian, coarser, num_tasks).instrument(tracing::info_span!("write_level", level = index)).await;
+    let compute = reduced + start.elapsed();
+    let start = Instant::now();
+    let (width, height) = frame.dimensions();
+    let path = level_path(output, index);
+    task::spawn_blocking(move || frame.save(path)).await.expect("Encoder panicked").map_err(|e| e.to_string())?;
+    Ok(LevelStats { width, height, compute, write: start.elapsed() })
+}
+
+// Writes `levels` levels of the pyramid to `output`. Each level is blurred and halved in bands
+// across the tasks, and needs the level before it, so the levels themselves come one after
+// another. With `pipelined` a writer task takes each Gaussian level over a channel holding one,
+// computes its Laplacian once the next arrives and encodes it while the next level is being
+// reduced; without, every level is made first and then written.
+pub async fn write(img: &Frame, kind: Kind, levels: usize, output: &str, pipelined: bool, num_tasks: usize) -> Result<Vec<LevelStats>, Box<dyn Error>> {
+    let output = PathBuf::from(output);
+    fs::create_dir_all(&output)?;
+
+    if !pipelined {
+        let mut reduced = vec![Duration::ZERO];
+        let mut pyramid = vec![Arc::new(img.clone())];
+        for index in 1..levels {
+            let start = Instant::now();
+            let next = reduce(&pyramid[index - 1], num_tasks).instrument(tracing::info_span!("reduce", level = index)).await;
+            reduced.push(start.elapsed());
+            pyramid.push(Arc::new(next));
+        }
+        let mut stats = Vec::with_capacity(levels);
+        for index in 0..levels {
+            stats.push(write_level(kind, &output, index, &pyramid[index], pyramid.get(index + 1), reduced[index], num_tasks).await?);
+        }
+        return Ok(stats);
+    }
+
+    let (sender, mut receiver) = mpsc::channel::<(Arc<Frame>, Duration)>(1);
+    let writer = task::spawn(async move {
+        let mut stats = Vec::with_capacity(levels);
+        // Each level waits for the next, which its Laplacian is taken against
+        let mut previous: Option<(Arc<Frame>, Duration)> = None;
+        while let Some((next, reduced)) = receiver.recv().await {
+            if let Some((gaussian, compute)) = previous.take() {
+                stats.push(write_level(kind, &output, stats.len(), &gaussian, Some(&next), compute, num_tasks).await?);
+            }
+            previous = Some((next, reduced));
+        }
+        if let Some((gaussian, compute)) = previous {
+            stats.push(write_level(kind, &output, stats.len(), &gaussian, None, compute, num_tasks).await?);
+        }
+        Ok::<_, String>(stats)
+    });
+
+    let mut current = Arc::new(img.clone());
+    // A failed writer drops the receiver, which stops the reducing too
+    if sender.send((Arc::clone(&current), Duration::ZERO)).await.is_ok() {
+        for index in 1..levels {
+            let start = Instant::now();
+            current = Arc::new(reduce(&current, num_tasks).instrument(tracing::info_span!("reduce", level = index)).await);
+            if sender.send((Arc::clone(&current), start.elapsed())).await.is_err() {
+                break;
+            }
+        }
+    }
+    drop(sender);
+    Ok(writer.await.expect("Writer task panicked")?)
+}
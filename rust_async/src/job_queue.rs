@@ -0,0 +1,262 @@
+// Batch worker consuming filter jobs from a Redis stream through a consumer group, so any number
+// of workers can share one queue. A job is an entry in `filter:jobs` with the fields
+// input, output, operation, radius and optionally tasks; input and output are paths or http(s) URLs.
+
+use crate::remote;
+use crate::serve;
+use image::GenericImageView;
+use redis::aio::MultiplexedConnection;
+use redis::streams::{StreamAutoClaimOptions, StreamAutoClaimReply, StreamClaimOptions, StreamId, StreamMaxlen, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, RedisResult};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::{self, JoinSet};
+
+pub const JOBS: &str = "filter:jobs";
+// One entry per job done, retried or given up on
+pub const EVENTS: &str = "filter:events";
+// Jobs that failed on every attempt, with their last error
+pub const DEAD_LETTERS: &str = "filter:dead";
+const GROUP: &str = "filter-workers";
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+// Older events are trimmed so an unread event stream does not grow without bound
+const EVENTS_KEPT: usize = 10_000;
+// How long a read waits for new jobs before checking for abandoned ones again
+const BLOCK: Duration = Duration::from_secs(5);
+// Jobs another worker has held this long without acking are taken over, on the assumption it died.
+// A live worker claims its running jobs again every `CLAIM_REFRESH`, so only a dead one's jobs idle
+// this long. Delivery is still at least once: a worker stalled past it, or one that dies between
+// writing the output and acking, has its job run a second time.
+const CLAIM_IDLE: Duration = Duration::from_secs(300);
+const CLAIM_REFRESH: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+struct Job {
+    input: String,
+    output: String,
+    operation: String,
+    radius: i32,
+    tasks: usize,
+    // Counts from 1, carried along when a failed job is queued again
+    attempt: u32,
+}
+
+impl Job {
+    fn parse(entry: &StreamId) -> Result<Job, String> {
+        let field = |name: &str| entry.get::<String>(name).ok_or_else(|| format!("missing field '{}'", name));
+        let number = |name: &str, default: u32| match entry.get::<String>(name) {
+            Some(value) => value.parse::<u32>().map_err(|_| format!("invalid {}: {}", name, value)),
+            None => Ok(default),
+        };
+
+        // Parsed as the i32 the filters take, so a radius past it is refused instead of wrapping negative
+        let radius = field("radius")?;
+        let job = Job {
+            input: field("input")?,
+            output: field("output")?,
+            operation: field("operation")?,
+            radius: radius.parse().map_err(|_| format!("invalid radius: {}", radius))?,
+            tasks: number("tasks", serve::default_tasks() as u32)? as usize,
+            attempt: number("attempt", 1)?,
+        };
+        // A refused job fails the same way on every worker, so `settle` dead-letters it at once
+        serve::check_request(&job.operation, job.radius, job.tasks)?;
+        Ok(job)
+    }
+
+    fn fields(&self) -> Vec<(String, String)> {
+        [
+            ("input", self.input.clone()),
+            ("output", self.output.clone()),
+            ("operation", self.operation.clone()),
+            ("radius", self.radius.to_string()),
+            ("tasks", self.tasks.to_string()),
+            ("attempt", self.attempt.to_string()),
+        ]
+        .map(|(key, value)| (key.to_string(), value))
+        .to_vec()
+    }
+}
+
+struct Done {
+    width: u32,
+    height: u32,
+    filter_time: Duration,
+}
+
+async fn process(job: &Job) -> Result<Done, Box<dyn Error + Send + Sync>> {
+    let img = if remote::is_url(&job.input) {
+        let data = remote::download(&job.input, job.tasks).await?;
+        image::load_from_memory(&data)?
+    } else {
+        let input = job.input.clone();
+        task::spawn_blocking(move || image::open(input)).await??
+    };
+
+    let start = Instant::now();
+    let result = serve::apply(&job.operation, &img, job.radius, job.tasks).await;
+    let filter_time = start.elapsed();
+    let (width, height) = result.dimensions();
+
+    if remote::is_url(&job.output) {
+        let encoded = remote::encode_for_url(&result, &job.output)?;
+        remote::upload(&job.output, encoded).await?;
+    } else {
+        let output = job.output.clone();
+        task::spawn_blocking(move || result.save(output)).await??;
+    }
+    Ok(Done { width, height, filter_time })
+}
+
+async fn emit(conn: &mut MultiplexedConnection, fields: &[(&str, String)]) -> RedisResult<()> {
+    conn.xadd_maxlen(EVENTS, StreamMaxlen::Approx(EVENTS_KEPT), "*", fields).await
+}
+
+// Claims `id` again every `CLAIM_REFRESH` until aborted, which resets its idle time without counting
+// another delivery, so a long job is not taken over by another worker while this one runs it
+async fn hold_claim(mut conn: MultiplexedConnection, consumer: String, id: String) {
+    let mut refresh = tokio::time::interval(CLAIM_REFRESH);
+    // The first tick is immediate, and the entry was just claimed
+    refresh.tick().await;
+    loop {
+        refresh.tick().await;
+        let options = StreamClaimOptions::default().with_justid();
+        let claimed: RedisResult<Vec<String>> = conn.xclaim_options(JOBS, GROUP, &consumer, 0, &[&id], options).await;
+        if let Err(e) = claimed {
+            eprintln!("Warning: failed to refresh the claim on job {}: {}", id, e);
+        }
+    }
+}
+
+// Runs one job and settles it: acked when done, queued again while attempts remain, dead-lettered after that.
+// Follow-up entries are added before the ack, so a crash in between repeats a job rather than losing it.
+async fn settle(mut conn: MultiplexedConnection, consumer: String, entry: StreamId, max_attempts: u32) -> RedisResult<()> {
+    let outcome = match Job::parse(&entry) {
+        Ok(job) => {
+            let running = job.clone();
+            let holding = task::spawn(hold_claim(conn.clone(), consumer, entry.id.clone()));
+            // A panicking filter fails the job instead of the worker
+            let result = task::spawn(async move { process(&running).await.map_err(|e| e.to_string()) }).await;
+            holding.abort();
+            match result {
+                Ok(Ok(done)) => Ok((job, done)),
+                Ok(Err(error)) => Err((Some(job), error)),
+                Err(_) => Err((Some(job), "filter panicked".to_string())),
+            }
+        }
+        Err(error) => Err((None, error)),
+    };
+    let mut event = vec![("job", entry.id.clone())];
+
+    match outcome {
+        Ok((job, done)) => {
+            println!("Job {}: {} radius {} on {}x{}: {}ms", entry.id, job.operation, job.radius, done.width, done.height, done.filter_time.as_millis());
+            event.extend([
+                ("status", "done".to_string()),
+                ("output", job.output),
+                ("width", done.width.to_string()),
+                ("height", done.height.to_string()),
+                ("filter_ms", done.filter_time.as_millis().to_string()),
+            ]);
+        }
+        Err((Some(mut job), error)) if job.attempt < max_attempts => {
+            println!("Job {} failed on attempt {} of {}: {}", entry.id, job.attempt, max_attempts, error);
+            job.attempt += 1;
+            let retry: String = conn.xadd(JOBS, "*", &job.fields()).await?;
+            event.extend([("status", "retry".to_string()), ("error", error), ("retry", retry)]);
+        }
+        Err((job, error)) => {
+            println!("Job {} given up: {}", entry.id, error);
+            let mut fields = match job {
+                Some(job) => job.fields(),
+                // Unparseable jobs are dead-lettered as they came, there is nothing to retry
+                None => entry.map.keys().filter_map(|key| Some((key.clone(), entry.get::<String>(key)?))).collect(),
+            };
+            fields.push(("error".to_string(), error.clone()));
+            let _: String = conn.xadd(DEAD_LETTERS, "*", &fields).await?;
+            event.extend([("status", "failed".to_string()), ("error", error)]);
+        }
+    }
+
+    let _: usize = conn.xack(JOBS, GROUP, &[&entry.id]).await?;
+    emit(&mut conn, &event).await
+}
+
+// Jobs abandoned by a dead worker come first, then new ones. None when nothing arrived in time.
+async fn next_entry(conn: &mut MultiplexedConnection, consumer: &str) -> RedisResult<Option<StreamId>> {
+    let options = StreamAutoClaimOptions::default().count(1);
+    let claimed: StreamAutoClaimReply = conn.xautoclaim_options(JOBS, GROUP, consumer, CLAIM_IDLE.as_millis() as usize, "0-0", options).await?;
+    if let Some(entry) = claimed.claimed.into_iter().next() {
+        return Ok(Some(entry));
+    }
+
+    let options = StreamReadOptions::default().group(GROUP, consumer).count(1).block(BLOCK.as_millis() as usize);
+    let reply: Option<StreamReadReply> = conn.xread_options(&[JOBS], &[">"], &options).await?;
+    Ok(reply.and_then(|reply| reply.keys.into_iter().flat_map(|key| key.ids).next()))
+}
+
+fn report(result: Result<RedisResult<()>, task::JoinError>) {
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("Warning: failed to settle a job, it will be retried once its claim expires: {}", e),
+        Err(e) => eprintln!("Warning: job task failed: {}", e),
+    }
+}
+
+// Consumes jobs with up to `concurrency` running at once until interrupted with Ctrl-C,
+// then finishes the running ones
+pub async fn run(url: &str, concurrency: usize, max_attempts: u32) -> Result<(), Box<dyn Error>> {
+    if !url.starts_with("redis://") && !url.starts_with("rediss://") {
+        return Err(format!("Unsupported queue: {}. Only redis:// and rediss:// URLs are supported", url).into());
+    }
+    if concurrency == 0 || max_attempts == 0 {
+        return Err("--max-concurrent and --max-attempts must be positive".into());
+    }
+
+    let client = redis::Client::open(url)?;
+    // Blocking reads get a connection of their own so acks and events are not stuck behind them
+    let mut reader = client.get_multiplexed_async_connection().await?;
+    let conn = client.get_multiplexed_async_connection().await?;
+    let created: RedisResult<()> = reader.xgroup_create_mkstream(JOBS, GROUP, "0").await;
+    match created {
+        Err(e) if e.code() != Some("BUSYGROUP") => return Err(e.into()),
+        _ => {}
+    }
+
+    let consumer = format!("{}-{}", std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string()), std::process::id());
+    println!("Consuming {} as {} with {} jobs at once", JOBS, consumer, concurrency);
+
+    let slots = Arc::new(Semaphore::new(concurrency));
+    let mut running = JoinSet::new();
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+    loop {
+        // A slot is taken before reading, so jobs wait in Redis for whichever worker has room
+        let slot = tokio::select! {
+            slot = Arc::clone(&slots).acquire_owned() => slot.expect("Semaphore is never closed"),
+            _ = &mut shutdown => break,
+        };
+        let entry = tokio::select! {
+            entry = next_entry(&mut reader, &consumer) => entry?,
+            _ = &mut shutdown => break,
+        };
+        if let Some(entry) = entry {
+            let (conn, consumer) = (conn.clone(), consumer.clone());
+            running.spawn(async move {
+                let _slot = slot;
+                settle(conn, consumer, entry, max_attempts).await
+            });
+        }
+        while let Some(result) = running.try_join_next() {
+            report(result);
+        }
+    }
+
+    println!("Stopping after {} running jobs", running.len());
+    while let Some(result) = running.join_next().await {
+        report(result);
+    }
+    Ok(())
+}
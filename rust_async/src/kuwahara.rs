@@ -1,147 +1,545 @@
+use crate::bands;
+use crate::blur;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::colorspace;
+use crate::integral::IntegralImage;
+use crate::progress;
+use crate::sobel;
+use crate::task_latency;
+use crate::timing::WorkerClock;
 use image::{DynamicImage, ImageBuffer, Rgba};
+use std::ops::Range;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tokio::task;
-use std::time::Instant;
+use tracing::Instrument;
 
-pub struct IntegralImage {
-    sum: Vec<f32>,
-    sum_sq: Vec<f32>,
-    width: usize,
-    height: usize,
+// Converts the color channels into the filter's color space, one band of rows per task
+pub async fn convert_to_space(src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>, filter: FilterOptions, num_tasks: usize) -> Vec<f32> {
+    convert::<3>(src, filter, num_tasks).await
+}
+
+// The converted color channels of every pixel followed by its alpha, as `--filter-alpha` reads them
+pub async fn convert_with_alpha(src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>, filter: FilterOptions, num_tasks: usize) -> Vec<f32> {
+    convert::<4>(src, filter, num_tasks).await
+}
+
+async fn convert<const C: usize>(src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>, filter: FilterOptions, num_tasks: usize) -> Vec<f32> {
+    let (width, height) = src.dimensions();
+    let mut tasks = Vec::new();
+
+    for (band, rows) in bands::split(height as usize, num_tasks).into_iter().enumerate() {
+        let src = Arc::clone(&src);
+
+        let span = tracing::debug_span!("task", id = band, rows = ?rows);
+        tasks.push(task_latency::spawn(async move {
+            let pixels = &src.as_raw()[rows.start * width as usize * 4..rows.end * width as usize * 4];
+            let clock = WorkerClock::start("convert", band, rows);
+            let values = pixels
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    let converted = colorspace::to_space([pixel[0], pixel[1], pixel[2]], filter.colorspace, filter.linear);
+                    converted.into_iter().chain((C == 4).then_some(pixel[3] as f32))
+                })
+                .collect::<Vec<f32>>();
+            clock.finish();
+            values
+        }
+        .instrument(span)));
+    }
+
+    let mut values = Vec::with_capacity(width as usize * height as usize * C);
+    for task in tasks {
+        values.extend_from_slice(&task.await.unwrap());
+    }
+    values
+}
+
+// Radii of the multi-scale mode: `radius`, then halved for each further scale while above 0
+pub fn scale_radii(radius: i32, scales: u32) -> impl Iterator<Item = i32> {
+    (0..scales.max(1)).map_while(move |scale| radius.checked_shr(scale).filter(|&radius| radius > 0))
+}
+
+// Each scale's mean weighted by how flat its quadrant is, so the fine radii win where there is
+// detail and the coarse ones smooth the flat areas around it
+pub fn blend_scales<const C: usize>(scales: impl Iterator<Item = ([f64; C], f64)>) -> Option<[f64; C]> {
+    let mut sum = [0.0; C];
+    let mut total = 0.0;
+    for (mean, variance) in scales {
+        let weight = 1.0 / (1.0 + variance);
+        for (sum, mean) in sum.iter_mut().zip(mean) {
+            *sum += weight * mean;
+        }
+        total += weight;
+    }
+    (total > 0.0).then(|| sum.map(|sum| sum / total))
+}
+
+// Mean and total variance of the quadrant with the least variance, None when all are transparent.
+// With alpha among the channels its variance counts too, so a quadrant across a soft edge loses.
+fn best_quadrant<const C: usize>(integral: &IntegralImage<C>, x: i32, y: i32, radius: i32) -> Option<([f64; C], f64)> {
+    let mut min_variance = f64::MAX;
+    let mut best_mean = None;
+
+    let quadrants = [
+        [x - radius, y - radius, x, y],
+        [x, y - radius, x + radius, y],
+        [x - radius, y, x, y + radius],
+        [x, y, x + radius, y + radius],
+    ];
+
+    for quad in &quadrants {
+        let Some((mean, variance)) = integral.get_region_stats(quad[0], quad[1], quad[2], quad[3]) else {
+            continue;
+        };
+        let total_variance: f64 = variance.iter().sum();
+
+        if total_variance < min_variance {
+            min_variance = total_variance;
+            best_mean = Some(mean);
+        }
+    }
+
+    best_mean.map(|mean| (mean, min_variance))
+}
+
+// Classic is the four square quadrants; adaptive shrinks them from the radius toward
+// `--min-radius` as the edges get stronger; anisotropic weights eight sectors of an ellipse that
+// follows the local orientation, which keeps strokes along edges instead of leaving blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KuwaharaMode {
+    Classic,
+    Adaptive,
+    Anisotropic,
+}
+
+impl FromStr for KuwaharaMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(KuwaharaMode::Classic),
+            "adaptive" => Ok(KuwaharaMode::Adaptive),
+            "anisotropic" => Ok(KuwaharaMode::Anisotropic),
+            other => Err(format!("Unknown Kuwahara mode: {}. Use 'classic', 'adaptive' or 'anisotropic'", other)),
+        }
+    }
+}
+
+// Whether the filter reads square quadrants from the summed-area table, the only path that can
+// filter alpha along with the colors
+pub fn uses_quadrants(filter: FilterOptions) -> bool {
+    match filter.kuwahara_mode {
+        KuwaharaMode::Classic => filter.sectors.is_none(),
+        KuwaharaMode::Adaptive => true,
+        KuwaharaMode::Anisotropic => false,
+    }
+}
+
+// The adaptive radius at a pixel whose Sobel magnitude is `edge`: the full radius where the image
+// is flat, falling linearly to `min_radius` where the edge saturates
+pub fn adaptive_radius(edge: u8, min_radius: i32, radius: i32) -> i32 {
+    let min_radius = min_radius.min(radius);
+    radius - ((radius - min_radius) as f64 * edge as f64 / 255.0).round() as i32
+}
+
+// Radius of the Gaussian that smooths the structure tensor, sigma 2 as for the blur's kernel
+pub const TENSOR_RADIUS: usize = 6;
+// How far the ellipse may stretch: a pixel on a clean edge gets axes of twice and half the radius
+const ECCENTRICITY: f64 = 1.0;
+// Angle at which a sector's polynomial weight falls to zero, a little past its half width of pi/8
+const ZERO_CROSSING: f64 = 0.58;
+// How fast a sector's weight falls with its summed variance in 8-bit levels, raised to the 4th power
+const HARDNESS: f64 = 8000.0 / (255.0 * 255.0);
+
+// Products of the Sobel gradients summed over R, G and B: [gx*gx, gx*gy, gy*gy]. `rgb` gives
+// the color at an offset from the pixel.
+pub fn tensor_at(rgb: impl Fn(i32, i32) -> [f32; 3]) -> [f32; 3] {
+    let (tl, t, tr) = (rgb(-1, -1), rgb(0, -1), rgb(1, -1));
+    let (l, r) = (rgb(-1, 0), rgb(1, 0));
+    let (bl, b, br) = (rgb(-1, 1), rgb(0, 1), rgb(1, 1));
+    let mut tensor = [0.0; 3];
+    for ch in 0..3 {
+        let gx = tr[ch] + 2.0 * r[ch] + br[ch] - tl[ch] - 2.0 * l[ch] - bl[ch];
+        let gy = bl[ch] + 2.0 * b[ch] + br[ch] - tl[ch] - 2.0 * t[ch] - tr[ch];
+        tensor[0] += gx * gx;
+        tensor[1] += gx * gy;
+        tensor[2] += gy * gy;
+    }
+    tensor
+}
+
+pub fn tensor_kernel() -> Vec<f32> {
+    blur::generate_gaussian_kernel(TENSOR_RADIUS).iter().map(|&weight| weight as f32).collect()
+}
+
+// One pass of the tensor smoothing, `tensor` giving the tensor at an offset along the pass
+pub fn smooth_tensor(kernel: &[f32], tensor: impl Fn(i32) -> [f32; 3]) -> [f32; 3] {
+    let mut sum = [0.0; 3];
+    for (k, weight) in (-(TENSOR_RADIUS as i32)..).zip(kernel) {
+        let tensor = tensor(k);
+        for (sum, value) in sum.iter_mut().zip(tensor) {
+            *sum += value * weight;
+        }
+    }
+    sum
+}
+
+// Angle of the edge through the pixel and how strongly oriented the neighborhood is, from 0 where
+// it is flat or isotropic to 1 on a clean edge
+pub fn orientation(tensor: [f32; 3]) -> (f64, f64) {
+    let [e, f, g] = tensor.map(|value| value as f64);
+    let root = ((e - g) * (e - g) + 4.0 * f * f).sqrt();
+    let (major, minor) = (0.5 * (e + g + root), 0.5 * (e + g - root));
+    // Along the edge, across the gradient; a purely horizontal gradient leaves it zero
+    let (tx, ty) = (major - e, -f);
+    let length = (tx * tx + ty * ty).sqrt();
+    let angle = if length > 0.0 { ty.atan2(tx) } else { std::f64::consts::FRAC_PI_2 };
+    let anisotropy = if major + minor > 0.0 { (major - minor) / (major + minor) } else { 0.0 };
+    (angle, anisotropy)
+}
+
+// Weights of the 8 sectors at `v`, an offset in the ellipse's frame scaled into a disc of radius
+// 1/2. Polynomials stand in for the sectors convolved with a Gaussian, and the radial Gaussian
+// fades the taps toward the rim.
+fn sector_weights(v: (f64, f64), zeta: f64, eta: f64) -> Option<[f64; 8]> {
+    let mut weights = [0.0; 8];
+    let diagonal = std::f64::consts::FRAC_1_SQRT_2;
+    for (offset, (x, y)) in [(0, v), (1, (diagonal * (v.0 - v.1), diagonal * (v.0 + v.1)))] {
+        let (xx, yy) = (zeta - eta * x * x, zeta - eta * y * y);
+        for (k, z) in [y + xx, -x + yy, -y + xx, x + yy].into_iter().enumerate() {
+            weights[2 * k + offset] = z.max(0.0) * z.max(0.0);
+        }
+    }
+    let sum: f64 = weights.iter().sum();
+    if sum <= 0.0 {
+        return None;
+    }
+    let radial = (-3.125 * (v.0 * v.0 + v.1 * v.1)).exp() / sum;
+    Some(weights.map(|weight| weight * radial))
+}
+
+// Generalized Kuwahara mean over the ellipse at `orientation`. `sample` gives the converted color
+// and weight of the pixel at an offset, None past the image. Each sector's mean counts by how
+// flat the sector is, so sectors straddling an edge drop out. None when no tap has any weight.
+pub fn sector_mean(radius: i32, orientation: (f64, f64), sample: impl Fn(i32, i32) -> Option<([f32; 3], f64)>) -> Option<[f64; 3]> {
+    let (angle, anisotropy) = orientation;
+    let radius = radius as f64;
+    let a = radius * ((ECCENTRICITY + anisotropy) / ECCENTRICITY).clamp(0.1, 2.0);
+    let b = radius * (ECCENTRICITY / (ECCENTRICITY + anisotropy)).clamp(0.1, 2.0);
+    let (sin, cos) = angle.sin_cos();
+    let max_x = (a * a * cos * cos + b * b * sin * sin).sqrt() as i32;
+    let max_y = (a * a * sin * sin + b * b * cos * cos).sqrt() as i32;
+    let zeta = 2.0 / radius;
+    let eta = (zeta + ZERO_CROSSING.cos()) / (ZERO_CROSSING.sin() * ZERO_CROSSING.sin());
+
+    let mut sums = [[0.0f64; 3]; 8];
+    let mut squares = [[0.0f64; 3]; 8];
+    let mut totals = [0.0f64; 8];
+    for dy in -max_y..=max_y {
+        for dx in -max_x..=max_x {
+            let (dx_f, dy_f) = (dx as f64, dy as f64);
+            let v = (0.5 / a * (cos * dx_f + sin * dy_f), 0.5 / b * (cos * dy_f - sin * dx_f));
+            if v.0 * v.0 + v.1 * v.1 > 0.25 {
+                continue;
+            }
+            let Some((color, weight)) = sample(dx, dy) else {
+                continue;
+            };
+            let Some(sectors) = sector_weights(v, zeta, eta) else {
+                continue;
+            };
+            for (k, sector) in sectors.iter().enumerate() {
+                let weight = sector * weight;
+                for ch in 0..3 {
+                    let value = color[ch] as f64;
+                    sums[k][ch] += weight * value;
+                    squares[k][ch] += weight * value * value;
+                }
+                totals[k] += weight;
+            }
+        }
+    }
+
+    blend_sectors(&sums, &squares, &totals)
 }
 
-impl IntegralImage {
-    fn new(width: usize, height: usize) -> Self {
-        let size = (width + 1) * (height + 1) * 3;
-        IntegralImage {
-            sum: vec![0.0; size],
-            sum_sq: vec![0.0; size],
-            width,
-            height,
+// Each sector's mean counted by how flat the sector is, from its weighted sums, sums of squares
+// and total weight. None when no sector has any weight.
+fn blend_sectors(sums: &[[f64; 3]], squares: &[[f64; 3]], totals: &[f64]) -> Option<[f64; 3]> {
+    let mut blended = [0.0; 3];
+    let mut total = 0.0;
+    for k in 0..totals.len() {
+        if totals[k] <= 0.0 {
+            continue;
         }
+        let mean = sums[k].map(|sum| sum / totals[k]);
+        let variance: f64 = (0..3).map(|ch| (squares[k][ch] / totals[k] - mean[ch] * mean[ch]).abs()).sum();
+        let weight = 1.0 / (1.0 + (HARDNESS * variance).powi(4));
+        for (blended, mean) in blended.iter_mut().zip(mean) {
+            *blended += weight * mean;
+        }
+        total += weight;
     }
+    (total > 0.0).then(|| blended.map(|sum| sum / total))
+}
 
-    fn build(&mut self, img: &ImageBuffer<Rgba<u8>, Vec<u8>>) {
-        let w = self.width;
-        let h = self.height;
-        let iw = self.width + 1;
+// The disc of `--sectors`: every tap within the radius with its weight in each of the N sectors.
+// A Gaussian across the disc fades the taps toward the rim, and a Gaussian of the angle from each
+// sector's middle stands in for the sector smoothed, so neighbouring sectors overlap and no
+// straight boundary is left for the output to copy.
+pub struct SectorKernel {
+    sectors: usize,
+    taps: Vec<(i32, i32)>,
+    // One weight per sector for every tap
+    weights: Vec<f64>,
+}
 
-        for y in 1..=h {
-            for x in 1..=w {
-                let pixel = img.get_pixel((x - 1) as u32, (y - 1) as u32);
-                let channels = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+impl SectorKernel {
+    pub fn new(radius: i32, sectors: u32) -> Self {
+        let sectors = sectors as usize;
+        let radius_sq = (radius * radius) as f64;
+        let spread = std::f64::consts::PI / sectors as f64;
+        let mut taps = Vec::new();
+        let mut weights = Vec::new();
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let distance_sq = (dx * dx + dy * dy) as f64;
+                if distance_sq > radius_sq {
+                    continue;
+                }
+                // Sigma of half the radius
+                let radial = (-2.0 * distance_sq / radius_sq).exp();
+                let angle = (dy as f64).atan2(dx as f64);
+                taps.push((dx, dy));
+                weights.extend((0..sectors).map(|k| {
+                    // The pixel itself lies in every sector
+                    if (dx, dy) == (0, 0) {
+                        return radial;
+                    }
+                    let offset = (angle - 2.0 * spread * k as f64).rem_euclid(std::f64::consts::TAU);
+                    let offset = offset.min(std::f64::consts::TAU - offset);
+                    radial * (-0.5 * offset * offset / (spread * spread)).exp()
+                }));
+            }
+        }
+        SectorKernel { sectors, taps, weights }
+    }
 
+    // Generalized Kuwahara mean over the disc, `sample` as for `sector_mean`
+    pub fn mean(&self, sample: impl Fn(i32, i32) -> Option<([f32; 3], f64)>) -> Option<[f64; 3]> {
+        let mut sums = vec![[0.0f64; 3]; self.sectors];
+        let mut squares = vec![[0.0f64; 3]; self.sectors];
+        let mut totals = vec![0.0f64; self.sectors];
+        for (&(dx, dy), sectors) in self.taps.iter().zip(self.weights.chunks_exact(self.sectors)) {
+            let Some((color, weight)) = sample(dx, dy) else {
+                continue;
+            };
+            for (k, sector) in sectors.iter().enumerate() {
+                let weight = sector * weight;
                 for ch in 0..3 {
-                    let val = channels[ch];
-                    let idx = (y * iw + x) * 3 + ch;
-                    let idx_up = ((y - 1) * iw + x) * 3 + ch;
-                    let idx_left = (y * iw + (x - 1)) * 3 + ch;
-                    let idx_diag = ((y - 1) * iw + (x - 1)) * 3 + ch;
-
-                    self.sum[idx] = val + self.sum[idx_up] + self.sum[idx_left] - self.sum[idx_diag];
-                    self.sum_sq[idx] = val * val
-                        + self.sum_sq[idx_up]
-                        + self.sum_sq[idx_left]
-                        - self.sum_sq[idx_diag];
+                    let value = color[ch] as f64;
+                    sums[k][ch] += weight * value;
+                    squares[k][ch] += weight * value * value;
                 }
+                totals[k] += weight;
             }
         }
+        blend_sectors(&sums, &squares, &totals)
     }
+}
 
-    fn get_region_stats(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> ([f32; 3], [f32; 3]) {
-        let iw = self.width + 1;
+// What the sector modes read around each pixel in place of the summed-area table: the converted
+// color and the weight of every pixel
+pub struct Samples {
+    values: Vec<f32>,
+    alpha: Option<Vec<u8>>,
+    width: usize,
+    height: usize,
+}
 
-        let x1 = x1.max(0) as usize;
-        let y1 = y1.max(0) as usize;
-        let x2 = x2.min(self.width as i32 - 1) as usize;
-        let y2 = y2.min(self.height as i32 - 1) as usize;
+impl Samples {
+    // The pixel at (x, y), None past the image
+    fn at(&self, x: i32, y: i32) -> Option<([f32; 3], f64)> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        let index = y as usize * self.width + x as usize;
+        let color = self.values[index * 3..][..3].try_into().unwrap();
+        Some((color, self.alpha.as_ref().map_or(1.0, |alpha| alpha[index] as f64)))
+    }
+}
 
-        let x1 = x1 + 1;
-        let y1 = y1 + 1;
-        let x2 = x2 + 1;
-        let y2 = y2 + 1;
+// The summed-area table of the quadrants, with alpha as a fourth channel under `--filter-alpha`,
+// the samples and kernel of the circular sectors, or the samples and tensor of the ellipse's
+enum Neighborhood {
+    Quadrants(IntegralImage),
+    QuadrantsWithAlpha(IntegralImage<4>),
+    Circle(Samples, SectorKernel),
+    Ellipse(Samples, Vec<[f32; 3]>),
+}
 
-        let area = ((x2 - x1 + 1) * (y2 - y1 + 1)) as f32;
-        let mut mean = [0.0; 3];
-        let mut variance = [0.0; 3];
+// Structure tensor of every pixel smoothed by a Gaussian, the horizontal and then the vertical
+// pass each over one band of rows per task
+pub async fn structure_tensor(src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>, num_tasks: usize) -> Vec<[f32; 3]> {
+    let (width, height) = (src.width() as usize, src.height() as usize);
+    let kernel = Arc::new(tensor_kernel());
+    let mut tasks = Vec::new();
 
-        if area > 0.0 {
-            for ch in 0..3 {
-                let idx_br = (y2 * iw + x2) * 3 + ch;
-                let idx_bl = (y2 * iw + x1 - 1) * 3 + ch;
-                let idx_tr = ((y1 - 1) * iw + x2) * 3 + ch;
-                let idx_tl = ((y1 - 1) * iw + x1 - 1) * 3 + ch;
+    for rows in bands::split(height, num_tasks) {
+        let src = Arc::clone(&src);
+        let kernel = Arc::clone(&kernel);
+        tasks.push(task_latency::spawn(async move {
+            let rgb = |x: i32, y: i32| {
+                let pixel = src.get_pixel(x.clamp(0, width as i32 - 1) as u32, y.clamp(0, height as i32 - 1) as u32);
+                [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]
+            };
+            let mut band = Vec::with_capacity(rows.len() * width);
+            for y in rows {
+                let tensors: Vec<[f32; 3]> = (0..width as i32).map(|x| tensor_at(|dx, dy| rgb(x + dx, y as i32 + dy))).collect();
+                band.extend((0..width).map(|x| smooth_tensor(&kernel, |k| tensors[(x as i32 + k).clamp(0, width as i32 - 1) as usize])));
+            }
+            band
+        }));
+    }
+    let mut horizontal = Vec::with_capacity(width * height);
+    for task in tasks {
+        horizontal.extend_from_slice(&task.await.unwrap());
+    }
+
+    let horizontal = Arc::new(horizontal);
+    let mut tasks = Vec::new();
+    for rows in bands::split(height, num_tasks) {
+        let horizontal = Arc::clone(&horizontal);
+        let kernel = Arc::clone(&kernel);
+        tasks.push(task_latency::spawn(async move {
+            let mut band = Vec::with_capacity(rows.len() * width);
+            for y in rows {
+                band.extend((0..width).map(|x| smooth_tensor(&kernel, |k| horizontal[(y as i32 + k).clamp(0, height as i32 - 1) as usize * width + x])));
+            }
+            band
+        }));
+    }
+    let mut smoothed = Vec::with_capacity(width * height);
+    for task in tasks {
+        smoothed.extend_from_slice(&task.await.unwrap());
+    }
+    smoothed
+}
 
-                let sum = self.sum[idx_br] - self.sum[idx_bl] - self.sum[idx_tr] + self.sum[idx_tl];
-                let sum_sq = self.sum_sq[idx_br] - self.sum_sq[idx_bl] - self.sum_sq[idx_tr]
-                    + self.sum_sq[idx_tl];
+// The adaptive mode's radius of every pixel, from the Sobel magnitude of the luma around it. One
+// band of rows per task, all finished before the filter pass reads the map.
+pub async fn radius_map(src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>, min_radius: i32, radius: i32, num_tasks: usize) -> Vec<i32> {
+    let (width, height) = src.dimensions();
+    let mut tasks = Vec::new();
 
-                mean[ch] = sum / area;
-                variance[ch] = (sum_sq / area) - (mean[ch] * mean[ch]);
-                if variance[ch] < 0.0 {
-                    variance[ch] = 0.0;
+    for rows in bands::split(height as usize, num_tasks) {
+        let src = Arc::clone(&src);
+        tasks.push(task_latency::spawn(async move {
+            let mut band = Vec::with_capacity(rows.len() * width as usize);
+            for y in rows.clone() {
+                for x in 0..width as i32 {
+                    let edge = sobel::magnitude(|dx, dy| {
+                        let sx = (x + dx).clamp(0, width as i32 - 1) as u32;
+                        let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                        channels::luma(&src.get_pixel(sx, sy).0)
+                    });
+                    band.push(adaptive_radius(edge, min_radius, radius));
                 }
             }
-        }
+            progress::advance(rows.len());
+            band
+        }));
+    }
 
-        (mean, variance)
+    let mut radii = Vec::with_capacity(width as usize * height as usize);
+    for task in tasks {
+        radii.extend_from_slice(&task.await.unwrap());
+    }
+    radii
+}
+
+// The radius of every pixel: one for the whole image, or the adaptive mode's map
+enum Radii {
+    Fixed(i32),
+    Map(Vec<i32>),
+}
+
+impl Radii {
+    fn at(&self, index: usize) -> i32 {
+        match self {
+            Radii::Fixed(radius) => *radius,
+            Radii::Map(radii) => radii[index],
+        }
     }
 }
 
 fn kuwahara_filter_pixel(
     src: &ImageBuffer<Rgba<u8>, Vec<u8>>,
-    integral: &IntegralImage,
+    neighborhood: &Neighborhood,
     x: i32,
     y: i32,
     radius: i32,
+    filter: FilterOptions,
 ) -> Rgba<u8> {
-    let mut min_variance = f32::MAX;
-    let mut best_mean = [0.0; 3];
-
-    let quadrants = [
-        [x - radius, y - radius, x, y],
-        [x, y - radius, x + radius, y],
-        [x - radius, y, x, y + radius],
-        [x, y, x + radius, y + radius],
-    ];
-
-    for quad in &quadrants {
-        let (mean, variance) = integral.get_region_stats(quad[0], quad[1], quad[2], quad[3]);
-        let total_variance = variance[0] + variance[1] + variance[2];
-
-        if total_variance < min_variance {
-            min_variance = total_variance;
-            best_mean = mean;
+    // The mean color, and the mean alpha when alpha was filtered along with it
+    let best_mean = match neighborhood {
+        // All scales share one summed-area table, which does not depend on the radius
+        Neighborhood::Quadrants(integral) if filter.scales > 1 => {
+            blend_scales(scale_radii(radius, filter.scales).filter_map(|radius| best_quadrant(integral, x, y, radius))).map(|mean| (mean, None))
         }
-    }
+        Neighborhood::Quadrants(integral) => best_quadrant(integral, x, y, radius).map(|(mean, _)| (mean, None)),
+        Neighborhood::QuadrantsWithAlpha(integral) if filter.scales > 1 => {
+            blend_scales(scale_radii(radius, filter.scales).filter_map(|radius| best_quadrant(integral, x, y, radius))).map(split_alpha)
+        }
+        Neighborhood::QuadrantsWithAlpha(integral) => best_quadrant(integral, x, y, radius).map(|(mean, _)| split_alpha(mean)),
+        Neighborhood::Circle(samples, kernel) => kernel.mean(|dx, dy| samples.at(x + dx, y + dy)).map(|mean| (mean, None)),
+        Neighborhood::Ellipse(samples, tensor) => {
+            let orientation = orientation(tensor[y as usize * samples.width + x as usize]);
+            sector_mean(radius, orientation, |dx, dy| samples.at(x + dx, y + dy)).map(|mean| (mean, None))
+        }
+    };
 
     let src_pixel = src.get_pixel(x as u32, y as u32);
-    Rgba([
-        best_mean[0].clamp(0.0, 255.0) as u8,
-        best_mean[1].clamp(0.0, 255.0) as u8,
-        best_mean[2].clamp(0.0, 255.0) as u8,
-        src_pixel[3],
-    ])
+    // Nothing opaque around the pixel to take a color from
+    let Some((best_mean, alpha)) = best_mean else {
+        return *src_pixel;
+    };
+    let [r, g, b] = colorspace::from_space(best_mean.map(|mean| mean as f32), filter.colorspace, filter.linear);
+    let a = alpha.map_or(src_pixel[3], |alpha| alpha.round().clamp(0.0, 255.0) as u8);
+    Rgba([r, g, b, a])
+}
+
+// The color of a mean taken over color and alpha, and its alpha
+pub fn split_alpha([r, g, b, a]: [f64; 4]) -> ([f64; 3], Option<f64>) {
+    ([r, g, b], Some(a))
 }
 
 async fn process_kuwahara_rows(
     src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>,
     dst: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
-    integral: Arc<IntegralImage>,
-    radius: i32,
-    start_row: u32,
-    end_row: u32,
+    neighborhood: Arc<Neighborhood>,
+    radii: Arc<Radii>,
+    filter: FilterOptions,
+    rows: Range<u32>,
+    clock: &mut WorkerClock,
 ) {
     let width = src.dimensions().0;
     let mut local_pixels = Vec::new();
 
-    for y in start_row..end_row {
+    for y in rows {
+        if progress::cancelled() {
+            break;
+        }
         for x in 0..width {
-            let pixel = kuwahara_filter_pixel(&src, &integral, x as i32, y as i32, radius);
+            let radius = radii.at((y * width + x) as usize);
+            let pixel = kuwahara_filter_pixel(&src, &neighborhood, x as i32, y as i32, radius, filter);
             local_pixels.push((x, y, pixel));
         }
+        progress::advance(1);
     }
+    clock.computed();
 
     let mut dst_locked = dst.lock().await;
     for (x, y, pixel) in local_pixels {
@@ -153,50 +551,101 @@ pub async fn apply_kuwahara_filter_async(
     img: &DynamicImage,
     radius: i32,
     num_tasks: usize,
+    filter: FilterOptions,
 ) -> DynamicImage {
     let rgba = img.to_rgba8();
     let (width, height) = rgba.dimensions();
-    
-    let mut integral = IntegralImage::new(width as usize, height as usize);
-
-    let start = Instant::now();
-    integral.build(&rgba);
-    let sat_time = start.elapsed();
-    println!("SAT build time: {}ms", sat_time.as_millis());
+    // Every quadrant is clamped to the image, so a larger radius than its longest side changes nothing
+    let radius = u32::try_from(radius).expect("radius must not be negative").min(width.max(height)) as i32;
+    if radius == 0 {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+    let adaptive = filter.kuwahara_mode == KuwaharaMode::Adaptive;
+    // The edge pass, then the filter pass
+    progress::expect(if adaptive { 2 * height as usize } else { height as usize });
 
     let src = Arc::new(rgba);
+    let radii = if adaptive {
+        let min_radius = filter.min_radius.min(radius as u32) as i32;
+        let radii = radius_map(Arc::clone(&src), min_radius, radius, num_tasks)
+            .instrument(tracing::info_span!("radius_map", min_radius))
+            .await;
+        Arc::new(Radii::Map(radii))
+    } else {
+        Arc::new(Radii::Fixed(radius))
+    };
+    // Alpha only goes through the quadrants; the sector modes keep the input's
+    let filter_alpha = filter.filter_alpha && uses_quadrants(filter);
+    let convert_span = tracing::info_span!("convert", colorspace = ?filter.colorspace);
+    let values = if filter_alpha {
+        convert_with_alpha(Arc::clone(&src), filter, num_tasks).instrument(convert_span).await
+    } else {
+        convert_to_space(Arc::clone(&src), filter, num_tasks).instrument(convert_span).await
+    };
+
+    let alpha = filter.alpha_weighted.then(|| src.pixels().map(|pixel| pixel[3]).collect::<Vec<u8>>());
+    let neighborhood = match (filter.kuwahara_mode, filter.sectors) {
+        (KuwaharaMode::Classic, Some(sectors)) => {
+            let kernel = SectorKernel::new(radius, sectors);
+            Neighborhood::Circle(Samples { values, alpha, width: width as usize, height: height as usize }, kernel)
+        }
+        (KuwaharaMode::Classic, None) | (KuwaharaMode::Adaptive, _) => {
+            // Timed by its span when tracing is on; the library itself prints nothing
+            tracing::info_span!("sat_build").in_scope(|| {
+                if filter_alpha {
+                    let mut integral = IntegralImage::with_alpha(width as usize, height as usize);
+                    integral.build(&values, alpha.as_deref());
+                    Neighborhood::QuadrantsWithAlpha(integral)
+                } else {
+                    let mut integral = IntegralImage::new(width as usize, height as usize);
+                    integral.build(&values, alpha.as_deref());
+                    Neighborhood::Quadrants(integral)
+                }
+            })
+        }
+        (KuwaharaMode::Anisotropic, _) => {
+            let tensor = structure_tensor(Arc::clone(&src), num_tasks)
+                .instrument(tracing::info_span!("structure_tensor"))
+                .await;
+            Neighborhood::Ellipse(Samples { values, alpha, width: width as usize, height: height as usize }, tensor)
+        }
+    };
+
     let dst = Arc::new(Mutex::new(ImageBuffer::new(width, height)));
-    let integral = Arc::new(integral);
+    let neighborhood = Arc::new(neighborhood);
 
-    let rows_per_task = height / num_tasks as u32;
+    let pass = tracing::info_span!("kuwahara_pass");
     let mut tasks = Vec::new();
 
-    for task_id in 0..num_tasks {
+    for (task_id, rows) in bands::split(height as usize, num_tasks).into_iter().enumerate() {
         let src = Arc::clone(&src);
         let dst = Arc::clone(&dst);
-        let integral = Arc::clone(&integral);
-
-        let task = task::spawn(async move {
-            let start_row = task_id as u32 * rows_per_task;
-            let end_row = if task_id == num_tasks - 1 {
-                height
-            } else {
-                (task_id as u32 + 1) * rows_per_task
-            };
+        let neighborhood = Arc::clone(&neighborhood);
+        let radii = Arc::clone(&radii);
 
-            process_kuwahara_rows(src, dst, integral, radius, start_row, end_row).await;
-        });
+        let task = task_latency::spawn(async move {
+            let mut clock = WorkerClock::start("kuwahara", task_id, rows.clone());
+            process_kuwahara_rows(src, dst, neighborhood, radii, filter, rows.start as u32..rows.end as u32, &mut clock).await;
+            clock.finish();
+        }
+        .instrument(tracing::debug_span!(parent: &pass, "task", id = task_id)));
 
         tasks.push(task);
     }
 
-    for task in tasks {
-        task.await.unwrap();
+    async {
+        for task in tasks {
+            task.await.unwrap();
+        }
     }
+    .instrument(pass)
+    .await;
 
     let result = Arc::try_unwrap(dst)
         .unwrap()
         .into_inner();
 
-    DynamicImage::ImageRgba8(result)
+    channels::restore(img, DynamicImage::ImageRgba8(result), filter.channels, num_tasks)
+        .instrument(tracing::info_span!("channels"))
+        .await
 }
\ No newline at end of file
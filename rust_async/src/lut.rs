@@ -0,0 +1,109 @@
+use crate::cli::FilterOptions;
+use crate::point;
+use image::DynamicImage;
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// A 3D color lookup table read from an Adobe/Resolve `.cube` file, the usual format for film-look
+// grades. Entries are stored with red changing fastest, as the file lists them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    table: Vec<[f32; 3]>,
+}
+
+fn parse_triple(keyword: &str, values: &[&str]) -> Result<[f32; 3], String> {
+    let invalid = || format!("Invalid {}: {}", keyword, values.join(" "));
+    match values {
+        [r, g, b] => Ok([r.parse().map_err(|_| invalid())?, g.parse().map_err(|_| invalid())?, b.parse().map_err(|_| invalid())?]),
+        _ => Err(invalid()),
+    }
+}
+
+impl FromStr for Lut {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["TITLE", ..] => {}
+                ["LUT_3D_SIZE", n] => {
+                    let n: usize = n.parse().map_err(|_| format!("Invalid LUT_3D_SIZE: {}", n))?;
+                    if !(2..=256).contains(&n) {
+                        return Err(format!("LUT_3D_SIZE must be between 2 and 256, got {}", n));
+                    }
+                    size = Some(n);
+                }
+                ["LUT_1D_SIZE", ..] => return Err("1D LUTs are not supported, only LUT_3D_SIZE".to_string()),
+                ["DOMAIN_MIN", values @ ..] => domain_min = parse_triple("DOMAIN_MIN", values)?,
+                ["DOMAIN_MAX", values @ ..] => domain_max = parse_triple("DOMAIN_MAX", values)?,
+                [first, ..] if first.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    return Err(format!("Unknown .cube keyword: {}", first));
+                }
+                values => table.push(parse_triple("table entry", values)?),
+            }
+        }
+
+        let size = size.ok_or("Missing LUT_3D_SIZE")?;
+        if table.len() != size * size * size {
+            return Err(format!("Expected {} table entries for LUT_3D_SIZE {}, got {}", size * size * size, size, table.len()));
+        }
+        if (0..3).any(|ch| domain_max[ch] <= domain_min[ch]) {
+            return Err("DOMAIN_MAX must be above DOMAIN_MIN".to_string());
+        }
+        Ok(Lut { size, domain_min, domain_max, table })
+    }
+}
+
+impl Lut {
+    pub fn load(path: &str) -> Result<Lut, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        text.parse().map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[(b * self.size + g) * self.size + r]
+    }
+
+    // Trilinear interpolation between the 8 entries around the color
+    pub fn map(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let last = (self.size - 1) as f32;
+        let position = [0, 1, 2].map(|ch| {
+            let t = (rgb[ch] as f32 / 255.0 - self.domain_min[ch]) / (self.domain_max[ch] - self.domain_min[ch]);
+            t.clamp(0.0, 1.0) * last
+        });
+        let low = position.map(|p| (p.floor() as usize).min(self.size - 2));
+        let frac = [0, 1, 2].map(|ch| position[ch] - low[ch] as f32);
+
+        let mut out = [0.0f32; 3];
+        for corner in 0..8 {
+            let step = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let weight: f32 = (0..3).map(|ch| if step[ch] == 1 { frac[ch] } else { 1.0 - frac[ch] }).product();
+            let entry = self.entry(low[0] + step[0], low[1] + step[1], low[2] + step[2]);
+            for (out, value) in out.iter_mut().zip(entry) {
+                *out += weight * value;
+            }
+        }
+        out.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+// Grades every pixel through the table, one band of rows per task; alpha is kept
+pub async fn apply_lut_async(img: &DynamicImage, lut: Arc<Lut>, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    point::map_pixels_async(img, num_tasks, filter, move |[r, g, b, a]| {
+        let [r, g, b] = lut.map([r, g, b]);
+        [r, g, b, a]
+    })
+    .await
+}
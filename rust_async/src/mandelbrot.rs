@@ -0,0 +1,106 @@
+use crate::bands;
+use crate::progress;
+use crate::task_latency;
+use crate::timing::WorkerClock;
+use image::{ImageBuffer, Rgba};
+use tracing::Instrument;
+
+// Units of the complex plane across the shorter side of the image at zoom 1, enough for the whole set
+const SPAN: f64 = 3.0;
+
+// The part of the complex plane `mandelbrot` renders
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    pub center: (f64, f64),
+    // Magnification, each doubling halves the span
+    pub zoom: f64,
+    // Iterations after which a point that has not escaped counts as inside the set
+    pub max_iterations: u32,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        View { center: (-0.5, 0.0), zoom: 1.0, max_iterations: 256 }
+    }
+}
+
+impl View {
+    // Point of the plane at the center of pixel (x, y), the imaginary axis pointing up
+    pub fn point(&self, x: u32, y: u32, width: u32, height: u32) -> (f64, f64) {
+        let pixel = SPAN / (self.zoom * width.min(height) as f64);
+        (
+            self.center.0 + (x as f64 + 0.5 - width as f64 / 2.0) * pixel,
+            self.center.1 - (y as f64 + 0.5 - height as f64 / 2.0) * pixel,
+        )
+    }
+}
+
+// `<width>x<height>` of the image to render
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid size: {}, expected <width>x<height>", s);
+    let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+// Iterations of z = z² + c before |z| passes 2, or `max_iterations` for points in the set. Points
+// inside cost the full count and those far outside one or two, which is what makes rows so uneven.
+pub fn escape_time(c: (f64, f64), max_iterations: u32) -> u32 {
+    let (mut zr, mut zi) = (0.0f64, 0.0f64);
+    for iteration in 0..max_iterations {
+        if zr * zr + zi * zi > 4.0 {
+            return iteration;
+        }
+        (zr, zi) = (zr * zr - zi * zi + c.0, 2.0 * zr * zi + c.1);
+    }
+    max_iterations
+}
+
+// Black inside the set, outside a blue to orange ramp by how soon the point escaped
+pub fn color(iterations: u32, max_iterations: u32) -> Rgba<u8> {
+    if iterations >= max_iterations {
+        return Rgba([0, 0, 0, 255]);
+    }
+    let t = iterations as f64 / max_iterations as f64;
+    let channel = |value: f64| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba([
+        channel(9.0 * (1.0 - t) * t * t * t),
+        channel(15.0 * (1.0 - t) * (1.0 - t) * t * t),
+        channel(8.5 * (1.0 - t) * (1.0 - t) * (1.0 - t) * t),
+        255,
+    ])
+}
+
+// Renders the view at `width` x `height`, one band of rows per task. Rows through the set take up
+// to `max_iterations` per pixel while rows above and below it escape at once, so with one band per
+// runtime thread the bands finish far apart; more tasks than threads let the runtime even them out.
+pub async fn render_async(width: u32, height: u32, view: View, num_tasks: usize) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    progress::expect(height as usize);
+    let row_len = width as usize * 4;
+    let pass = tracing::info_span!("mandelbrot_pass");
+    let mut tasks = Vec::new();
+    for (task_id, rows) in bands::split(height as usize, num_tasks).into_iter().enumerate() {
+        tasks.push(task_latency::spawn(async move {
+            let clock = WorkerClock::start("mandelbrot", task_id, rows.clone());
+            let mut band = Vec::with_capacity(rows.len() * row_len);
+            for y in rows {
+                for x in 0..width {
+                    let iterations = escape_time(view.point(x, y as u32, width, height), view.max_iterations);
+                    band.extend_from_slice(&color(iterations, view.max_iterations).0);
+                }
+                progress::advance(1);
+            }
+            clock.finish();
+            band
+        }
+        .instrument(tracing::debug_span!(parent: &pass, "task", id = task_id))));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    ImageBuffer::from_raw(width, height, data).expect("Rendered buffer matches dimensions")
+}
@@ -0,0 +1,229 @@
+use crate::bands;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task;
+
+// An irregular area to filter, such as a face or a licence plate, given either as a list of
+// points, `10,10 200,10 120,150`, or as an SVG path of straight segments, `M10 10 H200 L120 150 Z`.
+// Pixels whose centers lie inside, by the nonzero rule SVG fills with, are filtered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    // Closed outlines, one per subpath
+    contours: Vec<Vec<(f64, f64)>>,
+}
+
+impl FromStr for Polygon {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let contours = if s.starts_with(['M', 'm']) { parse_path(s)? } else { vec![parse_points(s)?] };
+        let contours: Vec<_> = contours.into_iter().filter(|contour| contour.len() >= 3).collect();
+        if contours.is_empty() {
+            return Err("A polygon needs at least 3 points".to_string());
+        }
+        Ok(Polygon { contours })
+    }
+}
+
+// `x,y` pairs separated by spaces or semicolons
+fn parse_points(s: &str) -> Result<Vec<(f64, f64)>, String> {
+    s.split([' ', ';'])
+        .filter(|point| !point.is_empty())
+        .map(|point| {
+            let (x, y) = point.split_once(',').ok_or_else(|| format!("Expected x,y: {}", point))?;
+            let x: f64 = x.parse().map_err(|_| format!("Invalid coordinate: {}", x))?;
+            let y: f64 = y.parse().map_err(|_| format!("Invalid coordinate: {}", y))?;
+            Ok((x, y))
+        })
+        .collect()
+}
+
+enum Token {
+    Command(char),
+    Number(f64),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() || c == ',' {
+            chars.next();
+        } else if c.is_ascii_alphabetic() && c != 'e' && c != 'E' {
+            tokens.push(Token::Command(c));
+            chars.next();
+        } else {
+            // A number runs until the next separator, command or sign that does not follow an exponent
+            let mut end = start;
+            let mut previous = None;
+            while let Some(&(i, c)) = chars.peek() {
+                let sign_continues = matches!(previous, None | Some('e') | Some('E'));
+                if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || ((c == '-' || c == '+') && sign_continues)) {
+                    break;
+                }
+                previous = Some(c);
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let number = &s[start..end];
+            if number.is_empty() {
+                return Err(format!("Unexpected character in path: {}", c));
+            }
+            tokens.push(Token::Number(number.parse().map_err(|_| format!("Invalid number in path: {}", number))?));
+        }
+    }
+    Ok(tokens)
+}
+
+// The straight-segment commands of SVG paths: M, L, H, V and Z, absolute or relative
+fn parse_path(s: &str) -> Result<Vec<Vec<(f64, f64)>>, String> {
+    let tokens = tokenize(s)?;
+    let mut contours = Vec::new();
+    let mut contour: Vec<(f64, f64)> = Vec::new();
+    let (mut x, mut y) = (0.0, 0.0);
+    let mut command = None;
+    let mut i = 0;
+
+    let number = |i: usize| match tokens.get(i) {
+        Some(Token::Number(value)) => Ok(*value),
+        _ => Err("Path command is missing a coordinate".to_string()),
+    };
+
+    while i < tokens.len() {
+        if let Token::Command(c) = tokens[i] {
+            command = Some(c);
+            i += 1;
+            if c == 'Z' || c == 'z' {
+                if let Some(&first) = contour.first() {
+                    (x, y) = first;
+                }
+                contours.push(std::mem::take(&mut contour));
+                continue;
+            }
+        }
+        let c = command.ok_or("Path must start with M")?;
+        let relative = c.is_ascii_lowercase();
+        let (dx, dy) = if relative { (x, y) } else { (0.0, 0.0) };
+        match c.to_ascii_uppercase() {
+            'M' => {
+                if !contour.is_empty() {
+                    contours.push(std::mem::take(&mut contour));
+                }
+                (x, y) = (dx + number(i)?, dy + number(i + 1)?);
+                i += 2;
+                // Pairs after a move are lines
+                command = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' => {
+                (x, y) = (dx + number(i)?, dy + number(i + 1)?);
+                i += 2;
+            }
+            'H' => {
+                x = dx + number(i)?;
+                i += 1;
+            }
+            'V' => {
+                y = dy + number(i)?;
+                i += 1;
+            }
+            'Z' => return Err("Closed path continues without a command".to_string()),
+            other => return Err(format!("Unsupported path command: {} (only M, L, H, V and Z)", other)),
+        }
+        contour.push((x, y));
+    }
+    contours.push(contour);
+    Ok(contours)
+}
+
+impl Polygon {
+    // Crossings of the row of pixel centers at height `y` with every edge, with their winding
+    fn crossings(&self, y: f64) -> Vec<(f64, i32)> {
+        let mut crossings = Vec::new();
+        for contour in &self.contours {
+            for (i, &(x0, y0)) in contour.iter().enumerate() {
+                let (x1, y1) = contour[(i + 1) % contour.len()];
+                // Half-open in y, so a vertex shared by two edges is counted once
+                if (y0 <= y) != (y1 <= y) {
+                    let x = x0 + (y - y0) / (y1 - y0) * (x1 - x0);
+                    crossings.push((x, if y1 > y0 { 1 } else { -1 }));
+                }
+            }
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+        crossings
+    }
+
+    fn fill_row(&self, y: u32, row: &mut [u8]) {
+        let width = row.len() as f64;
+        let mut winding = 0;
+        let mut span_start = 0.0;
+        for (x, direction) in self.crossings(y as f64 + 0.5) {
+            let was_inside = winding != 0;
+            winding += direction;
+            if !was_inside && winding != 0 {
+                span_start = x;
+            } else if was_inside && winding == 0 {
+                // Pixels whose centers x + 0.5 fall between the two crossings
+                let first = (span_start - 0.5).ceil().clamp(0.0, width) as usize;
+                let end = (x - 0.5).ceil().clamp(0.0, width) as usize;
+                row[first..end.max(first)].fill(255);
+            }
+        }
+    }
+
+    // One byte per pixel, 255 inside and 0 outside, one band of rows per task
+    pub async fn rasterize(&self, width: u32, height: u32, num_tasks: usize) -> Vec<u8> {
+        let polygon = Arc::new(self.clone());
+        let mut tasks = Vec::new();
+
+        for rows in bands::split(height as usize, num_tasks) {
+            let polygon = Arc::clone(&polygon);
+            tasks.push(task::spawn(async move {
+                let mut band = vec![0; rows.len() * width as usize];
+                for (y, row) in rows.zip(band.chunks_exact_mut(width as usize)) {
+                    polygon.fill_row(y as u32, row);
+                }
+                band
+            }));
+        }
+
+        let mut mask = Vec::with_capacity(width as usize * height as usize);
+        for task in tasks {
+            mask.extend_from_slice(&task.await.unwrap());
+        }
+        mask
+    }
+}
+
+// Mixes `filtered` with `src` by the mask, so pixels outside keep the input's values, one band of
+// rows per task
+pub async fn blend(src: &DynamicImage, filtered: DynamicImage, mask: Arc<Vec<u8>>, num_tasks: usize) -> DynamicImage {
+    let src = Arc::new(src.to_rgba8());
+    let filtered = Arc::new(filtered.into_rgba8());
+    let (width, height) = filtered.dimensions();
+    let row_len = width as usize * 4;
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height as usize, num_tasks) {
+        let (src, filtered, mask) = (Arc::clone(&src), Arc::clone(&filtered), Arc::clone(&mask));
+        tasks.push(task::spawn(async move {
+            let bytes = rows.start * row_len..rows.end * row_len;
+            let weights = mask[rows.start * width as usize..rows.end * width as usize].iter().flat_map(|&weight| [weight as u32; 4]);
+            filtered.as_raw()[bytes.clone()]
+                .iter()
+                .zip(&src.as_raw()[bytes])
+                .zip(weights)
+                .map(|((&dst, &src), weight)| ((dst as u32 * weight + src as u32 * (255 - weight) + 127) / 255) as u8)
+                .collect::<Vec<u8>>()
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Blended buffer matches dimensions");
+    DynamicImage::ImageRgba8(buffer)
+}
@@ -0,0 +1,139 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::task_latency;
+use crate::timing::WorkerClock;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::Instrument;
+
+// Counts of every value of each channel over the window. Sliding one column along a row adds and
+// removes 2r+1 pixels instead of sorting (2r+1)^2 of them (Huang's algorithm).
+struct WindowHistogram {
+    counts: [[u32; 256]; 4],
+    total: u32,
+}
+
+impl WindowHistogram {
+    fn new() -> Self {
+        WindowHistogram { counts: [[0; 256]; 4], total: 0 }
+    }
+
+    // The column at `x` over the rows `y - radius..=y + radius`, repeating edge pixels past the border
+    fn update_column(&mut self, src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: i32, y: i32, radius: i32, add: bool) {
+        let (width, height) = src.dimensions();
+        let x = x.clamp(0, width as i32 - 1) as u32;
+        for sy in y - radius..=y + radius {
+            let pixel = src.get_pixel(x, sy.clamp(0, height as i32 - 1) as u32);
+            for (counts, &value) in self.counts.iter_mut().zip(pixel.0.iter()) {
+                if add {
+                    counts[value as usize] += 1;
+                } else {
+                    counts[value as usize] -= 1;
+                }
+            }
+        }
+        if add {
+            self.total += 2 * radius as u32 + 1;
+        } else {
+            self.total -= 2 * radius as u32 + 1;
+        }
+    }
+
+    // The window holds an odd count, so the median is the value where the running count passes half
+    fn median(&self) -> Rgba<u8> {
+        Rgba([0, 1, 2, 3].map(|ch| {
+            let mut seen = 0;
+            let position = self.counts[ch].iter().position(|&count| {
+                seen += count;
+                seen > self.total / 2
+            });
+            position.unwrap_or(255) as u8
+        }))
+    }
+}
+
+async fn process_median_rows(
+    src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>,
+    dst: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
+    radius: i32,
+    rows: Range<u32>,
+    clock: &mut WorkerClock,
+) {
+    let width = src.dimensions().0 as i32;
+    let mut local_pixels = Vec::new();
+
+    for y in rows {
+        if progress::cancelled() {
+            break;
+        }
+        let y = y as i32;
+        let mut histogram = WindowHistogram::new();
+        for x in -radius..=radius {
+            histogram.update_column(&src, x, y, radius, true);
+        }
+        for x in 0..width {
+            local_pixels.push((x as u32, y as u32, histogram.median()));
+            histogram.update_column(&src, x - radius, y, radius, false);
+            histogram.update_column(&src, x + radius + 1, y, radius, true);
+        }
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst_locked = dst.lock().await;
+    for (x, y, pixel) in local_pixels {
+        dst_locked.put_pixel(x, y, pixel);
+    }
+}
+
+// Median of every channel over the (2r+1)x(2r+1) square around each pixel, a standard denoising
+// baseline that removes salt and pepper noise outright; one band of rows per task
+pub async fn apply_median_filter_async(img: &DynamicImage, radius: i32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let radius = u32::try_from(radius).expect("radius must not be negative") as i32;
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if radius == 0 {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+    progress::expect(height as usize);
+
+    let src = Arc::new(rgba);
+    let dst = Arc::new(Mutex::new(ImageBuffer::new(width, height)));
+
+    let pass = tracing::info_span!("median_pass");
+    let mut tasks = Vec::new();
+
+    for (task_id, rows) in bands::split(height as usize, num_tasks).into_iter().enumerate() {
+        let src = Arc::clone(&src);
+        let dst = Arc::clone(&dst);
+
+        let task = task_latency::spawn(async move {
+            let mut clock = WorkerClock::start("median", task_id, rows.clone());
+            process_median_rows(src, dst, radius, rows.start as u32..rows.end as u32, &mut clock).await;
+            clock.finish();
+        }
+        .instrument(tracing::debug_span!(parent: &pass, "task", id = task_id)));
+
+        tasks.push(task);
+    }
+
+    async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+    .instrument(pass)
+    .await;
+
+    let result = Arc::try_unwrap(dst)
+        .unwrap()
+        .into_inner();
+
+    channels::restore(img, DynamicImage::ImageRgba8(result), filter.channels, num_tasks)
+        .instrument(tracing::info_span!("channels"))
+        .await
+}
@@ -0,0 +1,91 @@
+#[cfg(feature = "alloc-stats")]
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(feature = "alloc-stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+pub fn to_mib(bytes: u64) -> f64 {
+    bytes as f64 / MIB
+}
+
+// Peak resident set size in bytes, from the kernel's high-water mark where /proc is available
+pub fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+// Global allocator that counts bytes, enabled with `--features alloc-stats`
+#[cfg(feature = "alloc-stats")]
+struct CountingAllocator;
+
+#[cfg(feature = "alloc-stats")]
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "alloc-stats")]
+static LIVE: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "alloc-stats")]
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "alloc-stats")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let size = layout.size() as u64;
+            TOTAL.fetch_add(size, Ordering::Relaxed);
+            let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+pub struct PhaseAllocations {
+    // Bytes allocated during the phase, freed or not
+    pub allocated: u64,
+    // Most bytes live at once during the phase, including what was live when it started
+    pub peak: u64,
+}
+
+// Tracks allocations between `start` and `finish`; always empty without the alloc-stats feature
+pub struct Phase {
+    #[cfg(feature = "alloc-stats")]
+    total_at_start: u64,
+}
+
+impl Phase {
+    pub fn start() -> Phase {
+        #[cfg(feature = "alloc-stats")]
+        {
+            PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+            Phase {
+                total_at_start: TOTAL.load(Ordering::Relaxed),
+            }
+        }
+        #[cfg(not(feature = "alloc-stats"))]
+        Phase {}
+    }
+
+    pub fn finish(self) -> Option<PhaseAllocations> {
+        #[cfg(feature = "alloc-stats")]
+        {
+            Some(PhaseAllocations {
+                allocated: TOTAL.load(Ordering::Relaxed) - self.total_at_start,
+                peak: PEAK.load(Ordering::Relaxed),
+            })
+        }
+        #[cfg(not(feature = "alloc-stats"))]
+        None
+    }
+}
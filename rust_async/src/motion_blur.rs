@@ -0,0 +1,99 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::srgb;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::task;
+
+// Offsets of the taps: `length` points one pixel apart on a line through the pixel at `angle`
+// degrees counterclockwise from the x axis, centered on it. Rows grow downward, hence the minus.
+pub fn offsets(length: u32, angle: f64) -> Vec<(f64, f64)> {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let center = (length as f64 - 1.0) / 2.0;
+    (0..length)
+        .map(|i| {
+            let t = i as f64 - center;
+            (t * cos, -t * sin)
+        })
+        .collect()
+}
+
+// Rows of context above and below a band: half the line, and the extra row bilinear sampling reads
+pub fn overlap(length: u32) -> usize {
+    length as usize / 2 + 1
+}
+
+// Bilinear sample of every channel at a point between pixels, edges repeated past the border
+fn sample(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: f64, y: f64, decode: impl Fn(u8, usize) -> f64) -> [f64; 4] {
+    let (width, height) = src.dimensions();
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let pixel = |x: f64, y: f64| src.get_pixel((x as i64).clamp(0, width as i64 - 1) as u32, (y as i64).clamp(0, height as i64 - 1) as u32);
+    let (p00, p10, p01, p11) = (pixel(x0, y0), pixel(x0 + 1.0, y0), pixel(x0, y0 + 1.0), pixel(x0 + 1.0, y0 + 1.0));
+    std::array::from_fn(|ch| {
+        let top = decode(p00[ch], ch) * (1.0 - fx) + decode(p10[ch], ch) * fx;
+        let bottom = decode(p01[ch], ch) * (1.0 - fx) + decode(p11[ch], ch) * fx;
+        top * (1.0 - fy) + bottom * fy
+    })
+}
+
+// Mean of the taps along the line through (x, y)
+fn motion_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, offsets: &[(f64, f64)], filter: FilterOptions) -> Rgba<u8> {
+    let lut = srgb::lut();
+    let decode = |value: u8, channel: usize| if filter.linear && channel < 3 { lut.decode(value) } else { value as f64 };
+    let encode = |value: f64, channel: usize| if filter.linear && channel < 3 { lut.encode(value) } else { value.round() as u8 };
+    let mut sums = [0.0; 4];
+    for &(dx, dy) in offsets {
+        let values = sample(src, x as f64 + dx, y as f64 + dy, decode);
+        for (sum, value) in sums.iter_mut().zip(values) {
+            *sum += value;
+        }
+    }
+    Rgba([0, 1, 2, 3].map(|channel| encode(sums[channel] / offsets.len() as f64, channel)))
+}
+
+// Directional blur: every pixel becomes the mean of `length` samples along a line at `angle`
+// degrees, as a camera moving during the exposure would record it. Unlike the Gaussian blur it
+// does not separate into a horizontal and a vertical pass, so each band reads rows around it
+// directly.
+pub async fn apply_motion_blur_async(
+    img: &DynamicImage,
+    length: u32,
+    angle: f64,
+    num_tasks: usize,
+    filter: FilterOptions,
+) -> DynamicImage {
+    let src = Arc::new(img.to_rgba8());
+    if length <= 1 {
+        return DynamicImage::ImageRgba8(Arc::unwrap_or_clone(src));
+    }
+    let offsets = Arc::new(offsets(length, angle));
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height as usize, num_tasks) {
+        let src = Arc::clone(&src);
+        let offsets = Arc::clone(&offsets);
+        tasks.push(task::spawn(async move {
+            let mut band = Vec::with_capacity(rows.len() * row_len);
+            for y in rows.clone() {
+                for x in 0..width {
+                    band.extend_from_slice(&motion_pixel(&src, x, y as u32, &offsets, filter).0);
+                }
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Blurred buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
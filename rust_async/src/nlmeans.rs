@@ -0,0 +1,157 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::progress;
+use crate::task_latency;
+use crate::timing::WorkerClock;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::Instrument;
+
+// Side of the square tiles the image is cut into. Neighboring pixels search mostly the same window,
+// so a tile's searches stay in cache where a full-width row's would not.
+pub const TILE_SIZE: u32 = 64;
+
+// A rectangle of the image, one unit of work
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub x: Range<u32>,
+    pub y: Range<u32>,
+}
+
+// Tiles covering the image row by row, those on the right and bottom edges cut to fit
+pub fn tiles(width: u32, height: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile { x: x..(x + TILE_SIZE).min(width), y: y..(y + TILE_SIZE).min(height) });
+        }
+    }
+    tiles
+}
+
+// Mean squared difference of the color channels between the patches around two pixels, edges
+// repeated past the border
+fn patch_distance(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, a: (i32, i32), b: (i32, i32), patch: i32) -> f32 {
+    let (width, height) = src.dimensions();
+    let pixel = |x: i32, y: i32| src.get_pixel(x.clamp(0, width as i32 - 1) as u32, y.clamp(0, height as i32 - 1) as u32);
+    let mut sum = 0.0;
+    for dy in -patch..=patch {
+        for dx in -patch..=patch {
+            let (p, q) = (pixel(a.0 + dx, a.1 + dy), pixel(b.0 + dx, b.1 + dy));
+            for ch in 0..3 {
+                let diff = p[ch] as f32 - q[ch] as f32;
+                sum += diff * diff;
+            }
+        }
+    }
+    sum / ((2 * patch + 1) * (2 * patch + 1) * 3) as f32
+}
+
+// Weighted mean of every pixel in the search window, each weighted by how closely its patch
+// resembles the patch around (x, y)
+fn denoise_pixel(src: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: i32, y: i32, search: i32, patch: i32, strength: f32) -> Rgba<u8> {
+    let (width, height) = src.dimensions();
+    let mut sums = [0.0f32; 4];
+    let mut total = 0.0f32;
+    for sy in y - search..=y + search {
+        for sx in x - search..=x + search {
+            let weight = (-patch_distance(src, (x, y), (sx, sy), patch) / (strength * strength)).exp();
+            let pixel = src.get_pixel(sx.clamp(0, width as i32 - 1) as u32, sy.clamp(0, height as i32 - 1) as u32);
+            for (sum, &value) in sums.iter_mut().zip(pixel.0.iter()) {
+                *sum += weight * value as f32;
+            }
+            total += weight;
+        }
+    }
+    Rgba(sums.map(|sum| (sum / total).round().clamp(0.0, 255.0) as u8))
+}
+
+async fn process_nlmeans_tiles(
+    src: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>,
+    dst: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
+    tiles: &[Tile],
+    search: i32,
+    filter: FilterOptions,
+    clock: &mut WorkerClock,
+) {
+    let strength = filter.strength as f32;
+    let patch = filter.patch_radius as i32;
+    let mut local_pixels = Vec::new();
+
+    for tile in tiles {
+        if progress::cancelled() {
+            break;
+        }
+        for y in tile.y.clone() {
+            for x in tile.x.clone() {
+                local_pixels.push((x, y, denoise_pixel(&src, x as i32, y as i32, search, patch, strength)));
+            }
+        }
+        progress::advance(1);
+    }
+    clock.computed();
+
+    let mut dst_locked = dst.lock().await;
+    for (x, y, pixel) in local_pixels {
+        dst_locked.put_pixel(x, y, pixel);
+    }
+}
+
+// Non-local means: every pixel becomes the mean of its search window, weighted by how similar the
+// patch around each candidate is to its own, so texture repeated anywhere nearby averages out the
+// noise while edges are kept. Each pixel compares (2r+1)^2 patches, far more work than any of the
+// local filters. The tiles are split into one contiguous run per task.
+pub async fn apply_nlmeans_async(img: &DynamicImage, radius: i32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let search = u32::try_from(radius).expect("radius must not be negative") as i32;
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if search == 0 {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+    let tiles = Arc::new(tiles(width, height));
+    // Progress counts tiles rather than rows here
+    progress::expect(tiles.len());
+
+    let src = Arc::new(rgba);
+    let dst = Arc::new(Mutex::new(ImageBuffer::new(width, height)));
+
+    let pass = tracing::info_span!("nlmeans_pass");
+    let mut tasks = Vec::new();
+
+    for (task_id, run) in bands::split(tiles.len(), num_tasks).into_iter().enumerate() {
+        let src = Arc::clone(&src);
+        let dst = Arc::clone(&dst);
+        let tiles = Arc::clone(&tiles);
+
+        let task = task_latency::spawn(async move {
+            let tiles = &tiles[run];
+            // Tiles go row by row, so a run covers the rows from its first tile's to its last's
+            let rows = tiles[0].y.start as usize..tiles[tiles.len() - 1].y.end as usize;
+            let mut clock = WorkerClock::start("nlmeans", task_id, rows);
+            process_nlmeans_tiles(src, dst, tiles, search, filter, &mut clock).await;
+            clock.finish();
+        }
+        .instrument(tracing::debug_span!(parent: &pass, "task", id = task_id)));
+
+        tasks.push(task);
+    }
+
+    async {
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+    .instrument(pass)
+    .await;
+
+    let result = Arc::try_unwrap(dst)
+        .unwrap()
+        .into_inner();
+
+    channels::restore(img, DynamicImage::ImageRgba8(result), filter.channels, num_tasks)
+        .instrument(tracing::info_span!("channels"))
+        .await
+}
@@ -0,0 +1,135 @@
+use crate::bands;
+use crate::blend::{self, Mode};
+use crate::thumbs;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::task;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Where `overlay` puts the stamp: once with its corner at an offset, or repeated over the whole
+// image from the top left. Negative offsets count from the right and bottom edges, so `-10,-10`
+// keeps a watermark 10 pixels inside the bottom right corner whatever the photo's size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    At(i64, i64),
+    Tile,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Placement::At(0, 0)
+    }
+}
+
+pub struct BatchStats {
+    pub images: usize,
+}
+
+// Top left corner of a single stamp in image coordinates, possibly outside the image
+pub fn origin(size: (u32, u32), stamp: (u32, u32), x: i64, y: i64) -> (i64, i64) {
+    let place = |offset: i64, size: u32, stamp: u32| if offset < 0 { size as i64 - stamp as i64 + offset + 1 } else { offset };
+    (place(x, size.0, stamp.0), place(y, size.1, stamp.1))
+}
+
+// Rows of the image the stamp covers, the only ones there is any work on
+pub fn affected_rows(size: (u32, u32), stamp: (u32, u32), placement: Placement) -> Range<usize> {
+    match placement {
+        Placement::Tile => 0..size.1 as usize,
+        Placement::At(x, y) => {
+            let (_, top) = origin(size, stamp, x, y);
+            let clip = |row: i64| row.clamp(0, size.1 as i64) as usize;
+            clip(top)..clip(top + stamp.1 as i64)
+        }
+    }
+}
+
+// Pixel of the stamp over image pixel (x, y), if any
+pub fn stamp_at(stamp: &Frame, size: (u32, u32), placement: Placement, x: u32, y: u32) -> Option<[u8; 4]> {
+    let (width, height) = stamp.dimensions();
+    match placement {
+        Placement::Tile => Some(stamp.get_pixel(x % width, y % height).0),
+        Placement::At(at_x, at_y) => {
+            let (left, top) = origin(size, (width, height), at_x, at_y);
+            let (sx, sy) = (x as i64 - left, y as i64 - top);
+            ((0..width as i64).contains(&sx) && (0..height as i64).contains(&sy)).then(|| stamp.get_pixel(sx as u32, sy as u32).0)
+        }
+    }
+}
+
+// Stamps the rows of `band`, the first of them image row `first`
+fn stamp_rows(band: &mut [u8], first: usize, size: (u32, u32), stamp: &Frame, placement: Placement, opacity: f64) {
+    for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
+        let (x, y) = ((i % size.0 as usize) as u32, (first + i / size.0 as usize) as u32);
+        if let Some(over) = stamp_at(stamp, size, placement, x, y) {
+            let stamped = blend::composite_pixel([pixel[0], pixel[1], pixel[2], pixel[3]], over, Mode::Normal, opacity);
+            pixel.copy_from_slice(&stamped);
+        }
+    }
+}
+
+// Stamps `stamp` over `img` by its alpha, scaled by `opacity`. Only the rows the stamp covers are
+// split into bands, so a small watermark in a corner costs a few rows, not the whole image.
+pub async fn apply_overlay_async(img: &DynamicImage, stamp: &DynamicImage, placement: Placement, opacity: f64, num_tasks: usize) -> DynamicImage {
+    let mut result = img.to_rgba8();
+    let stamp = Arc::new(stamp.to_rgba8());
+    let size = result.dimensions();
+    let row_len = size.0 as usize * 4;
+    let affected = affected_rows(size, stamp.dimensions(), placement);
+    let src = Arc::new(result.as_raw()[affected.start * row_len..affected.end * row_len].to_vec());
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(affected.len(), num_tasks) {
+        let (src, stamp) = (Arc::clone(&src), Arc::clone(&stamp));
+        let first = affected.start + rows.start;
+        tasks.push(task::spawn(async move {
+            let mut band = src[rows.start * row_len..rows.end * row_len].to_vec();
+            stamp_rows(&mut band, first, size, &stamp, placement, opacity);
+            (first, band)
+        }));
+    }
+
+    let data: &mut [u8] = &mut result;
+    for task in tasks {
+        let (first, band) = task.await.unwrap();
+        data[first * row_len..first * row_len + band.len()].copy_from_slice(&band);
+    }
+    DynamicImage::ImageRgba8(result)
+}
+
+// Stamps every image in `input` into `output` under the same name. Decoding and encoding cost far
+// more than the stamp, so the files are split between the tasks instead of the rows, each task
+// decoding, stamping and saving its own in turn on the blocking pool.
+pub async fn stamp_dir_async(input: &str, output: &str, stamp: &DynamicImage, placement: Placement, opacity: f64, num_tasks: usize) -> Result<BatchStats, Box<dyn Error>> {
+    let sources = Arc::new(thumbs::list_sources(input)?);
+    fs::create_dir_all(output)?;
+    let output = PathBuf::from(output);
+    let stamp = Arc::new(stamp.to_rgba8());
+    let mut tasks = Vec::new();
+
+    for files in bands::split(sources.len(), num_tasks) {
+        let (sources, stamp, output) = (Arc::clone(&sources), Arc::clone(&stamp), output.clone());
+        tasks.push(task::spawn_blocking(move || -> Result<(), String> {
+            for source in &sources[files] {
+                let mut img = image::open(source).map_err(|e| format!("{}: {}", source.display(), e))?.to_rgba8();
+                let size = img.dimensions();
+                let row_len = size.0 as usize * 4;
+                let affected = affected_rows(size, stamp.dimensions(), placement);
+                let data: &mut [u8] = &mut img;
+                stamp_rows(&mut data[affected.start * row_len..affected.end * row_len], affected.start, size, &stamp, placement, opacity);
+                let path = output.join(source.file_name().expect("Listed sources are files"));
+                img.save(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+            Ok(())
+        }));
+    }
+
+    for task in tasks {
+        task.await.expect("Stamping task panicked")?;
+    }
+    Ok(BatchStats { images: sources.len() })
+}
@@ -0,0 +1,89 @@
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+use perf_event::events::Hardware;
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+use perf_event::{Builder, Counter};
+
+// Hardware counters around one phase. They are opened with `inherit`, so threads created
+// after `new` are counted too and their counts are folded in when they exit.
+pub struct Counters {
+    #[cfg(all(feature = "perf-counters", target_os = "linux"))]
+    counters: Vec<Counter>,
+}
+
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+const EVENTS: [Hardware; 4] = [
+    Hardware::INSTRUCTIONS,
+    Hardware::CPU_CYCLES,
+    Hardware::CACHE_REFERENCES,
+    Hardware::CACHE_MISSES,
+];
+
+#[cfg(not(all(feature = "perf-counters", target_os = "linux")))]
+impl Counters {
+    pub fn new() -> Result<Counters, String> {
+        Err("Performance counters require Linux and building with `--features perf-counters`".to_string())
+    }
+
+    pub fn enable(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn disable(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn report(&mut self) -> Result<String, String> {
+        Ok(String::new())
+    }
+}
+
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+impl Counters {
+    pub fn new() -> Result<Counters, String> {
+        let counters = EVENTS
+            .iter()
+            .map(|&event| {
+                let mut builder = Builder::new().kind(event);
+                builder.inherit(true);
+                builder.build()
+            })
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(|e| format!("Cannot open performance counters ({}); check /proc/sys/kernel/perf_event_paranoid", e))?;
+        Ok(Counters { counters })
+    }
+
+    pub fn enable(&mut self) -> Result<(), String> {
+        for counter in &mut self.counters {
+            counter.enable().map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn disable(&mut self) -> Result<(), String> {
+        for counter in &mut self.counters {
+            counter.disable().map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    // Counts are scaled up when the kernel had to multiplex the counters
+    pub fn report(&mut self) -> Result<String, String> {
+        let mut counts = [0.0; 4];
+        for (count, counter) in counts.iter_mut().zip(&mut self.counters) {
+            let value = counter.read_count_and_time().map_err(|e| e.to_string())?;
+            *count = if value.time_running == 0 {
+                0.0
+            } else {
+                value.count as f64 * value.time_enabled as f64 / value.time_running as f64
+            };
+        }
+
+        let [instructions, cycles, references, misses] = counts;
+        let ipc = if cycles > 0.0 { instructions / cycles } else { 0.0 };
+        let miss_rate = if references > 0.0 { 100.0 * misses / references } else { 0.0 };
+        Ok(format!(
+            "Counters: {:.3e} instructions, {:.3e} cycles, IPC {:.2}, {:.3e} cache misses ({:.1}% of {:.3e} references)",
+            instructions, cycles, ipc, misses, miss_rate, references,
+        ))
+    }
+}
@@ -0,0 +1,81 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::colorspace::{self, ColorSpace};
+use crate::integral::IntegralImage;
+use crate::kuwahara;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::task;
+use tracing::Instrument;
+
+// Rows of blocks split into one run per task, as the pixel rows each covers. The last block row
+// may be cut short by the bottom of the image.
+fn block_bands(height: usize, block: usize, parts: usize) -> Vec<Range<usize>> {
+    bands::split(height.div_ceil(block), parts)
+        .into_iter()
+        .map(|blocks| blocks.start * block..(blocks.end * block).min(height))
+        .collect()
+}
+
+// Mosaic: the image cut into squares of `block` pixels from the top left, each filled with its
+// mean color. The means come from a summed-area table, so a block costs four lookups whatever
+// its size. Every task owns a run of block rows and fills them. With `--alpha-weighted`
+// transparent pixels do not tint their block; alpha itself is kept.
+pub async fn apply_pixelate_async(img: &DynamicImage, block: i32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let block = u32::try_from(block).expect("block size must not be negative") as usize;
+    let src = Arc::new(img.to_rgba8());
+    if block <= 1 {
+        return DynamicImage::ImageRgba8(src.as_ref().clone());
+    }
+    let (width, height) = (src.width() as usize, src.height() as usize);
+    progress::expect(height);
+
+    // Averaged in RGB, linear with `--linear`; hue does not average
+    let rgb = FilterOptions { colorspace: ColorSpace::Rgb, ..filter };
+    let values = kuwahara::convert_to_space(Arc::clone(&src), rgb, num_tasks)
+        .instrument(tracing::info_span!("convert"))
+        .await;
+    let alpha: Option<Vec<u8>> = filter.alpha_weighted.then(|| src.pixels().map(|pixel| pixel[3]).collect());
+    let mut integral = IntegralImage::new(width, height);
+    tracing::info_span!("sat_build").in_scope(|| integral.build(&values, alpha.as_deref()));
+    let integral = Arc::new(integral);
+
+    let row_len = width * 4;
+    let mut tasks = Vec::new();
+    for (task_id, rows) in block_bands(height, block, num_tasks).into_iter().enumerate() {
+        let src = Arc::clone(&src);
+        let integral = Arc::clone(&integral);
+        tasks.push(task::spawn(async move {
+            let mut band = src.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            for top in rows.clone().step_by(block) {
+                let bottom = (top + block).min(rows.end);
+                for left in (0..width).step_by(block) {
+                    let right = (left + block).min(width);
+                    let Some((mean, _)) = integral.get_region_stats(left as i32, top as i32, right as i32 - 1, bottom as i32 - 1) else {
+                        continue;
+                    };
+                    let color = colorspace::from_space(mean.map(|mean| mean as f32), ColorSpace::Rgb, filter.linear);
+                    for y in top..bottom {
+                        let row = &mut band[(y - rows.start) * row_len..][..row_len];
+                        for pixel in row[left * 4..right * 4].chunks_exact_mut(4) {
+                            pixel[..3].copy_from_slice(&color);
+                        }
+                    }
+                }
+                progress::advance(bottom - top);
+            }
+            band
+        }
+        .instrument(tracing::debug_span!("task", id = task_id))));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width as u32, height as u32, data).expect("Pixelated buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
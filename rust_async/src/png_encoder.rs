@@ -0,0 +1,174 @@
+use crate::bands;
+use crate::metadata::png_chunk;
+use flate2::{Compress, Compression, FlushCompress, Status};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+use tokio::task;
+
+const ADLER_MOD: u32 = 65521;
+// Rows per compressed strip; smaller strips parallelize better but lose matches across strip edges
+const MIN_STRIP_ROWS: usize = 64;
+
+pub fn is_png(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".png")
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest run that cannot overflow `b` before reducing
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= ADLER_MOD;
+        b %= ADLER_MOD;
+    }
+    (b << 16) | a
+}
+
+// Checksum of the concatenation of two blocks, given the checksums and the second length (zlib's adler32_combine)
+fn adler32_combine(first: u32, second: u32, second_len: usize) -> u32 {
+    let rem = (second_len % ADLER_MOD as usize) as u32;
+    let a1 = first & 0xFFFF;
+    let b1 = first >> 16;
+    let a2 = second & 0xFFFF;
+    let b2 = second >> 16;
+
+    let a = (a1 + a2 + ADLER_MOD - 1) % ADLER_MOD;
+    let b = (b1 + b2 + (rem * a1) % ADLER_MOD + ADLER_MOD - rem) % ADLER_MOD;
+    (b << 16) | a
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// Filters one RGBA row with each PNG filter type and keeps the one with the smallest
+// sum of absolute residuals, the usual adaptive heuristic
+fn filter_row(row: &[u8], prev: &[u8], out: &mut Vec<u8>, scratch: &mut [Vec<u8>; 5]) {
+    let bpp = 4;
+    for filtered in scratch.iter_mut() {
+        filtered.resize(row.len(), 0);
+    }
+    let [none, sub, up, avg, paeth_row] = scratch;
+
+    none.copy_from_slice(row);
+    for i in 0..bpp {
+        sub[i] = row[i];
+        up[i] = row[i].wrapping_sub(prev[i]);
+        avg[i] = row[i].wrapping_sub(prev[i] / 2);
+        paeth_row[i] = row[i].wrapping_sub(prev[i]);
+    }
+    for i in bpp..row.len() {
+        let (left, above, upper_left) = (row[i - bpp], prev[i], prev[i - bpp]);
+        sub[i] = row[i].wrapping_sub(left);
+        up[i] = row[i].wrapping_sub(above);
+        avg[i] = row[i].wrapping_sub(((left as u16 + above as u16) / 2) as u8);
+        paeth_row[i] = row[i].wrapping_sub(paeth(left, above, upper_left));
+    }
+
+    let cost = |filtered: &Vec<u8>| filtered.iter().map(|&v| (v as i8).unsigned_abs() as u32).sum::<u32>();
+    let (kind, best) = scratch.iter().enumerate().min_by_key(|(_, filtered)| cost(filtered)).unwrap();
+    out.push(kind as u8);
+    out.extend_from_slice(best);
+}
+
+// Filters and deflates rows [start, end); every strip but the last ends with a sync flush so
+// the raw deflate streams can be concatenated into one
+fn compress_strip(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, start: usize, end: usize, last: bool) -> (Vec<u8>, u32, usize) {
+    let stride = img.width() as usize * 4;
+    let raw = img.as_raw();
+    let mut filtered = Vec::with_capacity((stride + 1) * (end - start));
+    let mut scratch: [Vec<u8>; 5] = Default::default();
+    // The row above the image is treated as zeros
+    let zeros = vec![0u8; stride];
+
+    for y in start..end {
+        let row = &raw[y * stride..(y + 1) * stride];
+        let prev = if y > 0 { &raw[(y - 1) * stride..y * stride] } else { &zeros };
+        filter_row(row, prev, &mut filtered, &mut scratch);
+    }
+
+    let mut compress = Compress::new(Compression::fast(), false);
+    let mut deflated = Vec::with_capacity(filtered.len() / 2 + 64);
+    let flush = if last { FlushCompress::Finish } else { FlushCompress::Sync };
+    loop {
+        if deflated.capacity() - deflated.len() < 1024 {
+            deflated.reserve(filtered.len() / 4 + 1024);
+        }
+        let consumed = compress.total_in() as usize;
+        let status = compress.compress_vec(&filtered[consumed..], &mut deflated, flush).expect("deflate failed");
+
+        // A flush is complete once all input is consumed and the output was not filled up
+        let drained = compress.total_in() as usize == filtered.len() && deflated.len() < deflated.capacity();
+        if status == Status::StreamEnd || (!last && drained) {
+            break;
+        }
+    }
+
+    (deflated, adler32(&filtered), filtered.len())
+}
+
+// Encodes an 8-bit RGBA PNG, filtering and compressing row strips on `num_tasks` tasks
+pub async fn save(path: &str, img: &DynamicImage, num_tasks: usize) -> io::Result<()> {
+    let img = Arc::new(img.to_rgba8());
+    let (width, height) = img.dimensions();
+    // Fewer strips on short images, so that each has at least MIN_STRIP_ROWS
+    let strips: Vec<(usize, usize)> = bands::split(height as usize, num_tasks.min(height as usize / MIN_STRIP_ROWS))
+        .into_iter()
+        .map(|rows| (rows.start, rows.end))
+        .collect();
+
+    let strip_count = strips.len();
+
+    let tasks: Vec<_> = strips
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, end))| {
+            let img = Arc::clone(&img);
+            task::spawn(async move { compress_strip(&img, start, end, i + 1 == strip_count) })
+        })
+        .collect();
+
+    let mut compressed = Vec::with_capacity(strip_count);
+    for task in tasks {
+        compressed.push(task.await.unwrap());
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8-bit RGBA, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+    writer.write_all(&png_chunk(b"IHDR", &ihdr))?;
+
+    // One IDAT per strip: the first carries the zlib header (deflate, 32K window, fastest)
+    // and the last the Adler-32 of all strips
+    let mut checksum = 1;
+    for (i, (deflated, adler, len)) in compressed.into_iter().enumerate() {
+        checksum = adler32_combine(checksum, adler, len);
+        let mut data = if i == 0 { vec![0x78, 0x01] } else { Vec::new() };
+        data.extend_from_slice(&deflated);
+        if i + 1 == strip_count {
+            data.extend_from_slice(&checksum.to_be_bytes());
+        }
+        writer.write_all(&png_chunk(b"IDAT", &data))?;
+    }
+
+    writer.write_all(&png_chunk(b"IEND", &[]))?;
+    writer.flush()
+}
@@ -0,0 +1,208 @@
+use crate::bands;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+use tokio::task;
+
+pub fn is_pnm(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    [".ppm", ".pgm", ".pnm", ".pam"].iter().any(|ext| lower.ends_with(ext))
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    channels: usize,
+    maxval: u32,
+    // 2 for maxvals above 255, stored big-endian
+    sample_bytes: usize,
+    src_row: usize,
+    data_offset: usize,
+}
+
+// Reads whitespace separated header tokens, skipping `#` comments
+fn next_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    loop {
+        while *pos < data.len() && data[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if data.get(*pos) != Some(&b'#') {
+            break;
+        }
+        while *pos < data.len() && data[*pos] != b'\n' {
+            *pos += 1;
+        }
+    }
+
+    let start = *pos;
+    while *pos < data.len() && !data[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    std::str::from_utf8(&data[start..*pos]).ok().filter(|token| !token.is_empty())
+}
+
+fn parse_number(data: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let token = next_token(data, pos).ok_or("Truncated PNM header")?;
+    token.parse().map_err(|_| format!("Invalid PNM header value: {}", token))
+}
+
+fn parse_header(data: &[u8]) -> Result<Header, String> {
+    let mut pos = 2;
+    let (width, height, channels, maxval) = match data.get(..2) {
+        Some(b"P5") | Some(b"P6") => {
+            let channels = if data[1] == b'5' { 1 } else { 3 };
+            let width = parse_number(data, &mut pos)?;
+            let height = parse_number(data, &mut pos)?;
+            let maxval = parse_number(data, &mut pos)?;
+            (width, height, channels, maxval)
+        }
+        Some(b"P7") => {
+            let (mut width, mut height, mut channels, mut maxval) = (0, 0, 0, 255);
+            loop {
+                match next_token(data, &mut pos).ok_or("Truncated PAM header")? {
+                    "WIDTH" => width = parse_number(data, &mut pos)?,
+                    "HEIGHT" => height = parse_number(data, &mut pos)?,
+                    "DEPTH" => channels = parse_number(data, &mut pos)?,
+                    "MAXVAL" => maxval = parse_number(data, &mut pos)?,
+                    "TUPLTYPE" => {
+                        next_token(data, &mut pos);
+                    }
+                    "ENDHDR" => break,
+                    other => return Err(format!("Unknown PAM header field: {}", other)),
+                }
+            }
+            (width, height, channels, maxval)
+        }
+        _ => return Err("Only binary PGM (P5), PPM (P6) and PAM (P7) files are supported".into()),
+    };
+
+    if !(1..=4).contains(&channels) || maxval == 0 || maxval > u16::MAX as usize {
+        return Err("Unsupported PNM channel count or maxval".into());
+    }
+
+    // The sizes come from the file, so they are checked rather than left to wrap
+    let sample_bytes = if maxval > 255 { 2 } else { 1 };
+    let too_large = || format!("PNM image too large: {}x{}", width, height);
+    let src_row = width.checked_mul(channels * sample_bytes).ok_or_else(too_large)?;
+    let src_len = src_row.checked_mul(height).ok_or_else(too_large)?;
+    if u32::try_from(width).is_err() || u32::try_from(height).is_err() || width.checked_mul(4).and_then(|row| row.checked_mul(height)).is_none() {
+        return Err(too_large());
+    }
+
+    // Exactly one whitespace byte separates the header from the samples
+    let data_offset = pos + 1;
+    if data.len().saturating_sub(data_offset) < src_len {
+        return Err("Truncated PNM pixel data".into());
+    }
+    Ok(Header {
+        width,
+        height,
+        channels,
+        maxval: maxval as u32,
+        sample_bytes,
+        src_row,
+        data_offset,
+    })
+}
+
+fn expand_pixel(src: &[u8], dst: &mut [u8], channels: usize) {
+    match channels {
+        1 => dst.copy_from_slice(&[src[0], src[0], src[0], 255]),
+        2 => dst.copy_from_slice(&[src[0], src[0], src[0], src[1]]),
+        3 => dst.copy_from_slice(&[src[0], src[1], src[2], 255]),
+        _ => dst.copy_from_slice(&src[..4]),
+    }
+}
+
+// Converts `height` rows of `src` (starting at `offset`) band by band on separate tasks
+async fn convert_bands<F>(
+    src: Arc<Vec<u8>>,
+    offset: usize,
+    src_row: usize,
+    dst_row: usize,
+    height: usize,
+    num_tasks: usize,
+    convert: F,
+) -> Vec<u8>
+where
+    F: Fn(&[u8], &mut [u8]) + Copy + Send + 'static,
+{
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height, num_tasks) {
+        let src = Arc::clone(&src);
+
+        tasks.push(task::spawn(async move {
+            let mut band = vec![0u8; rows.len() * dst_row];
+            convert(&src[offset + rows.start * src_row..offset + rows.end * src_row], &mut band);
+            band
+        }));
+    }
+
+    let mut dst = Vec::with_capacity(height * dst_row);
+    for task in tasks {
+        dst.extend_from_slice(&task.await.unwrap());
+    }
+    dst
+}
+
+pub async fn load(path: &str, num_tasks: usize) -> Result<DynamicImage, Box<dyn Error>> {
+    let data = fs::read(path)?;
+    let header = parse_header(&data)?;
+
+    let (sample_bytes, src_row) = (header.sample_bytes, header.src_row);
+
+    let (channels, maxval) = (header.channels, header.maxval);
+    let rgba = convert_bands(Arc::new(data), header.data_offset, src_row, header.width * 4, header.height, num_tasks, move |src, dst| {
+        // Rescale samples to 8 bits when maxval is not 255
+        let scale = 255.0 / maxval as f32;
+        let sample = |bytes: &[u8]| -> u8 {
+            let value = if sample_bytes == 2 { u16::from_be_bytes([bytes[0], bytes[1]]) as u32 } else { bytes[0] as u32 };
+            if maxval == 255 { value as u8 } else { (value as f32 * scale).round().min(255.0) as u8 }
+        };
+
+        let mut pixel = [0u8; 4];
+        for (src, dst) in src.chunks_exact(channels * sample_bytes).zip(dst.chunks_exact_mut(4)) {
+            for (ch, value) in pixel.iter_mut().enumerate().take(channels) {
+                *value = sample(&src[ch * sample_bytes..]);
+            }
+            expand_pixel(&pixel, dst, channels);
+        }
+    })
+    .await;
+
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(header.width as u32, header.height as u32, rgba)
+        .expect("PNM buffer matches dimensions");
+    Ok(DynamicImage::ImageRgba8(buffer))
+}
+
+pub async fn save(path: &str, img: &DynamicImage, num_tasks: usize) -> io::Result<()> {
+    let (width, height) = (img.width(), img.height());
+    let lower = path.to_ascii_lowercase();
+    let (header, channels) = if lower.ends_with(".pgm") {
+        (format!("P5\n{} {}\n255\n", width, height), 1)
+    } else if lower.ends_with(".pam") {
+        (format!("P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n", width, height), 4)
+    } else {
+        (format!("P6\n{} {}\n255\n", width, height), 3)
+    };
+
+    let rgba = Arc::new(img.to_rgba8().into_raw());
+    let samples = convert_bands(rgba, 0, width as usize * 4, width as usize * channels, height as usize, num_tasks, move |src, dst| {
+        for (src, dst) in src.chunks_exact(4).zip(dst.chunks_exact_mut(channels)) {
+            match channels {
+                // Rec. 601 luma for grayscale output
+                1 => dst[0] = ((src[0] as u32 * 299 + src[1] as u32 * 587 + src[2] as u32 * 114 + 500) / 1000) as u8,
+                _ => dst.copy_from_slice(&src[..channels]),
+            }
+        }
+    })
+    .await;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(header.as_bytes())?;
+    writer.write_all(&samples)?;
+    writer.flush()
+}
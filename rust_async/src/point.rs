@@ -0,0 +1,72 @@
+use crate::bands;
+use crate::channels;
+use crate::cli::FilterOptions;
+use crate::dither;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::task;
+
+// Nearest of `levels` values spread evenly over 0..=255, the same steps `dither` rounds to
+pub fn posterize_value(value: u8, levels: u32) -> u8 {
+    dither::quantize(value as i32, levels)
+}
+
+// The value raised to 1 / gamma over 0..=1, so a gamma above 1 brightens the midtones and one
+// below darkens them; black and white stay put
+pub fn gamma_value(value: u8, gamma: f64) -> u8 {
+    (255.0 * (value as f64 / 255.0).powf(1.0 / gamma)).round() as u8
+}
+
+// Every 8-bit value through `map` once, so the per-pixel work is three lookups
+fn table(map: impl Fn(u8) -> u8) -> [u8; 256] {
+    std::array::from_fn(|value| map(value as u8))
+}
+
+// Runs `map` over every pixel, one band of rows per task. The shared body of the point
+// operations, whose output pixel depends on nothing but the input pixel at the same place: with
+// no neighborhood to read and little arithmetic per pixel they are bound by memory bandwidth.
+pub async fn map_pixels_async<F>(img: &DynamicImage, num_tasks: usize, filter: FilterOptions, map: F) -> DynamicImage
+where
+    F: Fn([u8; 4]) -> [u8; 4] + Send + Sync + 'static,
+{
+    let src = Arc::new(img.to_rgba8());
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    let map = Arc::new(map);
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height as usize, num_tasks) {
+        let src = Arc::clone(&src);
+        let map = Arc::clone(&map);
+        tasks.push(task::spawn(async move {
+            let mut band = src.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            for pixel in band.chunks_exact_mut(4) {
+                let mapped = map([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                pixel.copy_from_slice(&mapped);
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Mapped buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
+
+// Rounds every color channel to one of `levels` values; alpha is kept
+pub async fn apply_posterize_async(img: &DynamicImage, levels: u32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let table = table(|value| posterize_value(value, levels));
+    map_pixels_async(img, num_tasks, filter, move |[r, g, b, a]| [table[r as usize], table[g as usize], table[b as usize], a]).await
+}
+
+// Applies the gamma curve to every color channel; alpha is kept
+pub async fn apply_gamma_async(img: &DynamicImage, gamma: f64, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let table = table(|value| gamma_value(value, gamma));
+    map_pixels_async(img, num_tasks, filter, move |[r, g, b, a]| [table[r as usize], table[g as usize], table[b as usize], a]).await
+}
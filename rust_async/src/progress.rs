@@ -0,0 +1,143 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Rows every filter pass has finished and announced, across all workers
+static DONE: AtomicUsize = AtomicUsize::new(0);
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+// Rows of one job, for servers filtering several images at once
+#[derive(Default)]
+pub struct Job {
+    done: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl Job {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn fraction(&self) -> f64 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        (self.done.load(Ordering::Relaxed) as f64 / total as f64).min(1.0)
+    }
+}
+
+tokio::task_local! {
+    static JOB: Arc<Job>;
+}
+
+// Filters run inside `future` count their rows into `job` instead of the global counters
+pub async fn track<F: Future>(job: Arc<Job>, future: F) -> F::Output {
+    JOB.scope(job, future).await
+}
+
+// Task locals are not inherited, so tasks spawned by a tracked filter carry its job along.
+// The job is looked up here, in the spawning task, rather than when the task first runs.
+pub fn inherit<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let job = JOB.try_with(Arc::clone).ok();
+    async move {
+        match job {
+            Some(job) => JOB.scope(job, future).await,
+            None => future.await,
+        }
+    }
+}
+
+// How often `--eta` prints an estimate
+pub const INTERVAL: Duration = Duration::from_secs(2);
+// Weight of the newest throughput sample in the moving estimate
+const SMOOTHING: f64 = 0.3;
+
+// Filters announce the rows they are about to process, over all of their passes
+pub fn expect(rows: usize) {
+    if JOB.try_with(|job| job.total.fetch_add(rows, Ordering::Relaxed)).is_err() {
+        TOTAL.fetch_add(rows, Ordering::Relaxed);
+    }
+}
+
+pub fn advance(rows: usize) {
+    if JOB.try_with(|job| job.done.fetch_add(rows, Ordering::Relaxed)).is_err() {
+        DONE.fetch_add(rows, Ordering::Relaxed);
+    }
+}
+
+// Filters check this between rows and stop early, leaving the rest of the image unfiltered
+pub fn cancelled() -> bool {
+    JOB.try_with(|job| job.is_cancelled()).unwrap_or(false)
+}
+
+pub fn reset() {
+    DONE.store(0, Ordering::Relaxed);
+    TOTAL.store(0, Ordering::Relaxed);
+}
+
+pub struct Progress {
+    pub fraction: f64,
+    // Unknown until some throughput has been measured
+    pub remaining: Option<Duration>,
+}
+
+impl Progress {
+    pub fn format(&self) -> String {
+        let done = format!("{:.0}% done", self.fraction * 100.0);
+        match self.remaining {
+            Some(remaining) if remaining.as_secs() >= 60 => format!("{}, ~{}m {}s remaining", done, remaining.as_secs() / 60, remaining.as_secs() % 60),
+            Some(remaining) => format!("{}, ~{}s remaining", done, remaining.as_secs()),
+            None => done,
+        }
+    }
+}
+
+// Samples the row counter on its own thread and hands an estimate to the callback every interval
+pub struct Reporter {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl Reporter {
+    pub fn start<F>(interval: Duration, mut callback: F) -> Reporter
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut last = (Instant::now(), DONE.load(Ordering::Relaxed));
+            let mut rate: Option<f64> = None;
+
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let now = (Instant::now(), DONE.load(Ordering::Relaxed));
+                let (done, total) = (now.1, TOTAL.load(Ordering::Relaxed));
+                if total == 0 {
+                    continue;
+                }
+
+                // Exponentially weighted rows per second, so a slow start or a stalled pass fades out
+                let sample = (now.1 - last.1) as f64 / (now.0 - last.0).as_secs_f64();
+                rate = Some(rate.map_or(sample, |rate| SMOOTHING * sample + (1.0 - SMOOTHING) * rate));
+                last = now;
+
+                let remaining = rate.filter(|&rate| rate > 0.0).map(|rate| Duration::from_secs_f64(total.saturating_sub(done) as f64 / rate));
+                callback(Progress { fraction: (done as f64 / total as f64).min(1.0), remaining });
+            }
+        });
+        Reporter { stop, handle }
+    }
+
+    pub fn stop(self) {
+        drop(self.stop);
+        self.handle.join().unwrap();
+    }
+}
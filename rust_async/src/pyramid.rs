@@ -0,0 +1,193 @@
+use crate::bands;
+use image::{imageops, DynamicImage, ImageBuffer, ImageFormat, Rgba};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::task;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // Deep Zoom (OpenSeadragon): `<name>.dzi` plus `<name>_files/<level>/<col>_<row>.<ext>`
+    DeepZoom,
+    // Slippy map (Leaflet): `<dir>/<zoom>/<x>/<y>.<ext>` with fixed-size tiles
+    Xyz,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dzi" => Ok(Layout::DeepZoom),
+            "xyz" => Ok(Layout::Xyz),
+            other => Err(format!("Unknown pyramid layout: {}", other)),
+        }
+    }
+}
+
+impl Layout {
+    pub fn default_tile_size(self) -> u32 {
+        match self {
+            Layout::DeepZoom => 254,
+            Layout::Xyz => 256,
+        }
+    }
+}
+
+pub struct PyramidStats {
+    pub levels: usize,
+    pub tiles: usize,
+}
+
+struct Tile {
+    level: usize,
+    col: u32,
+    row: u32,
+}
+
+// 2x2 box filter, rounding odd sizes up; rows are split into bands across tasks
+pub async fn halve(img: Arc<ImageBuffer<Rgba<u8>, Vec<u8>>>, num_tasks: usize) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (width, height) = img.dimensions();
+    let (half_width, half_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(half_height as usize, num_tasks) {
+        let img = Arc::clone(&img);
+
+        tasks.push(task::spawn(async move {
+            let mut band = Vec::with_capacity(rows.len() * half_width as usize * 4);
+            for y in rows.map(|y| y as u32 * 2) {
+                for x in (0..half_width).map(|x| x * 2) {
+                    let (x1, y1) = ((x + 1).min(width - 1), (y + 1).min(height - 1));
+                    for ch in 0..4 {
+                        let sum = img.get_pixel(x, y)[ch] as u32
+                            + img.get_pixel(x1, y)[ch] as u32
+                            + img.get_pixel(x, y1)[ch] as u32
+                            + img.get_pixel(x1, y1)[ch] as u32;
+                        band.push(((sum + 2) / 4) as u8);
+                    }
+                }
+            }
+            band
+        }));
+    }
+
+    let mut dst = Vec::with_capacity((half_width * half_height * 4) as usize);
+    for task in tasks {
+        dst.extend_from_slice(&task.await.unwrap());
+    }
+    ImageBuffer::from_raw(half_width, half_height, dst).expect("Halved buffer matches dimensions")
+}
+
+// Writes `img` as a tile pyramid; levels are reduced one after the other, then the tiles of
+// all levels are cropped and encoded by `num_tasks` tasks pulling from a shared queue
+pub async fn write_pyramid(
+    img: &DynamicImage,
+    output: &str,
+    layout: Layout,
+    tile_size: u32,
+    overlap: u32,
+    extension: &str,
+    num_tasks: usize,
+) -> Result<PyramidStats, Box<dyn Error>> {
+    ImageFormat::from_extension(extension).ok_or_else(|| format!("Unsupported tile format: {}", extension))?;
+    let tile_size = tile_size.max(1);
+    let overlap = if layout == Layout::DeepZoom { overlap } else { 0 };
+
+    // Deep Zoom halves down to a single pixel, slippy maps until the image fits one tile
+    let mut levels = vec![Arc::new(img.to_rgba8())];
+    loop {
+        let current = levels.last().unwrap();
+        let (width, height) = current.dimensions();
+        let done = match layout {
+            Layout::DeepZoom => width <= 1 && height <= 1,
+            Layout::Xyz => width <= tile_size && height <= tile_size,
+        };
+        if done {
+            break;
+        }
+        let next = halve(Arc::clone(current), num_tasks).await;
+        levels.push(Arc::new(next));
+    }
+    levels.reverse();
+
+    let root = match layout {
+        Layout::DeepZoom => {
+            let base = output.strip_suffix(".dzi").unwrap_or(output);
+            let (width, height) = (img.width(), img.height());
+            let descriptor = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" Format=\"{}\" Overlap=\"{}\" TileSize=\"{}\">\n  \
+                 <Size Width=\"{}\" Height=\"{}\"/>\n</Image>\n",
+                extension, overlap, tile_size, width, height
+            );
+            fs::write(format!("{}.dzi", base), descriptor)?;
+            PathBuf::from(format!("{}_files", base))
+        }
+        Layout::Xyz => PathBuf::from(output),
+    };
+
+    let mut tiles = Vec::new();
+    for (level, image) in levels.iter().enumerate() {
+        let (cols, rows) = (image.width().div_ceil(tile_size), image.height().div_ceil(tile_size));
+        for col in 0..cols {
+            let dir = match layout {
+                Layout::DeepZoom => root.join(level.to_string()),
+                Layout::Xyz => root.join(level.to_string()).join(col.to_string()),
+            };
+            fs::create_dir_all(dir)?;
+            tiles.extend((0..rows).map(|row| Tile { level, col, row }));
+        }
+    }
+
+    let tile_count = tiles.len();
+    let tiles = Arc::new(tiles);
+    let levels = Arc::new(levels);
+    let root = Arc::new(root);
+    let extension = Arc::new(extension.to_string());
+    let next = Arc::new(AtomicUsize::new(0));
+
+    let workers: Vec<_> = (0..num_tasks.max(1))
+        .map(|_| {
+            let (tiles, levels, root, extension, next) =
+                (Arc::clone(&tiles), Arc::clone(&levels), Arc::clone(&root), Arc::clone(&extension), Arc::clone(&next));
+
+            task::spawn(async move {
+                while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let image = &levels[tile.level];
+                    let x = (tile.col * tile_size).saturating_sub(overlap);
+                    let y = (tile.row * tile_size).saturating_sub(overlap);
+                    let right = ((tile.col + 1) * tile_size + overlap).min(image.width());
+                    let bottom = ((tile.row + 1) * tile_size + overlap).min(image.height());
+                    let mut pixels = imageops::crop_imm(image.as_ref(), x, y, right - x, bottom - y).to_image();
+
+                    // Slippy map viewers expect every tile at full size, so edge tiles are padded
+                    if layout == Layout::Xyz && (pixels.width() < tile_size || pixels.height() < tile_size) {
+                        let mut padded = ImageBuffer::new(tile_size, tile_size);
+                        imageops::replace(&mut padded, &pixels, 0, 0);
+                        pixels = padded;
+                    }
+
+                    let path = match layout {
+                        Layout::DeepZoom => root.join(tile.level.to_string()).join(format!("{}_{}.{}", tile.col, tile.row, extension)),
+                        Layout::Xyz => root.join(tile.level.to_string()).join(tile.col.to_string()).join(format!("{}.{}", tile.row, extension)),
+                    };
+                    pixels.save(path)?;
+                }
+                Ok::<(), image::ImageError>(())
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        worker.await.unwrap()?;
+    }
+
+    Ok(PyramidStats {
+        levels: levels.len(),
+        tiles: tile_count,
+    })
+}
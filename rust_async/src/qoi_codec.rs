@@ -0,0 +1,31 @@
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs;
+
+pub fn is_qoi(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".qoi")
+}
+
+// Decodes straight into RGBA, whatever the channel count stored in the file
+pub fn load(path: &str) -> Result<DynamicImage, Box<dyn Error>> {
+    let data = fs::read(path)?;
+    let mut decoder = qoi::Decoder::new(&data)?.with_channels(qoi::Channels::Rgba);
+    let (width, height) = (decoder.header().width, decoder.header().height);
+    let pixels = decoder.decode_to_vec()?;
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, pixels)
+        .ok_or("QOI buffer does not match its dimensions")?;
+    Ok(DynamicImage::ImageRgba8(buffer))
+}
+
+pub fn save(path: &str, img: &DynamicImage) -> Result<(), Box<dyn Error>> {
+    // Filters produce RGBA already, so this only copies for other layouts
+    let encoded = match img.as_rgba8() {
+        Some(rgba) => qoi::Encoder::new(rgba.as_raw(), rgba.width(), rgba.height())?.encode_to_vec()?,
+        None => {
+            let rgba = img.to_rgba8();
+            qoi::Encoder::new(rgba.as_raw(), rgba.width(), rgba.height())?.encode_to_vec()?
+        }
+    };
+    fs::write(path, encoded)?;
+    Ok(())
+}
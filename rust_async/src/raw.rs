@@ -0,0 +1,116 @@
+use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawFormat {
+    Rgba8,
+    Rgb8,
+    Gray8,
+    // Planar Y, U, V with 2x2 subsampled chroma (I420)
+    Yuv420p,
+    // Planar Y followed by interleaved UV with 2x2 subsampled chroma
+    Nv12,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawSpec {
+    pub format: RawFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for RawSpec {
+    type Err = String;
+
+    // Parses `<format>:<width>x<height>`, e.g. `rgba8:1920x1080`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, size) = s.split_once(':').ok_or("Expected <format>:<width>x<height>")?;
+        let (width, height) = size.split_once('x').ok_or("Expected <width>x<height>")?;
+
+        let format = match format {
+            "rgba8" => RawFormat::Rgba8,
+            "rgb8" => RawFormat::Rgb8,
+            "gray8" => RawFormat::Gray8,
+            "yuv420p" | "i420" => RawFormat::Yuv420p,
+            "nv12" => RawFormat::Nv12,
+            other => return Err(format!("Unknown raw format: {}", other)),
+        };
+
+        Ok(RawSpec {
+            format,
+            width: width.parse().map_err(|_| format!("Invalid width: {}", width))?,
+            height: height.parse().map_err(|_| format!("Invalid height: {}", height))?,
+        })
+    }
+}
+
+impl RawSpec {
+    pub fn frame_len(&self) -> usize {
+        let pixels = self.width as usize * self.height as usize;
+        let chroma = self.width.div_ceil(2) as usize * self.height.div_ceil(2) as usize;
+        match self.format {
+            RawFormat::Rgba8 => pixels * 4,
+            RawFormat::Rgb8 => pixels * 3,
+            RawFormat::Gray8 => pixels,
+            RawFormat::Yuv420p | RawFormat::Nv12 => pixels + 2 * chroma,
+        }
+    }
+}
+
+// BT.601 limited-range YCbCr to RGB, the usual encoding of camera and codec output
+fn yuv_to_rgba(y: u8, u: u8, v: u8) -> [u8; 4] {
+    let c = (y as f32 - 16.0) * 1.164;
+    let d = u as f32 - 128.0;
+    let e = v as f32 - 128.0;
+    [
+        (c + 1.596 * e).round().clamp(0.0, 255.0) as u8,
+        (c - 0.392 * d - 0.813 * e).round().clamp(0.0, 255.0) as u8,
+        (c + 2.017 * d).round().clamp(0.0, 255.0) as u8,
+        255,
+    ]
+}
+
+fn decode_yuv(spec: &RawSpec, data: &[u8]) -> Vec<u8> {
+    let width = spec.width as usize;
+    let height = spec.height as usize;
+    let chroma_width = width.div_ceil(2);
+    let chroma_len = chroma_width * height.div_ceil(2);
+    let (luma, chroma) = data.split_at(width * height);
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let c = (y / 2) * chroma_width + x / 2;
+            let (u, v) = match spec.format {
+                RawFormat::Nv12 => (chroma[c * 2], chroma[c * 2 + 1]),
+                _ => (chroma[c], chroma[chroma_len + c]),
+            };
+            rgba.extend_from_slice(&yuv_to_rgba(luma[y * width + x], u, v));
+        }
+    }
+    rgba
+}
+
+// Interprets the first frame of `data` as `spec`; RGBA input is used as is
+pub fn decode(spec: &RawSpec, mut data: Vec<u8>) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, String> {
+    let frame_len = spec.frame_len();
+    if data.len() < frame_len {
+        return Err(format!("Raw input holds {} bytes, {} needed for one frame", data.len(), frame_len));
+    }
+    data.truncate(frame_len);
+
+    let rgba = match spec.format {
+        RawFormat::Rgba8 => data,
+        RawFormat::Rgb8 => data.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        RawFormat::Gray8 => data.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        RawFormat::Yuv420p | RawFormat::Nv12 => decode_yuv(spec, &data),
+    };
+
+    Ok(ImageBuffer::from_raw(spec.width, spec.height, rgba).expect("Raw frame length matches dimensions"))
+}
+
+pub fn load(path: &str, spec: &RawSpec) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Box<dyn Error>> {
+    Ok(decode(spec, fs::read(path)?)?)
+}
@@ -0,0 +1,51 @@
+use image::{DynamicImage, ImageFormat};
+use std::error::Error;
+use std::io::Cursor;
+use tokio::task;
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// Spawns and joins trivial tasks so every runtime worker thread is up and polling
+async fn warm_up_runtime(num_tasks: usize) {
+    let tasks: Vec<_> = (0..num_tasks)
+        .map(|_| task::spawn(task::yield_now()))
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+// Downloads the input while the runtime warms up, so network latency overlaps startup cost
+pub async fn download(url: &str, num_tasks: usize) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let url = url.to_string();
+    let download = task::spawn(async move {
+        let response = reqwest::get(&url).await?.error_for_status()?;
+        response.bytes().await
+    });
+
+    warm_up_runtime(num_tasks).await;
+    Ok(download.await??.to_vec())
+}
+
+// Encodes in the format implied by the URL path extension, PNG when there is none
+pub fn encode_for_url(img: &DynamicImage, url: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let path = reqwest::Url::parse(url)?.path().to_string();
+    let format = ImageFormat::from_path(&path).unwrap_or(ImageFormat::Png);
+
+    let mut encoded = Vec::new();
+    img.write_to(&mut Cursor::new(&mut encoded), format)?;
+    Ok(encoded)
+}
+
+pub async fn upload(url: &str, body: Vec<u8>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    reqwest::Client::new()
+        .put(url)
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
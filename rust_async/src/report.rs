@@ -0,0 +1,346 @@
+use crate::bench::BenchReport;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    // Self-contained page with a table and an SVG chart per operation
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            other => Err(format!("Unknown report format: {}", other)),
+        }
+    }
+}
+
+// One median time, whichever log it came from
+pub struct Row {
+    pub implementation: String,
+    // Unknown for hyperfine commands given a name with `-n`
+    pub operation: Option<String>,
+    pub workers: Option<usize>,
+    pub median_ms: f64,
+}
+
+#[derive(Deserialize)]
+struct HyperfineResult {
+    command: String,
+    // Seconds
+    median: f64,
+}
+
+#[derive(Deserialize)]
+struct HyperfineExport {
+    results: Vec<HyperfineResult>,
+}
+
+// Names used in the results section for each implementation's program
+fn implementation_name(program: &str) -> String {
+    let file = Path::new(program).file_name().and_then(|f| f.to_str()).unwrap_or(program);
+    match file {
+        "filter_c" => "C",
+        "filter_go" => "Go",
+        "rust_filter" | "threads" => "Rust threads",
+        "rust_filter_async" | "tokio" => "Rust async",
+        "filter_odin" => "Odin",
+        "filter_zig" => "Zig",
+        "main.py" => "Python",
+        other => other,
+    }
+    .to_string()
+}
+
+// Commands look like the Makefile's `<program> <operation> <input> <output> <radius> <workers>`
+fn row_from_command(command: &str, median_ms: f64) -> Row {
+    let mut words: Vec<&str> = command.split_whitespace().collect();
+    if words.first().is_some_and(|w| w.starts_with("python")) {
+        words.remove(0);
+    }
+    if words.len() < 2 {
+        return Row { implementation: command.to_string(), operation: None, workers: None, median_ms };
+    }
+    Row {
+        implementation: implementation_name(words[0]),
+        operation: Some(words[1].to_string()),
+        workers: words.last().and_then(|w| w.parse().ok()),
+        median_ms,
+    }
+}
+
+fn rows_from_bench(report: BenchReport) -> Vec<Row> {
+    report
+        .measurements
+        .iter()
+        .map(|m| Row {
+            implementation: implementation_name(&report.backend),
+            operation: Some(report.operation.clone()),
+            workers: Some(m.workers),
+            median_ms: m.median(),
+        })
+        .collect()
+}
+
+fn rows_from_json(text: &str) -> Result<Vec<Row>, Box<dyn Error>> {
+    if let Ok(report) = serde_json::from_str::<BenchReport>(text) {
+        return Ok(rows_from_bench(report));
+    }
+    let export: HyperfineExport = serde_json::from_str(text)?;
+    Ok(export.results.iter().map(|r| row_from_command(&r.command, r.median * 1000.0)).collect())
+}
+
+// Splits one CSV line, honouring double-quoted fields
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+// hyperfine's `--export-csv`, with times in seconds
+fn rows_from_csv(text: &str) -> Result<Vec<Row>, Box<dyn Error>> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = split_csv(lines.next().ok_or("Empty CSV log")?);
+    let column = |name: &str| header.iter().position(|h| h == name).ok_or(format!("CSV log has no '{}' column", name));
+    let (command, median) = (column("command")?, column("median")?);
+
+    lines
+        .map(|line| {
+            let fields = split_csv(line);
+            let seconds: f64 = fields.get(median).and_then(|m| m.parse().ok()).ok_or(format!("Invalid CSV row: {}", line))?;
+            Ok(row_from_command(&fields[command], seconds * 1000.0))
+        })
+        .collect()
+}
+
+// Reads a `bench --json` result or a hyperfine JSON/CSV export; JSON files may also hold one result per line
+pub fn load(path: &str) -> Result<Vec<Row>, Box<dyn Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    if path.ends_with(".csv") {
+        return rows_from_csv(&text);
+    }
+    if let Ok(rows) = rows_from_json(&text) {
+        return Ok(rows);
+    }
+
+    let mut rows = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        rows.extend(rows_from_json(line).map_err(|e| format!("{}: {}", path, e))?);
+    }
+    Ok(rows)
+}
+
+fn format_time(ms: f64) -> String {
+    if ms < 1000.0 {
+        format!("{:.1} ms", ms)
+    } else if ms < 10_000.0 {
+        format!("{:.0} ms", ms)
+    } else {
+        format!("{:.1} s", ms / 1000.0)
+    }
+}
+
+fn worker_label(workers: Option<usize>) -> String {
+    match workers {
+        Some(1) => "1 Worker".to_string(),
+        Some(n) => format!("{} Workers", n),
+        None => "Time".to_string(),
+    }
+}
+
+// Median times of one operation, implementations as rows and worker counts as columns
+struct Table {
+    title: String,
+    workers: Vec<Option<usize>>,
+    // Fastest implementation first; later logs overwrite earlier ones for the same cell
+    rows: Vec<(String, Vec<Option<f64>>)>,
+}
+
+fn tables(rows: &[Row]) -> Vec<Table> {
+    let mut operations: Vec<Option<&String>> = Vec::new();
+    for row in rows {
+        if !operations.contains(&row.operation.as_ref()) {
+            operations.push(row.operation.as_ref());
+        }
+    }
+
+    operations
+        .into_iter()
+        .map(|operation| {
+            let rows: Vec<&Row> = rows.iter().filter(|r| r.operation.as_ref() == operation).collect();
+            let mut workers: Vec<Option<usize>> = Vec::new();
+            let mut implementations: Vec<&str> = Vec::new();
+            for row in &rows {
+                if !workers.contains(&row.workers) {
+                    workers.push(row.workers);
+                }
+                if !implementations.contains(&row.implementation.as_str()) {
+                    implementations.push(&row.implementation);
+                }
+            }
+            workers.sort();
+
+            let mut table_rows: Vec<(String, Vec<Option<f64>>)> = implementations
+                .iter()
+                .map(|&implementation| {
+                    let times = workers
+                        .iter()
+                        .map(|&w| rows.iter().rev().find(|r| r.implementation == implementation && r.workers == w).map(|r| r.median_ms))
+                        .collect();
+                    (implementation.to_string(), times)
+                })
+                .collect();
+            let best = |times: &Vec<Option<f64>>| times.iter().flatten().cloned().fold(f64::INFINITY, f64::min);
+            table_rows.sort_by(|a, b| best(&a.1).total_cmp(&best(&b.1)));
+
+            Table {
+                title: operation.cloned().unwrap_or_else(|| "Results".to_string()),
+                workers,
+                rows: table_rows,
+            }
+        })
+        .collect()
+}
+
+// Cells of one row, the fastest worker count of the implementation in bold
+fn cells(times: &[Option<f64>], bold: impl Fn(&str) -> String) -> Vec<String> {
+    let best = times.iter().flatten().cloned().fold(f64::INFINITY, f64::min);
+    times
+        .iter()
+        .map(|time| match time {
+            Some(ms) if *ms == best && times.iter().flatten().count() > 1 => bold(&format_time(*ms)),
+            Some(ms) => format_time(*ms),
+            None => "-".to_string(),
+        })
+        .collect()
+}
+
+pub fn render_markdown(rows: &[Row]) -> String {
+    let mut out = String::new();
+    for table in tables(rows) {
+        let mut grid = vec![std::iter::once("Implementation".to_string()).chain(table.workers.iter().map(|&w| worker_label(w))).collect::<Vec<_>>()];
+        for (implementation, times) in &table.rows {
+            let mut line = vec![format!("**{}**", implementation)];
+            line.extend(cells(times, |text| format!("**{}**", text)));
+            grid.push(line);
+        }
+
+        let widths: Vec<usize> = (0..grid[0].len()).map(|col| grid.iter().map(|line| line[col].len()).max().unwrap_or(0)).collect();
+        let format_line = |line: &[String]| {
+            let padded: Vec<String> = line.iter().zip(&widths).map(|(cell, &width)| format!("{:<width$}", cell, width = width)).collect();
+            format!("| {} |\n", padded.join(" | "))
+        };
+
+        out += &format!("### {}\n\n", table.title);
+        out += &format_line(&grid[0]);
+        out += &format_line(&widths.iter().map(|&width| "-".repeat(width)).collect::<Vec<_>>());
+        for line in &grid[1..] {
+            out += &format_line(line);
+        }
+        out += "\n";
+    }
+    out.trim_end().to_string()
+}
+
+const COLORS: [&str; 8] = ["#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#9c755f"];
+
+// Median time against worker count, one line per implementation, worker counts evenly spaced.
+// Times are on a log scale since implementations can be orders of magnitude apart
+fn render_chart(table: &Table) -> String {
+    let (width, height, margin) = (640.0, 320.0, 50.0);
+    let times = || table.rows.iter().flat_map(|(_, times)| times.iter().flatten()).cloned().filter(|&ms| ms > 0.0);
+    let (min, max) = (times().fold(f64::INFINITY, f64::min), times().fold(0.0, f64::max));
+    if max <= 0.0 {
+        return String::new();
+    }
+    let span = (max / min).ln().max(f64::EPSILON);
+    let columns = table.workers.len().max(2) - 1;
+    let x = |col: usize| margin + col as f64 * (width - 2.0 * margin) / columns as f64;
+    let y = |ms: f64| height - margin - (ms.max(min) / min).ln() / span * (height - 2.0 * margin);
+
+    let mut svg = format!("<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\" font-family=\"sans-serif\" font-size=\"12\">\n", width + 160.0, height);
+    svg += &format!(
+        "<line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/><line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\" stroke=\"#999\"/>\n",
+        m = margin,
+        b = height - margin,
+        r = width - margin,
+    );
+    svg += &format!("<text x=\"5\" y=\"{}\">{}</text><text x=\"5\" y=\"{}\">{}</text>\n", margin, format_time(max), height - margin, format_time(min));
+    for (col, &workers) in table.workers.iter().enumerate() {
+        svg += &format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n", x(col), height - margin + 20.0, worker_label(workers));
+    }
+
+    for (index, (implementation, times)) in table.rows.iter().enumerate() {
+        let color = COLORS[index % COLORS.len()];
+        let points: Vec<String> = times.iter().enumerate().filter_map(|(col, t)| t.map(|ms| format!("{:.1},{:.1}", x(col), y(ms)))).collect();
+        svg += &format!("<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>\n", color, points.join(" "));
+        for point in &points {
+            let (px, py) = point.split_once(',').unwrap();
+            svg += &format!("<circle cx=\"{}\" cy=\"{}\" r=\"3\" fill=\"{}\"/>\n", px, py, color);
+        }
+        let legend_y = margin + index as f64 * 18.0;
+        svg += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/><text x=\"{}\" y=\"{}\">{}</text>\n",
+            width,
+            legend_y - 10.0,
+            color,
+            width + 18.0,
+            legend_y,
+            escape(implementation),
+        );
+    }
+    svg + "</svg>\n"
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn render_html(rows: &[Row]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Benchmark results</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: right; }\n\
+         th:first-child, td:first-child { text-align: left; }\n\
+         </style>\n</head>\n<body>\n<h1>Benchmark results</h1>\n",
+    );
+    for table in tables(rows) {
+        out += &format!("<h2>{}</h2>\n<table>\n<tr><th>Implementation</th>", escape(&table.title));
+        for &workers in &table.workers {
+            out += &format!("<th>{}</th>", worker_label(workers));
+        }
+        out += "</tr>\n";
+        for (implementation, times) in &table.rows {
+            out += &format!("<tr><td>{}</td>", escape(implementation));
+            for cell in cells(times, |text| format!("<b>{}</b>", text)) {
+                out += &format!("<td>{}</td>", cell);
+            }
+            out += "</tr>\n";
+        }
+        out += "</table>\n";
+        out += &render_chart(&table);
+    }
+    out + "</body>\n</html>"
+}
@@ -0,0 +1,88 @@
+use crate::cli::FilterOptions;
+use crate::stream;
+use image::{imageops, DynamicImage};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+
+// The rectangle `--roi x,y,w,h` filters, such as a face to blur; the rest of the image is copied
+// through unchanged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Roi {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Roi {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s.split(',').map(|value| value.trim().parse::<u32>()).collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("Invalid region: {}", s))?;
+        match values[..] {
+            [_, _, 0, _] | [_, _, _, 0] => Err(format!("Region must not be empty: {}", s)),
+            [x, y, width, height] => Ok(Roi { x, y, width, height }),
+            _ => Err(format!("Expected x,y,w,h: {}", s)),
+        }
+    }
+}
+
+// The form `--roi` takes, so the bench can pass the region on
+impl fmt::Display for Roi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl Roi {
+    // The part of the rectangle inside an image of `width` by `height`, None when it misses it
+    pub fn clip(self, width: u32, height: u32) -> Option<Roi> {
+        let right = self.x.saturating_add(self.width).min(width);
+        let bottom = self.y.saturating_add(self.height).min(height);
+        (self.x < right && self.y < bottom).then(|| Roi { x: self.x, y: self.y, width: right - self.x, height: bottom - self.y })
+    }
+
+    // The rectangle grown by `margin` pixels on every side, stopping at the image's border
+    pub fn window(self, margin: usize, width: u32, height: u32) -> Roi {
+        let margin = u32::try_from(margin).unwrap_or(u32::MAX);
+        let (x, y) = (self.x.saturating_sub(margin), self.y.saturating_sub(margin));
+        let right = self.x.saturating_add(self.width).saturating_add(margin).min(width);
+        let bottom = self.y.saturating_add(self.height).saturating_add(margin).min(height);
+        Roi { x, y, width: right - x, height: bottom - y }
+    }
+
+    pub fn contains(self, x: u32, y: u32) -> bool {
+        (self.x..self.x.saturating_add(self.width)).contains(&x) && (self.y..self.y.saturating_add(self.height)).contains(&y)
+    }
+}
+
+// Pixels of context around the rectangle: the rows a band of `--stream` carries, and as many
+// columns, except that a convolution kernel may reach further across than down
+pub fn margin(operation: &str, radius: i32, filter: FilterOptions) -> usize {
+    match (operation, filter.kernel) {
+        ("convolve", Some(kernel)) => radius as usize + kernel.width.max(kernel.height) / 2,
+        _ => stream::overlap(operation, radius, filter),
+    }
+}
+
+// Runs `filter` over the rectangle alone, cut out with `margin` pixels of context around it so the
+// pixels along its sides read what they would in the whole image. The filter only sees that window,
+// so its tasks split the window's rows rather than the image's. A rectangle reaching past the
+// image is clipped to it, and one missing it leaves the image as it is.
+pub async fn apply<F, Fut>(src: &DynamicImage, roi: Roi, margin: usize, filter: F) -> DynamicImage
+where
+    F: FnOnce(DynamicImage) -> Fut,
+    Fut: Future<Output = DynamicImage>,
+{
+    let Some(roi) = roi.clip(src.width(), src.height()) else {
+        return src.clone();
+    };
+    let window = roi.window(margin, src.width(), src.height());
+    let filtered = filter(src.crop_imm(window.x, window.y, window.width, window.height)).await.to_rgba8();
+    let own = imageops::crop_imm(&filtered, roi.x - window.x, roi.y - window.y, roi.width, roi.height);
+    let mut result = src.to_rgba8();
+    imageops::replace(&mut result, &*own, roi.x as i64, roi.y as i64);
+    DynamicImage::ImageRgba8(result)
+}
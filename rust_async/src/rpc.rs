@@ -0,0 +1,333 @@
+// JSON-RPC 2.0, one message per line, for front-ends that keep the process running: over stdin and
+// stdout with `--rpc`, or over a Unix socket any number of local clients share with `daemon`.
+// Requests: `applyFilter` filters an image file into another and `cancel` stops a running `applyFilter`.
+// While a filter runs, `progress` notifications report the share of rows done.
+
+use crate::progress::{self, Job};
+use crate::serve;
+use image::{DynamicImage, GenericImageView};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::{self, JoinSet};
+use tokio::time::MissedTickBehavior;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Server defined: the image could not be read, filtered or saved
+const FILTER_FAILED: i64 = -32000;
+// Same code as the Language Server Protocol uses for cancelled requests
+const REQUEST_CANCELLED: i64 = -32800;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// Decoded inputs kept for requests that filter the same file again
+const CACHED_IMAGES: usize = 4;
+
+#[derive(Deserialize)]
+struct ApplyFilter {
+    operation: String,
+    input: PathBuf,
+    output: PathBuf,
+    radius: i32,
+    #[serde(default = "serve::default_tasks")]
+    tasks: usize,
+}
+
+#[derive(Deserialize)]
+struct Cancel {
+    id: Value,
+}
+
+struct Failure {
+    code: i64,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: i64, message: impl ToString) -> Failure {
+        Failure { code, message: message.to_string() }
+    }
+}
+
+// Decoded images by path, least recently used first, so a front-end re-filtering one file
+// (say, while a radius slider moves) only pays for decoding once
+#[derive(Default)]
+struct Cache {
+    entries: VecDeque<(PathBuf, SystemTime, Arc<DynamicImage>)>,
+}
+
+impl Cache {
+    fn get(&mut self, path: &PathBuf, modified: SystemTime) -> Option<Arc<DynamicImage>> {
+        let index = self.entries.iter().position(|(p, m, _)| p == path && *m == modified)?;
+        let entry = self.entries.remove(index)?;
+        let img = Arc::clone(&entry.2);
+        self.entries.push_back(entry);
+        Some(img)
+    }
+
+    fn insert(&mut self, path: PathBuf, modified: SystemTime, img: Arc<DynamicImage>) {
+        self.entries.retain(|(p, _, _)| *p != path);
+        self.entries.push_back((path, modified, img));
+        if self.entries.len() > CACHED_IMAGES {
+            self.entries.pop_front();
+        }
+    }
+}
+
+// Shared by every connection, so a daemon's clients reuse each other's decoded images
+struct Shared {
+    cache: Mutex<Cache>,
+    compute: Semaphore,
+}
+
+impl Shared {
+    fn new(max_concurrent: usize) -> io::Result<Arc<Shared>> {
+        if max_concurrent == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "--max-concurrent must be positive"));
+        }
+        Ok(Arc::new(Shared { cache: Mutex::new(Cache::default()), compute: Semaphore::new(max_concurrent) }))
+    }
+}
+
+// One connection. Request ids are the client's own, so only its requests can be cancelled.
+struct Rpc {
+    out: mpsc::UnboundedSender<Value>,
+    // Running `applyFilter` requests by their id
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+    shared: Arc<Shared>,
+}
+
+impl Rpc {
+    fn send(&self, message: Value) {
+        // Only fails once stdout is gone, and then there is nobody left to tell
+        let _ = self.out.send(message);
+    }
+
+    fn respond(&self, id: Option<Value>, outcome: Result<Value, Failure>) {
+        // Notifications get no response
+        let Some(id) = id else {
+            return;
+        };
+        match outcome {
+            Ok(result) => self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+            Err(failure) => self.send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": failure.code, "message": failure.message },
+            })),
+        }
+    }
+}
+
+async fn load(rpc: &Rpc, path: PathBuf) -> Result<(Arc<DynamicImage>, bool), Failure> {
+    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).map_err(|e| Failure::new(FILTER_FAILED, format!("{}: {}", path.display(), e)))?;
+    if let Some(img) = rpc.shared.cache.lock().unwrap().get(&path, modified) {
+        return Ok((img, true));
+    }
+
+    let img = task::spawn_blocking({
+        let path = path.clone();
+        move || image::open(path)
+    })
+    .await
+    .expect("Decoder panicked")
+    .map_err(|e| Failure::new(FILTER_FAILED, format!("{}: {}", path.display(), e)))?;
+    let img = Arc::new(img);
+    rpc.shared.cache.lock().unwrap().insert(path, modified, Arc::clone(&img));
+    Ok((img, false))
+}
+
+async fn apply_filter(rpc: Arc<Rpc>, id: Option<Value>, params: ApplyFilter, job: Arc<Job>) -> Result<Value, Failure> {
+    let _slot = rpc.shared.compute.acquire().await.expect("Semaphore is never closed");
+    if job.is_cancelled() {
+        return Err(Failure::new(REQUEST_CANCELLED, "Request cancelled"));
+    }
+
+    let start = Instant::now();
+    let (img, cached) = load(&rpc, params.input).await?;
+    let load_time = start.elapsed();
+
+    let ticker = id.map(|id| {
+        let (rpc, job) = (Arc::clone(&rpc), Arc::clone(&job));
+        task::spawn(async move {
+            let mut ticks = tokio::time::interval(PROGRESS_INTERVAL);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticks.tick().await;
+                rpc.send(json!({ "jsonrpc": "2.0", "method": "progress", "params": { "id": id, "fraction": job.fraction() } }));
+            }
+        })
+    });
+    let start = Instant::now();
+    let result = progress::track(Arc::clone(&job), serve::apply(&params.operation, &img, params.radius, params.tasks)).await;
+    let filter_time = start.elapsed();
+    if let Some(ticker) = ticker {
+        ticker.abort();
+    }
+    // The filter stopped early and the image is only partly filtered
+    if job.is_cancelled() {
+        return Err(Failure::new(REQUEST_CANCELLED, "Request cancelled"));
+    }
+
+    let start = Instant::now();
+    let (width, height) = result.dimensions();
+    let output = params.output;
+    task::spawn_blocking(move || result.save(&output).map_err(|e| Failure::new(FILTER_FAILED, format!("{}: {}", output.display(), e))))
+        .await
+        .expect("Encoder panicked")?;
+
+    Ok(json!({
+        "width": width,
+        "height": height,
+        "cached": cached,
+        "loadMs": load_time.as_millis() as u64,
+        "filterMs": filter_time.as_millis() as u64,
+        "saveMs": start.elapsed().as_millis() as u64,
+    }))
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, Failure> {
+    serde_json::from_value(params).map_err(|e| Failure::new(INVALID_PARAMS, e))
+}
+
+fn handle(rpc: &Arc<Rpc>, message: Value, running: &mut JoinSet<()>) {
+    let id = message.get("id").cloned();
+    let (Some(method), true) = (message.get("method").and_then(Value::as_str), message.get("jsonrpc") == Some(&json!("2.0"))) else {
+        rpc.respond(Some(id.unwrap_or(Value::Null)), Err(Failure::new(INVALID_REQUEST, "Not a JSON-RPC 2.0 request")));
+        return;
+    };
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+    match method {
+        "applyFilter" => {
+            let params: ApplyFilter = match parse_params(params) {
+                Ok(params) => params,
+                Err(failure) => return rpc.respond(id, Err(failure)),
+            };
+            if let Err(message) = serve::check_request(&params.operation, params.radius, params.tasks) {
+                return rpc.respond(id, Err(Failure::new(INVALID_PARAMS, message)));
+            }
+
+            let job = Arc::new(Job::default());
+            if let Some(id) = &id {
+                rpc.jobs.lock().unwrap().insert(id.to_string(), Arc::clone(&job));
+            }
+            let rpc = Arc::clone(rpc);
+            running.spawn(async move {
+                let outcome = apply_filter(Arc::clone(&rpc), id.clone(), params, job).await;
+                if let Some(id) = &id {
+                    rpc.jobs.lock().unwrap().remove(&id.to_string());
+                }
+                rpc.respond(id, outcome);
+            });
+        }
+        "cancel" => {
+            let outcome = parse_params::<Cancel>(params).map(|cancel| {
+                let job = rpc.jobs.lock().unwrap().get(&cancel.id.to_string()).cloned();
+                if let Some(job) = &job {
+                    job.cancel();
+                }
+                json!({ "cancelled": job.is_some() })
+            });
+            rpc.respond(id, outcome);
+        }
+        other => rpc.respond(id, Err(Failure::new(METHOD_NOT_FOUND, format!("Unknown method: {}", other)))),
+    }
+}
+
+// Serves requests until `input` closes, then finishes the ones already running
+async fn session(input: impl AsyncRead + Unpin, mut output: impl AsyncWrite + Unpin + Send + 'static, shared: Arc<Shared>) -> io::Result<()> {
+    let (out, mut outgoing) = mpsc::unbounded_channel::<Value>();
+    // One writer, so concurrent responses and notifications never interleave within a line
+    let writer = task::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            let mut line = serde_json::to_vec(&message).expect("Messages serialize");
+            line.push(b'\n');
+            output.write_all(&line).await?;
+            output.flush().await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+
+    let rpc = Arc::new(Rpc { out, jobs: Mutex::new(HashMap::new()), shared });
+    let mut running = JoinSet::new();
+    let mut lines = BufReader::new(input).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(message) => handle(&rpc, message, &mut running),
+            Err(e) => rpc.respond(Some(Value::Null), Err(Failure::new(PARSE_ERROR, e))),
+        }
+        // Reap finished requests so the set does not grow with every one served
+        while running.try_join_next().is_some() {}
+    }
+
+    while running.join_next().await.is_some() {}
+    drop(rpc);
+    writer.await.expect("Writer panicked")
+}
+
+// Serves requests on stdin until it closes
+pub async fn run(max_concurrent: usize) -> io::Result<()> {
+    session(tokio::io::stdin(), tokio::io::stdout(), Shared::new(max_concurrent)?).await
+}
+
+pub fn default_socket() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    dir.join("rust_filter_async.sock")
+}
+
+// Serves clients connecting to the socket at `path` until interrupted with Ctrl-C. The runtime's
+// worker threads and the decoded images stay warm between requests, so repeated filters of one
+// file (say, a parameter sweep) pay for neither decoding nor spawning threads again.
+#[cfg(unix)]
+pub async fn serve(path: &Path, max_concurrent: usize) -> io::Result<()> {
+    use tokio::net::{UnixListener, UnixStream};
+
+    let shared = Shared::new(max_concurrent)?;
+    // A socket file nobody answers on is left over from a daemon that did not shut down cleanly
+    if path.exists() {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("a daemon is already listening on {}", path.display())));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    println!("Serving JSON-RPC on {} with {} filters at once", path.display(), max_concurrent);
+
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+    let result = loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => break Err(e),
+            },
+            _ = &mut shutdown => break Ok(()),
+        };
+        let (input, output) = stream.into_split();
+        let shared = Arc::clone(&shared);
+        task::spawn(async move {
+            if let Err(e) = session(input, output, shared).await {
+                eprintln!("Warning: client connection failed: {}", e);
+            }
+        });
+    };
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+#[cfg(not(unix))]
+pub async fn serve(_path: &Path, _max_concurrent: usize) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the daemon needs Unix domain sockets"))
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+struct WorkerSnapshot {
+    busy: Duration,
+    parks: u64,
+    #[cfg(tokio_unstable)]
+    polls: u64,
+    #[cfg(tokio_unstable)]
+    steals: u64,
+}
+
+// Cumulative runtime counters, diffed between two points to cover one phase
+pub struct Snapshot {
+    workers: Vec<WorkerSnapshot>,
+    #[cfg(tokio_unstable)]
+    spawned_tasks: u64,
+}
+
+impl Snapshot {
+    pub fn take() -> Snapshot {
+        let metrics = Handle::current().metrics();
+        let workers = (0..metrics.num_workers())
+            .map(|worker| WorkerSnapshot {
+                busy: metrics.worker_total_busy_duration(worker),
+                parks: metrics.worker_park_count(worker),
+                #[cfg(tokio_unstable)]
+                polls: metrics.worker_poll_count(worker),
+                #[cfg(tokio_unstable)]
+                steals: metrics.worker_steal_count(worker),
+            })
+            .collect();
+
+        Snapshot {
+            workers,
+            #[cfg(tokio_unstable)]
+            spawned_tasks: metrics.spawned_tasks_count(),
+        }
+    }
+
+    // Summarises what the runtime did since `self` was taken
+    pub fn report(&self) -> String {
+        let metrics = Handle::current().metrics();
+        let now = Snapshot::take();
+        let mut out = format!(
+            "Runtime: {} workers, {} alive tasks, global queue depth {}",
+            metrics.num_workers(),
+            metrics.num_alive_tasks(),
+            metrics.global_queue_depth(),
+        );
+
+        #[cfg(tokio_unstable)]
+        {
+            out += &format!(", {} tasks spawned", now.spawned_tasks - self.spawned_tasks);
+        }
+
+        for (worker, (before, after)) in self.workers.iter().zip(&now.workers).enumerate() {
+            out += &format!(
+                "\n  worker {}: busy {}ms, parked {} times",
+                worker,
+                (after.busy - before.busy).as_millis(),
+                after.parks - before.parks,
+            );
+
+            #[cfg(tokio_unstable)]
+            {
+                let polls = after.polls - before.polls;
+                out += &format!(
+                    ", {} polls, {} steals, mean poll {}us",
+                    polls,
+                    after.steals - before.steals,
+                    metrics.worker_mean_poll_time(worker).as_micros(),
+                );
+            }
+        }
+
+        #[cfg(not(tokio_unstable))]
+        {
+            out += "\n  (build with RUSTFLAGS=\"--cfg tokio_unstable\" for poll, steal and spawn counts)";
+        }
+
+        out
+    }
+}
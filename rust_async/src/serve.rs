@@ -0,0 +1,354 @@
+use crate::blur::apply_gaussian_blur_async;
+use crate::cli::FilterOptions;
+use crate::kuwahara::apply_kuwahara_filter_async;
+use crate::progress;
+use crate::size;
+use axum::body::{Body, Bytes};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, GenericImageView, ImageEncoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufWriter, Cursor, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio::task;
+use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::ReceiverStream;
+
+pub const DEFAULT_PORT: u16 = 8080;
+// Requests waiting for a filter slot before new ones are turned away with 503
+pub const DEFAULT_QUEUE_DEPTH: usize = 64;
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+// Encoded output is handed to the connection in chunks of this size
+const CHUNK_BYTES: usize = 64 * 1024;
+// Chunks buffered ahead of a slow client before the encoder waits for it
+const CHUNKS_IN_FLIGHT: usize = 4;
+// /live previews at 1/8 and 1/4 of the full size, before the full resolution result
+const PREVIEW_DIVISORS: [u32; 2] = [8, 4];
+// Previews smaller than this on either side are skipped
+const MIN_PREVIEW_SIZE: u32 = 16;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// Largest radius a request may ask for, checked before anything is decoded. The filters cap a
+// radius at the image's longest side anyway, so this only turns away absurd requests
+pub const MAX_RADIUS: i32 = 1 << 16;
+
+#[derive(Deserialize)]
+struct FilterQuery {
+    operation: String,
+    radius: i32,
+    #[serde(default = "default_tasks")]
+    tasks: usize,
+    format: Option<String>,
+}
+
+pub fn default_tasks() -> usize {
+    4
+}
+
+// Bounds the requests a server filters at once and the ones it lets wait, shared with the gRPC service
+pub struct Limits {
+    // Held from the moment a request is accepted until its response is fully sent
+    admission: Arc<Semaphore>,
+    // Held while a request decodes, filters and encodes
+    compute: Arc<Semaphore>,
+}
+
+impl Limits {
+    pub fn new(max_concurrent: usize, queue_depth: usize) -> io::Result<Limits> {
+        if max_concurrent == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "--max-concurrent must be positive"));
+        }
+        Ok(Limits {
+            admission: Arc::new(Semaphore::new(max_concurrent + queue_depth)),
+            compute: Arc::new(Semaphore::new(max_concurrent)),
+        })
+    }
+
+    // None when the queue is full
+    pub fn admit(&self) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.admission).try_acquire_owned().ok()
+    }
+
+    pub async fn compute_slot(&self) -> OwnedSemaphorePermit {
+        Arc::clone(&self.compute).acquire_owned().await.expect("Semaphore is never closed")
+    }
+}
+
+type Rejection = (StatusCode, String);
+
+fn bad_request(message: impl ToString) -> Rejection {
+    (StatusCode::BAD_REQUEST, message.to_string())
+}
+
+// Forwards encoder output to the response body, blocking while the client falls behind
+struct ChannelWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn encode(img: &DynamicImage, format: ImageFormat, writer: impl Write) -> image::ImageResult<()> {
+    let (width, height) = img.dimensions();
+    match format {
+        ImageFormat::Jpeg => JpegEncoder::new_with_quality(writer, 90).write_image(img.to_rgb8().as_raw(), width, height, image::ColorType::Rgb8),
+        _ => PngEncoder::new(writer).write_image(img.as_bytes(), width, height, img.color()),
+    }
+}
+
+// PNG or JPEG as requested, otherwise the uploaded image's format when it is one of them
+pub fn output_format(format: Option<&str>, input: &[u8]) -> Result<ImageFormat, String> {
+    match format {
+        Some("png") => Ok(ImageFormat::Png),
+        Some("jpeg" | "jpg") => Ok(ImageFormat::Jpeg),
+        Some(other) => Err(format!("Unknown format: {}. Use 'png' or 'jpeg'", other)),
+        None => Ok(match image::guess_format(input) {
+            Ok(ImageFormat::Jpeg) => ImageFormat::Jpeg,
+            _ => ImageFormat::Png,
+        }),
+    }
+}
+
+pub fn is_operation(operation: &str) -> bool {
+    matches!(operation, "blur" | "kuwahara")
+}
+
+pub async fn apply(operation: &str, img: &DynamicImage, radius: i32, tasks: usize) -> DynamicImage {
+    match operation {
+        "blur" => apply_gaussian_blur_async(img, radius as u32, tasks, FilterOptions::default()).await,
+        _ => apply_kuwahara_filter_async(img, radius, tasks, FilterOptions::default()).await,
+    }
+}
+
+// Checks a request's parameters before its image is touched, shared by every front end that takes
+// them from the network
+pub fn check_request(operation: &str, radius: i32, tasks: usize) -> Result<(), String> {
+    if !is_operation(operation) {
+        return Err(format!("Unknown operation: {}. Use 'blur' or 'kuwahara'", operation));
+    }
+    if !(0..=MAX_RADIUS).contains(&radius) || tasks == 0 {
+        return Err(format!("radius must be between 0 and {} and tasks must be positive", MAX_RADIUS));
+    }
+    Ok(())
+}
+
+fn validate(query: &FilterQuery) -> Result<(), Rejection> {
+    check_request(&query.operation, query.radius, query.tasks).map_err(bad_request)
+}
+
+// Reads the dimensions from the header first, so an upload whose filter buffers would not fit is
+// refused with 413 before anything is decoded
+pub fn decode(operation: &str, data: &[u8]) -> Result<DynamicImage, Rejection> {
+    let (width, height) = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(bad_request)?
+        .into_dimensions()
+        .map_err(bad_request)?;
+    size::check(operation, width, height, FilterOptions::default()).map_err(|message| (StatusCode::PAYLOAD_TOO_LARGE, message))?;
+    image::load_from_memory(data).map_err(bad_request)
+}
+
+fn too_busy() -> Rejection {
+    (StatusCode::SERVICE_UNAVAILABLE, "Too many requests queued, retry later".to_string())
+}
+
+async fn filter(State(limits): State<Arc<Limits>>, Query(query): Query<FilterQuery>, body: Bytes) -> Result<Response, Rejection> {
+    validate(&query)?;
+    let format = output_format(query.format.as_deref(), &body).map_err(bad_request)?;
+
+    let admitted = limits.admit().ok_or_else(too_busy)?;
+    let computing = limits.compute_slot().await;
+
+    // Decoding and encoding are blocking work, kept off the runtime's worker threads
+    let operation = query.operation.clone();
+    let img = task::spawn_blocking(move || decode(&operation, &body))
+        .await
+        .expect("Decoder panicked")?;
+
+    let start = Instant::now();
+    let result = apply(&query.operation, &img, query.radius, query.tasks).await;
+    let filter_time = start.elapsed();
+    let (width, height) = result.dimensions();
+    println!("{} radius {} on {}x{} with {} tasks: {}ms", query.operation, query.radius, width, height, query.tasks, filter_time.as_millis());
+
+    // The permits move into the encoder so a request counts against the limits until its last byte is sent
+    let (sender, receiver) = mpsc::channel(CHUNKS_IN_FLIGHT);
+    task::spawn_blocking(move || {
+        let _permits = (admitted, computing);
+        let mut writer = BufWriter::with_capacity(CHUNK_BYTES, ChannelWriter(sender.clone()));
+        let encoded = encode(&result, format, &mut writer).map_err(io::Error::other).and_then(|_| writer.flush());
+        if let Err(e) = encoded {
+            // Headers are already sent, so the failure can only abort the body
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.to_mime_type().to_string()),
+            (header::HeaderName::from_static("server-timing"), format!("filter;dur={}", filter_time.as_millis())),
+        ],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response())
+}
+
+// Text messages of the /live protocol; `preview` and `result` are each followed by a binary message with the image
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Event {
+    Progress { fraction: f64 },
+    Preview { scale: f64, width: u32, height: u32 },
+    Result { width: u32, height: u32, filter_ms: u128 },
+    Error { message: String },
+}
+
+async fn send_event(socket: &mut WebSocket, event: &Event) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(event).expect("Events serialize");
+    socket.send(Message::Text(text.into())).await
+}
+
+async fn send_error(socket: &mut WebSocket, message: String) -> Result<(), axum::Error> {
+    send_event(socket, &Event::Error { message }).await?;
+    socket.send(Message::Close(Some(CloseFrame { code: close_code::ERROR, reason: "".into() }))).await
+}
+
+async fn send_image(socket: &mut WebSocket, event: Event, image: Vec<u8>) -> Result<(), axum::Error> {
+    send_event(socket, &event).await?;
+    socket.send(Message::Binary(image.into())).await
+}
+
+async fn encode_to_vec(img: DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    task::spawn_blocking(move || {
+        let mut encoded = Vec::new();
+        encode(&img, format, &mut encoded).map(|_| encoded)
+    })
+    .await
+    .expect("Encoder panicked")
+    .map_err(|e| e.to_string())
+}
+
+// Filters a downscaled copy, with the radius scaled to match so the preview looks like the result
+async fn preview(img: Arc<DynamicImage>, divisor: u32, query: &FilterQuery, format: ImageFormat) -> Result<(Event, Vec<u8>), String> {
+    let (width, height) = (img.width() / divisor, img.height() / divisor);
+    let small = task::spawn_blocking(move || img.resize_exact(width, height, FilterType::Triangle))
+        .await
+        .expect("Resize panicked");
+    let radius = if query.radius == 0 { 0 } else { (query.radius as u32).div_ceil(divisor) as i32 };
+    let filtered = apply(&query.operation, &small, radius, query.tasks).await;
+    let event = Event::Preview { scale: 1.0 / divisor as f64, width, height };
+    Ok((event, encode_to_vec(filtered, format).await?))
+}
+
+async fn live_job(mut socket: WebSocket, limits: Arc<Limits>, query: FilterQuery) -> Result<(), axum::Error> {
+    // The image arrives as the first binary message
+    let body = loop {
+        match socket.recv().await {
+            Some(Ok(Message::Binary(data))) => break data,
+            Some(Ok(Message::Close(_))) | None => return Ok(()),
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e),
+        }
+    };
+    let format = match output_format(query.format.as_deref(), &body) {
+        Ok(format) => format,
+        Err(message) => return send_error(&mut socket, message).await,
+    };
+
+    let computing = limits.compute_slot().await;
+    let operation = query.operation.clone();
+    let img = match task::spawn_blocking(move || decode(&operation, &body)).await.expect("Decoder panicked") {
+        Ok(img) => Arc::new(img),
+        Err((_, message)) => return send_error(&mut socket, message).await,
+    };
+
+    // Previews cost a small fraction of the full job, and would queue behind its tasks if they ran alongside it
+    for divisor in PREVIEW_DIVISORS {
+        if img.width() / divisor < MIN_PREVIEW_SIZE || img.height() / divisor < MIN_PREVIEW_SIZE {
+            continue;
+        }
+        match preview(Arc::clone(&img), divisor, &query, format).await {
+            Ok((event, image)) => send_image(&mut socket, event, image).await?,
+            Err(message) => return send_error(&mut socket, message).await,
+        }
+    }
+
+    // The full resolution job keeps the compute slot until it is done, even if the client goes away first
+    let job = Arc::new(progress::Job::default());
+    let mut full = task::spawn(progress::track(Arc::clone(&job), {
+        let (operation, radius, tasks) = (query.operation.clone(), query.radius, query.tasks);
+        async move {
+            let _computing = computing;
+            let start = Instant::now();
+            let result = apply(&operation, &img, radius, tasks).await;
+            (result, start.elapsed())
+        }
+    }));
+
+    // Filter tasks can hold every worker for a while, and the ticks missed meanwhile are not worth sending
+    let mut ticks = tokio::time::interval(PROGRESS_INTERVAL);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let (result, filter_time) = loop {
+        tokio::select! {
+            joined = &mut full => break joined.expect("Filter panicked"),
+            _ = ticks.tick() => send_event(&mut socket, &Event::Progress { fraction: job.fraction() }).await?,
+        }
+    };
+    let (width, height) = result.dimensions();
+    println!("{} radius {} on {}x{} with {} tasks over /live: {}ms", query.operation, query.radius, width, height, query.tasks, filter_time.as_millis());
+
+    match encode_to_vec(result, format).await {
+        Ok(image) => send_image(&mut socket, Event::Result { width, height, filter_ms: filter_time.as_millis() }, image).await?,
+        Err(message) => return send_error(&mut socket, message).await,
+    }
+    socket.send(Message::Close(Some(CloseFrame { code: close_code::NORMAL, reason: "".into() }))).await
+}
+
+async fn live(State(limits): State<Arc<Limits>>, Query(query): Query<FilterQuery>, upgrade: WebSocketUpgrade) -> Result<Response, Rejection> {
+    validate(&query)?;
+    // Refused before the upgrade, so clients see the same 503 as on /filter
+    let admitted = limits.admit().ok_or_else(too_busy)?;
+    Ok(upgrade.max_message_size(MAX_BODY_BYTES).on_upgrade(move |socket| async move {
+        let _admitted = admitted;
+        if let Err(e) = live_job(socket, limits, query).await {
+            eprintln!("Warning: /live connection failed: {}", e);
+        }
+    }))
+}
+
+// Filters images POSTed to /filter?operation=blur&radius=5, or sent over a WebSocket to /live
+// with the same query, until interrupted with Ctrl-C
+pub async fn run(port: u16, max_concurrent: usize, queue_depth: usize) -> io::Result<()> {
+    let limits = Arc::new(Limits::new(max_concurrent, queue_depth)?);
+    let app = Router::new()
+        .route("/filter", post(filter))
+        .route("/live", get(live))
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(limits);
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    let address = listener.local_addr()?;
+    println!("Listening on http://{}/filter and ws://{}/live with {} filters at once and {} queued", address, address, max_concurrent, queue_depth);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
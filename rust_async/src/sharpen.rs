@@ -0,0 +1,62 @@
+use crate::bands;
+use crate::blur::apply_gaussian_blur_async;
+use crate::channels::{self, Channels};
+use crate::cli::FilterOptions;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::task;
+
+// One channel of the unsharp mask: the value pushed `amount` times its difference from the blurred
+// value further away from it. Differences of at most `threshold` levels are left alone, so noise
+// and smooth gradients are not sharpened along with the edges.
+pub fn unsharp(value: u8, soft: u8, amount: f32, threshold: u8) -> u8 {
+    if value.abs_diff(soft) <= threshold {
+        return value;
+    }
+    let sharpened = value as f32 + amount * (value as f32 - soft as f32);
+    sharpened.round().clamp(0.0, 255.0) as u8
+}
+
+// Unsharp mask: the Gaussian blur at `radius`, then a parallel pass over bands of rows that adds
+// back the detail the blur took out. Alpha is kept.
+pub async fn apply_unsharp_mask_async(
+    img: &DynamicImage,
+    radius: i32,
+    amount: f32,
+    threshold: u8,
+    num_tasks: usize,
+    filter: FilterOptions,
+) -> DynamicImage {
+    let radius = u32::try_from(radius).expect("radius must not be negative");
+    let blurred = apply_gaussian_blur_async(img, radius, num_tasks, FilterOptions { channels: Channels::Rgba, ..filter }).await;
+    let blurred = Arc::new(blurred.to_rgba8());
+    let src = Arc::new(img.to_rgba8());
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height as usize, num_tasks) {
+        let src = Arc::clone(&src);
+        let blurred = Arc::clone(&blurred);
+        tasks.push(task::spawn(async move {
+            let mut band = src.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            let soft = &blurred.as_raw()[rows.start * row_len..rows.end * row_len];
+            for (pixel, soft) in band.chunks_exact_mut(4).zip(soft.chunks_exact(4)) {
+                for ch in 0..3 {
+                    pixel[ch] = unsharp(pixel[ch], soft[ch], amount, threshold);
+                }
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Sharpened buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
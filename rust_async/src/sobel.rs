@@ -0,0 +1,61 @@
+use crate::bands;
+use crate::blur::apply_gaussian_blur_async;
+use crate::channels::{self, Channels};
+use crate::cli::FilterOptions;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::task;
+
+// Gradient magnitude from the 3x3 Sobel kernels, `luma` giving the luma at an offset from the
+// pixel. Clamped to 8 bits, so only edges steeper than a quarter of the range saturate.
+pub fn magnitude(luma: impl Fn(i32, i32) -> f32) -> u8 {
+    let gx = luma(1, -1) + 2.0 * luma(1, 0) + luma(1, 1) - luma(-1, -1) - 2.0 * luma(-1, 0) - luma(-1, 1);
+    let gy = luma(-1, 1) + 2.0 * luma(0, 1) + luma(1, 1) - luma(-1, -1) - 2.0 * luma(0, -1) - luma(1, -1);
+    (gx * gx + gy * gy).sqrt().round().min(255.0) as u8
+}
+
+// Edge map: the gradient magnitude of the luma in every color channel, alpha kept. A radius above
+// 0 smooths the image with the Gaussian blur first, so noise does not show up as edges.
+pub async fn apply_sobel_async(img: &DynamicImage, radius: i32, num_tasks: usize, filter: FilterOptions) -> DynamicImage {
+    let radius = u32::try_from(radius).expect("radius must not be negative");
+    let src = Arc::new(img.to_rgba8());
+    let smoothed = if radius == 0 {
+        Arc::clone(&src)
+    } else {
+        let blurred = apply_gaussian_blur_async(img, radius, num_tasks, FilterOptions { channels: Channels::Rgba, ..filter }).await;
+        Arc::new(blurred.to_rgba8())
+    };
+
+    let (width, height) = src.dimensions();
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height as usize, num_tasks) {
+        let src = Arc::clone(&src);
+        let smoothed = Arc::clone(&smoothed);
+        tasks.push(task::spawn(async move {
+            let mut band = src.as_raw()[rows.start * row_len..rows.end * row_len].to_vec();
+            for (y, row) in rows.clone().zip(band.chunks_exact_mut(row_len)) {
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let edge = magnitude(|dx, dy| {
+                        let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                        let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                        channels::luma(&smoothed.get_pixel(sx, sy).0)
+                    });
+                    pixel[..3].copy_from_slice(&[edge, edge, edge]);
+                }
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, data).expect("Edge buffer matches dimensions");
+    channels::restore(img, DynamicImage::ImageRgba8(buffer), filter.channels, num_tasks).await
+}
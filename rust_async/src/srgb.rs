@@ -0,0 +1,46 @@
+use std::sync::OnceLock;
+
+// Resolution of the linear -> sRGB table; fine enough that dark tones do not band
+const ENCODE_STEPS: usize = 1 << 16;
+
+// Lookup tables between 8-bit sRGB and linear light, linear values kept on a 0..255 scale
+// so filters can use them in place of the raw channel values
+pub struct SrgbLut {
+    to_linear: [f64; 256],
+    to_srgb: Vec<u8>,
+}
+
+impl SrgbLut {
+    fn new() -> Self {
+        let mut to_linear = [0.0; 256];
+        for (value, linear) in to_linear.iter_mut().enumerate() {
+            let c = value as f64 / 255.0;
+            let l = if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+            *linear = l * 255.0;
+        }
+
+        let to_srgb = (0..ENCODE_STEPS)
+            .map(|i| {
+                let l = i as f64 / (ENCODE_STEPS - 1) as f64;
+                let c = if l <= 0.0031308 { l * 12.92 } else { 1.055 * l.powf(1.0 / 2.4) - 0.055 };
+                (c * 255.0).round() as u8
+            })
+            .collect();
+
+        SrgbLut { to_linear, to_srgb }
+    }
+
+    pub fn decode(&self, value: u8) -> f64 {
+        self.to_linear[value as usize]
+    }
+
+    pub fn encode(&self, linear: f64) -> u8 {
+        let index = (linear / 255.0 * (ENCODE_STEPS - 1) as f64).round();
+        self.to_srgb[index.clamp(0.0, (ENCODE_STEPS - 1) as f64) as usize]
+    }
+}
+
+pub fn lut() -> &'static SrgbLut {
+    static LUT: OnceLock<SrgbLut> = OnceLock::new();
+    LUT.get_or_init(SrgbLut::new)
+}
@@ -0,0 +1,259 @@
+use crate::bands;
+use crate::blur::apply_gaussian_blur_async;
+use crate::channels;
+use crate::cli::FilterOptions;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// How the `stack` operation combines aligned exposures of the same scene, one value per channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackMode {
+    // Averages the noise down by the square root of the frame count
+    Mean,
+    // Also drops outliers such as satellite trails and hot pixels, but needs every frame in memory
+    Median,
+}
+
+impl FromStr for StackMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(StackMode::Mean),
+            "median" => Ok(StackMode::Median),
+            other => Err(format!("Unknown stack mode: {}. Use 'mean' or 'median'", other)),
+        }
+    }
+}
+
+fn check_dimensions(index: usize, frame: &Frame, expected: (u32, u32)) -> Result<(), String> {
+    if frame.dimensions() != expected {
+        let (width, height) = frame.dimensions();
+        return Err(format!("Frame {} is {}x{}, expected {}x{} like the first", index + 1, width, height, expected.0, expected.1));
+    }
+    Ok(())
+}
+
+fn frame_len((width, height): (u32, u32)) -> usize {
+    width as usize * height as usize * 4
+}
+
+// Stacks `count` frames, `load` decoding the frame at an index. Frames are split between blocking
+// tasks, which decode theirs concurrently; a mean never holds more than one frame per task.
+pub async fn stack_async<F>(count: usize, load: F, mode: StackMode, num_tasks: usize) -> Result<DynamicImage, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Send + Sync + 'static,
+{
+    if count == 0 {
+        return Err("stack needs at least one frame".to_string());
+    }
+    let load = Arc::new(load);
+    // The first frame sets the size every other one must match
+    let first = {
+        let load = Arc::clone(&load);
+        task::spawn_blocking(move || load(0)).await.expect("Decoding task panicked")?
+    };
+    let dimensions = first.dimensions();
+    let stacked = match mode {
+        StackMode::Mean => mean(count, first, load, num_tasks).await?,
+        StackMode::Median => {
+            let mut frames = vec![first];
+            frames.extend(decode_all(1..count, load, dimensions, num_tasks).await?);
+            median(Arc::new(frames), num_tasks).await
+        }
+    };
+    Ok(DynamicImage::ImageRgba8(stacked))
+}
+
+// The frames of `indices` split into runs, one per task
+fn groups(indices: Range<usize>, num_tasks: usize) -> Vec<Range<usize>> {
+    bands::split(indices.len(), num_tasks).into_iter().map(|group| group.start + indices.start..group.end + indices.start).collect()
+}
+
+// Each task sums its frames into its own buffer, then the partial sums are reduced band by band
+async fn mean<F>(count: usize, first: Frame, load: Arc<F>, num_tasks: usize) -> Result<Frame, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Send + Sync + 'static,
+{
+    let dimensions = first.dimensions();
+    let mut tasks = Vec::new();
+    for group in groups(1..count, num_tasks) {
+        let load = Arc::clone(&load);
+        tasks.push(task::spawn_blocking(move || {
+            let mut sum = vec![0u32; frame_len(dimensions)];
+            for index in group {
+                let frame = load(index)?;
+                check_dimensions(index, &frame, dimensions)?;
+                for (sum, &value) in sum.iter_mut().zip(frame.as_raw()) {
+                    *sum += value as u32;
+                }
+            }
+            Ok::<_, String>(sum)
+        }));
+    }
+    let mut partials: Vec<Vec<u32>> = vec![first.as_raw().iter().map(|&value| value as u32).collect()];
+    for task in tasks {
+        partials.push(task.await.expect("Stacking task panicked")?);
+    }
+
+    let partials = Arc::new(partials);
+    let row_len = dimensions.0 as usize * 4;
+    let count = count as u32;
+    let mut tasks = Vec::new();
+    for rows in bands::split(dimensions.1 as usize, num_tasks) {
+        let partials = Arc::clone(&partials);
+        tasks.push(task::spawn(async move {
+            (rows.start * row_len..rows.end * row_len)
+                .map(|i| {
+                    let total: u32 = partials.iter().map(|sum| sum[i]).sum();
+                    ((total + count / 2) / count) as u8
+                })
+                .collect::<Vec<u8>>()
+        }));
+    }
+    let mut data = Vec::with_capacity(frame_len(dimensions));
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    Ok(ImageBuffer::from_raw(dimensions.0, dimensions.1, data).expect("Stacked buffer matches dimensions"))
+}
+
+// Decodes the frames of `indices` concurrently, each task taking a run of them, kept in order
+async fn decode_all<F>(indices: Range<usize>, load: Arc<F>, dimensions: (u32, u32), num_tasks: usize) -> Result<Vec<Frame>, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Send + Sync + 'static,
+{
+    let mut tasks = Vec::new();
+    for group in groups(indices, num_tasks) {
+        let load = Arc::clone(&load);
+        tasks.push(task::spawn_blocking(move || {
+            group.map(|index| {
+                let frame = load(index)?;
+                check_dimensions(index, &frame, dimensions)?;
+                Ok(frame)
+            }).collect::<Result<Vec<_>, String>>()
+        }));
+    }
+    let mut frames = Vec::new();
+    for task in tasks {
+        frames.extend(task.await.expect("Decoding task panicked")?);
+    }
+    Ok(frames)
+}
+
+// The middle value of every channel, an even count taking the rounded mean of the two middle ones
+pub fn median_of(values: &mut [u8]) -> u8 {
+    let len = values.len();
+    let (below, &mut upper, _) = values.select_nth_unstable(len / 2);
+    if len % 2 == 1 {
+        return upper;
+    }
+    let lower = *below.iter().max().expect("An even count has a lower half");
+    (lower as u16 + upper as u16).div_ceil(2) as u8
+}
+
+async fn median(frames: Arc<Vec<Frame>>, num_tasks: usize) -> Frame {
+    let (width, height) = frames[0].dimensions();
+    let row_len = width as usize * 4;
+    let mut tasks = Vec::new();
+    for rows in bands::split(height as usize, num_tasks) {
+        let frames = Arc::clone(&frames);
+        tasks.push(task::spawn(async move {
+            let mut values = vec![0u8; frames.len()];
+            (rows.start * row_len..rows.end * row_len)
+                .map(|i| {
+                    for (slot, frame) in values.iter_mut().zip(frames.iter()) {
+                        *slot = frame.as_raw()[i];
+                    }
+                    median_of(&mut values)
+                })
+                .collect::<Vec<u8>>()
+        }));
+    }
+    let mut data = Vec::with_capacity(frame_len((width, height)));
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    ImageBuffer::from_raw(width, height, data).expect("Stacked buffer matches dimensions")
+}
+
+// Radius the sharpness maps are blurred over, so the sharpest frame is chosen by region rather
+// than by single noisy pixels
+pub const FOCUS_RADIUS: u32 = 4;
+
+// Magnitude of the luma's Laplacian, clamped to 8 bits and written to every color channel so the
+// Gaussian blur can smooth it like any image
+pub fn laplacian_energy(frame: &Frame) -> Frame {
+    let (width, height) = frame.dimensions();
+    let luma = |x: i32, y: i32| channels::luma(&frame.get_pixel(x.clamp(0, width as i32 - 1) as u32, y.clamp(0, height as i32 - 1) as u32).0);
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i32, y as i32);
+        let laplacian = 4.0 * luma(x, y) - luma(x - 1, y) - luma(x + 1, y) - luma(x, y - 1) - luma(x, y + 1);
+        let energy = laplacian.abs().round().min(255.0) as u8;
+        Rgba([energy, energy, energy, 255])
+    })
+}
+
+// Merges frames focused at different depths into one that is sharp throughout. Frames are decoded
+// and their sharpness maps built concurrently, then every pixel is taken from the frame whose map
+// is highest there.
+pub async fn focus_stack_async<F>(count: usize, load: F, num_tasks: usize) -> Result<DynamicImage, String>
+where
+    F: Fn(usize) -> Result<Frame, String> + Send + Sync + 'static,
+{
+    if count == 0 {
+        return Err("focus-stack needs at least one frame".to_string());
+    }
+    let load = Arc::new(load);
+    let first = {
+        let load = Arc::clone(&load);
+        task::spawn_blocking(move || load(0)).await.expect("Decoding task panicked")?
+    };
+    let dimensions = first.dimensions();
+    let mut frames = vec![first];
+    frames.extend(decode_all(1..count, load, dimensions, num_tasks).await?);
+    let frames = Arc::new(frames);
+
+    // Tasks left over when there are fewer frames than tasks go to each frame's blur
+    let tasks_per_frame = (num_tasks / count).max(1);
+    let mut tasks = Vec::new();
+    for index in 0..count {
+        let frames = Arc::clone(&frames);
+        tasks.push(task::spawn(async move {
+            let energy = DynamicImage::ImageRgba8(laplacian_energy(&frames[index]));
+            apply_gaussian_blur_async(&energy, FOCUS_RADIUS, tasks_per_frame, FilterOptions::default()).await.to_rgba8()
+        }));
+    }
+    let mut sharpness = Vec::new();
+    for task in tasks {
+        sharpness.push(task.await.expect("Sharpness task panicked"));
+    }
+    let sharpness = Arc::new(sharpness);
+
+    let row_len = dimensions.0 as usize * 4;
+    let mut tasks = Vec::new();
+    for rows in bands::split(dimensions.1 as usize, num_tasks) {
+        let frames = Arc::clone(&frames);
+        let sharpness = Arc::clone(&sharpness);
+        tasks.push(task::spawn(async move {
+            let mut band = Vec::with_capacity(rows.len() * row_len);
+            for index in (rows.start * row_len..rows.end * row_len).step_by(4) {
+                // The first of equally sharp frames wins
+                let best = (0..frames.len()).rev().max_by_key(|&frame| sharpness[frame].as_raw()[index]).unwrap_or(0);
+                band.extend_from_slice(&frames[best].as_raw()[index..index + 4]);
+            }
+            band
+        }));
+    }
+    let mut data = Vec::with_capacity(frame_len(dimensions));
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let stacked = ImageBuffer::from_raw(dimensions.0, dimensions.1, data).expect("Stacked buffer matches dimensions");
+    Ok(DynamicImage::ImageRgba8(stacked))
+}
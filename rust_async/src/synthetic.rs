@@ -0,0 +1,206 @@
+use crate::bands;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::f64::consts::SQRT_2;
+use std::fmt;
+use std::str::FromStr;
+use tokio::task;
+
+// Input paths starting with this generate the image instead of reading it,
+// e.g. `synthetic:noise:4096x4096`, so they also work when passed to another binary
+pub const PREFIX: &str = "synthetic:";
+// Side of one checkerboard square in pixels
+const CELL: u32 = 32;
+// Lattice cells across the image in Perlin noise's first octave, and the octaves summed, when
+// the spec leaves them out
+pub const DEFAULT_FREQUENCY: u32 = 4;
+pub const DEFAULT_OCTAVES: u32 = 4;
+// Each octave doubles the cells, so past this many the lattice is finer than any image
+const MAX_OCTAVES: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    // Independent pseudo-random channels, the same for every run
+    Noise,
+    // Red across, green down and blue along the diagonal
+    Gradient,
+    // Black and white squares, hard edges everywhere
+    Checkerboard,
+    // Gray fractal Perlin noise that tiles: `frequency` lattice cells across the image, then twice
+    // as many at half the amplitude for each further octave
+    Perlin { frequency: u32, octaves: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntheticSpec {
+    pub pattern: Pattern,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for SyntheticSpec {
+    type Err = String;
+
+    // Parses `<pattern>:<width>x<height>`, e.g. `noise:4096x4096`. Perlin noise may add its
+    // frequency and octaves, e.g. `perlin:4096x4096:8:5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, rest) = s.split_once(':').ok_or("Expected <pattern>:<width>x<height>")?;
+        let mut parts = rest.split(':');
+        let size = parts.next().unwrap_or_default();
+        let (width, height) = size.split_once('x').ok_or("Expected <width>x<height>")?;
+        let mut parameter = |name: &str, default: u32| -> Result<u32, String> {
+            parts.next().map_or(Ok(default), |value| value.parse().map_err(|_| format!("Invalid {}: {}", name, value)))
+        };
+
+        let pattern = match pattern {
+            "noise" => Pattern::Noise,
+            "gradient" => Pattern::Gradient,
+            "checkerboard" => Pattern::Checkerboard,
+            "perlin" => {
+                let frequency = parameter("frequency", DEFAULT_FREQUENCY)?;
+                let octaves = parameter("octaves", DEFAULT_OCTAVES)?;
+                if frequency == 0 || !(1..=MAX_OCTAVES).contains(&octaves) {
+                    return Err(format!("Perlin noise needs a frequency of at least 1 and 1 to {} octaves", MAX_OCTAVES));
+                }
+                // The last octave's cells must still fit in a u32
+                if (frequency as u64) << (octaves - 1) > u32::MAX as u64 {
+                    return Err(format!("Perlin frequency {} is too high for {} octaves", frequency, octaves));
+                }
+                Pattern::Perlin { frequency, octaves }
+            }
+            other => return Err(format!("Unknown synthetic pattern: {}", other)),
+        };
+        if parts.next().is_some() {
+            return Err(format!("Unexpected parameters in synthetic spec: {}", s));
+        }
+        let width: u32 = width.parse().map_err(|_| format!("Invalid width: {}", width))?;
+        let height: u32 = height.parse().map_err(|_| format!("Invalid height: {}", height))?;
+        if width == 0 || height == 0 {
+            return Err("Synthetic images need a non-zero size".to_string());
+        }
+
+        Ok(SyntheticSpec { pattern, width, height })
+    }
+}
+
+impl fmt::Display for SyntheticSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pattern = match self.pattern {
+            Pattern::Noise => "noise",
+            Pattern::Gradient => "gradient",
+            Pattern::Checkerboard => "checkerboard",
+            Pattern::Perlin { frequency, octaves } => return write!(f, "perlin:{}x{}:{}:{}", self.width, self.height, frequency, octaves),
+        };
+        write!(f, "{}:{}x{}", pattern, self.width, self.height)
+    }
+}
+
+impl SyntheticSpec {
+    pub fn to_path(self) -> String {
+        format!("{}{}", PREFIX, self)
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        match self.pattern {
+            Pattern::Noise => {
+                let h = hash(x, y);
+                [h as u8, (h >> 8) as u8, (h >> 16) as u8, 255]
+            }
+            Pattern::Gradient => {
+                let (w, h) = ((self.width - 1).max(1), (self.height - 1).max(1));
+                [
+                    (x * 255 / w) as u8,
+                    (y * 255 / h) as u8,
+                    ((x + y) as u64 * 255 / (w + h) as u64) as u8,
+                    255,
+                ]
+            }
+            Pattern::Checkerboard => {
+                let value = if (x / CELL + y / CELL).is_multiple_of(2) { 255 } else { 0 };
+                [value, value, value, 255]
+            }
+            Pattern::Perlin { frequency, octaves } => {
+                let value = fractal_noise(x, y, self.width, self.height, frequency, octaves);
+                // Two dimensional Perlin noise stays within ±√2/2
+                let value = ((value * SQRT_2 * 0.5 + 0.5) * 255.0).round().clamp(0.0, 255.0) as u8;
+                [value, value, value, 255]
+            }
+        }
+    }
+}
+
+// Perlin noise at the center of pixel (x, y) on a lattice of `cells` x `cells` across the image.
+// Lattice points wrap around, so the right edge continues into the left and the bottom into the top.
+fn perlin(x: u32, y: u32, width: u32, height: u32, cells: u32, salt: u32) -> f64 {
+    let u = (x as f64 + 0.5) * cells as f64 / width as f64;
+    let v = (y as f64 + 0.5) * cells as f64 / height as f64;
+    let (cell_x, cell_y) = (u.floor() as u32, v.floor() as u32);
+    let (fx, fy) = (u - cell_x as f64, v - cell_y as f64);
+    // Gradient at a lattice point dotted with the offset to it, one of eight directions per point
+    let corner = |dx: u32, dy: u32| {
+        let direction = hash(((cell_x + dx) % cells) ^ salt, (cell_y + dy) % cells) % 8;
+        let angle = direction as f64 * std::f64::consts::FRAC_PI_4;
+        angle.cos() * (fx - dx as f64) + angle.sin() * (fy - dy as f64)
+    };
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let (sx, sy) = (fade(fx), fade(fy));
+    lerp(lerp(corner(0, 0), corner(1, 0), sx), lerp(corner(0, 1), corner(1, 1), sx), sy)
+}
+
+// Octaves of `perlin`, each with twice the cells and half the amplitude of the one before, scaled
+// back to a single octave's range
+fn fractal_noise(x: u32, y: u32, width: u32, height: u32, frequency: u32, octaves: u32) -> f64 {
+    let (mut sum, mut total) = (0.0, 0.0);
+    for octave in 0..octaves {
+        let amplitude = 0.5f64.powi(octave as i32);
+        sum += amplitude * perlin(x, y, width, height, frequency << octave, octave << 24);
+        total += amplitude;
+    }
+    sum / total
+}
+
+// SplitMix64 finalizer of the coordinates, so any band can be generated without the others
+fn hash(x: u32, y: u32) -> u32 {
+    let mut h = ((x as u64) << 32) | y as u64;
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    h as u32
+}
+
+pub fn is_synthetic(path: &str) -> bool {
+    path.starts_with(PREFIX)
+}
+
+// Generates the image named by a `synthetic:` path
+pub async fn load(path: &str, num_tasks: usize) -> Result<DynamicImage, String> {
+    let spec: SyntheticSpec = path.strip_prefix(PREFIX).unwrap_or(path).parse()?;
+    Ok(generate(spec, num_tasks).await)
+}
+
+// Fills the image band by band, one task per band
+pub async fn generate(spec: SyntheticSpec, num_tasks: usize) -> DynamicImage {
+    let width = spec.width as usize;
+    let height = spec.height as usize;
+    let mut tasks = Vec::new();
+
+    for rows in bands::split(height, num_tasks) {
+        tasks.push(task::spawn(async move {
+            let mut band = vec![0u8; rows.len() * width * 4];
+            for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
+                let (x, y) = (i % width, rows.start + i / width);
+                pixel.copy_from_slice(&spec.pixel(x as u32, y as u32));
+            }
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(height * width * 4);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    let buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(spec.width, spec.height, data).expect("Synthetic buffer matches dimensions");
+    DynamicImage::ImageRgba8(buffer)
+}
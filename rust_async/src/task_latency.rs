@@ -0,0 +1,101 @@
+use crate::progress;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::{self, JoinHandle};
+
+pub struct TaskLatency {
+    // From spawn until a worker first polled the task
+    pub queued: Duration,
+    // From spawn until the task completed
+    pub total: Duration,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDS: Mutex<Vec<TaskLatency>> = Mutex::new(Vec::new());
+
+// Tasks spawned through `spawn` only record their latency once this is called
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// `task::spawn` that also records the task's queueing delay and completion time,
+// and keeps counting the task's rows into the spawning job's progress
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = progress::inherit(future);
+    if !ENABLED.load(Ordering::Relaxed) {
+        return task::spawn(future);
+    }
+    let submitted = Instant::now();
+    task::spawn(async move {
+        let queued = submitted.elapsed();
+        let output = future.await;
+        RECORDS.lock().unwrap().push(TaskLatency { queued, total: submitted.elapsed() });
+        output
+    })
+}
+
+pub fn take() -> Vec<TaskLatency> {
+    std::mem::take(&mut *RECORDS.lock().unwrap())
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Counts per power-of-two bucket of microseconds, from the smallest to the largest occupied bucket
+fn histogram(sorted: &[Duration]) -> String {
+    let bucket = |d: &Duration| (d.as_micros().max(1) as f64).log2().floor() as u32;
+    let (first, last) = (bucket(&sorted[0]), bucket(&sorted[sorted.len() - 1]));
+    let mut counts = vec![0usize; (last - first + 1) as usize];
+    for d in sorted {
+        counts[(bucket(d) - first) as usize] += 1;
+    }
+
+    let most = counts.iter().copied().max().unwrap_or(1);
+    let mut out = String::new();
+    for (i, &count) in counts.iter().enumerate() {
+        let upper = 1u64 << (first + i as u32 + 1);
+        let bar = "#".repeat((count * 40).div_ceil(most));
+        out += &format!("\n    < {:>9}us {:<40} {}", upper, bar, count);
+    }
+    out
+}
+
+pub fn report(records: &[TaskLatency]) -> String {
+    if records.is_empty() {
+        return "No task latencies recorded".to_string();
+    }
+    let mut queued: Vec<Duration> = records.iter().map(|r| r.queued).collect();
+    let mut total: Vec<Duration> = records.iter().map(|r| r.total).collect();
+    queued.sort();
+    total.sort();
+
+    let mut out = format!("Task latency ({} tasks):\n", records.len());
+    out += &format!("  {:<14} {:>9} {:>9} {:>9} {:>9}\n", "ms", "p50", "p90", "p99", "max");
+    for (name, sorted) in [("queued", &queued), ("spawn to done", &total)] {
+        out += &format!(
+            "  {:<14} {:>9.3} {:>9.3} {:>9.3} {:>9.3}\n",
+            name,
+            millis(percentile(sorted, 0.5)),
+            millis(percentile(sorted, 0.9)),
+            millis(percentile(sorted, 0.99)),
+            millis(sorted[sorted.len() - 1]),
+        );
+    }
+    out += "  queueing delay:";
+    out += &histogram(&queued);
+    out += "\n  spawn to completion:";
+    out += &histogram(&total);
+    out
+}
@@ -0,0 +1,146 @@
+use crate::cli::FilterOptions;
+use crate::png_encoder;
+use crate::sharpen::apply_unsharp_mask_async;
+use image::{imageops, DynamicImage, ImageFormat};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task;
+
+// Strength of `--sharpen`, an unsharp mask that restores the crispness downscaling takes away
+const SHARPEN_AMOUNT: f32 = 0.5;
+
+pub struct ThumbStats {
+    pub sources: usize,
+    pub thumbnails: usize,
+}
+
+// Images in `dir` the decoder knows by extension, sorted so runs write in the same order
+pub fn list_sources(dir: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut sources: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+        .collect();
+    sources.sort();
+    Ok(sources)
+}
+
+// Largest size that fits a `size` square with the same aspect ratio; thumbnails never upscale
+pub fn fit(width: u32, height: u32, size: u32) -> (u32, u32) {
+    if width <= size && height <= size {
+        return (width, height);
+    }
+    let scale = size as f64 / width.max(height) as f64;
+    (((width as f64 * scale).round() as u32).max(1), ((height as f64 * scale).round() as u32).max(1))
+}
+
+// Adds back the difference from a radius 1 blur; alpha is kept
+pub async fn sharpen(img: &DynamicImage) -> DynamicImage {
+    apply_unsharp_mask_async(img, 1, SHARPEN_AMOUNT, 0, 1, FilterOptions::default()).await
+}
+
+pub async fn thumbnail(img: Arc<DynamicImage>, size: u32, sharpened: bool) -> DynamicImage {
+    let resized = task::spawn_blocking(move || {
+        let (width, height) = fit(img.width(), img.height(), size);
+        if (width, height) == (img.width(), img.height()) {
+            return DynamicImage::ImageRgba8(img.to_rgba8());
+        }
+        DynamicImage::ImageRgba8(imageops::resize(img.as_ref(), width, height, imageops::FilterType::Lanczos3))
+    })
+    .await
+    .expect("Resize task panicked");
+    if sharpened { sharpen(&resized).await } else { resized }
+}
+
+// `<output>/<stem>_<size>.<extension of the source>`
+pub fn thumbnail_path(output: &Path, source: &Path, size: u32) -> PathBuf {
+    let stem = source.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let extension = source.extension().map(|extension| extension.to_string_lossy()).unwrap_or_default();
+    output.join(format!("{}_{}.{}", stem, size, extension))
+}
+
+// Decoder tasks pull sources from a shared queue, decode each once and resize it to every size in
+// its own task. Thumbnails go through a channel holding at most `num_tasks` of them to as many
+// encoder tasks, so slow encoding holds the decoders back instead of piling images up in memory.
+pub async fn generate(input: &str, output: &str, sizes: &[u32], sharpened: bool, num_tasks: usize) -> Result<ThumbStats, Box<dyn Error>> {
+    let sources = Arc::new(list_sources(input)?);
+    let output = Arc::new(PathBuf::from(output));
+    fs::create_dir_all(output.as_ref())?;
+    let num_tasks = num_tasks.max(1);
+    let sizes = Arc::new(sizes.to_vec());
+
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel::<(PathBuf, DynamicImage)>(num_tasks);
+    let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+    let written = Arc::new(AtomicUsize::new(0));
+    // The first failure; later sources are skipped and queued thumbnails dropped
+    let failure: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+    let fail = |failure: &Mutex<Option<String>>, message: String| {
+        failure.lock().expect("Failure lock poisoned").get_or_insert(message);
+    };
+    let failed = |failure: &Mutex<Option<String>>| failure.lock().expect("Failure lock poisoned").is_some();
+
+    let mut decoders = Vec::new();
+    for _ in 0..num_tasks {
+        let (sources, output, sizes, next, failure) = (Arc::clone(&sources), Arc::clone(&output), Arc::clone(&sizes), Arc::clone(&next), Arc::clone(&failure));
+        let sender = sender.clone();
+        decoders.push(task::spawn(async move {
+            while let Some(source) = sources.get(next.fetch_add(1, Ordering::Relaxed)) {
+                if failed(&failure) {
+                    return;
+                }
+                let path = source.clone();
+                let img = match task::spawn_blocking(move || image::open(path)).await.expect("Decoder panicked") {
+                    Ok(img) => Arc::new(DynamicImage::ImageRgba8(img.to_rgba8())),
+                    Err(e) => return fail(&failure, format!("{}: {}", source.display(), e)),
+                };
+                let resizes: Vec<_> = sizes.iter().map(|&size| task::spawn(thumbnail(Arc::clone(&img), size, sharpened))).collect();
+                for (&size, resize) in sizes.iter().zip(resizes) {
+                    let thumbnail = resize.await.expect("Resize task panicked");
+                    sender.send((thumbnail_path(&output, source, size), thumbnail)).await.expect("Encoders run until the channel closes");
+                }
+            }
+        }));
+    }
+    // Encoders see the channel close once every decoder has dropped its sender
+    drop(sender);
+
+    let mut encoders = Vec::new();
+    for _ in 0..num_tasks {
+        let (receiver, written, failure) = (Arc::clone(&receiver), Arc::clone(&written), Arc::clone(&failure));
+        encoders.push(task::spawn(async move {
+            loop {
+                let Some((path, thumbnail)) = receiver.lock().await.recv().await else {
+                    return;
+                };
+                if failed(&failure) {
+                    continue;
+                }
+                let path_str = path.to_string_lossy().to_string();
+                let saved = if png_encoder::is_png(&path_str) {
+                    png_encoder::save(&path_str, &thumbnail, 1).await.map_err(|e| e.to_string())
+                } else {
+                    task::spawn_blocking(move || thumbnail.save(path_str)).await.expect("Encoder panicked").map_err(|e| e.to_string())
+                };
+                match saved {
+                    Ok(()) => {
+                        written.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => fail(&failure, format!("{}: {}", path.display(), e)),
+                }
+            }
+        }));
+    }
+
+    for task in decoders.into_iter().chain(encoders) {
+        task.await.expect("Thumbnail task panicked");
+    }
+
+    if let Some(message) = failure.lock().expect("Failure lock poisoned").take() {
+        return Err(message.into());
+    }
+    Ok(ThumbStats { sources: sources.len(), thumbnails: written.load(Ordering::Relaxed) })
+}
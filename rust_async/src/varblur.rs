@@ -0,0 +1,78 @@
+use crate::bands;
+use crate::blur;
+use crate::cli::FilterOptions;
+use crate::progress;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::sync::Arc;
+use tokio::task;
+
+type Frame = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// Blurred copies the mask chooses between when `--blur-levels` is not given
+pub const DEFAULT_LEVELS: usize = 4;
+
+// Radii of the levels: the sharp image, then `levels` blurs spaced evenly up to `radius`
+pub fn level_radii(radius: u32, levels: usize) -> Vec<u32> {
+    (0..=levels).map(|level| ((radius as f64 * level as f64 / levels as f64).round()) as u32).collect()
+}
+
+// The pixel at (x, y) with the blur a mask value asks for: black keeps it sharp, white takes the
+// widest blur, and values in between mix the two nearest levels
+pub fn mix_pixel(levels: &[Frame], x: u32, y: u32, mask_value: u8) -> [u8; 4] {
+    let position = mask_value as f32 / 255.0 * (levels.len() - 1) as f32;
+    let lower = (position as usize).min(levels.len().saturating_sub(2));
+    let upper = (lower + 1).min(levels.len() - 1);
+    let weight = position - lower as f32;
+    let (a, b) = (levels[lower].get_pixel(x, y), levels[upper].get_pixel(x, y));
+    [0, 1, 2, 3].map(|ch| (a[ch] as f32 * (1.0 - weight) + b[ch] as f32 * weight).round() as u8)
+}
+
+// Blur whose strength varies across the image by a grayscale mask, the way a fake depth of field
+// blurs the background and leaves the subject sharp. Rather than a kernel per pixel, every level
+// of the stack is blurred at once, each by its own share of the tasks, and a final round of tasks
+// over bands of rows mixes the two levels around each pixel's mask value.
+pub async fn apply_variable_blur_async(img: &DynamicImage, mask: &DynamicImage, radius: u32, levels: usize, num_tasks: usize, filter: FilterOptions) -> Result<DynamicImage, String> {
+    let (width, height) = (img.width(), img.height());
+    if (mask.width(), mask.height()) != (width, height) {
+        return Err(format!("Mask is {}x{}, expected {}x{} like the image", mask.width(), mask.height(), width, height));
+    }
+    let src = Arc::new(img.clone());
+    let per_level = (num_tasks / levels).max(1);
+    // Each level's sigma follows its radius, and the sharp level stays sharp
+    let filter = FilterOptions { sigma_x: None, sigma_y: None, ..filter };
+    let blurs: Vec<_> = level_radii(radius, levels)
+        .into_iter()
+        .map(|radius| {
+            let src = Arc::clone(&src);
+            task::spawn(async move { blur::apply_gaussian_blur_async(&src, radius, per_level, filter).await.to_rgba8() })
+        })
+        .collect();
+    let mut stack = Vec::with_capacity(blurs.len());
+    for blur in blurs {
+        stack.push(blur.await.expect("Blur task panicked"));
+    }
+    let (stack, mask) = (Arc::new(stack), Arc::new(mask.to_luma8()));
+
+    let row_len = width as usize * 4;
+    progress::expect(height as usize);
+    let mut tasks = Vec::new();
+    for rows in bands::split(height as usize, num_tasks) {
+        let (stack, mask) = (Arc::clone(&stack), Arc::clone(&mask));
+        tasks.push(task::spawn(async move {
+            let mut band = Vec::with_capacity(rows.len() * row_len);
+            for y in rows.start as u32..rows.end as u32 {
+                for x in 0..width {
+                    band.extend_from_slice(&mix_pixel(&stack, x, y, mask.get_pixel(x, y)[0]));
+                }
+            }
+            progress::advance(rows.len());
+            band
+        }));
+    }
+
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for task in tasks {
+        data.extend_from_slice(&task.await.unwrap());
+    }
+    Ok(DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, data).expect("Blurred buffer matches dimensions")))
+}
@@ -0,0 +1,128 @@
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::process::Stdio;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::task::{self, JoinHandle};
+
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    // Frame rate as reported by ffprobe, e.g. "30000/1001"
+    pub frame_rate: String,
+}
+
+pub async fn probe(path: &str) -> Result<VideoInfo, Box<dyn Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,r_frame_rate"])
+        .args(["-of", "csv=p=0", path])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    let text = String::from_utf8(output.stdout)?;
+    let fields: Vec<&str> = text.trim().split(',').collect();
+    if fields.len() < 3 {
+        return Err(format!("Unexpected ffprobe output: {}", text.trim()).into());
+    }
+
+    Ok(VideoInfo {
+        width: fields[0].parse()?,
+        height: fields[1].parse()?,
+        frame_rate: fields[2].to_string(),
+    })
+}
+
+// Fills `buf` with the next frame, returning false on a clean end of stream
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> Result<bool, Box<dyn Error>> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err("Truncated frame in decoder output".into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+// Decodes `input` with ffmpeg, filters up to `frames_in_flight` frames concurrently and
+// re-encodes the result (keeping the source audio) to `output`. Returns the frame count.
+pub async fn process_video<F, Fut>(
+    input: &str,
+    output: &str,
+    info: &VideoInfo,
+    frames_in_flight: usize,
+    filter: F,
+) -> Result<usize, Box<dyn Error>>
+where
+    F: Fn(DynamicImage) -> Fut,
+    Fut: Future<Output = DynamicImage> + Send + 'static,
+{
+    let size = format!("{}x{}", info.width, info.height);
+    let frame_len = info.width as usize * info.height as usize * 4;
+
+    let mut decoder = Command::new("ffmpeg")
+        .args(["-v", "error", "-i", input])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let mut encoder = Command::new("ffmpeg")
+        .args(["-v", "error", "-y"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba", "-s", &size, "-r", &info.frame_rate, "-i", "-"])
+        .args(["-i", input, "-map", "0:v:0", "-map", "1:a?", "-c:a", "copy"])
+        .args(["-pix_fmt", "yuv420p", "-shortest", output])
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    let mut frames_out = decoder.stdout.take().ok_or("ffmpeg decoder has no stdout")?;
+    let mut frames_in = encoder.stdin.take().ok_or("ffmpeg encoder has no stdin")?;
+
+    let mut pending: VecDeque<JoinHandle<DynamicImage>> = VecDeque::new();
+    let mut frame_count = 0;
+
+    loop {
+        let mut buf = vec![0u8; frame_len];
+        if !read_frame(&mut frames_out, &mut buf).await? {
+            break;
+        }
+
+        // Keep the pipeline bounded: write the oldest frame before admitting a new one
+        if pending.len() == frames_in_flight.max(1) {
+            let filtered = pending.pop_front().unwrap().await.unwrap();
+            frames_in.write_all(filtered.to_rgba8().as_raw()).await?;
+        }
+
+        let frame = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(info.width, info.height, buf)
+            .expect("Frame buffer does not match video dimensions");
+        pending.push_back(task::spawn(filter(DynamicImage::ImageRgba8(frame))));
+        frame_count += 1;
+    }
+
+    for handle in pending {
+        let filtered = handle.await.unwrap();
+        frames_in.write_all(filtered.to_rgba8().as_raw()).await?;
+    }
+
+    frames_in.shutdown().await?;
+    drop(frames_in);
+
+    if !decoder.wait().await?.success() {
+        return Err("ffmpeg decoder exited with an error".into());
+    }
+    if !encoder.wait().await?.success() {
+        return Err("ffmpeg encoder exited with an error".into());
+    }
+
+    Ok(frame_count)
+}
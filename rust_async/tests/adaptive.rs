@@ -0,0 +1,74 @@
+// Adaptive Kuwahara: a Sobel pass maps every pixel to a radius between `--min-radius` and the
+// given one, and the filter pass reads that map, so edges keep their detail and flat areas smooth.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::colorspace::ColorSpace;
+use rust_filter_async::kuwahara::{self, apply_kuwahara_filter_async, KuwaharaMode};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+mod common;
+
+fn adaptive(min_radius: u32) -> FilterOptions {
+    FilterOptions { kuwahara_mode: KuwaharaMode::Adaptive, min_radius, ..FilterOptions::default() }
+}
+
+// Black and white squares two pixels wide: an edge at every pixel
+fn checker() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(20, 20, |x, y| if (x / 2 + y / 2) % 2 == 0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }))
+}
+
+#[test]
+fn radius_falls_with_edge_strength() {
+    assert_eq!(kuwahara::adaptive_radius(0, 1, 8), 8);
+    assert_eq!(kuwahara::adaptive_radius(255, 1, 8), 1);
+    assert_eq!(kuwahara::adaptive_radius(128, 2, 10), 6);
+    // A minimum above the radius leaves the radius everywhere
+    assert_eq!(kuwahara::adaptive_radius(255, 12, 5), 5);
+}
+
+#[tokio::test]
+async fn map_keeps_the_full_radius_away_from_edges() {
+    // Black on the left, white from column 8
+    let img = ImageBuffer::from_fn(16, 6, |x, _| if x < 8 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) });
+    let radii = kuwahara::radius_map(Arc::new(img), 1, 6, 3).await;
+    for (i, &radius) in radii.iter().enumerate() {
+        let expected = if (7..=8).contains(&(i % 16)) { 1 } else { 6 };
+        assert_eq!(radius, expected, "column {}", i % 16);
+    }
+}
+
+#[tokio::test]
+async fn edges_keep_their_contrast() {
+    let img = checker();
+    let contrast = |result: &DynamicImage| {
+        let values: Vec<u8> = (6..14).flat_map(|y| (6..14).map(move |x| (x, y))).map(|(x, y)| result.get_pixel(x, y)[0]).collect();
+        values.iter().max().unwrap() - values.iter().min().unwrap()
+    };
+    let classic = apply_kuwahara_filter_async(&img, 6, 3, FilterOptions::default()).await;
+    let result = apply_kuwahara_filter_async(&img, 6, 3, adaptive(1)).await;
+    assert!(contrast(&result) > contrast(&classic), "expected {} above {}", contrast(&result), contrast(&classic));
+}
+
+#[tokio::test]
+async fn result_is_independent_of_task_count() {
+    let img = checker();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_kuwahara_filter_async(&img, 5, num_tasks, adaptive(2))).await;
+}
+
+#[tokio::test]
+async fn reference_matches_with_adaptive_radii() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let img = image::open(path).expect("Missing fixture");
+    for filter in [
+        adaptive(1),
+        FilterOptions { colorspace: ColorSpace::Lab, ..adaptive(0) },
+        FilterOptions { scales: 2, filter_alpha: true, ..adaptive(2) },
+    ] {
+        for radius in [1, 5] {
+            let result = apply_kuwahara_filter_async(&img, radius, 3, filter).await;
+            common::assert_matches_reference("kuwahara", &img, &result, radius, filter, &format!("radius {} {:?}", radius, filter));
+        }
+    }
+}
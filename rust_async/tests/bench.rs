@@ -0,0 +1,135 @@
+// Bench statistics: runs disturbed by throttling or background load are rejected by their distance
+// from the median in MADs, the kept runs decide how stable the measurement is, and a worker sweep
+// is fitted to Amdahl's law and the Universal Scalability Law. `--against` runs another backend
+// with the same arguments.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter_async::bench::{self, Measurement};
+use rust_filter_async::cli;
+use std::fs;
+use std::process::Command;
+
+fn measurement(samples: &[f64]) -> Measurement {
+    Measurement::new(4, samples.to_vec())
+}
+
+#[test]
+fn slow_runs_are_rejected() {
+    let m = measurement(&[10.0, 10.2, 9.8, 10.1, 9.9, 25.0]);
+    assert_eq!(m.kept, [10.0, 10.2, 9.8, 10.1, 9.9]);
+    assert_eq!(m.rejected(), 1);
+    assert_eq!(m.samples.len(), 6);
+    assert!((m.mean() - 10.0).abs() < 1e-9);
+    assert!((m.median() - 10.0).abs() < 1e-9);
+    assert!(m.format().contains("kept 5 of 6 runs (1 outliers beyond 3.5 MAD)"), "{}", m.format());
+}
+
+#[test]
+fn zero_mad_keeps_every_run() {
+    // A zero MAD gives no scale to judge distances by, so every run is kept
+    let m = measurement(&[12.0, 12.0, 12.0, 12.0]);
+    assert_eq!(m.rejected(), 0);
+    assert_eq!(m.stddev(), 0.0);
+    assert_eq!(m.stability(), "stable");
+
+    let m = measurement(&[12.0, 12.0, 12.0, 30.0]);
+    assert_eq!(m.rejected(), 0);
+}
+
+#[test]
+fn stability_follows_the_coefficient_of_variation() {
+    assert_eq!(measurement(&[100.0, 101.0, 99.0, 100.0]).stability(), "stable");
+    assert_eq!(measurement(&[100.0, 104.0, 96.0, 100.0]).stability(), "moderate noise");
+    let noisy = measurement(&[100.0, 110.0, 90.0, 105.0, 95.0]);
+    assert!(noisy.cv() >= 5.0, "{}", noisy.cv());
+    assert_eq!(noisy.stability(), "noisy, rerun on an idle machine");
+}
+
+// A sweep whose every run took `time(p)` milliseconds
+fn sweep(workers: &[usize], time: impl Fn(f64) -> f64) -> Vec<Measurement> {
+    workers.iter().map(|&p| Measurement::new(p, vec![time(p as f64); 3])).collect()
+}
+
+#[test]
+fn amdahl_fit_recovers_the_serial_fraction() {
+    // 10% of a 100ms run does not parallelize
+    let report = bench::scaling_report(&sweep(&[1, 2, 4, 8], |p| 100.0 * (0.1 + 0.9 / p)));
+    assert!(report.contains("Scaling relative to 1 workers"), "{}", report);
+    assert!(report.contains("Amdahl fit: serial fraction 10.0%, speedup limit 10.0x"), "{}", report);
+    // 8 workers: 100 / 21.25 = 4.71x, 59% of linear
+    assert!(report.contains("        8       21.25    4.71x         59%"), "{}", report);
+
+    let report = bench::scaling_report(&sweep(&[2, 4], |p| 80.0 / p));
+    assert!(report.contains("Scaling relative to 2 workers"), "{}", report);
+    assert!(report.contains("no measurable serial fraction"), "{}", report);
+    assert!(report.contains("USL fit: needs at least three different worker counts"), "{}", report);
+
+    let report = bench::scaling_report(&sweep(&[4], |p| 80.0 / p));
+    assert!(report.contains("Amdahl fit: needs at least two different worker counts"), "{}", report);
+}
+
+#[test]
+fn usl_fit_recovers_contention_and_coherency() {
+    let (sigma, kappa) = (0.05, 0.001);
+    let report = bench::scaling_report(&sweep(&[1, 2, 4, 8, 16, 32], |p| 100.0 * (1.0 + sigma * (p - 1.0) + kappa * p * (p - 1.0)) / p));
+    assert!(report.contains("USL fit: contention 0.0500, coherency 0.00100"), "{}", report);
+    // Throughput peaks at sqrt((1 - sigma) / kappa)
+    assert!(report.contains("throughput peaks near 31 workers"), "{}", report);
+}
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("bench_{}_{}", std::process::id(), name)).to_string_lossy().into_owned()
+}
+
+fn parse(args: &[&str]) -> (Vec<String>, cli::Options) {
+    cli::parse(args.iter().map(|arg| arg.to_string()).collect()).expect("Invalid arguments")
+}
+
+#[test]
+fn against_passes_the_arguments_on_verbatim() {
+    let (positional, options) = parse(&["rust_filter_async", "bench", "blur", "in.png", "3", "2", "--runs", "1", "--against", "other", "--linear", "--save-baseline", "main"]);
+    assert_eq!(bench::against_args(&positional, &options.option_args), ["blur", "in.png", "3", "2", "--runs", "1", "--linear"]);
+}
+
+#[test]
+fn options_reach_the_other_backend() {
+    let options: [&[&str]; 8] = [
+        &["--sigma-x", "1.5", "--edge", "wrap", "--mode", "anisotropic"],
+        &["--linear", "--channels", "rgb", "--scales", "2"],
+        &["--resample", "nearest"],
+        &["--highlights", "0.7"],
+        &["--falloff", "2.5"],
+        &["--scale", "1.5", "--translate", "3,-2"],
+        &["--clip", "2"],
+        &["--seed", "42"],
+    ];
+    for given in options {
+        let (positional, local) = parse(&[&["rust_filter_async", "bench", "blur", "in.png", "3"][..], given].concat());
+        let forwarded = bench::against_args(&positional, &local.option_args);
+        let (_, other) = parse(&[&["other", "bench"][..], &forwarded.iter().map(String::as_str).collect::<Vec<_>>()].concat());
+        assert_eq!(format!("{:?}", other.filter), format!("{:?}", local.filter), "{:?}", given);
+    }
+}
+
+#[test]
+fn against_benches_the_other_backend_with_the_same_kernel() {
+    let (input, kernel) = (temp_path("input.png"), temp_path("kernel.txt"));
+    ImageBuffer::from_fn(16, 12, |x, y| Rgba([(x * 15) as u8, (y * 20) as u8, 90, 255])).save(&input).unwrap();
+    fs::write(&kernel, "0 1 0\n1 4 1\n0 1 0\n").unwrap();
+    let program = env!("CARGO_BIN_EXE_rust_filter_async");
+    let bench = |against: &str| {
+        Command::new(program)
+            .args(["bench", "convolve", &input, "0", "2", "--kernel", &kernel, "--runs", "1", "--warmup", "0", "--against", against])
+            .output()
+            .expect("Failed to run rust_filter_async")
+    };
+
+    let output = bench(program);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // A backend that cannot be run is reported, and the bench fails without a panic
+    let output = bench("/nonexistent/backend");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Failed to bench /nonexistent/backend") && !stderr.contains("panicked"), "{}", stderr);
+    let _ = (fs::remove_file(&input), fs::remove_file(&kernel));
+}
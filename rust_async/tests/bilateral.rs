@@ -0,0 +1,63 @@
+// The `bilateral` operation: it must match the serial reference whatever the task count, smooth
+// noise on flat regions and keep a hard edge that a blur of the same radius softens.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::bilateral::{self, apply_bilateral_filter_async};
+use rust_filter_async::blur::apply_gaussian_blur_async;
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::noise::{self, Noise};
+
+mod common;
+
+// Dark left half, light right half
+fn step() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(64, 32, |x, _| if x < 32 { Rgba([40, 40, 40, 255]) } else { Rgba([220, 220, 220, 255]) }))
+}
+
+#[tokio::test]
+async fn bilateral_matches_the_reference() {
+    let img = common::fixture();
+    for linear in [false, true] {
+        let filter = FilterOptions { linear, sigma_color: 30.0, ..FilterOptions::default() };
+        for radius in [1, 4] {
+            let result = apply_bilateral_filter_async(&img, radius, bilateral::sigma_space(radius, filter), 30.0, 3, filter).await;
+            common::assert_matches_reference("bilateral", &img, &result, radius, filter, &format!("radius {} linear {}", radius, linear));
+        }
+    }
+}
+
+#[tokio::test]
+async fn bilateral_is_independent_of_task_count() {
+    let img = common::fixture();
+    let filter = FilterOptions::default();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_bilateral_filter_async(&img, 3, 2.0, 25.0, num_tasks, filter)).await;
+}
+
+#[tokio::test]
+async fn bilateral_keeps_edges_the_blur_softens() {
+    let img = step();
+    let filter = FilterOptions::default();
+    let smoothed = apply_bilateral_filter_async(&img, 4, 2.0, 25.0, 4, filter).await;
+    assert!(smoothed == img, "a clean step should pass through unchanged");
+    let blurred = apply_gaussian_blur_async(&img, 4, 4, filter).await;
+    assert!(blurred.get_pixel(31, 16)[0] > 40 && blurred.get_pixel(32, 16)[0] < 220);
+}
+
+#[tokio::test]
+async fn bilateral_smooths_noise_within_a_region() {
+    let clean = step();
+    let filter = FilterOptions { seed: 5, ..FilterOptions::default() };
+    let noisy = noise::add_noise_async(&clean, Noise::Gaussian(8.0), 4, filter).await;
+    let smoothed = apply_bilateral_filter_async(&noisy, 3, 2.0, 40.0, 4, FilterOptions::default()).await;
+    let error = |img: &DynamicImage| {
+        img.pixels().map(|(x, y, pixel)| (pixel[0] as f64 - clean.get_pixel(x, y)[0] as f64).powi(2)).sum::<f64>()
+    };
+    assert!(error(&smoothed) < error(&noisy) / 4.0, "{} against {}", error(&smoothed), error(&noisy));
+    assert!(smoothed.get_pixel(31, 16)[0] < 80 && smoothed.get_pixel(32, 16)[0] > 180, "edge was smeared");
+}
+
+#[tokio::test]
+async fn radius_zero_keeps_the_image() {
+    let img = common::fixture();
+    assert!(apply_bilateral_filter_async(&img, 0, 1.0, 25.0, 4, FilterOptions::default()).await == img);
+}
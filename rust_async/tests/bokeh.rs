@@ -0,0 +1,54 @@
+// The `bokeh` operation: the banded disc blur must match the serial one exactly whatever the task
+// count, leave a flat image alone and, with --highlights, spread bright pixels further.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::bokeh::{self, apply_bokeh_async};
+use rust_filter_async::cli::FilterOptions;
+
+mod common;
+
+// A dark image with one white dot in the middle
+fn dot() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(31, 31, |x, y| if (x, y) == (15, 15) { Rgba([255, 255, 255, 255]) } else { Rgba([20, 30, 40, 255]) }))
+}
+
+#[tokio::test]
+async fn bokeh_matches_the_reference() {
+    let img = common::fixture();
+    let linear = FilterOptions { linear: true, ..FilterOptions::default() };
+    let boosted = FilterOptions { highlights: 4.0, ..FilterOptions::default() };
+    for (radius, num_tasks, filter) in [(1, 1, FilterOptions::default()), (4, 4, linear), (6, 3, boosted), (9, 7, FilterOptions::default())] {
+        let result = apply_bokeh_async(&img, radius, num_tasks, filter).await;
+        common::assert_matches_reference("bokeh", &img, &result, radius, filter, &format!("radius {} tasks {}", radius, num_tasks));
+    }
+}
+
+#[tokio::test]
+async fn bokeh_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 3, 8, 100], |num_tasks| apply_bokeh_async(&img, 5, num_tasks, FilterOptions::default())).await;
+}
+
+#[test]
+fn disc_is_round() {
+    assert_eq!(bokeh::disc(0), vec![(0, 0)]);
+    assert_eq!(bokeh::disc(2), vec![(-2, 0), (-1, 1), (0, 2), (1, 1), (2, 0)]);
+}
+
+#[tokio::test]
+async fn flat_image_is_unchanged() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(20, 12, Rgba([200, 100, 50, 180])));
+    let filter = FilterOptions { highlights: 3.0, ..FilterOptions::default() };
+    assert!(apply_bokeh_async(&img, 4, 3, filter).await == img);
+}
+
+#[tokio::test]
+async fn highlights_brighten_the_disc_around_a_light() {
+    let plain = apply_bokeh_async(&dot(), 5, 4, FilterOptions::default()).await.to_rgba8();
+    let boosted = apply_bokeh_async(&dot(), 5, 4, FilterOptions { highlights: 8.0, ..FilterOptions::default() }).await.to_rgba8();
+    // Inside the disc the dot is averaged in, brighter with the boost; outside it never reaches
+    assert!(plain.get_pixel(18, 15)[0] > 20);
+    assert!(boosted.get_pixel(18, 15)[0] > plain.get_pixel(18, 15)[0]);
+    assert_eq!(boosted.get_pixel(15, 21)[0], 20);
+    assert_eq!(boosted.get_pixel(19, 19)[0], 20);
+}
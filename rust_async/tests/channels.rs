@@ -0,0 +1,69 @@
+// `--channels` keeps the input's values in every channel it does not select, so a mask can be
+// feathered without touching the colors, or the luma blurred while chroma stays sharp.
+
+use image::{DynamicImage, GenericImageView};
+use rust_filter_async::blur::apply_gaussian_blur_async;
+use rust_filter_async::channels::{self, Channels};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::kuwahara::apply_kuwahara_filter_async;
+
+mod common;
+
+// The fixture with a ramp of alpha, so the alpha channel has something to blur
+fn translucent() -> DynamicImage {
+    let mut img = common::fixture().to_rgba8();
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        pixel[3] = ((x * 5 + y * 3) % 256) as u8;
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+fn with_channels(channels: Channels) -> FilterOptions {
+    FilterOptions { channels, ..FilterOptions::default() }
+}
+
+#[tokio::test]
+async fn unselected_channels_pass_through() {
+    let img = translucent();
+    let full = apply_gaussian_blur_async(&img, 4, 3, FilterOptions::default()).await;
+    let alpha = apply_gaussian_blur_async(&img, 4, 3, with_channels(Channels::A)).await;
+    let rgb = apply_gaussian_blur_async(&img, 4, 3, with_channels(Channels::Rgb)).await;
+    for (x, y, src) in img.pixels() {
+        let full = full.get_pixel(x, y);
+        assert_eq!(alpha.get_pixel(x, y).0, [src[0], src[1], src[2], full[3]]);
+        assert_eq!(rgb.get_pixel(x, y).0, [full[0], full[1], full[2], src[3]]);
+    }
+}
+
+#[test]
+fn luma_keeps_chroma() {
+    let src = [200, 40, 90, 128];
+    let merged = channels::select(src, [100, 100, 100, 7], Channels::Luma);
+    // Every color channel moves by the change in luma, and alpha is left alone
+    let shift = merged[0] as i32 - src[0] as i32;
+    assert_eq!(merged[1] as i32 - src[1] as i32, shift);
+    assert_eq!(merged[2] as i32 - src[2] as i32, shift);
+    assert_eq!(merged[3], 128);
+}
+
+#[tokio::test]
+async fn reference_matches_every_channel_selection() {
+    let img = translucent();
+    for channels in [Channels::Rgb, Channels::A, Channels::Luma] {
+        let filter = with_channels(channels);
+        for operation in ["blur", "kuwahara"] {
+            let result = match operation {
+                "blur" => apply_gaussian_blur_async(&img, 3, 3, filter).await,
+                _ => apply_kuwahara_filter_async(&img, 3, 3, filter).await,
+            };
+            common::assert_matches_reference(operation, &img, &result, 3, filter, &format!("{} {:?}", operation, channels));
+        }
+    }
+}
+
+#[test]
+fn channel_names_parse() {
+    assert_eq!("luma".parse(), Ok(Channels::Luma));
+    assert_eq!("a".parse(), Ok(Channels::A));
+    assert!("rgbx".parse::<Channels>().is_err());
+}
@@ -0,0 +1,31 @@
+// Shared by the operation tests: the golden input image, and the two checks every banded
+// operation gets, against the serial reference and across task counts.
+
+#![allow(dead_code)]
+
+use image::{DynamicImage, GenericImageView};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::verify;
+use std::future::Future;
+use std::path::PathBuf;
+
+pub fn fixture() -> DynamicImage {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    DynamicImage::ImageRgba8(image::open(path).expect("Missing fixture").to_rgba8())
+}
+
+// Every pixel of `result` must be what the serial reference of `operation` gives on `src`;
+// `case` names the parameters in the failure message
+pub fn assert_matches_reference(operation: &str, src: &DynamicImage, result: &DynamicImage, radius: i32, filter: FilterOptions, case: &str) {
+    let points: Vec<_> = result.pixels().map(|(x, y, _)| (x, y)).collect();
+    let comparison = verify::check_reference(operation, src, result, radius, filter, &points, 0);
+    assert_eq!(comparison.mismatches, 0, "{} first at {:?}", case, comparison.first_mismatches);
+}
+
+// `apply` must give on every one of `counts` tasks what it gives on one
+pub async fn assert_independent_of_task_count<T: PartialEq, F: Future<Output = T>>(counts: &[usize], apply: impl Fn(usize) -> F) {
+    let one = apply(1).await;
+    for &num_tasks in counts {
+        assert!(apply(num_tasks).await == one, "{} tasks", num_tasks);
+    }
+}
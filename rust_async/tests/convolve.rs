@@ -0,0 +1,96 @@
+// The `emboss`, `edges` and `convolve` operations: the kernels must match the serial reference
+// whatever the task count, turn flat areas mid gray or black, and answer a step edge on both of
+// its sides. Kernel files must parse into the same weights or say what is wrong with them.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::convolve::{self, apply_convolution_async, Kernel};
+
+mod common;
+
+// Dark on the left, light on the right, half transparent
+fn step() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(32, 16, |x, _| if x < 16 { Rgba([40, 40, 40, 128]) } else { Rgba([200, 200, 200, 128]) }))
+}
+
+#[tokio::test]
+async fn kernels_match_the_reference() {
+    let img = common::fixture();
+    for (operation, kernel) in [("emboss", convolve::EMBOSS), ("edges", convolve::LAPLACIAN)] {
+        for (linear, radius) in [(false, 0), (false, 2), (true, 1)] {
+            let filter = FilterOptions { linear, ..FilterOptions::default() };
+            let result = apply_convolution_async(&img, kernel, radius, 3, filter).await;
+            common::assert_matches_reference(operation, &img, &result, radius, filter, &format!("{} linear {} radius {}", operation, linear, radius));
+        }
+    }
+}
+
+#[tokio::test]
+async fn convolution_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_convolution_async(&img, convolve::EMBOSS, 1, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn flat_areas_turn_gray_or_black() {
+    let embossed = apply_convolution_async(&step(), convolve::EMBOSS, 0, 4, FilterOptions::default()).await;
+    let edges = apply_convolution_async(&step(), convolve::LAPLACIAN, 0, 4, FilterOptions::default()).await;
+    for x in [0, 8, 13, 18, 24, 31] {
+        assert_eq!(embossed.get_pixel(x, 8), Rgba([128, 128, 128, 128]), "x {}", x);
+        assert_eq!(edges.get_pixel(x, 8), Rgba([0, 0, 0, 128]), "x {}", x);
+    }
+}
+
+#[tokio::test]
+async fn step_edge_shows_on_both_sides() {
+    let embossed = apply_convolution_async(&step(), convolve::EMBOSS, 0, 4, FilterOptions::default()).await;
+    assert_eq!(embossed.get_pixel(15, 8)[0], 255);
+    assert_eq!(embossed.get_pixel(16, 8)[0], 255);
+    let edges = apply_convolution_async(&step(), convolve::LAPLACIAN, 0, 4, FilterOptions::default()).await;
+    assert_eq!(edges.get_pixel(15, 8)[0], 255);
+    assert_eq!(edges.get_pixel(16, 8)[0], 255);
+}
+
+#[test]
+fn kernel_sums_taps_row_by_row() {
+    let shift = Kernel { width: 3, height: 3, weights: &[0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0], bias: 0.0, absolute: false };
+    assert_eq!(shift.reach(), 1);
+    assert_eq!(shift.apply(|dx, dy| (10 * dx + dy + 50) as f32), 60);
+    let negative = Kernel { bias: -100.0, ..shift };
+    assert_eq!(negative.apply(|_, _| 30.0), 0);
+    assert_eq!(Kernel { absolute: true, ..negative }.apply(|_, _| 30.0), 70);
+}
+
+#[test]
+fn kernel_file_parses_rows_and_keywords() {
+    let kernel: Kernel = "# box blur\ndivisor 3\n1, 1, 1\nbias 2\nabsolute\n".parse().unwrap();
+    assert_eq!((kernel.width, kernel.height), (3, 1));
+    assert_eq!(kernel.weights, &[1.0 / 3.0; 3]);
+    assert_eq!((kernel.bias, kernel.absolute, kernel.reach()), (2.0, true, 0));
+    assert_eq!(kernel.apply(|dx, _| (dx + 2) as f32 * 30.0), 62);
+}
+
+#[test]
+fn kernel_file_errors() {
+    for (text, error) in [
+        ("", "Kernel has no weights"),
+        ("1 1 1\n1 1\n1 1 1", "Kernel rows must be the same length, got 3 and 2"),
+        ("1 1\n1 1", "Kernel must have an odd width and height to center on a pixel, got 2x2"),
+        ("1 x 1", "Invalid weight: x"),
+        ("divisor 0\n1", "divisor must not be 0"),
+        ("scale 2\n1", "Unknown kernel keyword: scale"),
+    ] {
+        assert_eq!(text.parse::<Kernel>().unwrap_err(), error);
+    }
+}
+
+#[tokio::test]
+async fn custom_kernel_matches_the_reference() {
+    let img = common::fixture();
+    let kernel: Kernel = "divisor 16\n1 2 1\n2 4 2\n1 2 1\n2 4 2\n1 2 1".parse().unwrap();
+    for radius in [0, 1] {
+        let filter = FilterOptions { kernel: Some(kernel), ..FilterOptions::default() };
+        let result = apply_convolution_async(&img, kernel, radius, 3, filter).await;
+        common::assert_matches_reference("convolve", &img, &result, radius, filter, &format!("radius {}", radius));
+    }
+}
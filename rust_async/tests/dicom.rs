@@ -0,0 +1,151 @@
+// Uncompressed grayscale DICOM built byte by byte: both transfer syntaxes, signed samples narrower
+// than their allocation, and the VOI window or the value range mapped to 16 bits.
+
+#[cfg(feature = "dicom")]
+use image::{ImageBuffer, Luma};
+use rust_filter_async::dicom;
+
+const EXPLICIT_VR: &str = "1.2.840.10008.1.2.1";
+#[cfg(feature = "dicom")]
+const IMPLICIT_VR: &str = "1.2.840.10008.1.2";
+
+// Values are padded to an even length as the standard requires
+fn even(value: &[u8], pad: u8) -> Vec<u8> {
+    let mut value = value.to_vec();
+    if value.len() % 2 == 1 {
+        value.push(pad);
+    }
+    value
+}
+
+fn tag(tag: u32) -> Vec<u8> {
+    [((tag >> 16) as u16).to_le_bytes(), (tag as u16).to_le_bytes()].concat()
+}
+
+fn explicit(id: u32, vr: &[u8; 2], value: &[u8]) -> Vec<u8> {
+    let mut bytes = [tag(id), vr.to_vec()].concat();
+    if matches!(vr, b"OB" | b"OW" | b"SQ" | b"UN" | b"UT") {
+        bytes.extend([0, 0]);
+        bytes.extend((value.len() as u32).to_le_bytes());
+    } else {
+        bytes.extend((value.len() as u16).to_le_bytes());
+    }
+    bytes.extend(value);
+    bytes
+}
+
+#[cfg(feature = "dicom")]
+fn implicit(id: u32, value: &[u8]) -> Vec<u8> {
+    [tag(id), (value.len() as u32).to_le_bytes().to_vec(), value.to_vec()].concat()
+}
+
+fn us(value: u16) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+#[cfg(feature = "dicom")]
+fn ds(value: &str) -> Vec<u8> {
+    even(value.as_bytes(), b' ')
+}
+
+// Preamble, magic and the explicit VR meta group declaring `syntax`, then `dataset`
+fn file(syntax: &str, dataset: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![0; 128];
+    bytes.extend(b"DICM");
+    bytes.extend(explicit(0x0002_0010, b"UI", &even(syntax.as_bytes(), 0)));
+    bytes.extend(dataset.concat());
+    bytes
+}
+
+// Rows, columns and the sample layout every test image shares
+fn image_elements(element: fn(u32, &[u8]) -> Vec<u8>, rows: u16, columns: u16, bits: (u16, u16), signed: bool) -> Vec<Vec<u8>> {
+    vec![
+        element(0x0028_0002, &us(1)),
+        element(0x0028_0004, &even(b"MONOCHROME2", b' ')),
+        element(0x0028_0010, &us(rows)),
+        element(0x0028_0011, &us(columns)),
+        element(0x0028_0100, &us(bits.0)),
+        element(0x0028_0101, &us(bits.1)),
+        element(0x0028_0103, &us(signed as u16)),
+    ]
+}
+
+fn us_element(id: u32, value: &[u8]) -> Vec<u8> {
+    explicit(id, if id == 0x0028_0004 { b"CS" } else { b"US" }, value)
+}
+
+#[cfg(feature = "dicom")]
+fn load(name: &str, bytes: &[u8]) -> Result<ImageBuffer<Luma<u16>, Vec<u16>>, String> {
+    let path = std::env::temp_dir().join(format!("dicom_{}_{}.dcm", std::process::id(), name)).to_string_lossy().into_owned();
+    std::fs::write(&path, bytes).unwrap();
+    let result = dicom::load(&path).map_err(|e| e.to_string());
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+#[cfg(feature = "dicom")]
+#[test]
+fn explicit_vr_maps_the_window_to_16_bits() {
+    let mut dataset = image_elements(us_element, 1, 4, (16, 16), false);
+    dataset.push(explicit(0x0028_1050, b"DS", &ds("200")));
+    dataset.push(explicit(0x0028_1051, b"DS", &ds("200")));
+    // A sequence of undefined length before the pixels is skipped whole
+    dataset.push([tag(0x0008_1140), b"SQ\0\0".to_vec(), vec![0xFF; 4], tag(0xFFFE_E0DD), vec![0; 4]].concat());
+    let samples: Vec<u8> = [50u16, 100, 200, 400].iter().flat_map(|v| v.to_le_bytes()).collect();
+    dataset.push(explicit(0x7FE0_0010, b"OW", &samples));
+
+    let img = load("window", &file(EXPLICIT_VR, &dataset)).unwrap();
+    assert_eq!(img.dimensions(), (4, 1));
+    // The window spans 100 to 300; values outside it clip
+    assert_eq!(img.as_raw(), &[0, 0, 32768, 65535]);
+}
+
+#[cfg(feature = "dicom")]
+#[test]
+fn implicit_vr_maps_the_value_range() {
+    let mut dataset = image_elements(implicit, 2, 2, (8, 8), false);
+    dataset.push(implicit(0x7FE0_0010, &[10, 20, 30, 60]));
+
+    let img = load("implicit", &file(IMPLICIT_VR, &dataset)).unwrap();
+    assert_eq!(img.dimensions(), (2, 2));
+    assert_eq!(img.as_raw(), &[0, 13107, 26214, 65535]);
+}
+
+#[cfg(feature = "dicom")]
+#[test]
+fn signed_samples_are_sign_extended_from_the_stored_bits() {
+    // 12 of 16 bits stored: 0xFFF is -1 and 0x800 is -2048, and the unused high bits are ignored
+    let mut dataset = image_elements(us_element, 1, 3, (16, 12), true);
+    dataset.push(explicit(0x0028_1052, b"DS", &ds("-1000")));
+    dataset.push(explicit(0x0028_1053, b"DS", &ds("2")));
+    dataset.push(explicit(0x0028_1050, b"DS", &ds("-1002")));
+    dataset.push(explicit(0x0028_1051, b"DS", &ds("8")));
+    let samples: Vec<u8> = [0xFFFFu16, 0xF800, 0x0001].iter().flat_map(|v| v.to_le_bytes()).collect();
+    dataset.push(explicit(0x7FE0_0010, b"OW", &samples));
+
+    // -1 * 2 - 1000 = -1002 at the center, -2048 far below it, 1 * 2 - 1000 = -998 at the top
+    let img = load("signed", &file(EXPLICIT_VR, &dataset)).unwrap();
+    assert_eq!(img.as_raw(), &[32768, 0, 65535]);
+}
+
+#[cfg(feature = "dicom")]
+#[test]
+fn unsupported_files_are_rejected() {
+    assert!(load("magic", &[0; 200]).unwrap_err().contains("Not a DICOM"));
+    let dataset = image_elements(us_element, 1, 1, (16, 16), false);
+    assert!(load("syntax", &file("1.2.840.10008.1.2.4.50", &dataset)).unwrap_err().contains("transfer syntax"));
+    assert!(load("pixels", &file(EXPLICIT_VR, &dataset)).unwrap_err().contains("no pixel data"));
+    let mut truncated = dataset.clone();
+    truncated.push(explicit(0x7FE0_0010, b"OW", &[0; 1]));
+    assert!(load("truncated", &file(EXPLICIT_VR, &truncated)).unwrap_err().contains("Truncated"));
+}
+
+#[cfg(not(feature = "dicom"))]
+#[test]
+fn load_needs_the_feature() {
+    let path = std::env::temp_dir().join(format!("dicom_{}_off.dcm", std::process::id()));
+    std::fs::write(&path, file(EXPLICIT_VR, &image_elements(us_element, 1, 1, (16, 16), false))).unwrap();
+    let error = dicom::load(&path.to_string_lossy()).unwrap_err().to_string();
+    let _ = std::fs::remove_file(&path);
+    assert!(error.contains("--features dicom"), "{}", error);
+}
@@ -0,0 +1,123 @@
+// Tiles spread over `tile-worker`s on loopback: the seams match a single-machine run, tiles lost
+// with a worker go to another, and the length-prefixed protocol refuses what it cannot parse.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::distributed;
+use rust_filter_async::serve;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn image() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(70, 45, |x, y| {
+        Rgba([(x * 3) as u8, (y * 5) as u8, ((x * y) % 251) as u8, 255 - (x + y) as u8])
+    }))
+}
+
+// Starts a tile worker on a free loopback port and waits until it accepts connections
+async fn start_worker() -> String {
+    let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+    tokio::spawn(distributed::serve(port, 2));
+    let address = format!("127.0.0.1:{}", port);
+    for _ in 0..100 {
+        if TcpStream::connect(&address).await.is_ok() {
+            return address;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("tile worker did not start on {}", address);
+}
+
+// A worker that accepts connections and then drops them, as one that crashed mid-tile would
+async fn start_dropping_worker() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut length = [0; 4];
+            let _ = stream.read_exact(&mut length).await;
+        }
+    });
+    address
+}
+
+#[tokio::test]
+async fn tiles_match_a_single_machine_run() {
+    let img = image();
+    let worker = start_worker().await;
+    for operation in ["blur", "kuwahara"] {
+        let (output, stats) = distributed::filter(&img, operation, 3, 2, std::slice::from_ref(&worker), 16, distributed::DEFAULT_TILE_TIMEOUT).await.unwrap();
+        assert_eq!(output, serve::apply(operation, &img, 3, 2).await, "{}", operation);
+        // 5 columns and 3 rows of 16 pixel tiles
+        assert_eq!((stats.tiles, stats.reassigned, stats.per_worker.as_slice()), (15, 0, &[15][..]));
+    }
+}
+
+#[tokio::test]
+async fn lost_tiles_go_to_another_worker() {
+    let img = image();
+    let workers = [start_dropping_worker().await, start_worker().await];
+    let (output, stats) = distributed::filter(&img, "kuwahara", 2, 1, &workers, 32, distributed::DEFAULT_TILE_TIMEOUT).await.unwrap();
+    assert_eq!(output, serve::apply("kuwahara", &img, 2, 1).await);
+    assert_eq!(stats.per_worker[0], 0);
+    assert_eq!(stats.per_worker[1], stats.tiles);
+    assert!(stats.reassigned > 0);
+}
+
+fn failure(job: Result<(DynamicImage, distributed::DistributedStats), Box<dyn std::error::Error>>) -> String {
+    job.err().expect("Job succeeded").to_string()
+}
+
+#[tokio::test]
+async fn unreachable_workers_fail_the_job() {
+    let workers = [start_dropping_worker().await];
+    let error = failure(distributed::filter(&image(), "blur", 1, 1, &workers, 32, distributed::DEFAULT_TILE_TIMEOUT).await);
+    // Each lost tile takes its connection with it, until none are left
+    assert!(error.contains("all workers failed with 6 of 6 tiles left"), "{}", error);
+
+    let error = failure(distributed::filter(&image(), "median", 1, 1, &workers, 32, distributed::DEFAULT_TILE_TIMEOUT).await);
+    assert!(error.contains("'blur' or 'kuwahara'"), "{}", error);
+    let error = failure(distributed::filter(&image(), "blur", serve::MAX_RADIUS + 1, 1, &workers, 32, distributed::DEFAULT_TILE_TIMEOUT).await);
+    assert!(error.contains("radius must be between 0"), "{}", error);
+    let error = failure(distributed::filter(&image(), "blur", 1, 1, &[], 32, distributed::DEFAULT_TILE_TIMEOUT).await);
+    assert!(error.contains("no workers"), "{}", error);
+    let error = failure(distributed::filter(&image(), "blur", 1, 1, &workers, 32, Duration::ZERO).await);
+    assert!(error.contains("tile timeout must be positive"), "{}", error);
+}
+
+#[test]
+fn tile_timeout_grows_with_the_tile() {
+    let timeout = Duration::from_secs(120);
+    assert_eq!(distributed::tile_timeout(timeout, 64, 64), timeout);
+    assert_eq!(distributed::tile_timeout(timeout, 1024, 1024), timeout);
+    // A 1024 pixel tile with a halo of radius 512 has four times the pixels
+    assert_eq!(distributed::tile_timeout(timeout, 2048, 2048), timeout * 4);
+}
+
+#[tokio::test]
+async fn workers_reply_to_bad_requests_with_an_error() {
+    let mut stream = TcpStream::connect(start_worker().await).await.unwrap();
+    // Operation, then radius, tasks, width and height, then the pixels
+    let header = |operation: u8, radius: u32| [vec![operation], [radius, 1, 2, 2].iter().flat_map(|n| n.to_be_bytes()).collect()].concat();
+    let bad_operation = [header(7, 1), vec![0; 16]].concat();
+    let short_tile = [header(0, 1), vec![0; 4]].concat();
+    // u32::MAX would wrap to -1 as an i32
+    let wrapped_radius = [header(1, u32::MAX), vec![0; 16]].concat();
+    let huge_radius = [header(0, 2_000_000_000), vec![0; 16]].concat();
+    let requests = [
+        (bad_operation, "unknown operation 7".to_string()),
+        (short_tile, "2x2 tile does not match 4 bytes of pixels".to_string()),
+        (wrapped_radius, format!("radius -1 is not between 0 and {}", serve::MAX_RADIUS)),
+        (huge_radius, format!("radius 2000000000 is not between 0 and {}", serve::MAX_RADIUS)),
+    ];
+    for (request, expected) in requests {
+        stream.write_all(&(request.len() as u32).to_be_bytes()).await.unwrap();
+        stream.write_all(&request).await.unwrap();
+        let mut length = [0; 4];
+        stream.read_exact(&mut length).await.unwrap();
+        let mut frame = vec![0; u32::from_be_bytes(length) as usize];
+        stream.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame[0], 1);
+        assert_eq!(String::from_utf8_lossy(&frame[1..]), expected);
+    }
+}
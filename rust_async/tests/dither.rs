@@ -0,0 +1,46 @@
+// The `dither` operation: the staircase of rows passing their error down to each other must give
+// exactly the serial result whatever the task count, use only the quantized levels and keep the
+// mean brightness of the input.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::dither::apply_dither_async;
+
+mod common;
+
+#[tokio::test]
+async fn dither_matches_the_reference() {
+    let img = common::fixture();
+    for (levels, num_tasks) in [(2, 1), (2, 4), (5, 3), (16, 7)] {
+        let result = apply_dither_async(&img, levels, num_tasks, FilterOptions::default()).await;
+        common::assert_matches_reference("dither", &img, &result, levels, FilterOptions::default(), &format!("levels {} tasks {}", levels, num_tasks));
+    }
+}
+
+#[tokio::test]
+async fn dither_is_independent_of_task_count() {
+    // Wider than a chunk so rows overlap, and more tasks than rows
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(150, 9, |x, y| Rgba([x as u8, (y * 25) as u8, 128, 255])));
+    common::assert_independent_of_task_count(&[2, 3, 8, 32], |num_tasks| apply_dither_async(&img, 2, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn only_the_quantized_levels_are_used() {
+    let result = apply_dither_async(&common::fixture(), 3, 4, FilterOptions::default()).await.to_rgba8();
+    for pixel in result.pixels() {
+        assert!(pixel.0[..3].iter().all(|value| [0, 128, 255].contains(value)), "{:?}", pixel);
+    }
+    let unchanged = apply_dither_async(&common::fixture(), 256, 4, FilterOptions::default()).await;
+    assert!(unchanged == common::fixture());
+}
+
+#[tokio::test]
+async fn flat_gray_keeps_its_brightness() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(64, 64, Rgba([64, 128, 192, 200])));
+    let result = apply_dither_async(&img, 2, 4, FilterOptions::default()).await.to_rgba8();
+    for (ch, expected) in [64.0, 128.0, 192.0].into_iter().enumerate() {
+        let mean = result.pixels().map(|pixel| pixel[ch] as f64).sum::<f64>() / (64.0 * 64.0);
+        assert!((mean - expected).abs() < 2.0, "channel {} mean {}", ch, mean);
+    }
+    assert!(result.pixels().all(|pixel| pixel[3] == 200));
+}
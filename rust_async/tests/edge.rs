@@ -0,0 +1,99 @@
+// `--edge`: what the blur and the convolutions read past the border, so a tiling texture can wrap
+// around or be mirrored rather than smeared by its repeated edge pixels.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::blur::{self, apply_gaussian_blur_async};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::convolve;
+use rust_filter_async::edge::Edge;
+use rust_filter_async::verify;
+
+mod common;
+
+const MODES: [Edge; 4] = [Edge::Clamp, Edge::Mirror, Edge::Wrap, Edge::Constant([200, 40, 90, 255])];
+
+fn edge(edge: Edge) -> FilterOptions {
+    FilterOptions { edge, ..FilterOptions::default() }
+}
+
+// A texture repeating every 8 pixels both ways, three tiles across and two down
+fn tiles() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(24, 16, |x, y| Rgba([((x % 8) * 30) as u8, ((y % 8) * 30) as u8, if (x % 8 + y % 8) % 3 == 0 { 255 } else { 0 }, 255])))
+}
+
+#[test]
+fn parses_every_mode() {
+    assert_eq!("clamp".parse::<Edge>(), Ok(Edge::Clamp));
+    assert_eq!("mirror".parse::<Edge>(), Ok(Edge::Mirror));
+    assert_eq!("wrap".parse::<Edge>(), Ok(Edge::Wrap));
+    assert_eq!("constant:1,2,3".parse::<Edge>(), Ok(Edge::Constant([1, 2, 3, 255])));
+    assert_eq!("constant:1,2,3,0".parse::<Edge>(), Ok(Edge::Constant([1, 2, 3, 0])));
+    for invalid in ["bounce", "constant:1,2", "constant:256,0,0", "mirror:1,2,3"] {
+        assert!(invalid.parse::<Edge>().is_err(), "{}", invalid);
+    }
+    for mode in MODES {
+        assert_eq!(mode.to_string().parse::<Edge>(), Ok(mode));
+    }
+}
+
+#[test]
+fn indices_past_the_border_map_back_in() {
+    let mapped = |mode: Edge| [-9, -2, -1, 0, 4, 5, 6, 12].map(|i| mode.index(i, 5));
+    assert_eq!(mapped(Edge::Clamp), [0, 0, 0, 0, 4, 4, 4, 4].map(Some));
+    // Reflected about the edge pixels, which are not repeated
+    assert_eq!(mapped(Edge::Mirror), [1, 2, 1, 0, 4, 3, 2, 4].map(Some));
+    assert_eq!(mapped(Edge::Wrap), [1, 3, 4, 0, 4, 0, 1, 2].map(Some));
+    assert_eq!(mapped(Edge::Constant([0; 4])), [None, None, None, Some(0), Some(4), None, None, None]);
+    assert_eq!(Edge::Mirror.index(-3, 1), Some(0));
+}
+
+#[tokio::test]
+async fn wrap_keeps_a_tiling_texture_seamless() {
+    let img = tiles();
+    for radius in [3, 10, blur::FFT_RADIUS as u32 + 4] {
+        let result = apply_gaussian_blur_async(&img, radius, 3, edge(Edge::Wrap)).await;
+        for (x, y, pixel) in result.pixels() {
+            assert_eq!(pixel, result.get_pixel((x + 8) % 24, (y + 8) % 16), "radius {} at ({}, {})", radius, x, y);
+        }
+        // Repeating the edge pixels breaks the tiling
+        let clamped = apply_gaussian_blur_async(&img, radius, 3, edge(Edge::Clamp)).await;
+        assert_ne!(clamped.get_pixel(0, 4), clamped.get_pixel(8, 4), "radius {}", radius);
+    }
+}
+
+#[tokio::test]
+async fn constant_reads_its_color_past_the_border() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(20, 20, Rgba([255, 255, 255, 255])));
+    for mode in [Edge::Clamp, Edge::Mirror, Edge::Wrap] {
+        assert!(apply_gaussian_blur_async(&img, 4, 3, edge(mode)).await == img, "{}", mode);
+    }
+    let result = apply_gaussian_blur_async(&img, 4, 3, edge(Edge::Constant([0, 0, 0, 255]))).await;
+    assert!(result.get_pixel(0, 0)[0] < 200 && result.get_pixel(0, 10)[0] < result.get_pixel(3, 10)[0]);
+    assert_eq!(result.get_pixel(10, 10), Rgba([255, 255, 255, 255]));
+}
+
+#[tokio::test]
+async fn blur_matches_the_reference_in_every_mode() {
+    let img = common::fixture();
+    let points: Vec<_> = img.pixels().map(|(x, y, _)| (x, y)).collect();
+    for mode in MODES {
+        let result = apply_gaussian_blur_async(&img, 5, 3, edge(mode)).await;
+        common::assert_matches_reference("blur", &img, &result, 5, edge(mode), &mode.to_string());
+        // The FFT pass pads its rows by the same mode
+        let radius = blur::FFT_RADIUS as u32 + 8;
+        let result = apply_gaussian_blur_async(&img, radius, 3, edge(mode)).await;
+        let comparison = verify::check_reference("blur", &img, &result, radius as i32, edge(mode), &points.iter().copied().step_by(7).collect::<Vec<_>>(), 1);
+        assert_eq!(comparison.mismatches, 0, "{} through the FFT first at {:?}", mode, comparison.first_mismatches);
+    }
+}
+
+#[tokio::test]
+async fn convolution_matches_the_reference_in_every_mode() {
+    let img = common::fixture();
+    let points: Vec<_> = img.pixels().map(|(x, y, _)| (x, y)).step_by(3).collect();
+    for (mode, radius) in MODES.into_iter().zip([0, 2, 1, 2]) {
+        let result = convolve::apply_convolution_async(&img, convolve::EMBOSS, radius, 4, edge(mode)).await;
+        let comparison = verify::check_reference("emboss", &img, &result, radius, edge(mode), &points, 0);
+        assert_eq!(comparison.mismatches, 0, "{} radius {} first at {:?}", mode, radius, comparison.first_mismatches);
+    }
+}
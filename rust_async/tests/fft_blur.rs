@@ -0,0 +1,70 @@
+// Large blur radii convolve through the FFT. The transform must give back its input, and the FFT
+// pass must agree with the direct one it replaces to within rounding of the last bit.
+
+use image::GenericImageView;
+use rust_filter_async::blur::{self, ImageData};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::fft::{self, Complex};
+use rust_filter_async::timing::WorkerClock;
+use rust_filter_async::verify;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+mod common;
+
+// One pass over every row of the image, through the FFT or directly
+async fn pass(src: &Arc<ImageData>, radius: usize, filter: FilterOptions, through_fft: bool) -> Vec<u8> {
+    let dst = Arc::new(Mutex::new(ImageData { data: vec![0; src.data.len()], width: src.width, height: src.height, channels: src.channels }));
+    let mut clock = WorkerClock::start("test", 0, 0..src.height);
+    let kernel = Arc::new(blur::generate_gaussian_kernel(radius));
+    if through_fft {
+        blur::horizontal_fft_blur(Arc::clone(src), Arc::clone(&dst), kernel, radius, filter, 0..src.height, &mut clock).await;
+    } else {
+        blur::horizontal_gaussian_blur(Arc::clone(src), Arc::clone(&dst), kernel, radius, filter, 0..src.height, &mut clock).await;
+    }
+    let data = dst.lock().await.data.clone();
+    data
+}
+
+#[test]
+fn inverse_transform_gives_back_the_input() {
+    let input: Vec<Complex> = (0..64).map(|i| Complex::new((i as f64 * 0.7).sin() * 100.0, (i % 5) as f64)).collect();
+    let mut data = input.clone();
+    fft::fft(&mut data, false);
+    // A constant's whole energy is in the first bin
+    let mut constant = vec![Complex::new(2.0, 0.0); 16];
+    fft::fft(&mut constant, false);
+    assert!((constant[0].re - 32.0).abs() < 1e-9 && constant[1..].iter().all(|value| value.re.abs() < 1e-9 && value.im.abs() < 1e-9));
+    fft::fft(&mut data, true);
+    for (a, b) in data.iter().zip(&input) {
+        assert!((a.re - b.re).abs() < 1e-9 && (a.im - b.im).abs() < 1e-9, "{:?} != {:?}", a, b);
+    }
+}
+
+#[tokio::test]
+async fn fft_pass_matches_the_direct_pass() {
+    let src = Arc::new(ImageData::from_dynamic_image(&common::fixture()));
+    for linear in [false, true] {
+        let filter = FilterOptions { linear, ..FilterOptions::default() };
+        for radius in [1, 7, 40, 90] {
+            let direct = pass(&src, radius, filter, false).await;
+            let fft = pass(&src, radius, filter, true).await;
+            let worst = direct.iter().zip(&fft).map(|(&a, &b)| a.abs_diff(b)).max().unwrap_or(0);
+            assert!(worst <= 1, "radius {} linear {} differs by {}", radius, linear, worst);
+        }
+    }
+}
+
+#[tokio::test]
+async fn large_radii_match_the_reference() {
+    let img = common::fixture();
+    // The reference costs a whole kernel of rows a pixel, so every seventh pixel is enough
+    let points: Vec<_> = img.pixels().map(|(x, y, _)| (x, y)).step_by(7).collect();
+    let radius = blur::FFT_RADIUS as u32 + 8;
+    let result = blur::apply_gaussian_blur_async(&img, radius, 3, FilterOptions::default()).await;
+    let comparison = verify::check_reference("blur", &img, &result, radius as i32, FilterOptions::default(), &points, 1);
+    assert_eq!(comparison.mismatches, 0, "first at {:?}", comparison.first_mismatches);
+    for num_tasks in [1, 4, 7] {
+        assert!(blur::apply_gaussian_blur_async(&img, radius, num_tasks, FilterOptions::default()).await == result, "{} tasks", num_tasks);
+    }
+}
@@ -0,0 +1,52 @@
+// Filters the fixture in testdata/golden at a few radii and task counts and compares every result
+// with the committed expected image, pixel for pixel. The expected images are shared with the
+// threaded crate, which regenerates them (see rust/tests/golden.rs), so both must stay identical.
+// No other implementation is checked against them.
+
+use image::RgbaImage;
+use rust_filter_async::blur::apply_gaussian_blur_async;
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::kuwahara::apply_kuwahara_filter_async;
+use std::path::PathBuf;
+
+mod common;
+
+// Radii up to more than a band's height at the largest task count, so band edges are covered
+const CASES: [(&str, i32); 5] = [("blur", 1), ("blur", 4), ("blur", 12), ("kuwahara", 2), ("kuwahara", 6)];
+const TASKS: [usize; 3] = [1, 3, 8];
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden")
+}
+
+fn check(operation: &str, radius: i32, tasks: usize, actual: &RgbaImage) {
+    let path = golden_dir().join(format!("{}_r{}.png", operation, radius));
+    let expected = image::open(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)).to_rgba8();
+    assert_eq!(actual.dimensions(), expected.dimensions(), "{} radius {} with {} tasks", operation, radius, tasks);
+    if let Some((x, y, pixel)) = actual.enumerate_pixels().find(|(x, y, pixel)| expected.get_pixel(*x, *y) != *pixel) {
+        panic!(
+            "{} radius {} with {} tasks: pixel ({}, {}) is {:?}, expected {:?}",
+            operation,
+            radius,
+            tasks,
+            x,
+            y,
+            pixel.0,
+            expected.get_pixel(x, y).0
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tasks_match_golden_images() {
+    let img = common::fixture();
+    for (operation, radius) in CASES {
+        for tasks in TASKS {
+            let result = match operation {
+                "blur" => apply_gaussian_blur_async(&img, radius as u32, tasks, FilterOptions::default()).await,
+                _ => apply_kuwahara_filter_async(&img, radius, tasks, FilterOptions::default()).await,
+            };
+            check(operation, radius, tasks, &result.to_rgba8());
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// The `histeq` operation: the merged partial histograms must equal one count over the whole image,
+// the output must match the serial reference whatever the task count, and a low-contrast image
+// must come out spread over the full range.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::channels::Channels;
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::histeq::{self, apply_histogram_equalization_async};
+
+mod common;
+
+// A gradient squeezed into levels 100 to 131
+fn dull() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(64, 40, |x, y| {
+        let value = 100 + (x / 2) as u8;
+        Rgba([value, value, 100 + (y % 32) as u8, 200])
+    }))
+}
+
+#[test]
+fn partial_histograms_merge_into_the_whole() {
+    let img = common::fixture().to_rgba8();
+    let row_len = img.width() as usize * 4;
+    let partials: Vec<_> = img.as_raw().chunks(row_len * 7).map(histeq::count).collect();
+    assert_eq!(histeq::merge(&partials), histeq::count(img.as_raw()));
+    assert_eq!(histeq::merge(&[]), [[0; 256]; 3]);
+}
+
+#[tokio::test]
+async fn histeq_matches_the_reference() {
+    let img = common::fixture();
+    for channels in [Channels::Rgba, Channels::Luma] {
+        let filter = FilterOptions { channels, ..FilterOptions::default() };
+        let result = apply_histogram_equalization_async(&img, 3, filter).await;
+        common::assert_matches_reference("histeq", &img, &result, 0, filter, &format!("{:?}", channels));
+    }
+}
+
+#[tokio::test]
+async fn histeq_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8, 1000], |num_tasks| apply_histogram_equalization_async(&img, num_tasks, FilterOptions::default())).await;
+}
+
+#[test]
+fn table_stretches_to_the_full_range() {
+    let mut histogram = [0; 256];
+    histogram[10] = 5;
+    histogram[20] = 5;
+    histogram[30] = 10;
+    let table = histeq::equalization_table(&histogram);
+    assert_eq!((table[10], table[20], table[30]), (0, 85, 255));
+    assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    // A flat channel has no distribution to stretch
+    let mut flat = [0; 256];
+    flat[77] = 40;
+    assert_eq!(histeq::equalization_table(&flat)[77], 77);
+}
+
+#[tokio::test]
+async fn low_contrast_image_spreads_out() {
+    let equalized = apply_histogram_equalization_async(&dull(), 4, FilterOptions::default()).await.to_rgba8();
+    let reds: Vec<u8> = equalized.pixels().map(|pixel| pixel[0]).collect();
+    assert_eq!((reds.iter().min(), reds.iter().max()), (Some(&0), Some(&255)));
+    assert!(equalized.pixels().all(|pixel| pixel[3] == 200));
+}
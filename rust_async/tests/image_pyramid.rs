@@ -0,0 +1,75 @@
+// The `pyramid` subcommand: Gaussian levels halve down to a pixel, Laplacian levels hold only the
+// detail over the next level, and the pipelined writer gives the same files as the staged one.
+
+use image::{ImageBuffer, Rgba};
+use rust_filter_async::image_pyramid::{self, Kind};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// A fresh directory under the target's scratch space
+fn scratch(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("image_pyramid").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create scratch directory");
+    dir
+}
+
+fn gradient(width: u32, height: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(width, height, |x, y| Rgba([(x * 7 % 256) as u8, (y * 3 % 256) as u8, ((x ^ y) % 256) as u8, 255]))
+}
+
+#[tokio::test]
+async fn levels_run_down_to_a_single_pixel() {
+    assert_eq!(image_pyramid::max_levels(1, 1), 1);
+    assert_eq!(image_pyramid::max_levels(2, 1), 2);
+    assert_eq!(image_pyramid::max_levels(96, 64), 8);
+    let pyramid = image_pyramid::gaussian_pyramid(gradient(45, 20), image_pyramid::max_levels(45, 20), 4).await;
+    let sizes: Vec<_> = pyramid.iter().map(|level| level.dimensions()).collect();
+    assert_eq!(sizes, [(45, 20), (23, 10), (12, 5), (6, 3), (3, 2), (2, 1), (1, 1)]);
+}
+
+#[tokio::test]
+async fn flat_images_have_no_detail() {
+    let img = ImageBuffer::from_pixel(32, 24, Rgba([40, 90, 200, 180]));
+    let coarser = image_pyramid::reduce(&img, 3).await;
+    let detail = image_pyramid::laplacian(Arc::new(img), Arc::new(coarser), 3).await;
+    assert!(detail.pixels().all(|pixel| pixel.0 == [128, 128, 128, 180]), "{:?}", detail.get_pixel(0, 0));
+}
+
+#[tokio::test]
+async fn laplacian_is_independent_of_task_count() {
+    let img = Arc::new(gradient(61, 37));
+    let coarser = Arc::new(image_pyramid::reduce(&img, 1).await);
+    let one = image_pyramid::laplacian(Arc::clone(&img), Arc::clone(&coarser), 1).await;
+    for num_tasks in [2, 3, 8, 64] {
+        assert!(image_pyramid::reduce(&img, num_tasks).await == *coarser, "{} tasks", num_tasks);
+        assert!(image_pyramid::laplacian(Arc::clone(&img), Arc::clone(&coarser), num_tasks).await == one, "{} tasks", num_tasks);
+    }
+}
+
+#[tokio::test]
+async fn pipelined_writes_the_same_levels() {
+    let img = gradient(80, 50);
+    for kind in [Kind::Gaussian, Kind::Laplacian] {
+        let staged = scratch(&format!("{:?}_staged", kind));
+        let pipelined = scratch(&format!("{:?}_pipelined", kind));
+        let stats = image_pyramid::write(&img, kind, 5, staged.to_str().unwrap(), false, 4).await.expect("Staged pyramid failed");
+        image_pyramid::write(&img, kind, 5, pipelined.to_str().unwrap(), true, 4).await.expect("Pipelined pyramid failed");
+        assert_eq!(stats.iter().map(|level| (level.width, level.height)).collect::<Vec<_>>(), [(80, 50), (40, 25), (20, 13), (10, 7), (5, 4)]);
+        for level in 0..5 {
+            let a = fs::read(image_pyramid::level_path(&staged, level)).expect("Missing staged level");
+            let b = fs::read(image_pyramid::level_path(&pipelined, level)).expect("Missing pipelined level");
+            assert!(a == b, "{:?} level {}", kind, level);
+        }
+        // The coarsest level of both is the Gaussian one
+        let top = image::open(image_pyramid::level_path(&pipelined, 4)).unwrap().to_rgba8();
+        assert!(top == image_pyramid::gaussian_pyramid(img.clone(), 5, 4).await[4]);
+    }
+}
+
+#[tokio::test]
+async fn unknown_kinds_are_rejected() {
+    assert_eq!("laplacian".parse::<Kind>(), Ok(Kind::Laplacian));
+    assert!("dog".parse::<Kind>().is_err());
+}
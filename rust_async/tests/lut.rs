@@ -0,0 +1,95 @@
+// `.cube` parsing and trilinear grading. Tables built from simple functions of the color have a
+// known result at every pixel, which the interpolation must reproduce.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::lut::{self, Lut};
+use rust_filter_async::verify;
+use std::sync::Arc;
+
+type Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+// A `.cube` file of `size` entries per side mapping each grid color through `grade`
+fn cube(size: usize, header: &str, grade: impl Fn([f32; 3]) -> [f32; 3]) -> String {
+    let mut text = format!("# generated\nTITLE \"test\"\nLUT_3D_SIZE {}\n{}", size, header);
+    let step = |i: usize| i as f32 / (size - 1) as f32;
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                let [r, g, b] = grade([step(r), step(g), step(b)]);
+                text.push_str(&format!("{} {} {}\n", r, g, b));
+            }
+        }
+    }
+    text
+}
+
+fn table(text: &str) -> Arc<Lut> {
+    Arc::new(text.parse().expect("Invalid .cube"))
+}
+
+fn image() -> Image {
+    Image::from_fn(61, 37, |x, y| Rgba([(x * 4) as u8, (y * 7) as u8, ((x * y) % 256) as u8, (x + y) as u8]))
+}
+
+async fn grade(img: &Image, lut: &Arc<Lut>, tasks: usize) -> Image {
+    let input = DynamicImage::ImageRgba8(img.clone());
+    lut::apply_lut_async(&input, Arc::clone(lut), tasks, FilterOptions::default()).await.to_rgba8()
+}
+
+#[tokio::test]
+async fn identity_tables_change_nothing() {
+    let img = image();
+    for size in [2, 17, 33] {
+        assert!(grade(&img, &table(&cube(size, "", |rgb| rgb)), 3).await == img, "size {}", size);
+    }
+}
+
+#[tokio::test]
+async fn interpolation_is_exact_for_linear_grades() {
+    let img = image();
+    let inverted = grade(&img, &table(&cube(2, "", |rgb| rgb.map(|c| 1.0 - c))), 3).await;
+    for (src, out) in img.pixels().zip(inverted.pixels()) {
+        assert_eq!(out.0, [255 - src[0], 255 - src[1], 255 - src[2], src[3]]);
+    }
+    // Channels swapped across the grid, so every output depends on another input channel
+    let swapped = grade(&img, &table(&cube(5, "", |[r, g, b]| [b, r, g])), 3).await;
+    for (src, out) in img.pixels().zip(swapped.pixels()) {
+        assert_eq!(out.0, [src[2], src[0], src[1], src[3]]);
+    }
+}
+
+#[test]
+fn domain_scales_the_input() {
+    let lut = table(&cube(2, "DOMAIN_MIN 0 0 0\nDOMAIN_MAX 0.5 0.5 0.5\n", |rgb| rgb));
+    assert_eq!(lut.map([0, 100, 200]), [0, 200, 255]);
+}
+
+#[tokio::test]
+async fn grade_is_independent_of_task_count() {
+    let img = image();
+    let lut = table(&cube(9, "", |[r, g, b]| [r * r, g.sqrt(), (r + b) / 2.0]));
+    let expected = grade(&img, &lut, 1).await;
+    for tasks in [0, 2, 7, 64] {
+        assert!(grade(&img, &lut, tasks).await == expected, "{} tasks", tasks);
+    }
+}
+
+#[tokio::test]
+async fn reference_grades_the_same_pixels() {
+    let img = image();
+    let lut = table(&cube(9, "", |[r, g, b]| [r * r, g.sqrt(), (r + b) / 2.0]));
+    let graded = DynamicImage::ImageRgba8(grade(&img, &lut, 4).await);
+    let points = verify::sample_points(img.width(), img.height());
+    let input = DynamicImage::ImageRgba8(img);
+    assert_eq!(verify::check_lut(&input, &graded, &lut, FilterOptions::default(), &points, 0).mismatches, 0);
+}
+
+#[test]
+fn malformed_tables_are_rejected() {
+    assert!("0 0 0\n1 1 1\n".parse::<Lut>().unwrap_err().contains("Missing LUT_3D_SIZE"));
+    assert!("LUT_1D_SIZE 4\n".parse::<Lut>().unwrap_err().contains("1D"));
+    assert!("LUT_3D_SIZE 2\n0 0 0\n".parse::<Lut>().unwrap_err().contains("Expected 8 table entries"));
+    assert!("LUT_3D_SIZE 2\nGAMMA 2.2\n".parse::<Lut>().unwrap_err().contains("Unknown .cube keyword"));
+    assert!(cube(2, "", |rgb| rgb).replace("1 1 1", "1 x 1").parse::<Lut>().is_err());
+}
@@ -0,0 +1,78 @@
+// ImageMagick-style command lines: operators become native steps in the order given.
+
+use rust_filter_async::magick::{self, Step};
+
+fn parse(line: &str) -> Result<magick::Command, String> {
+    magick::parse(&args(line))
+}
+
+fn blur_step(line: &str) -> (i32, Option<f64>) {
+    match parse(line).expect("Invalid command").steps.as_slice() {
+        [Step::Filter { operation: "blur", radius, sigma }] => (*radius, *sigma),
+        _ => panic!("expected a single blur in {}", line),
+    }
+}
+
+#[test]
+fn blur_sigma_goes_through_unchanged() {
+    assert_eq!(blur_step("in.png -blur 0x2.5 out.png"), (0, Some(2.5)));
+    assert_eq!(blur_step("in.png -gaussian-blur 4x1.5 out.png"), (4, Some(1.5)));
+    // ImageMagick's sigma defaults to 1, and the radius alone only bounds the kernel
+    assert_eq!(blur_step("in.png -blur 5 out.png"), (5, Some(1.0)));
+}
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(str::to_string).collect()
+}
+
+fn target(geometry: &str, width: u32, height: u32) -> (u32, u32) {
+    magick::Geometry::parse(geometry).expect("Invalid geometry").target(width, height)
+}
+
+#[test]
+fn single_dash_operators_mark_the_style() {
+    assert!(magick::is_magick_style(&args("in.png -blur 0x2 out.png")));
+    assert!(!magick::is_magick_style(&args("blur in.png out.png 3 --threads 4")));
+    // A negative number is an operand, not an operator
+    assert!(!magick::is_magick_style(&args("gamma in.png out.png -1")));
+}
+
+#[test]
+fn steps_keep_command_line_order() {
+    let command = parse("in.png -resize 50% -kuwahara 3x1 -blur 2 -limit thread 6 out.png").unwrap();
+    assert_eq!((command.input.as_str(), command.output.as_str()), ("in.png", "out.png"));
+    assert_eq!(command.threads, Some(6));
+    match command.steps.as_slice() {
+        [Step::Resize(_), Step::Filter { operation: "kuwahara", radius: 3, sigma: None }, Step::Filter { operation: "blur", radius: 2, .. }] => {}
+        _ => panic!("steps out of order"),
+    }
+}
+
+#[test]
+fn unsupported_input_is_an_error() {
+    let error = |line: &str| parse(line).err().unwrap_or_else(|| panic!("{} parsed", line));
+    assert!(error("in.png -sharpen 0x1 out.png").contains("Unsupported ImageMagick option: -sharpen"));
+    assert!(error("in.png -limit memory 1GiB out.png").contains("-limit memory"));
+    assert!(error("in.png -limit thread 0 out.png").contains("Invalid thread limit"));
+    assert!(error("in.png -blur").contains("Missing value for -blur"));
+    assert!(error("in.png -blur -1x2 out.png").contains("Invalid geometry"));
+    assert!(error("in.png -blur 0x2 out.png extra.png").contains("got 3 file names"));
+    assert!(error("in.png -resize x out.png").contains("Invalid geometry"));
+}
+
+#[test]
+fn resize_geometry_follows_imagemagick() {
+    assert_eq!(target("50%", 400, 300), (200, 150));
+    assert_eq!(target("180x70%", 100, 100), (180, 70));
+    assert_eq!(target("180%x70%", 100, 100), (180, 70));
+    // A box keeps the aspect ratio and fits inside, unless `!` asks for exactly that size
+    assert_eq!(target("200x200", 400, 300), (200, 150));
+    assert_eq!(target("200x200!", 400, 300), (200, 200));
+    assert_eq!(target("100x", 400, 300), (100, 75));
+    assert_eq!(target("x150", 400, 300), (200, 150));
+    // `>` only shrinks and `<` only enlarges
+    assert_eq!(target("800x800>", 400, 300), (400, 300));
+    assert_eq!(target("200x200>", 400, 300), (200, 150));
+    assert_eq!(target("200x200<", 400, 300), (400, 300));
+    assert_eq!(target("1%", 10, 10), (1, 1));
+}
@@ -0,0 +1,42 @@
+// The `median` operation: the sliding histogram must pick the same value as sorting the window,
+// whatever the task count, and clear isolated salt and pepper pixels.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::median::apply_median_filter_async;
+use rust_filter_async::noise::{self, Noise};
+
+mod common;
+
+#[tokio::test]
+async fn median_matches_the_sorted_reference() {
+    let img = common::fixture();
+    for radius in [1, 3, 7] {
+        let result = apply_median_filter_async(&img, radius, 3, FilterOptions::default()).await;
+        common::assert_matches_reference("median", &img, &result, radius, FilterOptions::default(), &format!("radius {}", radius));
+    }
+}
+
+#[tokio::test]
+async fn median_removes_salt_and_pepper() {
+    let gray = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(96, 64, Rgba([128, 128, 128, 255])));
+    let filter = FilterOptions { seed: 7, ..FilterOptions::default() };
+    let noisy = noise::add_noise_async(&gray, Noise::SaltPepper(0.05), 4, filter).await;
+    // Only a clump of five like hits in one window survives radius 1
+    let denoised = apply_median_filter_async(&noisy, 1, 4, FilterOptions::default()).await;
+    let hits = |img: &DynamicImage| img.pixels().filter(|(_, _, pixel)| pixel.0 != [128, 128, 128, 255]).count();
+    assert!(hits(&noisy) > 200, "{} noisy pixels", hits(&noisy));
+    assert!(hits(&denoised) < 5, "{} pixels left", hits(&denoised));
+}
+
+#[tokio::test]
+async fn median_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_median_filter_async(&img, 2, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn radius_zero_keeps_the_image() {
+    let img = common::fixture();
+    assert!(apply_median_filter_async(&img, 0, 4, FilterOptions::default()).await == img);
+}
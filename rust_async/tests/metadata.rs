@@ -0,0 +1,104 @@
+// EXIF orientation: every tag value turns the stored pixels upright, and outputs carry the EXIF
+// block with the orientation reset so viewers do not rotate them a second time.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, ImageFormat, Rgba, RgbaImage};
+use rust_filter_async::metadata::{self, Metadata};
+use std::io::Cursor;
+
+// A TIFF structure holding only IFD0 with the orientation entry
+fn exif(orientation: u16, little_endian: bool) -> Vec<u8> {
+    let u16_bytes = |v: u16| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+    let u32_bytes = |v: u32| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+    let mut tiff = if little_endian { b"II".to_vec() } else { b"MM".to_vec() };
+    tiff.extend(u16_bytes(42));
+    tiff.extend(u32_bytes(8));
+    tiff.extend(u16_bytes(1));
+    // Orientation, SHORT, one value, padded to four bytes
+    tiff.extend(u16_bytes(0x0112));
+    tiff.extend(u16_bytes(3));
+    tiff.extend(u32_bytes(1));
+    tiff.extend(u16_bytes(orientation));
+    tiff.extend([0, 0]);
+    tiff.extend(u32_bytes(0));
+    tiff
+}
+
+// 3x2 pixels that all differ, so any wrong turn shows
+fn image() -> RgbaImage {
+    ImageBuffer::from_fn(3, 2, |x, y| Rgba([x as u8 * 80, y as u8 * 200, 7, 255]))
+}
+
+fn encode(img: &RgbaImage, format: ImageFormat) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    img.write_to(&mut Cursor::new(&mut encoded), format).unwrap();
+    encoded
+}
+
+// A PNG of `image()` tagged with `orientation`
+fn tagged_png(orientation: u16, little_endian: bool) -> Vec<u8> {
+    let tags = Metadata { exif: Some(exif(orientation, little_endian)), orientation: 1, icc: None };
+    metadata::embed_in_memory(encode(&image(), ImageFormat::Png), &tags)
+}
+
+// Where the stored pixel at (x, y) of a `width` x `height` image is displayed, per the EXIF standard
+fn displayed(orientation: u32, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+    match orientation {
+        2 => (width - 1 - x, y),
+        3 => (width - 1 - x, height - 1 - y),
+        4 => (x, height - 1 - y),
+        5 => (y, x),
+        6 => (height - 1 - y, x),
+        7 => (height - 1 - y, width - 1 - x),
+        8 => (y, width - 1 - x),
+        _ => (x, y),
+    }
+}
+
+#[test]
+fn every_orientation_turns_upright() {
+    let img = image();
+    for orientation in 1..=8 {
+        for little_endian in [true, false] {
+            let tags = metadata::read_from_memory(&tagged_png(orientation as u16, little_endian));
+            assert_eq!(tags.orientation, orientation);
+            assert!(tags.exif.is_some());
+
+            let upright = metadata::apply_orientation(DynamicImage::ImageRgba8(img.clone()), tags.orientation).to_rgba8();
+            let expected_size = if orientation >= 5 { (2, 3) } else { (3, 2) };
+            assert_eq!(upright.dimensions(), expected_size, "orientation {}", orientation);
+            for (x, y, pixel) in img.enumerate_pixels() {
+                let (dx, dy) = displayed(orientation, x, y, 3, 2);
+                assert_eq!(upright.get_pixel(dx, dy), pixel, "orientation {} at {},{}", orientation, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn outputs_carry_exif_marked_upright() {
+    for little_endian in [true, false] {
+        let tags = metadata::read_from_memory(&tagged_png(6, little_endian));
+        let upright = metadata::apply_orientation(DynamicImage::ImageRgba8(image()), tags.orientation).to_rgba8();
+        for format in [ImageFormat::Png, ImageFormat::Jpeg] {
+            let output = metadata::embed_in_memory(encode(&upright, format), &tags);
+            let written = metadata::read_from_memory(&output);
+            assert_eq!(written.orientation, 1, "{:?}", format);
+            assert_eq!(image::load_from_memory(&output).unwrap().dimensions(), (2, 3));
+        }
+    }
+    // The input's tags are left alone
+    assert_eq!(metadata::read_from_memory(&tagged_png(6, true)).orientation, 6);
+}
+
+#[test]
+fn untagged_and_unsupported_outputs_pass_through() {
+    let png = encode(&image(), ImageFormat::Png);
+    let tags = metadata::read_from_memory(&png);
+    assert_eq!((tags.orientation, tags.exif.is_none()), (1, true));
+    assert_eq!(metadata::embed_in_memory(png.clone(), &tags), png);
+
+    // BMP has no place for EXIF, the pixels are written without it
+    let bmp = encode(&image(), ImageFormat::Bmp);
+    let tags = metadata::read_from_memory(&tagged_png(3, true));
+    assert_eq!(metadata::embed_in_memory(bmp.clone(), &tags), bmp);
+}
@@ -0,0 +1,29 @@
+// Every sample draws from one stream, so the estimate depends on the seed and the sample count,
+// never on how the samples are split among tasks.
+
+use rust_filter_async::monte_carlo;
+
+#[tokio::test]
+async fn estimate_is_independent_of_task_count() {
+    for total_samples in [0, 1, 7, 1000, 100_003] {
+        let expected = monte_carlo::count_inside(monte_carlo::SEED, 0..total_samples);
+        for tasks in [0, 1, 2, 3, 7, 16, 64] {
+            let inside = monte_carlo::sample_inside(total_samples, tasks).await;
+            assert_eq!(inside, expected, "{} samples on {} tasks", total_samples, tasks);
+        }
+    }
+}
+
+#[test]
+fn skipping_ahead_matches_drawing_in_order() {
+    // Any cut gives the same total, including ones that skip past millions of draws
+    let whole = monte_carlo::count_inside(monte_carlo::SEED, 0..20_000);
+    for cut in [1, 999, 10_000, 19_999] {
+        let parts = monte_carlo::count_inside(monte_carlo::SEED, 0..cut) + monte_carlo::count_inside(monte_carlo::SEED, cut..20_000);
+        assert_eq!(parts, whole, "cut at {}", cut);
+    }
+    let far = 5_000_000..5_010_000;
+    let skipped = monte_carlo::count_inside(monte_carlo::SEED, far.clone());
+    let drawn = monte_carlo::count_inside(monte_carlo::SEED, 0..far.end) - monte_carlo::count_inside(monte_carlo::SEED, 0..far.start);
+    assert_eq!(skipped, drawn);
+}
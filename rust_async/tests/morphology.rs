@@ -0,0 +1,53 @@
+// The `dilate` and `erode` operations: both passes must match the serial reference whatever the
+// task count, grow or shrink a shape by exactly the radius, and undo each other on a square.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::morphology::{apply_morphology_async, Morphology};
+
+mod common;
+
+// A white 8x8 square on black, from (12, 10) to (19, 17)
+fn square() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(32, 28, |x, y| if (12..20).contains(&x) && (10..18).contains(&y) { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) }))
+}
+
+fn white_bounds(img: &DynamicImage) -> (u32, u32, u32, u32) {
+    let white: Vec<_> = img.pixels().filter(|(_, _, p)| p[0] == 255).map(|(x, y, _)| (x, y)).collect();
+    let xs = white.iter().map(|p| p.0);
+    let ys = white.iter().map(|p| p.1);
+    (xs.clone().min().unwrap(), ys.clone().min().unwrap(), xs.max().unwrap(), ys.max().unwrap())
+}
+
+#[tokio::test]
+async fn morphology_matches_the_reference() {
+    let img = common::fixture();
+    for (operation, op) in [("dilate", Morphology::Dilate), ("erode", Morphology::Erode)] {
+        for radius in [1, 3, 100] {
+            let result = apply_morphology_async(&img, op, radius, 3, FilterOptions::default()).await;
+            common::assert_matches_reference(operation, &img, &result, radius, FilterOptions::default(), &format!("{} radius {}", operation, radius));
+        }
+    }
+}
+
+#[tokio::test]
+async fn morphology_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_morphology_async(&img, Morphology::Erode, 2, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn radius_grows_and_shrinks_the_square() {
+    let dilated = apply_morphology_async(&square(), Morphology::Dilate, 2, 4, FilterOptions::default()).await;
+    assert_eq!(white_bounds(&dilated), (10, 8, 21, 19));
+    let eroded = apply_morphology_async(&square(), Morphology::Erode, 2, 4, FilterOptions::default()).await;
+    assert_eq!(white_bounds(&eroded), (14, 12, 17, 15));
+}
+
+#[tokio::test]
+async fn closing_and_opening_keep_a_square() {
+    let dilated = apply_morphology_async(&square(), Morphology::Dilate, 3, 4, FilterOptions::default()).await;
+    assert!(apply_morphology_async(&dilated, Morphology::Erode, 3, 4, FilterOptions::default()).await.to_rgba8() == square().to_rgba8());
+    let eroded = apply_morphology_async(&square(), Morphology::Erode, 3, 4, FilterOptions::default()).await;
+    assert!(apply_morphology_async(&eroded, Morphology::Dilate, 3, 4, FilterOptions::default()).await.to_rgba8() == square().to_rgba8());
+}
@@ -0,0 +1,52 @@
+// The `motion-blur` operation: the directional blur must match the serial reference whatever the
+// task count, smear only along its angle and leave the image alone for lengths below two.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::motion_blur::apply_motion_blur_async;
+
+mod common;
+
+// A single white column on black
+fn vertical_line() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(32, 16, |x, _| if x == 16 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) }))
+}
+
+#[tokio::test]
+async fn motion_blur_matches_the_reference() {
+    let img = common::fixture();
+    for (linear, angle, length) in [(false, 0.0, 7), (false, 90.0, 6), (true, 30.0, 9), (false, -135.0, 5)] {
+        let filter = FilterOptions { linear, angle, ..FilterOptions::default() };
+        let result = apply_motion_blur_async(&img, length, angle, 3, filter).await;
+        common::assert_matches_reference("motion-blur", &img, &result, length as i32, filter, &format!("linear {} angle {} length {}", linear, angle, length));
+    }
+}
+
+#[tokio::test]
+async fn motion_blur_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_motion_blur_async(&img, 8, 45.0, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn horizontal_blur_spreads_a_line_sideways_only() {
+    let blurred = apply_motion_blur_async(&vertical_line(), 5, 0.0, 4, FilterOptions::default()).await;
+    for y in 0..16 {
+        assert_eq!(blurred.get_pixel(16, y)[0], 51);
+        assert_eq!(blurred.get_pixel(14, y)[0], 51);
+        assert_eq!(blurred.get_pixel(18, y)[0], 51);
+        assert_eq!(blurred.get_pixel(13, y)[0], 0);
+        assert_eq!(blurred.get_pixel(19, y)[0], 0);
+    }
+    // Along the line itself nothing changes
+    let along = apply_motion_blur_async(&vertical_line(), 5, 90.0, 4, FilterOptions::default()).await;
+    assert!(along.to_rgba8() == vertical_line().to_rgba8());
+}
+
+#[tokio::test]
+async fn short_lengths_are_the_identity() {
+    let img = common::fixture();
+    for length in [0, 1] {
+        assert!(apply_motion_blur_async(&img, length, 20.0, 3, FilterOptions::default()).await.to_rgba8() == img.to_rgba8(), "length {}", length);
+    }
+}
@@ -0,0 +1,61 @@
+// `--scales` Kuwahara: the radius and its halvings, blended by how flat each one's best quadrant is,
+// so fine detail survives where the full radius alone would smooth it away.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::colorspace::ColorSpace;
+use rust_filter_async::kuwahara::{self, apply_kuwahara_filter_async};
+use std::path::PathBuf;
+
+mod common;
+
+fn scales(scales: u32) -> FilterOptions {
+    FilterOptions { scales, ..FilterOptions::default() }
+}
+
+// A white line one pixel wide down the middle of a black image
+fn thin_line() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(21, 21, |x, _| if x == 10 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) }))
+}
+
+#[test]
+fn radii_halve_until_zero() {
+    assert_eq!(kuwahara::scale_radii(8, 3).collect::<Vec<_>>(), [8, 4, 2]);
+    assert_eq!(kuwahara::scale_radii(2, 5).collect::<Vec<_>>(), [2, 1]);
+    assert_eq!(kuwahara::scale_radii(5, 0).collect::<Vec<_>>(), [5]);
+}
+
+#[tokio::test]
+async fn one_scale_is_the_plain_filter() {
+    let img = thin_line();
+    assert!(apply_kuwahara_filter_async(&img, 6, 3, scales(1)).await == apply_kuwahara_filter_async(&img, 6, 3, FilterOptions::default()).await);
+}
+
+#[tokio::test]
+async fn finer_scales_keep_thin_detail() {
+    let img = thin_line();
+    let single = apply_kuwahara_filter_async(&img, 6, 3, scales(1)).await;
+    let blended = apply_kuwahara_filter_async(&img, 6, 3, scales(3)).await;
+    assert!(blended.get_pixel(10, 10)[0] > single.get_pixel(10, 10)[0], "expected the line to keep more of its contrast");
+    // Every scale agrees on flat areas
+    assert_eq!(blended.get_pixel(2, 10), Rgba([0, 0, 0, 255]));
+}
+
+#[tokio::test]
+async fn result_is_independent_of_task_count() {
+    let img = thin_line();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_kuwahara_filter_async(&img, 6, num_tasks, scales(3))).await;
+}
+
+#[tokio::test]
+async fn reference_matches_across_scales() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let img = image::open(path).expect("Missing fixture");
+    for colorspace in [ColorSpace::Rgb, ColorSpace::Lab] {
+        let filter = FilterOptions { colorspace, ..scales(3) };
+        for radius in [2, 6] {
+            let result = apply_kuwahara_filter_async(&img, radius, 3, filter).await;
+            common::assert_matches_reference("kuwahara", &img, &result, radius, filter, &format!("radius {} {:?}", radius, colorspace));
+        }
+    }
+}
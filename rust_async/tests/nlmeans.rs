@@ -0,0 +1,71 @@
+// The `nlmeans` operation: non-local means must match the serial reference whatever the task
+// count, cover every pixel with exactly one tile, and smooth noise away without blurring an edge.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::nlmeans::{self, apply_nlmeans_async, TILE_SIZE};
+use rust_filter_async::noise::Noise;
+
+mod common;
+
+// Dark on the left, light on the right, with Gaussian noise over both, three tiles by two
+fn noisy_step() -> (ImageBuffer<Rgba<u8>, Vec<u8>>, DynamicImage) {
+    let clean = ImageBuffer::from_fn(150, 80, |x, _| if x < 75 { Rgba([60, 60, 60, 255]) } else { Rgba([190, 190, 190, 255]) });
+    let noise = Noise::Gaussian(12.0);
+    let noisy = ImageBuffer::from_fn(150, 80, |x, y| Rgba(noise.pixel(7, x, y, clean.get_pixel(x, y).0)));
+    (clean, DynamicImage::ImageRgba8(noisy))
+}
+
+// Mean absolute difference of the red channel
+fn error(a: &ImageBuffer<Rgba<u8>, Vec<u8>>, b: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> f64 {
+    a.pixels().zip(b.pixels()).map(|(p, q)| p[0].abs_diff(q[0]) as f64).sum::<f64>() / a.pixels().len() as f64
+}
+
+#[tokio::test]
+async fn nlmeans_matches_the_reference() {
+    let img = common::fixture();
+    for (patch_radius, strength) in [(1, 10.0), (2, 25.0), (0, 4.0)] {
+        let filter = FilterOptions { patch_radius, strength, ..FilterOptions::default() };
+        let result = apply_nlmeans_async(&img, 3, 3, filter).await;
+        common::assert_matches_reference("nlmeans", &img, &result, 3, filter, &format!("patch {} strength {}", patch_radius, strength));
+    }
+}
+
+#[tokio::test]
+async fn nlmeans_is_independent_of_task_count() {
+    let (_, img) = noisy_step();
+    common::assert_independent_of_task_count(&[2, 4, 9], |num_tasks| apply_nlmeans_async(&img, 2, num_tasks, FilterOptions::default())).await;
+}
+
+#[test]
+fn tiles_cover_every_pixel_once() {
+    for (width, height) in [(150, 80), (64, 64), (1, 200), (TILE_SIZE + 1, 3)] {
+        let mut covered = vec![0; (width * height) as usize];
+        for tile in nlmeans::tiles(width, height) {
+            assert!(tile.x.len() as u32 <= TILE_SIZE && tile.y.len() as u32 <= TILE_SIZE);
+            for y in tile.y.clone() {
+                for x in tile.x.clone() {
+                    covered[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&count| count == 1), "{}x{}", width, height);
+    }
+}
+
+#[tokio::test]
+async fn nlmeans_removes_noise_and_keeps_the_edge() {
+    let (clean, noisy) = noisy_step();
+    let filter = FilterOptions { strength: 15.0, ..FilterOptions::default() };
+    let denoised = apply_nlmeans_async(&noisy, 4, 4, filter).await.to_rgba8();
+    assert!(error(&denoised, &clean) < error(&noisy.to_rgba8(), &clean) / 2.0, "{} against {}", error(&denoised, &clean), error(&noisy.to_rgba8(), &clean));
+    for y in 10..70 {
+        assert!(denoised.get_pixel(73, y)[0] < 100 && denoised.get_pixel(76, y)[0] > 150, "row {}", y);
+    }
+}
+
+#[tokio::test]
+async fn zero_radius_is_the_identity() {
+    let img = common::fixture();
+    assert!(apply_nlmeans_async(&img, 0, 3, FilterOptions::default()).await.to_rgba8() == img.to_rgba8());
+}
@@ -0,0 +1,43 @@
+// The `pixelate` operation: block means from the shared summed-area table must match the serial
+// reference whatever the task count, fill every block with one color and cut the last blocks
+// short at the image edge.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::pixelate::apply_pixelate_async;
+
+mod common;
+
+#[tokio::test]
+async fn pixelate_matches_the_reference() {
+    let img = common::fixture();
+    for (block, linear, alpha_weighted) in [(4, false, false), (7, true, false), (5, false, true)] {
+        let filter = FilterOptions { linear, alpha_weighted, ..FilterOptions::default() };
+        let result = apply_pixelate_async(&img, block, 3, filter).await;
+        common::assert_matches_reference("pixelate", &img, &result, block, filter, &format!("block {}", block));
+    }
+}
+
+#[tokio::test]
+async fn pixelate_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8, 64], |num_tasks| apply_pixelate_async(&img, 6, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn every_block_is_one_color_and_the_last_ones_are_cut_short() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(10, 7, |x, y| Rgba([(x * 20) as u8, (y * 30) as u8, 90, 255])));
+    let result = apply_pixelate_async(&img, 4, 2, FilterOptions::default()).await.to_rgba8();
+    for (x, y, pixel) in result.enumerate_pixels() {
+        assert_eq!(pixel, result.get_pixel(x / 4 * 4, y / 4 * 4), "at {} {}", x, y);
+    }
+    // The bottom right block is the 2x3 corner alone
+    assert_eq!(result.get_pixel(9, 6), &Rgba([170, 150, 90, 255]));
+}
+
+#[tokio::test]
+async fn blocks_of_one_pixel_change_nothing() {
+    let img = common::fixture();
+    assert!(apply_pixelate_async(&img, 1, 4, FilterOptions::default()).await == img);
+    assert!(apply_pixelate_async(&img, 0, 4, FilterOptions::default()).await == img);
+}
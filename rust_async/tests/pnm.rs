@@ -0,0 +1,80 @@
+// Binary PGM, PPM and PAM files read into RGBA and written back, including the header forms other
+// tools produce: comments, 16-bit samples and maxvals other than 255.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::pnm;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("pnm_{}_{}", std::process::id(), name)).to_string_lossy().into_owned()
+}
+
+async fn load_bytes(name: &str, bytes: &[u8]) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, String> {
+    let path = temp_path(name);
+    std::fs::write(&path, bytes).unwrap();
+    let result = pnm::load(&path, 3).await.map(|img| img.to_rgba8()).map_err(|e| e.to_string());
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+#[tokio::test]
+async fn reads_pgm_and_ppm_with_comments() {
+    let gray = load_bytes("gray.pgm", b"P5\n# made by hand\n2 1 # two wide\n255\n\x10\xf0").await.unwrap();
+    assert_eq!(gray.as_raw(), &[0x10, 0x10, 0x10, 255, 0xf0, 0xf0, 0xf0, 255]);
+
+    let color = load_bytes("color.ppm", b"P6 1 2 255\n\x01\x02\x03\x04\x05\x06").await.unwrap();
+    assert_eq!(color.dimensions(), (1, 2));
+    assert_eq!(color.as_raw(), &[1, 2, 3, 255, 4, 5, 6, 255]);
+}
+
+#[tokio::test]
+async fn reads_pam_with_alpha() {
+    let gray_alpha = load_bytes("ga.pam", b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 2\nMAXVAL 255\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n\x40\x80").await.unwrap();
+    assert_eq!(gray_alpha.as_raw(), &[0x40, 0x40, 0x40, 0x80]);
+    let rgba = load_bytes("rgba.pam", b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n\x01\x02\x03\x04").await.unwrap();
+    assert_eq!(rgba.as_raw(), &[1, 2, 3, 4]);
+}
+
+#[tokio::test]
+async fn rescales_other_maxvals() {
+    // 16-bit samples are big-endian
+    let deep = load_bytes("deep.pgm", b"P5\n2 1\n65535\n\xff\xff\x80\x00").await.unwrap();
+    assert_eq!(deep.as_raw(), &[255, 255, 255, 255, 128, 128, 128, 255]);
+    let shallow = load_bytes("shallow.pgm", b"P5\n2 1\n15\n\x0f\x05").await.unwrap();
+    assert_eq!(shallow.as_raw(), &[255, 255, 255, 255, 85, 85, 85, 255]);
+}
+
+#[tokio::test]
+async fn rejects_bad_headers() {
+    assert!(load_bytes("ascii.ppm", b"P3\n1 1\n255\n1 2 3\n").await.unwrap_err().contains("Only binary"));
+    assert!(load_bytes("short.ppm", b"P6\n2 2\n255\n\x01\x02\x03").await.unwrap_err().contains("Truncated PNM pixel data"));
+    assert!(load_bytes("cut.ppm", b"P6\n2").await.unwrap_err().contains("Truncated PNM header"));
+    assert!(load_bytes("maxval.pgm", b"P5\n1 1\n70000\n\x00").await.is_err());
+    assert!(load_bytes("depth.pam", b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nENDHDR\n\x00\x00\x00\x00\x00").await.is_err());
+}
+
+#[tokio::test]
+async fn huge_sizes_do_not_wrap() {
+    // width * channels * 2 overflows usize, and would wrap to a tiny row without the check
+    let header = format!("P6\n{} 1\n65535\n", usize::MAX / 4);
+    assert!(load_bytes("wide.ppm", header.as_bytes()).await.unwrap_err().contains("too large"));
+    let header = format!("P5\n{} {}\n255\n", u32::MAX as u64 + 1, 1);
+    assert!(load_bytes("u32.pgm", header.as_bytes()).await.unwrap_err().contains("too large"));
+}
+
+#[tokio::test]
+async fn round_trips_through_each_format() {
+    let img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_fn(7, 5, |x, y| Rgba([(x * 30) as u8, (y * 50) as u8, (x * y) as u8, (100 + x * 20) as u8]));
+    for name in ["rt.ppm", "rt.pam", "rt.pgm"] {
+        let path = temp_path(name);
+        pnm::save(&path, &DynamicImage::ImageRgba8(img.clone()), 3).await.unwrap();
+        let read = pnm::load(&path, 2).await.unwrap().to_rgba8();
+        let _ = std::fs::remove_file(&path);
+        for (src, out) in img.pixels().zip(read.pixels()) {
+            match name {
+                "rt.pam" => assert_eq!(out, src),
+                "rt.ppm" => assert_eq!(out.0, [src[0], src[1], src[2], 255]),
+                _ => assert!(out[0] == out[1] && out[1] == out[2] && out[3] == 255),
+            }
+        }
+    }
+}
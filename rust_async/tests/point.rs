@@ -0,0 +1,57 @@
+// The point operations `posterize` and `gamma`: each pixel must match the serial formula whatever
+// the task count, posterize must leave only its levels and gamma must keep black and white.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::point::{apply_gamma_async, apply_posterize_async};
+use std::collections::HashSet;
+
+mod common;
+
+fn ramp() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(256, 3, |x, y| Rgba([x as u8, (255 - x) as u8, (x * y) as u8, 200])))
+}
+
+#[tokio::test]
+async fn posterize_and_gamma_match_the_reference() {
+    let img = common::fixture();
+    for (levels, num_tasks) in [(2, 1), (5, 4), (256, 7)] {
+        let result = apply_posterize_async(&img, levels, num_tasks, FilterOptions::default()).await;
+        common::assert_matches_reference("posterize", &img, &result, levels as i32, FilterOptions::default(), &format!("{} levels", levels));
+    }
+    for (gamma, num_tasks) in [(0.45, 1), (1.0, 3), (2.2, 8)] {
+        let filter = FilterOptions { gamma: Some(gamma), ..FilterOptions::default() };
+        let result = apply_gamma_async(&img, gamma, num_tasks, filter).await;
+        common::assert_matches_reference("gamma", &img, &result, 0, filter, &format!("gamma {}", gamma));
+    }
+}
+
+#[tokio::test]
+async fn point_operations_are_independent_of_task_count() {
+    let img = common::fixture();
+    let posterized = apply_posterize_async(&img, 4, 1, FilterOptions::default()).await;
+    let corrected = apply_gamma_async(&img, 1.8, 1, FilterOptions::default()).await;
+    for num_tasks in [2, 3, 8, 100] {
+        assert!(apply_posterize_async(&img, 4, num_tasks, FilterOptions::default()).await == posterized, "{} tasks", num_tasks);
+        assert!(apply_gamma_async(&img, 1.8, num_tasks, FilterOptions::default()).await == corrected, "{} tasks", num_tasks);
+    }
+}
+
+#[tokio::test]
+async fn posterize_leaves_only_its_levels() {
+    let result = apply_posterize_async(&ramp(), 4, 3, FilterOptions::default()).await.to_rgba8();
+    let values: HashSet<u8> = result.pixels().flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    assert_eq!(values, HashSet::from([0, 85, 170, 255]));
+    assert!(result.pixels().all(|pixel| pixel[3] == 200));
+}
+
+#[tokio::test]
+async fn gamma_bends_the_midtones_only() {
+    let img = ramp();
+    assert!(apply_gamma_async(&img, 1.0, 4, FilterOptions::default()).await == img);
+    let brighter = apply_gamma_async(&img, 2.2, 4, FilterOptions::default()).await.to_rgba8();
+    assert_eq!(brighter.get_pixel(0, 0)[0], 0);
+    assert_eq!(brighter.get_pixel(255, 0)[0], 255);
+    assert!(brighter.get_pixel(128, 0)[0] > 128);
+    assert!(apply_gamma_async(&img, 0.5, 4, FilterOptions::default()).await.to_rgba8().get_pixel(128, 0)[0] < 128);
+}
@@ -0,0 +1,42 @@
+// The `quantize` operation: the parallel k-means must find exactly the serial palette whatever the
+// task count, use no more than k colors and leave an image that already has few colors as is.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::quantize::apply_quantize_async;
+use std::collections::HashSet;
+
+mod common;
+
+#[tokio::test]
+async fn quantize_matches_the_reference() {
+    let img = common::fixture();
+    for (k, num_tasks) in [(2, 1), (4, 4), (16, 3), (64, 7)] {
+        let result = apply_quantize_async(&img, k, num_tasks, FilterOptions::default()).await;
+        common::assert_matches_reference("quantize", &img, &result, k, FilterOptions::default(), &format!("k {} tasks {}", k, num_tasks));
+    }
+}
+
+#[tokio::test]
+async fn quantize_is_independent_of_task_count() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(40, 9, |x, y| Rgba([(x * 6) as u8, (y * 25) as u8, (x * y) as u8, 255])));
+    common::assert_independent_of_task_count(&[2, 3, 8, 32], |num_tasks| apply_quantize_async(&img, 8, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn at_most_k_colors_are_used() {
+    let result = apply_quantize_async(&common::fixture(), 5, 4, FilterOptions::default()).await.to_rgba8();
+    let colors: HashSet<[u8; 3]> = result.pixels().map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    assert!(colors.len() <= 5, "{} colors", colors.len());
+    assert!(result.pixels().zip(common::fixture().to_rgba8().pixels()).all(|(out, src)| out[3] == src[3]));
+}
+
+#[tokio::test]
+async fn few_colors_are_kept() {
+    let colors = [[255, 0, 0], [0, 0, 255], [240, 240, 240]];
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(30, 30, |x, y| {
+        let [r, g, b] = colors[((x / 10 + y / 10) % 3) as usize];
+        Rgba([r, g, b, 255])
+    }));
+    assert!(apply_quantize_async(&img, 3, 4, FilterOptions::default()).await == img);
+}
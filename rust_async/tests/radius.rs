@@ -0,0 +1,67 @@
+// Degenerate radii: 0 is the identity, a radius wider than the image clamps at the border, and a
+// negative one is refused by the binary before anything is loaded.
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use rust_filter_async::blur::{self, apply_gaussian_blur_async};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::colorspace::ColorSpace;
+use rust_filter_async::kuwahara::apply_kuwahara_filter_async;
+use std::process::Command;
+
+mod common;
+
+fn pattern(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        Rgba([(x * 37 + y * 11) as u8, (y * 53) as u8, ((x ^ y) * 29) as u8, (200 + x) as u8])
+    }))
+}
+
+#[tokio::test]
+async fn zero_radius_is_identity() {
+    let img = pattern(13, 7);
+    let lab = FilterOptions { colorspace: ColorSpace::Lab, linear: true, ..FilterOptions::default() };
+    for filter in [FilterOptions::default(), lab] {
+        assert!(apply_gaussian_blur_async(&img, 0, 4, filter).await == img);
+        assert!(apply_kuwahara_filter_async(&img, 0, 4, filter).await == img);
+    }
+    assert_eq!(blur::generate_gaussian_kernel(0), vec![1.0]);
+}
+
+#[tokio::test]
+async fn oversized_radius_clamps_at_the_border() {
+    let img = pattern(9, 5);
+    let longest = apply_kuwahara_filter_async(&img, 9, 2, FilterOptions::default()).await;
+    assert!(apply_kuwahara_filter_async(&img, 500, 2, FilterOptions::default()).await == longest);
+
+    let single = pattern(1, 1);
+    assert!(apply_gaussian_blur_async(&single, 50, 2, FilterOptions::default()).await == single);
+    let flat = DynamicImage::ImageRgba8(RgbaImage::from_pixel(6, 4, Rgba([90, 140, 30, 255])));
+    assert!(apply_gaussian_blur_async(&flat, 50, 2, FilterOptions::default()).await == flat);
+}
+
+#[tokio::test]
+async fn huge_blur_radius_is_capped_at_the_longest_side() {
+    let img = common::fixture();
+    // 96x64: a radius of 2 billion would otherwise allocate a kernel of 32 GB
+    let capped = apply_gaussian_blur_async(&img, 96, 2, FilterOptions::default()).await;
+    assert!(apply_gaussian_blur_async(&img, 2_000_000_000, 2, FilterOptions::default()).await == capped);
+    assert_eq!(blur::capped_radius(2_000_000_000, 96, 64), 96);
+}
+
+#[tokio::test]
+#[should_panic(expected = "radius must not be negative")]
+async fn negative_radius_panics_in_the_library() {
+    apply_kuwahara_filter_async(&pattern(4, 4), -1, 1, FilterOptions::default()).await;
+}
+
+#[test]
+fn negative_radius_is_an_error_on_the_command_line() {
+    for operation in ["blur", "kuwahara"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_rust_filter_async"))
+            .args([operation, "missing.png", "out.png", "-3"])
+            .output()
+            .expect("Failed to run rust_filter_async");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Radius must not be negative"));
+    }
+}
@@ -0,0 +1,68 @@
+// The `resize` operation: both passes must match the serial reference for every kernel, shrinking
+// and enlarging, whatever the task count, and the kernels must keep flat areas flat.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::magick::Geometry;
+use rust_filter_async::resize::{self, Resample};
+
+mod common;
+
+const KERNELS: [Resample; 4] = [Resample::Nearest, Resample::Bilinear, Resample::Bicubic, Resample::Lanczos3];
+
+async fn resized(img: &DynamicImage, size: &str, num_tasks: usize, filter: FilterOptions) -> (DynamicImage, FilterOptions) {
+    let filter = FilterOptions { size: Some(Geometry::parse(size).unwrap()), ..filter };
+    let (width, height) = filter.size.unwrap().target(img.width(), img.height());
+    (resize::apply_resize_async(img, width, height, num_tasks, filter).await, filter)
+}
+
+#[tokio::test]
+async fn resize_matches_the_reference() {
+    let img = common::fixture();
+    for resample in KERNELS {
+        for (size, linear, alpha_weighted) in [("37%", false, false), ("180%x70%", true, false), ("50x61!", false, true)] {
+            let (result, filter) = resized(&img, size, 3, FilterOptions { resample, linear, alpha_weighted, ..FilterOptions::default() }).await;
+            common::assert_matches_reference("resize", &img, &result, 0, filter, &format!("{:?} to {}", resample, size));
+        }
+    }
+}
+
+#[tokio::test]
+async fn resize_is_independent_of_task_count() {
+    let img = &common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| async move { resized(img, "61%", num_tasks, FilterOptions::default()).await.0 }).await;
+}
+
+#[tokio::test]
+async fn flat_color_stays_flat() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(40, 30, Rgba([10, 120, 250, 90])));
+    for resample in KERNELS {
+        for size in ["13x", "97x", "40x90!"] {
+            let (result, _) = resized(&img, size, 4, FilterOptions { resample, ..FilterOptions::default() }).await;
+            assert!(result.pixels().all(|(_, _, pixel)| pixel == Rgba([10, 120, 250, 90])), "{:?} to {}", resample, size);
+        }
+    }
+}
+
+#[tokio::test]
+async fn nearest_repeats_source_pixels() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(3, 2, |x, y| Rgba([(x * 80) as u8, (y * 200) as u8, 7, 255])));
+    let (result, _) = resized(&img, "200%", 2, FilterOptions { resample: Resample::Nearest, ..FilterOptions::default() }).await;
+    assert_eq!(result.dimensions(), (6, 4));
+    for (x, y, pixel) in result.pixels() {
+        assert_eq!(pixel, img.get_pixel(x / 2, y / 2), "at {} {}", x, y);
+    }
+}
+
+#[test]
+fn taps_add_up_to_one() {
+    for resample in KERNELS {
+        for (src_len, dst_len) in [(100, 7), (7, 100), (64, 64), (1, 5)] {
+            for taps in resize::taps(src_len, dst_len, resample) {
+                let total: f32 = taps.weights.iter().sum();
+                assert!((total - 1.0).abs() < 1e-5, "{:?} {} to {}", resample, src_len, dst_len);
+                assert!(taps.start + taps.weights.len() <= src_len);
+            }
+        }
+    }
+}
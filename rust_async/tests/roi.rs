@@ -0,0 +1,94 @@
+// `--roi`: only a rectangle is filtered, with enough context around it that its pixels come out as
+// they would from filtering the whole image, and everything outside is the input.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::blur::apply_gaussian_blur_async;
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::convolve;
+use rust_filter_async::kuwahara::{apply_kuwahara_filter_async, KuwaharaMode};
+use rust_filter_async::roi::{self, Roi};
+
+mod common;
+
+fn region(x: u32, y: u32, width: u32, height: u32) -> Roi {
+    Roi { x, y, width, height }
+}
+
+// The whole image filtered inside the rectangle, the input outside it
+fn expected(src: &DynamicImage, whole: &DynamicImage, roi: Roi) -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(src.width(), src.height(), |x, y| if roi.contains(x, y) { whole.get_pixel(x, y) } else { src.get_pixel(x, y) }))
+}
+
+#[test]
+fn parses_a_rectangle() {
+    assert_eq!("10,20,30,40".parse::<Roi>(), Ok(region(10, 20, 30, 40)));
+    for invalid in ["1,2,0,4", "1,2,3,0", "1,2,3", "1,2,3,4,5", "a,b,c,d", "-1,2,3,4"] {
+        assert!(invalid.parse::<Roi>().is_err(), "{}", invalid);
+    }
+    assert_eq!(region(1, 2, 3, 4).to_string().parse::<Roi>(), Ok(region(1, 2, 3, 4)));
+}
+
+#[test]
+fn rectangle_is_clipped_and_grown_within_the_image() {
+    assert_eq!(region(90, 5, 20, 10).clip(100, 50), Some(region(90, 5, 10, 10)));
+    assert_eq!(region(100, 5, 20, 10).clip(100, 50), None);
+    assert_eq!(region(10, 2, 5, 5).window(3, 100, 50), region(7, 0, 11, 10));
+    assert_eq!(region(0, 0, 100, 50).window(8, 100, 50), region(0, 0, 100, 50));
+}
+
+#[tokio::test]
+async fn only_the_window_is_filtered() {
+    let img = common::fixture();
+    let result = roi::apply(&img, region(10, 12, 20, 8), 4, |window| async move {
+        assert_eq!(window.dimensions(), (28, 16));
+        DynamicImage::ImageRgba8(ImageBuffer::from_pixel(window.width(), window.height(), Rgba([1, 2, 3, 4])))
+    }).await;
+    let flat = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(img.width(), img.height(), Rgba([1, 2, 3, 4])));
+    assert!(result == expected(&img, &flat, region(10, 12, 20, 8)));
+    // A rectangle off the image leaves it as it is, without running the filter
+    assert!(roi::apply(&img, region(img.width(), 0, 5, 5), 4, |_| async { unreachable!() }).await == img);
+}
+
+#[tokio::test]
+async fn blur_inside_matches_the_whole_image() {
+    let img = common::fixture();
+    let radius = 6;
+    let whole = apply_gaussian_blur_async(&img, radius, 3, FilterOptions::default()).await;
+    for roi in [region(10, 5, 30, 20), region(0, 0, 12, 40), region(img.width() - 7, img.height() - 9, 50, 50)] {
+        for num_tasks in [1, 3, 7] {
+            let result = roi::apply(&img, roi, radius as usize, |window| async move {
+                apply_gaussian_blur_async(&window, radius, num_tasks, FilterOptions::default()).await
+            }).await;
+            let clipped = roi.clip(img.width(), img.height()).unwrap();
+            assert!(result == expected(&img, &whole, clipped), "{:?} with {} tasks", roi, num_tasks);
+        }
+    }
+}
+
+#[tokio::test]
+async fn kuwahara_and_emboss_inside_match_the_whole_image() {
+    let img = common::fixture();
+    let roi = region(15, 20, 25, 18);
+    let whole = apply_kuwahara_filter_async(&img, 5, 4, FilterOptions::default()).await;
+    let result = roi::apply(&img, roi, roi::margin("kuwahara", 5, FilterOptions::default()), |window| async move { apply_kuwahara_filter_async(&window, 5, 4, FilterOptions::default()).await }).await;
+    assert!(result == expected(&img, &whole, roi));
+
+    // The pre-blur's radius plus the kernel's reach
+    let whole = convolve::apply_convolution_async(&img, convolve::EMBOSS, 2, 4, FilterOptions::default()).await;
+    let result = roi::apply(&img, roi, roi::margin("emboss", 2, FilterOptions::default()), |window| async move {
+        convolve::apply_convolution_async(&window, convolve::EMBOSS, 2, 4, FilterOptions::default()).await
+    }).await;
+    assert!(result == expected(&img, &whole, roi));
+}
+
+#[tokio::test]
+async fn anisotropic_kuwahara_inside_matches_the_whole_image() {
+    // The ellipses reach twice the radius, and their orientation reads the structure tensor's kernel
+    let img = common::fixture();
+    let filter = FilterOptions { kuwahara_mode: KuwaharaMode::Anisotropic, ..FilterOptions::default() };
+    let roi = region(30, 30, 36, 36);
+    let whole = apply_kuwahara_filter_async(&img, 6, 4, filter).await;
+    let result = roi::apply(&img, roi, roi::margin("kuwahara", 6, filter), |window| async move { apply_kuwahara_filter_async(&window, 6, 4, filter).await }).await;
+    let clipped = roi.clip(img.width(), img.height()).unwrap();
+    assert!(result == expected(&img, &whole, clipped));
+}
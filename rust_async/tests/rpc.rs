@@ -0,0 +1,162 @@
+// JSON-RPC over stdio with `--rpc`: malformed input gets the standard error codes, `applyFilter`
+// writes the filtered image and reuses decoded inputs, and requests are answered by id. The
+// `daemon` shares its decoded inputs between clients of its Unix socket.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+#[cfg(unix)]
+use rust_filter_async::rpc;
+use rust_filter_async::serve;
+use serde_json::{json, Value};
+use std::io::Write;
+#[cfg(unix)]
+use std::path::Path;
+use std::process::{Command, Stdio};
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("rpc_{}_{}.png", std::process::id(), name)).to_string_lossy().into_owned()
+}
+
+fn image() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(40, 30, |x, y| Rgba([(x * 6) as u8, (y * 8) as u8, ((x * y) % 251) as u8, 255])))
+}
+
+fn apply_filter(id: u32, input: &str, output: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "applyFilter",
+        "params": { "operation": "kuwahara", "input": input, "output": output, "radius": 2, "tasks": 2 },
+    })
+    .to_string()
+}
+
+// Sends `lines` and closes stdin, which lets the running requests finish before the process exits
+fn rpc_session(lines: &[String]) -> Vec<Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust_filter_async"))
+        .args(["--rpc", "--max-concurrent", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run rust_filter_async");
+    child.stdin.take().unwrap().write_all((lines.join("\n") + "\n").as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+// Responses by id, progress notifications left out
+fn response(messages: &[Value], id: Value) -> &Value {
+    messages.iter().find(|m| m.get("id") == Some(&id)).unwrap_or_else(|| panic!("no response to {}", id))
+}
+
+fn error_code(messages: &[Value], id: Value) -> i64 {
+    response(messages, id)["error"]["code"].as_i64().expect("not an error")
+}
+
+#[test]
+fn malformed_requests_get_standard_errors() {
+    let messages = rpc_session(&[
+        "not json".to_string(),
+        json!({ "jsonrpc": "1.0", "id": 1, "method": "applyFilter" }).to_string(),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "resize" }).to_string(),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "applyFilter", "params": { "operation": "blur" } }).to_string(),
+        json!({ "jsonrpc": "2.0", "id": 4, "method": "applyFilter", "params": { "operation": "median", "input": "a.png", "output": "b.png", "radius": 2 } }).to_string(),
+        json!({ "jsonrpc": "2.0", "id": 5, "method": "cancel", "params": { "id": 99 } }).to_string(),
+        // Refused before the input is read, which does not exist
+        json!({ "jsonrpc": "2.0", "id": 6, "method": "applyFilter", "params": { "operation": "blur", "input": "a.png", "output": "b.png", "radius": 2_000_000_000 } }).to_string(),
+        // A notification gets no response, even a failing one
+        json!({ "jsonrpc": "2.0", "method": "resize" }).to_string(),
+    ]);
+
+    assert_eq!(messages.len(), 7);
+    assert_eq!(error_code(&messages, Value::Null), -32700);
+    assert_eq!(error_code(&messages, json!(1)), -32600);
+    assert_eq!(error_code(&messages, json!(2)), -32601);
+    assert_eq!(error_code(&messages, json!(3)), -32602);
+    assert_eq!(error_code(&messages, json!(4)), -32602);
+    assert_eq!(response(&messages, json!(5))["result"], json!({ "cancelled": false }));
+    assert_eq!(error_code(&messages, json!(6)), -32602);
+    assert!(response(&messages, json!(6))["error"]["message"].as_str().unwrap().contains(&serve::MAX_RADIUS.to_string()));
+}
+
+#[tokio::test]
+async fn apply_filter_writes_the_output_and_reuses_the_input() {
+    let (input, first, second) = (temp_path("input"), temp_path("first"), temp_path("second"));
+    image().save(&input).unwrap();
+    let missing = temp_path("missing");
+
+    let messages = rpc_session(&[apply_filter(1, &input, &first), apply_filter(2, &input, &second), apply_filter(3, &missing, &first)]);
+    let expected = serve::apply("kuwahara", &image(), 2, 2).await.to_rgba8();
+    for output in [&first, &second] {
+        assert_eq!(image::open(output).unwrap().to_rgba8(), expected);
+        let _ = std::fs::remove_file(output);
+    }
+    let _ = std::fs::remove_file(&input);
+
+    let results = [&response(&messages, json!(1))["result"], &response(&messages, json!(2))["result"]];
+    for result in results {
+        assert_eq!((&result["width"], &result["height"]), (&json!(40), &json!(30)));
+    }
+    // One at a time, so whichever ran second found the decoded input
+    let cached: Vec<bool> = results.iter().map(|result| result["cached"].as_bool().unwrap()).collect();
+    assert!(cached.contains(&true) && cached.contains(&false), "{:?}", cached);
+
+    assert_eq!(error_code(&messages, json!(3)), -32000);
+    assert!(response(&messages, json!(3))["error"]["message"].as_str().unwrap().contains("missing"));
+}
+
+// Sends one request on a fresh connection to the daemon and returns its response
+#[cfg(unix)]
+async fn daemon_request(socket: &Path, request: String) -> Value {
+    let (input, mut output) = UnixStream::connect(socket).await.unwrap().into_split();
+    output.write_all((request + "\n").as_bytes()).await.unwrap();
+    let mut lines = BufReader::new(input).lines();
+    while let Some(line) = lines.next_line().await.unwrap() {
+        let message: Value = serde_json::from_str(&line).unwrap();
+        if message.get("id").is_some() {
+            return message;
+        }
+    }
+    panic!("daemon closed the connection without responding");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn daemon_clients_share_decoded_inputs() {
+    let socket = std::env::temp_dir().join(format!("rpc_{}.sock", std::process::id()));
+    // A socket file left behind by a daemon that did not shut down cleanly is replaced
+    let _ = std::fs::remove_file(&socket);
+    drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+    tokio::spawn({
+        let socket = socket.clone();
+        async move { rpc::serve(&socket, 2).await }
+    });
+    for _ in 0..100 {
+        if UnixStream::connect(&socket).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    let (input, output) = (temp_path("daemon_input"), temp_path("daemon_output"));
+    image().save(&input).unwrap();
+    let first = daemon_request(&socket, apply_filter(1, &input, &output)).await;
+    let second = daemon_request(&socket, apply_filter(1, &input, &output)).await;
+    assert_eq!(first["result"]["cached"], json!(false), "{}", first);
+    assert_eq!(second["result"]["cached"], json!(true), "{}", second);
+
+    // A newer modification time means the file changed, and it is decoded again
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+    std::fs::File::options().write(true).open(&input).unwrap().set_modified(later).unwrap();
+    let third = daemon_request(&socket, apply_filter(1, &input, &output)).await;
+    assert_eq!(third["result"]["cached"], json!(false), "{}", third);
+    let _ = std::fs::remove_file(&input);
+    let _ = std::fs::remove_file(&output);
+
+    let error = rpc::serve(&socket, 2).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+}
@@ -0,0 +1,69 @@
+// `--sectors` Kuwahara: overlapping Gaussian sectors of a disc in place of the square quadrants, so
+// edges at any angle come through without the quadrants' axis-aligned blocks.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::{self, FilterOptions};
+use rust_filter_async::colorspace::ColorSpace;
+use rust_filter_async::kuwahara::apply_kuwahara_filter_async;
+use std::path::PathBuf;
+
+mod common;
+
+fn sectors(sectors: u32) -> FilterOptions {
+    FilterOptions { sectors: Some(sectors), ..FilterOptions::default() }
+}
+
+// Black above a diagonal running down from the top left, white below it
+fn diagonal() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(24, 24, |x, y| if 2 * x > y + 8 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }))
+}
+
+#[tokio::test]
+async fn flat_images_are_unchanged() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(13, 9, Rgba([40, 120, 200, 255])));
+    assert!(apply_kuwahara_filter_async(&img, 4, 3, sectors(8)).await == img);
+}
+
+#[tokio::test]
+async fn diagonal_edges_keep_both_sides() {
+    let img = diagonal();
+    let result = apply_kuwahara_filter_async(&img, 4, 3, sectors(8)).await;
+    for (x, y, pixel) in result.pixels() {
+        // Clear of the edge, every sector on the pixel's side is flat and outweighs the rest
+        let distance = (2 * x as i32 - y as i32 - 8).abs();
+        if distance > 2 {
+            let expected = img.get_pixel(x, y)[0];
+            assert!(pixel[0].abs_diff(expected) <= 2, "({}, {}) is {:?}", x, y, pixel);
+        }
+    }
+}
+
+#[tokio::test]
+async fn result_is_independent_of_task_count() {
+    let img = diagonal();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_kuwahara_filter_async(&img, 5, num_tasks, sectors(6))).await;
+}
+
+#[tokio::test]
+async fn reference_matches_with_sectors() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden/input.png");
+    let img = image::open(path).expect("Missing fixture");
+    for (colorspace, count) in [(ColorSpace::Rgb, 8), (ColorSpace::Lab, 5)] {
+        let filter = FilterOptions { colorspace, ..sectors(count) };
+        for radius in [1, 4] {
+            let result = apply_kuwahara_filter_async(&img, radius, 3, filter).await;
+            common::assert_matches_reference("kuwahara", &img, &result, radius, filter, &format!("radius {} {:?}", radius, filter));
+        }
+    }
+}
+
+#[test]
+fn sectors_need_the_classic_mode() {
+    let parse = |args: &[&str]| cli::parse(args.iter().map(|arg| arg.to_string()).collect());
+    assert!(parse(&["filter", "kuwahara", "in.png", "out.png", "4", "--sectors", "8"]).is_ok());
+    assert!(parse(&["filter", "kuwahara", "in.png", "out.png", "4", "--mode", "classic", "--sectors", "8"]).is_ok());
+    for mode in ["adaptive", "anisotropic"] {
+        assert!(parse(&["filter", "kuwahara", "in.png", "out.png", "4", "--mode", mode, "--sectors", "8"]).is_err(), "{}", mode);
+        assert!(parse(&["filter", "kuwahara", "in.png", "out.png", "4", "--sectors", "8", "--mode", mode]).is_err(), "{}", mode);
+    }
+}
@@ -0,0 +1,55 @@
+// Uploads are sized from their header before they are decoded, so a huge image is refused cheaply,
+// and a radius past the maximum is refused before the upload is looked at.
+
+use axum::http::StatusCode;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use rust_filter_async::serve;
+use std::io::Cursor;
+use std::time::Duration;
+
+#[test]
+fn huge_uploads_are_refused_before_decoding() {
+    // A header alone, with no pixels behind it: reading them would fail with 400 instead
+    let header = b"P6\n1000000000 1000000000\n255\n";
+    let (code, message) = serve::decode("kuwahara", header).unwrap_err();
+    assert_eq!(code, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(message.contains("Image too large"), "{}", message);
+}
+
+#[test]
+fn small_uploads_decode() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(5, 3, Rgba([10, 20, 30, 255])));
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+    assert_eq!(serve::decode("blur", &png).unwrap(), img);
+    assert_eq!(serve::decode("blur", b"not an image").unwrap_err().0, StatusCode::BAD_REQUEST);
+}
+
+// Starts the server on a free loopback port and waits until it accepts connections
+async fn start_server() -> String {
+    let port = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+    tokio::spawn(serve::run(port, 1, 4));
+    let address = format!("127.0.0.1:{}", port);
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(&address).await.is_ok() {
+            return format!("http://{}", address);
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("server did not start on {}", address);
+}
+
+#[tokio::test]
+async fn radius_past_the_maximum_is_a_bad_request() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(5, 3, Rgba([10, 20, 30, 255])));
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+    let server = start_server().await;
+    let client = reqwest::Client::new();
+    let post = |radius: i32| client.post(format!("{}/filter?operation=blur&radius={}", server, radius)).body(png.clone()).send();
+
+    let response = post(2_000_000_000).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response.text().await.unwrap().contains(&serve::MAX_RADIUS.to_string()));
+    assert_eq!(post(serve::MAX_RADIUS).await.unwrap().status(), StatusCode::OK);
+}
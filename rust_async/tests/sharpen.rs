@@ -0,0 +1,44 @@
+// The `sharpen` operation: the unsharp mask must match the serial reference whatever the task
+// count, steepen edges and leave differences within the threshold alone.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::sharpen::apply_unsharp_mask_async;
+
+mod common;
+
+// Mid gray on the left, lighter gray on the right
+fn step() -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_fn(32, 16, |x, _| if x < 16 { Rgba([100, 100, 100, 255]) } else { Rgba([150, 150, 150, 255]) }))
+}
+
+#[tokio::test]
+async fn sharpen_matches_the_reference() {
+    let img = common::fixture();
+    for (linear, amount, threshold) in [(false, 1.0, 0), (true, 1.5, 0), (false, 0.8, 6)] {
+        let filter = FilterOptions { linear, amount, sharpen_threshold: threshold, ..FilterOptions::default() };
+        let result = apply_unsharp_mask_async(&img, 3, amount, threshold, 3, filter).await;
+        common::assert_matches_reference("sharpen", &img, &result, 3, filter, &format!("linear {} amount {} threshold {}", linear, amount, threshold));
+    }
+}
+
+#[tokio::test]
+async fn sharpen_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_unsharp_mask_async(&img, 2, 1.0, 0, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn sharpen_steepens_an_edge() {
+    let sharpened = apply_unsharp_mask_async(&step(), 2, 1.0, 0, 4, FilterOptions::default()).await;
+    assert!(sharpened.get_pixel(15, 8)[0] < 100 && sharpened.get_pixel(16, 8)[0] > 150);
+    assert_eq!(sharpened.get_pixel(0, 8), Rgba([100, 100, 100, 255]));
+    assert_eq!(sharpened.get_pixel(31, 8), Rgba([150, 150, 150, 255]));
+}
+
+#[tokio::test]
+async fn threshold_leaves_small_differences_alone() {
+    let img = step();
+    assert!(apply_unsharp_mask_async(&img, 2, 1.0, 50, 4, FilterOptions::default()).await == img);
+    assert!(apply_unsharp_mask_async(&img, 0, 1.0, 0, 4, FilterOptions::default()).await == img);
+}
@@ -0,0 +1,48 @@
+// Sizes of huge images, with the byte limit lowered to what a 32-bit target can address, so the
+// overflow checks are exercised without allocating anything.
+
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::size;
+
+// isize::MAX on a 32-bit target
+const MAX_BYTES_32: u128 = i32::MAX as u128;
+
+#[test]
+fn kuwahara_table_must_fit_the_address_space() {
+    // An 8192x8192 blur fits, but its summed-area table needs 3 GiB
+    assert!(size::check_with_limit("blur", 8192, 8192, FilterOptions::default(), MAX_BYTES_32).is_ok());
+    assert!(size::check_with_limit("kuwahara", 8192, 8192, FilterOptions::default(), MAX_BYTES_32).is_err());
+    assert!(size::check_with_limit("kuwahara", 2048, 2048, FilterOptions::default(), MAX_BYTES_32).is_ok());
+}
+
+#[test]
+fn alpha_widens_the_tables() {
+    // 6000x6000 fits with three channels, but not with alpha as the fourth
+    let filter_alpha = FilterOptions { filter_alpha: true, ..FilterOptions::default() };
+    assert!(size::check_with_limit("kuwahara", 6000, 6000, FilterOptions::default(), MAX_BYTES_32).is_ok());
+    assert!(size::check_with_limit("kuwahara", 6000, 6000, filter_alpha, MAX_BYTES_32).is_err());
+
+    // Pixelate sums through a table as well, and alpha-weighted sums add the summed alpha
+    let alpha_weighted = FilterOptions { alpha_weighted: true, ..FilterOptions::default() };
+    assert!(size::check_with_limit("pixelate", 8192, 8192, FilterOptions::default(), MAX_BYTES_32).is_err());
+    assert!(size::check_with_limit("pixelate", 6500, 6500, FilterOptions::default(), MAX_BYTES_32).is_ok());
+    assert!(size::check_with_limit("pixelate", 6500, 6500, alpha_weighted, MAX_BYTES_32).is_err());
+}
+
+#[test]
+fn largest_sides_do_not_wrap() {
+    for operation in ["blur", "kuwahara"] {
+        let message = size::check(operation, u32::MAX, u32::MAX, FilterOptions::default()).unwrap_err();
+        assert!(message.contains("Image too large"), "{}", message);
+        assert!(size::check_with_limit(operation, u32::MAX, 1, FilterOptions::default(), MAX_BYTES_32).is_err());
+    }
+}
+
+#[test]
+fn integral_len_reports_overflow() {
+    assert_eq!(size::integral_len(2, 3, 3), Some(36));
+    assert_eq!(size::integral_len(2, 3, 4), Some(48));
+    assert_eq!(size::integral_len(usize::MAX, 1, 3), None);
+    assert_eq!(size::integral_len(usize::MAX / 2, usize::MAX / 2, 3), None);
+}
+
@@ -0,0 +1,36 @@
+// The `sobel` operation: the edge map must match the serial reference whatever the task count,
+// stay black on flat regions and light up along an edge.
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::sobel::apply_sobel_async;
+
+mod common;
+
+#[tokio::test]
+async fn sobel_matches_the_reference() {
+    let img = common::fixture();
+    for linear in [false, true] {
+        let filter = FilterOptions { linear, ..FilterOptions::default() };
+        for radius in [0, 2] {
+            let result = apply_sobel_async(&img, radius, 3, filter).await;
+            common::assert_matches_reference("sobel", &img, &result, radius, filter, &format!("radius {} linear {}", radius, linear));
+        }
+    }
+}
+
+#[tokio::test]
+async fn sobel_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 5, 8], |num_tasks| apply_sobel_async(&img, 1, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn sobel_marks_only_the_edge() {
+    let step = DynamicImage::ImageRgba8(ImageBuffer::from_fn(32, 16, |x, _| if x < 16 { Rgba([20, 20, 20, 180]) } else { Rgba([200, 200, 200, 180]) }));
+    let edges = apply_sobel_async(&step, 0, 4, FilterOptions::default()).await;
+    for (x, _, pixel) in edges.pixels() {
+        let expected = if x == 15 || x == 16 { 255 } else { 0 };
+        assert_eq!(pixel.0, [expected, expected, expected, 180], "column {}", x);
+    }
+}
@@ -0,0 +1,53 @@
+// Synthetic inputs: Perlin noise must tile, not depend on the task count, and its spec must
+// parse back from what it prints.
+
+use image::Rgba;
+use rust_filter_async::synthetic::{self, Pattern, SyntheticSpec};
+
+mod common;
+
+async fn perlin(spec: &str, num_tasks: usize) -> image::RgbaImage {
+    synthetic::load(&format!("synthetic:{}", spec), num_tasks).await.expect("Failed to generate image").to_rgba8()
+}
+
+#[tokio::test]
+async fn perlin_is_independent_of_task_count() {
+    common::assert_independent_of_task_count(&[2, 7, 200], |num_tasks| perlin("perlin:160x90:3:5", num_tasks)).await;
+}
+
+#[tokio::test]
+async fn perlin_tiles() {
+    // Side by side, the image continues across the seam as smoothly as anywhere inside it
+    let img = perlin("perlin:128x96:2:3", 4).await;
+    let step = |a: &Rgba<u8>, b: &Rgba<u8>| (a[0] as i32 - b[0] as i32).abs();
+    // Cells are shorter than they are wide here, so each seam is held to steps in its own direction
+    let largest_across = (0..96).flat_map(|y| (1..128).map(move |x| (x, y))).map(|(x, y)| step(img.get_pixel(x - 1, y), img.get_pixel(x, y))).max().unwrap();
+    let largest_down = (1..96).flat_map(|y| (0..128).map(move |x| (x, y))).map(|(x, y)| step(img.get_pixel(x, y - 1), img.get_pixel(x, y))).max().unwrap();
+    for y in 0..96 {
+        assert!(step(img.get_pixel(127, y), img.get_pixel(0, y)) <= largest_across, "row {}", y);
+    }
+    for x in 0..128 {
+        assert!(step(img.get_pixel(x, 95), img.get_pixel(x, 0)) <= largest_down, "column {}", x);
+    }
+}
+
+#[tokio::test]
+async fn perlin_is_gray_and_not_flat() {
+    let img = perlin("perlin:64x64", 2).await;
+    assert!(img.pixels().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2] && pixel[3] == 255));
+    let (lowest, highest) = img.pixels().fold((255, 0), |(lowest, highest), pixel| (pixel[0].min(lowest), pixel[0].max(highest)));
+    assert!(highest - lowest > 64, "{} to {}", lowest, highest);
+}
+
+#[test]
+fn specs_round_trip() {
+    let spec: SyntheticSpec = "perlin:640x480".parse().unwrap();
+    let defaults = Pattern::Perlin { frequency: synthetic::DEFAULT_FREQUENCY, octaves: synthetic::DEFAULT_OCTAVES };
+    assert_eq!(spec, SyntheticSpec { pattern: defaults, width: 640, height: 480 });
+    for text in ["perlin:640x480:8:2", "noise:10x20", "checkerboard:1x1"] {
+        assert_eq!(text.parse::<SyntheticSpec>().unwrap().to_string(), text);
+    }
+    for text in ["perlin:640x480:0", "perlin:640x480:4:0", "perlin:640x480:4:17", "perlin:64x64:1073741824:4", "perlin:64x64:4:4:4", "noise:64x64:4"] {
+        assert!(text.parse::<SyntheticSpec>().is_err(), "{}", text);
+    }
+}
@@ -0,0 +1,72 @@
+// The `transform` operation: every pixel must match the serial inverse map whatever the task
+// count, the identity changes nothing, a quarter turn moves pixels where a rotation would and
+// what no source pixel covers comes out transparent.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::transform::apply_transform_async;
+
+mod common;
+
+#[tokio::test]
+async fn transform_matches_the_reference() {
+    let img = common::fixture();
+    let turned = FilterOptions { angle: 30.0, ..FilterOptions::default() };
+    let zoomed = FilterOptions { angle: -75.0, scale: 1.7, translate: (6.5, -3.0), linear: true, ..FilterOptions::default() };
+    let shrunk = FilterOptions { scale: 0.4, translate: (-20.0, 11.0), ..FilterOptions::default() };
+    for (num_tasks, filter) in [(1, turned), (4, zoomed), (7, shrunk)] {
+        let result = apply_transform_async(&img, num_tasks, filter).await;
+        common::assert_matches_reference("transform", &img, &result, 0, filter, &format!("tasks {}", num_tasks));
+    }
+}
+
+#[tokio::test]
+async fn transform_is_independent_of_task_count() {
+    let img = common::fixture();
+    let filter = FilterOptions { angle: 40.0, scale: 1.3, ..FilterOptions::default() };
+    common::assert_independent_of_task_count(&[2, 3, 8, 100], |num_tasks| apply_transform_async(&img, num_tasks, filter)).await;
+}
+
+#[tokio::test]
+async fn identity_changes_nothing() {
+    let img = common::fixture();
+    assert!(apply_transform_async(&img, 4, FilterOptions::default()).await == img);
+}
+
+#[tokio::test]
+async fn quarter_turn_rotates_pixels_counterclockwise() {
+    let img = ImageBuffer::from_fn(6, 6, |x, y| Rgba([(x * 40) as u8, (y * 40) as u8, 0, 255]));
+    let filter = FilterOptions { angle: 90.0, ..FilterOptions::default() };
+    let result = apply_transform_async(&DynamicImage::ImageRgba8(img.clone()), 3, filter).await.to_rgba8();
+    // Turning counterclockwise on screen carries the right edge to the top
+    for y in 0..6 {
+        for x in 0..6 {
+            assert_eq!(result.get_pixel(x, y), img.get_pixel(5 - y, x), "({}, {})", x, y);
+        }
+    }
+}
+
+#[tokio::test]
+async fn uncovered_areas_are_transparent() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(20, 10, Rgba([200, 100, 40, 255])));
+    let shifted = FilterOptions { translate: (8.0, 0.0), ..FilterOptions::default() };
+    let result = apply_transform_async(&img, 2, shifted).await.to_rgba8();
+    assert_eq!(result.get_pixel(3, 5), &Rgba([0, 0, 0, 0]));
+    assert_eq!(result.get_pixel(12, 5), &Rgba([200, 100, 40, 255]));
+    // Colors are weighted by alpha, so the faded border keeps the color rather than darkening
+    let shrunk = FilterOptions { scale: 0.5, ..FilterOptions::default() };
+    let result = apply_transform_async(&img, 2, shrunk).await.to_rgba8();
+    assert_eq!(result.get_pixel(0, 0)[3], 0);
+    assert_eq!(result.get_pixel(10, 5), &Rgba([200, 100, 40, 255]));
+    assert!(result.pixels().filter(|p| p[3] > 0).all(|p| p.0[..3] == [200, 100, 40]));
+}
+
+#[tokio::test]
+async fn scaling_magnifies_about_the_center() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_fn(8, 8, |x, y| Rgba([(x * 30) as u8, (y * 30) as u8, 60, 255])));
+    let filter = FilterOptions { scale: 2.0, ..FilterOptions::default() };
+    let result = apply_transform_async(&img, 3, filter).await.to_rgba8();
+    // Distances from the center halve: output pixels 4 and 0 sample the source at 3.75 and 1.75
+    assert_eq!(result.get_pixel(4, 4), &Rgba([113, 113, 60, 255]));
+    assert_eq!(result.get_pixel(0, 4), &Rgba([53, 113, 60, 255]));
+}
@@ -0,0 +1,73 @@
+// The `varblur` operation: a black mask must keep the image, a white one must match the plain blur
+// at the full radius, values in between must mix the nearest levels, and the result must not
+// depend on the task count.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::blur::apply_gaussian_blur_async;
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::varblur::{self, apply_variable_blur_async};
+
+mod common;
+
+fn flat_mask(img: &DynamicImage, value: u8) -> DynamicImage {
+    DynamicImage::ImageLuma8(ImageBuffer::from_pixel(img.width(), img.height(), image::Luma([value])))
+}
+
+// Black on the left, rising to white on the right
+fn ramp(img: &DynamicImage) -> DynamicImage {
+    let width = img.width();
+    DynamicImage::ImageLuma8(ImageBuffer::from_fn(width, img.height(), |x, _| image::Luma([(x * 255 / (width - 1)) as u8])))
+}
+
+#[tokio::test]
+async fn black_keeps_the_image_and_white_blurs_it_fully() {
+    let img = common::fixture();
+    let filter = FilterOptions::default();
+    let sharp = apply_variable_blur_async(&img, &flat_mask(&img, 0), 6, 3, 4, filter).await.unwrap();
+    assert!(sharp == img);
+    let blurred = apply_variable_blur_async(&img, &flat_mask(&img, 255), 6, 3, 4, filter).await.unwrap();
+    assert!(blurred == apply_gaussian_blur_async(&img, 6, 2, filter).await);
+}
+
+#[tokio::test]
+async fn varblur_is_independent_of_task_count() {
+    let img = &common::fixture();
+    let mask = &ramp(img);
+    common::assert_independent_of_task_count(&[2, 3, 8, 64], |num_tasks| async move {
+        apply_variable_blur_async(img, mask, 5, 4, num_tasks, FilterOptions::default()).await.unwrap()
+    })
+    .await;
+}
+
+#[test]
+fn mask_values_between_levels_mix_the_nearest_two() {
+    let levels = [0, 100, 200].map(|value| ImageBuffer::from_pixel(1, 1, Rgba([value, value, value, 255])));
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 0), [0, 0, 0, 255]);
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 64), [50, 50, 50, 255]);
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 191), [150, 150, 150, 255]);
+    assert_eq!(varblur::mix_pixel(&levels, 0, 0, 255), [200, 200, 200, 255]);
+}
+
+#[test]
+fn levels_are_spread_evenly_up_to_the_radius() {
+    assert_eq!(varblur::level_radii(8, 4), vec![0, 2, 4, 6, 8]);
+    assert_eq!(varblur::level_radii(3, 1), vec![0, 3]);
+}
+
+#[tokio::test]
+async fn ramp_leaves_the_dark_side_sharp() {
+    let img = common::fixture();
+    let result = apply_variable_blur_async(&img, &ramp(&img), 8, 4, 4, FilterOptions::default()).await.unwrap().to_rgba8();
+    let blurred = apply_gaussian_blur_async(&img, 8, 4, FilterOptions::default()).await.to_rgba8();
+    let (img, height) = (img.to_rgba8(), img.height());
+    assert!((0..height).all(|y| result.get_pixel(0, y) == img.get_pixel(0, y)));
+    let last = img.width() - 1;
+    assert!((0..height).all(|y| result.get_pixel(last, y) == blurred.get_pixel(last, y)));
+}
+
+#[tokio::test]
+async fn mask_of_another_size_is_rejected() {
+    let img = common::fixture();
+    let mask = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(img.width() + 1, img.height(), image::Luma([255])));
+    assert!(apply_variable_blur_async(&img, &mask, 4, 2, 2, FilterOptions::default()).await.is_err());
+}
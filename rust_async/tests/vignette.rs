@@ -0,0 +1,44 @@
+// The `vignette` operation: every pixel must match the serial formula whatever the task count,
+// the center stays as it is, the corners lose the given strength and alpha is kept.
+
+use image::{DynamicImage, ImageBuffer, Rgba};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::vignette::apply_vignette_async;
+
+mod common;
+
+#[tokio::test]
+async fn vignette_matches_the_reference() {
+    let img = common::fixture();
+    let linear = FilterOptions { linear: true, ..FilterOptions::default() };
+    let steep = FilterOptions { falloff: 5.0, ..FilterOptions::default() };
+    for (percent, num_tasks, filter) in [(50, 1, FilterOptions::default()), (80, 4, linear), (100, 7, steep)] {
+        let result = apply_vignette_async(&img, percent as f64 / 100.0, num_tasks, filter).await;
+        common::assert_matches_reference("vignette", &img, &result, percent, filter, &format!("{}% tasks {}", percent, num_tasks));
+    }
+}
+
+#[tokio::test]
+async fn vignette_is_independent_of_task_count() {
+    let img = common::fixture();
+    common::assert_independent_of_task_count(&[2, 3, 8, 100], |num_tasks| apply_vignette_async(&img, 0.6, num_tasks, FilterOptions::default())).await;
+}
+
+#[tokio::test]
+async fn center_is_kept_and_corners_darken_by_the_strength() {
+    let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(21, 15, Rgba([200, 100, 40, 170])));
+    let result = apply_vignette_async(&img, 0.5, 3, FilterOptions::default()).await.to_rgba8();
+    assert_eq!(result.get_pixel(10, 7), &Rgba([200, 100, 40, 170]));
+    // The corner pixel's center sits just inside the corner itself
+    let corner = result.get_pixel(0, 0);
+    assert!((100..=112).contains(&corner[0]), "{:?}", corner);
+    assert_eq!(corner[3], 170);
+    // Darker the further out, along a row through the center
+    assert!((0..10).all(|x| result.get_pixel(x, 7)[0] <= result.get_pixel(x + 1, 7)[0]));
+}
+
+#[tokio::test]
+async fn zero_strength_changes_nothing() {
+    let img = common::fixture();
+    assert!(apply_vignette_async(&img, 0.0, 4, FilterOptions::default()).await == img);
+}
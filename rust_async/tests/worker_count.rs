@@ -0,0 +1,78 @@
+// The task count only decides which task filters which rows, never the pixels. For random small
+// images, including ones with fewer rows than tasks, every count must give exactly what a naive
+// single-threaded blur does.
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use proptest::prelude::*;
+use rust_filter_async::blur::{self, apply_gaussian_blur_async};
+use rust_filter_async::cli::FilterOptions;
+use rust_filter_async::kuwahara::apply_kuwahara_filter_async;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+
+const TASKS: [usize; 5] = [1, 2, 3, 7, 16];
+
+// One runtime for every case, as the binary uses one for every task
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| Runtime::new().expect("Failed to start runtime"))
+}
+
+// Same arithmetic as the filter: a horizontal then a vertical pass, edges clamped, weights summed
+// in kernel order in f64, and each pass rounded to u8
+fn naive_blur(img: &RgbaImage, radius: i32) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    // A radius past the longest side is capped there, as the filter caps it
+    let radius = radius.min(width.max(height) as i32);
+    let kernel = blur::generate_gaussian_kernel(radius as usize);
+    let pass = |src: &RgbaImage, dx: i32, dy: i32| {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let mut sums = [0.0; 4];
+            for k in -radius..=radius {
+                let sx = (x as i32 + k * dx).clamp(0, width as i32 - 1) as u32;
+                let sy = (y as i32 + k * dy).clamp(0, height as i32 - 1) as u32;
+                let weight = kernel[(k + radius) as usize];
+                for (sum, &value) in sums.iter_mut().zip(&src.get_pixel(sx, sy).0) {
+                    *sum += value as f64 * weight;
+                }
+            }
+            Rgba(sums.map(|sum| sum.round() as u8))
+        })
+    };
+    pass(&pass(img, 1, 0), 0, 1)
+}
+
+fn image() -> impl Strategy<Value = RgbaImage> {
+    (1u32..40, 1u32..40).prop_flat_map(|(width, height)| {
+        proptest::collection::vec(any::<u8>(), (width * height * 4) as usize)
+            .prop_map(move |data| RgbaImage::from_raw(width, height, data).unwrap())
+    })
+}
+
+proptest! {
+    #[test]
+    fn blur_is_independent_of_task_count(img in image(), radius in 1i32..10) {
+        let expected = naive_blur(&img, radius);
+        let input = DynamicImage::ImageRgba8(img);
+        for tasks in TASKS {
+            let result = runtime().block_on(apply_gaussian_blur_async(&input, radius as u32, tasks, FilterOptions::default()));
+            prop_assert!(result.to_rgba8() == expected, "radius {} with {} tasks differs from the naive blur", radius, tasks);
+        }
+    }
+}
+
+// Single rows and columns, where most task counts exceed the rows of one of the two passes
+#[test]
+fn thin_images_filter_with_more_tasks_than_rows() {
+    for (width, height) in [(1, 1), (1, 9), (9, 1), (2, 17), (17, 2)] {
+        let img = RgbaImage::from_fn(width, height, |x, y| Rgba([(x * 29) as u8, (y * 31) as u8, ((x + y) * 7) as u8, 255]));
+        let input = DynamicImage::ImageRgba8(img.clone());
+        let kuwahara_reference = runtime().block_on(apply_kuwahara_filter_async(&input, 2, 1, FilterOptions::default()));
+        for tasks in [0, 1, 2, 3, 7, 16] {
+            let blurred = runtime().block_on(apply_gaussian_blur_async(&input, 3, tasks, FilterOptions::default()));
+            assert!(blurred.to_rgba8() == naive_blur(&img, 3), "blur of {}x{} with {} tasks", width, height, tasks);
+            let filtered = runtime().block_on(apply_kuwahara_filter_async(&input, 2, tasks, FilterOptions::default()));
+            assert!(filtered == kuwahara_reference, "Kuwahara of {}x{} with {} tasks", width, height, tasks);
+        }
+    }
+}